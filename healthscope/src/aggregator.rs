@@ -0,0 +1,200 @@
+use crate::alert::{self, Alertmanager};
+use crate::cache::StatusCache;
+use crate::discovery::discover_components;
+use crate::finalizer;
+use crate::logging;
+use crate::state::{ScopeState, ScopeStates};
+use crate::{aggregate_component_health, scope_health};
+use failure::Error;
+use kube::api::{ListParams, RawApi};
+use kube::client::APIClient;
+use log::{error, info};
+use rudr::metrics::TimedRequest;
+use rudr::schematic::scopes::health::{
+    ComponentInfo, HealthScopeObject, HEALTH_SCOPE_CRD, HEALTH_SCOPE_GROUP, HEALTH_SCOPE_VERSION,
+};
+use std::collections::HashSet;
+
+/// Aggregator runs the passes of the aggregate loop and publishes their results into the status cache.
+pub struct Aggregator {
+    client: APIClient,
+    namespace: String,
+    states: ScopeStates,
+    alertmanager: Option<Alertmanager>,
+    cache: StatusCache,
+    default_probe_interval: i64,
+}
+
+impl Aggregator {
+    pub fn new(
+        client: APIClient,
+        namespace: String,
+        alertmanager: Option<Alertmanager>,
+        cache: StatusCache,
+        default_probe_interval: i64,
+    ) -> Self {
+        Aggregator {
+            client,
+            namespace,
+            states: ScopeStates::new(),
+            alertmanager,
+            cache,
+            default_probe_interval,
+        }
+    }
+
+    /// aggregate_all probes every health scope in the namespace, `force` ignores the probe interval.
+    /// Deleted scopes are cleaned up instead of probed.
+    ///
+    /// Replicas that are not the leader only publish the status the leader wrote into the cache.
+    /// `is_leader` is asked again before the writes of every scope, so a replica that lost its lease
+    /// while probing the scopes before stops patching them.
+    pub fn aggregate_all(&mut self, force: bool, is_leader: &dyn Fn() -> bool) {
+        let leader = is_leader();
+        let healthscope_resource = RawApi::customResource(HEALTH_SCOPE_CRD)
+            .version(HEALTH_SCOPE_VERSION)
+            .group(HEALTH_SCOPE_GROUP)
+            .within(self.namespace.as_str());
+        let req = match healthscope_resource.list(&ListParams::default()) {
+            Ok(req) => req,
+            Err(e) => {
+                error!("build health scope list request err {:?}", e);
+                return;
+            }
+        };
+        // kube's ObjectMeta has no deletionTimestamp, so the list is read untyped first.
+        let items = match self.client.timed_request::<serde_json::Value>(req) {
+            Ok(mut list) => match list["items"].take() {
+                serde_json::Value::Array(items) => items,
+                _ => vec![],
+            },
+            Err(e) => {
+                error!("get health scope list err {:?}", e);
+                return;
+            }
+        };
+        // Only the leader patches status, so only the leader needs to know the members of each scope.
+        let mut members = if leader {
+            match discover_components(&self.client, self.namespace.as_str()) {
+                Ok(members) => Some(members),
+                Err(e) => {
+                    error!("discover components of health scopes err {:?}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let mut seen = HashSet::new();
+        for item in items {
+            let deleting = item["metadata"]["deletionTimestamp"].is_string();
+            let scope: HealthScopeObject = match serde_json::from_value(item) {
+                Ok(scope) => scope,
+                Err(e) => {
+                    error!("decode health scope err {:?}", e);
+                    continue;
+                }
+            };
+            let name = scope.metadata.name.clone();
+            seen.insert(name.clone());
+            // A scope no configuration references has no components.
+            let discovered = members
+                .as_mut()
+                .map(|m| m.remove(&name).unwrap_or_default());
+            logging::with_field("scope", name, || {
+                if let Err(res) =
+                    self.process_scope(scope, force, deleting, leader, is_leader, discovered)
+                {
+                    // Log the error and continue.
+                    error!("Error processing event: {:?}", res)
+                };
+            });
+        }
+        // Scopes deleted without our finalizer (e.g. force removed) are forgotten on the next list.
+        self.states.retain(|name, _| {
+            if !seen.contains(name) {
+                info!("health scope {} is gone, dropping its state", name);
+            }
+            seen.contains(name)
+        });
+        self.cache.retain(|name| seen.contains(name));
+    }
+
+    fn process_scope(
+        &mut self,
+        scope: HealthScopeObject,
+        force: bool,
+        deleting: bool,
+        leader: bool,
+        is_leader: &dyn Fn() -> bool,
+        discovered: Option<Vec<ComponentInfo>>,
+    ) -> Result<(), Error> {
+        let name = scope.metadata.name.clone();
+        let suspended = scope.spec.suspended == Some(true);
+        // The lease is only renewed for scopes of a pass that started out as leader.
+        let leader = leader && is_leader();
+        if deleting {
+            self.cache.remove(&name);
+            if leader && finalizer::has_finalizer(&scope) {
+                if let (Some(alertmanager), Some(state)) =
+                    (self.alertmanager.as_ref(), self.states.get_mut(&name))
+                {
+                    alert::update_alert(alertmanager, &mut state.firing_alert, None)?;
+                }
+                finalizer::cleanup(
+                    &self.client,
+                    scope,
+                    self.namespace.as_str(),
+                    &mut self.states,
+                )?;
+            }
+            return Ok(());
+        }
+        if !leader {
+            self.cache.publish(name, scope.status, suspended);
+            return Ok(());
+        }
+        let scope = finalizer::ensure_finalizer(&self.client, scope, self.namespace.as_str())?;
+        // A scope recreated under the same name must not inherit the state of the old one.
+        if self.states.get(&name).map(|s| s.uid != scope.metadata.uid) != Some(false) {
+            self.states.insert(
+                name.clone(),
+                ScopeState {
+                    uid: scope.metadata.uid.clone(),
+                    ..Default::default()
+                },
+            );
+        }
+        let state = self.states.get_mut(&name).unwrap();
+        let previous = state.last_status.clone().or_else(|| scope.status.clone());
+        let status = match aggregate_component_health(
+            &self.client,
+            scope,
+            self.namespace.clone(),
+            force,
+            discovered,
+            self.default_probe_interval,
+            state,
+            is_leader,
+        )? {
+            Some(status) => status,
+            None => {
+                self.cache.publish(name, previous, suspended);
+                return Ok(());
+            }
+        };
+        self.cache
+            .publish(name.clone(), Some(status.clone()), suspended);
+        if let (Some(alertmanager), Some(state)) =
+            (self.alertmanager.as_ref(), self.states.get_mut(&name))
+        {
+            let unhealthy = if scope_health(Some(status.clone())) == "unhealthy" {
+                Some(alert::alert_labels(&name, &self.namespace, &status))
+            } else {
+                None
+            };
+            alert::update_alert(alertmanager, &mut state.firing_alert, unhealthy)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::Utc;
+use failure::{format_err, Error};
+use log::info;
+use rudr::schematic::scopes::health::HealthStatus;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+pub const ALERT_NAME: &str = "HealthScopeUnhealthy";
+
+pub type AlertLabels = BTreeMap<String, String>;
+
+/// Alertmanager pushes scope health alerts to the Alertmanager v2 API.
+pub struct Alertmanager {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Alertmanager {
+    pub fn new(url: &str) -> Self {
+        Alertmanager {
+            client: reqwest::Client::new(),
+            url: format!("{}/api/v2/alerts", url.trim_end_matches('/')),
+        }
+    }
+
+    /// fire (re)sends a firing alert, Alertmanager deduplicates alerts with the same labels.
+    pub fn fire(&self, labels: &AlertLabels, summary: String) -> Result<(), Error> {
+        self.post(json!([{
+            "labels": labels,
+            "annotations": {"summary": summary},
+            "startsAt": Utc::now().to_rfc3339(),
+        }]))
+    }
+
+    /// resolve ends the alert with the given labels.
+    pub fn resolve(&self, labels: &AlertLabels) -> Result<(), Error> {
+        let now = Utc::now().to_rfc3339();
+        self.post(json!([{
+            "labels": labels,
+            "startsAt": now,
+            "endsAt": now,
+        }]))
+    }
+
+    fn post(&self, alerts: Value) -> Result<(), Error> {
+        let resp = self.client.post(self.url.as_str()).json(&alerts).send()?;
+        if !resp.status().is_success() {
+            return Err(format_err!(
+                "push alert to {} failed with {}",
+                self.url,
+                resp.status()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// alert_labels identifies the alert of a scope, the failing components are part of the identity
+/// so that a different set of failing components shows up as a new alert.
+pub fn alert_labels(scope: &str, namespace: &str, status: &HealthStatus) -> AlertLabels {
+    let failing: Vec<String> = status
+        .components
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| c.status.as_deref() == Some("unhealthy"))
+        .map(|c| c.name)
+        .collect();
+    let mut labels = AlertLabels::new();
+    labels.insert("alertname".to_string(), ALERT_NAME.to_string());
+    labels.insert("scope".to_string(), scope.to_string());
+    labels.insert("namespace".to_string(), namespace.to_string());
+    labels.insert("components".to_string(), failing.join(","));
+    labels
+}
+
+/// update_alert fires the alert of an unhealthy scope and resolves it once the scope recovers
+/// or its failing components change. `firing` holds the labels of the alert currently firing.
+pub fn update_alert(
+    alertmanager: &Alertmanager,
+    firing: &mut Option<AlertLabels>,
+    unhealthy: Option<AlertLabels>,
+) -> Result<(), Error> {
+    if let Some(old) = firing.as_ref() {
+        if Some(old) != unhealthy.as_ref() {
+            info!("resolving alert {:?}", old);
+            alertmanager.resolve(old)?;
+            *firing = None;
+        }
+    }
+    if let Some(labels) = unhealthy {
+        let summary = format!(
+            "health scope {} is unhealthy, failing components: {}",
+            labels["scope"], labels["components"]
+        );
+        alertmanager.fire(&labels, summary)?;
+        *firing = Some(labels);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::alert::{alert_labels, ALERT_NAME};
+    use rudr::schematic::scopes::health::{ComponentInfo, HealthStatus};
+
+    #[test]
+    fn test_alert_labels() {
+        let component = |name: &str, status: &str| ComponentInfo {
+            name: name.to_string(),
+            instance_name: name.to_string(),
+            status: Some(status.to_string()),
+            ..Default::default()
+        };
+        let status = HealthStatus {
+            components: Some(vec![
+                component("a", "unhealthy"),
+                component("b", "healthy"),
+                component("c", "unhealthy"),
+            ]),
+            ..Default::default()
+        };
+        let labels = alert_labels("scope", "default", &status);
+        assert_eq!(labels["alertname"], ALERT_NAME);
+        assert_eq!(labels["scope"], "scope");
+        assert_eq!(labels["namespace"], "default");
+        assert_eq!(labels["components"], "a,c");
+    }
+}
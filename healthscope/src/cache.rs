@@ -0,0 +1,227 @@
+use crate::scope_health;
+use chrono::{DateTime, Duration, Utc};
+use rudr::schematic::scopes::health::{HealthStatus, Uptime};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// CachedStatus is the health of a scope as last seen by the aggregate loop.
+#[derive(Clone, Debug)]
+pub struct CachedStatus {
+    pub health: &'static str,
+    pub last_aggregate_timestamp: Option<DateTime<Utc>>,
+    pub cached_at: DateTime<Utc>,
+    pub suspended: bool,
+    pub uptime: Uptime,
+}
+
+impl CachedStatus {
+    /// age is how many seconds ago the components of the scope were probed.
+    pub fn age(&self, now: DateTime<Utc>) -> i64 {
+        let since = self.last_aggregate_timestamp.unwrap_or(self.cached_at);
+        now.signed_duration_since(since).num_seconds().max(0)
+    }
+
+    /// etag changes whenever the scope is probed again or its health or suspension changes.
+    pub fn etag(&self) -> Option<String> {
+        let timestamp = self.last_aggregate_timestamp?;
+        let suspended = if self.suspended { "-suspended" } else { "" };
+        Some(format!(
+            "\"{}-{}{}\"",
+            timestamp.timestamp(),
+            self.health,
+            suspended
+        ))
+    }
+
+    /// last_modified is the HTTP date of the last probe of the scope.
+    pub fn last_modified(&self) -> Option<String> {
+        self.last_aggregate_timestamp
+            .map(|t| t.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+    }
+
+    /// not_modified evaluates the conditional request headers, `If-None-Match` takes precedence
+    /// over `If-Modified-Since` as in RFC 7232.
+    pub fn not_modified(
+        &self,
+        if_none_match: Option<&str>,
+        if_modified_since: Option<&str>,
+    ) -> bool {
+        let (etag, timestamp) = match (self.etag(), self.last_aggregate_timestamp) {
+            (Some(etag), Some(timestamp)) => (etag, timestamp),
+            _ => return false,
+        };
+        if let Some(tags) = if_none_match {
+            return tags
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag);
+        }
+        let since = match if_modified_since.and_then(|s| DateTime::parse_from_rfc2822(s).ok()) {
+            Some(since) => since,
+            None => return false,
+        };
+        timestamp.timestamp() <= since.timestamp()
+    }
+}
+
+/// StatusCache is filled by the aggregate loop and read by the health scope endpoint,
+/// so serving a request never needs a round trip to the API server.
+#[derive(Clone, Default)]
+pub struct StatusCache {
+    scopes: Arc<RwLock<HashMap<String, CachedStatus>>>,
+}
+
+impl StatusCache {
+    pub fn publish(&self, scope: String, status: Option<HealthStatus>, suspended: bool) {
+        let last_aggregate_timestamp = status
+            .as_ref()
+            .and_then(|s| s.last_aggregate_timestamp.as_ref())
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc));
+        let uptime = status
+            .as_ref()
+            .and_then(|s| s.uptime.clone())
+            .unwrap_or_default();
+        let cached = CachedStatus {
+            health: scope_health(status),
+            last_aggregate_timestamp,
+            cached_at: Utc::now(),
+            suspended,
+            uptime,
+        };
+        self.scopes.write().unwrap().insert(scope, cached);
+    }
+
+    pub fn get(&self, scope: &str) -> Option<CachedStatus> {
+        self.scopes.read().unwrap().get(scope).cloned()
+    }
+
+    /// fresh returns the status of a scope if it was cached less than `ttl` seconds ago,
+    /// a `ttl` of 0 never finds one.
+    pub fn fresh(&self, scope: &str, ttl: i64) -> Option<CachedStatus> {
+        self.get(scope).filter(|cached| {
+            Utc::now().signed_duration_since(cached.cached_at) < Duration::seconds(ttl)
+        })
+    }
+
+    pub fn remove(&self, scope: &str) {
+        self.scopes.write().unwrap().remove(scope);
+    }
+
+    /// snapshot returns every cached scope ordered by name.
+    pub fn snapshot(&self) -> Vec<(String, CachedStatus)> {
+        let mut scopes: Vec<_> = self
+            .scopes
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(scope, cached)| (scope.clone(), cached.clone()))
+            .collect();
+        scopes.sort_by(|a, b| a.0.cmp(&b.0));
+        scopes
+    }
+
+    /// retain drops every scope `keep` returns false for.
+    pub fn retain(&self, keep: impl Fn(&str) -> bool) {
+        self.scopes.write().unwrap().retain(|scope, _| keep(scope));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cache::{CachedStatus, StatusCache};
+    use chrono::{Duration, Utc};
+    use rudr::schematic::scopes::health::{ComponentInfo, HealthStatus};
+
+    #[test]
+    fn test_status_cache() {
+        let cache = StatusCache::default();
+        assert!(cache.get("scope").is_none());
+        let aggregated = Utc::now() - Duration::seconds(20);
+        cache.publish(
+            "scope".to_string(),
+            Some(HealthStatus {
+                components: Some(vec![ComponentInfo {
+                    name: "c".to_string(),
+                    instance_name: "i".to_string(),
+                    status: Some("unhealthy".to_string()),
+                    ..Default::default()
+                }]),
+                last_aggregate_timestamp: Some(aggregated.to_rfc3339()),
+                ..Default::default()
+            }),
+            false,
+        );
+        let cached = cache.get("scope").unwrap();
+        assert_eq!(cached.health, "unhealthy");
+        assert_eq!(cached.age(aggregated + Duration::seconds(20)), 20);
+        cache.publish("other".to_string(), None, true);
+        assert_eq!(cache.get("other").unwrap().health, "healthy");
+        assert!(cache.get("other").unwrap().suspended);
+        let names: Vec<_> = cache.snapshot().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["other", "scope"]);
+        cache.retain(|scope| scope == "other");
+        assert!(cache.get("scope").is_none());
+        cache.remove("other");
+        assert!(cache.get("other").is_none());
+    }
+
+    #[test]
+    fn test_fresh() {
+        let cache = StatusCache::default();
+        assert!(cache.fresh("scope", 5).is_none());
+        cache.publish("scope".to_string(), None, false);
+        assert!(cache.fresh("scope", 5).is_some());
+        // A ttl of 0 disables the cache.
+        assert!(cache.fresh("scope", 0).is_none());
+        let stale = CachedStatus {
+            cached_at: Utc::now() - Duration::seconds(10),
+            ..cache.get("scope").unwrap()
+        };
+        cache
+            .scopes
+            .write()
+            .unwrap()
+            .insert("scope".to_string(), stale);
+        assert!(cache.fresh("scope", 5).is_none());
+        assert!(cache.fresh("scope", 60).is_some());
+        // Stale statuses are kept for the conditional requests of the next live read.
+        assert!(cache.get("scope").is_some());
+    }
+
+    #[test]
+    fn test_not_modified() {
+        let aggregated = Utc::now();
+        let cached = CachedStatus {
+            health: "healthy",
+            last_aggregate_timestamp: Some(aggregated),
+            cached_at: aggregated,
+            suspended: false,
+            uptime: Default::default(),
+        };
+        let etag = cached.etag().unwrap();
+        let last_modified = cached.last_modified().unwrap();
+        assert!(cached.not_modified(Some(etag.as_str()), None));
+        assert!(cached.not_modified(Some(&format!("\"x\", W/{}", etag)), None));
+        assert!(!cached.not_modified(Some("\"x\""), Some(last_modified.as_str())));
+        assert!(cached.not_modified(None, Some(last_modified.as_str())));
+        let before = (aggregated - Duration::seconds(10))
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        assert!(!cached.not_modified(None, Some(before.as_str())));
+        assert!(!cached.not_modified(None, Some("garbage")));
+        assert!(!cached.not_modified(None, None));
+
+        let suspended = CachedStatus {
+            suspended: true,
+            ..cached.clone()
+        };
+        assert_ne!(suspended.etag(), cached.etag());
+        let never_probed = CachedStatus {
+            last_aggregate_timestamp: None,
+            ..cached
+        };
+        assert!(never_probed.etag().is_none());
+        assert!(!never_probed.not_modified(Some("*"), None));
+    }
+}
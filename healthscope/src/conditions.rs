@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use rudr::schematic::scopes::health::{ComponentInfo, Condition};
+
+/// True while no component of the scope is unhealthy.
+pub const READY: &str = "Ready";
+/// True while a component of the scope is degraded but none is unhealthy.
+pub const DEGRADED: &str = "Degraded";
+
+/// scope_conditions computes the conditions of a scope from the health of its components.
+/// A condition keeps the lastTransitionTime of `previous` as long as its status does not change.
+pub fn scope_conditions(
+    previous: Option<Vec<Condition>>,
+    health: &str,
+    components: &[ComponentInfo],
+    now: DateTime<Utc>,
+) -> Vec<Condition> {
+    let with_status = |status: &str| -> Vec<String> {
+        components
+            .iter()
+            .filter(|c| c.status.as_deref() == Some(status))
+            .map(|c| c.name.clone())
+            .collect()
+    };
+    let (ready, ready_reason, ready_message) = match health {
+        "unhealthy" => (
+            "False",
+            "Unhealthy",
+            format!(
+                "unhealthy components: {}",
+                with_status("unhealthy").join(",")
+            ),
+        ),
+        "degraded" => ("True", "Degraded", "no component is unhealthy".to_string()),
+        _ => ("True", "Healthy", "all components are healthy".to_string()),
+    };
+    let (degraded, degraded_reason, degraded_message) = if health == "degraded" {
+        (
+            "True",
+            "Degraded",
+            format!("degraded components: {}", with_status("degraded").join(",")),
+        )
+    } else {
+        (
+            "False",
+            "NotDegraded",
+            "no component is degraded".to_string(),
+        )
+    };
+    let previous = previous.unwrap_or_default();
+    let condition = |type_: &str, status: &str, reason: &str, message: String| {
+        let last_transition_time = previous
+            .iter()
+            .find(|c| c.type_ == type_ && c.status == status)
+            .and_then(|c| c.last_transition_time.clone())
+            .unwrap_or_else(|| now.to_rfc3339());
+        Condition {
+            type_: type_.to_string(),
+            status: status.to_string(),
+            reason: Some(reason.to_string()),
+            message: Some(message),
+            last_transition_time: Some(last_transition_time),
+        }
+    };
+    vec![
+        condition(READY, ready, ready_reason, ready_message),
+        condition(DEGRADED, degraded, degraded_reason, degraded_message),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::conditions::{scope_conditions, DEGRADED, READY};
+    use chrono::{Duration, Utc};
+    use rudr::schematic::scopes::health::ComponentInfo;
+
+    #[test]
+    fn test_scope_conditions() {
+        let component = |name: &str, status: &str| ComponentInfo {
+            name: name.to_string(),
+            status: Some(status.to_string()),
+            ..Default::default()
+        };
+        let before = Utc::now() - Duration::minutes(5);
+        let conditions = scope_conditions(None, "healthy", &[component("a", "healthy")], before);
+        assert_eq!(conditions[0].type_, READY);
+        assert_eq!(conditions[0].status, "True");
+        assert_eq!(conditions[1].type_, DEGRADED);
+        assert_eq!(conditions[1].status, "False");
+
+        let now = Utc::now();
+        let components = [component("a", "unhealthy"), component("b", "degraded")];
+        let updated = scope_conditions(Some(conditions.clone()), "unhealthy", &components, now);
+        assert_eq!(updated[0].status, "False");
+        assert_eq!(updated[0].reason.as_deref(), Some("Unhealthy"));
+        assert_eq!(
+            updated[0].message.as_deref(),
+            Some("unhealthy components: a")
+        );
+        assert_eq!(updated[0].last_transition_time, Some(now.to_rfc3339()));
+        // Degraded stays False, so it keeps its transition time.
+        assert_eq!(
+            updated[1].last_transition_time,
+            conditions[1].last_transition_time
+        );
+
+        let degraded = scope_conditions(Some(updated), "degraded", &components[1..], now);
+        assert_eq!(degraded[0].status, "True");
+        assert_eq!(degraded[0].reason.as_deref(), Some("Degraded"));
+        assert_eq!(degraded[1].status, "True");
+        assert_eq!(
+            degraded[1].message.as_deref(),
+            Some("degraded components: b")
+        );
+    }
+}
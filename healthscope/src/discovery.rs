@@ -0,0 +1,107 @@
+use failure::Error;
+use kube::api::{ListParams, ObjectList, RawApi};
+use kube::client::APIClient;
+use rudr::instigator::{OpResource, CONFIG_CRD, CONFIG_GROUP, CONFIG_VERSION};
+use rudr::metrics::TimedRequest;
+use rudr::schematic::scopes::health::ComponentInfo;
+use std::collections::HashMap;
+
+/// ScopeMembers maps a scope name to the component instances whose configuration references it.
+pub type ScopeMembers = HashMap<String, Vec<ComponentInfo>>;
+
+/// discover_components lists the ApplicationConfigurations in the namespace and collects the
+/// components of every scope from their `applicationScopes`.
+pub fn discover_components(client: &APIClient, namespace: &str) -> Result<ScopeMembers, Error> {
+    let req = RawApi::customResource(CONFIG_CRD)
+        .group(CONFIG_GROUP)
+        .version(CONFIG_VERSION)
+        .within(namespace)
+        .list(&ListParams::default())?;
+    let configs = client.timed_request::<ObjectList<OpResource>>(req)?;
+    Ok(scope_members(configs.items))
+}
+
+fn scope_members(configs: Vec<OpResource>) -> ScopeMembers {
+    let mut members = ScopeMembers::new();
+    for config in configs {
+        for component in config.spec.components.unwrap_or_default() {
+            for scope in component.application_scopes.clone().unwrap_or_default() {
+                members.entry(scope).or_default().push(ComponentInfo {
+                    name: component.component_name.clone(),
+                    instance_name: component.instance_name.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    members
+}
+
+/// reconcile_components returns the discovered components, keeping the probe results already
+/// recorded for components that were known before.
+pub fn reconcile_components(
+    existing: Vec<ComponentInfo>,
+    discovered: Vec<ComponentInfo>,
+) -> Vec<ComponentInfo> {
+    discovered
+        .into_iter()
+        .map(|d| {
+            existing
+                .iter()
+                .find(|e| e.name == d.name && e.instance_name == d.instance_name)
+                .cloned()
+                .unwrap_or(d)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::discovery::{reconcile_components, scope_members};
+    use rudr::instigator::OpResource;
+    use rudr::schematic::scopes::health::ComponentInfo;
+    use serde_json::json;
+
+    fn component(name: &str, status: Option<&str>) -> ComponentInfo {
+        ComponentInfo {
+            name: name.to_string(),
+            instance_name: format!("{}-instance", name),
+            status: status.map(|s| s.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_scope_members() {
+        let config: OpResource = serde_json::from_value(json!({
+            "apiVersion": "core.oam.dev/v1alpha1",
+            "kind": "ApplicationConfiguration",
+            "metadata": {"name": "app"},
+            "spec": {"components": [
+                {"componentName": "a", "instanceName": "a-instance", "applicationScopes": ["health"]},
+                {"componentName": "b", "instanceName": "b-instance"},
+            ]},
+        }))
+        .unwrap();
+        let members = scope_members(vec![config]);
+        assert_eq!(members.len(), 1);
+        assert_eq!(members["health"].len(), 1);
+        assert_eq!(members["health"][0].name, "a");
+        assert_eq!(members["health"][0].instance_name, "a-instance");
+    }
+
+    #[test]
+    fn test_reconcile_components() {
+        let existing = vec![
+            component("a", Some("healthy")),
+            component("b", Some("unhealthy")),
+        ];
+        let discovered = vec![component("a", None), component("c", None)];
+        let components = reconcile_components(existing, discovered);
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0].name, "a");
+        assert_eq!(components[0].status, Some("healthy".to_string()));
+        assert_eq!(components[1].name, "c");
+        assert_eq!(components[1].status, None);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::state::ScopeStates;
+use failure::Error;
+use k8s_openapi::api::core::v1::ObjectReference;
+use kube::api::{PatchParams, RawApi};
+use kube::client::APIClient;
+use log::{error, info};
+use rudr::kube_event;
+use rudr::metrics::TimedRequest;
+use rudr::schematic::scopes::health::{
+    HealthScopeObject, HEALTH_SCOPE_CRD, HEALTH_SCOPE_GROUP, HEALTH_SCOPE_KIND,
+    HEALTH_SCOPE_VERSION,
+};
+use serde_json::json;
+
+/// The finalizer healthscope puts on every HealthScope so it can clean up before the object is gone.
+pub const HEALTH_SCOPE_FINALIZER: &str = "healthscope.core.oam.dev/cleanup";
+
+pub fn has_finalizer(scope: &HealthScopeObject) -> bool {
+    scope
+        .metadata
+        .finalizers
+        .iter()
+        .any(|f| f == HEALTH_SCOPE_FINALIZER)
+}
+
+/// ensure_finalizer adds our finalizer to the scope and returns the updated object.
+pub fn ensure_finalizer(
+    client: &APIClient,
+    scope: HealthScopeObject,
+    namespace: &str,
+) -> Result<HealthScopeObject, Error> {
+    if has_finalizer(&scope) {
+        return Ok(scope);
+    }
+    let mut finalizers = scope.metadata.finalizers.clone();
+    finalizers.push(HEALTH_SCOPE_FINALIZER.to_string());
+    patch_finalizers(client, &scope, namespace, finalizers)
+}
+
+/// cleanup drops the in-memory state of a deleted scope, records a `ScopeRemoved` event and
+/// removes our finalizer so Kubernetes can delete the object.
+pub fn cleanup(
+    client: &APIClient,
+    scope: HealthScopeObject,
+    namespace: &str,
+    states: &mut ScopeStates,
+) -> Result<(), Error> {
+    let name = scope.metadata.name.clone();
+    info!("health scope {} deleted, cleaning up", name);
+    states.remove(&name);
+    let event_handler = kube_event::Event::new(client.clone(), namespace.to_string());
+    if let Err(e) = event_handler.push_event_message(
+        kube_event::Type::Normal,
+        kube_event::Info {
+            action: "delete".to_string(),
+            message: format!("health scope {} removed", name),
+            reason: "ScopeRemoved".to_string(),
+        },
+        object_ref(&scope),
+    ) {
+        error!("push scope removed event for {} err {:?}", name, e)
+    }
+    let finalizers = scope
+        .metadata
+        .finalizers
+        .iter()
+        .filter(|f| *f != HEALTH_SCOPE_FINALIZER)
+        .cloned()
+        .collect();
+    patch_finalizers(client, &scope, namespace, finalizers)?;
+    Ok(())
+}
+
+fn patch_finalizers(
+    client: &APIClient,
+    scope: &HealthScopeObject,
+    namespace: &str,
+    finalizers: Vec<String>,
+) -> Result<HealthScopeObject, Error> {
+    // The resourceVersion makes the patch fail instead of dropping a finalizer someone else just added.
+    let patch = json!({
+        "metadata": {
+            "finalizers": finalizers,
+            "resourceVersion": scope.metadata.resourceVersion,
+        }
+    });
+    let req = RawApi::customResource(HEALTH_SCOPE_CRD)
+        .version(HEALTH_SCOPE_VERSION)
+        .group(HEALTH_SCOPE_GROUP)
+        .within(namespace)
+        .patch(
+            scope.metadata.name.as_str(),
+            &PatchParams::default(),
+            serde_json::to_vec(&patch)?,
+        )?;
+    Ok(client.timed_request::<HealthScopeObject>(req)?)
+}
+
+fn object_ref(scope: &HealthScopeObject) -> ObjectReference {
+    ObjectReference {
+        api_version: Some(format!("{}/{}", HEALTH_SCOPE_GROUP, HEALTH_SCOPE_VERSION)),
+        kind: Some(HEALTH_SCOPE_KIND.to_string()),
+        name: Some(scope.metadata.name.clone()),
+        namespace: scope.metadata.namespace.clone(),
+        uid: scope.metadata.uid.clone(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::finalizer::{has_finalizer, HEALTH_SCOPE_FINALIZER};
+    use rudr::schematic::scopes::health::HealthScopeObject;
+    use serde_json::json;
+
+    #[test]
+    fn test_has_finalizer() {
+        let scope = |finalizers: Vec<&str>| -> HealthScopeObject {
+            serde_json::from_value(json!({
+                "apiVersion": "core.oam.dev/v1alpha1",
+                "kind": "HealthScope",
+                "metadata": {"name": "scope", "finalizers": finalizers},
+                "spec": {"probeMethod": "kube-get", "probeEndpoint": ".status"},
+            }))
+            .unwrap()
+        };
+        assert!(!has_finalizer(&scope(vec![])));
+        assert!(!has_finalizer(&scope(vec!["other"])));
+        assert!(has_finalizer(&scope(vec!["other", HEALTH_SCOPE_FINALIZER])));
+    }
+}
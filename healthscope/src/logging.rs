@@ -0,0 +1,104 @@
+use chrono::Utc;
+use failure::{format_err, Error};
+use log::Record;
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_REQUEST_ID: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    /// Fields attached to every log line written by the current thread.
+    static FIELDS: RefCell<Vec<(&'static str, String)>> = RefCell::new(Vec::new());
+}
+
+/// init sets up env_logger, `format` is either `text` or `json`.
+pub fn init(format: &str) -> Result<(), Error> {
+    let mut builder = env_logger::Builder::from_default_env();
+    match format {
+        "text" => builder.format(|buf, record| writeln!(buf, "{}", text_line(record))),
+        "json" => builder.format(|buf, record| writeln!(buf, "{}", json_line(record))),
+        _ => {
+            return Err(format_err!(
+                "unknown log format {}, use text or json",
+                format
+            ))
+        }
+    };
+    builder.init();
+    Ok(())
+}
+
+/// next_request_id returns an ID unique within this process to correlate the log lines of one HTTP call.
+pub fn next_request_id() -> String {
+    format!(
+        "{:x}-{}",
+        Utc::now().timestamp_millis(),
+        NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// with_field attaches `key=value` to every log line the current thread writes while `f` runs.
+pub fn with_field<T>(key: &'static str, value: String, f: impl FnOnce() -> T) -> T {
+    FIELDS.with(|fields| fields.borrow_mut().push((key, value)));
+    let res = f();
+    FIELDS.with(|fields| fields.borrow_mut().pop());
+    res
+}
+
+fn fields() -> Vec<(&'static str, String)> {
+    FIELDS.with(|fields| fields.borrow().clone())
+}
+
+fn text_line(record: &Record) -> String {
+    let mut line = format!(
+        "[{} {:<5} {}] {}",
+        Utc::now().to_rfc3339(),
+        record.level(),
+        record.target(),
+        record.args()
+    );
+    for (key, value) in fields() {
+        line.push_str(&format!(" {}={}", key, value));
+    }
+    line
+}
+
+fn json_line(record: &Record) -> Value {
+    let mut line = Map::new();
+    line.insert("ts".to_string(), json!(Utc::now().to_rfc3339()));
+    line.insert("level".to_string(), json!(record.level().to_string()));
+    line.insert("target".to_string(), json!(record.target()));
+    line.insert("msg".to_string(), json!(record.args().to_string()));
+    for (key, value) in fields() {
+        line.insert(key.to_string(), json!(value));
+    }
+    Value::Object(line)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::logging::{json_line, with_field};
+    use log::{Level, Record};
+
+    #[test]
+    fn test_json_line() {
+        let line = with_field("scope", "my-scope".to_string(), || {
+            json_line(
+                &Record::builder()
+                    .args(format_args!("probe {}", "done"))
+                    .level(Level::Info)
+                    .target("healthscope")
+                    .build(),
+            )
+        });
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["msg"], "probe done");
+        assert_eq!(line["scope"], "my-scope");
+        assert!(line["ts"].is_string());
+
+        let line = json_line(&Record::builder().args(format_args!("")).build());
+        assert!(line.get("scope").is_none());
+    }
+}
@@ -1,28 +1,58 @@
 use chrono::{DateTime, Utc};
 use clap::{App, Arg};
-use env_logger;
 use failure::{format_err, Error};
-use futures::task::{current, Task};
-use futures::{future, Async};
+use futures::future;
+use futures::sync::oneshot;
+use hyper::header::{
+    HeaderValue, AGE, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED, RETRY_AFTER,
+};
 use hyper::rt::Future;
-use hyper::service::{service_fn, service_fn_ok};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use kube::api::{ListParams, ObjectList, RawApi};
 use kube::{client::APIClient, config::incluster_config, config::load_kube_config};
-use log::{debug, error, info};
-use rudr::instigator::{combine_name, CONFIG_GROUP, CONFIG_VERSION};
-use rudr::schematic::component_instance::KubeComponentInstance;
+use log::{debug, error, info, warn};
+use rudr::leader::{self, LeaderElector};
+use rudr::metrics::TimedRequest;
+use rudr::retry::{on_conflict, CONFLICT_ATTEMPTS};
 use rudr::schematic::scopes::health::{
     ComponentInfo, HealthScopeObject, HealthStatus, HEALTH_SCOPE_CRD, HEALTH_SCOPE_GROUP,
     HEALTH_SCOPE_VERSION,
 };
 use std::{
-    sync::{Arc, Mutex},
+    net::IpAddr,
+    sync::Arc,
     thread,
+    time::{Duration, Instant},
 };
 
-const DEFAULT_NAMESPACE: &str = "default";
-const DEFAULT_PROBE_INTERVAL: i64 = 30;
+mod aggregator;
+mod alert;
+mod cache;
+mod conditions;
+mod discovery;
+mod finalizer;
+mod logging;
+mod metrics;
+mod openapi;
+mod probe;
+mod ratelimit;
+mod shutdown;
+mod state;
+mod uptime;
+mod watchdog;
+mod webhook;
+
+use aggregator::Aggregator;
+use alert::Alertmanager;
+use cache::{CachedStatus, StatusCache};
+use ratelimit::RateLimiter;
+use state::ScopeState;
+
+/// Seconds after which an unchanged status is patched anyway to refresh its timestamp.
+const STATUS_HEARTBEAT: i64 = 300;
 
 fn kubeconfig() -> kube::Result<kube::config::Configuration> {
     // If env var is set, use in cluster config
@@ -32,6 +62,13 @@ fn kubeconfig() -> kube::Result<kube::config::Configuration> {
     load_kube_config()
 }
 
+// identity names this replica in the leader lease, the pod name is unique among replicas.
+fn identity() -> String {
+    std::env::var("POD_NAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| format!("healthscope-{}", std::process::id()))
+}
+
 fn main() -> Result<(), Error> {
     let flags = App::new("healthscope")
         .version(env!("CARGO_PKG_VERSION"))
@@ -49,270 +86,752 @@ fn main() -> Result<(), Error> {
                 .default_value(":80")
                 .help("The address the health scope endpoint binds to."),
         )
+        .arg(
+            Arg::with_name("shutdown-timeout")
+                .long("shutdown-timeout")
+                .default_value("10")
+                .help("Seconds to wait for in-flight requests to drain on shutdown."),
+        )
+        .arg(
+            Arg::with_name("final-status-patch")
+                .long("final-status-patch")
+                .help("Probe every health scope once more and patch its status before exiting."),
+        )
+        .arg(
+            Arg::with_name("leader-elect")
+                .long("leader-elect")
+                .help("Only run the aggregate loop on the replica holding the leader lease."),
+        )
+        .arg(
+            Arg::with_name("leader-election-lease")
+                .long("leader-election-lease")
+                .default_value("healthscope-leader")
+                .help("The name of the Lease used for leader election."),
+        )
+        .arg(
+            Arg::with_name("watchdog-timeout")
+                .long("watchdog-timeout")
+                .default_value("120")
+                .help("Seconds without a finished aggregate loop iteration before /livez fails."),
+        )
+        .arg(
+            Arg::with_name("rate-limit")
+                .long("rate-limit")
+                .default_value("5")
+                .help("Requests per second each client may send to the health scope endpoint."),
+        )
+        .arg(
+            Arg::with_name("rate-limit-burst")
+                .long("rate-limit-burst")
+                .default_value("10")
+                .help("Requests a client may send at once before the rate limit applies."),
+        )
+        .arg(
+            Arg::with_name("kube-api-qps")
+                .long("kube-api-qps")
+                .default_value("20")
+                .help("Average requests per second to the kube API server, 0 doesn't limit them."),
+        )
+        .arg(
+            Arg::with_name("kube-api-burst")
+                .long("kube-api-burst")
+                .default_value("30")
+                .help("Requests that may be sent to the kube API server at once above the average rate."),
+        )
+        .arg(
+            Arg::with_name("cache-ttl")
+                .long("cache-ttl")
+                .default_value("5")
+                .help(
+                    "Seconds a health scope response is served from memory, 0 disables the cache.",
+                ),
+        )
+        .arg(
+            Arg::with_name("alertmanager-url")
+                .long("alertmanager-url")
+                .takes_value(true)
+                .help("Push an alert to this Alertmanager when a scope becomes unhealthy."),
+        )
+        .arg(
+            Arg::with_name("webhook-addr")
+                .long("webhook-addr")
+                .takes_value(true)
+                .help("Serve the HealthScope admission webhook on this address, e.g. :9443."),
+        )
+        .arg(
+            Arg::with_name("webhook-cert")
+                .long("webhook-cert")
+                .default_value("/etc/healthscope/webhook/tls.crt")
+                .help("PEM certificate chain the admission webhook serves."),
+        )
+        .arg(
+            Arg::with_name("webhook-key")
+                .long("webhook-key")
+                .default_value("/etc/healthscope/webhook/tls.key")
+                .help("PEM private key of the admission webhook certificate."),
+        )
+        .arg(
+            Arg::with_name("log-format")
+                .long("log-format")
+                .default_value("text")
+                .possible_values(&["text", "json"])
+                .help("The format of the log lines."),
+        )
+        .arg(
+            Arg::with_name("namespace")
+                .long("namespace")
+                .env("KUBERNETES_NAMESPACE")
+                .default_value("default")
+                .help("The namespace whose health scopes are aggregated."),
+        )
+        .arg(
+            Arg::with_name("default-probe-interval")
+                .long("default-probe-interval")
+                .env("HEALTHSCOPE_DEFAULT_PROBE_INTERVAL")
+                .default_value("30")
+                .help("Seconds between probes of scopes that don't set a probe-interval."),
+        )
+        .arg(
+            Arg::with_name("relist-interval")
+                .long("relist-interval")
+                .env("HEALTHSCOPE_RELIST_INTERVAL")
+                .default_value("5")
+                .help("Seconds between two passes of the aggregate loop."),
+        )
+        .arg(
+            Arg::with_name("otlp-endpoint")
+                .long("otlp-endpoint")
+                .env("HEALTHSCOPE_OTLP_ENDPOINT")
+                .takes_value(true)
+                .help("Export traces to the OTLP/HTTP collector at this URL, e.g. http://otel-collector:4318."),
+        )
         .get_matches();
     let metrics_addr = "0.0.0.0".to_owned() + flags.value_of("metrics-addr").unwrap();
     let endpoint_addr = "0.0.0.0".to_owned() + flags.value_of("addr").unwrap();
+    let shutdown_timeout = flags
+        .value_of("shutdown-timeout")
+        .unwrap()
+        .parse::<u64>()
+        .map_err(|e| format_err!("invalid shutdown-timeout: {}", e))?;
+    let final_status_patch = flags.is_present("final-status-patch");
+    let leader_elect = flags.is_present("leader-elect");
+    let lease_name = flags.value_of("leader-election-lease").unwrap().to_string();
+    let rate_limit = flags
+        .value_of("rate-limit")
+        .unwrap()
+        .parse::<f64>()
+        .map_err(|e| format_err!("invalid rate-limit: {}", e))?;
+    let rate_limit_burst = flags
+        .value_of("rate-limit-burst")
+        .unwrap()
+        .parse::<f64>()
+        .map_err(|e| format_err!("invalid rate-limit-burst: {}", e))?;
+    let kube_api_qps = flags
+        .value_of("kube-api-qps")
+        .unwrap()
+        .parse::<f64>()
+        .map_err(|e| format_err!("invalid kube-api-qps: {}", e))?;
+    let kube_api_burst = flags
+        .value_of("kube-api-burst")
+        .unwrap()
+        .parse::<u32>()
+        .map_err(|e| format_err!("invalid kube-api-burst: {}", e))?;
+    let cache_ttl = flags
+        .value_of("cache-ttl")
+        .unwrap()
+        .parse::<i64>()
+        .map_err(|e| format_err!("invalid cache-ttl: {}", e))?;
+    rudr::ratelimit::configure(kube_api_qps, kube_api_burst);
+    if let Some(endpoint) = flags.value_of("otlp-endpoint") {
+        info!("exporting traces to {}", endpoint);
+        rudr::trace::configure(endpoint, "healthscope");
+    }
+    let default_probe_interval = flags
+        .value_of("default-probe-interval")
+        .unwrap()
+        .parse::<i64>()
+        .map_err(|e| format_err!("invalid default-probe-interval: {}", e))?;
+    let relist_interval = flags
+        .value_of("relist-interval")
+        .unwrap()
+        .parse::<u64>()
+        .map_err(|e| format_err!("invalid relist-interval: {}", e))?;
+    let alertmanager_url = flags.value_of("alertmanager-url").map(str::to_string);
+    let webhook_addr = flags
+        .value_of("webhook-addr")
+        .map(|addr| "0.0.0.0".to_owned() + addr);
+    let webhook_cert = flags.value_of("webhook-cert").unwrap().to_string();
+    let webhook_key = flags.value_of("webhook-key").unwrap().to_string();
+    let watchdog_timeout = flags
+        .value_of("watchdog-timeout")
+        .unwrap()
+        .parse::<i64>()
+        .map_err(|e| format_err!("invalid watchdog-timeout: {}", e))?;
 
-    env_logger::init();
+    logging::init(flags.value_of("log-format").unwrap())?;
+    shutdown::install_handlers();
     info!("starting server");
+    info!(
+        "kube API rate limit {} qps, burst {}",
+        kube_api_qps, kube_api_burst
+    );
 
-    let top_ns = std::env::var("KUBERNETES_NAMESPACE").unwrap_or_else(|_| DEFAULT_NAMESPACE.into());
+    let top_ns = flags.value_of("namespace").unwrap().to_string();
     let top_cfg = kubeconfig().expect("Load default kubeconfig");
 
     let cfg_watch = top_cfg.clone();
+    let status_cache = StatusCache::default();
+    let watch_cache = status_cache.clone();
+    let cfg_probe = top_cfg.clone();
+    let probe_ns = top_ns.clone();
+    let probe_cache = status_cache.clone();
 
     let health_scope_watch = std::thread::spawn(move || {
-        let ns = top_ns.clone();
-        let healthscope_resource = RawApi::customResource("healthscopes")
-            .version("v1alpha1")
-            .group("core.oam.dev")
-            .within(ns.as_str());
         let client = APIClient::new(cfg_watch);
+        let elector = if leader_elect {
+            Some(LeaderElector::new(
+                client.clone(),
+                top_ns.clone(),
+                lease_name,
+                identity(),
+                leader::DEFAULT_LEASE_DURATION,
+            ))
+        } else {
+            None
+        };
+        let mut aggregator = Aggregator::new(
+            client.clone(),
+            top_ns.clone(),
+            alertmanager_url.as_deref().map(Alertmanager::new),
+            watch_cache,
+            default_probe_interval,
+        );
+        let is_leader = || match elector {
+            Some(ref elector) => elector.try_acquire_or_renew().unwrap_or_else(|e| {
+                error!("leader election err {:?}", e);
+                false
+            }),
+            None => true,
+        };
         let mut cnt = 0;
-        loop {
-            let req = healthscope_resource.list(&ListParams::default())?;
-            match client.request::<ObjectList<HealthScopeObject>>(req) {
-                Ok(health_scopes) => {
-                    for scope in health_scopes.items {
-                        if let Err(res) = aggregate_component_health(&client, scope, ns.clone()) {
-                            // Log the error and continue.
-                            error!("Error processing event: {:?}", res)
-                        };
-                    }
-                }
-                Err(e) => error!("get health scope list err {:?}", e),
+        // The current pass always runs to completion, the loop only stops between passes.
+        while !shutdown::requested() {
+            // Every replica keeps serving the HTTP endpoint, only the leader writes scope status.
+            {
+                let _span = rudr::trace::span("healthscope.aggregate");
+                aggregator.aggregate_all(false, &is_leader);
             }
             cnt = (cnt + 1) % 10;
             if cnt == 0 {
                 debug!("health scope aggregate loop running...");
             }
+            watchdog::record_pass();
             //FIXME: we could change this to use an informer if we have a runtime controller queue
-            std::thread::sleep(std::time::Duration::from_secs(5));
+            shutdown::sleep(Duration::from_secs(relist_interval));
+        }
+        info!("health scope aggregate loop stopped");
+        if final_status_patch && is_leader() {
+            info!("writing final status patch");
+            aggregator.aggregate_all(true, &is_leader);
+        }
+        if let Some(ref elector) = elector {
+            if let Err(e) = elector.release() {
+                error!("release leader lease err {:?}", e);
+            }
         }
     });
 
-    let server = std::thread::spawn(move || {
+    let (drained_tx, drained_rx) = std::sync::mpsc::channel();
+
+    let (server_stop, server_stopped) = oneshot::channel::<()>();
+    let endpoint = Arc::new(Endpoint {
+        limiter: RateLimiter::new(rate_limit, rate_limit_burst),
+        cache: status_cache,
+        cache_ttl,
+        cfg: top_cfg.clone(),
+        namespace: probe_ns.clone(),
+    });
+    let server_drained = drained_tx.clone();
+    std::thread::spawn(move || {
         let addr = endpoint_addr.parse().unwrap();
         info!("Server is running on {}", addr);
         hyper::rt::run(
             Server::bind(&addr)
-                .serve(move || service_fn(serve_health))
-                .map_err(|e| eprintln!("server error: {}", e)),
+                .serve(make_service_fn(move |conn: &AddrStream| {
+                    let endpoint = endpoint.clone();
+                    let ip = conn.remote_addr().ip();
+                    service_fn(move |req| serve_health(req, ip, endpoint.clone()))
+                }))
+                .with_graceful_shutdown(server_stopped.map_err(|_| ()))
+                .map_err(|e| error!("server error: {}", e)),
         );
+        let _ = server_drained.send("health scope server");
     });
 
+    if let Some(addr) = webhook_addr {
+        std::thread::spawn(move || {
+            if let Err(e) =
+                webhook::serve(&addr, &webhook_cert, &webhook_key, default_probe_interval)
+            {
+                error!("admission webhook stopped: {:?}", e);
+            }
+        });
+    }
+
+    let (health_stop, health_stopped) = oneshot::channel::<()>();
     std::thread::spawn(move || {
         let addr = metrics_addr.parse().unwrap();
         info!("Health server is running on {}", addr);
         hyper::rt::run(
             Server::bind(&addr)
-                .serve(|| {
-                    service_fn_ok(|_req| match (_req.method(), _req.uri().path()) {
-                        (&Method::GET, "/health") => {
-                            debug!("health check");
-                            Response::new(Body::from("OK"))
-                        }
-                        _ => Response::builder()
-                            .status(StatusCode::NOT_FOUND)
-                            .body(Body::from(""))
-                            .unwrap(),
+                .serve(move || {
+                    let cfg = cfg_probe.clone();
+                    let ns = probe_ns.clone();
+                    let cache = probe_cache.clone();
+                    service_fn(move |req| {
+                        serve_probe(req, cfg.clone(), ns.clone(), watchdog_timeout, &cache)
                     })
                 })
-                .map_err(|e| eprintln!("health server error: {}", e)),
+                .with_graceful_shutdown(health_stopped.map_err(|_| ()))
+                .map_err(|e| error!("health server error: {}", e)),
         );
-    })
-    .join()
-    .unwrap();
-
-    server.join().unwrap();
-    health_scope_watch.join().unwrap()
-}
+        let _ = drained_tx.send("health server");
+    });
 
-pub struct HealthFuture {
-    shared_state: Arc<Mutex<SharedState>>,
-}
+    shutdown::wait();
+    info!("shutdown requested, waiting for the aggregate loop to finish its current pass");
+    health_scope_watch.join().unwrap();
 
-/// Shared state between the future and the waiting thread
-struct SharedState {
-    /// Whether or not the sleep time has elapsed
-    completed: bool,
-    resp: String,
-    task: Option<Task>,
-}
-
-impl Future for HealthFuture {
-    type Item = Response<Body>;
-    type Error = hyper::Error;
-    fn poll(&mut self) -> futures::Poll<Response<Body>, hyper::Error> {
-        // Look at the shared state to see if the timer has already completed.
-        let mut shared_state = self.shared_state.lock().unwrap();
-        if shared_state.completed {
-            Ok(Async::Ready(Response::new(Body::from(
-                shared_state.resp.clone(),
-            ))))
-        } else {
-            shared_state.task = Some(current());
-            Ok(Async::NotReady)
+    // Stop accepting connections and give in-flight requests until the deadline to finish.
+    let _ = server_stop.send(());
+    let _ = health_stop.send(());
+    let deadline = Instant::now() + Duration::from_secs(shutdown_timeout);
+    for _ in 0..2 {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match drained_rx.recv_timeout(remaining) {
+            Ok(name) => info!("{} drained", name),
+            Err(_) => {
+                error!(
+                    "in-flight requests not drained within {}s, exiting anyway",
+                    shutdown_timeout
+                );
+                break;
+            }
         }
     }
+    info!("healthscope stopped");
+    Ok(())
 }
 
-// FIXME kube-rs client doesn't support async call so we have to wrap it in HealthFuture here.
-// we could remove this wrapper until kube-rs support. https://github.com/clux/kube-rs/issues/63
-impl HealthFuture {
-    /// Create a new `TimerFuture` which will complete after the provided
-    /// timeout.
-    pub fn new(instance: String) -> Self {
-        let shared_state = Arc::new(Mutex::new(SharedState {
-            completed: false,
-            task: None,
-            resp: String::new(),
-        }));
-
-        // Spawn the new thread
-        let thread_shared_state = shared_state.clone();
-        thread::spawn(move || {
-            let res = match request_health(instance) {
-                Ok(status) => status.clone(),
-                Err(err) => {
-                    error!("{:?}", err);
-                    format!("{}", err)
-                }
-            };
-
-            let mut shared_state = thread_shared_state.lock().unwrap();
-            // Signal that the request has completed and wake up the last
-            // task on which the future was polled, if one exists.
-            shared_state.completed = true;
-            shared_state.resp = res;
-            if let Some(ref task) = shared_state.task {
-                task.notify()
-            }
-        });
+type BoxFut = Box<dyn Future<Item = Response<Body>, Error = hyper::Error> + Send>;
 
-        HealthFuture { shared_state }
-    }
+/// Endpoint is the state shared by all requests to the health scope endpoint.
+pub struct Endpoint {
+    limiter: RateLimiter,
+    cache: StatusCache,
+    cache_ttl: i64,
+    cfg: kube::config::Configuration,
+    namespace: String,
 }
 
-type BoxFut = Box<dyn Future<Item = Response<Body>, Error = hyper::Error> + Send>;
-
 // serve_health make health scope controller as an http server, it will serve requests and get the real health status from health scope instance
-fn serve_health(req: Request<Body>) -> BoxFut {
+fn serve_health(req: Request<Body>, client_ip: IpAddr, endpoint: Arc<Endpoint>) -> BoxFut {
     let mut response = Response::new(Body::empty());
     let path = req.uri().path().to_owned();
+    if !endpoint.limiter.allow(client_ip) {
+        warn!("rate limit exceeded for {}", client_ip);
+        *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from_static("1"));
+        return Box::new(future::ok(response));
+    }
     match (req.method(), path) {
-        (&Method::GET, path) => {
+        (&Method::GET, ref path) if path == "/openapi.json" => {
+            let mut response = Response::new(Body::from(openapi::document().to_string()));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            return Box::new(future::ok(response));
+        }
+        (&Method::GET, path) | (&Method::HEAD, path) => {
             let instance = path.trim_start_matches('/').to_string();
-            info!("{} health scope requested", instance);
-            return Box::new(HealthFuture::new(instance));
+            let request_id = logging::next_request_id();
+            logging::with_field("request_id", request_id.clone(), || {
+                info!("{} health scope requested by {}", instance, client_ip)
+            });
+            if let Some(cached) = endpoint.cache.fresh(&instance, endpoint.cache_ttl) {
+                return Box::new(future::ok(cached_health(cached, &req)));
+            }
+            if instance.is_empty() {
+                return Box::new(future::ok(probe_response(
+                    StatusCode::NOT_FOUND,
+                    "no health scope requested".to_string(),
+                )));
+            }
+            // A scope the aggregate loop hasn't published in the last `--cache-ttl` seconds is read
+            // from the API server, later requests are answered from the response kept in the
+            // cache. The kube client is blocking, so the read runs on its own thread.
+            let (tx, rx) = oneshot::channel();
+            let (cfg, namespace, name) = (
+                endpoint.cfg.clone(),
+                endpoint.namespace.clone(),
+                instance.clone(),
+            );
+            thread::spawn(move || {
+                let _ = tx.send(read_scope(cfg, namespace, name));
+            });
+            return Box::new(rx.then(move |res| {
+                Ok(match res {
+                    Ok(Ok(Some((status, suspended)))) => {
+                        endpoint.cache.publish(instance.clone(), status, suspended);
+                        match endpoint.cache.get(&instance) {
+                            Some(cached) => cached_health(cached, &req),
+                            None => {
+                                probe_response(StatusCode::INTERNAL_SERVER_ERROR, String::new())
+                            }
+                        }
+                    }
+                    Ok(Ok(None)) => probe_response(
+                        StatusCode::NOT_FOUND,
+                        format!("health scope {} not found", instance),
+                    ),
+                    Ok(Err(e)) => {
+                        warn!("read health scope {} err {:?}", instance, e);
+                        probe_response(
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            format!("health scope {} could not be read: {}", instance, e),
+                        )
+                    }
+                    Err(_) => probe_response(StatusCode::INTERNAL_SERVER_ERROR, String::new()),
+                })
+            }));
         }
         _ => *response.status_mut() = StatusCode::NOT_FOUND,
     }
     Box::new(future::ok(response))
 }
 
-// request_health will request health scope instance CR and get status from the CR object
-fn request_health(instance_name: String) -> Result<String, Error> {
-    let namespace =
-        std::env::var("KUBERNETES_NAMESPACE").unwrap_or_else(|_| DEFAULT_NAMESPACE.into());
-    let cfg = kubeconfig().unwrap();
-    println!(
-        "cfg {:?}, instance {}",
-        cfg.base_path.clone(),
-        instance_name
-    );
-    let client = &(APIClient::new(cfg));
-    println!("client namespace {}", namespace.clone());
-    let healthscope_resource = RawApi::customResource("healthscopes")
-        .version("v1alpha1")
-        .group("core.oam.dev")
-        .within(namespace.as_str());
-    let req = healthscope_resource.get(instance_name.as_str())?;
-    let obj = client.request::<HealthScopeObject>(req)?;
-    let mut health = "healthy";
-    obj.status.map(|status| {
-        status.clone().components.map(|comps| {
-            comps.iter().for_each(|c| {
-                if let Some(real_status) = c.status.as_ref() {
-                    if real_status != "healthy" {
-                        health = "unhealthy"
-                    }
-                };
-            })
-        })
+// cached_health answers from the status cache, the `Age` header tells how many seconds ago the
+// components were probed and `X-Last-Aggregate-Timestamp` when. Suspended scopes answer with
+// their frozen health and `X-Scope-Suspended: true`. Conditional requests get `304 Not Modified`
+// until the scope is probed again, `HEAD` requests get the headers alone.
+fn cached_health(cached: CachedStatus, req: &Request<Body>) -> Response<Body> {
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let mut response = if cached.not_modified(header(IF_NONE_MATCH), header(IF_MODIFIED_SINCE)) {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        response
+    } else if req.method() == Method::HEAD {
+        Response::new(Body::empty())
+    } else {
+        Response::new(Body::from(cached.health))
+    };
+    let headers = response.headers_mut();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    if let Some(value) = cached.etag().and_then(|v| HeaderValue::from_str(&v).ok()) {
+        headers.insert(ETAG, value);
+    }
+    if let Some(value) = cached
+        .last_modified()
+        .and_then(|v| HeaderValue::from_str(&v).ok())
+    {
+        headers.insert(LAST_MODIFIED, value);
+    }
+    if cached.suspended {
+        headers.insert("x-scope-suspended", HeaderValue::from_static("true"));
+    }
+    headers.insert(AGE, HeaderValue::from(cached.age(Utc::now())));
+    if let Some(timestamp) = cached.last_aggregate_timestamp {
+        if let Ok(value) = HeaderValue::from_str(&timestamp.to_rfc3339()) {
+            headers.insert("x-last-aggregate-timestamp", value);
+        }
+    }
+    response
+}
+
+// serve_probe serves the probes of the controller itself:
+// `/livez` fails when the aggregate loop is stuck, `/readyz` fails until the kube API is reachable and
+// the aggregate loop finished its first iteration, `/healthz` (and the legacy `/health`) requires both.
+// `/metrics` exposes the uptime of the health scopes.
+fn serve_probe(
+    req: Request<Body>,
+    cfg: kube::config::Configuration,
+    namespace: String,
+    watchdog_timeout: i64,
+    cache: &StatusCache,
+) -> BoxFut {
+    let (live, ready) = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => {
+            let mut response = Response::new(Body::from(metrics::render(cache, &namespace)));
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; version=0.0.4"),
+            );
+            return Box::new(future::ok(response));
+        }
+        (&Method::GET, "/livez") => (true, false),
+        (&Method::GET, "/readyz") => (false, true),
+        (&Method::GET, "/healthz") | (&Method::GET, "/health") => (true, true),
+        _ => {
+            return Box::new(future::ok(probe_response(
+                StatusCode::NOT_FOUND,
+                String::new(),
+            )))
+        }
+    };
+    debug!("{} probe requested", req.uri().path());
+    if live && !watchdog::alive(watchdog_timeout) {
+        warn!("aggregate loop stalled for more than {}s", watchdog_timeout);
+        return Box::new(future::ok(probe_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "aggregate loop stalled".to_string(),
+        )));
+    }
+    if !ready {
+        return Box::new(future::ok(probe_response(StatusCode::OK, "OK".to_string())));
+    }
+    if !watchdog::passed_once() {
+        return Box::new(future::ok(probe_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "aggregate loop has not finished a pass yet".to_string(),
+        )));
+    }
+    // The kube client is blocking, so the API check runs on its own thread.
+    let (tx, rx) = oneshot::channel();
+    thread::spawn(move || {
+        let _ = tx.send(kube_api_reachable(cfg, namespace));
     });
-    Ok(health.to_string())
+    Box::new(rx.then(|res| {
+        Ok(match res {
+            Ok(Ok(())) => probe_response(StatusCode::OK, "OK".to_string()),
+            Ok(Err(e)) => {
+                warn!("kube API not reachable: {:?}", e);
+                probe_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("kube API not reachable: {}", e),
+                )
+            }
+            Err(_) => probe_response(StatusCode::INTERNAL_SERVER_ERROR, String::new()),
+        })
+    }))
+}
+
+fn probe_response(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+// read_scope reads the status of a scope from the API server, none if there is no such scope.
+fn read_scope(
+    cfg: kube::config::Configuration,
+    namespace: String,
+    name: String,
+) -> Result<Option<(Option<HealthStatus>, bool)>, Error> {
+    let client = APIClient::new(cfg);
+    let req = RawApi::customResource(HEALTH_SCOPE_CRD)
+        .version(HEALTH_SCOPE_VERSION)
+        .group(HEALTH_SCOPE_GROUP)
+        .within(namespace.as_str())
+        .get(name.as_str())?;
+    match client.timed_request::<serde_json::Value>(req) {
+        Ok(item) => published_status(item),
+        Err(e) if e.api_error().map(|err| err.code) == Some(404) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// published_status is the status and suspension of a scope read untyped from the API server.
+// Scopes being deleted are dropped from the cache by the aggregate loop, so they are none too.
+fn published_status(
+    item: serde_json::Value,
+) -> Result<Option<(Option<HealthStatus>, bool)>, Error> {
+    // kube's ObjectMeta has no deletionTimestamp, so the scope is read untyped first.
+    if item["metadata"]["deletionTimestamp"].is_string() {
+        return Ok(None);
+    }
+    let scope: HealthScopeObject = serde_json::from_value(item)?;
+    Ok(Some((scope.status, scope.spec.suspended == Some(true))))
 }
 
+fn kube_api_reachable(cfg: kube::config::Configuration, namespace: String) -> Result<(), Error> {
+    let client = APIClient::new(cfg);
+    let req = RawApi::customResource(HEALTH_SCOPE_CRD)
+        .version(HEALTH_SCOPE_VERSION)
+        .group(HEALTH_SCOPE_GROUP)
+        .within(namespace.as_str())
+        .list(&ListParams::default())?;
+    client.timed_request::<ObjectList<HealthScopeObject>>(req)?;
+    Ok(())
+}
+
+// scope_health computes the health of a whole scope from its components:
+// any unhealthy component makes the scope unhealthy, a degraded one (but no unhealthy) makes it degraded.
+fn scope_health(status: Option<HealthStatus>) -> &'static str {
+    let mut health = "healthy";
+    for c in status
+        .and_then(|status| status.components)
+        .unwrap_or_default()
+    {
+        match c.status.as_deref() {
+            None | Some("healthy") => {}
+            Some("degraded") => health = "degraded",
+            Some(_) => return "unhealthy",
+        }
+    }
+    health
+}
+
+// aggregate_component_health probes the components of a scope once its probe interval elapsed
+// and returns the new status it patched. `discovered` replaces the components listed in the status,
+// it is None if the components of the scope could not be discovered. Scopes without a probe-interval
+// are probed every `default_interval` seconds.
+//
+// The status last computed for the scope is kept in `state`, the patch is skipped when the new status
+// only differs from the stored one by its timestamps and counters, unless the stored one is older
+// than STATUS_HEARTBEAT. Probing takes a while, so `is_leader` is asked again before the patch.
+#[allow(clippy::too_many_arguments)]
 fn aggregate_component_health(
     client: &APIClient,
     mut event: HealthScopeObject,
     namespace: String,
-) -> Result<(), Error> {
-    let interval = event.spec.probe_interval.unwrap_or(DEFAULT_PROBE_INTERVAL);
-    if !time_to_aggregate(event.status.clone(), interval) {
-        return Ok(());
+    force: bool,
+    discovered: Option<Vec<ComponentInfo>>,
+    default_interval: i64,
+    state: &mut ScopeState,
+    is_leader: &dyn Fn() -> bool,
+) -> Result<Option<HealthStatus>, Error> {
+    if event.spec.suspended == Some(true) {
+        debug!("health scope {} is suspended", event.metadata.name);
+        return Ok(None);
+    }
+    let stored = event.status.take();
+    let current = state.last_status.clone().or_else(|| stored.clone());
+    let interval = event.spec.probe_interval.unwrap_or(default_interval);
+    if !force && !time_to_aggregate(current.clone(), interval) {
+        return Ok(None);
     }
     info!("start to probe instance: {}", event.metadata.name);
-    match (
-        event.spec.probe_method.as_str(),
-        event.spec.probe_endpoint.as_str(),
-    ) {
-        ("kube-get", ".status") => {
-            let components =
-                event
-                    .status
-                    .and_then(|status| status.components)
-                    .and_then(|mut components| {
-                        for c in components.iter_mut() {
-                            c.status = Some(get_health_from_component(
-                                client,
-                                c.clone(),
-                                namespace.clone(),
-                            ))
-                        }
-                        Some(components)
-                    });
-            event.status = Some(HealthStatus {
-                components,
-                last_aggregate_timestamp: Some(Utc::now().to_rfc3339()),
-            });
-            let pp = kube::api::PatchParams::default();
-            let healthscope_resource = RawApi::customResource(HEALTH_SCOPE_CRD)
-                .version(HEALTH_SCOPE_VERSION)
-                .group(HEALTH_SCOPE_GROUP)
-                .within(namespace.as_str());
-            let req = healthscope_resource.patch(
-                event.metadata.clone().name.as_str(),
-                &pp,
-                serde_json::to_vec(&event)?,
-            )?;
-            client.request::<HealthScopeObject>(req)?;
-            Ok(())
-        }
-        _ => Err(format_err!(
-            "unknown probe-method {} and probe_endpoint {}",
-            event.spec.probe_method,
-            event.spec.probe_endpoint
+    let probes = event
+        .spec
+        .probes()
+        .into_iter()
+        .map(|p| match probe::probe_fn(&p) {
+            Some(probe_fn) => Ok((p, probe_fn)),
+            None => Err(format_err!(
+                "unknown probe-method {} and probe_endpoint {}",
+                p.probe_method,
+                p.probe_endpoint
+            )),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let target = probe::ProbeTarget {
+        client,
+        namespace: namespace.as_str(),
+        timeout: event.spec.probe_timeout,
+    };
+    let previous_conditions = current.as_ref().and_then(|s| s.conditions.clone());
+    let known = current.and_then(|status| status.components);
+    let discovered_members = discovered.is_some();
+    let components = match discovered {
+        Some(discovered) => Some(discovery::reconcile_components(
+            known.unwrap_or_default(),
+            discovered,
         )),
+        None => known,
+    };
+    let components = components.map(|mut components| {
+        for c in components.iter_mut() {
+            let (health, error) = probe::probe_with_fallback(&target, &probes, c);
+            probe::record_probe(c, health, error, Utc::now());
+        }
+        components
+    });
+    let now = Utc::now();
+    let mut status = HealthStatus {
+        components,
+        last_aggregate_timestamp: Some(now.to_rfc3339()),
+        uptime: None,
+        conditions: None,
+    };
+    let health = scope_health(Some(status.clone()));
+    state.uptime.record(now, health != "unhealthy");
+    status.uptime = Some(state.uptime.uptime(now));
+    status.conditions = Some(conditions::scope_conditions(
+        previous_conditions,
+        health,
+        status.components.as_deref().unwrap_or_default(),
+        now,
+    ));
+    state.last_status = Some(status.clone());
+    if !needs_patch(stored.as_ref(), &status, now) {
+        debug!("status of {} unchanged, skip patching", event.metadata.name);
+        return Ok(Some(status));
     }
+    if !is_leader() {
+        info!(
+            "lost the leader lease, skip patching status of {}",
+            event.metadata.name
+        );
+        return Ok(None);
+    }
+    let pp = kube::api::PatchParams::default();
+    let healthscope_resource = RawApi::customResource(HEALTH_SCOPE_CRD)
+        .version(HEALTH_SCOPE_VERSION)
+        .group(HEALTH_SCOPE_GROUP)
+        .within(namespace.as_str());
+    let name = event.metadata.name.clone();
+    // Rudr adds and removes the components of a scope it doesn't discover them for at the same
+    // time. After a conflict the probe results are put on the components the scope has now.
+    on_conflict(CONFLICT_ATTEMPTS, |attempt| {
+        if attempt > 0 {
+            warn!("conflict patching status of health scope {}, retry", name);
+            event = client.timed_request(healthscope_resource.get(name.as_str())?)?;
+            let members = event.status.as_ref().and_then(|s| s.components.clone());
+            if !discovered_members {
+                if let Some(members) = members {
+                    let probed = status.components.clone().unwrap_or_default();
+                    status.components = Some(discovery::reconcile_components(probed, members));
+                }
+            }
+        }
+        event.status = Some(status.clone());
+        let req = healthscope_resource.patch(name.as_str(), &pp, serde_json::to_vec(&event)?)?;
+        client.timed_request::<HealthScopeObject>(req)?;
+        Ok(())
+    })?;
+    state.last_status = Some(status.clone());
+    Ok(Some(status))
 }
 
-fn get_health_from_component(client: &APIClient, info: ComponentInfo, namespace: String) -> String {
-    let name = combine_name(info.name, info.instance_name);
-    let crd_req = RawApi::customResource("componentinstances")
-        .group(CONFIG_GROUP)
-        .version(CONFIG_VERSION)
-        .within(namespace.as_str());
-    let req = crd_req.get(name.as_str()).unwrap();
-    let res: KubeComponentInstance = match client.request(req) {
-        Ok(ins) => ins,
-        Err(e) => {
-            error!("get component instance failed {:?}", e);
-            return "unhealthy".to_string();
+// needs_patch is false when `new` only differs from the stored status by its timestamps and the stored
+// status is younger than STATUS_HEARTBEAT, so readers of the resource still see a recent timestamp.
+// The uptime and the consecutive failures change with every probe of a scope that isn't healthy,
+// they are left out of the comparison and refreshed with the heartbeat.
+fn needs_patch(stored: Option<&HealthStatus>, new: &HealthStatus, now: DateTime<Utc>) -> bool {
+    let stored = match stored {
+        Some(stored) => stored,
+        None => return true,
+    };
+    let fresh = stored
+        .last_aggregate_timestamp
+        .as_ref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| now.signed_duration_since(t).num_seconds() < STATUS_HEARTBEAT)
+        == Some(true);
+    let without_counters = |status: &HealthStatus| {
+        let mut status = status.clone();
+        status.last_aggregate_timestamp = None;
+        status.uptime = None;
+        for c in status.components.iter_mut().flatten() {
+            c.last_probe_time = None;
+            c.consecutive_failures = None;
         }
+        status
     };
-    res.status.unwrap_or_else(|| "unhealthy".to_string())
+    !fresh || without_counters(stored) != without_counters(new)
 }
 
 fn time_to_aggregate(status: Option<HealthStatus>, interval: i64) -> bool {
@@ -340,9 +859,9 @@ fn time_to_aggregate(status: Option<HealthStatus>, interval: i64) -> bool {
 
 #[cfg(test)]
 mod test {
-    use crate::time_to_aggregate;
+    use crate::{needs_patch, published_status, scope_health, time_to_aggregate};
     use chrono::{Duration, Utc};
-    use rudr::schematic::scopes::health::HealthStatus;
+    use rudr::schematic::scopes::health::{ComponentInfo, HealthStatus, Uptime};
 
     #[test]
     fn test_time_to_action() {
@@ -365,4 +884,105 @@ mod test {
         assert_eq!(time_to_aggregate(status.clone(), 15), false);
         assert_eq!(time_to_aggregate(status.clone(), 0), true);
     }
+
+    #[test]
+    fn test_scope_health() {
+        let status = |states: Vec<Option<&str>>| {
+            Some(HealthStatus {
+                components: Some(
+                    states
+                        .into_iter()
+                        .map(|s| ComponentInfo {
+                            name: "c".to_string(),
+                            instance_name: "i".to_string(),
+                            status: s.map(|s| s.to_string()),
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+                ..Default::default()
+            })
+        };
+        assert_eq!(scope_health(None), "healthy");
+        assert_eq!(scope_health(status(vec![Some("healthy"), None])), "healthy");
+        assert_eq!(
+            scope_health(status(vec![Some("healthy"), Some("degraded")])),
+            "degraded"
+        );
+        assert_eq!(
+            scope_health(status(vec![Some("unhealthy"), Some("degraded")])),
+            "unhealthy"
+        );
+    }
+
+    #[test]
+    fn test_needs_patch() {
+        let now = Utc::now();
+        let status = |ago: i64, health: &str| HealthStatus {
+            components: Some(vec![ComponentInfo {
+                name: "c".to_string(),
+                status: Some(health.to_string()),
+                last_probe_time: Some((now - Duration::seconds(ago)).to_rfc3339()),
+                ..Default::default()
+            }]),
+            last_aggregate_timestamp: Some((now - Duration::seconds(ago)).to_rfc3339()),
+            ..Default::default()
+        };
+        let fresh = status(0, "healthy");
+        assert!(needs_patch(None, &fresh, now));
+        assert!(!needs_patch(Some(&status(30, "healthy")), &fresh, now));
+        assert!(needs_patch(Some(&status(30, "unhealthy")), &fresh, now));
+        assert!(needs_patch(Some(&status(301, "healthy")), &fresh, now));
+
+        let failing = |ago: i64, failures: i64, last_hour: f64| {
+            let mut status = status(ago, "unhealthy");
+            status.uptime = Some(Uptime {
+                last_hour: Some(last_hour),
+                ..Default::default()
+            });
+            for c in status.components.iter_mut().flatten() {
+                c.consecutive_failures = Some(failures);
+            }
+            status
+        };
+        let fresh = failing(0, 4, 96.5);
+        assert!(!needs_patch(Some(&failing(30, 3, 97.0)), &fresh, now));
+        assert!(needs_patch(Some(&failing(301, 3, 97.0)), &fresh, now));
+        assert!(needs_patch(Some(&status(30, "healthy")), &fresh, now));
+    }
+
+    #[test]
+    fn test_published_status() {
+        let scope = |metadata: serde_json::Value, suspended: bool| {
+            serde_json::json!({
+                "apiVersion": "core.oam.dev/v1alpha1",
+                "kind": "HealthScope",
+                "metadata": metadata,
+                "spec": {
+                    "probeMethod": "kube-get",
+                    "probeEndpoint": "/",
+                    "suspended": suspended
+                },
+                "status": {
+                    "components": [{
+                        "name": "c",
+                        "instanceName": "i",
+                        "status": "unhealthy"
+                    }]
+                }
+            })
+        };
+        let (status, suspended) =
+            published_status(scope(serde_json::json!({"name": "my-scope"}), true))
+                .unwrap()
+                .unwrap();
+        assert_eq!(scope_health(status), "unhealthy");
+        assert!(suspended);
+        let deleting = serde_json::json!({
+            "name": "my-scope",
+            "deletionTimestamp": "2019-10-02T08:00:00Z"
+        });
+        assert!(published_status(scope(deleting, false)).unwrap().is_none());
+        assert!(published_status(serde_json::json!({"kind": "HealthScope"})).is_err());
+    }
 }
@@ -0,0 +1,67 @@
+use crate::cache::StatusCache;
+use crate::uptime::WINDOWS;
+use std::fmt::Write;
+
+/// render writes the uptime windows of every cached scope in the Prometheus text format.
+pub fn render(cache: &StatusCache, namespace: &str) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "# HELP healthscope_uptime_ratio Share of probes that found the health scope not unhealthy.\n",
+    );
+    out.push_str("# TYPE healthscope_uptime_ratio gauge\n");
+    for (scope, cached) in cache.snapshot() {
+        let windows = [
+            cached.uptime.last_hour,
+            cached.uptime.last_day,
+            cached.uptime.last_week,
+        ];
+        for ((window, _), percentage) in WINDOWS.iter().zip(windows.iter()) {
+            if let Some(percentage) = percentage {
+                let _ = writeln!(
+                    out,
+                    "healthscope_uptime_ratio{{namespace=\"{}\",scope=\"{}\",window=\"{}\"}} {}",
+                    namespace,
+                    scope,
+                    window,
+                    percentage / 100.0
+                );
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cache::StatusCache;
+    use crate::metrics::render;
+    use rudr::schematic::scopes::health::{HealthStatus, Uptime};
+
+    #[test]
+    fn test_render() {
+        let cache = StatusCache::default();
+        cache.publish(
+            "scope".to_string(),
+            Some(HealthStatus {
+                uptime: Some(Uptime {
+                    last_hour: Some(50.0),
+                    last_day: None,
+                    last_week: Some(100.0),
+                }),
+                ..Default::default()
+            }),
+            false,
+        );
+        cache.publish("new".to_string(), None, false);
+        let out = render(&cache, "default");
+        assert!(out.contains("# TYPE healthscope_uptime_ratio gauge\n"));
+        assert!(out.contains(
+            "healthscope_uptime_ratio{namespace=\"default\",scope=\"scope\",window=\"1h\"} 0.5\n"
+        ));
+        assert!(out.contains(
+            "healthscope_uptime_ratio{namespace=\"default\",scope=\"scope\",window=\"7d\"} 1\n"
+        ));
+        assert!(!out.contains("window=\"24h\""));
+        assert!(!out.contains("scope=\"new\""));
+    }
+}
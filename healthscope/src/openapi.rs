@@ -0,0 +1,146 @@
+use serde_json::{json, Value};
+
+/// The values `scope_health` answers with.
+pub const HEALTH_VALUES: [&str; 3] = ["healthy", "degraded", "unhealthy"];
+
+/// document describes the health scope endpoint as an OpenAPI 3 document, served at `/openapi.json`.
+pub fn document() -> Value {
+    let text = |description: &str| {
+        json!({
+            "description": description,
+            "content": {"text/plain": {"schema": {"type": "string"}}},
+        })
+    };
+    let validators = json!({
+        "ETag": {
+            "description": "Changes whenever the scope is probed again.",
+            "schema": {"type": "string"},
+        },
+        "Last-Modified": {
+            "description": "When the components of the scope were probed.",
+            "schema": {"type": "string"},
+        },
+    });
+    let scope_get = json!({
+        "summary": "Get the health of a health scope",
+        "parameters": [
+            {
+                "name": "scope",
+                "in": "path",
+                "required": true,
+                "description": "Name of the HealthScope resource.",
+                "schema": {"type": "string"},
+            },
+            {
+                "name": "If-None-Match",
+                "in": "header",
+                "description": "ETag of a previous response.",
+                "schema": {"type": "string"},
+            },
+            {
+                "name": "If-Modified-Since",
+                "in": "header",
+                "description": "Last-Modified of a previous response.",
+                "schema": {"type": "string"},
+            },
+        ],
+        "responses": {
+            "200": {
+                "description": "Health of the scope as of its last probe.",
+                "headers": {
+                    "Age": {
+                        "description": "Seconds since the components of the scope were probed.",
+                        "schema": {"type": "integer"},
+                    },
+                    "X-Last-Aggregate-Timestamp": {
+                        "description": "When the components of the scope were probed.",
+                        "schema": {"type": "string", "format": "date-time"},
+                    },
+                    "X-Scope-Suspended": {
+                        "description": "Set to true while probing of the scope is suspended.",
+                        "schema": {"type": "boolean"},
+                    },
+                    "ETag": validators["ETag"],
+                    "Last-Modified": validators["Last-Modified"],
+                },
+                "content": {"text/plain": {"schema": {"$ref": "#/components/schemas/Health"}}},
+            },
+            "304": {
+                "description": "The scope was not probed since the response the client holds.",
+                "headers": validators,
+            },
+            "404": text("The scope does not exist."),
+            "429": {
+                "description": "The client exceeded its rate limit.",
+                "headers": {
+                    "Retry-After": {
+                        "description": "Seconds to wait before retrying.",
+                        "schema": {"type": "integer"},
+                    },
+                },
+            },
+            "503": text("The first aggregation pass has not finished yet."),
+        },
+    });
+    json!({
+        "openapi": "3.0.0",
+        "info": {
+            "title": "healthscope",
+            "description": "Aggregated health of the OAM health scopes in a namespace.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/{scope}": {
+                "get": scope_get,
+                "head": scope_get,
+            },
+            "/openapi.json": {
+                "get": {
+                    "summary": "Get this document",
+                    "responses": {
+                        "200": {
+                            "description": "OpenAPI description of the endpoint.",
+                            "content": {"application/json": {"schema": {"type": "object"}}},
+                        },
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "Health": {"type": "string", "enum": HEALTH_VALUES},
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::openapi::{document, HEALTH_VALUES};
+    use crate::scope_health;
+    use rudr::schematic::scopes::health::{ComponentInfo, HealthStatus};
+
+    #[test]
+    fn test_document() {
+        let doc = document();
+        assert_eq!(doc["openapi"], "3.0.0");
+        assert!(doc["paths"]["/{scope}"]["get"]["responses"]["200"].is_object());
+        assert!(doc["paths"]["/{scope}"]["head"]["responses"]["304"].is_object());
+        assert!(doc["paths"]["/openapi.json"]["get"].is_object());
+
+        let health = doc["components"]["schemas"]["Health"]["enum"]
+            .as_array()
+            .unwrap();
+        assert_eq!(health.len(), HEALTH_VALUES.len());
+        for component_status in &["healthy", "degraded", "unhealthy"] {
+            let status = HealthStatus {
+                components: Some(vec![ComponentInfo {
+                    status: Some(component_status.to_string()),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            };
+            assert!(HEALTH_VALUES.contains(&scope_health(Some(status))));
+        }
+    }
+}
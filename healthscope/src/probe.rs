@@ -0,0 +1,362 @@
+use chrono::{DateTime, Utc};
+use failure::Error;
+use k8s_openapi::api::apps::v1::{DeploymentSpec, DeploymentStatus};
+use k8s_openapi::api::core::v1::{PodSpec, PodStatus};
+use kube::api::{ListParams, Object, ObjectList, RawApi};
+use kube::client::APIClient;
+use log::{debug, warn};
+use rudr::instigator::{combine_name, CONFIG_GROUP, CONFIG_VERSION};
+use rudr::metrics::TimedRequest;
+use rudr::schematic::component_instance::KubeComponentInstance;
+use rudr::schematic::scopes::health::{ComponentInfo, Probe};
+use std::time::Duration;
+
+/// Pods whose containers restarted more often than this are treated as crash looping.
+pub const DEFAULT_RESTART_THRESHOLD: i32 = 5;
+
+/// Seconds an http-get probe waits for a response if the scope has no probe-timeout.
+const DEFAULT_HTTP_TIMEOUT: i64 = 5;
+
+/// The label rudr puts on every pod it creates for a component instance.
+const INSTANCE_NAME_LABEL: &str = "oam.dev/instance-name";
+
+/// ProbeTarget is what every probe needs to reach the components of a scope.
+pub struct ProbeTarget<'a> {
+    pub client: &'a APIClient,
+    pub namespace: &'a str,
+    /// Seconds an http-get probe waits for a response.
+    pub timeout: Option<i64>,
+}
+
+/// A probe returns the health of a component, or an error if it could not find out.
+pub type ProbeFn = fn(&ProbeTarget, &Probe, &ComponentInfo) -> Result<String, Error>;
+
+/// probe_fn returns the implementation of a probe, or None if the method and endpoint are unknown.
+pub fn probe_fn(probe: &Probe) -> Option<ProbeFn> {
+    match (probe.probe_method.as_str(), probe.probe_endpoint.as_str()) {
+        ("kube-get", ".status") => Some(get_health_from_component),
+        ("kube-get", ".pods") => Some(get_health_from_pods),
+        ("kube-get", ".deployment") => Some(get_health_from_deployment),
+        ("http-get", _) => Some(get_health_over_http),
+        _ => None,
+    }
+}
+
+/// probe_with_fallback runs the probes in order and returns the result of the first one that didn't fail.
+/// A component is unhealthy if every probe failed, the second value then holds their errors.
+pub fn probe_with_fallback(
+    target: &ProbeTarget,
+    probes: &[(Probe, ProbeFn)],
+    info: &ComponentInfo,
+) -> (String, Option<String>) {
+    let mut errors = vec![];
+    for (probe, probe_fn) in probes {
+        match probe_fn(target, probe, info) {
+            Ok(health) => return (health, None),
+            Err(e) => {
+                warn!(
+                    "probe {} {} of instance {} failed {:?}",
+                    probe.probe_method, probe.probe_endpoint, info.instance_name, e
+                );
+                errors.push(format!(
+                    "{} {}: {}",
+                    probe.probe_method, probe.probe_endpoint, e
+                ));
+            }
+        }
+    }
+    ("unhealthy".to_string(), Some(errors.join("; ")))
+}
+
+/// record_probe stores the result of a probe in the component, together with when it ran and
+/// how many probes in a row found the component unhealthy.
+pub fn record_probe(
+    info: &mut ComponentInfo,
+    health: String,
+    error: Option<String>,
+    now: DateTime<Utc>,
+) {
+    info.consecutive_failures = if health == "unhealthy" {
+        Some(info.consecutive_failures.unwrap_or(0) + 1)
+    } else {
+        Some(0)
+    };
+    if error.is_some() || health == "healthy" {
+        info.last_error = error;
+    }
+    info.status = Some(health);
+    info.last_probe_time = Some(now.to_rfc3339());
+}
+
+/// get_health_from_component reads the status rudr wrote to the component instance.
+pub fn get_health_from_component(
+    target: &ProbeTarget,
+    _probe: &Probe,
+    info: &ComponentInfo,
+) -> Result<String, Error> {
+    let name = combine_name(info.name.clone(), info.instance_name.clone());
+    let req = RawApi::customResource("componentinstances")
+        .group(CONFIG_GROUP)
+        .version(CONFIG_VERSION)
+        .within(target.namespace)
+        .get(name.as_str())?;
+    let res: KubeComponentInstance = target.client.timed_request(req)?;
+    Ok(res
+        .status
+        .map(|s| s.health().to_string())
+        .unwrap_or_else(|| "unhealthy".to_string()))
+}
+
+/// get_health_from_pods resolves the pods of a component instance by label selector
+/// and computes the health from their real state.
+pub fn get_health_from_pods(
+    target: &ProbeTarget,
+    _probe: &Probe,
+    info: &ComponentInfo,
+) -> Result<String, Error> {
+    let params = ListParams {
+        label_selector: Some(format!("{}={}", INSTANCE_NAME_LABEL, info.instance_name)),
+        ..Default::default()
+    };
+    let req = RawApi::v1Pod().within(target.namespace).list(&params)?;
+    let pods: ObjectList<Object<PodSpec, PodStatus>> = target.client.timed_request(req)?;
+    let statuses: Vec<PodStatus> = pods.items.into_iter().filter_map(|p| p.status).collect();
+    Ok(pods_health(&statuses, DEFAULT_RESTART_THRESHOLD))
+}
+
+/// get_health_over_http sends a GET to the Service of the component instance.
+///
+/// The probe endpoint is the path, optionally prefixed with a port, e.g. `/health` or `:8080/health`.
+/// Any 2xx response is healthy, other responses are unhealthy and connection errors fail the probe.
+pub fn get_health_over_http(
+    target: &ProbeTarget,
+    probe: &Probe,
+    info: &ComponentInfo,
+) -> Result<String, Error> {
+    let url = http_probe_url(&info.instance_name, target.namespace, &probe.probe_endpoint);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(
+            target.timeout.unwrap_or(DEFAULT_HTTP_TIMEOUT).max(1) as u64,
+        ))
+        .build()?;
+    let resp = client.get(url.as_str()).send()?;
+    if resp.status().is_success() {
+        return Ok("healthy".to_string());
+    }
+    debug!("{} answered {}", url, resp.status());
+    Ok("unhealthy".to_string())
+}
+
+pub fn http_probe_url(instance_name: &str, namespace: &str, endpoint: &str) -> String {
+    let endpoint = if endpoint.starts_with(':') || endpoint.starts_with('/') {
+        endpoint.to_string()
+    } else {
+        format!("/{}", endpoint)
+    };
+    format!("http://{}.{}.svc{}", instance_name, namespace, endpoint)
+}
+
+/// pods_health is healthy only if there is at least one pod and every pod is healthy.
+pub fn pods_health(pods: &[PodStatus], restart_threshold: i32) -> String {
+    if pods.is_empty() || pods.iter().any(|p| !pod_healthy(p, restart_threshold)) {
+        return "unhealthy".to_string();
+    }
+    "healthy".to_string()
+}
+
+/// pod_healthy checks pod phase, container readiness and restart counts.
+///
+/// A succeeded pod (e.g. a finished task) is healthy, a running pod is healthy
+/// when all of its containers are ready and none of them is crash looping.
+pub fn pod_healthy(pod: &PodStatus, restart_threshold: i32) -> bool {
+    match pod.phase.as_deref() {
+        Some("Succeeded") => true,
+        Some("Running") => pod
+            .container_statuses
+            .as_ref()
+            .map(|containers| {
+                !containers.is_empty()
+                    && containers
+                        .iter()
+                        .all(|c| c.ready && c.restart_count <= restart_threshold)
+            })
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// get_health_from_deployment reads the Deployment backing a component instance and
+/// computes the health from its conditions and replica counts.
+pub fn get_health_from_deployment(
+    target: &ProbeTarget,
+    _probe: &Probe,
+    info: &ComponentInfo,
+) -> Result<String, Error> {
+    let req = RawApi::v1Deployment()
+        .within(target.namespace)
+        .get_status(info.instance_name.as_str())?;
+    let deploy: Object<DeploymentSpec, DeploymentStatus> = target.client.timed_request(req)?;
+    let desired = deploy.spec.replicas.unwrap_or(1);
+    Ok(deployment_health(
+        desired,
+        &deploy.status.unwrap_or_default(),
+    ))
+}
+
+/// deployment_health evaluates the `Available`/`Progressing` conditions and the replica counts.
+///
+/// A Deployment that is missing its progress deadline or has no available replica is unhealthy,
+/// one that has fewer available replicas than desired is degraded.
+pub fn deployment_health(desired: i32, status: &DeploymentStatus) -> String {
+    let condition_false = |type_: &str| {
+        status
+            .conditions
+            .as_ref()
+            .map(|conditions| {
+                conditions
+                    .iter()
+                    .any(|c| c.type_ == type_ && c.status == "False")
+            })
+            .unwrap_or(false)
+    };
+    if condition_false("Progressing") {
+        return "unhealthy".to_string();
+    }
+    if desired <= 0 {
+        return "healthy".to_string();
+    }
+    let available = status.available_replicas.unwrap_or(0);
+    if available <= 0 || condition_false("Available") {
+        return "unhealthy".to_string();
+    }
+    if available < desired {
+        return "degraded".to_string();
+    }
+    "healthy".to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::probe::{deployment_health, http_probe_url, pod_healthy, pods_health, record_probe};
+    use chrono::Utc;
+    use k8s_openapi::api::apps::v1::{DeploymentCondition, DeploymentStatus};
+    use k8s_openapi::api::core::v1::{ContainerStatus, PodStatus};
+    use rudr::schematic::scopes::health::ComponentInfo;
+
+    fn pod(phase: &str, ready: bool, restart_count: i32) -> PodStatus {
+        PodStatus {
+            phase: Some(phase.to_string()),
+            container_statuses: Some(vec![ContainerStatus {
+                name: "c".to_string(),
+                ready,
+                restart_count,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pod_healthy() {
+        assert!(pod_healthy(&pod("Running", true, 0), 5));
+        assert!(!pod_healthy(&pod("Running", false, 0), 5));
+        assert!(!pod_healthy(&pod("Running", true, 6), 5));
+        assert!(!pod_healthy(&pod("Pending", true, 0), 5));
+        assert!(!pod_healthy(&pod("Failed", true, 0), 5));
+        assert!(pod_healthy(&pod("Succeeded", false, 0), 5));
+        assert!(!pod_healthy(&PodStatus::default(), 5));
+    }
+
+    #[test]
+    fn test_pods_health() {
+        assert_eq!(pods_health(&[], 5), "unhealthy");
+        assert_eq!(
+            pods_health(&[pod("Running", true, 0), pod("Running", true, 1)], 5),
+            "healthy"
+        );
+        assert_eq!(
+            pods_health(&[pod("Running", true, 0), pod("Running", false, 1)], 5),
+            "unhealthy"
+        );
+    }
+
+    fn deployment(available: i32, conditions: Vec<(&str, &str)>) -> DeploymentStatus {
+        DeploymentStatus {
+            available_replicas: Some(available),
+            conditions: Some(
+                conditions
+                    .into_iter()
+                    .map(|(type_, status)| DeploymentCondition {
+                        type_: type_.to_string(),
+                        status: status.to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_deployment_health() {
+        let ok = vec![("Available", "True"), ("Progressing", "True")];
+        assert_eq!(deployment_health(3, &deployment(3, ok.clone())), "healthy");
+        assert_eq!(deployment_health(3, &deployment(2, ok.clone())), "degraded");
+        assert_eq!(
+            deployment_health(3, &deployment(0, ok.clone())),
+            "unhealthy"
+        );
+        assert_eq!(deployment_health(0, &deployment(0, ok)), "healthy");
+        assert_eq!(
+            deployment_health(
+                3,
+                &deployment(3, vec![("Available", "True"), ("Progressing", "False")])
+            ),
+            "unhealthy"
+        );
+        assert_eq!(
+            deployment_health(1, &deployment(1, vec![("Available", "False")])),
+            "unhealthy"
+        );
+        assert_eq!(
+            deployment_health(1, &DeploymentStatus::default()),
+            "unhealthy"
+        );
+    }
+
+    #[test]
+    fn test_http_probe_url() {
+        assert_eq!(
+            http_probe_url("web", "default", "/health"),
+            "http://web.default.svc/health"
+        );
+        assert_eq!(
+            http_probe_url("web", "default", ":8080/health"),
+            "http://web.default.svc:8080/health"
+        );
+        assert_eq!(
+            http_probe_url("web", "default", "health"),
+            "http://web.default.svc/health"
+        );
+    }
+
+    #[test]
+    fn test_record_probe() {
+        let now = Utc::now();
+        let mut info = ComponentInfo::default();
+        record_probe(
+            &mut info,
+            "unhealthy".to_string(),
+            Some("timeout".into()),
+            now,
+        );
+        record_probe(&mut info, "unhealthy".to_string(), None, now);
+        assert_eq!(info.status, Some("unhealthy".to_string()));
+        assert_eq!(info.consecutive_failures, Some(2));
+        assert_eq!(info.last_error, Some("timeout".to_string()));
+        assert_eq!(info.last_probe_time, Some(now.to_rfc3339()));
+        record_probe(&mut info, "healthy".to_string(), None, now);
+        assert_eq!(info.consecutive_failures, Some(0));
+        assert_eq!(info.last_error, None);
+    }
+}
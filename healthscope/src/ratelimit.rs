@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Buckets of clients that stayed away this long are dropped to bound memory.
+const IDLE_BUCKET_SECS: f64 = 600.0;
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// RateLimiter is a token bucket per client IP, refilled with `rate` tokens per second up to `burst`.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        RateLimiter {
+            rate,
+            burst,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// allow takes a token from the bucket of `ip`, it returns false if the bucket is empty.
+    pub fn allow(&self, ip: IpAddr) -> bool {
+        self.allow_at(ip, Instant::now())
+    }
+
+    fn allow_at(&self, ip: IpAddr, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > 1000 {
+            buckets.retain(|_, b| now.duration_since(b.last).as_secs_f64() < IDLE_BUCKET_SECS);
+        }
+        let burst = self.burst;
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: burst,
+            last: now,
+        });
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.last = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ratelimit::RateLimiter;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_allow() {
+        let limiter = RateLimiter::new(1.0, 2.0);
+        let a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let now = Instant::now();
+        assert!(limiter.allow_at(a, now));
+        assert!(limiter.allow_at(a, now));
+        assert!(!limiter.allow_at(a, now));
+        // Other clients have their own bucket.
+        assert!(limiter.allow_at(b, now));
+        assert!(limiter.allow_at(a, now + Duration::from_secs(1)));
+        assert!(!limiter.allow_at(a, now + Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often sleeping loops check whether a shutdown was requested.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_signal(_signal: libc::c_int) {
+    // Only async-signal-safe work is allowed here, so we just flip the flag.
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// install_handlers makes SIGTERM and SIGINT request a graceful shutdown instead of killing the process.
+pub fn install_handlers() {
+    let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+/// requested returns true once a shutdown signal was received.
+pub fn requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// sleep waits for the given duration, but returns early if a shutdown was requested.
+///
+/// The return value tells whether the full duration elapsed.
+pub fn sleep(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    while !requested() {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep(std::cmp::min(POLL_INTERVAL, deadline - now));
+    }
+    false
+}
+
+/// wait blocks until a shutdown was requested.
+pub fn wait() {
+    while !requested() {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shutdown::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sleep_without_shutdown() {
+        assert!(sleep(Duration::from_millis(10)));
+    }
+}
@@ -0,0 +1,19 @@
+use crate::alert::AlertLabels;
+use crate::uptime::UptimeTracker;
+use rudr::schematic::scopes::health::HealthStatus;
+use std::collections::HashMap;
+
+/// ScopeState is what the controller remembers about a health scope between aggregate passes.
+#[derive(Default, Clone, Debug)]
+pub struct ScopeState {
+    pub uid: Option<String>,
+    /// Labels of the Alertmanager alert currently firing for the scope.
+    pub firing_alert: Option<AlertLabels>,
+    /// Probe outcomes the uptime windows of the scope are computed from.
+    pub uptime: UptimeTracker,
+    /// The status computed by the last probe, it is only patched into the resource when it changed.
+    pub last_status: Option<HealthStatus>,
+}
+
+/// ScopeStates holds the in-memory state of every known health scope, keyed by scope name.
+pub type ScopeStates = HashMap<String, ScopeState>;
@@ -0,0 +1,72 @@
+use chrono::{DateTime, Duration, Utc};
+use rudr::schematic::scopes::health::Uptime;
+use std::collections::VecDeque;
+
+/// The rolling windows uptime is computed over, the longest one bounds the samples kept.
+pub const WINDOWS: [(&str, i64); 3] = [("1h", 3600), ("24h", 24 * 3600), ("7d", 7 * 24 * 3600)];
+
+/// UptimeTracker remembers the outcome of every probe of a scope within the longest window.
+#[derive(Default, Clone, Debug)]
+pub struct UptimeTracker {
+    samples: VecDeque<(DateTime<Utc>, bool)>,
+}
+
+impl UptimeTracker {
+    /// record adds a probe of the scope, `up` is false if the scope was unhealthy.
+    pub fn record(&mut self, now: DateTime<Utc>, up: bool) {
+        self.samples.push_back((now, up));
+        let oldest = now - Duration::seconds(WINDOWS[WINDOWS.len() - 1].1);
+        while self.samples.front().map(|(t, _)| *t <= oldest) == Some(true) {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn uptime(&self, now: DateTime<Utc>) -> Uptime {
+        Uptime {
+            last_hour: self.percentage(now, WINDOWS[0].1),
+            last_day: self.percentage(now, WINDOWS[1].1),
+            last_week: self.percentage(now, WINDOWS[2].1),
+        }
+    }
+
+    fn percentage(&self, now: DateTime<Utc>, window: i64) -> Option<f64> {
+        let since = now - Duration::seconds(window);
+        let (up, total) = self
+            .samples
+            .iter()
+            .filter(|(t, _)| *t > since)
+            .fold((0, 0), |(up, total), (_, sample)| {
+                (up + *sample as u32, total + 1)
+            });
+        if total == 0 {
+            return None;
+        }
+        Some(f64::from(up) * 100.0 / f64::from(total))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::uptime::UptimeTracker;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_uptime() {
+        let now = Utc::now();
+        let mut tracker = UptimeTracker::default();
+        assert_eq!(tracker.uptime(now).last_hour, None);
+
+        tracker.record(now - Duration::days(8), false);
+        tracker.record(now - Duration::days(2), false);
+        tracker.record(now - Duration::hours(2), true);
+        tracker.record(now - Duration::minutes(30), false);
+        tracker.record(now, true);
+        // The sample older than the longest window is dropped.
+        assert_eq!(tracker.samples.len(), 4);
+
+        let uptime = tracker.uptime(now);
+        assert_eq!(uptime.last_hour, Some(50.0));
+        assert_eq!(uptime.last_day, Some(200.0 / 3.0));
+        assert_eq!(uptime.last_week, Some(50.0));
+    }
+}
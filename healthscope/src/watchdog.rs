@@ -0,0 +1,41 @@
+use chrono::Utc;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Unix timestamp of the last finished aggregate loop iteration, 0 before the first one.
+static LAST_PASS: AtomicI64 = AtomicI64::new(0);
+
+/// record_pass is called by the aggregate loop at the end of every iteration.
+pub fn record_pass() {
+    LAST_PASS.store(Utc::now().timestamp(), Ordering::SeqCst);
+}
+
+/// passed_once returns true once the aggregate loop finished its first iteration.
+pub fn passed_once() -> bool {
+    LAST_PASS.load(Ordering::SeqCst) > 0
+}
+
+/// alive returns false if the aggregate loop has not finished an iteration for longer than `timeout` seconds.
+///
+/// Before the first iteration finished the loop is still considered alive, readiness covers that case.
+pub fn alive(timeout: i64) -> bool {
+    stalled_for(LAST_PASS.load(Ordering::SeqCst), Utc::now().timestamp()) <= timeout
+}
+
+fn stalled_for(last_pass: i64, now: i64) -> i64 {
+    if last_pass == 0 {
+        return 0;
+    }
+    now - last_pass
+}
+
+#[cfg(test)]
+mod test {
+    use crate::watchdog::stalled_for;
+
+    #[test]
+    fn test_stalled_for() {
+        assert_eq!(stalled_for(0, 100), 0);
+        assert_eq!(stalled_for(90, 100), 10);
+        assert_eq!(stalled_for(100, 100), 0);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::probe;
+use failure::{format_err, Error};
+use log::{error, info, warn};
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use rudr::schematic::scopes::health::HealthScope;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+/// Admission requests larger than this are rejected before they are parsed.
+const MAX_REQUEST_SIZE: usize = 4 * 1024 * 1024;
+
+/// serve runs the admission webhook on `addr` until the process exits. The API server only talks
+/// to webhooks over TLS, `cert` and `key` are the PEM files of the serving certificate.
+///
+/// `/mutate` defaults the probe interval of a HealthScope, `/validate` rejects scopes the
+/// aggregate loop could not probe.
+pub fn serve(addr: &str, cert: &str, key: &str, default_interval: i64) -> Result<(), Error> {
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    acceptor.set_private_key_file(key, SslFiletype::PEM)?;
+    acceptor.set_certificate_chain_file(cert)?;
+    acceptor.check_private_key()?;
+    let acceptor = Arc::new(acceptor.build());
+    let listener = TcpListener::bind(addr)?;
+    info!("Admission webhook is running on {}", addr);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("accept webhook connection err {:?}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        thread::spawn(move || {
+            let res = acceptor
+                .accept(stream)
+                .map_err(|e| format_err!("TLS handshake failed: {}", e))
+                .and_then(|mut stream| handle(&mut stream, default_interval));
+            if let Err(e) = res {
+                error!("serve admission request err {:?}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle<S: Read + Write>(stream: &mut S, default_interval: i64) -> Result<(), Error> {
+    let (path, body) = read_request(stream)?;
+    let (status, body) = match (path.as_str(), serde_json::from_slice(&body)) {
+        ("/mutate", Ok(request)) => ("200 OK", review(request, true, default_interval)),
+        ("/validate", Ok(request)) => ("200 OK", review(request, false, default_interval)),
+        ("/mutate", Err(e)) | ("/validate", Err(e)) => {
+            ("400 Bad Request", json!({"error": e.to_string()}))
+        }
+        _ => ("404 Not Found", json!({})),
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// read_request reads one HTTP/1.1 request and returns its path and body.
+fn read_request<R: Read>(stream: &mut R) -> Result<(String, Vec<u8>), Error> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 8192];
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_REQUEST_SIZE {
+            return Err(format_err!("request headers too large"));
+        }
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(format_err!(
+                "connection closed before the request was complete"
+            ));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let path = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| format_err!("malformed request line"))?
+        .to_string();
+    let length = lines
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) if name.eq_ignore_ascii_case("content-length") => {
+                    value.trim().parse::<usize>().ok()
+                }
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or(0);
+    if length > MAX_REQUEST_SIZE {
+        return Err(format_err!("request body of {} bytes too large", length));
+    }
+    let mut body = buf.split_off(header_end);
+    while body.len() < length {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(format_err!(
+                "connection closed before the body was complete"
+            ));
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    Ok((path, body))
+}
+
+/// review answers an admission.k8s.io/v1beta1 AdmissionReview for a HealthScope. With `mutate`
+/// a scope without probe interval gets `default_interval`, otherwise the scope is validated.
+pub fn review(review: Value, mutate: bool, default_interval: i64) -> Value {
+    let request = &review["request"];
+    let mut response = json!({
+        "uid": request["uid"],
+        "allowed": true,
+    });
+    let spec = request["object"]["spec"].clone();
+    if mutate {
+        if spec.is_object() && spec["probeInterval"].is_null() {
+            let patch = json!([{
+                "op": "add",
+                "path": "/spec/probeInterval",
+                "value": default_interval,
+            }]);
+            response["patchType"] = json!("JSONPatch");
+            response["patch"] = json!(base64::encode(&patch.to_string()));
+        }
+    } else if let Err(e) = validate(spec) {
+        response["allowed"] = json!(false);
+        response["status"] = json!({"code": 422, "message": e.to_string()});
+    }
+    json!({
+        "apiVersion": "admission.k8s.io/v1beta1",
+        "kind": "AdmissionReview",
+        "response": response,
+    })
+}
+
+fn validate(spec: Value) -> Result<(), Error> {
+    let scope: HealthScope = serde_json::from_value(spec)
+        .map_err(|e| format_err!("invalid health scope spec: {}", e))?;
+    for p in scope.probes() {
+        if probe::probe_fn(&p).is_none() {
+            return Err(format_err!(
+                "unknown probe-method {} and probe_endpoint {}",
+                p.probe_method,
+                p.probe_endpoint
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::webhook::{read_request, review};
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_review() {
+        let request = |spec| {
+            json!({
+                "apiVersion": "admission.k8s.io/v1beta1",
+                "kind": "AdmissionReview",
+                "request": {"uid": "1", "object": {"spec": spec}},
+            })
+        };
+        let valid = json!({"probeMethod": "kube-get", "probeEndpoint": ".status"});
+        let resp = review(request(valid.clone()), false, 30);
+        assert_eq!(resp["response"]["uid"], "1");
+        assert_eq!(resp["response"]["allowed"], true);
+
+        let unknown = json!({"probeMethod": "exec", "probeEndpoint": ".status"});
+        let resp = review(request(unknown), false, 30);
+        assert_eq!(resp["response"]["allowed"], false);
+        assert_eq!(
+            resp["response"]["status"]["message"],
+            "unknown probe-method exec and probe_endpoint .status"
+        );
+
+        let bad_fallback = json!({
+            "probeMethod": "kube-get",
+            "probeEndpoint": ".status",
+            "fallbackProbes": [{"probeMethod": "kube-get", "probeEndpoint": ".spec"}],
+        });
+        let resp = review(request(bad_fallback), false, 30);
+        assert_eq!(resp["response"]["allowed"], false);
+
+        let resp = review(request(json!({"probeMethod": "kube-get"})), false, 30);
+        assert_eq!(resp["response"]["allowed"], false);
+
+        let resp = review(request(valid), true, 30);
+        assert_eq!(resp["response"]["allowed"], true);
+        let patch = base64::decode(resp["response"]["patch"].as_str().unwrap()).unwrap();
+        let patch: serde_json::Value = serde_json::from_slice(&patch).unwrap();
+        assert_eq!(patch[0]["path"], "/spec/probeInterval");
+        assert_eq!(patch[0]["value"], 30);
+
+        let with_interval =
+            json!({"probeMethod": "kube-get", "probeEndpoint": ".status", "probeInterval": 5});
+        let resp = review(request(with_interval), true, 30);
+        assert!(resp["response"]["patch"].is_null());
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw = "POST /validate HTTP/1.1\r\nHost: x\r\ncontent-length: 4\r\n\r\n{}{}extra";
+        let (path, body) = read_request(&mut Cursor::new(raw.as_bytes())).unwrap();
+        assert_eq!(path, "/validate");
+        assert_eq!(body, b"{}{}");
+
+        let truncated = "POST /validate HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}";
+        assert!(read_request(&mut Cursor::new(truncated.as_bytes())).is_err());
+    }
+}
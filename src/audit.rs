@@ -0,0 +1,217 @@
+use failure::Error;
+use log::warn;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// How deep into a request body `fields` looks for the fields a change touches.
+const FIELD_DEPTH: usize = 3;
+
+lazy_static! {
+    static ref SINK: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+}
+
+thread_local! {
+    static CAUSE: RefCell<Vec<String>> = RefCell::new(vec![]);
+}
+
+/// configure writes the audit log as JSON lines to the file at `path`, appending to it, or to
+/// standard output for `-`.
+pub fn configure(path: &str) -> Result<(), Error> {
+    let sink: Box<dyn Write + Send> = if path == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format_err!("open audit log {}: {}", path, e))?,
+        )
+    };
+    if let Ok(mut current) = SINK.lock() {
+        *current = Some(sink);
+    }
+    Ok(())
+}
+
+fn enabled() -> bool {
+    SINK.lock().map(|s| s.is_some()).unwrap_or(false)
+}
+
+/// Caused names what the changes the thread makes are made for until it is dropped.
+pub struct Caused {
+    pushed: bool,
+}
+
+/// caused_by attributes the changes of the thread to `cause`, like `configuration default/app`,
+/// until the returned guard is dropped.
+pub fn caused_by(cause: Option<String>) -> Caused {
+    let pushed = cause.is_some();
+    if let Some(cause) = cause {
+        CAUSE.with(|c| c.borrow_mut().push(cause));
+    }
+    Caused { pushed }
+}
+
+impl Drop for Caused {
+    fn drop(&mut self) {
+        if self.pushed {
+            CAUSE.with(|c| c.borrow_mut().pop());
+        }
+    }
+}
+
+/// cause is what the changes of the thread are made for, to hand over to other threads.
+pub fn cause() -> Option<String> {
+    CAUSE.with(|c| c.borrow().last().cloned())
+}
+
+/// Entry is a change rudr sends to the kube API server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub cause: Option<String>,
+    /// `create`, `replace`, `apply`, `patch` or `delete`.
+    pub action: String,
+    pub namespace: Option<String>,
+    pub resource: String,
+    pub name: Option<String>,
+    /// The fields of the object the request sets, a few levels deep.
+    pub fields: Vec<String>,
+}
+
+impl Entry {
+    /// for_request is the entry of a request, None for requests that don't change resources:
+    /// reads, events, leases and status updates.
+    pub fn for_request(
+        method: &str,
+        path: &str,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Option<Entry> {
+        let action = match method {
+            "POST" => "create",
+            "PUT" => "replace",
+            "PATCH" if content_type.map(|c| c.contains("apply-patch")) == Some(true) => "apply",
+            "PATCH" => "patch",
+            "DELETE" => "delete",
+            _ => return None,
+        };
+        let (namespace, rest) = resource_path(path)?;
+        let resource = rest.first()?.to_string();
+        if resource == "events" || resource == "leases" || rest.get(2) == Some(&"status") {
+            return None;
+        }
+        let body: Value = serde_json::from_slice(body).unwrap_or(Value::Null);
+        let name = rest
+            .get(1)
+            .map(|n| n.to_string())
+            .or_else(|| body["metadata"]["name"].as_str().map(String::from));
+        let fields = if action == "delete" {
+            vec![]
+        } else {
+            fields(&body)
+        };
+        Some(Entry {
+            cause: cause(),
+            action: action.to_string(),
+            namespace,
+            resource,
+            name,
+            fields,
+        })
+    }
+
+    /// to_json is the line of the audit log for the entry, with the error of the request if it
+    /// failed.
+    pub fn to_json(&self, time: &str, error: Option<String>) -> Value {
+        json!({
+            "time": time,
+            "cause": self.cause,
+            "action": self.action,
+            "namespace": self.namespace,
+            "resource": self.resource,
+            "name": self.name,
+            "fields": self.fields,
+            "result": if error.is_some() { "error" } else { "success" },
+            "error": error,
+        })
+    }
+}
+
+/// entry is the entry of a request if the audit log is on, see `Entry::for_request`.
+pub fn entry(req: &http::Request<Vec<u8>>) -> Option<Entry> {
+    if !enabled() {
+        return None;
+    }
+    let content_type = req
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    Entry::for_request(
+        req.method().as_str(),
+        req.uri().path(),
+        content_type,
+        req.body(),
+    )
+}
+
+/// record writes `entry` with the `error` of its request to the audit log.
+pub fn record(entry: Option<Entry>, error: Option<String>) {
+    let entry = match entry {
+        Some(entry) => entry,
+        None => return,
+    };
+    let line = entry.to_json(chrono::Utc::now().to_rfc3339().as_str(), error);
+    if let Ok(mut sink) = SINK.lock() {
+        if let Some(sink) = sink.as_mut() {
+            if let Err(e) = writeln!(sink, "{}", line).and_then(|_| sink.flush()) {
+                warn!("write audit log: {}", e);
+            }
+        }
+    }
+}
+
+/// resource_path splits the path of a request into its namespace and the resource, name and
+/// subresource after it.
+fn resource_path(path: &str) -> Option<(Option<String>, Vec<&str>)> {
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let rest = match parts.first() {
+        Some(&"api") if parts.len() >= 2 => &parts[2..],
+        Some(&"apis") if parts.len() >= 3 => &parts[3..],
+        _ => return None,
+    };
+    if rest.len() > 2 && rest[0] == "namespaces" {
+        return Some((Some(rest[1].to_string()), rest[2..].to_vec()));
+    }
+    Some((None, rest.to_vec()))
+}
+
+/// fields lists the fields a body sets, like `spec.replicas`, down to `FIELD_DEPTH` levels.
+pub fn fields(body: &Value) -> Vec<String> {
+    let mut fields = vec![];
+    collect_fields(body, "", FIELD_DEPTH, &mut fields);
+    fields
+}
+
+fn collect_fields(value: &Value, prefix: &str, depth: usize, fields: &mut Vec<String>) {
+    match value.as_object() {
+        Some(object) if depth > 0 && !object.is_empty() => {
+            for (key, value) in object {
+                // The identity of the object is not a change.
+                if prefix.is_empty() && (key == "apiVersion" || key == "kind") {
+                    continue;
+                }
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                collect_fields(value, path.as_str(), depth - 1, fields);
+            }
+        }
+        _ if !prefix.is_empty() => fields.push(prefix.to_string()),
+        _ => {}
+    }
+}
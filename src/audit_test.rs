@@ -0,0 +1,91 @@
+use serde_json::json;
+
+use crate::audit::*;
+
+#[test]
+fn test_entry_for_request() {
+    let body = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": {"name": "web", "labels": {"app": "web"}},
+        "spec": {"replicas": 2, "template": {"spec": {"containers": []}}},
+    })
+    .to_string();
+    let entry = Entry::for_request(
+        "POST",
+        "/apis/apps/v1/namespaces/prod/deployments",
+        Some("application/json"),
+        body.as_bytes(),
+    )
+    .expect("create is audited");
+    assert_eq!("create", entry.action);
+    assert_eq!(Some("prod".to_string()), entry.namespace);
+    assert_eq!("deployments", entry.resource);
+    assert_eq!(Some("web".to_string()), entry.name);
+    assert_eq!(
+        vec![
+            "metadata.labels.app",
+            "metadata.name",
+            "spec.replicas",
+            "spec.template.spec"
+        ],
+        entry.fields
+    );
+
+    let entry = Entry::for_request(
+        "PATCH",
+        "/api/v1/namespaces/prod/services/web",
+        Some("application/apply-patch+yaml"),
+        b"{}",
+    )
+    .expect("apply is audited");
+    assert_eq!("apply", entry.action);
+    assert_eq!(Some("web".to_string()), entry.name);
+
+    let entry = Entry::for_request("DELETE", "/api/v1/namespaces/prod-app", None, b"")
+        .expect("delete is audited");
+    assert_eq!("delete", entry.action);
+    assert_eq!(None, entry.namespace);
+    assert_eq!("namespaces", entry.resource);
+    assert_eq!(Some("prod-app".to_string()), entry.name);
+
+    for (method, path) in &[
+        ("GET", "/apis/apps/v1/namespaces/prod/deployments/web"),
+        ("POST", "/api/v1/namespaces/prod/events"),
+        (
+            "PUT",
+            "/apis/coordination.k8s.io/v1/namespaces/prod/leases/rudr-leader",
+        ),
+        (
+            "PATCH",
+            "/apis/core.oam.dev/v1alpha1/namespaces/prod/applicationconfigurations/app/status",
+        ),
+        ("PATCH", "/version"),
+    ] {
+        assert_eq!(None, Entry::for_request(method, path, None, b"{}"));
+    }
+}
+
+#[test]
+fn test_entry_to_json() {
+    let caused = caused_by(Some("configuration prod/app".to_string()));
+    let entry = Entry::for_request(
+        "DELETE",
+        "/apis/apps/v1/namespaces/prod/deployments/web",
+        None,
+        b"",
+    )
+    .unwrap();
+    let line = entry.to_json("2020-01-01T00:00:00+00:00", Some("not found".to_string()));
+    assert_eq!("configuration prod/app", line["cause"]);
+    assert_eq!("delete", line["action"]);
+    assert_eq!("web", line["name"]);
+    assert_eq!("error", line["result"]);
+    assert_eq!("not found", line["error"]);
+
+    drop(caused);
+    assert_eq!(None, cause());
+    let line = entry.to_json("2020-01-01T00:00:00+00:00", None);
+    assert_eq!("success", line["result"]);
+    assert!(line["error"].is_null());
+}
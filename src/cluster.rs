@@ -0,0 +1,58 @@
+use failure::Error;
+use kube::client::APIClient;
+use log::info;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Clusters are the API clients of the remote clusters components can be placed in, by name.
+pub type Clusters = BTreeMap<String, APIClient>;
+
+const KUBECONFIG: &str = "KUBECONFIG";
+
+/// cluster_files returns the kubeconfig of every cluster in `dir`, by cluster name. Every file in
+/// the directory is the kubeconfig of the cluster it is named after, the way a Secret with one
+/// key per cluster is mounted. Hidden files, like the `..data` link of a mounted Secret, are
+/// skipped.
+pub fn cluster_files(dir: &Path) -> Result<BTreeMap<String, PathBuf>, Error> {
+    let mut files = BTreeMap::new();
+    for entry in std::fs::read_dir(dir)
+        .map_err(|e| format_err!("read clusters config {}: {}", dir.display(), e))?
+    {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if !name.starts_with('.') => name.to_string(),
+            _ => continue,
+        };
+        if path.is_file() {
+            files.insert(name, path);
+        }
+    }
+    Ok(files)
+}
+
+/// load_clusters creates a client for every cluster in `dir`, see `cluster_files`. The
+/// kubeconfig loader only reads the file `KUBECONFIG` names, so this sets the variable while it
+/// loads and has to run before other threads read the environment.
+pub fn load_clusters(dir: &Path) -> Result<Clusters, Error> {
+    let previous = std::env::var_os(KUBECONFIG);
+    let mut clusters = Clusters::new();
+    let mut res = Ok(());
+    for (name, path) in cluster_files(dir)? {
+        std::env::set_var(KUBECONFIG, &path);
+        match kube::config::load_kube_config() {
+            Ok(config) => {
+                info!("cluster {}: apiserver {}", name, config.base_path);
+                clusters.insert(name, APIClient::new(config));
+            }
+            Err(e) => {
+                res = Err(format_err!("load kubeconfig of cluster {}: {}", name, e));
+                break;
+            }
+        }
+    }
+    match previous {
+        Some(previous) => std::env::set_var(KUBECONFIG, previous),
+        None => std::env::remove_var(KUBECONFIG),
+    }
+    res.map(|_| clusters)
+}
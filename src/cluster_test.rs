@@ -0,0 +1,27 @@
+use crate::cluster::*;
+
+#[test]
+fn test_cluster_files() {
+    let dir = std::env::temp_dir().join(format!("rudr-clusters-{}", std::process::id()));
+    // The layout of a mounted Secret: the keys link into a hidden directory of the data.
+    std::fs::create_dir_all(dir.join("..2019_10_01")).expect("create clusters dir");
+    for file in &[
+        "..2019_10_01/us-east",
+        "..2019_10_01/eu-west",
+        "us-east",
+        "eu-west",
+    ] {
+        std::fs::write(dir.join(file), "apiVersion: v1").expect("write kubeconfig");
+    }
+    std::fs::write(dir.join(".hidden"), "").expect("write hidden file");
+
+    let files = cluster_files(&dir).expect("cluster files");
+    std::fs::remove_dir_all(&dir).expect("remove clusters dir");
+    assert_eq!(
+        vec!["eu-west", "us-east"],
+        files.keys().map(String::as_str).collect::<Vec<&str>>()
+    );
+    assert_eq!(Some(&dir.join("us-east")), files.get("us-east"));
+
+    assert!(cluster_files(&dir).is_err());
+}
@@ -0,0 +1,131 @@
+use failure::Error;
+use kube::api::{PatchParams, PostParams, RawApi};
+use kube::client::APIClient;
+use log::info;
+use serde_json::Value;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::instigator::{COMPONENT_CRD, CONFIG_CRD, CONFIG_GROUP, SCOPE_CRD, TRAIT_CRD};
+use crate::metrics::TimedRequest;
+
+/// The custom resources rudr reads or writes, it can't reconcile anything without them.
+pub const REQUIRED: &[&str] = &[
+    CONFIG_CRD,
+    COMPONENT_CRD,
+    TRAIT_CRD,
+    SCOPE_CRD,
+    "componentinstances",
+];
+
+/// The CRD manifests of the chart, which `install` applies.
+const MANIFESTS: &[&str] = &[
+    include_str!("../charts/rudr/crds/appconfigs.yaml"),
+    include_str!("../charts/rudr/crds/componentinstances.yaml"),
+    include_str!("../charts/rudr/crds/componentschematics.yaml"),
+    include_str!("../charts/rudr/crds/healthscope.yaml"),
+    include_str!("../charts/rudr/crds/scopes.yaml"),
+    include_str!("../charts/rudr/crds/traitdefinitions.yaml"),
+    include_str!("../charts/rudr/crds/traits.yaml"),
+    include_str!("../charts/rudr/crds/workloadtypes.yaml"),
+];
+
+/// crd_name is the name of the CRD of the custom resource `plural` of rudr.
+pub fn crd_name(plural: &str) -> String {
+    format!("{}.{}", plural, CONFIG_GROUP)
+}
+
+/// established is true once the API server serves the resources of the CRD `crd`.
+pub fn established(crd: &Value) -> bool {
+    crd["status"]["conditions"]
+        .as_array()
+        .map(|conditions| {
+            conditions
+                .iter()
+                .any(|c| c["type"] == "Established" && c["status"] == "True")
+        })
+        .unwrap_or(false)
+}
+
+/// not_established returns the required CRDs that are missing or not established yet.
+pub fn not_established(client: &APIClient) -> Result<Vec<String>, Error> {
+    let mut pending = vec![];
+    for plural in REQUIRED {
+        let name = crd_name(plural);
+        let req = RawApi::v1beta1CustomResourceDefinition().get(name.as_str())?;
+        match client.timed_request::<Value>(req) {
+            Ok(crd) if established(&crd) => {}
+            Ok(_) => pending.push(name),
+            Err(e) => match e.api_error() {
+                Some(ref err) if err.code == 404 => pending.push(name),
+                _ => return Err(e.into()),
+            },
+        }
+    }
+    Ok(pending)
+}
+
+/// wait_established waits until all the required CRDs are established, polling every `interval`.
+/// It gives up after `timeout`, or never without one.
+pub fn wait_established(
+    client: &APIClient,
+    timeout: Option<Duration>,
+    interval: Duration,
+) -> Result<(), Error> {
+    let start = Instant::now();
+    loop {
+        let pending = match not_established(client) {
+            Ok(pending) if pending.is_empty() => return Ok(()),
+            Ok(pending) => pending.join(", "),
+            // The API server may not be reachable yet either.
+            Err(e) => format!("unknown, API server error: {}", e),
+        };
+        if let Some(timeout) = timeout.filter(|t| start.elapsed() >= *t) {
+            return Err(format_err!(
+                "CRDs not established within {:?}: {}. Install them with the rudr chart or --install-crds",
+                timeout,
+                pending
+            ));
+        }
+        info!("waiting for CRDs to be established: {}", pending);
+        thread::sleep(interval);
+    }
+}
+
+/// manifests returns the CRDs of the chart as JSON objects.
+pub fn manifests() -> Result<Vec<Value>, Error> {
+    MANIFESTS
+        .iter()
+        .map(|m| Ok(serde_yaml::from_str::<Value>(m)?))
+        .collect()
+}
+
+/// install creates the CRDs of the chart, or updates the ones that are there already.
+pub fn install(client: &APIClient) -> Result<(), Error> {
+    let resource = RawApi::v1beta1CustomResourceDefinition();
+    for crd in manifests()? {
+        let name = crd["metadata"]["name"]
+            .as_str()
+            .ok_or_else(|| format_err!("CRD manifest without a name"))?
+            .to_string();
+        let req = resource.create(&PostParams::default(), serde_json::to_vec(&crd)?)?;
+        match client.timed_request::<Value>(req) {
+            Ok(_) => info!("created CRD {}", name),
+            Err(e) => match e.api_error() {
+                Some(ref err) if err.code == 409 => {
+                    let req = resource.patch(
+                        name.as_str(),
+                        &PatchParams::default(),
+                        serde_json::to_vec(&crd)?,
+                    )?;
+                    client
+                        .timed_request::<Value>(req)
+                        .map_err(|e| format_err!("update CRD {}: {}", name, e))?;
+                    info!("updated CRD {}", name);
+                }
+                _ => return Err(format_err!("create CRD {}: {}", name, e)),
+            },
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use serde_json::json;
+
+use crate::crds::*;
+
+#[test]
+fn test_established() {
+    assert!(established(&json!({"status": {"conditions": [
+        {"type": "NamesAccepted", "status": "True"},
+        {"type": "Established", "status": "True"},
+    ]}})));
+    assert!(!established(&json!({"status": {"conditions": [
+        {"type": "NamesAccepted", "status": "True"},
+        {"type": "Established", "status": "False"},
+    ]}})));
+    assert!(!established(
+        &json!({"metadata": {"name": "traits.core.oam.dev"}})
+    ));
+}
+
+#[test]
+fn test_manifests() {
+    let manifests = manifests().expect("parse CRD manifests");
+    // install creates every CRD rudr needs.
+    for plural in REQUIRED {
+        assert!(
+            manifests
+                .iter()
+                .any(|m| m["metadata"]["name"] == crd_name(plural).as_str()),
+            "no manifest for {}",
+            plural
+        );
+    }
+    assert!(manifests
+        .iter()
+        .all(|m| m["kind"] == "CustomResourceDefinition"));
+}
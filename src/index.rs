@@ -0,0 +1,69 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::schematic::configuration::ApplicationConfiguration;
+
+/// SchematicIndex maps the ComponentSchematics of a namespace to the ApplicationConfigurations
+/// whose components use them, so that a changed schematic can be rolled out to its
+/// configurations.
+#[derive(Debug, Default)]
+pub struct SchematicIndex {
+    /// Configurations by namespace and schematic
+    configurations: BTreeMap<(String, String), BTreeSet<String>>,
+    /// Schematics by namespace and configuration
+    schematics: BTreeMap<(String, String), BTreeSet<String>>,
+}
+
+impl SchematicIndex {
+    /// update records the schematics configuration `config` of `namespace` uses now, replacing
+    /// those it used before.
+    pub fn update(&mut self, namespace: &str, config: &str, spec: &ApplicationConfiguration) {
+        self.remove(namespace, config);
+        let schematics = referenced_schematics(spec);
+        for schematic in schematics.iter() {
+            self.configurations
+                .entry((namespace.to_string(), schematic.clone()))
+                .or_default()
+                .insert(config.to_string());
+        }
+        if !schematics.is_empty() {
+            self.schematics
+                .insert((namespace.to_string(), config.to_string()), schematics);
+        }
+    }
+
+    /// remove forgets configuration `config` of `namespace`.
+    pub fn remove(&mut self, namespace: &str, config: &str) {
+        let key = (namespace.to_string(), config.to_string());
+        for schematic in self.schematics.remove(&key).unwrap_or_default() {
+            let key = (namespace.to_string(), schematic);
+            let now_empty = match self.configurations.get_mut(&key) {
+                Some(configs) => {
+                    configs.remove(config);
+                    configs.is_empty()
+                }
+                None => false,
+            };
+            if now_empty {
+                self.configurations.remove(&key);
+            }
+        }
+    }
+
+    /// configurations are the names of the configurations of `namespace` that use `schematic`.
+    pub fn configurations(&self, namespace: &str, schematic: &str) -> Vec<String> {
+        self.configurations
+            .get(&(namespace.to_string(), schematic.to_string()))
+            .map(|configs| configs.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// referenced_schematics are the names of the ComponentSchematics the components of a
+/// configuration are instantiated from.
+pub fn referenced_schematics(spec: &ApplicationConfiguration) -> BTreeSet<String> {
+    spec.components
+        .iter()
+        .flatten()
+        .map(|c| c.component_name.clone())
+        .collect()
+}
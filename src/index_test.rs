@@ -0,0 +1,48 @@
+use crate::index::*;
+use crate::schematic::configuration::ApplicationConfiguration;
+
+fn config(schematics: &[&str]) -> ApplicationConfiguration {
+    let components: Vec<serde_json::Value> = schematics
+        .iter()
+        .map(|s| serde_json::json!({"componentName": s, "instanceName": format!("{}-1", s)}))
+        .collect();
+    serde_json::from_value(serde_json::json!({ "components": components }))
+        .expect("configuration must parse")
+}
+
+#[test]
+fn test_schematic_index() {
+    let mut index = SchematicIndex::default();
+    index.update("default", "first-app", &config(&["web", "db"]));
+    index.update("default", "second-app", &config(&["web"]));
+    index.update("staging", "first-app", &config(&["web"]));
+
+    assert_eq!(
+        vec!["first-app".to_string(), "second-app".to_string()],
+        index.configurations("default", "web")
+    );
+    assert_eq!(
+        vec!["first-app".to_string()],
+        index.configurations("default", "db")
+    );
+    assert!(index.configurations("default", "cache").is_empty());
+
+    // An update replaces the schematics used before.
+    index.update("default", "first-app", &config(&["cache"]));
+    assert!(index.configurations("default", "db").is_empty());
+    assert_eq!(
+        vec!["second-app".to_string()],
+        index.configurations("default", "web")
+    );
+    assert_eq!(
+        vec!["first-app".to_string()],
+        index.configurations("default", "cache")
+    );
+
+    index.remove("default", "second-app");
+    assert!(index.configurations("default", "web").is_empty());
+    assert_eq!(
+        vec!["first-app".to_string()],
+        index.configurations("staging", "web")
+    );
+}
@@ -1,30 +1,47 @@
 use failure::Error;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
-use kube::{api::Api, api::Object, api::PatchParams, api::RawApi, api::Void, client::APIClient};
+use kube::{api::Object, api::PatchParams, api::RawApi, api::Void, client::APIClient};
 use log::{debug, error, info, warn};
 use serde_json::json;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use k8s_openapi::api::core::v1::ObjectReference;
+use k8s_openapi::api::batch::v1 as batch;
+use k8s_openapi::api::core::v1::{ConfigMap, ObjectReference};
 
+use crate::audit;
+use crate::cluster::Clusters;
+use crate::metrics::{self, TimedRequest};
+use crate::mutator;
+use crate::rendering::Renderings;
+use crate::retry::{on_conflict, CONFLICT_ATTEMPTS};
+use crate::revision::{History, ROLLBACK_ANNOTATION};
 use crate::schematic::variable::Variable;
+use crate::trace;
 use crate::{
     kube_event,
     lifecycle::Phase,
     schematic::{
         component::Component,
-        component_instance::KubeComponentInstance,
-        configuration::{ApplicationConfiguration, ComponentConfiguration, ScopeBinding},
-        parameter::{resolve_parameters, resolve_values, ParameterValue},
+        component_instance::{ComponentInstanceStatus, InstanceStatus, KubeComponentInstance},
+        configuration::{
+            ApplicationConfiguration, ComponentConfiguration, Hook, HookType, ScopeBinding,
+        },
+        parameter::{resolve_parameters, resolve_values, secret_value, ParameterValue},
         scopes::{self, Health, Network, OAMScope},
+        trait_definition::{trait_definitions, TraitDefinition},
+        traits::{self, TraitBinding},
         variable::{get_variable_values, resolve_variables},
-        OAMStatus,
+        ComponentState, OAMStatus, COMPONENT_FAILED, COMPONENT_RUNNING,
     },
     trait_manager::TraitManager,
     workload_type::{
-        self, CoreWorkloadType, ExtendedWorkloadType, ReplicatedServer, ReplicatedTask,
-        ReplicatedWorker, SingletonServer, SingletonTask, SingletonWorker, WorkloadMetadata,
-        WorkloadType, OAM_API_VERSION,
+        self, extended_workload::others::form_plural, CoreWorkloadType, ExtendedWorkloadType,
+        Propagated, ReplicatedServer, ReplicatedTask, ReplicatedWorker, SingletonServer,
+        SingletonTask, SingletonWorker, WorkloadMetadata, WorkloadType, OAM_API_VERSION,
     },
 };
 
@@ -36,6 +53,45 @@ pub const COMPONENT_CRD: &str = "componentschematics";
 pub const TRAIT_CRD: &str = "traits";
 pub const SCOPE_CRD: &str = "applicationscopes";
 pub const COMPONENT_RECORD_ANNOTATION: &str = "component_record_annotation";
+/// The finalizer that keeps a deleted configuration around until its resources are cleaned up.
+pub const CONFIG_FINALIZER: &str = "applicationconfiguration.core.oam.dev/cleanup";
+/// A configuration with this annotation set to "true" is rendered instead of applied.
+pub const DRY_RUN_ANNOTATION: &str = "core.oam.dev/dry-run";
+/// A configuration with this annotation set to "true" is not reconciled until it is removed.
+pub const PAUSED_ANNOTATION: &str = "core.oam.dev/paused";
+/// The phase of a paused configuration.
+pub const PAUSED_PHASE: &str = "paused";
+/// A generated resource with this annotation set to "true" is left alone by drift detection.
+pub const IGNORE_DRIFT_ANNOTATION: &str = "core.oam.dev/ignore-drift";
+
+/// The label with the instance name of the component on the Jobs of its hooks.
+pub const HOOK_INSTANCE_LABEL: &str = "oam.dev/hook-instance-name";
+/// The label with the name of the hook on its Jobs.
+pub const HOOK_NAME_LABEL: &str = "oam.dev/hook-name";
+/// The label with the instance name of the component on its ServiceAccount and RoleBindings.
+pub const SERVICE_ACCOUNT_INSTANCE_LABEL: &str = "core.oam.dev/service-account-instance";
+/// The label with the name of the configuration on the namespace created for it.
+pub const APP_CONFIG_LABEL: &str = "core.oam.dev/application-configuration";
+/// The label with the namespace of the configuration on the namespace created for it.
+pub const APP_CONFIG_NAMESPACE_LABEL: &str = "core.oam.dev/configuration-namespace";
+/// The label with the uid of the configuration on its component instances.
+pub const CONFIG_UID_LABEL: &str = "core.oam.dev/configuration-uid";
+/// Seconds a hook may run if it doesn't set a timeout.
+const DEFAULT_HOOK_TIMEOUT: u64 = 300;
+const HOOK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// The traits that set the replicas of a workload, of which a component takes one at most.
+const SCALER_TRAITS: &[&str] = &[
+    traits::MANUAL_SCALER_V1ALPHA1,
+    traits::AUTOSCALER_V1ALPHA1,
+    traits::KEDA_AUTOSCALER_V1ALPHA1,
+];
+/// The traits a component may take more than once, which add to its pods. Every other trait
+/// creates objects named after the component instance, which a second one would overwrite.
+const REPEATABLE_TRAITS: &[&str] = &[
+    traits::SIDECAR_V1ALPHA1,
+    traits::INIT_CONTAINER_V1ALPHA1,
+    traits::VOLUME_MOUNTER_V1ALPHA1,
+];
 
 /// Type alias for the results that all instantiation operations return
 pub type InstigatorResult = Result<(), Error>;
@@ -75,8 +131,26 @@ pub struct Instigator {
     //cache: Reflector<Component, Status>,
     namespace: String,
     pub event_handler: kube_event::Event,
+    /// How many components are instantiated at the same time.
+    concurrency: usize,
+    /// Whether every configuration gets a namespace of its own for its components.
+    namespace_per_app: bool,
+    /// The namespace the components of the configuration at hand are created in, if it isn't
+    /// the namespace of the configuration.
+    app_namespace: Option<String>,
+    /// How long an applied component may take to become running before it is reported failed.
+    progress_deadline: Option<Duration>,
+    /// How long applying a component with its traits and hooks may take before it fails.
+    component_timeout: Option<Duration>,
+    /// The clients of the remote clusters components can be placed in, by name.
+    clusters: Arc<Clusters>,
+    /// Set once the component this instigator applies timed out, see `instantiate_in_time`.
+    cancelled: Arc<AtomicBool>,
 }
 
+/// The number of components an instigator instantiates at the same time by default.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
 /// Alias for a Kubernetes wrapper on a component.
 type KubeComponent = Object<Component, Void>;
 
@@ -97,15 +171,205 @@ impl Instigator {
             client: client.clone(),
             namespace: namespace.clone(),
             event_handler: kube_event::Event::new(client, namespace),
+            concurrency: DEFAULT_CONCURRENCY,
+            namespace_per_app: false,
+            app_namespace: None,
+            progress_deadline: None,
+            component_timeout: None,
+            clusters: Arc::new(Clusters::new()),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// with_concurrency sets how many independent components are instantiated at the same time.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// with_namespace_per_app creates the components of every configuration in a namespace of
+    /// its own, named by `app_namespace`, which is deleted together with the configuration.
+    pub fn with_namespace_per_app(mut self, enabled: bool) -> Self {
+        self.namespace_per_app = enabled;
+        self
+    }
+
+    /// with_progress_deadline reports components failed that aren't running `deadline` after they
+    /// were applied. Without a deadline they are pending until they run.
+    pub fn with_progress_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.progress_deadline = deadline;
+        self
+    }
+
+    /// with_component_timeout fails components that take longer than `timeout` to apply, so that
+    /// a hung request doesn't hold up the other components. Without a timeout they may take
+    /// forever.
+    pub fn with_component_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.component_timeout = timeout;
+        self
+    }
+
+    /// with_clusters sets the remote clusters components can be placed in with `cluster`.
+    pub fn with_clusters(mut self, clusters: Arc<Clusters>) -> Self {
+        self.clusters = clusters;
+        self
+    }
+
+    /// cluster_client returns the client of the cluster the workload and traits of `component`
+    /// are created in.
+    fn cluster_client(&self, component: &ComponentConfiguration) -> Result<APIClient, Error> {
+        match component.cluster.as_ref() {
+            None => Ok(self.client.clone()),
+            Some(cluster) => self.clusters.get(cluster).cloned().ok_or_else(|| {
+                format_err!(
+                    "component {} is placed in unknown cluster {}",
+                    component.instance_name,
+                    cluster
+                )
+            }),
+        }
+    }
+
+    /// trait_definitions returns the TraitDefinitions of the traits of `component` that aren't
+    /// built in, which live next to the configurations. There's no request for built-in ones.
+    fn trait_definitions(
+        &self,
+        component: &ComponentConfiguration,
+    ) -> Result<BTreeMap<String, TraitDefinition>, Error> {
+        let defined = component
+            .traits
+            .iter()
+            .flatten()
+            .any(|t| !traits::BUILTIN.contains(&t.name.as_str()));
+        if !defined {
+            return Ok(BTreeMap::new());
+        }
+        trait_definitions(&self.client, self.namespace.as_str())
+    }
+
+    /// for_app returns the instigator that handles `event` in its own namespace, if every
+    /// configuration gets one and this instigator doesn't handle one already. Scope-only
+    /// configurations live next to the configuration.
+    fn for_app(&self, event: &OpResource) -> Option<Instigator> {
+        if !self.namespace_per_app || self.app_namespace.is_some() || event.spec.scopes.is_some() {
+            return None;
+        }
+        let mut inst = self.clone();
+        inst.app_namespace = Some(app_namespace(
+            self.namespace.as_str(),
+            event.metadata.name.as_str(),
+        ));
+        Some(inst)
+    }
+
+    /// workload_namespace is the namespace the workloads, traits and component instances are
+    /// created in.
+    fn workload_namespace(&self) -> &str {
+        self.app_namespace
+            .as_ref()
+            .map_or(self.namespace.as_str(), String::as_str)
+    }
+
+    /// ensure_app_namespace creates the namespace of the configuration, unless it is there. A
+    /// namespace of the same name that wasn't created for the configuration is never taken over.
+    fn ensure_app_namespace(&self, event: &OpResource) -> InstigatorResult {
+        let name = match self.app_namespace.as_ref() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        if name.len() > 63 {
+            return Err(format_err!(
+                "namespace {} of configuration {} is longer than 63 characters",
+                name,
+                event.metadata.name
+            ));
+        }
+        let resource = RawApi::v1Namespace();
+        match self
+            .client
+            .timed_request::<Object<serde_json::Value, Void>>(resource.get(name)?)
+        {
+            Ok(ns) if owns_namespace(&ns.metadata.labels, self.namespace.as_str(), event) => {
+                return Ok(())
+            }
+            Ok(_) => {
+                return Err(format_err!(
+                    "namespace {} exists and doesn't belong to configuration {}",
+                    name,
+                    event.metadata.name
+                ))
+            }
+            Err(e) if e.api_error().map(|err| err.code) == Some(404) => {}
+            Err(e) => return Err(e.into()),
+        }
+        info!(
+            "MainControlLoop: creating namespace {} for {}",
+            name, event.metadata.name
+        );
+        let namespace = json!({
+            "apiVersion": "v1",
+            "kind": "Namespace",
+            "metadata": {
+                "name": name,
+                "labels": {
+                    APP_CONFIG_LABEL: event.metadata.name,
+                    APP_CONFIG_NAMESPACE_LABEL: self.namespace,
+                },
+            },
+        });
+        workload_type::apply(&self.client, &resource, name, &namespace)
+    }
+
+    /// delete_app_namespace deletes the namespace of the configuration with everything left in
+    /// it, if it was created for the configuration.
+    fn delete_app_namespace(&self, event: &OpResource) -> InstigatorResult {
+        let name = match self.app_namespace.as_ref() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let resource = RawApi::v1Namespace();
+        match self
+            .client
+            .timed_request::<Object<serde_json::Value, Void>>(resource.get(name)?)
+        {
+            Ok(ns) if owns_namespace(&ns.metadata.labels, self.namespace.as_str(), event) => {}
+            Ok(_) => return Ok(()),
+            Err(e) if e.api_error().map(|err| err.code) == Some(404) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        info!(
+            "MainControlLoop: deleting namespace {} of {}",
+            name, event.metadata.name
+        );
+        let req = resource.delete(name, &kube::api::DeleteParams::default())?;
+        match self.client.timed_request::<serde_json::Value>(req) {
+            Err(e) if e.api_error().map(|err| err.code) != Some(404) => Err(e.into()),
+            _ => Ok(()),
         }
     }
 
     pub fn sync_status(&self, event: OpResource) -> InstigatorResult {
+        if is_paused(&event) {
+            return self.pause(event);
+        }
+        if let Some(inst) = self.for_app(&event) {
+            return inst.sync_status(event);
+        }
         let mut component_status = BTreeMap::new();
+        let mut component_states = BTreeMap::new();
         let name = event.metadata.name.clone();
         let record_ann = event.metadata.annotations.get(COMPONENT_RECORD_ANNOTATION);
         let mut last_components = get_record_annotation(record_ann)?;
         let mut has_diff = false;
+        let mut waiting = false;
+        let current_states = event
+            .status
+            .as_ref()
+            .and_then(|s| s.component_states.clone())
+            .unwrap_or_default();
+        let now = chrono::Utc::now();
+        // Components that failed to run since they were applied, with the reason.
+        let mut failed = vec![];
         for component in event.clone().spec.components.unwrap_or_else(|| vec![]) {
             let comp_def: KubeComponent = get_component_def(
                 self.namespace.clone(),
@@ -124,22 +388,17 @@ impl Instigator {
             // we can't update status when there is update not finished yet.
             if check_diff(record.clone(), new_record) {
                 has_diff = true;
+                waiting |= current_states
+                    .get(component.instance_name.as_str())
+                    .map(ComponentState::is_waiting)
+                    == Some(true);
                 continue;
             }
             // Resolve variables/parameters
             let variables = event.spec.variables.clone().unwrap_or_else(|| vec![]);
             let parent = get_variable_values(Some(variables.clone()));
 
-            let child = component
-                .parameter_values
-                .clone()
-                .map(|values| resolve_variables(values, variables))
-                .unwrap_or_else(|| Ok(vec![]))?;
-
-            let params = resolve_parameters(
-                comp_def.spec.parameters.clone(),
-                resolve_values(child, vec![])?,
-            )?;
+            let params = self.component_params(&event, &component, &comp_def)?;
 
             let inst_name = component.instance_name.clone();
             let owner_ref = self.component_instance_owner_reference(
@@ -149,13 +408,13 @@ impl Instigator {
             let new_owner_ref = Some(owner_ref);
 
             let workload_meta = self.get_workload_meta(
-                name.clone(),
-                inst_name.clone(),
+                &event,
+                &component,
                 &comp_def,
                 &params,
                 new_owner_ref.clone(),
                 "StatusCheckLoop".to_string(),
-            );
+            )?;
             // Instantiate components
             let workload = self.load_workload_type(&comp_def, workload_meta)?;
             let mut status = workload.status()?;
@@ -164,18 +423,11 @@ impl Instigator {
                 component.component_name.clone(),
                 status.clone()
             );
-            let mut health_state = "healthy".to_string();
-            for (_, v) in status.clone() {
-                if v != "running" && v != "created" && v != "succeeded" {
-                    health_state = "unhealthy".to_string();
-                    break;
-                }
-            }
-            self.component_instance_set_status(
-                component.component_name.clone(),
-                inst_name.clone(),
-                health_state,
-            )?;
+            let previous = current_states.get(inst_name.as_str());
+            let state =
+                ComponentState::from_workload(component.component_name.clone(), &status, None)
+                    .progress(previous, now, self.progress_deadline);
+            let client = self.cluster_client(&component)?;
             // Load all of the traits related to this component.
             let mut trait_manager = TraitManager {
                 config_name: name.clone(),
@@ -186,17 +438,48 @@ impl Instigator {
                 workload_type: comp_def.spec.workload_type.clone(),
                 traits: vec![], // Always starts empty.
                 component_schematic: comp_def.spec.clone(),
+                definitions: self.trait_definitions(&component)?,
             };
             trait_manager.load_traits()?;
-            if let Some(trait_status) =
-                trait_manager.status(self.namespace.as_str(), self.client.clone())
-            {
+            let trait_status = trait_manager.status(self.workload_namespace(), client.clone());
+            let mut instance_status = self.observe_instance(
+                &client,
+                workload.as_ref(),
+                inst_name.as_str(),
+                &state,
+                &status,
+            );
+            instance_status.traits = trait_status.clone().unwrap_or_default();
+            self.component_instance_set_status(
+                component.component_name.clone(),
+                inst_name.clone(),
+                instance_status,
+            )?;
+            let state = state.with_traits(trait_status.clone());
+            if state.phase == COMPONENT_FAILED && state.progressing_since.is_some() {
+                failed.push(format!(
+                    "{}: {}",
+                    inst_name,
+                    state.message.clone().unwrap_or_default()
+                ));
+            }
+            component_states.insert(inst_name.clone(), state);
+            if let Some(trait_status) = trait_status {
                 for (key, state) in trait_status {
                     status.insert(key, state);
                 }
             };
             component_status.insert(component.component_name.clone(), status.clone());
         }
+        // Components waiting for their dependencies are only created by the MainControlLoop once
+        // the dependencies are running, so give it another go.
+        if waiting {
+            info!(
+                "StatusCheckLoop: {} has components waiting for their dependencies, retry them",
+                event.metadata.name.clone()
+            );
+            return self.modify(event);
+        }
         //we won't update status if there's any update
         if has_diff || !last_components.is_empty() {
             info!(
@@ -205,17 +488,67 @@ impl Instigator {
             );
             return Ok(());
         }
+        let status = OAMStatus {
+            observed_generation: observed_generation(&event),
+            component_states: Some(component_states),
+            message: if failed.is_empty() {
+                None
+            } else {
+                Some(failed.join("; "))
+            },
+            ..OAMStatus::new(Some("synced".to_string()), Some(component_status))
+        };
         self.retry_patch_status(
             event.clone(),
-            Some(OAMStatus::new(
-                Some("synced".to_string()),
-                Some(component_status),
-            )),
+            Some(status),
             None,
             "StatusCheckLoop".to_string(),
         )
     }
 
+    /// pause reports a configuration annotated with PAUSED_ANNOTATION as paused, without touching
+    /// its resources. Manual changes to them are left alone until the annotation is removed, and
+    /// the next reconcile applies the configuration again.
+    pub fn pause(&self, event: OpResource) -> InstigatorResult {
+        let mut status = event.status.clone().unwrap_or_default();
+        if status.phase.as_deref() == Some(PAUSED_PHASE) {
+            return Ok(());
+        }
+        info!("MainControlLoop: {} is paused", event.metadata.name);
+        status.phase = Some(PAUSED_PHASE.to_string());
+        status.message = Some(format!(
+            "reconciliation paused by the {} annotation",
+            PAUSED_ANNOTATION
+        ));
+        if let Err(err) = self.event_handler.push_event_message(
+            kube_event::Type::Normal,
+            kube_event::Info {
+                action: "paused".to_string(),
+                message: format!("configuration {} paused", event.metadata.name),
+                reason: "Paused".to_string(),
+            },
+            get_object_ref(event.clone()),
+        ) {
+            error!("MainControlLoop: adding event err {:?}", err)
+        }
+        self.retry_patch_status(event, Some(status), None, "MainControlLoop".to_string())
+    }
+
+    /// record_failure writes the error of the last reconcile of the configuration into its status,
+    /// so it shows up in `kubectl get configurations`.
+    pub fn record_failure(&self, event: &OpResource, err: &Error) -> InstigatorResult {
+        let mut status = event.status.clone().unwrap_or_default();
+        status.phase = Some("failed".to_string());
+        status.message = Some(err.to_string());
+        status.observed_generation = observed_generation(event);
+        self.retry_patch_status(
+            event.clone(),
+            Some(status),
+            None,
+            "MainControlLoop".to_string(),
+        )
+    }
+
     pub fn retry_patch_status(
         &self,
         event: OpResource,
@@ -223,51 +556,49 @@ impl Instigator {
         annotation: Option<BTreeMap<String, String>>,
         controlled_by: String,
     ) -> InstigatorResult {
-        let config_resource: Api<Object<ApplicationConfiguration, OAMStatus>> =
-            Api::customResource(self.client.clone(), CONFIG_CRD)
-                .version(CONFIG_VERSION)
-                .group(CONFIG_GROUP)
-                .within(&self.namespace);
-        let mut new_event = event.clone();
-        loop {
+        let config_resource = RawApi::customResource(CONFIG_CRD)
+            .version(CONFIG_VERSION)
+            .group(CONFIG_GROUP)
+            .within(&self.namespace);
+        let name = event.metadata.name.clone();
+        let patch_params = PatchParams::default();
+        let mut current = event;
+        on_conflict(CONFLICT_ATTEMPTS, |attempt| {
+            if attempt > 0 {
+                warn!("{}: conflict happen to {}, retry", controlled_by, name);
+                current = self.client.timed_request(config_resource.get(&name)?)?;
+            }
+            let mut new_event = current.clone();
             new_event.status = status.clone();
-            if let Some(newann) = annotation.clone() {
-                new_event.metadata.annotations = newann;
+            if let Some(newann) = annotation.as_ref() {
+                // Annotations added since the configuration was read are kept.
+                new_event.metadata.annotations.extend(newann.clone());
+                // The status is a subresource, so the main resource only takes the annotations.
+                let req =
+                    config_resource.patch(&name, &patch_params, serde_json::to_vec(&new_event)?)?;
+                let o: OpResource = self.client.timed_request(req)?;
+                new_event.metadata.resourceVersion = o.metadata.resourceVersion;
             }
-            let patch_params = PatchParams::default();
-            match config_resource.patch(
-                &event.metadata.name,
+            let req = config_resource.patch_status(
+                &name,
                 &patch_params,
                 serde_json::to_vec(&new_event)?,
-            ) {
-                Ok(o) => {
-                    debug!(
-                        "{}: Patched status {:?} for {}",
-                        controlled_by, o.status, o.metadata.name
-                    );
-                    return Ok(());
-                }
-                Err(e) => {
-                    if let Some(err) = e.api_error() {
-                        if err.reason == "Conflict" {
-                            warn!(
-                                "{}: conflict happen to {}, retry",
-                                controlled_by,
-                                event.metadata.name.clone()
-                            );
-                            new_event = config_resource.get(&event.metadata.name)?;
-                            continue;
-                        }
-                    }
-                    return Err(e.into());
-                }
-            }
-        }
+            )?;
+            let o: OpResource = self.client.timed_request(req)?;
+            debug!(
+                "{}: Patched status {:?} for {}",
+                controlled_by, o.status, o.metadata.name
+            );
+            Ok(())
+        })
     }
 
     /// The workhorse for Instigator.
     /// This will execute only Add, Modify, and Delete phases.
     fn exec(&self, event: OpResource, mut phase: Phase) -> InstigatorResult {
+        if let Some(inst) = self.for_app(&event) {
+            return inst.exec(event, phase);
+        }
         let name = event.metadata.name.clone();
         let variables = event.spec.variables.clone().unwrap_or_else(|| vec![]);
         let owner_ref = config_owner_reference(name.clone(), event.metadata.uid.clone())?;
@@ -282,17 +613,17 @@ impl Instigator {
             match phase {
                 Phase::Add => {
                     for sc in scopes.iter() {
-                        sc.create(owner_ref.clone())?;
+                        sc.create(owner_ref.clone()).map_err(scope_error)?;
                     }
                 }
                 Phase::Modify => {
                     for sc in scopes.iter() {
-                        sc.modify()?;
+                        sc.modify().map_err(scope_error)?;
                     }
                 }
                 Phase::Delete => {
                     for sc in scopes.iter() {
-                        sc.delete()?;
+                        sc.delete().map_err(scope_error)?;
                     }
                 }
                 _ => {
@@ -307,11 +638,24 @@ impl Instigator {
             return Ok(());
         }
 
+        if phase != Phase::Delete {
+            self.ensure_app_namespace(&event)?;
+        }
         let record_ann = event.metadata.annotations.get(COMPONENT_RECORD_ANNOTATION);
         let mut last_components = get_record_annotation(record_ann)?;
         let mut new_components: BTreeMap<String, ComponentRecord> = BTreeMap::new();
         let mut component_updated = false;
-        for component in event.clone().spec.components.unwrap_or_else(|| vec![]) {
+        let components = dependency_order(event.spec.components.clone().unwrap_or_default())?;
+        // Traits of a configuration deleted are removed, whether they conflict or not.
+        if phase != Phase::Delete {
+            check_trait_conflicts(&components)?;
+        }
+        // Components changed in this pass are not running yet, dependents have to wait for them.
+        let mut not_ready: Vec<String> = vec![];
+        let mut waiting: BTreeMap<String, ComponentState> = BTreeMap::new();
+        let mut instantiate = vec![];
+        let mut attempted = vec![];
+        for component in components.iter().cloned() {
             let record = last_components
                 .get_mut(component.instance_name.as_str())
                 .cloned();
@@ -332,6 +676,40 @@ impl Instigator {
             if !check_diff(record.clone(), new_record) {
                 continue;
             }
+            let inst_name = component.instance_name.clone();
+            let pending = self.pending_dependencies(&event, &component, &components, &not_ready)?;
+            not_ready.push(inst_name.clone());
+            if !pending.is_empty() {
+                info!(
+                    "MainControlLoop: component {} waits for {}",
+                    inst_name,
+                    pending.join(",")
+                );
+                // Keep the previous record, so the component is updated once its dependencies run.
+                match record {
+                    Some(r) => new_components.insert(inst_name.clone(), r),
+                    None => new_components.remove(inst_name.as_str()),
+                };
+                waiting.insert(
+                    inst_name,
+                    ComponentState::waiting(component.component_name.clone(), &pending),
+                );
+                continue;
+            }
+            // A component that moves to another cluster is deleted from the one it was in, and
+            // created anew.
+            let record = match record {
+                Some(r) if r.config.cluster != component.cluster => {
+                    info!(
+                        "MainControlLoop: component {} moves to cluster {}",
+                        inst_name,
+                        component.cluster.as_deref().unwrap_or("local")
+                    );
+                    self.delete_component(&event, r.config)?;
+                    None
+                }
+                record => record,
+            };
             // record exists means component exists so event is just modify
             // while record is none means component don't exist so event is Add
             if record.is_some() && phase == Phase::Add {
@@ -359,204 +737,72 @@ impl Instigator {
                         }
                         scope_overlap.insert(scope.scope_type(), true);
                     }
-                    scope.add(component.clone())?;
+                    scope.add(component.clone()).map_err(scope_error)?;
                 }
             }
 
+            if let Some(previous) = record.as_ref() {
+                self.prune_traits(&event, &previous.config, &component, &comp_def)?;
+            }
             component_updated = true;
-            // Resolve variables/parameters
-
-            let parent = get_variable_values(Some(variables.clone()));
-
-            let child = component
-                .parameter_values
-                .clone()
-                .map(|values| resolve_variables(values, variables.clone()))
-                .unwrap_or_else(|| Ok(vec![]))?;
-
-            let params = resolve_parameters(
-                comp_def.spec.parameters.clone(),
-                resolve_values(child, vec![])?,
-            )?;
-
-            let inst_name = component.instance_name.clone();
-            let new_owner_ref =
-                self.get_new_own_ref(phase.clone(), component.clone(), owner_ref.clone())?;
-
-            // Instantiate components
-            let workload_meta = self.get_workload_meta(
-                name.clone(),
-                inst_name.clone(),
-                &comp_def,
-                &params,
-                new_owner_ref.clone(),
-                "MainControlLoop".to_string(),
-            );
-            // Instantiate components
-            let workload = self.load_workload_type(&comp_def, workload_meta)?;
-            // Load all of the traits related to this component.
-            let mut trait_manager = TraitManager {
-                config_name: name.clone(),
-                instance_name: inst_name.clone(),
-                component: component.clone(),
-                parent_params: parent.clone(),
-                owner_ref: new_owner_ref.clone(),
-                workload_type: comp_def.spec.workload_type.clone(),
-                traits: vec![], // Always starts empty.
-                component_schematic: comp_def.spec.clone(),
-            };
-            trait_manager.load_traits()?;
-
-            match phase {
-                Phase::Add => {
-                    info!(
-                        "MainControlLoop: Adding component {}",
-                        component.component_name.clone()
-                    );
-                    workload.validate()?;
-                    trait_manager.exec(
-                        self.namespace.as_str(),
-                        self.client.clone(),
-                        Phase::PreAdd,
-                    )?;
-                    workload.add()?;
-                    trait_manager.exec(self.namespace.as_str(), self.client.clone(), Phase::Add)?;
-                    if let Err(err) = self.event_handler.push_event_message(
-                        kube_event::Type::Normal,
-                        kube_event::Info {
-                            action: "created".to_string(),
-                            message: format!(
-                                "component {} created",
-                                component.component_name.clone(),
-                            ),
-                            reason: "".to_string(),
-                        },
-                        get_object_ref(event.clone()),
-                    ) {
-                        error!("MainControlLoop: adding event err: {:?}", err)
-                    }
-                }
-                Phase::Modify => {
-                    info!(
-                        "MainControlLoop: Modifying component {}",
-                        component.component_name.clone()
-                    );
-
-                    workload.validate()?;
-                    trait_manager.exec(
-                        self.namespace.as_str(),
-                        self.client.clone(),
-                        Phase::PreModify,
-                    )?;
-                    workload.modify()?;
-                    trait_manager.exec(
-                        self.namespace.as_str(),
-                        self.client.clone(),
-                        Phase::Modify,
-                    )?;
-                    if let Err(err) = self.event_handler.push_event_message(
-                        kube_event::Type::Normal,
-                        kube_event::Info {
-                            action: "updated".to_string(),
-                            message: format!(
-                                "component {} updated",
-                                component.component_name.clone(),
-                            ),
-                            reason: "".to_string(),
-                        },
-                        get_object_ref(event.clone()),
-                    ) {
-                        error!("MainControlLoop: adding event err {:?}", err)
-                    }
-                }
-                Phase::Delete => {
-                    info!(
-                        "MainControlLoop: Deleting component {}",
-                        component.component_name.clone()
-                    );
-                    trait_manager.exec(
-                        self.namespace.as_str(),
-                        self.client.clone(),
-                        Phase::PreDelete,
-                    )?;
-                    // we leave owner reference to do delete work, so we don't need to invoke delete function here.
-                }
-                _ => {
-                    return Err(format_err!(
-                        "Illegal phase: only Add, Modify, and Delete are supported here"
-                    ))
-                }
+            attempted.push((component.clone(), record));
+            instantiate.push((component, comp_def, phase.clone()));
+        }
+        if let Err(err) = self.instantiate_components(&event, &instantiate, &owner_ref) {
+            if event.spec.rollback_on_failure.unwrap_or(false) {
+                self.rollback(&event, &attempted, &owner_ref);
+                return Err(format_err!(
+                    "{}, rolled back {} components",
+                    err,
+                    attempted.len()
+                ));
             }
+            return Err(err);
+        }
+        // Applied components are pending until the status check sees them running.
+        let applied_at = chrono::Utc::now().to_rfc3339();
+        for (component, _, _) in instantiate.iter() {
+            waiting.insert(
+                component.instance_name.clone(),
+                ComponentState::progressing(component.component_name.clone(), applied_at.clone()),
+            );
         }
 
         // delete the component left
         for component_record in last_components.values() {
-            let component = component_record.config.clone();
             component_updated = true;
-            //FIXME: if component is not found, what can we do?
-            let comp_def: KubeComponent = get_component_def(
-                self.namespace.clone(),
-                component.component_name.clone(),
-                self.client.clone(),
-            )?;
-            // Resolve variables/parameters
-            let parent = get_variable_values(event.spec.variables.clone());
-            let inst_name = component.instance_name.clone();
-            // Load all of the traits related to this component.
-            let mut trait_manager = TraitManager {
-                config_name: name.clone(),
-                instance_name: inst_name.clone(),
-                component: component.clone(),
-                parent_params: parent.clone(),
-                owner_ref: None,
-                workload_type: comp_def.spec.workload_type.clone(),
-                traits: vec![], // Always starts empty.
-                component_schematic: comp_def.spec.clone(),
-            };
-            trait_manager.load_traits()?;
-
-            info!(
-                "MainControlLoop: Deleting component {}",
-                component.component_name.clone()
-            );
-            //The reason for this is that we do not require that traits be deployed only in-cluster.
-            //For example, a trait could create an object storage bucket or work with an external API service.
-            //So we want to give them a chance to react to a deletion event.
-            trait_manager.exec(
-                self.namespace.as_str(),
-                self.client.clone(),
-                Phase::PreDelete,
-            )?;
-            //delete component instance and let owner_reference to delete real resource
-            self.delete_component_instance(component.component_name.clone(), inst_name.clone())?;
-            for sc in &component
-                .application_scopes
-                .clone()
-                .unwrap_or_else(|| vec![])
-            {
-                let scopes =
-                    get_scope_instance(sc.clone(), self.namespace.clone(), self.client.clone())?;
-                for scope in scopes.iter() {
-                    scope.remove(component.clone())?;
-                }
-            }
-            if let Err(err) = self.event_handler.push_event_message(
-                kube_event::Type::Normal,
-                kube_event::Info {
-                    action: "deleted".to_string(),
-                    message: format!("component {} deleted", component.component_name.clone(),),
-                    reason: "".to_string(),
-                },
-                get_object_ref(event.clone()),
-            ) {
-                error!("MainControlLoop: adding event err {:?}", err)
+            self.delete_component(&event, component_record.config.clone())?;
+        }
+        if phase != Phase::Delete {
+            match self.prune(&event) {
+                Ok(0) => {}
+                Ok(_) => component_updated = true,
+                Err(e) => warn!("MainControlLoop: prune instances of {} err {:?}", name, e),
             }
         }
+        let current_states = event
+            .status
+            .as_ref()
+            .and_then(|s| s.component_states.clone())
+            .unwrap_or_default();
+        let waiting_changed = waiting
+            .iter()
+            .any(|(inst, state)| current_states.get(inst) != Some(state));
+        if phase == Phase::Delete {
+            self.delete_app_namespace(&event)?;
+        }
         // if no component was updated or this is an delete phase, just return without status change.
-        if !component_updated || phase == Phase::Delete {
+        if (!component_updated && !waiting_changed) || phase == Phase::Delete {
             return Ok(());
         }
 
+        let revision = match self.history(&event).record(&event.spec, owner_ref.clone()) {
+            Ok(revision) => Some(revision),
+            Err(e) => {
+                warn!("MainControlLoop: record revision of {} err {:?}", name, e);
+                None
+            }
+        };
         let new_record = serde_json::to_string(&new_components)?;
         let mut annotation = event.metadata.annotations.clone();
         annotation.insert(COMPONENT_RECORD_ANNOTATION.to_string(), new_record);
@@ -567,6 +813,17 @@ impl Instigator {
             .map_or(default_status.clone(), |mut hs| {
                 hs.phase = Some(phase.to_string());
                 Some(hs)
+            })
+            .map(|mut hs| {
+                hs.observed_generation = observed_generation(&event);
+                hs.message = None;
+                hs.revision = revision.or(hs.revision);
+                if !waiting.is_empty() {
+                    let mut states = hs.component_states.unwrap_or_default();
+                    states.extend(waiting);
+                    hs.component_states = Some(states);
+                }
+                hs
             });
 
         self.retry_patch_status(
@@ -577,43 +834,1280 @@ impl Instigator {
         )
     }
 
-    /// Create new Kubernetes objects based on this config.
-    pub fn add(&self, event: OpResource) -> InstigatorResult {
-        self.exec(event, Phase::Add)
-    }
-    /// Modify existing Kubernetes objects based on config and workload type.
-    pub fn modify(&self, event: OpResource) -> InstigatorResult {
-        self.exec(event, Phase::Modify)
-    }
-    /// Delete the Kubernetes objects associated with this config.
-    pub fn delete(&self, event: OpResource) -> InstigatorResult {
-        self.exec(event, Phase::Delete)
+    /// instantiate_components creates or updates the workloads and traits of `components`, up to
+    /// `concurrency` components at a time. The components must not depend on each other, and the
+    /// first error in their order is returned once all of them are done.
+    fn instantiate_components(
+        &self,
+        event: &OpResource,
+        components: &[(ComponentConfiguration, KubeComponent, Phase)],
+        owner_ref: &meta::OwnerReference,
+    ) -> InstigatorResult {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(BTreeMap::new());
+        let parent = trace::current();
+        let cause = audit::cause();
+        crossbeam_utils::thread::scope(|s| {
+            for _ in 0..self.concurrency.min(components.len()) {
+                s.spawn(|_| {
+                    let _attached = trace::attach(parent);
+                    let _caused = audit::caused_by(cause.clone());
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        let (component, comp_def, phase) = match components.get(i) {
+                            Some(c) => c,
+                            None => break,
+                        };
+                        let res =
+                            self.instantiate_in_time(event, component, comp_def, phase, owner_ref);
+                        results.lock().unwrap().insert(i, res);
+                    }
+                });
+            }
+        })
+        .map_err(|_| format_err!("a component instantiation thread panicked"))?;
+        let results = results.into_inner().unwrap();
+        results.into_iter().try_for_each(|(_, res)| res)
     }
 
-    fn get_workload_meta(
+    /// rollback undoes a reconciliation attempt that failed part way. Components it created are
+    /// deleted, components it updated get the configuration of their previous record back.
+    /// Errors are only logged, the attempt has failed already. Components whose attempt timed
+    /// out and is still applying are not rolled back, they get a RollbackFailed event.
+    fn rollback(
         &self,
-        config_name: String,
-        instance_name: String,
-        comp: &KubeComponent,
-        params: &ParamMap,
-        owner_ref: Option<Vec<meta::OwnerReference>>,
-        controlled_by: String,
-    ) -> WorkloadMetadata {
-        info!(
-            "{}: Looking up workload for {} <{}>",
-            controlled_by, config_name, comp.metadata.name
+        event: &OpResource,
+        attempted: &[(ComponentConfiguration, Option<ComponentRecord>)],
+        owner_ref: &meta::OwnerReference,
+    ) {
+        for (component, record) in attempted {
+            let res = match record {
+                None => self
+                    .claim_instance(component)
+                    .and_then(|_in_flight| self.delete_component(event, component.clone())),
+                Some(record) => get_component_def(
+                    self.namespace.clone(),
+                    record.config.component_name.clone(),
+                    self.client.clone(),
+                )
+                .and_then(|comp_def| {
+                    self.instantiate_in_time(
+                        event,
+                        &record.config,
+                        &comp_def,
+                        &Phase::Modify,
+                        owner_ref,
+                    )
+                }),
+            };
+            let (type_, reason, message) = match res {
+                Ok(()) => (
+                    kube_event::Type::Normal,
+                    "RolledBack",
+                    format!("rolled back component {}", component.instance_name),
+                ),
+                Err(e) => (
+                    kube_event::Type::Warning,
+                    "RollbackFailed",
+                    format!(
+                        "roll back component {} failed: {}",
+                        component.instance_name, e
+                    ),
+                ),
+            };
+            info!("MainControlLoop: {}", message);
+            if let Err(err) = self.event_handler.push_event_message(
+                type_,
+                kube_event::Info {
+                    action: "rollback".to_string(),
+                    message,
+                    reason: reason.to_string(),
+                },
+                get_object_ref(event.clone()),
+            ) {
+                error!("adding event err {:?}", err)
+            }
+        }
+    }
+
+    /// instantiate_in_time is `instantiate_component` bounded by the component timeout. Blocking
+    /// requests can't be cancelled, so a component that times out is left to finish the request
+    /// at hand in the background while the reconciliation goes on without it. The worker stops
+    /// before its next apply, and holds the `InFlight` mark of the instance until it is done, so
+    /// that neither a rollback nor the next reconciliation applies the instance meanwhile.
+    fn instantiate_in_time(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        comp_def: &KubeComponent,
+        phase: &Phase,
+        owner_ref: &meta::OwnerReference,
+    ) -> InstigatorResult {
+        let in_flight = self.claim_instance(component)?;
+        let mut inst = self.clone();
+        inst.cancelled = Arc::new(AtomicBool::new(false));
+        let cancelled = inst.cancelled.clone();
+        let (event, component, comp_def, phase, owner_ref) = (
+            event.clone(),
+            component.clone(),
+            comp_def.clone(),
+            phase.clone(),
+            owner_ref.clone(),
+        );
+        let inst_name = component.instance_name.clone();
+        let res = within(self.component_timeout, move || {
+            let _in_flight = in_flight;
+            inst.instantiate_component(&event, &component, &comp_def, &phase, &owner_ref)
+        });
+        // A worker that is done already doesn't look at the flag anymore.
+        cancelled.store(true, Ordering::SeqCst);
+        res.map_err(|e| format_err!("component {}: {}", inst_name, e))
+    }
+
+    /// claim_instance marks the instance of `component` as being applied, see `InFlight`.
+    fn claim_instance(&self, component: &ComponentConfiguration) -> Result<InFlight, Error> {
+        let key = format!("{}/{}", self.workload_namespace(), component.instance_name);
+        InFlight::claim(key).ok_or_else(|| {
+            format_err!(
+                "component {} is still being applied, a previous attempt timed out",
+                component.instance_name
+            )
+        })
+    }
+
+    /// check_cancelled fails once the component at hand timed out, so that its worker applies
+    /// nothing more.
+    fn check_cancelled(&self) -> InstigatorResult {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(format_err!("timed out, stopped applying"));
+        }
+        Ok(())
+    }
+
+    fn instantiate_component(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        comp_def: &KubeComponent,
+        phase: &Phase,
+        owner_ref: &meta::OwnerReference,
+    ) -> InstigatorResult {
+        let mut span = trace::span("component");
+        span.set_attribute("component.instance", component.instance_name.as_str());
+        span.set_attribute("component.schematic", component.component_name.as_str());
+        span.set_attribute("component.phase", format!("{:?}", phase));
+        let res = self.apply_component(event, component, comp_def, phase, owner_ref);
+        span.record(&res);
+        res
+    }
+
+    fn apply_component(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        comp_def: &KubeComponent,
+        phase: &Phase,
+        owner_ref: &meta::OwnerReference,
+    ) -> InstigatorResult {
+        let name = event.metadata.name.clone();
+        let inst_name = component.instance_name.clone();
+        let variables = event.spec.variables.clone().unwrap_or_default();
+        // Resolve variables/parameters
+        let parent = get_variable_values(Some(variables.clone()));
+
+        let params = self.component_params(event, component, comp_def)?;
+
+        let new_owner_ref =
+            self.get_new_own_ref(phase.clone(), component.clone(), owner_ref.clone())?;
+        // Everything created for the component is owned by its instance, so that the garbage
+        // collector cleans it up even if rudr fails to.
+        if new_owner_ref.iter().flatten().next().is_none() {
+            return Err(format_err!(
+                "no owner reference for component {}, refusing to create unowned resources",
+                inst_name
+            ));
+        }
+
+        let client = self.cluster_client(component)?;
+        let owned = local_owner(component, new_owner_ref.clone());
+
+        // Instantiate components
+        let workload_meta = self.get_workload_meta(
+            event,
+            component,
+            comp_def,
+            &params,
+            new_owner_ref.clone(),
+            "MainControlLoop".to_string(),
+        )?;
+        // Instantiate components
+        let workload = self.load_workload_type(comp_def, workload_meta)?;
+        // Load all of the traits related to this component.
+        let mut trait_manager = TraitManager {
+            config_name: name.clone(),
+            instance_name: inst_name.clone(),
+            component: component.clone(),
+            parent_params: parent.clone(),
+            owner_ref: owned.clone(),
+            workload_type: comp_def.spec.workload_type.clone(),
+            traits: vec![], // Always starts empty.
+            component_schematic: comp_def.spec.clone(),
+            definitions: self.trait_definitions(component)?,
+        };
+        trait_manager.load_traits()?;
+        let instance = new_owner_ref.as_ref().and_then(|refs| refs.first());
+
+        match phase {
+            Phase::Add => {
+                info!(
+                    "MainControlLoop: Adding component {}",
+                    component.component_name.clone()
+                );
+                workload.validate()?;
+                self.check_cancelled()?;
+                self.apply_service_account(event, component, owned.clone())?;
+                self.check_cancelled()?;
+                self.run_hooks(
+                    event,
+                    component,
+                    HookType::PreInstall,
+                    &params,
+                    owned.clone(),
+                )?;
+                self.check_cancelled()?;
+                trait_manager.exec(self.workload_namespace(), client.clone(), Phase::PreAdd)?;
+                self.check_cancelled()?;
+                self.report(event, instance, component, "create", workload.add())?;
+                self.check_cancelled()?;
+                self.report_traits(
+                    event,
+                    instance,
+                    component,
+                    "create",
+                    trait_manager.exec(self.workload_namespace(), client.clone(), Phase::Add),
+                )?;
+                self.check_cancelled()?;
+                self.run_hooks(
+                    event,
+                    component,
+                    HookType::PostInstall,
+                    &params,
+                    owned.clone(),
+                )?;
+                self.check_cancelled()?;
+                self.record_rendering(
+                    component,
+                    workload.as_ref(),
+                    &trait_manager,
+                    new_owner_ref.clone(),
+                );
+                if let Err(err) = self.event_handler.push_event_message(
+                    kube_event::Type::Normal,
+                    kube_event::Info {
+                        action: "created".to_string(),
+                        message: format!("component {} created", component.component_name.clone(),),
+                        reason: "".to_string(),
+                    },
+                    get_object_ref(event.clone()),
+                ) {
+                    error!("MainControlLoop: adding event err: {:?}", err)
+                }
+            }
+            Phase::Modify => {
+                info!(
+                    "MainControlLoop: Modifying component {}",
+                    component.component_name.clone()
+                );
+
+                workload.validate()?;
+                self.check_cancelled()?;
+                self.apply_service_account(event, component, owned.clone())?;
+                self.check_cancelled()?;
+                self.run_hooks(
+                    event,
+                    component,
+                    HookType::PreInstall,
+                    &params,
+                    owned.clone(),
+                )?;
+                self.check_cancelled()?;
+                trait_manager.exec(self.workload_namespace(), client.clone(), Phase::PreModify)?;
+                self.check_cancelled()?;
+                self.report(event, instance, component, "update", workload.modify())?;
+                self.check_cancelled()?;
+                self.report_traits(
+                    event,
+                    instance,
+                    component,
+                    "update",
+                    trait_manager.exec(self.workload_namespace(), client.clone(), Phase::Modify),
+                )?;
+                self.check_cancelled()?;
+                self.run_hooks(
+                    event,
+                    component,
+                    HookType::PostInstall,
+                    &params,
+                    owned.clone(),
+                )?;
+                self.check_cancelled()?;
+                self.record_rendering(
+                    component,
+                    workload.as_ref(),
+                    &trait_manager,
+                    new_owner_ref.clone(),
+                );
+                if let Err(err) = self.event_handler.push_event_message(
+                    kube_event::Type::Normal,
+                    kube_event::Info {
+                        action: "updated".to_string(),
+                        message: format!("component {} updated", component.component_name.clone(),),
+                        reason: "".to_string(),
+                    },
+                    get_object_ref(event.clone()),
+                ) {
+                    error!("MainControlLoop: adding event err {:?}", err)
+                }
+            }
+            Phase::Delete => {
+                info!(
+                    "MainControlLoop: Deleting component {}",
+                    component.component_name.clone()
+                );
+                self.check_cancelled()?;
+                self.run_hooks(
+                    event,
+                    component,
+                    HookType::PreDelete,
+                    &params,
+                    owned.clone(),
+                )?;
+                self.check_cancelled()?;
+                trait_manager.exec(self.workload_namespace(), client.clone(), Phase::PreDelete)?;
+                // we leave owner reference to do delete work, so we don't need to invoke delete function here.
+                // Nothing owns the resources in a remote cluster though.
+                if component.cluster.is_some() {
+                    self.check_cancelled()?;
+                    self.report(event, instance, component, "delete", workload.delete())?;
+                    self.report_traits(
+                        event,
+                        instance,
+                        component,
+                        "delete",
+                        trait_manager.exec(self.workload_namespace(), client, Phase::Delete),
+                    )?;
+                }
+            }
+            _ => {
+                return Err(format_err!(
+                    "Illegal phase: only Add, Modify, and Delete are supported here"
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// record_rendering stores the objects applied for `component` with its instance, see
+    /// `Renderings`. Failing to record them doesn't fail the component.
+    fn record_rendering(
+        &self,
+        component: &ComponentConfiguration,
+        workload: &dyn WorkloadType,
+        trait_manager: &TraitManager,
+        owner: Option<Vec<meta::OwnerReference>>,
+    ) {
+        let instance_name = combine_name(
+            component.component_name.clone(),
+            component.instance_name.clone(),
+        );
+        let renderings = Renderings {
+            client: self.client.clone(),
+            namespace: self.workload_namespace().to_string(),
+        };
+        let res = workload
+            .render()
+            .and_then(|mut objects| {
+                objects.extend(trait_manager.render()?);
+                objects.into_iter().map(mutator::mutate).collect()
+            })
+            .and_then(|objects: Vec<serde_json::Value>| {
+                renderings.record(instance_name.as_str(), &objects, owner)
+            });
+        if let Err(e) = res {
+            warn!(
+                "MainControlLoop: record rendering of {} err {:?}",
+                instance_name, e
+            );
+        }
+    }
+
+    /// apply_service_account creates or updates the ServiceAccount of `component` and its
+    /// RoleBindings, and deletes the RoleBindings it doesn't name anymore. Both are named after
+    /// the instance and owned by it.
+    fn apply_service_account(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        owner_ref: Option<Vec<meta::OwnerReference>>,
+    ) -> InstigatorResult {
+        let account = match component.service_account.as_ref() {
+            Some(account) => account,
+            None => return Ok(()),
+        };
+        let client = self.cluster_client(component)?;
+        let name = component.instance_name.clone();
+        let mut labels = BTreeMap::new();
+        labels.insert(
+            "app.kubernetes.io/name".to_string(),
+            event.metadata.name.clone(),
+        );
+        labels.insert(SERVICE_ACCOUNT_INSTANCE_LABEL.to_string(), name.clone());
+
+        let resource = RawApi {
+            resource: "serviceaccounts".into(),
+            ..RawApi::v1Secret()
+        }
+        .within(self.workload_namespace());
+        let sa = account.to_service_account(name.clone(), labels.clone(), owner_ref.clone());
+        workload_type::apply(&client, &resource, name.as_str(), &sa)
+            .map_err(|e| format_err!("service account of component {}: {}", name, e))?;
+
+        let resource = RawApi {
+            resource: "rolebindings".into(),
+            group: "rbac.authorization.k8s.io".into(),
+            version: "v1".into(),
+            prefix: "apis".into(),
+            ..Default::default()
+        }
+        .within(self.workload_namespace());
+        let bindings = account.to_role_bindings(
+            name.clone(),
+            self.workload_namespace().to_string(),
+            labels.clone(),
+            owner_ref,
+        );
+        let mut keep = vec![];
+        for binding in bindings.iter() {
+            let binding_name = binding
+                .metadata
+                .as_ref()
+                .and_then(|m| m.name.clone())
+                .unwrap_or_default();
+            workload_type::apply(&client, &resource, binding_name.as_str(), binding).map_err(
+                |e| format_err!("role binding {} of component {}: {}", binding_name, name, e),
+            )?;
+            keep.push(binding_name);
+        }
+        let selector: Vec<String> = labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let lp = kube::api::ListParams {
+            label_selector: Some(selector.join(",")),
+            ..Default::default()
+        };
+        let existing = client
+            .timed_request::<kube::api::ObjectList<Object<Void, Void>>>(resource.list(&lp)?)?;
+        for binding in existing
+            .items
+            .iter()
+            .filter(|b| !keep.contains(&b.metadata.name))
+        {
+            client.timed_request::<serde_json::Value>(
+                resource.delete(binding.metadata.name.as_str(), &Default::default())?,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// run_hooks runs the hooks of `component` of type `hook_type` one after another, each as a
+    /// Job it waits for. A hook that fails or doesn't complete in time fails the component.
+    ///
+    /// The Job of a hook is named after the generation of the configuration, so a hook runs once
+    /// per change of the configuration, and the Jobs of earlier generations are deleted.
+    fn run_hooks(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        hook_type: HookType,
+        params: &ParamMap,
+        owner_ref: Option<Vec<meta::OwnerReference>>,
+    ) -> InstigatorResult {
+        let client = self.cluster_client(component)?;
+        let resource = RawApi::v1Job().within(self.workload_namespace());
+        let generation = observed_generation(event).unwrap_or_default();
+        for hook in component
+            .hooks
+            .iter()
+            .flatten()
+            .filter(|h| h.hook_type == hook_type)
+        {
+            let name = hook_job_name(component.instance_name.as_str(), hook, generation);
+            let mut labels = BTreeMap::new();
+            labels.insert(
+                "app.kubernetes.io/name".to_string(),
+                event.metadata.name.clone(),
+            );
+            labels.insert(
+                HOOK_INSTANCE_LABEL.to_string(),
+                component.instance_name.clone(),
+            );
+            labels.insert(HOOK_NAME_LABEL.to_string(), hook.name.clone());
+            self.delete_hook_jobs(&client, &resource, &labels, name.as_str())?;
+            info!(
+                "MainControlLoop: running hook {} of component {}",
+                hook.name, component.instance_name
+            );
+            let job = hook.to_job(name.clone(), labels, owner_ref.clone(), params.clone());
+            let timeout = Duration::from_secs(hook.timeout_seconds.unwrap_or(DEFAULT_HOOK_TIMEOUT));
+            workload_type::apply(&client, &resource, name.as_str(), &job)
+                .and_then(|_| self.wait_for_job(&client, &resource, name.as_str(), timeout))
+                .map_err(|e| {
+                    format_err!(
+                        "hook {} of component {}: {}",
+                        hook.name,
+                        component.instance_name,
+                        e
+                    )
+                })?;
+        }
+        Ok(())
+    }
+
+    /// delete_hook_jobs deletes the Jobs with `labels` other than `keep`, the Jobs a hook ran
+    /// with for earlier generations.
+    fn delete_hook_jobs(
+        &self,
+        client: &APIClient,
+        resource: &RawApi,
+        labels: &BTreeMap<String, String>,
+        keep: &str,
+    ) -> InstigatorResult {
+        let selector: Vec<String> = labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let lp = kube::api::ListParams {
+            label_selector: Some(selector.join(",")),
+            ..Default::default()
+        };
+        let jobs = client
+            .timed_request::<kube::api::ObjectList<Object<Void, Void>>>(resource.list(&lp)?)?;
+        let dp = kube::api::DeleteParams {
+            propagation_policy: Some(kube::api::PropagationPolicy::Background),
+            ..Default::default()
+        };
+        for job in jobs.items.iter().filter(|j| j.metadata.name != keep) {
+            client.timed_request::<serde_json::Value>(
+                resource.delete(job.metadata.name.as_str(), &dp)?,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// wait_for_job polls the Job `name` until it completes, fails or `timeout` elapses.
+    fn wait_for_job(
+        &self,
+        client: &APIClient,
+        resource: &RawApi,
+        name: &str,
+        timeout: Duration,
+    ) -> InstigatorResult {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let job: Object<batch::JobSpec, batch::JobStatus> =
+                client.timed_request(resource.get_status(name)?)?;
+            if let Some(outcome) = job_outcome(job.status.as_ref()) {
+                return outcome;
+            }
+            if Instant::now() >= deadline {
+                return Err(format_err!(
+                    "job {} did not complete within {}s",
+                    name,
+                    timeout.as_secs()
+                ));
+            }
+            thread::sleep(HOOK_POLL_INTERVAL);
+        }
+    }
+
+    /// pending_dependencies returns the dependencies of a component that are not running yet.
+    fn pending_dependencies(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        components: &[ComponentConfiguration],
+        not_ready: &[String],
+    ) -> Result<Vec<String>, Error> {
+        let mut pending = vec![];
+        for dep in component.depends_on.iter().flatten() {
+            let running = match components.iter().find(|c| &c.instance_name == dep) {
+                Some(dependency) if !not_ready.contains(dep) => {
+                    self.component_running(event, dependency)?
+                }
+                _ => false,
+            };
+            if !running {
+                pending.push(dep.clone());
+            }
+        }
+        Ok(pending)
+    }
+
+    /// component_running returns true once every workload resource of a component is running.
+    fn component_running(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+    ) -> Result<bool, Error> {
+        let comp_def: KubeComponent = get_component_def(
+            self.namespace.clone(),
+            component.component_name.clone(),
+            self.client.clone(),
+        )?;
+        let params = self.component_params(event, component, &comp_def)?;
+        // The status check recreates missing resources, they keep their owner.
+        let owner_ref = self
+            .component_instance_owner_reference(
+                component.component_name.clone(),
+                component.instance_name.clone(),
+            )
+            .ok();
+        let workload_meta = self.get_workload_meta(
+            event,
+            component,
+            &comp_def,
+            &params,
+            owner_ref,
+            "MainControlLoop".to_string(),
+        )?;
+        let status = self
+            .load_workload_type(&comp_def, workload_meta)?
+            .status()?;
+        let state = ComponentState::from_workload(component.component_name.clone(), &status, None);
+        Ok(state.phase == COMPONENT_RUNNING)
+    }
+
+    /// delete_component deletes the workload, traits and component instance of a component that
+    /// is no longer part of the configuration, and removes it from its scopes.
+    fn delete_component(
+        &self,
+        event: &OpResource,
+        component: ComponentConfiguration,
+    ) -> InstigatorResult {
+        let name = event.metadata.name.clone();
+        let inst_name = component.instance_name.clone();
+        // Without its schematic the workload can't be rendered, the instance still owns it.
+        let resolved = get_component_def(
+            self.namespace.clone(),
+            component.component_name.clone(),
+            self.client.clone(),
+        )
+        .and_then(|comp_def| {
+            let params = self.component_params(event, &component, &comp_def)?;
+            Ok((comp_def, params))
+        });
+        let (comp_def, params) = match resolved {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                warn!(
+                    "MainControlLoop: can't render removed component {}: {}, leave its resources to the garbage collector",
+                    inst_name, e
+                );
+                return self.delete_component_instance(component.component_name.clone(), inst_name);
+            }
+        };
+        // Resolve variables/parameters
+        let variables = event.spec.variables.clone().unwrap_or_default();
+        let parent = get_variable_values(Some(variables.clone()));
+        // Load all of the traits related to this component.
+        let mut trait_manager = TraitManager {
+            config_name: name.clone(),
+            instance_name: inst_name.clone(),
+            component: component.clone(),
+            parent_params: parent.clone(),
+            owner_ref: None,
+            workload_type: comp_def.spec.workload_type.clone(),
+            traits: vec![], // Always starts empty.
+            component_schematic: comp_def.spec.clone(),
+            definitions: self.trait_definitions(&component)?,
+        };
+        trait_manager.load_traits()?;
+        let client = self.cluster_client(&component)?;
+        // Fetched up front, the component instance is gone once the component is deleted.
+        let instance = self
+            .component_instance_owner_reference(component.component_name.clone(), inst_name.clone())
+            .ok()
+            .and_then(|refs| refs.into_iter().next());
+
+        info!(
+            "MainControlLoop: Deleting component {}",
+            component.component_name.clone()
+        );
+        //The reason for this is that we do not require that traits be deployed only in-cluster.
+        //For example, a trait could create an object storage bucket or work with an external API service.
+        //So we want to give them a chance to react to a deletion event.
+        self.run_hooks(
+            event,
+            &component,
+            HookType::PreDelete,
+            &params,
+            local_owner(&component, instance.clone().map(|i| vec![i])),
+        )?;
+        trait_manager.exec(self.workload_namespace(), client.clone(), Phase::PreDelete)?;
+        // Owner references are the fallback if a resource can't be deleted directly.
+        let deleted = self
+            .get_workload_meta(
+                event,
+                &component,
+                &comp_def,
+                &params,
+                None,
+                "MainControlLoop".to_string(),
+            )
+            .and_then(|workload_meta| self.load_workload_type(&comp_def, workload_meta))
+            .and_then(|workload| workload.delete());
+        if let Err(err) = self.report(event, instance.as_ref(), &component, "delete", deleted) {
+            warn!(
+                "MainControlLoop: delete workload of {} err {:?}, leave it to the garbage collector",
+                inst_name, err
+            );
+        }
+        self.report_traits(
+            event,
+            instance.as_ref(),
+            &component,
+            "delete",
+            trait_manager.exec(self.workload_namespace(), client, Phase::Delete),
+        )?;
+        //delete component instance and let owner_reference to delete real resource
+        self.delete_component_instance(component.component_name.clone(), inst_name.clone())?;
+        for sc in &component
+            .application_scopes
+            .clone()
+            .unwrap_or_else(|| vec![])
+        {
+            let scopes =
+                match get_scope_instance(sc.clone(), self.namespace.clone(), self.client.clone()) {
+                    Ok(scopes) => scopes,
+                    // The scope may be deleted together with the component.
+                    Err(e) if e.to_string().contains("NotFound") => continue,
+                    Err(e) => return Err(e),
+                };
+            for scope in scopes.iter() {
+                scope.remove(component.clone())?;
+            }
+        }
+        if let Err(err) = self.event_handler.push_event_message(
+            kube_event::Type::Normal,
+            kube_event::Info {
+                action: "deleted".to_string(),
+                message: format!("component {} deleted", component.component_name.clone(),),
+                reason: "".to_string(),
+            },
+            get_object_ref(event.clone()),
+        ) {
+            error!("MainControlLoop: adding event err {:?}", err)
+        }
+        Ok(())
+    }
+
+    /// report pushes an event about the outcome of `action` ("create", "update" or "delete") on
+    /// the workload of a component, to the configuration and to the component instance.
+    fn report<T>(
+        &self,
+        event: &OpResource,
+        instance: Option<&meta::OwnerReference>,
+        component: &ComponentConfiguration,
+        action: &str,
+        res: Result<T, Error>,
+    ) -> Result<T, Error> {
+        self.push_outcome(
+            event, instance, "Workload", "workload", component, action, &res,
+        );
+        res
+    }
+
+    /// report_traits is `report` for the traits of a component, it stays silent for a component
+    /// without traits.
+    fn report_traits<T>(
+        &self,
+        event: &OpResource,
+        instance: Option<&meta::OwnerReference>,
+        component: &ComponentConfiguration,
+        action: &str,
+        res: Result<T, Error>,
+    ) -> Result<T, Error> {
+        let names: Vec<String> = component
+            .traits
+            .iter()
+            .flatten()
+            .map(|t| t.name.clone())
+            .collect();
+        if !names.is_empty() {
+            let subject = format!("traits {}", names.join(","));
+            self.push_outcome(event, instance, "Traits", &subject, component, action, &res);
+        }
+        res
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push_outcome<T>(
+        &self,
+        event: &OpResource,
+        instance: Option<&meta::OwnerReference>,
+        kind: &str,
+        subject: &str,
+        component: &ComponentConfiguration,
+        action: &str,
+        res: &Result<T, Error>,
+    ) {
+        let verb = capitalize(action);
+        let (type_, reason, message) = match res {
+            Ok(_) => (
+                kube_event::Type::Normal,
+                format!("{}{}d", kind, verb),
+                format!(
+                    "{} of component {} {}d",
+                    subject, component.instance_name, action
+                ),
+            ),
+            Err(e) => {
+                metrics::inc_error(if kind == "Traits" {
+                    "trait"
+                } else {
+                    "component"
+                });
+                (
+                    kube_event::Type::Warning,
+                    format!("{}{}Failed", kind, verb),
+                    format!(
+                        "{} {} of component {} failed: {}",
+                        action, subject, component.instance_name, e
+                    ),
+                )
+            }
+        };
+        let mut objects = vec![get_object_ref(event.clone())];
+        // Events are recorded in the namespace of the configuration, they can't refer to an
+        // instance in the namespace of the application.
+        if let Some(owner) = instance.filter(|_| self.app_namespace.is_none()) {
+            objects.push(ObjectReference {
+                api_version: Some(owner.api_version.clone()),
+                kind: Some(owner.kind.clone()),
+                name: Some(owner.name.clone()),
+                namespace: Some(self.namespace.clone()),
+                uid: Some(owner.uid.clone()),
+                field_path: None,
+                resource_version: None,
+            });
+        }
+        for object in objects {
+            if let Err(err) = self.event_handler.push_event_message(
+                type_,
+                kube_event::Info {
+                    action: format!("{}d", action),
+                    message: message.clone(),
+                    reason: reason.clone(),
+                },
+                object,
+            ) {
+                error!("MainControlLoop: adding event err {:?}", err)
+            }
+        }
+    }
+
+    /// render returns every workload and trait resource the configuration would create, without
+    /// creating anything.
+    pub fn render(&self, event: &OpResource) -> Result<Vec<serde_json::Value>, Error> {
+        if let Some(inst) = self.for_app(event) {
+            return inst.render(event);
+        }
+        let mut objects = vec![];
+        let components = dependency_order(event.spec.components.clone().unwrap_or_default())?;
+        check_trait_conflicts(&components)?;
+        for component in components {
+            let comp_def: KubeComponent = get_component_def(
+                self.namespace.clone(),
+                component.component_name.clone(),
+                self.client.clone(),
+            )?;
+            objects.extend(self.render_component(event, &component, &comp_def, None, "DryRun")?);
+        }
+        Ok(objects)
+    }
+
+    /// render_component returns the workload and trait resources of one component, owned by
+    /// `owner_ref`. `controlled_by` names the caller in the logs.
+    fn render_component(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        comp_def: &KubeComponent,
+        owner_ref: Option<Vec<meta::OwnerReference>>,
+        controlled_by: &str,
+    ) -> Result<Vec<serde_json::Value>, Error> {
+        let name = event.metadata.name.clone();
+        let variables = event.spec.variables.clone().unwrap_or_default();
+        let parent = get_variable_values(Some(variables));
+        let params = self.component_params(event, component, comp_def)?;
+        let inst_name = component.instance_name.clone();
+        let workload_meta = self.get_workload_meta(
+            event,
+            component,
+            comp_def,
+            &params,
+            owner_ref.clone(),
+            controlled_by.to_string(),
+        )?;
+        let workload = self.load_workload_type(comp_def, workload_meta)?;
+        workload.validate()?;
+        let mut objects = workload.render()?;
+        let mut trait_manager = TraitManager {
+            config_name: name,
+            instance_name: inst_name,
+            component: component.clone(),
+            parent_params: parent,
+            owner_ref: local_owner(component, owner_ref),
+            workload_type: comp_def.spec.workload_type.clone(),
+            traits: vec![], // Always starts empty.
+            component_schematic: comp_def.spec.clone(),
+            definitions: self.trait_definitions(component)?,
+        };
+        trait_manager.load_traits()?;
+        objects.extend(trait_manager.render()?);
+        // Render what would be applied, or drift checks would undo the mutators.
+        objects.into_iter().map(mutator::mutate).collect()
+    }
+
+    /// heal re-applies the generated resources of a configuration that were changed or deleted
+    /// since rudr applied them, and returns how many it re-applied. Only components whose current
+    /// spec has been applied are checked, the others are still handled by the control loop.
+    pub fn heal(&self, event: &OpResource) -> Result<usize, Error> {
+        if let Some(inst) = self.for_app(event) {
+            return inst.heal(event);
+        }
+        if event.spec.scopes.is_some() || is_dry_run(event) || is_paused(event) {
+            return Ok(0);
+        }
+        let record_ann = event.metadata.annotations.get(COMPONENT_RECORD_ANNOTATION);
+        let records = get_record_annotation(record_ann)?;
+        let mut healed = 0;
+        for component in event.spec.components.clone().unwrap_or_default() {
+            let record = match records.get(component.instance_name.as_str()) {
+                Some(record) => record.clone(),
+                None => continue,
+            };
+            let comp_def: KubeComponent = get_component_def(
+                self.namespace.clone(),
+                component.component_name.clone(),
+                self.client.clone(),
+            )?;
+            let current = ComponentRecord {
+                version: comp_def
+                    .metadata
+                    .resourceVersion
+                    .clone()
+                    .unwrap_or_default(),
+                config: component.clone(),
+            };
+            if check_diff(Some(record), &current) {
+                continue;
+            }
+            let owner_ref = self.component_instance_owner_reference(
+                component.component_name.clone(),
+                component.instance_name.clone(),
+            )?;
+            let objects = self.render_component(
+                event,
+                &component,
+                &comp_def,
+                Some(owner_ref),
+                "DriftDetection",
+            )?;
+            let client = self.cluster_client(&component)?;
+            for object in objects {
+                if self.heal_object(event, &client, &object)? {
+                    healed += 1;
+                }
+            }
+        }
+        Ok(healed)
+    }
+
+    /// heal_object compares a rendered resource with the live one, creates it if it is gone and
+    /// patches it if a field rudr sets has a different value.
+    fn heal_object(
+        &self,
+        event: &OpResource,
+        client: &APIClient,
+        desired: &serde_json::Value,
+    ) -> Result<bool, Error> {
+        let name = desired["metadata"]["name"]
+            .as_str()
+            .ok_or_else(|| format_err!("rendered resource without name"))?;
+        let kind = desired["kind"].as_str().unwrap_or_default();
+        let resource = resource_api(desired, self.workload_namespace())?;
+        let action = match client.timed_request::<serde_json::Value>(resource.get(name)?) {
+            Ok(live) => {
+                if live["metadata"]["annotations"][IGNORE_DRIFT_ANNOTATION] == "true"
+                    || workload_type::is_subset(desired, &live)
+                {
+                    return Ok(false);
+                }
+                "patched"
+            }
+            Err(e) if e.api_error().map(|err| err.code) == Some(404) => "recreated",
+            Err(e) => return Err(e.into()),
+        };
+        workload_type::force_apply(client, &resource, name, desired)?;
+        info!("DriftDetection: {} {} {}", action, kind, name);
+        if let Err(err) = self.event_handler.push_event_message(
+            kube_event::Type::Normal,
+            kube_event::Info {
+                action: action.to_string(),
+                message: format!(
+                    "{} {} {} that drifted from the configuration",
+                    action, kind, name
+                ),
+                reason: "DriftCorrected".to_string(),
+            },
+            get_object_ref(event.clone()),
+        ) {
+            error!("DriftDetection: adding event err {:?}", err)
+        }
+        Ok(true)
+    }
+
+    fn history(&self, event: &OpResource) -> History {
+        History {
+            client: self.client.clone(),
+            namespace: self.namespace.clone(),
+            config_name: event.metadata.name.clone(),
+        }
+    }
+
+    /// rollback_to honors ROLLBACK_ANNOTATION: it writes the recorded revision back as the spec of
+    /// the configuration and drops the annotation. The update then applies the old spec like any
+    /// other change.
+    pub fn rollback_to(&self, event: OpResource) -> InstigatorResult {
+        let name = event.metadata.name.clone();
+        let target = event
+            .metadata
+            .annotations
+            .get(ROLLBACK_ANNOTATION)
+            .cloned()
+            .unwrap_or_default();
+        let revision = target.parse::<i64>().map_err(|e| {
+            format_err!(
+                "invalid {} annotation {:?}: {}",
+                ROLLBACK_ANNOTATION,
+                target,
+                e
+            )
+        })?;
+        let spec = self.history(&event).get(revision)?;
+        let mut config = event.clone();
+        config.spec = spec;
+        config.metadata.annotations.remove(ROLLBACK_ANNOTATION);
+        let req = RawApi::customResource(CONFIG_CRD)
+            .version(CONFIG_VERSION)
+            .group(CONFIG_GROUP)
+            .within(&self.namespace)
+            .replace(
+                name.as_str(),
+                &kube::api::PostParams::default(),
+                serde_json::to_vec(&config)?,
+            )?;
+        self.client.timed_request::<OpResource>(req)?;
+        info!(
+            "MainControlLoop: rolled {} back to revision {}",
+            name, revision
+        );
+        if let Err(err) = self.event_handler.push_event_message(
+            kube_event::Type::Normal,
+            kube_event::Info {
+                action: "rollback".to_string(),
+                message: format!("rolled back to revision {}", revision),
+                reason: "RolledBack".to_string(),
+            },
+            get_object_ref(event),
+        ) {
+            error!("adding event err {:?}", err)
+        }
+        Ok(())
+    }
+
+    /// dry_run renders a configuration annotated with DRY_RUN_ANNOTATION, logs the manifests and
+    /// lists them in an event on the configuration. Nothing is applied to the cluster.
+    pub fn dry_run(&self, event: OpResource) -> InstigatorResult {
+        let objects = self.render(&event)?;
+        let mut rendered = vec![];
+        for object in objects.iter() {
+            info!(
+                "DryRun: {} would apply:\n{}",
+                event.metadata.name,
+                serde_json::to_string_pretty(object)?
+            );
+            rendered.push(format!(
+                "{}/{}",
+                object["kind"].as_str().unwrap_or("Unknown"),
+                object["metadata"]["name"].as_str().unwrap_or("")
+            ));
+        }
+        if let Err(err) = self.event_handler.push_event_message(
+            kube_event::Type::Normal,
+            kube_event::Info {
+                action: "rendered".to_string(),
+                message: format!(
+                    "dry run would apply {} resources: {}",
+                    rendered.len(),
+                    rendered.join(", ")
+                ),
+                reason: "DryRun".to_string(),
+            },
+            get_object_ref(event.clone()),
+        ) {
+            error!("DryRun: adding event err {:?}", err)
+        }
+        Ok(())
+    }
+
+    /// ensure_finalizer adds CONFIG_FINALIZER to the configuration, so that deleting it waits
+    /// until `finalize` cleaned up everything generated for it.
+    pub fn ensure_finalizer(&self, event: &OpResource) -> InstigatorResult {
+        if has_finalizer(event) {
+            return Ok(());
+        }
+        let mut finalizers = event.metadata.finalizers.clone();
+        finalizers.push(CONFIG_FINALIZER.to_string());
+        self.patch_finalizers(event, finalizers)
+    }
+
+    /// deletion_requested returns true if the configuration has a deletionTimestamp.
+    ///
+    /// kube's ObjectMeta does not carry the deletionTimestamp, so the configuration is read untyped.
+    pub fn deletion_requested(&self, name: &str) -> Result<bool, Error> {
+        let req = RawApi::customResource(CONFIG_CRD)
+            .group(CONFIG_GROUP)
+            .version(CONFIG_VERSION)
+            .within(self.namespace.as_str())
+            .get(name)?;
+        match self.client.timed_request::<serde_json::Value>(req) {
+            Ok(cfg) => Ok(cfg["metadata"]["deletionTimestamp"].is_string()),
+            Err(e) if e.api_error().map(|err| err.code) == Some(404) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// finalize deletes every component and scope generated for a configuration that is being
+    /// deleted, and then removes CONFIG_FINALIZER so Kubernetes can delete the configuration.
+    pub fn finalize(&self, event: OpResource) -> InstigatorResult {
+        if !has_finalizer(&event) {
+            return Ok(());
+        }
+        if let Some(inst) = self.for_app(&event) {
+            return inst.finalize(event);
+        }
+        info!(
+            "MainControlLoop: finalizing configuration {}",
+            event.metadata.name
         );
-        WorkloadMetadata {
-            name: config_name,
-            instance_name,
+        if event.spec.scopes.is_some() {
+            self.exec(event.clone(), Phase::Delete)?;
+        } else {
+            let record_ann = event.metadata.annotations.get(COMPONENT_RECORD_ANNOTATION);
+            for component_record in get_record_annotation(record_ann)?.values() {
+                self.delete_component(&event, component_record.config.clone())?;
+            }
+            self.delete_app_namespace(&event)?;
+        }
+        let finalizers = event
+            .metadata
+            .finalizers
+            .iter()
+            .filter(|f| *f != CONFIG_FINALIZER)
+            .cloned()
+            .collect();
+        self.patch_finalizers(&event, finalizers)
+    }
+
+    fn patch_finalizers(&self, event: &OpResource, finalizers: Vec<String>) -> InstigatorResult {
+        // The resourceVersion makes the patch fail instead of dropping a finalizer someone else just added.
+        let patch = json!({
+            "metadata": {
+                "finalizers": finalizers,
+                "resourceVersion": event.metadata.resourceVersion,
+            }
+        });
+        let req = RawApi::customResource(CONFIG_CRD)
+            .group(CONFIG_GROUP)
+            .version(CONFIG_VERSION)
+            .within(self.namespace.as_str())
+            .patch(
+                event.metadata.name.as_str(),
+                &PatchParams::default(),
+                serde_json::to_vec(&patch)?,
+            )?;
+        self.client.timed_request::<OpResource>(req)?;
+        Ok(())
+    }
+
+    /// Create new Kubernetes objects based on this config.
+    pub fn add(&self, event: OpResource) -> InstigatorResult {
+        self.exec(event, Phase::Add)
+    }
+    /// Modify existing Kubernetes objects based on config and workload type.
+    pub fn modify(&self, event: OpResource) -> InstigatorResult {
+        self.exec(event, Phase::Modify)
+    }
+    /// Delete the Kubernetes objects associated with this config.
+    pub fn delete(&self, event: OpResource) -> InstigatorResult {
+        self.exec(event, Phase::Delete)
+    }
+
+    fn get_workload_meta(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        comp: &KubeComponent,
+        params: &ParamMap,
+        owner_ref: Option<Vec<meta::OwnerReference>>,
+        controlled_by: String,
+    ) -> Result<WorkloadMetadata, Error> {
+        info!(
+            "{}: Looking up workload for {} <{}>",
+            controlled_by, event.metadata.name, comp.metadata.name
+        );
+        // The workload is rendered with what the traits add to its pods.
+        let mut trait_manager = TraitManager {
+            config_name: event.metadata.name.clone(),
+            instance_name: component.instance_name.clone(),
+            component: component.clone(),
+            parent_params: get_variable_values(event.spec.variables.clone()),
+            owner_ref: None,
+            workload_type: comp.spec.workload_type.clone(),
+            traits: vec![], // Always starts empty.
+            component_schematic: comp.spec.clone(),
+            definitions: self.trait_definitions(component)?,
+        };
+        trait_manager.load_traits()?;
+        Ok(WorkloadMetadata {
+            name: event.metadata.name.clone(),
+            instance_name: component.instance_name.clone(),
             component_name: comp.metadata.name.clone(),
             annotations: Some(comp.metadata.annotations.clone()),
-            namespace: self.namespace.clone(),
+            propagated: Propagated {
+                labels: event.spec.propagated_labels(&event.metadata.labels),
+                annotations: event
+                    .spec
+                    .propagated_annotations(&event.metadata.annotations),
+                service_account: component
+                    .service_account
+                    .as_ref()
+                    .map(|_| component.instance_name.clone()),
+                pod_patch: trait_manager.pod_patch()?,
+            },
+            namespace: self.workload_namespace().to_string(),
             definition: comp.spec.clone(),
-            client: self.client.clone(),
+            client: self.cluster_client(component)?,
             params: params.clone(),
-            owner_ref,
-        }
+            owner_ref: local_owner(component, owner_ref),
+        })
     }
 
     fn load_workload_type(
@@ -674,49 +2168,222 @@ impl Instigator {
         }
     }
 
+    /// component_params resolves the parameter values of `component` against the parameters its
+    /// schematic declares. Values taken from Secrets and ConfigMaps are read first. A missing
+    /// required parameter, an unknown parameter or a value of the wrong type fails the component
+    /// before anything is rendered for it.
+    fn component_params(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        comp_def: &KubeComponent,
+    ) -> Result<ParamMap, Error> {
+        self.component_params_within(event, component, comp_def, &mut vec![])
+    }
+
+    /// component_params_within resolves the parameters of a component that the components in
+    /// `path` read outputs of, in that order. A component that shows up twice is a cycle.
+    fn component_params_within(
+        &self,
+        event: &OpResource,
+        component: &ComponentConfiguration,
+        comp_def: &KubeComponent,
+        path: &mut Vec<String>,
+    ) -> Result<ParamMap, Error> {
+        if path.contains(&component.instance_name) {
+            path.push(component.instance_name.clone());
+            return Err(format_err!("output cycle {}", path.join(" -> ")));
+        }
+        let with_component = |e: &dyn std::fmt::Display| {
+            format_err!(
+                "component {} of schematic {}: {}",
+                component.instance_name,
+                component.component_name,
+                e
+            )
+        };
+        let variables = event
+            .spec
+            .variables
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|var| self.resolve_variable_from(var))
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(|e| with_component(&e))?;
+        let child = component
+            .parameter_values
+            .clone()
+            .map(|values| resolve_variables(values, variables))
+            .unwrap_or_else(|| Ok(vec![]))?;
+        path.push(component.instance_name.clone());
+        let child = child
+            .into_iter()
+            .map(|value| self.resolve_from_component(event, value, path))
+            .collect::<Result<Vec<_>, Error>>();
+        path.pop();
+        let child = child
+            .map_err(|e| with_component(&e))?
+            .into_iter()
+            .map(|value| self.resolve_value_from(value))
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(|e| with_component(&e))?;
+        let params = resolve_parameters(
+            comp_def.spec.parameters.clone(),
+            resolve_values(child, vec![])?,
+        )
+        .map_err(|e| with_component(&e))?;
+        comp_def
+            .spec
+            .check_secret_params(&params)
+            .map_err(|e| with_component(&e))?;
+        Ok(params)
+    }
+
+    /// resolve_from_component replaces a fromComponent of a parameter value with the output of
+    /// the component instance it names, which is rendered without being applied.
+    fn resolve_from_component(
+        &self,
+        event: &OpResource,
+        mut value: ParameterValue,
+        path: &mut Vec<String>,
+    ) -> Result<ParameterValue, Error> {
+        let instance_name = match value.from_component.take() {
+            Some(instance_name) => instance_name,
+            None => return Ok(value),
+        };
+        let output = value.output.take().ok_or_else(|| {
+            format_err!(
+                "parameter {} reads component {} without an output",
+                value.name,
+                instance_name
+            )
+        })?;
+        let producer = event
+            .spec
+            .components
+            .iter()
+            .flatten()
+            .find(|c| c.instance_name == instance_name)
+            .ok_or_else(|| {
+                format_err!(
+                    "parameter {} reads unknown component {}",
+                    value.name,
+                    instance_name
+                )
+            })?;
+        let comp_def = get_component_def(
+            self.namespace.clone(),
+            producer.component_name.clone(),
+            self.client.clone(),
+        )?;
+        let params = self.component_params_within(event, producer, &comp_def, path)?;
+        let rendered = comp_def
+            .spec
+            .output(
+                output.as_str(),
+                instance_name.as_str(),
+                self.workload_namespace(),
+                &params,
+            )
+            .map_err(|e| {
+                format_err!(
+                    "parameter {}: component {}: {}",
+                    value.name,
+                    instance_name,
+                    e
+                )
+            })?;
+        value.value = Some(serde_json::Value::String(rendered));
+        Ok(value)
+    }
+
+    /// resolve_variable_from replaces a valueFrom of a variable with its value, see
+    /// `resolve_value_from`.
+    fn resolve_variable_from(&self, var: Variable) -> Result<Variable, Error> {
+        if var.value_from.is_none() {
+            return Ok(var);
+        }
+        let value = self.resolve_value_from(var.clone().into())?;
+        Ok(Variable {
+            value: value.value.unwrap_or_default(),
+            value_from: None,
+            ..var
+        })
+    }
+
+    /// resolve_value_from replaces a valueFrom of a parameter value with the string stored under
+    /// the referenced key of a ConfigMap. Secrets are not read, the value only names their key,
+    /// so that it ends up in the env of the containers as `valueFrom.secretKeyRef`.
+    fn resolve_value_from(&self, mut value: ParameterValue) -> Result<ParameterValue, Error> {
+        let value_from = match value.value_from.take() {
+            Some(value_from) => value_from,
+            None => return Ok(value),
+        };
+        let found = match (value_from.secret_key_ref, value_from.config_map_key_ref) {
+            (Some(key_ref), None) => {
+                value.value = Some(secret_value(&key_ref));
+                return Ok(value);
+            }
+            (None, Some(key_ref)) => {
+                let (req, _) = ConfigMap::read_namespaced_config_map(
+                    key_ref.name.as_str(),
+                    self.namespace.as_str(),
+                    Default::default(),
+                )?;
+                let config_map = self.client.timed_request::<ConfigMap>(req).map_err(|e| {
+                    format_err!(
+                        "read config map {} for parameter {}: {}",
+                        key_ref.name,
+                        value.name,
+                        e
+                    )
+                })?;
+                config_map
+                    .data
+                    .and_then(|mut data| data.remove(&key_ref.key))
+                    .ok_or_else(|| {
+                        format_err!("config map {} has no key {}", key_ref.name, key_ref.key)
+                    })?
+            }
+            _ => {
+                return Err(format_err!(
+                "valueFrom of parameter {} needs exactly one of secretKeyRef and configMapKeyRef",
+                value.name
+            ))
+            }
+        };
+        value.value = Some(serde_json::Value::String(found));
+        Ok(value)
+    }
+
+    /// get_new_own_ref returns the owner reference of the component instance of `component`,
+    /// creating the instance if it doesn't exist yet. Reconciling the same configuration again,
+    /// after a crash half way through, finds the instance it created before.
     fn get_new_own_ref(
         &self,
         phase: Phase,
         component: ComponentConfiguration,
         owner_ref: meta::OwnerReference,
     ) -> Result<Option<Vec<meta::OwnerReference>>, Error> {
-        let new = match phase {
-            Phase::Add => Some(self.create_component_instance(
-                component.component_name.clone(),
-                component.instance_name.clone(),
-                owner_ref.clone(),
-            )?),
-            Phase::Modify => {
-                let ownref = self.component_instance_owner_reference(
+        match phase {
+            Phase::Add | Phase::Modify => self
+                .ensure_component_instance(
                     component.component_name.clone(),
                     component.instance_name.clone(),
-                );
-                match ownref {
-                    Err(err) => {
-                        let e = err.to_string();
-                        if !e.contains("NotFound") {
-                            // Wrap the error to make it clear where we failed
-                            // During deletion, this might indicate that something else
-                            // remove the component instance.
-                            return Err(format_err!(
-                                "{:?} on {}: {}",
-                                phase.clone(),
-                                component.instance_name.clone(),
-                                e
-                            ));
-                        }
-                        Some(self.create_component_instance(
-                            component.component_name.clone(),
-                            component.instance_name.clone(),
-                            owner_ref.clone(),
-                        )?)
-                    }
-                    Ok(own) => Some(own),
-                }
-            }
-            _ => None,
-        };
-        Ok(new)
+                    owner_ref,
+                )
+                .map(Some)
+                .map_err(|e| {
+                    format_err!(
+                        "{:?} on {}: {}",
+                        phase.clone(),
+                        component.instance_name.clone(),
+                        e
+                    )
+                }),
+            _ => Ok(None),
+        }
     }
 
     fn delete_component_instance(
@@ -724,14 +2391,22 @@ impl Instigator {
         component_name: String,
         instance_name: String,
     ) -> InstigatorResult {
-        let name = combine_name(component_name, instance_name);
-        let pp = kube::api::DeleteParams::default();
+        self.delete_instance(combine_name(component_name, instance_name).as_str())
+    }
+
+    /// delete_instance deletes the component instance `name` and everything it owns.
+    pub fn delete_instance(&self, name: &str) -> InstigatorResult {
+        // Foreground deletion removes everything owned by the instance before the instance itself.
+        let pp = kube::api::DeleteParams {
+            propagation_policy: Some(kube::api::PropagationPolicy::Foreground),
+            ..Default::default()
+        };
         let crd_req = RawApi::customResource("componentinstances")
             .group(CONFIG_GROUP)
             .version(CONFIG_VERSION)
-            .within(self.namespace.as_str());
-        let req = crd_req.delete(name.as_str(), &pp)?;
-        if let Err(e) = self.client.request_status::<KubeComponentInstance>(req) {
+            .within(self.workload_namespace());
+        let req = crd_req.delete(name, &pp)?;
+        if let Err(e) = self.client.timed_request::<serde_json::Value>(req) {
             if e.to_string().contains("NotFound") {
                 return Ok(());
             }
@@ -740,7 +2415,104 @@ impl Instigator {
         Ok(())
     }
 
-    fn create_component_instance(
+    /// prune deletes the component instances the configuration owns that none of its components
+    /// uses anymore, together with everything they own. These are left behind when a component
+    /// was removed but could not be deleted, or its record was lost.
+    fn prune(&self, event: &OpResource) -> Result<usize, Error> {
+        let uid = match event.metadata.uid.as_ref() {
+            Some(uid) => uid,
+            None => return Ok(0),
+        };
+        let keep: Vec<String> = event
+            .spec
+            .components
+            .iter()
+            .flatten()
+            .map(|c| combine_name(c.component_name.clone(), c.instance_name.clone()))
+            .collect();
+        let req = RawApi::customResource("componentinstances")
+            .group(CONFIG_GROUP)
+            .version(CONFIG_VERSION)
+            .within(self.workload_namespace())
+            .list(&kube::api::ListParams::default())?;
+        let instances = self
+            .client
+            .timed_request::<kube::api::ObjectList<serde_json::Value>>(req)?;
+        let orphans = orphaned_instances(&instances.items, uid, &keep);
+        for name in orphans.iter() {
+            info!(
+                "MainControlLoop: pruning component instance {} of {}",
+                name, event.metadata.name
+            );
+            self.delete_instance(name.as_str())?;
+        }
+        Ok(orphans.len())
+    }
+
+    /// prune_traits deletes the resources of traits that were removed from a component since its
+    /// `previous` configuration was applied.
+    fn prune_traits(
+        &self,
+        event: &OpResource,
+        previous: &ComponentConfiguration,
+        component: &ComponentConfiguration,
+        comp_def: &KubeComponent,
+    ) -> InstigatorResult {
+        let current: Vec<&str> = component
+            .traits
+            .iter()
+            .flatten()
+            .map(|t| t.name.as_str())
+            .collect();
+        let removed: Vec<TraitBinding> = previous
+            .traits
+            .iter()
+            .flatten()
+            .filter(|t| !current.contains(&t.name.as_str()))
+            .cloned()
+            .collect();
+        if removed.is_empty() {
+            return Ok(());
+        }
+        let mut old = previous.clone();
+        old.traits = Some(removed);
+        let variables = event.spec.variables.clone().unwrap_or_default();
+        let mut trait_manager = TraitManager {
+            config_name: event.metadata.name.clone(),
+            instance_name: old.instance_name.clone(),
+            component: old.clone(),
+            parent_params: get_variable_values(Some(variables)),
+            owner_ref: None,
+            workload_type: comp_def.spec.workload_type.clone(),
+            traits: vec![], // Always starts empty.
+            component_schematic: comp_def.spec.clone(),
+            definitions: self.trait_definitions(&old)?,
+        };
+        trait_manager.load_traits()?;
+        let instance = self
+            .component_instance_owner_reference(
+                old.component_name.clone(),
+                old.instance_name.clone(),
+            )
+            .ok()
+            .and_then(|refs| refs.into_iter().next());
+        self.report_traits(
+            event,
+            instance.as_ref(),
+            &old,
+            "delete",
+            trait_manager.exec(
+                self.workload_namespace(),
+                self.cluster_client(&old)?,
+                Phase::Delete,
+            ),
+        )
+    }
+
+    /// ensure_component_instance returns the owner reference of the component instance, creating
+    /// it if it doesn't exist. An instance of the same name that belongs to another
+    /// configuration is an error, rather than adopted.
+    fn ensure_component_instance(
         &self,
         component_name: String,
         instance_name: String,
@@ -751,12 +2523,27 @@ impl Instigator {
         let crd_req = RawApi::customResource("componentinstances")
             .group(CONFIG_GROUP)
             .version(CONFIG_VERSION)
-            .within(self.namespace.as_str());
-        let comp_inst = json!({
+            .within(self.workload_namespace());
+        let existing = match self
+            .client
+            .timed_request::<KubeComponentInstance>(crd_req.get(name.as_str())?)
+        {
+            Ok(existing) => Some(existing),
+            Err(e) if e.api_error().map(|err| err.code) == Some(404) => None,
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(existing) = existing {
+            check_instance_owner(&existing, owner.uid.as_str())?;
+            return instance_owner_reference(existing);
+        }
+        let mut comp_inst = json!({
             "apiVersion": OAM_API_VERSION,
             "kind": "ComponentInstance",
             "metadata": {
                 "name": name.clone(),
+                "labels": {
+                    CONFIG_UID_LABEL: owner.uid.clone(),
+                },
                 "ownerReferences": [{
                     "apiVersion": OAM_API_VERSION,
                     "kind": "ApplicationConfiguration",
@@ -770,15 +2557,25 @@ impl Instigator {
                 "traits": []
             }
         });
+        // The configuration lives in another namespace, where the garbage collector wouldn't find
+        // it. The instance goes with the namespace instead.
+        if self.app_namespace.is_some() {
+            if let Some(metadata) = comp_inst["metadata"].as_object_mut() {
+                metadata.remove("ownerReferences");
+            }
+        }
 
         let req = crd_req.create(&pp, serde_json::to_vec(&comp_inst)?)?;
-        let res: KubeComponentInstance = match self.client.request(req) {
+        let res: KubeComponentInstance = match self.client.timed_request(req) {
             Ok(res) => res,
             Err(e) => {
                 if let Some(api_err) = e.api_error() {
                     if api_err.reason == "AlreadyExists" {
+                        // Created by another reconciliation since we looked.
                         let req = crd_req.get(name.as_str())?;
-                        self.client.request(req)?
+                        let res = self.client.timed_request(req)?;
+                        check_instance_owner(&res, owner.uid.as_str())?;
+                        res
                     } else {
                         return Err(e.into());
                     }
@@ -787,21 +2584,8 @@ impl Instigator {
                 }
             }
         };
-
-        if res.metadata.uid.is_none() {
-            return Err(format_err!("UID was not set on component instance"));
-        }
-        info!("UID: {}", res.metadata.uid.clone().unwrap());
-
-        let new_owner = meta::OwnerReference {
-            api_version: OAM_API_VERSION.into(),
-            kind: "ComponentInstance".into(),
-            uid: res.metadata.uid.unwrap(),
-            controller: Some(true),
-            block_owner_deletion: Some(true),
-            name,
-        };
-        Ok(vec![new_owner])
+        info!("UID: {}", res.metadata.uid.clone().unwrap_or_default());
+        instance_owner_reference(res)
     }
 
     fn component_instance_owner_reference(
@@ -813,43 +2597,85 @@ impl Instigator {
         let crd_req = RawApi::customResource("componentinstances")
             .group(CONFIG_GROUP)
             .version(CONFIG_VERSION)
-            .within(self.namespace.as_str());
+            .within(self.workload_namespace());
         let req = crd_req.get(name.as_str())?;
-        let res: KubeComponentInstance = self.client.request(req)?;
-
-        let owner = meta::OwnerReference {
-            api_version: OAM_API_VERSION.into(),
-            kind: "ComponentInstance".into(),
-            uid: res.metadata.uid.unwrap(),
-            controller: Some(true),
-            block_owner_deletion: Some(true),
-            name: res.metadata.name,
-        };
-        Ok(vec![owner])
+        let res: KubeComponentInstance = self.client.timed_request(req)?;
+        instance_owner_reference(res)
     }
 
+    /// component_instance_set_status writes the observed `status` of a component instance. It is
+    /// only written when it changed, with the time of the change.
     fn component_instance_set_status(
         &self,
         component_name: String,
         instance_name: String,
-        status: String,
+        mut status: ComponentInstanceStatus,
     ) -> Result<(), Error> {
         let name = combine_name(component_name, instance_name);
         let crd_req = RawApi::customResource("componentinstances")
             .group(CONFIG_GROUP)
             .version(CONFIG_VERSION)
-            .within(self.namespace.as_str());
+            .within(self.workload_namespace());
         let req = crd_req.get(name.as_str())?;
-        let mut res: KubeComponentInstance = self.client.request(req)?;
-        res.status = Some(status);
+        let res: KubeComponentInstance = self.client.timed_request(req)?;
+        if let Some(InstanceStatus::Status(current)) = res.status {
+            status.last_update_time = current.last_update_time.clone();
+            if current == status {
+                return Ok(());
+            }
+        }
+        status.last_update_time = Some(chrono::Utc::now().to_rfc3339());
         let req = crd_req.patch(
             name.as_str(),
             &PatchParams::default(),
-            serde_json::to_vec(&res)?,
+            serde_json::to_vec(&json!({ "status": status }))?,
         )?;
-        let _: KubeComponentInstance = self.client.request(req)?;
+        let _: KubeComponentInstance = self.client.timed_request(req)?;
         Ok(())
     }
+
+    /// observe_instance reads the live workload objects and pods of a component instance for its
+    /// status. Objects that can't be read are left out.
+    fn observe_instance(
+        &self,
+        client: &APIClient,
+        workload: &dyn WorkloadType,
+        instance_name: &str,
+        state: &ComponentState,
+        resources: &BTreeMap<String, String>,
+    ) -> ComponentInstanceStatus {
+        let namespace = self.workload_namespace();
+        let live: Vec<serde_json::Value> = workload
+            .render()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|object| {
+                let name = object["metadata"]["name"].as_str()?;
+                let req = resource_api(object, namespace).ok()?.get(name).ok()?;
+                client.timed_request::<serde_json::Value>(req).ok()
+            })
+            .collect();
+        let lp = kube::api::ListParams {
+            label_selector: Some(format!("oam.dev/instance-name={}", instance_name)),
+            ..Default::default()
+        };
+        let pods = RawApi::v1Pod()
+            .within(namespace)
+            .list(&lp)
+            .map_err(Error::from)
+            .and_then(|req| {
+                Ok(client.timed_request::<kube::api::ObjectList<serde_json::Value>>(req)?)
+            })
+            .map(|pods| pods.items)
+            .unwrap_or_else(|e| {
+                warn!(
+                    "StatusCheckLoop: list pods of {} err {:?}",
+                    instance_name, e
+                );
+                vec![]
+            });
+        ComponentInstanceStatus::observe(state, resources, &live, &pods)
+    }
 }
 
 pub fn get_object_ref(event: OpResource) -> ObjectReference {
@@ -864,10 +2690,489 @@ pub fn get_object_ref(event: OpResource) -> ObjectReference {
     }
 }
 
+/// dependency_order sorts components so that every component comes after the components it
+/// depends on. Unknown dependencies, dependency cycles and instance names used twice are errors.
+pub fn dependency_order(
+    components: Vec<ComponentConfiguration>,
+) -> Result<Vec<ComponentConfiguration>, Error> {
+    check_instance_names(&components)?;
+    for component in components.iter() {
+        for dep in component.depends_on.iter().flatten() {
+            if !components.iter().any(|c| &c.instance_name == dep) {
+                return Err(format_err!(
+                    "component {} depends on unknown component {}",
+                    component.instance_name,
+                    dep
+                ));
+            }
+        }
+    }
+    let mut ordered: Vec<ComponentConfiguration> = vec![];
+    let mut remaining = components;
+    while !remaining.is_empty() {
+        let (ready, blocked): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|c| {
+            c.depends_on
+                .iter()
+                .flatten()
+                .all(|dep| ordered.iter().any(|o| &o.instance_name == dep))
+        });
+        if ready.is_empty() {
+            return Err(format_err!(
+                "dependency cycle between components {}",
+                blocked
+                    .iter()
+                    .map(|c| c.instance_name.clone())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+        ordered.extend(ready);
+        remaining = blocked;
+    }
+    Ok(ordered)
+}
+
+pub fn is_dry_run(event: &OpResource) -> bool {
+    event
+        .metadata
+        .annotations
+        .get(DRY_RUN_ANNOTATION)
+        .map(|v| v == "true")
+        == Some(true)
+}
+
+lazy_static! {
+    static ref IN_FLIGHT: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// InFlight marks a component instance, `<namespace>/<instance name>`, as being applied until it
+/// is dropped. At most one mark of an instance exists at a time.
+pub struct InFlight(String);
+
+impl InFlight {
+    /// claim marks `key` as being applied, None if it already is.
+    pub fn claim(key: String) -> Option<InFlight> {
+        if IN_FLIGHT.lock().unwrap().insert(key.clone()) {
+            Some(InFlight(key))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// within runs `f` and returns its result, or fails once `timeout` has passed. `f` runs in a
+/// thread of its own that is left running when it times out.
+pub fn within<T, F>(timeout: Option<Duration>, f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return f(),
+    };
+    let (tx, rx) = std::sync::mpsc::channel();
+    let parent = trace::current();
+    let cause = audit::cause();
+    thread::spawn(move || {
+        let _attached = trace::attach(parent);
+        let _caused = audit::caused_by(cause);
+        // Nobody waits for the result anymore if it timed out.
+        let _ = tx.send(f());
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(res) => res,
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+            Err(format_err!("not done within {:?}", timeout))
+        }
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => Err(format_err!("panicked")),
+    }
+}
+
+pub fn is_paused(event: &OpResource) -> bool {
+    event
+        .metadata
+        .annotations
+        .get(PAUSED_ANNOTATION)
+        .map(|v| v == "true")
+        == Some(true)
+}
+
+pub fn is_rollback(event: &OpResource) -> bool {
+    event.metadata.annotations.contains_key(ROLLBACK_ANNOTATION)
+}
+
+pub fn has_finalizer(event: &OpResource) -> bool {
+    event
+        .metadata
+        .finalizers
+        .iter()
+        .any(|f| f == CONFIG_FINALIZER)
+}
+
+/// observed_generation is the metadata.generation of the configuration, for its status.
+fn observed_generation(event: &OpResource) -> Option<i64> {
+    event.metadata.generation.map(|g| g as i64)
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// orphaned_instances returns the names of the component instances owned by the configuration
+/// with `uid` that are not in `keep`.
+pub fn orphaned_instances(
+    instances: &[serde_json::Value],
+    uid: &str,
+    keep: &[String],
+) -> Vec<String> {
+    instances
+        .iter()
+        .filter(|inst| {
+            inst["metadata"]["ownerReferences"]
+                .as_array()
+                .map(|refs| refs.iter().any(|r| r["uid"] == uid))
+                .unwrap_or(false)
+                || inst["metadata"]["labels"][CONFIG_UID_LABEL] == uid
+        })
+        .filter_map(|inst| inst["metadata"]["name"].as_str())
+        .filter(|name| !keep.iter().any(|k| k == name))
+        .map(String::from)
+        .collect()
+}
+
+/// config_uid is the uid of the configuration a component instance belongs to: the uid of its
+/// owner, or of its CONFIG_UID_LABEL in the namespace of an application.
+fn config_uid(instance: &serde_json::Value) -> Option<&str> {
+    let metadata = &instance["metadata"];
+    metadata["ownerReferences"]
+        .as_array()
+        .and_then(|refs| {
+            refs.iter()
+                .find(|r| r["kind"] == "ApplicationConfiguration")
+                .and_then(|r| r["uid"].as_str())
+        })
+        .or_else(|| metadata["labels"][CONFIG_UID_LABEL].as_str())
+}
+
+/// created_before is true if the object was created before `before`. Objects without a
+/// creationTimestamp are never old enough.
+fn created_before(object: &serde_json::Value, before: chrono::DateTime<chrono::Utc>) -> bool {
+    object["metadata"]["creationTimestamp"]
+        .as_str()
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&chrono::Utc) < before)
+        == Some(true)
+}
+
+/// stale_instances returns the namespace and name of the component instances created before
+/// `before` whose configuration is none of `live_uids`. These are left behind when a
+/// configuration went away while rudr couldn't clean up after it. Instances that don't tell
+/// which configuration they belong to are kept.
+pub fn stale_instances(
+    instances: &[serde_json::Value],
+    live_uids: &[String],
+    before: chrono::DateTime<chrono::Utc>,
+) -> Vec<(String, String)> {
+    instances
+        .iter()
+        .filter(|inst| created_before(inst, before))
+        .filter(|inst| {
+            config_uid(inst).map(|uid| !live_uids.iter().any(|l| l == uid)) == Some(true)
+        })
+        .filter_map(|inst| {
+            let metadata = &inst["metadata"];
+            Some((
+                metadata["namespace"].as_str()?.to_string(),
+                metadata["name"].as_str()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// stale_app_namespaces returns the namespaces created before `before` for a configuration that
+/// is none of `live`, given as namespace and name.
+pub fn stale_app_namespaces(
+    namespaces: &[serde_json::Value],
+    live: &[(String, String)],
+    before: chrono::DateTime<chrono::Utc>,
+) -> Vec<String> {
+    namespaces
+        .iter()
+        .filter(|ns| created_before(ns, before))
+        .filter_map(|ns| {
+            let labels = &ns["metadata"]["labels"];
+            let config = (
+                labels[APP_CONFIG_NAMESPACE_LABEL].as_str()?.to_string(),
+                labels[APP_CONFIG_LABEL].as_str()?.to_string(),
+            );
+            if live.contains(&config) {
+                None
+            } else {
+                ns["metadata"]["name"].as_str().map(String::from)
+            }
+        })
+        .collect()
+}
+
+/// local_owner returns the owner references of the resources of `component` in the cluster
+/// they are created in. The component instance that owns them is in the cluster rudr runs in, so
+/// the resources of a component in a remote cluster are not owned, and deleted by rudr alone.
+pub fn local_owner(
+    component: &ComponentConfiguration,
+    owner_ref: Option<Vec<meta::OwnerReference>>,
+) -> Option<Vec<meta::OwnerReference>> {
+    match component.cluster {
+        Some(_) => None,
+        None => owner_ref,
+    }
+}
+
+/// app_namespace is the name of the namespace the components of configuration `config_name` in
+/// `namespace` are created in, when every configuration gets a namespace of its own.
+pub fn app_namespace(namespace: &str, config_name: &str) -> String {
+    format!("{}-{}", namespace, config_name)
+}
+
+/// owns_namespace is true if the namespace with `labels` was created for `event` in `namespace`.
+pub fn owns_namespace(
+    labels: &BTreeMap<String, String>,
+    namespace: &str,
+    event: &OpResource,
+) -> bool {
+    labels.get(APP_CONFIG_LABEL) == Some(&event.metadata.name)
+        && labels.get(APP_CONFIG_NAMESPACE_LABEL).map(String::as_str) == Some(namespace)
+}
+
+/// hook_job_name is the name of the Job that runs `hook` of a component for the `generation` of
+/// its configuration.
+pub fn hook_job_name(instance_name: &str, hook: &Hook, generation: i64) -> String {
+    format!("{}-{}-{}", instance_name, hook.name, generation)
+}
+
+/// job_outcome is the result of a Job once it completed or failed, None while it runs.
+pub fn job_outcome(status: Option<&batch::JobStatus>) -> Option<InstigatorResult> {
+    let status = status?;
+    if status.succeeded.unwrap_or_default() > 0 {
+        return Some(Ok(()));
+    }
+    status
+        .conditions
+        .iter()
+        .flatten()
+        .find(|c| c.type_ == "Failed" && c.status == "True")
+        .map(|c| {
+            Err(format_err!(
+                "job failed: {}",
+                c.message
+                    .clone()
+                    .unwrap_or_else(|| c.reason.clone().unwrap_or_default())
+            ))
+        })
+}
+
+/// resource_api is the API of the kind of a rendered resource in `namespace`.
+pub fn resource_api(object: &serde_json::Value, namespace: &str) -> Result<RawApi, Error> {
+    let api_version = object["apiVersion"]
+        .as_str()
+        .ok_or_else(|| format_err!("resource without apiVersion"))?;
+    let kind = object["kind"]
+        .as_str()
+        .ok_or_else(|| format_err!("resource without kind"))?;
+    let (prefix, group, version) = match api_version.rfind('/') {
+        Some(i) => ("apis", &api_version[..i], &api_version[i + 1..]),
+        None => ("api", "", api_version),
+    };
+    Ok(RawApi {
+        resource: form_plural(kind.to_lowercase().as_str()),
+        group: group.to_string(),
+        namespace: Some(namespace.to_string()),
+        version: version.to_string(),
+        prefix: prefix.to_string(),
+    })
+}
+
+/// scope_error counts a failed scope operation in the reconcile error metrics.
+fn scope_error(e: Error) -> Error {
+    metrics::inc_error("scope");
+    e
+}
+
+/// The longest name of a custom resource.
+const MAX_NAME_LENGTH: usize = 253;
+
 /// combine_name combine component name with instance_name,
-/// so we won't afraid different components using same instance_name   
+/// so we won't afraid different components using same instance_name.
+/// Names too long for a resource are cut and end in a hash of the whole name, so that they stay
+/// the same on every reconciliation and distinct from each other.
 pub fn combine_name(component_name: String, instance_name: String) -> String {
-    component_name + "-" + instance_name.as_str()
+    let name = component_name + "-" + instance_name.as_str();
+    if name.len() <= MAX_NAME_LENGTH {
+        return name;
+    }
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in name.bytes() {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    let mut end = MAX_NAME_LENGTH - 9;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}-{:08x}", name[..end].trim_end_matches('-'), hash)
+}
+
+/// check_instance_names fails if two components of a configuration have the same instance
+/// name, or their component instances would.
+pub fn check_instance_names(components: &[ComponentConfiguration]) -> Result<(), Error> {
+    for (i, component) in components.iter().enumerate() {
+        let name = combine_name(
+            component.component_name.clone(),
+            component.instance_name.clone(),
+        );
+        for other in components[..i].iter() {
+            if other.instance_name == component.instance_name {
+                return Err(format_err!(
+                    "more than one component has the instance name {}",
+                    component.instance_name
+                ));
+            }
+            if combine_name(other.component_name.clone(), other.instance_name.clone()) == name {
+                return Err(format_err!(
+                    "components {} and {} both have the component instance {}",
+                    other.instance_name,
+                    component.instance_name,
+                    name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// check_trait_conflicts fails if the traits of a component would fight over it: two traits
+/// that set its replicas, two ingress traits for the same host, or another trait taken twice.
+pub fn check_trait_conflicts(components: &[ComponentConfiguration]) -> Result<(), Error> {
+    for component in components {
+        let bindings = component.traits.as_deref().unwrap_or_default();
+        for (i, binding) in bindings.iter().enumerate() {
+            let name = binding.name.as_str();
+            for other in bindings[..i].iter().map(|b| b.name.as_str()) {
+                if SCALER_TRAITS.contains(&name) && SCALER_TRAITS.contains(&other) {
+                    return Err(format_err!(
+                        "component {} has both the traits {} and {}, which set its replicas",
+                        component.instance_name,
+                        other,
+                        name
+                    ));
+                }
+            }
+            if REPEATABLE_TRAITS.contains(&name) {
+                continue;
+            }
+            let earlier = match bindings[..i].iter().find(|b| b.name == binding.name) {
+                Some(earlier) => earlier,
+                None => continue,
+            };
+            if name == traits::INGRESS_V1ALPHA1 {
+                let hosts = traits::ingress_hosts(properties_of(earlier));
+                if let Some(host) = traits::ingress_hosts(properties_of(binding))
+                    .into_iter()
+                    .find(|h| hosts.contains(h))
+                {
+                    return Err(format_err!(
+                        "component {} has two ingress traits for the host {}",
+                        component.instance_name,
+                        host
+                    ));
+                }
+            }
+            return Err(format_err!(
+                "component {} has the trait {} more than once",
+                component.instance_name,
+                name
+            ));
+        }
+        check_backup_volumes(component, bindings)?;
+    }
+    Ok(())
+}
+
+/// check_backup_volumes fails if the backup trait of a component backs up a volume that no
+/// volume-mounter trait of the component mounts: there would be no PVC to back up.
+fn check_backup_volumes(
+    component: &ComponentConfiguration,
+    bindings: &[TraitBinding],
+) -> Result<(), Error> {
+    let mounted: Vec<&str> = bindings
+        .iter()
+        .filter(|b| b.name == traits::VOLUME_MOUNTER_V1ALPHA1)
+        .filter_map(|b| properties_of(b).and_then(|p| p.get("volumeName")))
+        .filter_map(|v| v.as_str())
+        .collect();
+    for binding in bindings
+        .iter()
+        .filter(|b| b.name == traits::BACKUP_V1ALPHA1)
+    {
+        if let Some(volume) = traits::backup_volumes(properties_of(binding))
+            .into_iter()
+            .find(|v| !mounted.contains(&v.as_str()))
+        {
+            return Err(format_err!(
+                "component {} backs up the volume {}, which no volume-mounter trait mounts",
+                component.instance_name,
+                volume
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn properties_of(binding: &TraitBinding) -> Option<&serde_json::Map<String, serde_json::Value>> {
+    binding.properties.as_ref().and_then(|p| p.as_object())
+}
+
+/// check_instance_owner fails if the component instance `instance` belongs to another
+/// configuration than the one with `config_uid`.
+pub fn check_instance_owner(
+    instance: &KubeComponentInstance,
+    config_uid: &str,
+) -> Result<(), Error> {
+    match instance.metadata.labels.get(CONFIG_UID_LABEL) {
+        Some(uid) if uid != config_uid => Err(format_err!(
+            "component instance {} belongs to another configuration",
+            instance.metadata.name
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// instance_owner_reference is the reference the resources of a component instance are owned by.
+fn instance_owner_reference(
+    instance: KubeComponentInstance,
+) -> Result<Vec<meta::OwnerReference>, Error> {
+    let uid = instance
+        .metadata
+        .uid
+        .ok_or_else(|| format_err!("UID was not set on component instance"))?;
+    Ok(vec![meta::OwnerReference {
+        api_version: OAM_API_VERSION.into(),
+        kind: "ComponentInstance".into(),
+        uid,
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+        name: instance.metadata.name,
+    }])
 }
 
 /// Build an owner reference for the given parent UID of kind Configuration.
@@ -920,7 +3225,7 @@ pub fn get_component_def(
         .group("core.oam.dev")
         .within(&namespace);
     let comp_def_req = component_resource.get(comp_name.as_str())?;
-    let comp_def: KubeComponent = match client.request::<KubeComponent>(comp_def_req) {
+    let comp_def: KubeComponent = match client.timed_request::<KubeComponent>(comp_def_req) {
         Ok(comp) => comp,
         Err(err) => {
             return Err(format_err!(
@@ -1036,7 +3341,7 @@ fn get_scope_instance(
         .version(CONFIG_VERSION);
     //init all the existing objects at initiate, this should be done by informer
     let req = resource.get(name.as_str())?;
-    let cfg = client.request::<KubeOpsConfig>(req)?;
+    let cfg = client.timed_request::<KubeOpsConfig>(req)?;
     for scope_binding in cfg.spec.scopes.clone().unwrap_or_else(|| vec![]).iter() {
         let param = scope_binding
             .parameter_values
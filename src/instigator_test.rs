@@ -1,6 +1,8 @@
 use crate::instigator::*;
+use crate::schematic::component_instance::KubeComponentInstance;
 use crate::schematic::configuration::ComponentConfiguration;
 use std::collections::BTreeMap;
+use std::time::Duration;
 
 #[test]
 fn test_config_owner_reference() {
@@ -30,6 +32,10 @@ fn test_record_ann() {
             parameter_values: None,
             traits: None,
             application_scopes: None,
+            depends_on: None,
+            hooks: None,
+            cluster: None,
+            service_account: None,
         },
     };
     let cr2 = ComponentRecord {
@@ -40,6 +46,10 @@ fn test_record_ann() {
             parameter_values: None,
             traits: None,
             application_scopes: None,
+            depends_on: None,
+            hooks: None,
+            cluster: None,
+            service_account: None,
         },
     };
     one.insert("comp1".to_string(), cr.clone());
@@ -64,6 +74,10 @@ fn test_check_diff() {
             parameter_values: None,
             traits: None,
             application_scopes: None,
+            depends_on: None,
+            hooks: None,
+            cluster: None,
+            service_account: None,
         },
     };
     let old_record = ComponentRecord {
@@ -74,6 +88,10 @@ fn test_check_diff() {
             parameter_values: None,
             traits: None,
             application_scopes: None,
+            depends_on: None,
+            hooks: None,
+            cluster: None,
+            service_account: None,
         },
     };
 
@@ -88,6 +106,10 @@ fn test_check_diff() {
             parameter_values: None,
             traits: None,
             application_scopes: None,
+            depends_on: None,
+            hooks: None,
+            cluster: None,
+            service_account: None,
         },
     };
     assert_eq!(check_diff(Some(new_record2), &old_record), true);
@@ -99,6 +121,10 @@ fn test_check_diff() {
             parameter_values: Some(vec![]),
             traits: None,
             application_scopes: None,
+            depends_on: None,
+            hooks: None,
+            cluster: None,
+            service_account: None,
         },
     };
     assert_eq!(check_diff(Some(new_record3), &old_record), true);
@@ -109,3 +135,407 @@ fn test_combine_name() {
     let name = combine_name("component-a".to_string(), "instance-b".to_string());
     assert_eq!("component-a-instance-b", name.as_str())
 }
+
+#[test]
+fn test_combine_long_name() {
+    let long = "x".repeat(300);
+    let name = combine_name("component".to_string(), long.clone());
+    assert_eq!(253, name.len());
+    // The same every time, and different for another long name.
+    assert_eq!(name, combine_name("component".to_string(), long.clone()));
+    assert_ne!(
+        name,
+        combine_name("component".to_string(), long.clone() + "y")
+    );
+}
+
+#[test]
+fn test_check_instance_names() {
+    let component = |component_name: &str, instance_name: &str| -> ComponentConfiguration {
+        serde_json::from_value(serde_json::json!({
+            "componentName": component_name,
+            "instanceName": instance_name,
+        }))
+        .expect("component")
+    };
+    assert!(check_instance_names(&[component("web", "a"), component("web", "b")]).is_ok());
+    assert!(check_instance_names(&[component("web", "a"), component("db", "a")]).is_err());
+    let err = check_instance_names(&[component("a-b", "c"), component("a", "b-c")])
+        .expect_err("same component instance");
+    assert_eq!(
+        "components c and b-c both have the component instance a-b-c",
+        err.to_string()
+    );
+}
+
+#[test]
+fn test_check_trait_conflicts() {
+    let component = |traits: serde_json::Value| -> ComponentConfiguration {
+        serde_json::from_value(serde_json::json!({
+            "componentName": "web",
+            "instanceName": "web-a",
+            "traits": traits,
+        }))
+        .expect("component")
+    };
+    assert!(check_trait_conflicts(&[component(serde_json::json!([
+        {"name": "manual-scaler", "properties": {"replicaCount": 3}},
+        {"name": "sidecar", "properties": {"name": "proxy"}},
+        {"name": "sidecar", "properties": {"name": "agent"}},
+    ]))])
+    .is_ok());
+    let err = check_trait_conflicts(&[component(serde_json::json!([
+        {"name": "manual-scaler", "properties": {"replicaCount": 3}},
+        {"name": "auto-scaler", "properties": {"maximum": 6}},
+    ]))])
+    .expect_err("two scalers");
+    assert_eq!(
+        "component web-a has both the traits manual-scaler and auto-scaler, which set its replicas",
+        err.to_string()
+    );
+    let err = check_trait_conflicts(&[component(serde_json::json!([
+        {"name": "ingress", "properties": {"hostname": "shop.example.com"}},
+        {"name": "ingress", "properties": {"rules": [{"host": "shop.example.com"}]}},
+    ]))])
+    .expect_err("two ingresses for a host");
+    assert_eq!(
+        "component web-a has two ingress traits for the host shop.example.com",
+        err.to_string()
+    );
+    assert!(check_trait_conflicts(&[component(serde_json::json!([
+        {"name": "dns", "properties": {}},
+        {"name": "dns", "properties": {}},
+    ]))])
+    .is_err());
+    assert!(check_trait_conflicts(&[component(serde_json::json!([
+        {"name": "volume-mounter", "properties": {"volumeName": "data", "storageClass": "default"}},
+        {"name": "backup", "properties": {"volumes": ["data"], "schedule": "0 3 * * *"}},
+    ]))])
+    .is_ok());
+    let err = check_trait_conflicts(&[component(serde_json::json!([
+        {"name": "backup", "properties": {"volumes": ["data"], "schedule": "0 3 * * *"}},
+    ]))])
+    .expect_err("backup without the volume");
+    assert_eq!(
+        "component web-a backs up the volume data, which no volume-mounter trait mounts",
+        err.to_string()
+    );
+}
+
+#[test]
+fn test_check_instance_owner() {
+    let instance = |labels: serde_json::Value| -> KubeComponentInstance {
+        serde_json::from_value(serde_json::json!({
+            "apiVersion": "core.oam.dev/v1alpha1",
+            "kind": "ComponentInstance",
+            "metadata": {"name": "web-a", "labels": labels},
+            "spec": {"traits": []},
+        }))
+        .expect("component instance")
+    };
+    assert!(
+        check_instance_owner(&instance(serde_json::json!({CONFIG_UID_LABEL: "1"})), "1").is_ok()
+    );
+    assert!(
+        check_instance_owner(&instance(serde_json::json!({CONFIG_UID_LABEL: "2"})), "1").is_err()
+    );
+    // Instances created before the label was set are adopted.
+    assert!(check_instance_owner(&instance(serde_json::json!({})), "1").is_ok());
+}
+
+#[test]
+fn test_has_finalizer() {
+    let config = |finalizers: Vec<&str>| -> OpResource {
+        serde_json::from_value(serde_json::json!({
+            "apiVersion": "core.oam.dev/v1alpha1",
+            "kind": "ApplicationConfiguration",
+            "metadata": {"name": "config", "finalizers": finalizers},
+            "spec": {},
+        }))
+        .expect("decode configuration")
+    };
+    assert!(!has_finalizer(&config(vec![])));
+    assert!(!has_finalizer(&config(vec!["other"])));
+    assert!(has_finalizer(&config(vec!["other", CONFIG_FINALIZER])));
+}
+
+#[test]
+fn test_is_paused() {
+    let config = |annotations: serde_json::Value| -> OpResource {
+        serde_json::from_value(serde_json::json!({
+            "apiVersion": "core.oam.dev/v1alpha1",
+            "kind": "ApplicationConfiguration",
+            "metadata": {"name": "config", "annotations": annotations},
+            "spec": {},
+        }))
+        .expect("decode configuration")
+    };
+    assert!(!is_paused(&config(serde_json::json!({}))));
+    assert!(!is_paused(&config(
+        serde_json::json!({PAUSED_ANNOTATION: "false"})
+    )));
+    assert!(is_paused(&config(
+        serde_json::json!({PAUSED_ANNOTATION: "true"})
+    )));
+}
+
+#[test]
+fn test_app_namespace() {
+    let config: OpResource = serde_json::from_value(serde_json::json!({
+        "apiVersion": "core.oam.dev/v1alpha1",
+        "kind": "ApplicationConfiguration",
+        "metadata": {"name": "shop"},
+        "spec": {},
+    }))
+    .expect("decode configuration");
+    assert_eq!("default-shop", app_namespace("default", "shop"));
+
+    let labels = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+    let owned = labels(&[
+        (APP_CONFIG_LABEL, "shop"),
+        (APP_CONFIG_NAMESPACE_LABEL, "default"),
+    ]);
+    assert!(owns_namespace(&owned, "default", &config));
+    // A configuration of the same name in another namespace doesn't own it.
+    assert!(!owns_namespace(&owned, "staging", &config));
+    assert!(!owns_namespace(&labels(&[]), "default", &config));
+}
+
+#[test]
+fn test_local_owner() {
+    let mut component: ComponentConfiguration = serde_json::from_value(serde_json::json!({
+        "componentName": "web",
+        "instanceName": "web",
+    }))
+    .expect("decode component");
+    let owner = Some(vec![
+        k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference {
+            api_version: "core.oam.dev/v1alpha1".to_string(),
+            kind: "ComponentInstance".to_string(),
+            name: "web-web".to_string(),
+            uid: "1".to_string(),
+            ..Default::default()
+        },
+    ]);
+    assert_eq!(owner, local_owner(&component, owner.clone()));
+    // The owner would be missing in the remote cluster, and its garbage collector would delete
+    // the resources right away.
+    component.cluster = Some("us-east".to_string());
+    assert_eq!(None, local_owner(&component, owner));
+}
+
+#[test]
+fn test_dependency_order() {
+    let component = |name: &str, deps: &[&str]| ComponentConfiguration {
+        component_name: name.to_string(),
+        instance_name: name.to_string(),
+        parameter_values: None,
+        traits: None,
+        application_scopes: None,
+        depends_on: Some(deps.iter().map(|d| d.to_string()).collect()),
+        hooks: None,
+        cluster: None,
+        service_account: None,
+    };
+    let names = |components: Vec<ComponentConfiguration>| -> Vec<String> {
+        components.into_iter().map(|c| c.instance_name).collect()
+    };
+
+    let ordered = dependency_order(vec![
+        component("api", &["db", "cache"]),
+        component("db", &[]),
+        component("web", &["api"]),
+        component("cache", &[]),
+    ])
+    .expect("components without cycle are ordered");
+    assert_eq!(names(ordered), vec!["db", "cache", "api", "web"]);
+
+    let err = dependency_order(vec![component("api", &["db"])]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "component api depends on unknown component db"
+    );
+
+    let err = dependency_order(vec![
+        component("a", &["b"]),
+        component("b", &["a"]),
+        component("c", &[]),
+    ])
+    .unwrap_err();
+    assert_eq!(err.to_string(), "dependency cycle between components a, b");
+}
+
+#[test]
+fn test_resource_api() {
+    let deployment = serde_json::json!({"apiVersion": "apps/v1", "kind": "Deployment"});
+    let api = resource_api(&deployment, "default").expect("resource api");
+    assert_eq!("apis", api.prefix);
+    assert_eq!("apps", api.group);
+    assert_eq!("v1", api.version);
+    assert_eq!("deployments", api.resource);
+
+    let service = serde_json::json!({"apiVersion": "v1", "kind": "Service"});
+    let api = resource_api(&service, "default").expect("resource api");
+    assert_eq!("api", api.prefix);
+    assert_eq!("", api.group);
+    assert_eq!("services", api.resource);
+
+    assert!(resource_api(&serde_json::json!({"kind": "Service"}), "default").is_err());
+}
+
+#[test]
+fn test_orphaned_instances() {
+    let instance = |name: &str, uid: &str| {
+        serde_json::json!({
+            "metadata": {"name": name, "ownerReferences": [{"uid": uid}]},
+        })
+    };
+    let instances = vec![
+        instance("web-frontend", "1"),
+        instance("db-backend", "1"),
+        instance("other-app", "2"),
+        serde_json::json!({"metadata": {"name": "unowned"}}),
+        // Instances in the namespace of the application are only labeled.
+        serde_json::json!({"metadata": {"name": "cache", "labels": {CONFIG_UID_LABEL: "1"}}}),
+    ];
+    let keep = vec!["web-frontend".to_string()];
+    assert_eq!(
+        vec!["db-backend".to_string(), "cache".to_string()],
+        orphaned_instances(&instances, "1", &keep)
+    );
+    assert!(orphaned_instances(&instances, "3", &keep).is_empty());
+}
+
+#[test]
+fn test_job_outcome() {
+    use k8s_openapi::api::batch::v1 as batch;
+
+    assert!(job_outcome(None).is_none());
+    let running = batch::JobStatus {
+        active: Some(1),
+        ..Default::default()
+    };
+    assert!(job_outcome(Some(&running)).is_none());
+    let succeeded = batch::JobStatus {
+        succeeded: Some(1),
+        ..Default::default()
+    };
+    assert!(job_outcome(Some(&succeeded)).expect("finished").is_ok());
+    let failed = batch::JobStatus {
+        failed: Some(1),
+        conditions: Some(vec![batch::JobCondition {
+            type_: "Failed".to_string(),
+            status: "True".to_string(),
+            message: Some("Job has reached the specified backoff limit".to_string()),
+            ..Default::default()
+        }]),
+        ..Default::default()
+    };
+    let err = job_outcome(Some(&failed))
+        .expect("finished")
+        .expect_err("failed");
+    assert!(err.to_string().contains("backoff limit"));
+}
+
+#[test]
+fn test_stale_instances() {
+    let before = chrono::DateTime::parse_from_rfc3339("2019-10-01T12:00:00Z")
+        .expect("time")
+        .with_timezone(&chrono::Utc);
+    let instance = |name: &str, created: &str, owner: serde_json::Value| {
+        let mut inst = serde_json::json!({
+            "metadata": {"name": name, "namespace": "default", "creationTimestamp": created},
+        });
+        inst["metadata"]
+            .as_object_mut()
+            .expect("metadata")
+            .extend(owner.as_object().expect("owner").clone());
+        inst
+    };
+    let owned = |uid: &str| serde_json::json!({"ownerReferences": [{"kind": "ApplicationConfiguration", "uid": uid}]});
+    let labeled = |uid: &str| serde_json::json!({"labels": {CONFIG_UID_LABEL: uid}});
+    let instances = vec![
+        instance("live", "2019-10-01T10:00:00Z", owned("1")),
+        instance("gone", "2019-10-01T10:00:00Z", owned("2")),
+        instance("gone-labeled", "2019-10-01T10:00:00Z", labeled("2")),
+        // Young instances may belong to a configuration that wasn't listed yet.
+        instance("young", "2019-10-01T12:30:00Z", owned("3")),
+        instance("unknown", "2019-10-01T10:00:00Z", serde_json::json!({})),
+    ];
+    assert_eq!(
+        vec![
+            ("default".to_string(), "gone".to_string()),
+            ("default".to_string(), "gone-labeled".to_string()),
+        ],
+        stale_instances(&instances, &["1".to_string()], before)
+    );
+
+    let namespace = |name: &str, config: &str, created: &str| {
+        serde_json::json!({"metadata": {
+            "name": name,
+            "creationTimestamp": created,
+            "labels": {APP_CONFIG_LABEL: config, APP_CONFIG_NAMESPACE_LABEL: "default"},
+        }})
+    };
+    let namespaces = vec![
+        namespace("default-shop", "shop", "2019-10-01T10:00:00Z"),
+        namespace("default-blog", "blog", "2019-10-01T10:00:00Z"),
+        namespace("default-wiki", "wiki", "2019-10-01T12:30:00Z"),
+    ];
+    let live = vec![("default".to_string(), "shop".to_string())];
+    assert_eq!(
+        vec!["default-blog".to_string()],
+        stale_app_namespaces(&namespaces, &live, before)
+    );
+}
+
+#[test]
+fn test_within() {
+    assert_eq!(1, within(None, || Ok(1)).unwrap());
+    assert_eq!(2, within(Some(Duration::from_secs(5)), || Ok(2)).unwrap());
+    assert!(within(
+        Some(Duration::from_secs(5)),
+        || -> Result<(), failure::Error> { Err(format_err!("apply failed")) }
+    )
+    .is_err());
+    // A hung call doesn't hold up the caller.
+    let res = within(Some(Duration::from_millis(50)), || {
+        std::thread::sleep(Duration::from_secs(5));
+        Ok(())
+    });
+    assert_eq!("not done within 50ms", res.unwrap_err().to_string());
+}
+
+#[test]
+fn test_in_flight() {
+    let first = InFlight::claim("prod/in-flight".to_string()).expect("first claim");
+    assert!(InFlight::claim("prod/in-flight".to_string()).is_none());
+    assert!(InFlight::claim("staging/in-flight".to_string()).is_some());
+    drop(first);
+    let claim = InFlight::claim("prod/in-flight".to_string()).expect("claim after drop");
+
+    // The worker of a timed out call holds the mark until it is done.
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+    let (finished_tx, finished_rx) = std::sync::mpsc::channel();
+    let res = within(Some(Duration::from_millis(50)), move || {
+        let _claim = claim;
+        let _ = done_rx.recv();
+        let _ = finished_tx.send(());
+        Ok(())
+    });
+    assert!(res.is_err());
+    assert!(InFlight::claim("prod/in-flight".to_string()).is_none());
+    done_tx.send(()).unwrap();
+    finished_rx.recv().unwrap();
+    // The mark is dropped right after the worker returned.
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while InFlight::claim("prod/in-flight".to_string()).is_none() {
+        assert!(std::time::Instant::now() < deadline, "mark not released");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
@@ -1,8 +1,10 @@
+use crate::metrics::TimedRequest;
 use failure::Error;
 use k8s_openapi::api::core::v1::ObjectReference;
-use kube::{api::Api, api::PostParams, client::APIClient};
+use kube::{api::PostParams, api::RawApi, client::APIClient};
 use std::fmt;
 
+#[derive(Clone, Copy)]
 pub enum Type {
     Normal,
     Warning,
@@ -22,7 +24,7 @@ pub struct Event {
     pub namespace: String,
     pub reporting_component: Option<String>, // Name of the controller that emitted this Event,e.g. "oam.dev/rudr"
     pub reporting_instance: Option<String>,  //ID of the controller instance
-    pub event_handle: Api<kube::api::v1Event>,
+    pub event_handle: RawApi,
 }
 
 pub struct Info {
@@ -38,7 +40,7 @@ impl Event {
             namespace: namespace.clone(),
             reporting_component: None,
             reporting_instance: None,
-            event_handle: Api::v1Event(client).within(namespace.as_str()),
+            event_handle: RawApi::v1Event().within(namespace.as_str()),
         }
     }
     fn make_event(
@@ -93,8 +95,10 @@ impl Event {
             self.reporting_component.clone(),
             self.reporting_instance.clone(),
         );
-        self.event_handle
+        let req = self
+            .event_handle
             .create(&PostParams::default(), serde_json::to_vec(&event)?)?;
+        self.client.timed_request::<serde_json::Value>(req)?;
         Ok(())
     }
 }
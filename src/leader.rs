@@ -0,0 +1,188 @@
+use chrono::{DateTime, Duration, Utc};
+use failure::Error;
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+use kube::api::{PostParams, RawApi};
+use kube::client::APIClient;
+use log::{error, info, warn};
+use std::thread;
+
+use crate::metrics::TimedRequest;
+
+/// How long a lease is valid without being renewed.
+pub const DEFAULT_LEASE_DURATION: i32 = 15;
+
+/// LeaderElector uses a coordination.k8s.io/v1 Lease to decide which replica of a controller does
+/// the writing, the others stand by until the lease expires.
+pub struct LeaderElector {
+    client: APIClient,
+    resource: RawApi,
+    name: String,
+    identity: String,
+    lease_duration: i32,
+}
+
+impl LeaderElector {
+    pub fn new(
+        client: APIClient,
+        namespace: String,
+        name: String,
+        identity: String,
+        lease_duration: i32,
+    ) -> Self {
+        let resource = RawApi::customResource("leases")
+            .group("coordination.k8s.io")
+            .version("v1")
+            .within(namespace.as_str());
+        LeaderElector {
+            client,
+            resource,
+            name,
+            identity,
+            lease_duration,
+        }
+    }
+
+    /// try_acquire_or_renew returns true if this replica holds the lease after the call.
+    ///
+    /// Updates go through `replace`, so the resourceVersion of the lease we read guards
+    /// against two replicas taking over an expired lease at the same time.
+    pub fn try_acquire_or_renew(&self) -> Result<bool, Error> {
+        let now = Utc::now();
+        let req = self.resource.get(self.name.as_str())?;
+        let mut lease = match self.client.timed_request::<Lease>(req) {
+            Ok(lease) => lease,
+            Err(e) => {
+                if e.api_error().map(|err| err.code) == Some(404) {
+                    return self.create(now);
+                }
+                return Err(e.into());
+            }
+        };
+        let mut spec = lease.spec.clone().unwrap_or_default();
+        if !can_acquire(&spec, self.identity.as_str(), now) {
+            return Ok(false);
+        }
+        if spec.holder_identity.as_deref() != Some(self.identity.as_str()) {
+            info!("{} takes over lease {}", self.identity, self.name);
+            spec.acquire_time = Some(MicroTime(now));
+            spec.lease_transitions = Some(spec.lease_transitions.unwrap_or(0) + 1);
+        }
+        spec.holder_identity = Some(self.identity.clone());
+        spec.lease_duration_seconds = Some(self.lease_duration);
+        spec.renew_time = Some(MicroTime(now));
+        lease.spec = Some(spec);
+        self.replace(&lease)
+    }
+
+    /// acquire blocks until this replica holds the lease, trying again every `retry_period`.
+    pub fn acquire(&self, retry_period: std::time::Duration) {
+        loop {
+            match self.try_acquire_or_renew() {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(e) => error!("leader election err {:?}", e),
+            }
+            thread::sleep(retry_period);
+        }
+    }
+
+    /// keep renews the lease every `renew_period`. It returns once the lease was lost or could
+    /// not be renewed for a whole lease duration, another replica may hold it by then.
+    pub fn keep(&self, renew_period: std::time::Duration) {
+        let lease_duration = std::time::Duration::from_secs(self.lease_duration as u64);
+        let mut renewed = std::time::Instant::now();
+        loop {
+            thread::sleep(renew_period);
+            match self.try_acquire_or_renew() {
+                Ok(true) => renewed = std::time::Instant::now(),
+                Ok(false) => return,
+                Err(e) => {
+                    error!("renew lease {} err {:?}", self.name, e);
+                    if renewed.elapsed() >= lease_duration {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// release gives up the lease so another replica can take over without waiting for it to expire.
+    pub fn release(&self) -> Result<(), Error> {
+        let req = self.resource.get(self.name.as_str())?;
+        let mut lease = self.client.timed_request::<Lease>(req)?;
+        let mut spec = lease.spec.clone().unwrap_or_default();
+        if spec.holder_identity.as_deref() != Some(self.identity.as_str()) {
+            return Ok(());
+        }
+        spec.holder_identity = None;
+        spec.renew_time = None;
+        lease.spec = Some(spec);
+        self.replace(&lease)?;
+        Ok(())
+    }
+
+    fn create(&self, now: DateTime<Utc>) -> Result<bool, Error> {
+        let lease = Lease {
+            metadata: Some(ObjectMeta {
+                name: Some(self.name.clone()),
+                ..Default::default()
+            }),
+            spec: Some(LeaseSpec {
+                holder_identity: Some(self.identity.clone()),
+                lease_duration_seconds: Some(self.lease_duration),
+                acquire_time: Some(MicroTime(now)),
+                renew_time: Some(MicroTime(now)),
+                lease_transitions: Some(0),
+            }),
+        };
+        let req = self
+            .resource
+            .create(&PostParams::default(), serde_json::to_vec(&lease)?)?;
+        match self.client.timed_request::<Lease>(req) {
+            Ok(_) => {
+                info!("{} acquired new lease {}", self.identity, self.name);
+                Ok(true)
+            }
+            Err(e) => match e.api_error() {
+                Some(ref err) if err.reason == "AlreadyExists" => Ok(false),
+                _ => Err(e.into()),
+            },
+        }
+    }
+
+    fn replace(&self, lease: &Lease) -> Result<bool, Error> {
+        let req = self.resource.replace(
+            self.name.as_str(),
+            &PostParams::default(),
+            serde_json::to_vec(lease)?,
+        )?;
+        match self.client.timed_request::<Lease>(req) {
+            Ok(_) => Ok(true),
+            Err(e) => match e.api_error() {
+                Some(ref err) if err.reason == "Conflict" => {
+                    warn!("conflict updating lease {}, another replica won", self.name);
+                    Ok(false)
+                }
+                _ => Err(e.into()),
+            },
+        }
+    }
+}
+
+/// can_acquire is true when the lease is ours, has no holder, or its holder stopped renewing it.
+pub fn can_acquire(spec: &LeaseSpec, identity: &str, now: DateTime<Utc>) -> bool {
+    match spec.holder_identity.as_deref() {
+        None | Some("") => return true,
+        Some(holder) if holder == identity => return true,
+        _ => {}
+    }
+    let renew_time = match spec.renew_time.as_ref().or(spec.acquire_time.as_ref()) {
+        Some(MicroTime(time)) => *time,
+        None => return true,
+    };
+    let duration = spec
+        .lease_duration_seconds
+        .unwrap_or(DEFAULT_LEASE_DURATION);
+    renew_time + Duration::seconds(i64::from(duration)) < now
+}
@@ -0,0 +1,22 @@
+use chrono::{Duration, Utc};
+use k8s_openapi::api::coordination::v1::LeaseSpec;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::MicroTime;
+
+use crate::leader::*;
+
+#[test]
+fn test_can_acquire() {
+    let now = Utc::now();
+    let held_by = |holder: &str, renewed_ago: i64| LeaseSpec {
+        holder_identity: Some(holder.to_string()),
+        lease_duration_seconds: Some(15),
+        renew_time: Some(MicroTime(now - Duration::seconds(renewed_ago))),
+        ..Default::default()
+    };
+    assert!(can_acquire(&LeaseSpec::default(), "a", now));
+    assert!(can_acquire(&held_by("a", 1), "a", now));
+    assert!(can_acquire(&held_by("a", 100), "a", now));
+    assert!(!can_acquire(&held_by("b", 1), "a", now));
+    assert!(can_acquire(&held_by("b", 16), "a", now));
+    assert!(can_acquire(&held_by("", 1), "a", now));
+}
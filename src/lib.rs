@@ -6,16 +6,61 @@ extern crate serde_derive;
 extern crate lazy_static;
 extern crate regex;
 
+pub mod audit;
+pub mod cluster;
+pub mod crds;
+pub mod index;
 pub mod instigator;
 pub mod kube_event;
+pub mod leader;
 pub mod lifecycle;
+pub mod management;
+pub mod metrics;
+pub mod mutator;
+pub mod ratelimit;
+pub mod rendering;
+pub mod retry;
+pub mod revision;
 pub mod schematic;
+pub mod shard;
+pub mod trace;
 mod trait_manager;
+pub mod webhook;
 pub mod workload_type;
 
+#[cfg(test)]
+mod audit_test;
+#[cfg(test)]
+mod cluster_test;
+#[cfg(test)]
+mod crds_test;
+#[cfg(test)]
+mod index_test;
 #[cfg(test)]
 mod instigator_test;
 #[cfg(test)]
+mod leader_test;
+#[cfg(test)]
 mod lifecycle_test;
 #[cfg(test)]
+mod management_test;
+#[cfg(test)]
+mod metrics_test;
+#[cfg(test)]
+mod mutator_test;
+#[cfg(test)]
+mod ratelimit_test;
+#[cfg(test)]
+mod rendering_test;
+#[cfg(test)]
+mod retry_test;
+#[cfg(test)]
+mod revision_test;
+#[cfg(test)]
+mod shard_test;
+#[cfg(test)]
+mod trace_test;
+#[cfg(test)]
+mod webhook_test;
+#[cfg(test)]
 mod workload_type_test;
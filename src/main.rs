@@ -5,23 +5,37 @@ use failure::{format_err, Error};
 use hyper::rt::Future;
 use hyper::service::service_fn_ok;
 use hyper::{Body, Method, Response, Server, StatusCode};
-use kube::api::{Informer, ListParams, Object, ObjectList, RawApi, WatchEvent};
+use kube::api::{Informer, ListParams, Object, ObjectList, ObjectMeta, RawApi, Void, WatchEvent};
 use kube::{client::APIClient, config::incluster_config, config::load_kube_config, ApiError};
 use log::{debug, error, info};
+use std::collections::BTreeMap;
 use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1beta1::{
-    CustomResourceDefinitionSpec as CrdSpec, CustomResourceDefinitionStatus as CrdStatus,
-};
+use rudr::audit;
+use rudr::cluster::{load_clusters, Clusters};
+use rudr::crds;
+use rudr::index::SchematicIndex;
 use rudr::instigator::{
-    Instigator, COMPONENT_CRD, CONFIG_CRD, CONFIG_GROUP, CONFIG_VERSION, SCOPE_CRD, TRAIT_CRD,
+    has_finalizer, is_dry_run, is_paused, is_rollback, stale_app_namespaces, stale_instances,
+    Instigator, APP_CONFIG_NAMESPACE_LABEL, COMPONENT_CRD, CONFIG_CRD, CONFIG_GROUP, CONFIG_VERSION,
 };
 use rudr::kube_event;
-use rudr::schematic::{
-    configuration::ApplicationConfiguration, OAMStatus,
-};
+use rudr::leader::{self, LeaderElector};
+use rudr::management::{self, Management};
+use rudr::metrics::{self, TimedRequest};
+use rudr::mutator::{self, InjectLabels};
+use rudr::ratelimit;
+use rudr::retry::RetryQueue;
+use rudr::schematic::{configuration::ApplicationConfiguration, OAMStatus};
+use rudr::shard::{self, Shard};
+use rudr::trace;
+use rudr::webhook;
 
 const DEFAULT_NAMESPACE: &str = "default";
+/// Passed as --namespaces, configurations of all namespaces are reconciled.
+const ALL_NAMESPACES: &str = "*";
 
 fn kubeconfig() -> kube::Result<kube::config::Configuration> {
     // If env var is set, use in cluster config
@@ -34,7 +48,41 @@ fn kubeconfig() -> kube::Result<kube::config::Configuration> {
     }
 }
 
+// identity names this replica in the leader lease, the pod name is unique among replicas.
+fn identity() -> String {
+    std::env::var("POD_NAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| format!("rudr-{}", std::process::id()))
+}
+
 type KubeOpsConfig = Object<ApplicationConfiguration, OAMStatus>;
+type Retries = Mutex<RetryQueue<WatchEvent<KubeOpsConfig>>>;
+type Index = Mutex<SchematicIndex>;
+// Schematics are read untyped, only their name matters here.
+type KubeSchematic = Object<serde_json::Value, Void>;
+
+/// Settings of the instigators that handle the configurations.
+#[derive(Clone)]
+struct Settings {
+    concurrency: usize,
+    namespace_per_app: bool,
+    progress_deadline: Option<Duration>,
+    component_timeout: Option<Duration>,
+    clusters: Arc<Clusters>,
+    // The configurations outside of the shard are left to the other replicas.
+    shard: Shard,
+}
+
+impl Settings {
+    fn instigator(&self, cli: &APIClient, namespace: String) -> Instigator {
+        Instigator::new(cli.clone(), namespace)
+            .with_concurrency(self.concurrency)
+            .with_namespace_per_app(self.namespace_per_app)
+            .with_progress_deadline(self.progress_deadline)
+            .with_component_timeout(self.component_timeout)
+            .with_clusters(self.clusters.clone())
+    }
+}
 
 fn main() -> Result<(), Error> {
     let env = env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "trace");
@@ -62,90 +110,355 @@ fn main() -> Result<(), Error> {
                 .default_value(":8080")
                 .help("The address the metric endpoint binds to."),
         )
+        .arg(
+            Arg::with_name("namespaces")
+                .long("namespaces")
+                .takes_value(true)
+                .help("Comma separated namespaces whose configurations are reconciled, or * for all namespaces. Defaults to the KUBERNETES_NAMESPACE environment variable."),
+        )
+        .arg(
+            Arg::with_name("component-concurrency")
+                .long("component-concurrency")
+                .default_value("4")
+                .help("How many independent components are instantiated at the same time."),
+        )
+        .arg(
+            Arg::with_name("namespace-per-app")
+                .long("namespace-per-app")
+                .help("Create the components of every configuration in a namespace of its own, deleted with the configuration."),
+        )
+        .arg(
+            Arg::with_name("leader-elect")
+                .long("leader-elect")
+                .help("Only reconcile configurations on the replica holding the leader lease."),
+        )
+        .arg(
+            Arg::with_name("leader-election-lease")
+                .long("leader-election-lease")
+                .default_value("rudr-leader")
+                .help("The name of the Lease used for leader election."),
+        )
+        .arg(
+            Arg::with_name("retry-base-delay")
+                .long("retry-base-delay")
+                .default_value("1")
+                .help("Seconds before a failed configuration event is retried the first time."),
+        )
+        .arg(
+            Arg::with_name("retry-max-delay")
+                .long("retry-max-delay")
+                .default_value("300")
+                .help("Upper bound in seconds of the doubling delay between retries of an event."),
+        )
+        .arg(
+            Arg::with_name("drift-interval")
+                .long("drift-interval")
+                .default_value("60")
+                .help("Seconds between checks for changed or deleted generated resources, 0 disables them."),
+        )
+        .arg(
+            Arg::with_name("gc-interval")
+                .long("gc-interval")
+                .default_value("300")
+                .help("Seconds between deletions of component instances whose configuration is gone, 0 disables them."),
+        )
+        .arg(
+            Arg::with_name("clusters-config")
+                .long("clusters-config")
+                .takes_value(true)
+                .help("Directory with the kubeconfig of every remote cluster components can be placed in, named after the cluster."),
+        )
+        .arg(
+            Arg::with_name("inject-label")
+                .long("inject-label")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Label key=value to add to every object Rudr applies that doesn't set it, can be repeated."),
+        )
+        .arg(
+            Arg::with_name("progress-deadline")
+                .long("progress-deadline")
+                .default_value("0")
+                .help("Seconds an applied component may take to become running before it is reported failed, 0 waits forever."),
+        )
+        .arg(
+            Arg::with_name("component-timeout")
+                .long("component-timeout")
+                .default_value("600")
+                .help("Seconds applying a component with its traits and hooks may take before it fails, 0 waits forever."),
+        )
+        .arg(
+            Arg::with_name("resync-period")
+                .long("resync-period")
+                .default_value("10")
+                .help("Seconds between syncs of the status of every configuration and finalizations of deleted ones."),
+        )
+        .arg(
+            Arg::with_name("watch-timeout")
+                .long("watch-timeout")
+                .default_value("10")
+                .help("Seconds a watch of configurations or schematics stays open before it is renewed."),
+        )
+        .arg(
+            Arg::with_name("kube-api-qps")
+                .long("kube-api-qps")
+                .default_value("20")
+                .help("Average requests per second to the kube API server, 0 doesn't limit them."),
+        )
+        .arg(
+            Arg::with_name("kube-api-burst")
+                .long("kube-api-burst")
+                .default_value("30")
+                .help("Requests that may be sent to the kube API server at once above the average rate."),
+        )
+        .arg(
+            Arg::with_name("webhook-addr")
+                .long("webhook-addr")
+                .takes_value(true)
+                .help("Serve the validating and mutating admission webhooks on this address, e.g. :9443."),
+        )
+        .arg(
+            Arg::with_name("webhook-cert")
+                .long("webhook-cert")
+                .default_value("/etc/rudr/webhook/tls.crt")
+                .help("PEM certificate chain the admission webhook serves."),
+        )
+        .arg(
+            Arg::with_name("webhook-key")
+                .long("webhook-key")
+                .default_value("/etc/rudr/webhook/tls.key")
+                .help("PEM private key of the admission webhook certificate."),
+        )
+        .arg(
+            Arg::with_name("api-addr")
+                .long("api-addr")
+                .takes_value(true)
+                .help("Serve the management API on this address, e.g. :8082."),
+        )
+        .arg(
+            Arg::with_name("api-token-file")
+                .long("api-token-file")
+                .takes_value(true)
+                .help(
+                    "File with the bearer token requests to the management API must send, without \
+                     it the API only listens on 127.0.0.1.",
+                ),
+        )
+        .arg(
+            Arg::with_name("default-scope")
+                .long("default-scope")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Application scope the mutating webhook adds to components that don't name any, can be repeated."),
+        )
+        .arg(
+            Arg::with_name("shards")
+                .long("shards")
+                .default_value("1")
+                .help("Split the configurations among this many replicas, each of them reconciles its own shard."),
+        )
+        .arg(
+            Arg::with_name("shard-index")
+                .long("shard-index")
+                .takes_value(true)
+                .help("The shard of this replica. Defaults to the StatefulSet ordinal in the pod name."),
+        )
+        .arg(
+            Arg::with_name("otlp-endpoint")
+                .long("otlp-endpoint")
+                .takes_value(true)
+                .help("Export traces to the OTLP/HTTP collector at this URL, e.g. http://otel-collector:4318."),
+        )
+        .arg(
+            Arg::with_name("audit-log")
+                .long("audit-log")
+                .takes_value(true)
+                .help("Append every change rudr makes to cluster resources to this file as JSON lines, - for stdout."),
+        )
+        .arg(
+            Arg::with_name("crd-timeout")
+                .long("crd-timeout")
+                .default_value("300")
+                .help("Seconds to wait at startup for the OAM CRDs to be established, 0 waits forever."),
+        )
+        .arg(
+            Arg::with_name("install-crds")
+                .long("install-crds")
+                .help("Create or update the OAM CRDs at startup."),
+        )
         .get_matches();
     let metrics_addr = "0.0.0.0".to_owned() + flags.value_of("metrics-addr").unwrap();
+    let concurrency = flags
+        .value_of("component-concurrency")
+        .unwrap()
+        .parse::<usize>()
+        .map_err(|e| format_err!("invalid component-concurrency: {}", e))?;
+    let mut inject = BTreeMap::new();
+    for label in flags.values_of("inject-label").into_iter().flatten() {
+        let mut parts = label.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if !key.is_empty() => {
+                inject.insert(key.to_string(), value.to_string())
+            }
+            _ => return Err(format_err!("invalid inject-label {}: must be key=value", label)),
+        };
+    }
+    if !inject.is_empty() {
+        info!("adding labels {:?} to every object", inject);
+        mutator::register(Box::new(InjectLabels { labels: inject }));
+    }
+    let seconds = |name: &str| {
+        flags
+            .value_of(name)
+            .unwrap()
+            .parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|e| format_err!("invalid {}: {}", name, e))
+    };
+    let shards = flags
+        .value_of("shards")
+        .unwrap()
+        .parse::<u32>()
+        .map_err(|e| format_err!("invalid shards: {}", e))?;
+    let shard_index = match flags.value_of("shard-index") {
+        Some(index) => index
+            .parse::<u32>()
+            .map_err(|e| format_err!("invalid shard-index: {}", e))?,
+        None if shards > 1 => shard::ordinal(&identity()).ok_or_else(|| {
+            format_err!(
+                "shard-index is required, {} has no StatefulSet ordinal",
+                identity()
+            )
+        })?,
+        None => 0,
+    };
+    let shard = Shard::new(shard_index, shards)?;
+    if shards > 1 && flags.is_present("leader-elect") {
+        return Err(format_err!(
+            "leader-elect and shards are exclusive, every shard reconciles on its own"
+        ));
+    }
+    let settings = Settings {
+        concurrency,
+        namespace_per_app: flags.is_present("namespace-per-app"),
+        progress_deadline: Some(seconds("progress-deadline")?)
+            .filter(|d| *d > Duration::from_secs(0)),
+        component_timeout: Some(seconds("component-timeout")?)
+            .filter(|d| *d > Duration::from_secs(0)),
+        // Loaded before any thread is started, the loader reads the environment.
+        clusters: Arc::new(match flags.value_of("clusters-config") {
+            Some(dir) => load_clusters(std::path::Path::new(dir))?,
+            None => Clusters::new(),
+        }),
+        shard,
+    };
+    let retries: Arc<Retries> = Arc::new(Mutex::new(RetryQueue::new(
+        seconds("retry-base-delay")?,
+        seconds("retry-max-delay")?,
+    )));
+    let index: Arc<Index> = Arc::new(Mutex::new(SchematicIndex::default()));
+    let drift_interval = Some(seconds("drift-interval")?).filter(|d| *d > Duration::from_secs(0));
+    let gc_interval = Some(seconds("gc-interval")?).filter(|d| *d > Duration::from_secs(0));
+    let resync_period = seconds("resync-period")?;
+    if resync_period == Duration::from_secs(0) {
+        return Err(format_err!("invalid resync-period: must be positive"));
+    }
+    let watch_timeout = flags
+        .value_of("watch-timeout")
+        .unwrap()
+        .parse::<u32>()
+        .map_err(|e| format_err!("invalid watch-timeout: {}", e))?;
+    if watch_timeout == 0 {
+        return Err(format_err!("invalid watch-timeout: must be positive"));
+    }
+    let qps = flags
+        .value_of("kube-api-qps")
+        .unwrap()
+        .parse::<f64>()
+        .map_err(|e| format_err!("invalid kube-api-qps: {}", e))?;
+    let burst = flags
+        .value_of("kube-api-burst")
+        .unwrap()
+        .parse::<u32>()
+        .map_err(|e| format_err!("invalid kube-api-burst: {}", e))?;
+    ratelimit::configure(qps, burst);
+    if let Some(endpoint) = flags.value_of("otlp-endpoint") {
+        info!("exporting traces to {}", endpoint);
+        trace::configure(endpoint, "rudr");
+    }
+    if let Some(path) = flags.value_of("audit-log") {
+        info!("writing the audit log to {}", path);
+        audit::configure(path)?;
+    }
+    let leader_elect = flags.is_present("leader-elect");
+    let lease_name = flags.value_of("leader-election-lease").unwrap().to_string();
+    let webhook_addr = flags
+        .value_of("webhook-addr")
+        .map(|addr| "0.0.0.0".to_owned() + addr);
+    let webhook_cert = flags.value_of("webhook-cert").unwrap().to_string();
+    let webhook_key = flags.value_of("webhook-key").unwrap().to_string();
+    let api_token = match flags.value_of("api-token-file") {
+        Some(path) => Some(
+            std::fs::read_to_string(path)
+                .map_err(|e| format_err!("read api-token-file {}: {}", path, e))?
+                .trim()
+                .to_string(),
+        ),
+        None => None,
+    };
+    let api_addr = flags
+        .value_of("api-addr")
+        .map(|addr| management::listen_addr(addr, api_token.as_deref()));
+    let defaults = webhook::Defaults {
+        application_scopes: flags
+            .values_of("default-scope")
+            .into_iter()
+            .flatten()
+            .map(str::to_string)
+            .collect(),
+    };
 
     info!("starting server");
 
     let top_ns = std::env::var("KUBERNETES_NAMESPACE").unwrap_or_else(|_| DEFAULT_NAMESPACE.into());
+    let namespaces = watched_namespaces(flags.value_of("namespaces"), top_ns.clone());
+    info!("reconciling configurations of namespaces {:?}", namespaces);
+    if shard.count > 1 {
+        info!("reconciling shard {} of {}", shard.index, shard.count);
+    }
+    info!(
+        "resync period {:?}, watch timeout {}s, drift interval {:?}, gc interval {:?}, progress deadline {:?}, component timeout {:?}, retry delay {:?} to {:?}",
+        resync_period,
+        watch_timeout,
+        drift_interval,
+        gc_interval,
+        settings.progress_deadline,
+        settings.component_timeout,
+        seconds("retry-base-delay")?,
+        seconds("retry-max-delay")?,
+    );
     let top_cfg = kubeconfig().expect("Load default kubeconfig");
     info!("apiserver:{}", top_cfg.base_path);
+    info!("kube API rate limit {} qps, burst {}", qps, burst);
 
     // There is probably a better way to do this than to create two clones, but there is a potential
     // thread safety issue here.
     let cfg_watch = top_cfg.clone();
+    let cfg_retry = top_cfg.clone();
+    let cfg_sync = top_cfg.clone();
     let client = APIClient::new(top_cfg);
 
-    precheck_crds(&client)?;
-
-    // Watch for configuration objects to be added, and react to those.
-    let configuration_watch = std::thread::spawn(move || {
-        let ns = top_ns.clone();
-        let client = APIClient::new(cfg_watch.clone());
-        let resource = RawApi::customResource(CONFIG_CRD)
-            .within(ns.as_str())
-            .group(CONFIG_GROUP)
-            .version(CONFIG_VERSION);
-        //init all the existing objects at initiate, this should be done by informer
-        let req = resource.list(&ListParams::default()).unwrap();
-        match client.request::<ObjectList<KubeOpsConfig>>(req) {
-            Ok(cfgs) => {
-                for cfg in cfgs.items {
-                    let event = WatchEvent::Added(cfg);
-                    if let Err(res) = handle_event(&client, event, ns.clone()) {
-                        // Log the error and continue. In the future, should probably
-                        // re-queue data in some cases.
-                        error!("Error processing event: {:?}", res)
-                    };
-                }
-            }
-            Err(err) => error!("Error list application configs: {:?}", err),
-        }
-        // This listens for new items, and then processes them as they come in.
-        let informer: Informer<KubeOpsConfig> =
-            Informer::raw(client.clone(), resource.clone()).init()?;
-        loop {
-            informer.poll()?;
-            debug!("loop");
-
-            // Clear out the event queue
-            while let Some(event) = informer.pop() {
-                if let Err(res) = handle_event(&client, event, ns.clone()) {
-                    // Log the error and continue. In the future, should probably
-                    // re-queue data in some cases.
-                    error!("Error processing event: {:?}", res)
-                };
-                info!("Handled event");
-            }
-        }
-    });
-    info!("ApplicationConfiguration watcher is running");
-
-    // Sync status will periodically sync all the configuration status from their workload.
-    let sync_status = std::thread::spawn(move || {
-        loop {
-            let ns =
-                std::env::var("KUBERNETES_NAMESPACE").unwrap_or_else(|_| DEFAULT_NAMESPACE.into());
-            let cfg_watch = kubeconfig().expect("Load default kubeconfig");
-            let client = APIClient::new(cfg_watch.clone());
-            let resource = RawApi::customResource(CONFIG_CRD)
-                .within(ns.as_str())
-                .group(CONFIG_GROUP)
-                .version(CONFIG_VERSION);
-            //get all the configuration object and sync status
-            let req = resource.list(&ListParams::default()).unwrap();
-            if let Ok(cfgs) = client.request::<ObjectList<KubeOpsConfig>>(req) {
-                for cfg in cfgs.items {
-                    if let Err(res) = sync_status(&client, cfg, ns.clone()) {
-                        error!("Error sync status: {:?}", res)
-                    };
-                }
-            }
-            std::thread::sleep(std::time::Duration::from_secs(10));
-        }
-    });
+    if flags.is_present("install-crds") {
+        crds::install(&client)?;
+    }
+    let crd_timeout = Some(seconds("crd-timeout")?).filter(|d| *d > Duration::from_secs(0));
+    crds::wait_established(&client, crd_timeout, Duration::from_secs(2))?;
+    info!("CRDs are established");
 
-    std::thread::spawn(move || {
+    // Every replica serves the health endpoint, the admission webhook and the management API, also
+    // while it waits for the leader lease.
+    let health_server = std::thread::spawn(move || {
         let addr = metrics_addr.parse().unwrap();
         info!("Health server is running on {}", addr);
         hyper::rt::run(
@@ -156,6 +469,10 @@ fn main() -> Result<(), Error> {
                             debug!("health check");
                             Response::new(Body::from("OK"))
                         }
+                        (&Method::GET, "/metrics") => Response::builder()
+                            .header("Content-Type", "text/plain; version=0.0.4")
+                            .body(Body::from(metrics::render()))
+                            .unwrap(),
                         _ => Response::builder()
                             .status(StatusCode::NOT_FOUND)
                             .body(Body::from(""))
@@ -164,11 +481,462 @@ fn main() -> Result<(), Error> {
                 })
                 .map_err(|e| eprintln!("health server error: {}", e)),
         );
-    })
-    .join()
-    .unwrap();
+    });
+
+    if let Some(addr) = webhook_addr {
+        let client = client.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = webhook::serve(&addr, &webhook_cert, &webhook_key, client, defaults) {
+                error!("admission webhook stopped: {:?}", e);
+            }
+        });
+    }
+
+    if let Some(addr) = api_addr {
+        let api_client = client.clone();
+        let api_settings = settings.clone();
+        let api_retries = retries.clone();
+        let management = Management {
+            client: client.clone(),
+            namespaces: namespaces.clone(),
+            token: api_token,
+            instigator: Box::new(move |ns| api_settings.instigator(&api_client, ns.to_string())),
+            // The retry loop picks the configuration up right away.
+            reconcile: Box::new(move |cfg| {
+                let key = format!(
+                    "{}/{}",
+                    object_namespace(&cfg.metadata, DEFAULT_NAMESPACE),
+                    cfg.metadata.name
+                );
+                api_retries
+                    .lock()
+                    .unwrap()
+                    .schedule(&key, WatchEvent::Modified(cfg), Instant::now());
+            }),
+        };
+        std::thread::spawn(move || {
+            if let Err(e) = management::serve(&addr, Arc::new(management)) {
+                error!("management API stopped: {:?}", e);
+            }
+        });
+    }
+
+    if leader_elect {
+        let elector = LeaderElector::new(
+            client.clone(),
+            top_ns,
+            lease_name,
+            identity(),
+            leader::DEFAULT_LEASE_DURATION,
+        );
+        // Only the controllers wait for the lease, the servers above answer on every replica.
+        info!("waiting for leader lease");
+        elector.acquire(Duration::from_secs(2));
+        info!("acquired leader lease, starting controllers");
+        std::thread::spawn(move || {
+            elector.keep(Duration::from_secs(5));
+            // Another replica may take over now, stop before both write the same resources.
+            error!("lost leader lease, exiting");
+            std::process::exit(1);
+        });
+    }
+
+    // Retry failed events once their backoff ran out, instead of waiting for the next change.
+    let retry_queue = retries.clone();
+    let retry_index = index.clone();
+    let retry_settings = settings.clone();
+    std::thread::spawn(move || {
+        let client = APIClient::new(cfg_retry);
+        loop {
+            let ready = retry_queue.lock().unwrap().pop_ready(Instant::now());
+            for (key, event) in ready {
+                info!("Retrying event of configuration {}", key);
+                process_event(
+                    &client,
+                    event,
+                    DEFAULT_NAMESPACE,
+                    &retry_settings,
+                    &retry_queue,
+                    &retry_index,
+                );
+            }
+            let (next, depth) = {
+                let queue = retry_queue.lock().unwrap();
+                (queue.next_ready(), queue.len())
+            };
+            metrics::set_queue_depth(depth);
+            let wait = next
+                .map(|at| at.saturating_duration_since(Instant::now()))
+                .unwrap_or(Duration::from_secs(1))
+                .min(Duration::from_secs(1));
+            std::thread::sleep(wait);
+        }
+    });
+
+    // Watch for configuration objects to be added, and react to those.
+    let configuration_watches: Vec<_> = namespaces
+        .iter()
+        .map(|ns| {
+            let ns = ns.clone();
+            let cfg_watch = cfg_watch.clone();
+            let retries = retries.clone();
+            let index = index.clone();
+            let settings = settings.clone();
+            std::thread::spawn(move || -> Result<(), Error> {
+                let client = APIClient::new(cfg_watch);
+                let resource = config_resource(ns.as_str());
+                //init all the existing objects at initiate, this should be done by informer
+                let req = resource.list(&ListParams::default()).unwrap();
+                match client.timed_request::<ObjectList<KubeOpsConfig>>(req) {
+                    Ok(cfgs) => {
+                        for cfg in cfgs.items {
+                            let event = WatchEvent::Added(cfg);
+                            let ns = ns.as_str();
+                            process_event(&client, event, ns, &settings, &retries, &index);
+                        }
+                    }
+                    Err(err) => error!("Error list application configs of {}: {:?}", ns, err),
+                }
+                // This listens for new items, and then processes them as they come in.
+                let informer: Informer<KubeOpsConfig> =
+                    Informer::raw(client.clone(), resource.clone())
+                        .timeout(watch_timeout)
+                        .init()?;
+                loop {
+                    informer.poll()?;
+                    debug!("loop");
+
+                    // Clear out the event queue
+                    while let Some(event) = informer.pop() {
+                        let ns = ns.as_str();
+                        process_event(&client, event, ns, &settings, &retries, &index);
+                        info!("Handled event");
+                    }
+                }
+            })
+        })
+        .collect();
+    info!("ApplicationConfiguration watcher is running");
+
+    // Roll changed schematics out to the configurations that use them.
+    let schematic_watches: Vec<_> = namespaces
+        .iter()
+        .map(|ns| {
+            let ns = ns.clone();
+            let cfg_watch = cfg_watch.clone();
+            let retries = retries.clone();
+            let index = index.clone();
+            std::thread::spawn(move || -> Result<(), Error> {
+                let client = APIClient::new(cfg_watch);
+                let resource = watched_resource(COMPONENT_CRD, ns.as_str());
+                let informer: Informer<KubeSchematic> =
+                    Informer::raw(client.clone(), resource)
+                        .timeout(watch_timeout)
+                        .init()?;
+                loop {
+                    informer.poll()?;
+                    while let Some(event) = informer.pop() {
+                        if let WatchEvent::Added(s) | WatchEvent::Modified(s) = event {
+                            requeue_configurations(&client, &s, ns.as_str(), &index, &retries);
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    info!("ComponentSchematic watcher is running");
+
+    // Sync status will periodically sync all the configuration status from their workload.
+    let sync_status = std::thread::spawn(move || {
+        let client = APIClient::new(cfg_sync);
+        let mut last_heal = Instant::now();
+        let mut last_gc = Instant::now();
+        loop {
+            // Drift detection re-reads every generated resource, so it runs less often.
+            let heal = drift_interval
+                .filter(|interval| last_heal.elapsed() >= *interval)
+                .is_some();
+            if heal {
+                last_heal = Instant::now();
+            }
+            let gc = gc_interval
+                .filter(|interval| last_gc.elapsed() >= *interval)
+                .is_some();
+            if gc {
+                last_gc = Instant::now();
+            }
+            for watched in namespaces.iter() {
+                //get all the configuration object and sync status
+                let req = config_resource(watched.as_str())
+                    .list(&ListParams::default())
+                    .unwrap();
+                // Read untyped to see the deletionTimestamp, deleted configurations are finalized instead.
+                if let Ok(cfgs) = client.timed_request::<ObjectList<serde_json::Value>>(req) {
+                    // Instances of deleted configurations belong to no shard, the first one collects them.
+                    if gc && settings.shard.index == 0 {
+                        let _caused = audit::caused_by(Some("garbage collection".to_string()));
+                        if let Err(e) = collect_garbage(&client, watched.as_str(), &cfgs.items) {
+                            error!("Error collect stale component instances: {:?}", e)
+                        }
+                    }
+                    for item in cfgs.items {
+                        let deleting = item["metadata"]["deletionTimestamp"].is_string();
+                        let cfg: KubeOpsConfig = match serde_json::from_value(item) {
+                            Ok(cfg) => cfg,
+                            Err(e) => {
+                                error!("Error decode configuration: {:?}", e);
+                                continue;
+                            }
+                        };
+                        let ns = object_namespace(&cfg.metadata, watched.as_str());
+                        if !settings.shard.owns(ns.as_str(), cfg.metadata.name.as_str()) {
+                            continue;
+                        }
+                        let _caused = audit::caused_by(Some(format!(
+                            "configuration {}/{}",
+                            ns, cfg.metadata.name
+                        )));
+                        if deleting {
+                            let inst = settings.instigator(&client, ns);
+                            if let Err(res) = inst.finalize(cfg) {
+                                error!("Error finalize configuration: {:?}", res)
+                            }
+                            continue;
+                        }
+                        if heal {
+                            let inst = settings.instigator(&client, ns.clone());
+                            match inst.heal(&cfg) {
+                                Ok(0) => {}
+                                Ok(n) => info!(
+                                    "Re-applied {} drifted resources of configuration {}",
+                                    n, cfg.metadata.name
+                                ),
+                                Err(res) => error!("Error detect drift: {:?}", res),
+                            }
+                        }
+                        if let Err(res) = settings.instigator(&client, ns).sync_status(cfg) {
+                            error!("Error sync status: {:?}", res)
+                        };
+                    }
+                }
+            }
+            std::thread::sleep(resync_period);
+        }
+    });
+
+    health_server.join().unwrap();
     sync_status.join().expect("status syncer crashed");
-    configuration_watch.join().unwrap()
+    for watch in configuration_watches {
+        watch.join().expect("configuration watcher crashed")?;
+    }
+    for watch in schematic_watches {
+        watch.join().expect("schematic watcher crashed")?;
+    }
+    Ok(())
+}
+
+/// collect_garbage deletes the component instances and application namespaces of a watched
+/// namespace whose configuration isn't among `configs` anymore. They are left behind when a
+/// configuration went away while rudr was down or its finalizer was removed by hand.
+fn collect_garbage(
+    cli: &APIClient,
+    watched: &str,
+    configs: &[serde_json::Value],
+) -> Result<(), Error> {
+    // Objects younger than this may belong to a configuration created after it was listed.
+    let before = chrono::Utc::now() - chrono::Duration::minutes(5);
+    let live_uids: Vec<String> = configs
+        .iter()
+        .filter_map(|c| c["metadata"]["uid"].as_str().map(String::from))
+        .collect();
+    let req = watched_resource("componentinstances", watched).list(&ListParams::default())?;
+    let instances = cli.timed_request::<ObjectList<serde_json::Value>>(req)?;
+    for (ns, name) in stale_instances(&instances.items, &live_uids, before) {
+        info!("deleting component instance {}/{} of a deleted configuration", ns, name);
+        Instigator::new(cli.clone(), ns).delete_instance(name.as_str())?;
+    }
+
+    let live: Vec<(String, String)> = configs
+        .iter()
+        .filter_map(|c| {
+            let metadata = &c["metadata"];
+            let name = metadata["name"].as_str()?.to_string();
+            let ns = metadata["namespace"].as_str().unwrap_or(watched).to_string();
+            Some((ns, name))
+        })
+        .collect();
+    let label_selector = match watched {
+        ALL_NAMESPACES => APP_CONFIG_NAMESPACE_LABEL.to_string(),
+        ns => format!("{}={}", APP_CONFIG_NAMESPACE_LABEL, ns),
+    };
+    let lp = ListParams {
+        label_selector: Some(label_selector),
+        ..Default::default()
+    };
+    let req = RawApi::v1Namespace().list(&lp)?;
+    let namespaces = cli.timed_request::<ObjectList<serde_json::Value>>(req)?;
+    for ns in stale_app_namespaces(&namespaces.items, &live, before) {
+        info!("deleting namespace {} of a deleted configuration", ns);
+        let req = RawApi::v1Namespace().delete(ns.as_str(), &Default::default())?;
+        cli.timed_request::<serde_json::Value>(req)?;
+    }
+    Ok(())
+}
+
+/// watched_namespaces splits the --namespaces flag, without it only `default_ns` is watched.
+fn watched_namespaces(flag: Option<&str>, default_ns: String) -> Vec<String> {
+    let mut namespaces: Vec<String> = flag
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|ns| !ns.is_empty())
+        .map(String::from)
+        .collect();
+    if namespaces.iter().any(|ns| ns == ALL_NAMESPACES) {
+        return vec![ALL_NAMESPACES.to_string()];
+    }
+    namespaces.sort();
+    namespaces.dedup();
+    if namespaces.is_empty() {
+        namespaces.push(default_ns);
+    }
+    namespaces
+}
+
+/// config_resource is the ApplicationConfiguration API of a watched namespace.
+fn config_resource(namespace: &str) -> RawApi {
+    watched_resource(CONFIG_CRD, namespace)
+}
+
+/// watched_resource is the API of the OAM custom resource `crd` in a watched namespace.
+fn watched_resource(crd: &str, namespace: &str) -> RawApi {
+    let resource = RawApi::customResource(crd)
+        .group(CONFIG_GROUP)
+        .version(CONFIG_VERSION);
+    if namespace == ALL_NAMESPACES {
+        resource
+    } else {
+        resource.within(namespace)
+    }
+}
+
+/// object_namespace is the namespace of an object of a watched namespace, the namespace the
+/// resources of a configuration are created in.
+fn object_namespace(metadata: &ObjectMeta, watched: &str) -> String {
+    metadata
+        .namespace
+        .clone()
+        .filter(|ns| !ns.is_empty())
+        .unwrap_or_else(|| match watched {
+            ALL_NAMESPACES => DEFAULT_NAMESPACE.to_string(),
+            ns => ns.to_string(),
+        })
+}
+
+/// requeue_configurations schedules the configurations that use a changed schematic right away,
+/// so that their components are updated to it.
+fn requeue_configurations(
+    cli: &APIClient,
+    schematic: &KubeSchematic,
+    watched: &str,
+    index: &Index,
+    retries: &Retries,
+) {
+    let namespace = object_namespace(&schematic.metadata, watched);
+    let configs = index
+        .lock()
+        .unwrap()
+        .configurations(namespace.as_str(), schematic.metadata.name.as_str());
+    for name in configs {
+        let req = config_resource(namespace.as_str())
+            .get(name.as_str())
+            .unwrap();
+        match cli.timed_request::<KubeOpsConfig>(req) {
+            Ok(cfg) => {
+                info!(
+                    "ComponentSchematic {} changed, requeue configuration {}",
+                    schematic.metadata.name, name
+                );
+                let key = format!("{}/{}", namespace, name);
+                let event = WatchEvent::Modified(cfg);
+                retries
+                    .lock()
+                    .unwrap()
+                    .schedule(&key, event, Instant::now());
+            }
+            Err(e) => error!("Error get configuration {} of {}: {:?}", name, namespace, e),
+        }
+    }
+}
+
+/// process_event handles an event and requeues it with backoff when it fails. A handled event of
+/// a configuration replaces the retries that are still waiting for it.
+fn process_event(
+    cli: &APIClient,
+    event: WatchEvent<KubeOpsConfig>,
+    watched: &str,
+    settings: &Settings,
+    retries: &Retries,
+    index: &Index,
+) {
+    let (namespace, key) = match &event {
+        WatchEvent::Added(o) | WatchEvent::Modified(o) | WatchEvent::Deleted(o) => {
+            let namespace = object_namespace(&o.metadata, watched);
+            let key = format!("{}/{}", namespace, o.metadata.name);
+            (namespace, Some(key))
+        }
+        WatchEvent::Error(_) => (watched.to_string(), None),
+    };
+    if let WatchEvent::Added(o) | WatchEvent::Modified(o) | WatchEvent::Deleted(o) = &event {
+        if !settings
+            .shard
+            .owns(namespace.as_str(), o.metadata.name.as_str())
+        {
+            return;
+        }
+    }
+    match &event {
+        WatchEvent::Added(o) | WatchEvent::Modified(o) => index.lock().unwrap().update(
+            namespace.as_str(),
+            o.metadata.name.as_str(),
+            &o.spec,
+        ),
+        WatchEvent::Deleted(o) => index
+            .lock()
+            .unwrap()
+            .remove(namespace.as_str(), o.metadata.name.as_str()),
+        WatchEvent::Error(_) => {}
+    }
+    let kind = match &event {
+        WatchEvent::Added(_) => "added",
+        WatchEvent::Modified(_) => "modified",
+        WatchEvent::Deleted(_) => "deleted",
+        WatchEvent::Error(_) => "error",
+    };
+    let retry = event.clone();
+    let _caused = audit::caused_by(key.as_ref().map(|key| format!("configuration {}", key)));
+    let mut span = trace::span("reconcile");
+    span.set_attribute("rudr.event", kind);
+    if let Some(key) = key.as_ref() {
+        span.set_attribute("rudr.configuration", key);
+    }
+    let start = Instant::now();
+    let res = handle_event(cli, event, namespace, settings);
+    span.record(&res);
+    drop(span);
+    metrics::observe_reconcile(kind, res.is_ok(), start.elapsed());
+    match (res, key) {
+        (Ok(()), Some(key)) => retries.lock().unwrap().forget(&key),
+        (Ok(()), None) => {}
+        (Err(e), Some(key)) => {
+            let delay = retries.lock().unwrap().requeue(&key, retry, Instant::now());
+            error!(
+                "Error processing event of configuration {}, retry in {:?}: {:?}",
+                key, delay, e
+            )
+        }
+        (Err(e), None) => error!("Error processing event: {:?}", e),
+    }
 }
 
 /// This takes an event off the stream and delegates it to the instigator, calling the correct verb.
@@ -176,10 +944,15 @@ fn handle_event(
     cli: &APIClient,
     event: WatchEvent<KubeOpsConfig>,
     namespace: String,
+    settings: &Settings,
 ) -> Result<(), Error> {
-    let inst = Instigator::new(cli.clone(), namespace);
+    let inst = settings.instigator(cli, namespace);
     match event {
+        WatchEvent::Added(o) if is_paused(&o) => inst.pause(o),
+        WatchEvent::Added(o) if is_dry_run(&o) => inst.dry_run(o),
+        WatchEvent::Added(o) if is_rollback(&o) => rollback(&inst, o),
         WatchEvent::Added(o) => {
+            inst.ensure_finalizer(&o)?;
             if let Err(err) = inst.add(o.clone()) {
                 if let Err(e) = inst.event_handler.push_event_message(
                     kube_event::Type::Warning,
@@ -192,11 +965,26 @@ fn handle_event(
                 ) {
                     log::warn!("push event message for update err {}", e)
                 }
+                if let Err(e) = inst.record_failure(&o, &err) {
+                    log::warn!("record failure in status err {}", e)
+                }
                 return Err(err);
             }
             Ok(())
         }
         WatchEvent::Modified(o) => {
+            if has_finalizer(&o) && inst.deletion_requested(o.metadata.name.as_str())? {
+                return inst.finalize(o);
+            }
+            if is_paused(&o) {
+                return inst.pause(o);
+            }
+            if is_dry_run(&o) {
+                return inst.dry_run(o);
+            }
+            if is_rollback(&o) {
+                return rollback(&inst, o);
+            }
             if let Err(err) = inst.modify(o.clone()) {
                 if let Err(e) = inst.event_handler.push_event_message(
                     kube_event::Type::Warning,
@@ -209,6 +997,9 @@ fn handle_event(
                 ) {
                     log::warn!("push event message for update err {}", e)
                 }
+                if let Err(e) = inst.record_failure(&o, &err) {
+                    log::warn!("record failure in status err {}", e)
+                }
                 return Err(err);
             }
             Ok(())
@@ -231,21 +1022,13 @@ fn handle_event(
     }
 }
 
-fn sync_status(cli: &APIClient, event: KubeOpsConfig, namespace: String) -> Result<(), Error> {
-    let inst = Instigator::new(cli.clone(), namespace);
-    inst.sync_status(event)
-}
-
-type CrdObj = Object<CrdSpec, CrdStatus>;
-fn precheck_crds(client: &APIClient) -> Result<(), failure::Error> {
-    let crds = vec![CONFIG_CRD, TRAIT_CRD, COMPONENT_CRD, SCOPE_CRD];
-    for crd in crds.iter() {
-        let req = RawApi::v1beta1CustomResourceDefinition()
-            .get(format!("{}.core.oam.dev", crd).as_str())?;
-        if let Err(e) = client.request::<CrdObj>(req) {
-            error!("Error prechecking CRDs {}: {:?}", crd, e);
-            return Err(failure::format_err!("Missing CRD {}", crd));
+/// rollback re-applies the revision a configuration asks for, a failure is recorded in its status.
+fn rollback(inst: &Instigator, o: KubeOpsConfig) -> Result<(), Error> {
+    let res = inst.rollback_to(o.clone());
+    if let Err(err) = &res {
+        if let Err(e) = inst.record_failure(&o, err) {
+            log::warn!("record failure in status err {}", e)
         }
     }
-    Ok(())
+    res
 }
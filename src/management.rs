@@ -0,0 +1,263 @@
+use failure::Error;
+use futures::sync::oneshot;
+use hyper::rt::Future;
+use hyper::service::service_fn;
+use hyper::{Body, Request, Response, Server, StatusCode};
+use kube::api::{ListParams, ObjectList, RawApi};
+use kube::client::APIClient;
+use log::{error, info};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::thread;
+
+use crate::instigator::{Instigator, OpResource, CONFIG_CRD, CONFIG_GROUP, CONFIG_VERSION};
+use crate::metrics::TimedRequest;
+
+/// Route is a request to the management API. Applications are the ApplicationConfigurations of a
+/// namespace, under `/api/v1/namespaces/<namespace>/applications`.
+#[derive(Debug, PartialEq)]
+pub enum Route {
+    /// `GET .../applications` lists the applications with their status.
+    List { namespace: String },
+    /// `GET .../applications/<name>` is one application with its status.
+    Get { namespace: String, name: String },
+    /// `POST .../applications/<name>/reconcile` queues the application for reconciliation.
+    Reconcile { namespace: String, name: String },
+    /// `GET .../applications/<name>/render` renders the resources of the application.
+    Render { namespace: String, name: String },
+    /// `GET .../applications/<name>/events` lists the events recorded for the application.
+    Events { namespace: String, name: String },
+}
+
+impl Route {
+    pub fn namespace(&self) -> &str {
+        match self {
+            Route::List { namespace }
+            | Route::Get { namespace, .. }
+            | Route::Reconcile { namespace, .. }
+            | Route::Render { namespace, .. }
+            | Route::Events { namespace, .. } => namespace.as_str(),
+        }
+    }
+}
+
+/// route returns the route of a request, None if there is no such route.
+pub fn route(method: &str, path: &str) -> Option<Route> {
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if parts.len() < 5 || parts[..3] != ["api", "v1", "namespaces"] || parts[4] != "applications" {
+        return None;
+    }
+    let namespace = parts[3].to_string();
+    let rest = &parts[5..];
+    match (method, rest) {
+        ("GET", []) => Some(Route::List { namespace }),
+        ("GET", [name]) => Some(Route::Get {
+            namespace,
+            name: name.to_string(),
+        }),
+        ("POST", [name, "reconcile"]) => Some(Route::Reconcile {
+            namespace,
+            name: name.to_string(),
+        }),
+        ("GET", [name, "render"]) => Some(Route::Render {
+            namespace,
+            name: name.to_string(),
+        }),
+        ("GET", [name, "events"]) => Some(Route::Events {
+            namespace,
+            name: name.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// listen_addr is the address to serve the management API on for `port`, e.g. `:8082`. Without a
+/// `token` every caller is authorized, so the API is only served on the loopback interface then.
+pub fn listen_addr(port: &str, token: Option<&str>) -> String {
+    let host = if token.is_some() {
+        "0.0.0.0"
+    } else {
+        "127.0.0.1"
+    };
+    host.to_owned() + port
+}
+
+/// authorized is true if no `token` is required or the `Authorization` header bears it.
+pub fn authorized(token: Option<&str>, header: Option<&str>) -> bool {
+    match token {
+        None => true,
+        Some(token) => header.map(|h| h == format!("Bearer {}", token)) == Some(true),
+    }
+}
+
+/// summary is an application as the management API returns it.
+pub fn summary(config: &OpResource) -> Value {
+    let status = config.status.clone().unwrap_or_default();
+    json!({
+        "name": config.metadata.name,
+        "namespace": config.metadata.namespace,
+        "generation": config.metadata.generation.map(|g| g as i64),
+        "phase": status.phase,
+        "observedGeneration": status.observed_generation,
+        "message": status.message,
+        "revision": status.revision,
+        "componentStates": status.component_states,
+    })
+}
+
+/// Management answers the requests to the management API with the permissions of rudr, so that
+/// platform UIs can integrate without access to the kube API.
+pub struct Management {
+    pub client: APIClient,
+    /// The namespaces rudr reconciles, `*` for all of them. Other namespaces are not served.
+    pub namespaces: Vec<String>,
+    /// The bearer token requests need, if any.
+    pub token: Option<String>,
+    /// instigator returns the instigator of a namespace with the settings of the controllers.
+    pub instigator: Box<dyn Fn(&str) -> Instigator + Send + Sync>,
+    /// reconcile queues an application for reconciliation by the controllers.
+    pub reconcile: Box<dyn Fn(OpResource) + Send + Sync>,
+}
+
+impl Management {
+    /// handle answers a request to `route`.
+    pub fn handle(&self, route: Route) -> Result<Value, Error> {
+        match route {
+            Route::List { namespace } => {
+                let req = self
+                    .configs(namespace.as_str())?
+                    .list(&ListParams::default())?;
+                let configs: ObjectList<OpResource> = self.client.timed_request(req)?;
+                Ok(json!({
+                    "items": configs.items.iter().map(summary).collect::<Vec<Value>>(),
+                }))
+            }
+            Route::Get { namespace, name } => Ok(summary(&self.get(&namespace, &name)?)),
+            Route::Reconcile { namespace, name } => {
+                let config = self.get(&namespace, &name)?;
+                info!("Management: reconcile {}/{}", namespace, name);
+                (self.reconcile)(config);
+                Ok(json!({"queued": true}))
+            }
+            Route::Render { namespace, name } => {
+                let config = self.get(&namespace, &name)?;
+                let objects = (self.instigator)(namespace.as_str()).render(&config)?;
+                Ok(json!({ "objects": objects }))
+            }
+            Route::Events { namespace, name } => {
+                self.configs(namespace.as_str())?;
+                let params = ListParams {
+                    field_selector: Some(format!(
+                        "involvedObject.kind=ApplicationConfiguration,involvedObject.name={}",
+                        name
+                    )),
+                    ..Default::default()
+                };
+                let req = RawApi::v1Event().within(namespace.as_str()).list(&params)?;
+                let events: ObjectList<kube::api::v1Event> = self.client.timed_request(req)?;
+                let items: Vec<Value> = events
+                    .items
+                    .iter()
+                    .map(|e| {
+                        json!({
+                            "type": e.type_,
+                            "reason": e.reason,
+                            "message": e.message,
+                            "count": e.count,
+                            "firstTimestamp": e.firstTimestamp,
+                            "lastTimestamp": e.lastTimestamp,
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "items": items }))
+            }
+        }
+    }
+
+    /// configs is the ApplicationConfiguration API of `namespace`, if rudr reconciles it.
+    fn configs(&self, namespace: &str) -> Result<RawApi, Error> {
+        if !self.manages(namespace) {
+            return Err(format_err!(
+                "namespace {} is not managed by rudr",
+                namespace
+            ));
+        }
+        Ok(RawApi::customResource(CONFIG_CRD)
+            .group(CONFIG_GROUP)
+            .version(CONFIG_VERSION)
+            .within(namespace))
+    }
+
+    /// manages is true if rudr reconciles the configurations of `namespace`.
+    pub fn manages(&self, namespace: &str) -> bool {
+        self.namespaces
+            .iter()
+            .any(|ns| ns == "*" || ns == namespace)
+    }
+
+    fn get(&self, namespace: &str, name: &str) -> Result<OpResource, Error> {
+        let req = self.configs(namespace)?.get(name)?;
+        Ok(self.client.timed_request(req)?)
+    }
+
+    fn respond(&self, req: &Request<Body>) -> Response<Body> {
+        let header = req
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok());
+        if !authorized(self.token.as_deref(), header) {
+            return json_response(StatusCode::UNAUTHORIZED, json!({"error": "unauthorized"}));
+        }
+        let route = match route(req.method().as_str(), req.uri().path()) {
+            Some(route) if self.manages(route.namespace()) => route,
+            _ => return json_response(StatusCode::NOT_FOUND, json!({"error": "not found"})),
+        };
+        match self.handle(route) {
+            Ok(body) => json_response(StatusCode::OK, body),
+            Err(e) => json_response(error_status(&e), json!({"error": e.to_string()})),
+        }
+    }
+}
+
+/// error_status is the status of the kube API error behind `e`, 500 for other errors.
+pub fn error_status(e: &Error) -> StatusCode {
+    crate::metrics::api_error(e)
+        .and_then(|err| StatusCode::from_u16(err.code).ok())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// serve runs the management API on `addr` until the process exits.
+pub fn serve(addr: &str, management: Arc<Management>) -> Result<(), Error> {
+    let addr = addr.parse()?;
+    info!("Management API is running on {}", addr);
+    hyper::rt::run(
+        Server::bind(&addr)
+            .serve(move || {
+                let management = management.clone();
+                service_fn(move |req: Request<Body>| {
+                    // The kube client is blocking, so each request is answered on its own thread.
+                    let (tx, rx) = oneshot::channel();
+                    let management = management.clone();
+                    thread::spawn(move || {
+                        let _ = tx.send(management.respond(&req));
+                    });
+                    rx.or_else(|_| {
+                        Ok::<_, hyper::Error>(json_response(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            json!({"error": "request dropped"}),
+                        ))
+                    })
+                })
+            })
+            .map_err(|e| error!("management API error: {}", e)),
+    );
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use serde_json::json;
+
+use crate::instigator::OpResource;
+use crate::management::*;
+
+#[test]
+fn test_route() {
+    let app = |name: &str| (String::from("prod"), name.to_string());
+    assert_eq!(
+        Some(Route::List {
+            namespace: "prod".to_string()
+        }),
+        route("GET", "/api/v1/namespaces/prod/applications/")
+    );
+    let (namespace, name) = app("shop");
+    assert_eq!(
+        Some(Route::Get { namespace, name }),
+        route("GET", "/api/v1/namespaces/prod/applications/shop")
+    );
+    let (namespace, name) = app("shop");
+    assert_eq!(
+        Some(Route::Reconcile { namespace, name }),
+        route(
+            "POST",
+            "/api/v1/namespaces/prod/applications/shop/reconcile"
+        )
+    );
+    let (namespace, name) = app("shop");
+    assert_eq!(
+        Some(Route::Render { namespace, name }),
+        route("GET", "/api/v1/namespaces/prod/applications/shop/render")
+    );
+    let (namespace, name) = app("shop");
+    assert_eq!(
+        Some(Route::Events { namespace, name }),
+        route("GET", "/api/v1/namespaces/prod/applications/shop/events")
+    );
+
+    assert_eq!(
+        None,
+        route("GET", "/api/v1/namespaces/prod/applications/shop/reconcile")
+    );
+    assert_eq!(
+        None,
+        route("DELETE", "/api/v1/namespaces/prod/applications/shop")
+    );
+    assert_eq!(None, route("GET", "/api/v1/namespaces/prod"));
+    assert_eq!(None, route("GET", "/"));
+}
+
+#[test]
+fn test_authorized() {
+    assert!(authorized(None, None));
+    assert!(authorized(Some("secret"), Some("Bearer secret")));
+    assert!(!authorized(Some("secret"), Some("Bearer other")));
+    assert!(!authorized(Some("secret"), None));
+}
+
+#[test]
+fn test_listen_addr() {
+    assert_eq!("0.0.0.0:8082", listen_addr(":8082", Some("secret")));
+    // Without a token only callers on the node of rudr reach the API.
+    assert_eq!("127.0.0.1:8082", listen_addr(":8082", None));
+}
+
+#[test]
+fn test_summary() {
+    let config: OpResource = serde_json::from_value(json!({
+        "apiVersion": "core.oam.dev/v1alpha1",
+        "kind": "ApplicationConfiguration",
+        "metadata": {"name": "shop", "namespace": "prod", "generation": 3},
+        "spec": {},
+        "status": {
+            "phase": "synced",
+            "observedGeneration": 3,
+            "componentStates": {"web": {"componentName": "web", "phase": "Running"}},
+        },
+    }))
+    .expect("decode configuration");
+    let summary = summary(&config);
+    assert_eq!("shop", summary["name"]);
+    assert_eq!("prod", summary["namespace"]);
+    assert_eq!(3, summary["generation"]);
+    assert_eq!("synced", summary["phase"]);
+    assert_eq!(3, summary["observedGeneration"]);
+    assert_eq!("Running", summary["componentStates"]["web"]["phase"]);
+}
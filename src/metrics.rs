@@ -0,0 +1,268 @@
+use failure::{Backtrace, Fail};
+use kube::client::APIClient;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::ops::Deref;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{audit, ratelimit, trace};
+
+/// Upper bounds in seconds of the reconcile duration buckets.
+const RECONCILE_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+/// Upper bounds in seconds of the kube API request latency buckets.
+const REQUEST_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+lazy_static! {
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+}
+
+/// Histogram counts observations into cumulative buckets the way Prometheus expects them.
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    buckets: Vec<(f64, u64)>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &[f64]) -> Self {
+        Histogram {
+            buckets: bounds.iter().map(|b| (*b, 0)).collect(),
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        for (bound, count) in self.buckets.iter_mut() {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (bound, count) in self.buckets.iter() {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, sep, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, sep, self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+/// Metrics of the controller, rendered in the Prometheus text format.
+#[derive(Debug)]
+pub struct Metrics {
+    /// Handled configuration events by event type and result.
+    reconciliations: BTreeMap<(String, String), u64>,
+    reconcile_duration: Histogram,
+    /// Failed applies by what failed: component, trait or scope.
+    errors: BTreeMap<String, u64>,
+    queue_depth: usize,
+    /// Kube API request latencies by HTTP method.
+    requests: BTreeMap<String, Histogram>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            reconciliations: BTreeMap::new(),
+            reconcile_duration: Histogram::new(RECONCILE_BUCKETS),
+            errors: BTreeMap::new(),
+            queue_depth: 0,
+            requests: BTreeMap::new(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn observe_reconcile(&mut self, event: &str, success: bool, duration: Duration) {
+        let result = if success { "success" } else { "error" };
+        *self
+            .reconciliations
+            .entry((event.to_string(), result.to_string()))
+            .or_insert(0) += 1;
+        self.reconcile_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn inc_error(&mut self, kind: &str) {
+        *self.errors.entry(kind.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn set_queue_depth(&mut self, depth: usize) {
+        self.queue_depth = depth;
+    }
+
+    pub fn observe_request(&mut self, method: &str, duration: Duration) {
+        self.requests
+            .entry(method.to_string())
+            .or_insert_with(|| Histogram::new(REQUEST_BUCKETS))
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str(
+            "# HELP rudr_reconciliations_total Handled ApplicationConfiguration events.\n",
+        );
+        out.push_str("# TYPE rudr_reconciliations_total counter\n");
+        for ((event, result), count) in self.reconciliations.iter() {
+            let _ = writeln!(
+                out,
+                "rudr_reconciliations_total{{event=\"{}\",result=\"{}\"}} {}",
+                event, result, count
+            );
+        }
+        out.push_str(
+            "# HELP rudr_reconcile_duration_seconds Time it took to handle a configuration event.\n",
+        );
+        out.push_str("# TYPE rudr_reconcile_duration_seconds histogram\n");
+        self.reconcile_duration
+            .render(&mut out, "rudr_reconcile_duration_seconds", "");
+        out.push_str("# HELP rudr_reconcile_errors_total Failed applies by what failed.\n");
+        out.push_str("# TYPE rudr_reconcile_errors_total counter\n");
+        for (kind, count) in self.errors.iter() {
+            let _ = writeln!(
+                out,
+                "rudr_reconcile_errors_total{{kind=\"{}\"}} {}",
+                kind, count
+            );
+        }
+        out.push_str("# HELP rudr_work_queue_depth Failed events waiting to be retried.\n");
+        out.push_str("# TYPE rudr_work_queue_depth gauge\n");
+        let _ = writeln!(out, "rudr_work_queue_depth {}", self.queue_depth);
+        out.push_str(
+            "# HELP rudr_kube_request_duration_seconds Latency of requests to the kube API server.\n",
+        );
+        out.push_str("# TYPE rudr_kube_request_duration_seconds histogram\n");
+        for (method, histogram) in self.requests.iter() {
+            histogram.render(
+                &mut out,
+                "rudr_kube_request_duration_seconds",
+                format!("method=\"{}\"", method).as_str(),
+            );
+        }
+        out
+    }
+}
+
+fn with_metrics<F: FnOnce(&mut Metrics)>(f: F) {
+    if let Ok(mut metrics) = METRICS.lock() {
+        f(&mut metrics)
+    }
+}
+
+/// observe_reconcile counts a handled configuration event of type `event`, e.g. "added".
+pub fn observe_reconcile(event: &str, success: bool, duration: Duration) {
+    with_metrics(|m| m.observe_reconcile(event, success, duration))
+}
+
+/// inc_error counts a failed apply of a component, trait or scope.
+pub fn inc_error(kind: &str) {
+    with_metrics(|m| m.inc_error(kind))
+}
+
+pub fn set_queue_depth(depth: usize) {
+    with_metrics(|m| m.set_queue_depth(depth))
+}
+
+/// render returns the metrics of the process in the Prometheus text format.
+pub fn render() -> String {
+    METRICS.lock().map(|m| m.render()).unwrap_or_default()
+}
+
+/// KubeError is the error of `APIClient::request`, boxed because a `kube::Error` is too large to
+/// return by value from every request.
+#[derive(Debug)]
+pub struct KubeError(Box<kube::Error>);
+
+impl Deref for KubeError {
+    type Target = kube::Error;
+    fn deref(&self) -> &kube::Error {
+        &self.0
+    }
+}
+
+impl fmt::Display for KubeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl Fail for KubeError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.0.cause()
+    }
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.0.backtrace()
+    }
+}
+
+impl From<kube::Error> for KubeError {
+    fn from(e: kube::Error) -> Self {
+        KubeError(Box::new(e))
+    }
+}
+
+impl From<KubeError> for kube::Error {
+    fn from(e: KubeError) -> Self {
+        *e.0
+    }
+}
+
+/// api_error is the error the API server answered with behind `e`, if there is one.
+pub fn api_error(e: &failure::Error) -> Option<kube::ApiError> {
+    match e.downcast_ref::<KubeError>() {
+        Some(e) => e.api_error(),
+        None => e.downcast_ref::<kube::Error>().and_then(|e| e.api_error()),
+    }
+}
+
+/// TimedRequest sends a request like `APIClient::request` once the rate limit of the process
+/// allows it, and records its latency.
+pub trait TimedRequest {
+    fn timed_request<T: DeserializeOwned>(
+        &self,
+        req: http::Request<Vec<u8>>,
+    ) -> Result<T, KubeError>;
+}
+
+impl TimedRequest for APIClient {
+    fn timed_request<T: DeserializeOwned>(
+        &self,
+        req: http::Request<Vec<u8>>,
+    ) -> Result<T, KubeError> {
+        let method = req.method().to_string();
+        let mut span = trace::span("kube.request");
+        span.set_attribute("http.method", method.as_str());
+        span.set_attribute("http.target", req.uri().path());
+        let entry = audit::entry(&req);
+        ratelimit::wait();
+        let start = Instant::now();
+        let res = self.request(req);
+        span.record(&res);
+        audit::record(entry, res.as_ref().err().map(|e| e.to_string()));
+        let elapsed = start.elapsed();
+        with_metrics(|m| m.observe_request(method.as_str(), elapsed));
+        res.map_err(KubeError::from)
+    }
+}
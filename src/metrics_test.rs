@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use crate::metrics::*;
+
+#[test]
+fn test_histogram() {
+    let mut metrics = Metrics::default();
+    metrics.observe_request("GET", Duration::from_millis(50));
+    metrics.observe_request("GET", Duration::from_secs(10));
+    let out = metrics.render();
+    assert!(out.contains("# TYPE rudr_kube_request_duration_seconds histogram\n"));
+    assert!(
+        out.contains("rudr_kube_request_duration_seconds_bucket{method=\"GET\",le=\"0.05\"} 1\n")
+    );
+    assert!(out.contains("rudr_kube_request_duration_seconds_bucket{method=\"GET\",le=\"5\"} 1\n"));
+    assert!(
+        out.contains("rudr_kube_request_duration_seconds_bucket{method=\"GET\",le=\"+Inf\"} 2\n")
+    );
+    assert!(out.contains("rudr_kube_request_duration_seconds_count{method=\"GET\"} 2\n"));
+}
+
+#[test]
+fn test_render() {
+    let mut metrics = Metrics::default();
+    metrics.observe_reconcile("added", true, Duration::from_millis(200));
+    metrics.observe_reconcile("added", false, Duration::from_secs(3));
+    metrics.observe_reconcile("added", true, Duration::from_millis(20));
+    metrics.inc_error("trait");
+    metrics.set_queue_depth(2);
+    let out = metrics.render();
+    assert!(out.contains("rudr_reconciliations_total{event=\"added\",result=\"success\"} 2\n"));
+    assert!(out.contains("rudr_reconciliations_total{event=\"added\",result=\"error\"} 1\n"));
+    assert!(out.contains("rudr_reconcile_duration_seconds_bucket{le=\"0.25\"} 2\n"));
+    assert!(out.contains("rudr_reconcile_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+    assert!(out.contains("rudr_reconcile_duration_seconds_count 3\n"));
+    assert!(out.contains("rudr_reconcile_errors_total{kind=\"trait\"} 1\n"));
+    assert!(out.contains("rudr_work_queue_depth 2\n"));
+}
+
+#[test]
+fn test_api_error() {
+    let not_found = || {
+        kube::Error::from(kube::ErrorKind::Api(kube::ApiError {
+            status: "Failure".to_string(),
+            message: "configmaps \"squid\" not found".to_string(),
+            reason: "NotFound".to_string(),
+            code: 404,
+        }))
+    };
+    // Requests fail with a boxed error, its API error is the one of the kube error.
+    let e: failure::Error = KubeError::from(not_found()).into();
+    assert_eq!(Some(404), api_error(&e).map(|err| err.code));
+    assert_eq!(not_found().to_string(), e.to_string());
+    let e: failure::Error = not_found().into();
+    assert_eq!(Some(404), api_error(&e).map(|err| err.code));
+    assert!(api_error(&format_err!("not found")).is_none());
+}
@@ -0,0 +1,99 @@
+use failure::Error;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref MUTATORS: RwLock<Mutators> = RwLock::new(Mutators::default());
+}
+
+/// Mutator post-processes the Kubernetes objects rudr renders before they are applied, to add
+/// what every object of an installation needs, like company-standard labels, a sidecar container
+/// or a priority class.
+///
+/// Mutators see every object rudr creates or updates: workloads, trait resources, hook Jobs and
+/// namespaces. Rendered objects are mutated again on every reconciliation and drift check, so a
+/// mutator has to be idempotent.
+pub trait Mutator: Send + Sync {
+    /// name identifies the mutator in errors.
+    fn name(&self) -> &str;
+    /// mutate changes `object` in place. An error fails the apply of the object.
+    fn mutate(&self, object: &mut serde_json::Value) -> Result<(), Error>;
+}
+
+/// Mutators runs a list of mutators in the order they were added.
+#[derive(Default)]
+pub struct Mutators {
+    mutators: Vec<Box<dyn Mutator>>,
+}
+
+impl Mutators {
+    pub fn add(&mut self, mutator: Box<dyn Mutator>) {
+        self.mutators.push(mutator)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mutators.is_empty()
+    }
+
+    /// mutate passes `object` through every mutator.
+    pub fn mutate(&self, mut object: serde_json::Value) -> Result<serde_json::Value, Error> {
+        for mutator in self.mutators.iter() {
+            mutator
+                .mutate(&mut object)
+                .map_err(|e| format_err!("mutator {}: {}", mutator.name(), e))?;
+        }
+        Ok(object)
+    }
+}
+
+/// register adds a mutator for every object rudr applies from now on. Mutators are meant to be
+/// registered at startup, before the controllers run.
+pub fn register(mutator: Box<dyn Mutator>) {
+    if let Ok(mut mutators) = MUTATORS.write() {
+        mutators.add(mutator)
+    }
+}
+
+/// mutate passes `object` through the registered mutators.
+pub fn mutate(object: serde_json::Value) -> Result<serde_json::Value, Error> {
+    match MUTATORS.read() {
+        Ok(mutators) if !mutators.is_empty() => mutators.mutate(object),
+        _ => Ok(object),
+    }
+}
+
+/// InjectLabels adds labels to every object that doesn't set them itself.
+pub struct InjectLabels {
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Mutator for InjectLabels {
+    fn name(&self) -> &str {
+        "inject-labels"
+    }
+
+    fn mutate(&self, object: &mut serde_json::Value) -> Result<(), Error> {
+        let labels = field(object, "metadata")
+            .and_then(|metadata| field(metadata, "labels"))
+            .and_then(serde_json::Value::as_object_mut)
+            .ok_or_else(|| format_err!("object without metadata"))?;
+        for (key, value) in self.labels.iter() {
+            labels
+                .entry(key.as_str())
+                .or_insert_with(|| serde_json::Value::String(value.clone()));
+        }
+        Ok(())
+    }
+}
+
+/// field returns the object `key` of `value`, which is added if it isn't set.
+fn field<'a>(value: &'a mut serde_json::Value, key: &str) -> Option<&'a mut serde_json::Value> {
+    let field = value
+        .as_object_mut()?
+        .entry(key)
+        .or_insert(serde_json::Value::Null);
+    if field.is_null() {
+        *field = serde_json::json!({});
+    }
+    Some(field).filter(|field| field.is_object())
+}
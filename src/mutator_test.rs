@@ -0,0 +1,68 @@
+use crate::mutator::*;
+use failure::Error;
+
+struct PriorityClass;
+
+impl Mutator for PriorityClass {
+    fn name(&self) -> &str {
+        "priority-class"
+    }
+
+    fn mutate(&self, object: &mut serde_json::Value) -> Result<(), Error> {
+        if object["kind"] != "Deployment" {
+            return Ok(());
+        }
+        match object.pointer_mut("/spec/template/spec") {
+            Some(spec) => {
+                spec["priorityClassName"] = serde_json::json!("business-critical");
+                Ok(())
+            }
+            None => Err(format_err!("deployment without pod spec")),
+        }
+    }
+}
+
+#[test]
+fn test_mutators() {
+    let mut mutators = Mutators::default();
+    assert!(mutators.is_empty());
+    let mut labels = std::collections::BTreeMap::new();
+    labels.insert("cost-center".to_string(), "retail".to_string());
+    labels.insert("app".to_string(), "default".to_string());
+    mutators.add(Box::new(InjectLabels { labels }));
+    mutators.add(Box::new(PriorityClass));
+
+    let deployment = mutators
+        .mutate(serde_json::json!({
+            "kind": "Deployment",
+            "metadata": {"name": "web", "labels": {"app": "web"}},
+            "spec": {"template": {"spec": {"containers": []}}},
+        }))
+        .expect("mutate deployment");
+    // Labels the object sets win over the injected ones.
+    assert_eq!(
+        serde_json::json!({"app": "web", "cost-center": "retail"}),
+        deployment["metadata"]["labels"]
+    );
+    assert_eq!(
+        "business-critical",
+        deployment["spec"]["template"]["spec"]["priorityClassName"]
+    );
+
+    let service = mutators
+        .mutate(serde_json::json!({"kind": "Service", "metadata": {"name": "web"}}))
+        .expect("mutate service");
+    assert_eq!(
+        serde_json::json!({"app": "default", "cost-center": "retail"}),
+        service["metadata"]["labels"]
+    );
+    assert!(service.get("spec").is_none());
+
+    let err = mutators
+        .mutate(serde_json::json!({"kind": "Deployment", "metadata": {"name": "web"}}))
+        .expect_err("mutate deployment without spec");
+    assert_eq!(
+        "mutator priority-class: deployment without pod spec",
+        err.to_string()
+    );
+}
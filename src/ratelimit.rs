@@ -0,0 +1,75 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The average number of requests per second to the kube API server by default.
+pub const DEFAULT_QPS: f64 = 20.0;
+/// The number of requests that may be sent at once by default.
+pub const DEFAULT_BURST: u32 = 30;
+
+lazy_static! {
+    static ref LIMITER: Mutex<Option<RateLimiter>> = Mutex::new(None);
+}
+
+/// RateLimiter is a token bucket that allows `burst` requests at once, and `qps` requests per
+/// second on average.
+#[derive(Debug)]
+pub struct RateLimiter {
+    qps: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(qps: f64, burst: u32, now: Instant) -> Self {
+        let burst = f64::from(burst.max(1));
+        RateLimiter {
+            qps,
+            burst,
+            tokens: burst,
+            last: now,
+        }
+    }
+
+    /// reserve takes a token for a request at `now` and returns how long the request has to wait
+    /// for it. Requests that wait queue up: every one of them waits for a token of its own.
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        if now > self.last {
+            let elapsed = now.duration_since(self.last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.qps).min(self.burst);
+            self.last = now;
+        }
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.qps)
+        }
+    }
+}
+
+/// configure limits the requests of the process to the kube API server to `qps` per second on
+/// average, in bursts of up to `burst`. A `qps` of 0 lifts the limit.
+pub fn configure(qps: f64, burst: u32) {
+    if let Ok(mut limiter) = LIMITER.lock() {
+        *limiter = if qps > 0.0 {
+            Some(RateLimiter::new(qps, burst, Instant::now()))
+        } else {
+            None
+        };
+    }
+}
+
+/// wait blocks until the next request may be sent.
+pub fn wait() {
+    let delay = match LIMITER.lock() {
+        Ok(mut limiter) => limiter
+            .as_mut()
+            .map(|l| l.reserve(Instant::now()))
+            .unwrap_or_default(),
+        Err(_) => Duration::from_secs(0),
+    };
+    if delay > Duration::from_secs(0) {
+        std::thread::sleep(delay);
+    }
+}
@@ -0,0 +1,28 @@
+use std::time::{Duration, Instant};
+
+use crate::ratelimit::*;
+
+#[test]
+fn test_rate_limiter() {
+    let now = Instant::now();
+    let mut limiter = RateLimiter::new(10.0, 3, now);
+    // A burst goes out right away.
+    for _ in 0..3 {
+        assert_eq!(Duration::from_secs(0), limiter.reserve(now));
+    }
+    // Then every request waits for a token of its own.
+    assert_eq!(Duration::from_millis(100), limiter.reserve(now));
+    assert_eq!(Duration::from_millis(200), limiter.reserve(now));
+
+    // The queued requests used up the tokens of the next 200ms.
+    let later = now + Duration::from_millis(300);
+    assert_eq!(Duration::from_secs(0), limiter.reserve(later));
+    assert_eq!(Duration::from_millis(100), limiter.reserve(later));
+
+    // Tokens don't add up beyond the burst while nothing is sent.
+    let idle = later + Duration::from_secs(60);
+    for _ in 0..3 {
+        assert_eq!(Duration::from_secs(0), limiter.reserve(idle));
+    }
+    assert_eq!(Duration::from_millis(100), limiter.reserve(idle));
+}
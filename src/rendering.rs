@@ -0,0 +1,104 @@
+use failure::Error;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use kube::{api::RawApi, client::APIClient};
+use serde_json::json;
+
+use crate::metrics::TimedRequest;
+use crate::workload_type::apply;
+
+/// The key of the rendering ConfigMap that holds the applied objects, a JSON list.
+pub const RENDERING_KEY: &str = "manifests.json";
+/// The label that ties a rendering ConfigMap to the component instance it was recorded for.
+pub const RENDERING_LABEL: &str = "core.oam.dev/component-instance";
+/// What the values of Secrets are recorded as.
+pub const REDACTED: &str = "<redacted>";
+
+/// Renderings records the objects rudr applied last for each component instance, mutators
+/// included, in a ConfigMap next to the instance. It is the baseline to tell what rudr applied
+/// from what other controllers or users changed.
+pub struct Renderings {
+    pub client: APIClient,
+    pub namespace: String,
+}
+
+impl Renderings {
+    /// record stores `objects` as the rendering of the component instance `instance_name`. The
+    /// ConfigMap is owned by `owner`, the component instance, and goes away with it.
+    pub fn record(
+        &self,
+        instance_name: &str,
+        objects: &[serde_json::Value],
+        owner: Option<Vec<meta::OwnerReference>>,
+    ) -> Result<(), Error> {
+        let name = rendering_name(instance_name);
+        let config_map = rendering_config_map(instance_name, objects, owner)?;
+        let resource = RawApi::v1ConfigMap().within(self.namespace.as_str());
+        apply(&self.client, &resource, name.as_str(), &config_map)
+    }
+
+    /// get returns the objects recorded for the component instance `instance_name`.
+    pub fn get(&self, instance_name: &str) -> Result<Vec<serde_json::Value>, Error> {
+        let req = RawApi::v1ConfigMap()
+            .within(self.namespace.as_str())
+            .get(rendering_name(instance_name).as_str())?;
+        let config_map = self.client.timed_request::<serde_json::Value>(req)?;
+        manifests(&config_map)
+    }
+}
+
+pub fn rendering_name(instance_name: &str) -> String {
+    format!("{}-rendering", instance_name)
+}
+
+/// rendering_config_map is the ConfigMap recording `objects` for the component instance
+/// `instance_name`. ConfigMaps can be read by more users than Secrets, so the Secrets among
+/// `objects` are recorded with their keys but without their values, see `redact`.
+pub fn rendering_config_map(
+    instance_name: &str,
+    objects: &[serde_json::Value],
+    owner: Option<Vec<meta::OwnerReference>>,
+) -> Result<serde_json::Value, Error> {
+    let objects: Vec<serde_json::Value> = objects.iter().map(redact).collect();
+    let mut config_map = json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": rendering_name(instance_name),
+            "labels": {
+                RENDERING_LABEL: instance_name,
+            },
+        },
+        "data": {
+            RENDERING_KEY: serde_json::to_string_pretty(&objects)?,
+        },
+    });
+    if let Some(owner) = owner.filter(|refs| !refs.is_empty()) {
+        config_map["metadata"]["ownerReferences"] = serde_json::to_value(owner)?;
+    }
+    Ok(config_map)
+}
+
+/// redact replaces every value in the `data` and `stringData` of a Secret with `REDACTED`, other
+/// objects are returned as they are.
+pub fn redact(object: &serde_json::Value) -> serde_json::Value {
+    let mut object = object.clone();
+    if object["kind"] != "Secret" {
+        return object;
+    }
+    for field in &["data", "stringData"] {
+        if let Some(values) = object.get_mut(*field).and_then(|v| v.as_object_mut()) {
+            for value in values.values_mut() {
+                *value = json!(REDACTED);
+            }
+        }
+    }
+    object
+}
+
+/// manifests returns the objects recorded in a rendering ConfigMap.
+pub fn manifests(config_map: &serde_json::Value) -> Result<Vec<serde_json::Value>, Error> {
+    let data = config_map["data"][RENDERING_KEY]
+        .as_str()
+        .ok_or_else(|| format_err!("rendering without {}", RENDERING_KEY))?;
+    Ok(serde_json::from_str(data)?)
+}
@@ -0,0 +1,71 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+
+use crate::rendering::*;
+
+#[test]
+fn test_rendering_config_map() {
+    let objects = vec![
+        json!({"apiVersion": "apps/v1", "kind": "Deployment", "metadata": {"name": "web"}}),
+        json!({"apiVersion": "v1", "kind": "Service", "metadata": {"name": "web"}}),
+    ];
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".to_string(),
+        kind: "ComponentInstance".to_string(),
+        name: "web-1".to_string(),
+        uid: "uid".to_string(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let config_map = rendering_config_map("web-1", &objects, Some(vec![owner])).unwrap();
+    assert_eq!("web-1-rendering", config_map["metadata"]["name"]);
+    assert_eq!("web-1", config_map["metadata"]["labels"][RENDERING_LABEL]);
+    assert_eq!("uid", config_map["metadata"]["ownerReferences"][0]["uid"]);
+    assert_eq!(objects, manifests(&config_map).unwrap());
+
+    // Without an owner the ConfigMap has no owner references at all.
+    let config_map = rendering_config_map("web-1", &objects, Some(vec![])).unwrap();
+    assert!(config_map["metadata"].get("ownerReferences").is_none());
+
+    assert!(manifests(&json!({"data": {}})).is_err());
+}
+
+#[test]
+fn test_rendering_redacts_secrets() {
+    let secret = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": {"name": "web-trait-image-pull-secret"},
+        "type": "kubernetes.io/dockerconfigjson",
+        "data": {"token": "czNjcmV0"},
+        "stringData": {".dockerconfigjson": "{\"auths\": {}}"},
+    });
+    let deployment = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": {"name": "web"},
+        "data": {"token": "not a secret"},
+    });
+    let config_map =
+        rendering_config_map("web-1", &[secret.clone(), deployment.clone()], None).unwrap();
+    let recorded = manifests(&config_map).unwrap();
+    // The keys of the Secret are kept, so the rendering still tells which ones rudr applied.
+    assert_eq!(
+        json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": {"name": "web-trait-image-pull-secret"},
+            "type": "kubernetes.io/dockerconfigjson",
+            "data": {"token": REDACTED},
+            "stringData": {".dockerconfigjson": REDACTED},
+        }),
+        recorded[0]
+    );
+    assert_eq!(deployment, recorded[1]);
+    let data = config_map["data"][RENDERING_KEY].as_str().unwrap();
+    assert!(!data.contains("czNjcmV0") && !data.contains("auths"));
+
+    // A Secret without values is recorded as it is.
+    let empty = json!({"apiVersion": "v1", "kind": "Secret", "metadata": {"name": "empty"}});
+    assert_eq!(empty, redact(&empty));
+}
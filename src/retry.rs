@@ -0,0 +1,114 @@
+use failure::Error;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// How many times a read-modify-write of an object is attempted before a conflict is returned.
+pub const CONFLICT_ATTEMPTS: u32 = 5;
+
+/// RetryQueue holds work that failed, keyed by the object it belongs to, until its backoff ran
+/// out. Every failure of a key doubles its delay, from `base` up to `max`, until the key is
+/// forgotten after a success.
+pub struct RetryQueue<T> {
+    base: Duration,
+    max: Duration,
+    failures: BTreeMap<String, u32>,
+    pending: BTreeMap<String, (Instant, T)>,
+}
+
+impl<T> RetryQueue<T> {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        RetryQueue {
+            base,
+            max,
+            failures: BTreeMap::new(),
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// backoff is the delay after the given number of consecutive failures.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let exp = failures.saturating_sub(1).min(31);
+        self.base
+            .checked_mul(1 << exp)
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+
+    /// requeue records a failure of `key` and schedules `item` after its backoff, replacing work
+    /// of `key` that is still waiting. It returns the delay.
+    pub fn requeue(&mut self, key: &str, item: T, now: Instant) -> Duration {
+        let failures = {
+            let failures = self.failures.entry(key.to_string()).or_insert(0);
+            *failures += 1;
+            *failures
+        };
+        let delay = self.backoff(failures);
+        self.pending.insert(key.to_string(), (now + delay, item));
+        delay
+    }
+
+    /// schedule queues `item` for `key` at `at` without counting a failure, replacing work of
+    /// `key` that is still waiting.
+    pub fn schedule(&mut self, key: &str, item: T, at: Instant) {
+        self.pending.insert(key.to_string(), (at, item));
+    }
+
+    /// forget drops the backoff state and waiting work of `key`.
+    pub fn forget(&mut self, key: &str) {
+        self.failures.remove(key);
+        self.pending.remove(key);
+    }
+
+    /// pop_ready removes and returns the work whose backoff ran out by `now`. The failure count
+    /// of the keys is kept, so that another failure backs off further.
+    pub fn pop_ready(&mut self, now: Instant) -> Vec<(String, T)> {
+        let ready: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, (at, _))| *at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        ready
+            .into_iter()
+            .filter_map(|key| self.pending.remove(&key).map(|(_, item)| (key, item)))
+            .collect()
+    }
+
+    /// next_ready is when the earliest waiting work is due.
+    pub fn next_ready(&self) -> Option<Instant> {
+        self.pending.values().map(|(at, _)| *at).min()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// is_conflict is true if `e` is the error the API server returns when an update was based on an
+/// outdated resourceVersion of the object.
+pub fn is_conflict(e: &Error) -> bool {
+    crate::metrics::api_error(e).map(|err| err.code == 409 || err.reason == "Conflict")
+        == Some(true)
+}
+
+/// on_conflict calls `update` with the number of the attempt until it doesn't fail with a
+/// conflict, at most `attempts` times. Every attempt after the first has to read the object again
+/// and re-apply its change to it.
+pub fn on_conflict<T, F>(attempts: u32, mut update: F) -> Result<T, Error>
+where
+    F: FnMut(u32) -> Result<T, Error>,
+{
+    let mut attempt = 0;
+    loop {
+        match update(attempt) {
+            Err(e) if is_conflict(&e) && attempt + 1 < attempts => attempt += 1,
+            Err(e) if is_conflict(&e) => {
+                return Err(format_err!("gave up after {} conflicts: {}", attempts, e))
+            }
+            res => return res,
+        }
+    }
+}
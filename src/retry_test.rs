@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use crate::retry::*;
+
+#[test]
+fn test_backoff() {
+    let queue: RetryQueue<()> = RetryQueue::new(Duration::from_secs(1), Duration::from_secs(60));
+    assert_eq!(Duration::from_secs(1), queue.backoff(1));
+    assert_eq!(Duration::from_secs(2), queue.backoff(2));
+    assert_eq!(Duration::from_secs(32), queue.backoff(6));
+    assert_eq!(Duration::from_secs(60), queue.backoff(7));
+    assert_eq!(Duration::from_secs(60), queue.backoff(100));
+}
+
+#[test]
+fn test_retry_queue() {
+    let mut queue = RetryQueue::new(Duration::from_secs(1), Duration::from_secs(60));
+    let now = Instant::now();
+    assert_eq!(Duration::from_secs(1), queue.requeue("a", 1, now));
+    assert_eq!(Duration::from_secs(1), queue.requeue("b", 1, now));
+    // A second failure replaces the waiting work and backs off further.
+    assert_eq!(Duration::from_secs(2), queue.requeue("a", 2, now));
+    assert_eq!(2, queue.len());
+    assert_eq!(Some(now + Duration::from_secs(1)), queue.next_ready());
+
+    assert!(queue.pop_ready(now).is_empty());
+    assert_eq!(
+        vec![("b".to_string(), 1)],
+        queue.pop_ready(now + Duration::from_secs(1))
+    );
+    assert_eq!(
+        vec![("a".to_string(), 2)],
+        queue.pop_ready(now + Duration::from_secs(2))
+    );
+    assert!(queue.is_empty());
+
+    // The failure count survives popping, until the key is forgotten.
+    assert_eq!(Duration::from_secs(4), queue.requeue("a", 3, now));
+    queue.forget("a");
+    assert!(queue.is_empty());
+    assert_eq!(Duration::from_secs(1), queue.requeue("a", 4, now));
+}
+
+#[test]
+fn test_schedule() {
+    let mut queue = RetryQueue::new(Duration::from_secs(1), Duration::from_secs(60));
+    let now = Instant::now();
+    queue.requeue("a", 1, now);
+    queue.requeue("a", 2, now);
+    // Scheduled work replaces the waiting retry and doesn't add to the backoff.
+    queue.schedule("a", 3, now);
+    assert_eq!(vec![("a".to_string(), 3)], queue.pop_ready(now));
+    assert_eq!(Duration::from_secs(4), queue.requeue("a", 4, now));
+}
+
+#[test]
+fn test_on_conflict() {
+    let conflict = || -> failure::Error {
+        kube::Error::from(kube::ErrorKind::Api(kube::ApiError {
+            status: "Failure".to_string(),
+            message: "the object has been modified".to_string(),
+            reason: "Conflict".to_string(),
+            code: 409,
+        }))
+        .into()
+    };
+    assert!(is_conflict(&conflict()));
+    assert!(!is_conflict(&format_err!("not found")));
+
+    // Conflicts are retried until the update goes through.
+    let mut calls = vec![];
+    let res = on_conflict(CONFLICT_ATTEMPTS, |attempt| {
+        calls.push(attempt);
+        if attempt < 2 {
+            Err(conflict())
+        } else {
+            Ok(attempt)
+        }
+    });
+    assert_eq!(2, res.expect("update after two conflicts"));
+    assert_eq!(vec![0, 1, 2], calls);
+
+    // Other errors aren't retried, and conflicts only a bounded number of times.
+    let mut calls = 0;
+    assert!(
+        on_conflict(CONFLICT_ATTEMPTS, |_| -> Result<(), failure::Error> {
+            calls += 1;
+            Err(format_err!("not found"))
+        })
+        .is_err()
+    );
+    assert_eq!(1, calls);
+    let mut calls = 0;
+    assert!(
+        on_conflict(CONFLICT_ATTEMPTS, |_| -> Result<(), failure::Error> {
+            calls += 1;
+            Err(conflict())
+        })
+        .is_err()
+    );
+    assert_eq!(CONFLICT_ATTEMPTS, calls);
+}
@@ -0,0 +1,129 @@
+use failure::Error;
+use k8s_openapi::api::apps::v1::ControllerRevision;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use k8s_openapi::apimachinery::pkg::runtime::RawExtension;
+use kube::{api::ObjectList, client::APIClient};
+use std::collections::BTreeMap;
+
+use crate::metrics::TimedRequest;
+use crate::schematic::configuration::ApplicationConfiguration;
+
+/// The label that ties a ControllerRevision to the ApplicationConfiguration it was recorded for.
+pub const REVISION_LABEL: &str = "core.oam.dev/application-configuration";
+/// An annotation that asks the instigator to re-apply a recorded revision of a configuration.
+pub const ROLLBACK_ANNOTATION: &str = "core.oam.dev/rollback-to";
+/// How many revisions are kept per configuration, older ones are deleted.
+pub const REVISION_HISTORY_LIMIT: usize = 10;
+
+/// History records the applied specs of an ApplicationConfiguration as ControllerRevisions.
+pub struct History {
+    pub client: APIClient,
+    pub namespace: String,
+    pub config_name: String,
+}
+
+impl History {
+    /// list returns the revisions of the configuration, oldest first.
+    pub fn list(&self) -> Result<Vec<ControllerRevision>, Error> {
+        let selector = format!("{}={}", REVISION_LABEL, self.config_name);
+        let (req, _) = ControllerRevision::list_namespaced_controller_revision(
+            self.namespace.as_str(),
+            k8s_openapi::ListOptional {
+                label_selector: Some(selector.as_str()),
+                ..Default::default()
+            },
+        )?;
+        let mut revisions = self
+            .client
+            .timed_request::<ObjectList<ControllerRevision>>(req)?
+            .items;
+        revisions.sort_by_key(|r| r.revision);
+        Ok(revisions)
+    }
+
+    /// record stores `spec` as the newest revision, unless it matches the newest revision already,
+    /// and deletes the revisions beyond REVISION_HISTORY_LIMIT. It returns the revision of `spec`.
+    pub fn record(
+        &self,
+        spec: &ApplicationConfiguration,
+        owner: meta::OwnerReference,
+    ) -> Result<i64, Error> {
+        let revisions = self.list()?;
+        let data = serde_json::to_value(spec)?;
+        if let Some(latest) = revisions.last() {
+            if latest.data.as_ref().map(|d| &d.0) == Some(&data) {
+                return Ok(latest.revision);
+            }
+        }
+        let revision = next_revision(&revisions);
+        let mut labels = BTreeMap::new();
+        labels.insert(REVISION_LABEL.to_string(), self.config_name.clone());
+        let controller_revision = ControllerRevision {
+            data: Some(RawExtension(data)),
+            metadata: Some(meta::ObjectMeta {
+                name: Some(revision_name(self.config_name.as_str(), revision)),
+                labels: Some(labels),
+                owner_references: Some(vec![owner]),
+                ..Default::default()
+            }),
+            revision,
+        };
+        let (req, _) = ControllerRevision::create_namespaced_controller_revision(
+            self.namespace.as_str(),
+            &controller_revision,
+            Default::default(),
+        )?;
+        self.client.timed_request::<serde_json::Value>(req)?;
+        for name in prunable(&revisions, REVISION_HISTORY_LIMIT - 1) {
+            let (req, _) = ControllerRevision::delete_namespaced_controller_revision(
+                name.as_str(),
+                self.namespace.as_str(),
+                Default::default(),
+            )?;
+            if let Err(e) = self.client.timed_request::<serde_json::Value>(req) {
+                log::warn!("delete revision {} err {:?}", name, e);
+            }
+        }
+        Ok(revision)
+    }
+
+    /// get returns the spec recorded as `revision`.
+    pub fn get(&self, revision: i64) -> Result<ApplicationConfiguration, Error> {
+        let found = self
+            .list()?
+            .into_iter()
+            .find(|r| r.revision == revision)
+            .ok_or_else(|| {
+                format_err!(
+                    "revision {} of configuration {} not found",
+                    revision,
+                    self.config_name
+                )
+            })?;
+        let data = found
+            .data
+            .ok_or_else(|| format_err!("revision {} has no data", revision))?;
+        Ok(serde_json::from_value(data.0)?)
+    }
+}
+
+pub fn revision_name(config_name: &str, revision: i64) -> String {
+    format!("{}-{}", config_name, revision)
+}
+
+/// next_revision is one above the newest of `revisions`.
+pub fn next_revision(revisions: &[ControllerRevision]) -> i64 {
+    revisions.iter().map(|r| r.revision).max().unwrap_or(0) + 1
+}
+
+/// prunable returns the names of the oldest revisions, so that at most `keep` of them are left.
+pub fn prunable(revisions: &[ControllerRevision], keep: usize) -> Vec<String> {
+    let mut sorted: Vec<&ControllerRevision> = revisions.iter().collect();
+    sorted.sort_by_key(|r| r.revision);
+    let excess = sorted.len().saturating_sub(keep);
+    sorted
+        .into_iter()
+        .take(excess)
+        .filter_map(|r| r.metadata.as_ref().and_then(|m| m.name.clone()))
+        .collect()
+}
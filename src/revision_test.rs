@@ -0,0 +1,35 @@
+use k8s_openapi::api::apps::v1::ControllerRevision;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+
+use crate::revision::*;
+
+fn controller_revision(revision: i64) -> ControllerRevision {
+    ControllerRevision {
+        data: None,
+        metadata: Some(meta::ObjectMeta {
+            name: Some(revision_name("app", revision)),
+            ..Default::default()
+        }),
+        revision,
+    }
+}
+
+#[test]
+fn test_next_revision() {
+    assert_eq!(1, next_revision(&[]));
+    let revisions = vec![controller_revision(3), controller_revision(1)];
+    assert_eq!(4, next_revision(&revisions));
+}
+
+#[test]
+fn test_prunable() {
+    let revisions = vec![
+        controller_revision(2),
+        controller_revision(4),
+        controller_revision(1),
+        controller_revision(3),
+    ];
+    assert_eq!(vec!["app-1", "app-2"], prunable(&revisions, 2));
+    assert!(prunable(&revisions, 4).is_empty());
+    assert!(prunable(&revisions, 10).is_empty());
+}
@@ -6,9 +6,12 @@ pub mod component_instance;
 pub mod configuration;
 pub mod parameter;
 pub mod scopes;
+pub mod trait_definition;
 pub mod traits;
 pub mod variable;
 
+#[cfg(test)]
+mod component_instance_test;
 #[cfg(test)]
 mod component_test;
 #[cfg(test)]
@@ -16,6 +19,8 @@ mod configuration_test;
 #[cfg(test)]
 mod parameter_test;
 #[cfg(test)]
+mod trait_definition_test;
+#[cfg(test)]
 mod traits_test;
 
 /// Application defines an OAM application
@@ -32,12 +37,28 @@ pub struct Application {}
 pub struct OAMStatus {
     pub phase: Option<String>,
     pub components: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    /// The metadata.generation of the object this status was written for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+    /// Why the last reconcile of the object failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The state of every component instance, keyed by instance name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component_states: Option<BTreeMap<String, ComponentState>>,
+    /// The revision of the configuration spec that was applied last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<i64>,
 }
 impl Default for OAMStatus {
     fn default() -> Self {
         OAMStatus {
             phase: None,
             components: None,
+            observed_generation: None,
+            message: None,
+            component_states: None,
+            revision: None,
         }
     }
 }
@@ -46,7 +67,165 @@ impl OAMStatus {
         phase: Option<String>,
         components: Option<BTreeMap<String, BTreeMap<String, String>>>,
     ) -> OAMStatus {
-        OAMStatus { phase, components }
+        OAMStatus {
+            phase,
+            components,
+            ..Default::default()
+        }
+    }
+}
+
+/// The component instance is not running yet.
+pub const COMPONENT_PENDING: &str = "Pending";
+/// All resources of the component instance are running.
+pub const COMPONENT_RUNNING: &str = "Running";
+/// A resource of the component instance failed or could not be checked.
+pub const COMPONENT_FAILED: &str = "Failed";
+
+/// ComponentState is the state of one component instance of an application configuration.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentState {
+    pub component_name: String,
+    /// One of Pending, Running or Failed.
+    pub phase: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The status of the resources created by the traits of the component.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traits: Option<BTreeMap<String, String>>,
+    /// When the component was applied, in RFC 3339, until it is running for the first time since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progressing_since: Option<String>,
+}
+
+/// The message of a component that is held back until its dependencies are running.
+const WAITING_MESSAGE: &str = "waiting for dependencies";
+/// The message of a component that was applied and isn't running yet.
+const PROGRESSING_MESSAGE: &str = "waiting for the workload to become ready";
+
+impl ComponentState {
+    /// waiting is the state of a component that waits for the components in `dependencies`.
+    pub fn waiting(component_name: String, dependencies: &[String]) -> ComponentState {
+        ComponentState {
+            component_name,
+            phase: COMPONENT_PENDING.to_string(),
+            message: Some(format!("{} {}", WAITING_MESSAGE, dependencies.join(", "))),
+            traits: None,
+            progressing_since: None,
+        }
+    }
+
+    /// progressing is the state of a component that was applied at `since`.
+    pub fn progressing(component_name: String, since: String) -> ComponentState {
+        ComponentState {
+            component_name,
+            phase: COMPONENT_PENDING.to_string(),
+            message: Some(PROGRESSING_MESSAGE.to_string()),
+            traits: None,
+            progressing_since: Some(since),
+        }
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.phase == COMPONENT_PENDING
+            && self
+                .message
+                .as_ref()
+                .map(|m| m.starts_with(WAITING_MESSAGE))
+                == Some(true)
+    }
+
+    /// from_workload derives the state of a component from the status of its workload resources,
+    /// as returned by `WorkloadType::status`.
+    pub fn from_workload(
+        component_name: String,
+        resources: &BTreeMap<String, String>,
+        traits: Option<BTreeMap<String, String>>,
+    ) -> ComponentState {
+        let not_ready: Vec<(&String, &String)> = resources
+            .iter()
+            .filter(|(_, v)| {
+                v.as_str() != "running" && v.as_str() != "created" && v.as_str() != "succeeded"
+            })
+            .collect();
+        let phase = if not_ready.is_empty() {
+            COMPONENT_RUNNING
+        } else if not_ready.iter().any(|(_, v)| v.as_str() == "failed") {
+            COMPONENT_FAILED
+        } else {
+            COMPONENT_PENDING
+        };
+        let message = if not_ready.is_empty() {
+            None
+        } else {
+            Some(
+                not_ready
+                    .iter()
+                    .map(|(k, v)| format!("{} is {}", k, v))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            )
+        };
+        ComponentState {
+            component_name,
+            phase: phase.to_string(),
+            message,
+            traits,
+            progressing_since: None,
+        }
+    }
+
+    /// with_traits adds the status of the resources of the traits of the component. A running
+    /// component keeps running while some of them aren't ready, but its message names them.
+    pub fn with_traits(mut self, traits: Option<BTreeMap<String, String>>) -> ComponentState {
+        if self.phase == COMPONENT_RUNNING {
+            let not_ready: Vec<String> = traits
+                .iter()
+                .flatten()
+                .filter(|(_, state)| !traits::trait_ready(state))
+                .map(|(resource, state)| format!("{} is {}", resource, state))
+                .collect();
+            if !not_ready.is_empty() {
+                self.message = Some(format!("traits not ready: {}", not_ready.join(", ")));
+            }
+        }
+        self.traits = traits;
+        self
+    }
+
+    /// progress carries over when the component was applied from its `previous` state until it
+    /// is running, and fails a component that still isn't running `deadline` after it was
+    /// applied.
+    pub fn progress(
+        mut self,
+        previous: Option<&ComponentState>,
+        now: chrono::DateTime<chrono::Utc>,
+        deadline: Option<std::time::Duration>,
+    ) -> ComponentState {
+        if self.phase == COMPONENT_RUNNING {
+            return self;
+        }
+        self.progressing_since = previous.and_then(|p| p.progressing_since.clone());
+        let since = self
+            .progressing_since
+            .as_ref()
+            .and_then(|since| chrono::DateTime::parse_from_rfc3339(since).ok());
+        let deadline_exceeded = match (since, deadline) {
+            (Some(since), Some(deadline)) => chrono::Duration::from_std(deadline)
+                .map(|deadline| now.signed_duration_since(since) > deadline)
+                .unwrap_or(false),
+            _ => false,
+        };
+        if deadline_exceeded && self.phase == COMPONENT_PENDING {
+            self.phase = COMPONENT_FAILED.to_string();
+            self.message = Some(format!(
+                "not running within the progress deadline of {}s: {}",
+                deadline.map(|d| d.as_secs()).unwrap_or_default(),
+                self.message.unwrap_or_default()
+            ));
+        }
+        self
     }
 }
 
@@ -1,3 +1,4 @@
+use failure::Error;
 use k8s_openapi::api::core::v1 as core;
 use k8s_openapi::apimachinery::pkg::{api::resource::Quantity, util::intstr::IntOrString};
 use log::info;
@@ -5,7 +6,7 @@ use std::collections::BTreeMap;
 use std::path::Path;
 
 use crate::schematic::parameter::{
-    resolve_value, resolve_value_string, ParameterList, ParameterType,
+    resolve_value, resolve_value_string, secret_key_ref, ParameterList, ParameterType,
 };
 use crate::workload_type::ParamMap;
 
@@ -28,6 +29,7 @@ pub struct Component {
     pub parameters: ParameterList,
     pub containers: Vec<Container>,
     pub workload_settings: Vec<WorkloadSetting>,
+    pub outputs: Vec<Output>,
 }
 impl Component {
     /// listening_port returns the first container port listed.
@@ -194,11 +196,60 @@ impl Component {
             .collect()
     }
 
+    /// output returns the value of the output `name` of the instance `instance_name` in
+    /// `namespace`, whose parameters are `param_vals`.
+    pub fn output(
+        &self,
+        name: &str,
+        instance_name: &str,
+        namespace: &str,
+        param_vals: &ParamMap,
+    ) -> Result<String, Error> {
+        let output = self
+            .outputs
+            .iter()
+            .find(|o| o.name == name)
+            .ok_or_else(|| format_err!("no output {}", name))?;
+        output.render(instance_name, namespace, param_vals)
+    }
+
     pub fn get_workload_setting(&self, key: &str) -> Option<&WorkloadSetting> {
         self.workload_settings
             .iter()
             .find(|&item| item.name.eq(key))
     }
+
+    /// check_secret_params fails if a parameter read from a Secret is used for anything but env.
+    /// Its value is only known to the kubelet, so it can't be written into config files,
+    /// workload settings or outputs.
+    pub fn check_secret_params(&self, param_vals: &ParamMap) -> Result<(), Error> {
+        let secrets = param_vals
+            .iter()
+            .filter(|(_, value)| secret_key_ref(value).is_some())
+            .map(|(name, _)| name.as_str());
+        for name in secrets {
+            let config = self
+                .containers
+                .iter()
+                .flat_map(|c| c.config.iter().flatten())
+                .any(|c| c.from_param.as_deref() == Some(name));
+            let setting = self
+                .workload_settings
+                .iter()
+                .any(|s| s.from_param.as_deref() == Some(name));
+            let output = self
+                .outputs
+                .iter()
+                .any(|o| o.value.contains(&format!("[fromParam({})]", name)));
+            if config || setting || output {
+                return Err(format_err!(
+                    "parameter {} reads a secret, it can only be used in env",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for Component {
@@ -210,6 +261,7 @@ impl Default for Component {
             parameters: Vec::new(),
             containers: Vec::new(),
             workload_settings: Vec::new(),
+            outputs: Vec::new(),
         }
     }
 }
@@ -314,6 +366,55 @@ impl Container {
     }
 }
 
+/// Output is a value a component exposes to the other components of a configuration, like the
+/// DNS name of its Service. They read it with `fromComponent` and `output` in their parameter
+/// values.
+///
+/// The value is a string in which `[instanceName]`, `[namespace]` and `[fromParam(NAME)]` are
+/// replaced by the instance name, the namespace and the parameters of the component instance.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Output {
+    pub name: String,
+    pub description: Option<String>,
+    pub value: String,
+}
+
+impl Output {
+    /// render returns the value of the output for an instance.
+    pub fn render(
+        &self,
+        instance_name: &str,
+        namespace: &str,
+        param_vals: &ParamMap,
+    ) -> Result<String, Error> {
+        let mut rendered = self
+            .value
+            .replace("[instanceName]", instance_name)
+            .replace("[namespace]", namespace);
+        while let Some(start) = rendered.find("[fromParam(") {
+            let end = rendered[start..]
+                .find(")]")
+                .map(|end| start + end)
+                .ok_or_else(|| format_err!("output {}: unterminated fromParam", self.name))?;
+            let param = &rendered[start + "[fromParam(".len()..end];
+            let value = match param_vals.get(param) {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(serde_json::Value::Null) | None => {
+                    return Err(format_err!(
+                        "output {}: parameter {} has no value",
+                        self.name,
+                        param
+                    ))
+                }
+                Some(v) => v.to_string(),
+            };
+            rendered.replace_range(start..end + ")]".len(), value.as_str());
+        }
+        Ok(rendered)
+    }
+}
+
 /// Workload settings describe the configuration for a workload.
 ///
 /// This information is passed to the underlying workload defined by Component::worload_type.
@@ -356,7 +457,7 @@ impl ConfigFile {
 }
 
 /// Env describes an environment variable for a container.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Env {
     pub name: String,
@@ -365,6 +466,26 @@ pub struct Env {
 }
 impl Env {
     pub(crate) fn to_env_var(&self, params: ParamMap) -> core::EnvVar {
+        // A parameter read from a Secret only names its key, the kubelet reads the value.
+        let secret = self
+            .from_param
+            .as_ref()
+            .and_then(|p| params.get(p))
+            .and_then(secret_key_ref);
+        if let Some(key_ref) = secret {
+            return core::EnvVar {
+                name: self.name.clone(),
+                value: None,
+                value_from: Some(core::EnvVarSource {
+                    secret_key_ref: Some(core::SecretKeySelector {
+                        name: Some(key_ref.name),
+                        key: key_ref.key,
+                        optional: None,
+                    }),
+                    ..Default::default()
+                }),
+            };
+        }
         let value = resolve_value_string(params, self.from_param.clone(), self.value.clone());
         // FIXME: This needs to support fromParam
         core::EnvVar {
@@ -1,8 +1,184 @@
+use std::collections::BTreeMap;
+
+use crate::schematic::{ComponentState, COMPONENT_RUNNING};
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ComponentInstance {
     pub traits: Option<Vec<crate::schematic::traits::TraitBinding>>,
 }
 
+/// Container waiting reasons that are part of a normal start.
+const STARTING_REASONS: &[&str] = &["ContainerCreating", "PodInitializing"];
+
+/// ComponentInstanceStatus is the observed state of the workload of a component instance.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentInstanceStatus {
+    /// "healthy" once every workload resource is running, "unhealthy" otherwise.
+    pub health: String,
+    /// One of Pending, Running or Failed.
+    pub phase: String,
+    /// The status of each workload resource, e.g. `deployment/web: running`.
+    #[serde(default)]
+    pub resources: BTreeMap<String, String>,
+    /// The desired replicas of the replicated workload resources.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready_replicas: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<WorkloadCondition>,
+    /// The latest problem of the workload, like a failed Job or a crash looping container.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_update_time: Option<String>,
+    /// The status of each resource of the traits, e.g. `ingress/web-trait-ingress: ready at
+    /// 10.0.0.1`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub traits: BTreeMap<String, String>,
+}
+
+/// WorkloadCondition is a condition of one of the workload resources of a component instance.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkloadCondition {
+    /// The resource with the condition, e.g. `deployment/web`.
+    pub resource: String,
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    pub type_: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl WorkloadCondition {
+    /// is_failure is true for the conditions Kubernetes reports a failed rollout or Job with.
+    pub fn is_failure(&self) -> bool {
+        let failed = self.type_ == "Failed" || self.type_ == "ReplicaFailure";
+        (failed && self.status == "True") || (self.type_ == "Progressing" && self.status == "False")
+    }
+}
+
+impl ComponentInstanceStatus {
+    /// observe builds the status of an instance from the `state` its workload `resources` add up
+    /// to, the `live` workload objects and their `pods`.
+    pub fn observe(
+        state: &ComponentState,
+        resources: &BTreeMap<String, String>,
+        live: &[serde_json::Value],
+        pods: &[serde_json::Value],
+    ) -> Self {
+        let health = if state.phase == COMPONENT_RUNNING {
+            "healthy"
+        } else {
+            "unhealthy"
+        };
+        let mut status = ComponentInstanceStatus {
+            health: health.to_string(),
+            phase: state.phase.clone(),
+            resources: resources.clone(),
+            ..Default::default()
+        };
+        for object in live {
+            let resource = format!(
+                "{}/{}",
+                object["kind"].as_str().unwrap_or_default().to_lowercase(),
+                object["metadata"]["name"].as_str().unwrap_or_default()
+            );
+            if let Some(replicas) = object["spec"]["replicas"].as_i64() {
+                *status.replicas.get_or_insert(0) += replicas;
+                *status.ready_replicas.get_or_insert(0) +=
+                    object["status"]["readyReplicas"].as_i64().unwrap_or(0);
+            }
+            let conditions = object["status"]["conditions"].as_array();
+            for c in conditions.into_iter().flatten() {
+                let text = |field: &str| c[field].as_str().map(String::from);
+                status.conditions.push(WorkloadCondition {
+                    resource: resource.clone(),
+                    type_: text("type").unwrap_or_default(),
+                    status: text("status").unwrap_or_default(),
+                    reason: text("reason"),
+                    message: text("message"),
+                });
+            }
+        }
+        status.last_error = status
+            .conditions
+            .iter()
+            .find(|c| c.is_failure())
+            .map(|c| {
+                format!(
+                    "{} {}: {}",
+                    c.resource,
+                    c.type_,
+                    c.message
+                        .clone()
+                        .or_else(|| c.reason.clone())
+                        .unwrap_or_default()
+                )
+            })
+            .or_else(|| pods_error(pods));
+        status
+    }
+}
+
+/// pods_error describes the first pod that failed or has a container that can't start.
+fn pods_error(pods: &[serde_json::Value]) -> Option<String> {
+    pods.iter().find_map(|pod| {
+        let name = pod["metadata"]["name"].as_str().unwrap_or_default();
+        if pod["status"]["phase"] == "Failed" {
+            return Some(format!(
+                "pod {} failed: {}",
+                name,
+                pod["status"]["message"].as_str().unwrap_or_default()
+            ));
+        }
+        let containers = pod["status"]["containerStatuses"].as_array();
+        containers.into_iter().flatten().find_map(|c| {
+            let waiting = &c["state"]["waiting"];
+            let reason = waiting["reason"].as_str()?;
+            if STARTING_REASONS.contains(&reason) {
+                return None;
+            }
+            Some(format!(
+                "pod {} container {} is {}: {}",
+                name,
+                c["name"].as_str().unwrap_or_default(),
+                reason,
+                waiting["message"].as_str().unwrap_or_default()
+            ))
+        })
+    })
+}
+
+/// InstanceStatus is the status of a ComponentInstance. Instances written by earlier versions of
+/// rudr only have a health string.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum InstanceStatus {
+    Status(ComponentInstanceStatus),
+    Health(String),
+}
+
+impl Default for InstanceStatus {
+    fn default() -> Self {
+        InstanceStatus::Status(ComponentInstanceStatus::default())
+    }
+}
+
+impl InstanceStatus {
+    /// health is "healthy" or "unhealthy".
+    pub fn health(&self) -> &str {
+        match self {
+            InstanceStatus::Status(status) => status.health.as_str(),
+            InstanceStatus::Health(health) => health.as_str(),
+        }
+    }
+}
+
 /// Convenience type for Kubernetes wrapped ComponentInstance.
-pub type KubeComponentInstance = kube::api::Object<ComponentInstance, String>;
+pub type KubeComponentInstance = kube::api::Object<ComponentInstance, InstanceStatus>;
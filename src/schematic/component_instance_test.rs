@@ -0,0 +1,80 @@
+use crate::schematic::component_instance::*;
+use crate::schematic::ComponentState;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_instance_status_compat() {
+    // Instances written by earlier versions only have a health string.
+    let legacy: InstanceStatus = serde_json::from_str(r#""healthy""#).expect("legacy status");
+    assert_eq!("healthy", legacy.health());
+
+    let status: InstanceStatus = serde_json::from_value(serde_json::json!({
+        "health": "unhealthy",
+        "phase": "Pending",
+        "resources": {"deployment/web": "pending"},
+        "readyReplicas": 1
+    }))
+    .expect("status");
+    match status {
+        InstanceStatus::Status(ref s) => assert_eq!(Some(1), s.ready_replicas),
+        InstanceStatus::Health(_) => panic!("expected a structured status"),
+    }
+    assert_eq!("unhealthy", status.health());
+}
+
+#[test]
+fn test_observe_instance_status() {
+    let mut resources = BTreeMap::new();
+    resources.insert("deployment/web".to_string(), "updating".to_string());
+    let state = ComponentState::from_workload("web".to_string(), &resources, None);
+    let live = vec![serde_json::json!({
+        "kind": "Deployment",
+        "metadata": {"name": "web"},
+        "spec": {"replicas": 3},
+        "status": {
+            "readyReplicas": 2,
+            "conditions": [
+                {"type": "Available", "status": "True"},
+                {"type": "Progressing", "status": "False", "reason": "ProgressDeadlineExceeded",
+                 "message": "ReplicaSet \"web-1\" has timed out progressing."}
+            ]
+        }
+    })];
+    let pods = vec![serde_json::json!({
+        "metadata": {"name": "web-1-abc"},
+        "status": {"phase": "Running", "containerStatuses": [
+            {"name": "web", "state": {"waiting": {"reason": "CrashLoopBackOff", "message": "back-off 5m0s"}}}
+        ]}
+    })];
+
+    let status = ComponentInstanceStatus::observe(&state, &resources, &live, &pods);
+    assert_eq!("unhealthy", status.health);
+    assert_eq!("Pending", status.phase);
+    assert_eq!(Some(3), status.replicas);
+    assert_eq!(Some(2), status.ready_replicas);
+    assert_eq!(2, status.conditions.len());
+    assert_eq!("deployment/web", status.conditions[1].resource);
+    assert_eq!(
+        Some(
+            "deployment/web Progressing: ReplicaSet \"web-1\" has timed out progressing."
+                .to_string()
+        ),
+        status.last_error
+    );
+
+    // Without failed conditions the pods tell what is wrong.
+    let status = ComponentInstanceStatus::observe(&state, &resources, &[], &pods);
+    assert_eq!(
+        Some("pod web-1-abc container web is CrashLoopBackOff: back-off 5m0s".to_string()),
+        status.last_error
+    );
+
+    let starting = vec![serde_json::json!({
+        "metadata": {"name": "web-1-abc"},
+        "status": {"containerStatuses": [
+            {"name": "web", "state": {"waiting": {"reason": "ContainerCreating"}}}
+        ]}
+    })];
+    let status = ComponentInstanceStatus::observe(&state, &resources, &[], &starting);
+    assert_eq!(None, status.last_error);
+}
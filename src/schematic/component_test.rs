@@ -1,4 +1,4 @@
-use crate::schematic::parameter::resolve_parameters;
+use crate::schematic::parameter::{resolve_parameters, secret_value, KeyRef};
 use crate::schematic::{component::*, parameter::ParameterType, GroupVersionKind};
 use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
 use std::collections::BTreeMap;
@@ -734,3 +734,105 @@ fn test_evaluate_configs() {
     exp.insert("container30".to_string(), c30);
     assert_eq!(exp, configs);
 }
+
+#[test]
+fn test_output() {
+    let component = Component::from_str(
+        r#"{
+            "outputs": [
+                {"name": "serviceName", "value": "[instanceName].[namespace].svc.cluster.local"},
+                {"name": "url", "value": "http://[instanceName]:[fromParam(port)]/[fromParam(path)]"},
+                {"name": "secret", "value": "[fromParam(secret)]"}
+            ]
+        }"#,
+    )
+    .expect("component should deserialize");
+    let mut params = BTreeMap::new();
+    params.insert("port".to_string(), serde_json::json!(8080));
+    params.insert("path".to_string(), serde_json::json!("api"));
+    params.insert("secret".to_string(), serde_json::Value::Null);
+
+    assert_eq!(
+        "web.prod.svc.cluster.local",
+        component
+            .output("serviceName", "web", "prod", &params)
+            .unwrap()
+    );
+    assert_eq!(
+        "http://web:8080/api",
+        component.output("url", "web", "prod", &params).unwrap()
+    );
+    assert_eq!(
+        "output secret: parameter secret has no value",
+        component
+            .output("secret", "web", "prod", &params)
+            .unwrap_err()
+            .to_string()
+    );
+    assert_eq!(
+        "no output missing",
+        component
+            .output("missing", "web", "prod", &params)
+            .unwrap_err()
+            .to_string()
+    );
+}
+
+#[test]
+fn test_secret_params() {
+    let component = Component::from_str(
+        r#"{
+            "containers": [
+                {
+                    "name": "api",
+                    "image": "api:1.0",
+                    "env": [{"name": "DB_PASSWORD", "fromParam": "password"}],
+                    "config": [{"path": "/etc/api/token", "fromParam": "token"}]
+                }
+            ],
+            "workloadSettings": [
+                {"name": "key", "type": "string", "fromParam": "key"}
+            ],
+            "outputs": [
+                {"name": "dsn", "value": "postgres://api:[fromParam(dsn)]@db"}
+            ]
+        }"#,
+    )
+    .expect("component should deserialize");
+    let secret = |key: &str| {
+        secret_value(&KeyRef {
+            name: "api".into(),
+            key: key.into(),
+        })
+    };
+    let mut params = BTreeMap::new();
+    params.insert("password".to_string(), secret("password"));
+    params.insert("token".to_string(), serde_json::json!("not a secret"));
+    assert!(component.check_secret_params(&params).is_ok());
+
+    // The env reads the key from the Secret, the value is never written into the pod spec.
+    let env = component.containers[0].env[0].to_env_var(params.clone());
+    assert_eq!(None, env.value);
+    let key_ref = env
+        .value_from
+        .expect("env reads the secret")
+        .secret_key_ref
+        .expect("secret key");
+    assert_eq!(Some("api".to_string()), key_ref.name);
+    assert_eq!("password", key_ref.key);
+
+    for name in &["token", "key", "dsn"] {
+        let mut params = params.clone();
+        params.insert(name.to_string(), secret(name));
+        assert_eq!(
+            format!(
+                "parameter {} reads a secret, it can only be used in env",
+                name
+            ),
+            component
+                .check_secret_params(&params)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use crate::schematic::{parameter::ParameterValue, traits::TraitBinding, variable::Variable};
+use k8s_openapi::api::{batch::v1 as batch, core::v1 as core, rbac::v1 as rbac};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use std::collections::BTreeMap;
+
+use crate::schematic::{
+    component::Env, parameter::ParameterValue, traits::TraitBinding, variable::Variable,
+};
 
 /// Configuration creates an instance of a specified component, and attaches configuration to it.
 ///
@@ -18,6 +24,180 @@ pub struct ComponentConfiguration {
     pub traits: Option<Vec<TraitBinding>>,
     /// Application Scopes which the component was involved
     pub application_scopes: Option<Vec<String>>,
+    /// Instance names of the components that must be running before this one is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+    /// Jobs to run before or after the workload of the component is applied, or before it is
+    /// deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Vec<Hook>>,
+    /// The name of the remote cluster the workload and traits of the component are created in,
+    /// one of `--clusters-config`. Without it they are created in the cluster rudr runs in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    /// A ServiceAccount of the instance that its pods run as, instead of the default
+    /// ServiceAccount of the namespace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account: Option<ServiceAccount>,
+}
+
+/// ServiceAccount is the identity of the pods of a component instance. Cloud integrations like
+/// IRSA or Workload Identity bind a cloud role to it with annotations.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceAccount {
+    /// Annotations of the ServiceAccount, like `eks.amazonaws.com/role-arn`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+    /// Roles or ClusterRoles the ServiceAccount is bound to in the namespace of the instance
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub role_bindings: Vec<RoleBinding>,
+}
+
+/// RoleBinding names a Role or ClusterRole to bind the ServiceAccount of an instance to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleBinding {
+    /// `Role` or `ClusterRole`, `ClusterRole` if unset
+    #[serde(default = "default_role_kind")]
+    pub kind: String,
+    pub name: String,
+}
+
+fn default_role_kind() -> String {
+    "ClusterRole".to_string()
+}
+
+impl ServiceAccount {
+    /// to_service_account renders the ServiceAccount of the instance `name`.
+    pub fn to_service_account(
+        &self,
+        name: String,
+        labels: BTreeMap<String, String>,
+        owner_ref: Option<Vec<meta::OwnerReference>>,
+    ) -> core::ServiceAccount {
+        core::ServiceAccount {
+            metadata: Some(meta::ObjectMeta {
+                name: Some(name),
+                labels: Some(labels),
+                annotations: Some(self.annotations.clone()).filter(|a| !a.is_empty()),
+                owner_references: owner_ref,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// to_role_bindings renders the RoleBindings of the ServiceAccount `name` in `namespace`,
+    /// named `<name>-<role>`.
+    pub fn to_role_bindings(
+        &self,
+        name: String,
+        namespace: String,
+        labels: BTreeMap<String, String>,
+        owner_ref: Option<Vec<meta::OwnerReference>>,
+    ) -> Vec<rbac::RoleBinding> {
+        self.role_bindings
+            .iter()
+            .map(|binding| rbac::RoleBinding {
+                metadata: Some(meta::ObjectMeta {
+                    name: Some(format!("{}-{}", name, binding.name.replace(':', "-"))),
+                    labels: Some(labels.clone()),
+                    owner_references: owner_ref.clone(),
+                    ..Default::default()
+                }),
+                role_ref: rbac::RoleRef {
+                    api_group: "rbac.authorization.k8s.io".to_string(),
+                    kind: binding.kind.clone(),
+                    name: binding.name.clone(),
+                },
+                subjects: Some(vec![rbac::Subject {
+                    kind: "ServiceAccount".to_string(),
+                    name: name.clone(),
+                    namespace: Some(namespace.clone()),
+                    ..Default::default()
+                }]),
+            })
+            .collect()
+    }
+}
+
+/// HookType is the point of the component's lifecycle at which a hook runs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookType {
+    /// Before the workload is created or updated
+    PreInstall,
+    /// After the workload and traits are created or updated
+    PostInstall,
+    /// Before the workload is deleted
+    PreDelete,
+}
+
+/// Hook is a Job that runs to completion at a point of the component's lifecycle, like a schema
+/// migration that has to finish before the new version of the workload starts.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+    pub name: String,
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    pub hook_type: HookType,
+    pub image: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<Env>,
+    /// Seconds to wait for the Job to complete, 300 if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+}
+
+impl Hook {
+    /// to_job renders the Job of the hook. `env` values `fromParam` are resolved from `params`.
+    pub fn to_job(
+        &self,
+        name: String,
+        labels: BTreeMap<String, String>,
+        owner_ref: Option<Vec<meta::OwnerReference>>,
+        params: BTreeMap<String, serde_json::Value>,
+    ) -> batch::Job {
+        let metadata = meta::ObjectMeta {
+            name: Some(name),
+            labels: Some(labels),
+            owner_references: owner_ref,
+            ..Default::default()
+        };
+        batch::Job {
+            metadata: Some(metadata.clone()),
+            spec: Some(batch::JobSpec {
+                backoff_limit: Some(0),
+                template: core::PodTemplateSpec {
+                    metadata: Some(metadata),
+                    spec: Some(core::PodSpec {
+                        containers: vec![core::Container {
+                            name: self.name.clone(),
+                            image: Some(self.image.clone()),
+                            command: self.cmd.clone(),
+                            args: self.args.clone(),
+                            env: Some(
+                                self.env
+                                    .iter()
+                                    .map(|e| e.to_env_var(params.clone()))
+                                    .collect(),
+                            ),
+                            ..Default::default()
+                        }],
+                        restart_policy: Some("Never".to_string()),
+                        ..Default::default()
+                    }),
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
 }
 
 /// ApplicationConfiguration is the top-level configuration object in OAM.
@@ -30,6 +210,82 @@ pub struct ApplicationConfiguration {
     pub variables: Option<Vec<Variable>>,
     pub scopes: Option<Vec<ScopeBinding>>,
     pub components: Option<Vec<ComponentConfiguration>>,
+    /// Undo the changes of a reconciliation whose components failed part way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_on_failure: Option<bool>,
+    /// Labels to set on every generated workload object and its pods
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<BTreeMap<String, String>>,
+    /// Annotations to set on every generated workload object and its pods
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<BTreeMap<String, String>>,
+    /// Whether the labels and annotations of the configuration's own metadata are copied too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub propagation_policy: Option<PropagationPolicy>,
+}
+
+/// PropagationPolicy selects which metadata of an ApplicationConfiguration is copied onto the
+/// objects generated for its components. The `labels` and `annotations` of the spec are always
+/// copied.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PropagationPolicy {
+    None,
+    Labels,
+    Annotations,
+    All,
+}
+
+/// is_internal_annotation is true for annotations that tools, including rudr itself, keep on
+/// the configuration for their own bookkeeping. They are never propagated.
+fn is_internal_annotation(key: &str) -> bool {
+    key == crate::instigator::COMPONENT_RECORD_ANNOTATION
+        || key.starts_with("kubectl.kubernetes.io/")
+        || key.starts_with("core.oam.dev/")
+}
+
+impl ApplicationConfiguration {
+    fn policy(&self) -> PropagationPolicy {
+        self.propagation_policy
+            .clone()
+            .unwrap_or(PropagationPolicy::None)
+    }
+
+    /// propagated_labels are the labels to set on generated objects, given the labels of the
+    /// configuration's metadata. Labels of the spec win over the ones of the metadata.
+    pub fn propagated_labels(
+        &self,
+        metadata: &BTreeMap<String, String>,
+    ) -> BTreeMap<String, String> {
+        let mut labels = BTreeMap::new();
+        let policy = self.policy();
+        if policy == PropagationPolicy::Labels || policy == PropagationPolicy::All {
+            labels.extend(metadata.clone());
+        }
+        labels.extend(self.labels.clone().unwrap_or_default());
+        labels
+    }
+
+    /// propagated_annotations are the annotations to set on generated objects, given the
+    /// annotations of the configuration's metadata. Annotations of the spec win over the ones
+    /// of the metadata.
+    pub fn propagated_annotations(
+        &self,
+        metadata: &BTreeMap<String, String>,
+    ) -> BTreeMap<String, String> {
+        let mut annotations = BTreeMap::new();
+        let policy = self.policy();
+        if policy == PropagationPolicy::Annotations || policy == PropagationPolicy::All {
+            annotations.extend(
+                metadata
+                    .iter()
+                    .filter(|(k, _)| !is_internal_annotation(k))
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            );
+        }
+        annotations.extend(self.annotations.clone().unwrap_or_default());
+        annotations
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
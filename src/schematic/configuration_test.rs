@@ -41,4 +41,289 @@ fn test_application_configuration() {
     .expect("JSON must parse");
 
     assert!(conf.variables.is_some());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_application_configuration_rollback_on_failure() {
+    let conf: ApplicationConfiguration = serde_json::from_str(
+        r#"{
+            "components": [],
+            "rollbackOnFailure": true
+        }"#,
+    )
+    .expect("JSON must parse");
+    assert_eq!(Some(true), conf.rollback_on_failure);
+
+    // Failed reconciliations are left as they are unless the configuration opts in.
+    let conf: ApplicationConfiguration =
+        serde_json::from_str(r#"{"components": []}"#).expect("JSON must parse");
+    assert_eq!(None, conf.rollback_on_failure);
+    assert!(!conf.rollback_on_failure.unwrap_or(false));
+    let conf: ApplicationConfiguration =
+        serde_json::from_str(r#"{"components": [], "rollbackOnFailure": false}"#)
+            .expect("JSON must parse");
+    assert_eq!(Some(false), conf.rollback_on_failure);
+}
+
+#[test]
+fn test_component_state_from_workload() {
+    use crate::schematic::{
+        ComponentState, COMPONENT_FAILED, COMPONENT_PENDING, COMPONENT_RUNNING,
+    };
+    use std::collections::BTreeMap;
+
+    let resources = |states: &[(&str, &str)]| -> BTreeMap<String, String> {
+        states
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+    let state = ComponentState::from_workload(
+        "web".to_string(),
+        &resources(&[("deployment/web", "running"), ("service/web", "created")]),
+        None,
+    );
+    assert_eq!(state.component_name, "web");
+    assert_eq!(state.phase, COMPONENT_RUNNING);
+    assert_eq!(state.message, None);
+
+    let state = ComponentState::from_workload(
+        "web".to_string(),
+        &resources(&[("deployment/web", "updating"), ("service/web", "created")]),
+        None,
+    );
+    assert_eq!(state.phase, COMPONENT_PENDING);
+    assert_eq!(state.message.as_deref(), Some("deployment/web is updating"));
+
+    let state = ComponentState::from_workload(
+        "job".to_string(),
+        &resources(&[("job/a", "failed"), ("job/b", "updating")]),
+        Some(resources(&[("ingress/job", "created")])),
+    );
+    assert_eq!(state.phase, COMPONENT_FAILED);
+    assert_eq!(
+        state.message.as_deref(),
+        Some("job/a is failed, job/b is updating")
+    );
+    assert_eq!(state.traits.unwrap()["ingress/job"], "created");
+}
+
+#[test]
+fn test_component_state_with_traits() {
+    use crate::schematic::{ComponentState, COMPONENT_RUNNING};
+    use std::collections::BTreeMap;
+
+    let mut resources = BTreeMap::new();
+    resources.insert("deployment/web".to_string(), "running".to_string());
+    let mut traits = BTreeMap::new();
+    traits.insert(
+        "ingress/web-trait-ingress".to_string(),
+        "waiting for an address".to_string(),
+    );
+    traits.insert(
+        "persistentvolumeclaim/data".to_string(),
+        "ready".to_string(),
+    );
+    let state = ComponentState::from_workload("web".to_string(), &resources, None)
+        .with_traits(Some(traits));
+    assert_eq!(state.phase, COMPONENT_RUNNING);
+    assert_eq!(
+        state.message.as_deref(),
+        Some("traits not ready: ingress/web-trait-ingress is waiting for an address")
+    );
+    assert_eq!(state.traits.unwrap().len(), 2);
+}
+
+#[test]
+fn test_component_state_progress() {
+    use crate::schematic::{
+        ComponentState, COMPONENT_FAILED, COMPONENT_PENDING, COMPONENT_RUNNING,
+    };
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    let time = |t: &str| {
+        chrono::DateTime::parse_from_rfc3339(t)
+            .expect("time")
+            .with_timezone(&chrono::Utc)
+    };
+    let applied =
+        ComponentState::progressing("web".to_string(), "2019-10-01T12:00:00Z".to_string());
+    assert_eq!(applied.phase, COMPONENT_PENDING);
+    let mut resources = BTreeMap::new();
+    resources.insert("deployment/web".to_string(), "updating".to_string());
+    let updating = ComponentState::from_workload("web".to_string(), &resources, None);
+    let deadline = Some(Duration::from_secs(600));
+
+    // Within the deadline the component stays pending, and remembers when it was applied.
+    let state = updating
+        .clone()
+        .progress(Some(&applied), time("2019-10-01T12:05:00Z"), deadline);
+    assert_eq!(state.phase, COMPONENT_PENDING);
+    assert_eq!(state.progressing_since, applied.progressing_since);
+
+    let state = updating
+        .clone()
+        .progress(Some(&state), time("2019-10-01T12:10:01Z"), deadline);
+    assert_eq!(state.phase, COMPONENT_FAILED);
+    assert_eq!(
+        state.message.as_deref(),
+        Some("not running within the progress deadline of 600s: deployment/web is updating")
+    );
+
+    // Without a deadline it waits forever.
+    let state = updating
+        .clone()
+        .progress(Some(&applied), time("2019-10-02T12:00:00Z"), None);
+    assert_eq!(state.phase, COMPONENT_PENDING);
+
+    // Once running, a later update isn't held against the deadline any more.
+    resources.insert("deployment/web".to_string(), "running".to_string());
+    let running = ComponentState::from_workload("web".to_string(), &resources, None).progress(
+        Some(&applied),
+        time("2019-10-02T12:00:00Z"),
+        deadline,
+    );
+    assert_eq!(running.phase, COMPONENT_RUNNING);
+    assert_eq!(running.progressing_since, None);
+    let state = updating.progress(Some(&running), time("2019-10-03T12:00:00Z"), deadline);
+    assert_eq!(state.phase, COMPONENT_PENDING);
+}
+
+#[test]
+fn test_component_hooks() {
+    let conf: ComponentConfiguration = serde_json::from_str(
+        r#"{
+            "componentName": "test",
+            "instanceName": "squidgy",
+            "hooks": [
+                {
+                    "name": "migrate",
+                    "type": "pre-install",
+                    "image": "migrator:v2",
+                    "args": ["up"],
+                    "env": [{"name": "DB", "fromParam": "db"}],
+                    "timeoutSeconds": 60
+                }
+            ]
+        }"#,
+    )
+    .expect("JSON must parse");
+
+    let hook = &conf.hooks.expect("hooks")[0];
+    assert_eq!(HookType::PreInstall, hook.hook_type);
+    assert_eq!(Some(60), hook.timeout_seconds);
+
+    let mut params = std::collections::BTreeMap::new();
+    params.insert("db".to_string(), serde_json::json!("postgres"));
+    let job = hook.to_job("squidgy-migrate-1".into(), Default::default(), None, params);
+    let spec = job.spec.expect("job spec");
+    assert_eq!(Some(0), spec.backoff_limit);
+    let pod = spec.template.spec.expect("pod spec");
+    assert_eq!(Some("Never".to_string()), pod.restart_policy);
+    let container = &pod.containers[0];
+    assert_eq!(Some("migrator:v2".to_string()), container.image);
+    assert_eq!(Some(vec!["up".to_string()]), container.args);
+    let env = container.env.clone().expect("env");
+    assert_eq!(Some("postgres".to_string()), env[0].value);
+}
+
+#[test]
+fn test_propagated_metadata() {
+    let conf: ApplicationConfiguration = serde_json::from_str(
+        r#"{
+            "labels": {"cost-center": "retail"},
+            "annotations": {"team": "checkout"},
+            "propagationPolicy": "all"
+        }"#,
+    )
+    .expect("JSON must parse");
+    let metadata = |pairs: &[(&str, &str)]| -> std::collections::BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    let labels = conf.propagated_labels(&metadata(&[("cost-center", "web"), ("tier", "front")]));
+    assert_eq!(
+        metadata(&[("cost-center", "retail"), ("tier", "front")]),
+        labels
+    );
+
+    // rudr's and kubectl's bookkeeping annotations stay on the configuration.
+    let annotations = conf.propagated_annotations(&metadata(&[
+        ("component_record_annotation", "{}"),
+        ("kubectl.kubernetes.io/last-applied-configuration", "{}"),
+        ("core.oam.dev/dry-run", "true"),
+        ("owner", "jane"),
+    ]));
+    assert_eq!(
+        metadata(&[("owner", "jane"), ("team", "checkout")]),
+        annotations
+    );
+
+    // Without a policy only the spec's blocks are propagated.
+    let conf: ApplicationConfiguration =
+        serde_json::from_str(r#"{"labels": {"cost-center": "retail"}}"#).expect("JSON must parse");
+    assert_eq!(
+        metadata(&[("cost-center", "retail")]),
+        conf.propagated_labels(&metadata(&[("tier", "front")]))
+    );
+    assert!(conf
+        .propagated_annotations(&metadata(&[("owner", "jane")]))
+        .is_empty());
+}
+
+#[test]
+fn test_component_service_account() {
+    let conf: ComponentConfiguration = serde_json::from_str(
+        r#"{
+            "componentName": "test",
+            "instanceName": "squidgy",
+            "serviceAccount": {
+                "annotations": {"eks.amazonaws.com/role-arn": "arn:aws:iam::1234:role/squidgy"},
+                "roleBindings": [
+                    {"name": "view"},
+                    {"kind": "Role", "name": "secret-reader"}
+                ]
+            }
+        }"#,
+    )
+    .expect("JSON must parse");
+    let account = conf.service_account.expect("service account");
+
+    let sa = account.to_service_account(
+        "squidgy".to_string(),
+        std::collections::BTreeMap::new(),
+        None,
+    );
+    let metadata = sa.metadata.expect("metadata");
+    assert_eq!(Some("squidgy".to_string()), metadata.name);
+    assert_eq!(
+        Some(&"arn:aws:iam::1234:role/squidgy".to_string()),
+        metadata
+            .annotations
+            .expect("annotations")
+            .get("eks.amazonaws.com/role-arn")
+    );
+
+    let bindings = account.to_role_bindings(
+        "squidgy".to_string(),
+        "prod".to_string(),
+        std::collections::BTreeMap::new(),
+        None,
+    );
+    assert_eq!(2, bindings.len());
+    assert_eq!(
+        Some("squidgy-view".to_string()),
+        bindings[0].metadata.as_ref().and_then(|m| m.name.clone())
+    );
+    assert_eq!("ClusterRole", bindings[0].role_ref.kind);
+    assert_eq!("Role", bindings[1].role_ref.kind);
+    assert_eq!("secret-reader", bindings[1].role_ref.name);
+    let subject = &bindings[1].subjects.as_ref().expect("subjects")[0];
+    assert_eq!("ServiceAccount", subject.kind);
+    assert_eq!("squidgy", subject.name);
+    assert_eq!(Some("prod".to_string()), subject.namespace);
+}
@@ -98,11 +98,17 @@ pub fn resolve_value_string(
 }
 
 #[derive(Fail, Debug)]
-#[fail(display = "validation failed: {:?}", errs)]
 pub struct ValidationErrors {
     errs: Vec<Error>,
 }
 
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let errs: Vec<String> = self.errs.iter().map(|e| e.to_string()).collect();
+        write!(f, "validation failed: {}", errs.join("; "))
+    }
+}
+
 pub fn resolve_parameters(
     definition: Vec<Parameter>,
     values: ParamMap,
@@ -118,33 +124,79 @@ pub fn resolve_parameters(
                     name: d.name.clone(),
                     value: Some(val.clone()),
                     from_param: None,
+                    value_from: None,
+                    from_component: None,
+                    output: None,
                 },
                 None => ParameterValue {
                     name: d.name.clone(),
                     value: d.default.clone().or(Some(serde_json::Value::Null)),
                     from_param: None,
+                    value_from: None,
+                    from_component: None,
+                    output: None,
                 },
             };
             // Validation:
-            if d.required
-                && (resolved.value.is_none() || resolved.value.as_ref().unwrap().is_null())
-            {
+            let missing = resolved.value.as_ref().filter(|v| !v.is_null()).is_none();
+            if d.required && missing {
                 errors.push(format_err!("parameter {} is required", d.name.clone()));
+            } else if !missing {
+                // An optional parameter without value or default stays null.
+                let value = resolved.value.as_ref().unwrap();
+                if secret_key_ref(value).is_some() {
+                    // The kubelet resolves the key, and env values are strings.
+                    if d.parameter_type != ParameterType::String {
+                        errors.push(format_err!(
+                            "parameter {} reads a secret but is not a string",
+                            d.name
+                        ));
+                    }
+                } else if let Err(e) = d.validate(value) {
+                    errors.push(e)
+                };
             }
-            if let Err(e) = d.validate(resolved.value.as_ref().unwrap()) {
-                errors.push(e)
-            };
             resolved
         })
         .for_each(|p| {
             resolved.insert(p.name, p.value.unwrap());
         });
+    for name in values.keys() {
+        if !definition.iter().any(|d| &d.name == name) {
+            errors.push(format_err!("unknown parameter {}", name));
+        }
+    }
     if !errors.is_empty() {
         return Err(ValidationErrors { errs: errors });
     }
     Ok(resolved)
 }
 
+/// validate_parameters checks the parameters a schematic declares: names are unique, and every
+/// default has the type of its parameter.
+pub fn validate_parameters(definition: &[Parameter]) -> Result<(), Error> {
+    let mut seen = std::collections::BTreeSet::new();
+    let mut errors: Vec<Error> = Vec::new();
+    for d in definition {
+        if !seen.insert(d.name.as_str()) {
+            errors.push(format_err!("parameter {} is declared twice", d.name));
+        }
+        if let Some(default) = d.default.as_ref() {
+            if let Err(e) = d.validate(default) {
+                errors.push(format_err!(
+                    "default of parameter {} is invalid: {}",
+                    d.name,
+                    e
+                ));
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ValidationErrors { errs: errors }.into());
+    }
+    Ok(())
+}
+
 /// Resolve current values with material from parent values and return a map of name/value pairs.
 ///
 /// If the current values have a `from` directive, the `from will be looked up in parent.
@@ -214,6 +266,48 @@ pub struct ParameterValue {
     pub name: String,
     pub value: Option<serde_json::Value>,
     pub from_param: Option<String>,
+    /// A secret or config map key the value comes from, see `ValueFrom`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_from: Option<ValueFrom>,
+    /// The instance name of another component of the configuration whose `output` is the value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_component: Option<String>,
+    /// The output of `from_component` the value is read from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// ValueFrom points a parameter value at a key of a Secret or ConfigMap in the namespace of the
+/// configuration, so that the value doesn't have to be written into the configuration.
+///
+/// The instigator reads the keys of ConfigMaps when it renders the component. Secrets are never
+/// read: the parameter only names the key, see `secret_value`, and env reading the parameter
+/// gets it from the kubelet through `valueFrom.secretKeyRef`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueFrom {
+    pub secret_key_ref: Option<KeyRef>,
+    pub config_map_key_ref: Option<KeyRef>,
+}
+
+/// KeyRef selects the key of a Secret or ConfigMap.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRef {
+    pub name: String,
+    pub key: String,
+}
+
+/// secret_value is the value of a parameter read from the key of a Secret, it names the key
+/// instead of holding what is stored under it.
+pub fn secret_value(key_ref: &KeyRef) -> serde_json::Value {
+    serde_json::json!({"valueFrom": {"secretKeyRef": {"name": key_ref.name, "key": key_ref.key}}})
+}
+
+/// secret_key_ref returns the key of a Secret `value` names, if it is a `secret_value`.
+pub fn secret_key_ref(value: &serde_json::Value) -> Option<KeyRef> {
+    let key_ref = value.get("valueFrom")?.get("secretKeyRef")?;
+    serde_json::from_value(key_ref.clone()).ok()
 }
 
 pub fn extract_string_params(name: &str, params: Vec<ParameterValue>) -> Option<String> {
@@ -62,6 +62,28 @@ fn test_resolve_parameters() {
         json!(1912),
         *res.get("yob").expect("yob should be set to default")
     );
+
+    // Errors name the parameters that failed.
+    let mut vals4 = BTreeMap::new();
+    vals4.insert("yob".into(), json!("not an integer"));
+    vals4.insert("nickname".into(), json!("tom"));
+    let msg = resolve_parameters(params.clone(), vals4)
+        .unwrap_err()
+        .to_string();
+    assert!(msg.contains("parameter email is required"));
+    assert!(msg.contains("expected numeric value for yob"));
+    assert!(msg.contains("unknown parameter nickname"));
+
+    // An optional parameter without value or default is left null.
+    let optional = vec![Parameter {
+        name: "motto".into(),
+        description: None,
+        parameter_type: ParameterType::String,
+        required: false,
+        default: None,
+    }];
+    let res = resolve_parameters(optional, BTreeMap::new()).expect("optional may be unset");
+    assert_eq!(json!(null), *res.get("motto").expect("motto should be set"));
 }
 
 #[test]
@@ -71,11 +93,17 @@ fn test_resolve_values() {
             name: "pet".into(),
             value: Some(json!("dog")),
             from_param: None,
+            value_from: None,
+            from_component: None,
+            output: None,
         },
         ParameterValue {
             name: "home".into(),
             value: Some(json!("house")),
             from_param: None,
+            value_from: None,
+            from_component: None,
+            output: None,
         },
     ];
     let child = vec![
@@ -83,11 +111,17 @@ fn test_resolve_values() {
             name: "favorite_animal".into(),
             value: None,
             from_param: Some("pet".to_string()),
+            value_from: None,
+            from_component: None,
+            output: None,
         },
         ParameterValue {
             name: "abode".into(),
             value: None,
             from_param: Some("home".to_string()),
+            value_from: None,
+            from_component: None,
+            output: None,
         },
     ];
 
@@ -109,17 +143,26 @@ fn test_resolve_values() {
         name: "home".into(),
         value: Some(json!("house")),
         from_param: None,
+        value_from: None,
+        from_component: None,
+        output: None,
     }];
     let child = vec![
         ParameterValue {
             name: "favorite_animal".into(),
             value: None,
             from_param: Some("pet".to_string()),
+            value_from: None,
+            from_component: None,
+            output: None,
         },
         ParameterValue {
             name: "abode".into(),
             value: None,
             from_param: Some("home".to_string()),
+            value_from: None,
+            from_component: None,
+            output: None,
         },
     ];
 
@@ -135,17 +178,26 @@ fn test_resolve_values() {
         name: "home".into(),
         value: Some(json!("house")),
         from_param: None,
+        value_from: None,
+        from_component: None,
+        output: None,
     }];
     let child = vec![
         ParameterValue {
             name: "favorite_animal".into(),
             value: Some(json!("cat")),
             from_param: Some("pet".to_string()),
+            value_from: None,
+            from_component: None,
+            output: None,
         },
         ParameterValue {
             name: "abode".into(),
             value: Some(json!("condo")),
             from_param: Some("home".to_string()),
+            value_from: None,
+            from_component: None,
+            output: None,
         },
     ];
 
@@ -194,6 +246,9 @@ fn test_extract_params() {
             name: "a".to_string(),
             value: Some("1".into()),
             from_param: None,
+            value_from: None,
+            from_component: None,
+            output: None,
         },
     );
     params.insert(
@@ -202,6 +257,9 @@ fn test_extract_params() {
             name: "b".to_string(),
             value: Some("2".into()),
             from_param: None,
+            value_from: None,
+            from_component: None,
+            output: None,
         },
     );
     params.insert(
@@ -210,6 +268,9 @@ fn test_extract_params() {
             name: "c".to_string(),
             value: Some("3".into()),
             from_param: None,
+            value_from: None,
+            from_component: None,
+            output: None,
         },
     );
     assert_eq!(
@@ -226,3 +287,98 @@ fn test_extract_params() {
     );
     assert_eq!(None, extract_string_params("d", params.clone()));
 }
+
+#[test]
+fn test_validate_parameters() {
+    let param =
+        |name: &str, parameter_type: ParameterType, default: Option<serde_json::Value>| Parameter {
+            name: name.into(),
+            description: None,
+            parameter_type,
+            required: false,
+            default,
+        };
+    assert!(validate_parameters(&[
+        param("port", ParameterType::Number, Some(json!(8080))),
+        param("host", ParameterType::String, None),
+    ])
+    .is_ok());
+
+    let err = validate_parameters(&[param("port", ParameterType::Number, Some(json!("http")))])
+        .expect_err("a string default of a number parameter is invalid");
+    assert!(err
+        .to_string()
+        .contains("default of parameter port is invalid"));
+
+    let err = validate_parameters(&[
+        param("port", ParameterType::Number, None),
+        param("port", ParameterType::String, None),
+    ])
+    .expect_err("a parameter may only be declared once");
+    assert!(err.to_string().contains("parameter port is declared twice"));
+}
+
+#[test]
+fn test_parameter_value_from() {
+    let value: ParameterValue = serde_json::from_value(json!({
+        "name": "password",
+        "valueFrom": {"secretKeyRef": {"name": "db", "key": "password"}},
+    }))
+    .expect("parameter value should parse");
+    assert_eq!(value.value, None);
+    assert_eq!(
+        value.value_from,
+        Some(ValueFrom {
+            secret_key_ref: Some(KeyRef {
+                name: "db".into(),
+                key: "password".into(),
+            }),
+            config_map_key_ref: None,
+        })
+    );
+
+    let plain: ParameterValue =
+        serde_json::from_value(json!({"name": "port", "value": 80})).unwrap();
+    assert_eq!(plain.value_from, None);
+    assert_eq!(
+        serde_json::to_value(&plain).unwrap(),
+        json!({"name": "port", "value": 80, "fromParam": null})
+    );
+}
+
+#[test]
+fn test_secret_value() {
+    let key_ref = KeyRef {
+        name: "db".into(),
+        key: "password".into(),
+    };
+    let value = secret_value(&key_ref);
+    assert_eq!(
+        json!({"valueFrom": {"secretKeyRef": {"name": "db", "key": "password"}}}),
+        value
+    );
+    assert_eq!(Some(key_ref), secret_key_ref(&value));
+    assert_eq!(None, secret_key_ref(&json!("password")));
+    assert_eq!(None, secret_key_ref(&json!({"valueFrom": {}})));
+
+    // The kubelet resolves the key, so the type of the parameter can't be checked but a string.
+    let password = |parameter_type| {
+        vec![Parameter {
+            name: "password".into(),
+            description: None,
+            parameter_type,
+            required: true,
+            default: None,
+        }]
+    };
+    let mut vals = BTreeMap::new();
+    vals.insert("password".to_string(), value.clone());
+    let res = resolve_parameters(password(ParameterType::String), vals.clone())
+        .expect("a secret is a string");
+    assert_eq!(value, res["password"]);
+    let err = resolve_parameters(password(ParameterType::Number), vals)
+        .expect_err("a secret is no number");
+    assert!(err
+        .to_string()
+        .contains("parameter password reads a secret but is not a string"));
+}
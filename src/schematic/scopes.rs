@@ -4,6 +4,7 @@ pub use crate::schematic::scopes::health::Health;
 pub mod network;
 use crate::schematic::configuration::ComponentConfiguration;
 pub use crate::schematic::scopes::network::Network;
+use crate::trace;
 use failure::Error;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
 
@@ -44,39 +45,47 @@ impl OAMScope {
             OAMScope::Network(n) => n.scope_type(),
         }
     }
+    /// traced runs the scope operation `op` in a span of its own.
+    fn traced<F: FnOnce() -> Result<(), Error>>(&self, op: &str, f: F) -> Result<(), Error> {
+        let mut span = trace::span(format!("scope.{}", op).as_str());
+        span.set_attribute("scope.type", self.scope_type());
+        let res = f();
+        span.record(&res);
+        res
+    }
     /// create will create a real scope instance
     pub fn create(&self, owner: meta::OwnerReference) -> Result<(), Error> {
-        match self {
+        self.traced("create", || match self {
             OAMScope::Health(h) => h.create(convert_owner_ref(owner.clone())),
             OAMScope::Network(n) => n.create(owner.clone()),
-        }
+        })
     }
     /// modify will modify the scope instance
     pub fn modify(&self) -> Result<(), Error> {
-        match self {
+        self.traced("modify", || match self {
             OAMScope::Health(h) => h.modify(),
             OAMScope::Network(n) => n.modify(),
-        }
+        })
     }
     /// delete will delete the scope instance, we can depend on OwnerReference if only k8s objects were created
     pub fn delete(&self) -> Result<(), Error> {
-        match self {
+        self.traced("delete", || match self {
             OAMScope::Health(h) => h.delete(),
             OAMScope::Network(n) => n.delete(),
-        }
+        })
     }
     /// add will add a component to this scope
     pub fn add(&self, spec: ComponentConfiguration) -> Result<(), Error> {
-        match self {
+        self.traced("add", || match self {
             OAMScope::Health(h) => h.add(spec),
             OAMScope::Network(n) => n.add(spec),
-        }
+        })
     }
     /// remove will remove component from this scope
     pub fn remove(&self, spec: ComponentConfiguration) -> Result<(), Error> {
-        match self {
+        self.traced("remove", || match self {
             OAMScope::Health(h) => h.remove(spec),
             OAMScope::Network(n) => n.remove(spec),
-        }
+        })
     }
 }
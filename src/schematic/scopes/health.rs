@@ -1,3 +1,5 @@
+use crate::metrics::TimedRequest;
+use crate::retry::{on_conflict, CONFLICT_ATTEMPTS};
 use crate::schematic::configuration::ComponentConfiguration;
 use crate::schematic::parameter::{
     self, extract_number_params, extract_string_params, ParameterValue,
@@ -22,31 +24,97 @@ pub struct HealthScope {
     pub healthy_rate_threshold: Option<f64>,
     pub health_threshold_percentage: Option<f64>,
     pub required_healthy_components: Option<Vec<String>>,
+    /// Probes tried in order when the probe before them fails with an error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_probes: Option<Vec<Probe>>,
+    /// A suspended scope is not probed, its status stays as it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspended: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+impl HealthScope {
+    /// probes returns the probe of the scope followed by its fallback probes.
+    pub fn probes(&self) -> Vec<Probe> {
+        let mut probes = vec![Probe {
+            probe_method: self.probe_method.clone(),
+            probe_endpoint: self.probe_endpoint.clone(),
+        }];
+        probes.extend(self.fallback_probes.clone().unwrap_or_default());
+        probes
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Probe {
+    pub probe_method: String,
+    pub probe_endpoint: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ComponentInfo {
     pub name: String,
     pub instance_name: String,
     pub status: Option<String>,
+    /// Why the last probe could not determine the health, cleared once the component is healthy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_probe_time: Option<String>,
+    /// How many probes in a row found the component unhealthy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consecutive_failures: Option<i64>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct HealthStatus {
     pub components: Option<Vec<ComponentInfo>>,
     pub last_aggregate_timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime: Option<Uptime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<Condition>>,
 }
 impl Default for HealthStatus {
     fn default() -> Self {
         HealthStatus {
             components: None,
             last_aggregate_timestamp: None,
+            uptime: None,
+            conditions: None,
         }
     }
 }
 
+/// Condition follows the Kubernetes status condition conventions, `status` is True, False or Unknown.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Condition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_transition_time: Option<String>,
+}
+
+/// Uptime is the percentage of probes that found the scope not unhealthy over rolling windows.
+/// A window is None until the scope was probed within it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Uptime {
+    #[serde(rename = "1h", default, skip_serializing_if = "Option::is_none")]
+    pub last_hour: Option<f64>,
+    #[serde(rename = "24h", default, skip_serializing_if = "Option::is_none")]
+    pub last_day: Option<f64>,
+    #[serde(rename = "7d", default, skip_serializing_if = "Option::is_none")]
+    pub last_week: Option<f64>,
+}
+
 pub type HealthScopeObject = kube::api::Object<HealthScope, HealthStatus>;
 
 /// Health scope is defined as https://github.com/oam-dev/spec/blob/master/4.application_scopes.md#health-scope
@@ -64,6 +132,8 @@ pub struct Health {
     pub healthy_rate_threshold: Option<f64>,
     pub health_threshold_percentage: Option<f64>,
     pub required_healthy_components: Option<Vec<String>>,
+    pub fallback_probes: Option<Vec<Probe>>,
+    pub suspended: Option<bool>,
 }
 
 impl Health {
@@ -113,6 +183,13 @@ impl Health {
                         .clone()
                         .collect()
                 });
+        let fallback_probes =
+            match parameter::extract_value_params("fallback-probes", params.clone()) {
+                Some(value) => Some(parse_probes(value)?),
+                None => None,
+            };
+        let suspended =
+            parameter::extract_value_params("suspended", params.clone()).and_then(|v| v.as_bool());
         Ok(Health {
             name,
             namespace,
@@ -126,6 +203,8 @@ impl Health {
             healthy_rate_threshold,
             health_threshold_percentage,
             required_healthy_components,
+            fallback_probes,
+            suspended,
         })
     }
     pub fn allow_overlap(&self) -> bool {
@@ -148,6 +227,8 @@ impl Health {
                 healthy_rate_threshold: self.healthy_rate_threshold,
                 health_threshold_percentage: self.health_threshold_percentage,
                 required_healthy_components: self.required_healthy_components.clone(),
+                fallback_probes: self.fallback_probes.clone(),
+                suspended: self.suspended,
             },
             types: kube::api::TypeMeta {
                 apiVersion: Some(HEALTH_SCOPE_GROUP.to_string() + "/" + HEALTH_SCOPE_VERSION),
@@ -167,7 +248,7 @@ impl Health {
         let req = healthscope_resource.create(&pp, serde_json::to_vec(&scope)?)?;
         let err = self
             .client
-            .request::<HealthScopeObject>(req)
+            .timed_request::<HealthScopeObject>(req)
             .err()
             .and_then(|e| {
                 let exist = e
@@ -198,35 +279,40 @@ impl Health {
         Ok(())
     }
     pub fn add(&self, spec: ComponentConfiguration) -> Result<(), Error> {
-        let mut obj = self.get_obj()?;
-        let mut components = self.remove_one(spec.clone(), obj.status.clone());
-        components.insert(
-            components.len(),
-            ComponentInfo {
-                name: spec.component_name.clone(),
-                instance_name: spec.instance_name.clone(),
-                status: None,
-            },
-        );
-        obj.status = Some(HealthStatus {
-            components: Some(components),
-            ..Default::default()
-        });
         info!(
             "add component {} to health scope {}",
             spec.component_name.clone(),
             self.name.clone()
         );
-        self.patch_obj(obj)
+        // The healthscope controller writes the status too, a conflict reads the scope again.
+        on_conflict(CONFLICT_ATTEMPTS, |_| {
+            let mut obj = self.get_obj()?;
+            let mut components = self.remove_one(spec.clone(), obj.status.clone());
+            components.insert(
+                components.len(),
+                ComponentInfo {
+                    name: spec.component_name.clone(),
+                    instance_name: spec.instance_name.clone(),
+                    ..Default::default()
+                },
+            );
+            obj.status = Some(HealthStatus {
+                components: Some(components),
+                ..Default::default()
+            });
+            self.patch_obj(obj)
+        })
     }
     pub fn remove(&self, spec: ComponentConfiguration) -> Result<(), Error> {
-        let mut obj = self.get_obj()?;
-        let components = self.remove_one(spec.clone(), obj.status.clone());
-        obj.status = Some(HealthStatus {
-            components: Some(components),
-            ..Default::default()
-        });
-        self.patch_obj(obj)
+        on_conflict(CONFLICT_ATTEMPTS, |_| {
+            let mut obj = self.get_obj()?;
+            let components = self.remove_one(spec.clone(), obj.status.clone());
+            obj.status = Some(HealthStatus {
+                components: Some(components),
+                ..Default::default()
+            });
+            self.patch_obj(obj)
+        })
     }
 
     pub fn get_obj(&self) -> Result<HealthScopeObject, Error> {
@@ -235,7 +321,7 @@ impl Health {
             .group(HEALTH_SCOPE_GROUP)
             .within(self.namespace.as_str());
         let req = healthscope_resource.get(self.name.as_str())?;
-        Ok(self.client.request::<HealthScopeObject>(req)?)
+        Ok(self.client.timed_request::<HealthScopeObject>(req)?)
     }
     fn remove_one(
         &self,
@@ -260,15 +346,43 @@ impl Health {
             .group(HEALTH_SCOPE_GROUP)
             .within(self.namespace.as_str());
         let req = healthscope_resource.patch(self.name.as_str(), &pp, serde_json::to_vec(&obj)?)?;
-        self.client.request::<HealthScopeObject>(req)?;
+        self.client.timed_request::<HealthScopeObject>(req)?;
         Ok(())
     }
 }
 
+/// parse_probes reads a list like `[{"probe-method": "kube-get", "probe-endpoint": ".status"}]`.
+fn parse_probes(value: serde_json::Value) -> Result<Vec<Probe>, Error> {
+    let items = match value.as_array() {
+        Some(items) => items.clone(),
+        None => return Err(format_err!("fallback-probes must be a list, got {}", value)),
+    };
+    items
+        .iter()
+        .map(|item| {
+            let field = |name: &str| {
+                item.get(name)
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string())
+                    .ok_or_else(|| {
+                        format_err!("{} does not exist in fallback probe {}", name, item)
+                    })
+            };
+            Ok(Probe {
+                probe_method: field("probe-method")?,
+                probe_endpoint: field("probe-endpoint")?,
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use crate::schematic::parameter::ParameterValue;
-    use crate::schematic::scopes::{health::Health, HEALTH_SCOPE};
+    use crate::schematic::scopes::{
+        health::{Health, Probe},
+        HEALTH_SCOPE,
+    };
     use kube::client::APIClient;
     use kube::config::Configuration;
     /// This mock builds a KubeConfig that will not be able to make any requests.
@@ -287,6 +401,9 @@ mod test {
                 name: "probe-method".to_string(),
                 value: Some("httpGet".into()),
                 from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
             },
         );
         params.insert(
@@ -295,6 +412,9 @@ mod test {
                 name: "probe-endpoint".to_string(),
                 value: Some("/v1/health".into()),
                 from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
             },
         );
         params.insert(
@@ -303,6 +423,9 @@ mod test {
                 name: "probe-timeout".to_string(),
                 value: Some(10.into()),
                 from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
             },
         );
         params.insert(
@@ -311,6 +434,9 @@ mod test {
                 name: "failure-rate-threshold".to_string(),
                 value: Some(80.into()),
                 from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
             },
         );
         let mut comps = vec![];
@@ -322,6 +448,33 @@ mod test {
                 name: "required-healthy-components".to_string(),
                 value: Some(serde_json::Value::Array(comps)),
                 from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
+            },
+        );
+        params.insert(
+            params.len(),
+            ParameterValue {
+                name: "fallback-probes".to_string(),
+                value: Some(serde_json::json!([
+                    {"probe-method": "kube-get", "probe-endpoint": ".status"}
+                ])),
+                from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
+            },
+        );
+        params.insert(
+            params.len(),
+            ParameterValue {
+                name: "suspended".to_string(),
+                value: Some(true.into()),
+                from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
             },
         );
 
@@ -343,5 +496,13 @@ mod test {
         comps.insert(0, "comp1".to_string());
         comps.insert(1, "comp2".to_string());
         assert_eq!(Some(comps), net.required_healthy_components);
+        assert_eq!(
+            Some(vec![Probe {
+                probe_method: "kube-get".to_string(),
+                probe_endpoint: ".status".to_string(),
+            }]),
+            net.fallback_probes
+        );
+        assert_eq!(Some(true), net.suspended);
     }
 }
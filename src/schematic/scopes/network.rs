@@ -92,6 +92,9 @@ mod test {
                 name: "network-id".to_string(),
                 value: Some("nid".into()),
                 from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
             },
         );
         params.insert(
@@ -100,6 +103,9 @@ mod test {
                 name: "subnet-id".to_string(),
                 value: Some("sid".into()),
                 from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
             },
         );
         let net = Network::from_params(
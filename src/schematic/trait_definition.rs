@@ -0,0 +1,214 @@
+use failure::Error;
+use kube::api::{ListParams, Object, ObjectList, RawApi, Void};
+use kube::client::APIClient;
+use regex::Regex;
+use serde_json::map::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::metrics::TimedRequest;
+
+/// The plural of the TraitDefinition custom resource.
+pub const TRAIT_DEFINITION_CRD: &str = "traitdefinitions";
+
+/// TraitDefinition registers a trait at runtime: the objects its template renders are created
+/// with the component, so that platform teams can add traits without changing Rudr.
+///
+/// The strings of the template may reference the properties of the trait binding with
+/// [fromProperty(NAME)], a dotted path into them, and the component instance with
+/// [fromContext(NAME)]: `applicationName`, `componentName`, `instanceName` and `workloadType`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TraitDefinition {
+    /// The workload types the trait applies to, `*` or none for every one.
+    #[serde(default)]
+    pub applies_to: Vec<String>,
+    /// The JSON schema of the properties, like the one of a Trait. Its defaults fill in the
+    /// properties a binding leaves out.
+    pub properties: Option<String>,
+    /// The objects the trait creates.
+    #[serde(default)]
+    pub template: Vec<Value>,
+}
+
+impl TraitDefinition {
+    /// applies_to is true if the trait applies to the workload type `workload_type`.
+    pub fn applies_to(&self, workload_type: &str) -> bool {
+        self.applies_to.is_empty()
+            || self
+                .applies_to
+                .iter()
+                .any(|t| t == "*" || t == workload_type)
+    }
+
+    /// validate checks the schema of the properties and the objects of the template.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.schema()?;
+        if self.template.is_empty() {
+            return Err(format_err!("trait definition has no template"));
+        }
+        for object in self.template.iter() {
+            if !object["apiVersion"].is_string() || !object["kind"].is_string() {
+                return Err(format_err!(
+                    "object of the template without apiVersion or kind"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn schema(&self) -> Result<Value, Error> {
+        match self.properties.as_ref() {
+            Some(schema) => serde_json::from_str(schema)
+                .map_err(|e| format_err!("invalid properties schema: {}", e)),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// properties are the properties of a binding with the defaults of the schema for the
+    /// ones it leaves out. It fails if a required property is missing.
+    pub fn properties(
+        &self,
+        given: Option<&Map<String, Value>>,
+    ) -> Result<Map<String, Value>, Error> {
+        let mut properties = given.cloned().unwrap_or_default();
+        let schema = self.schema()?;
+        if let Some(declared) = schema["properties"].as_object() {
+            for (name, property) in declared {
+                if let Some(default) = property.get("default") {
+                    properties
+                        .entry(name.clone())
+                        .or_insert_with(|| default.clone());
+                }
+            }
+        }
+        for required in schema["required"].as_array().into_iter().flatten() {
+            if let Some(name) = required.as_str() {
+                if !properties.contains_key(name) {
+                    return Err(format_err!("missing required property `{}`", name));
+                }
+            }
+        }
+        Ok(properties)
+    }
+
+    /// render renders the objects of the template with `properties` and `context`.
+    pub fn render(
+        &self,
+        properties: &Map<String, Value>,
+        context: &BTreeMap<String, String>,
+    ) -> Result<Vec<Value>, Error> {
+        self.validate()?;
+        self.template
+            .iter()
+            .map(|object| render_value(object, properties, context))
+            .collect()
+    }
+}
+
+fn render_value(
+    value: &Value,
+    properties: &Map<String, Value>,
+    context: &BTreeMap<String, String>,
+) -> Result<Value, Error> {
+    match value {
+        Value::String(text) => render_string(text, properties, context),
+        Value::Array(items) => Ok(Value::Array(
+            items
+                .iter()
+                .map(|v| render_value(v, properties, context))
+                .collect::<Result<_, Error>>()?,
+        )),
+        Value::Object(fields) => {
+            let mut rendered = Map::new();
+            for (key, v) in fields {
+                rendered.insert(key.clone(), render_value(v, properties, context)?);
+            }
+            Ok(Value::Object(rendered))
+        }
+        other => Ok(other.clone()),
+    }
+}
+
+/// render_string replaces the expressions in `text`. A string that is one expression is
+/// replaced with its value, keeping its type, so that a number of replicas stays a number.
+/// Otherwise strings are substituted as they are and other values as JSON.
+fn render_string(
+    text: &str,
+    properties: &Map<String, Value>,
+    context: &BTreeMap<String, String>,
+) -> Result<Value, Error> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r#"\[(?P<source>fromProperty|fromContext)\((?P<name>[[:word:].-]+)\)\]"#)
+                .unwrap();
+    }
+    let mut substituted = String::with_capacity(text.len());
+    let mut last = 0;
+    for cap in RE.captures_iter(text) {
+        let expression = cap.get(0).expect("match");
+        let value = expression_value(&cap["source"], &cap["name"], properties, context)?;
+        if expression.as_str().len() == text.len() {
+            return Ok(value);
+        }
+        let value = match value {
+            Value::String(s) => s,
+            Value::Null => {
+                return Err(format_err!(
+                    "{} has no value to substitute",
+                    expression.as_str()
+                ))
+            }
+            value => value.to_string(),
+        };
+        substituted.push_str(&text[last..expression.start()]);
+        substituted.push_str(&value);
+        last = expression.end();
+    }
+    substituted.push_str(&text[last..]);
+    Ok(Value::String(substituted))
+}
+
+fn expression_value(
+    source: &str,
+    name: &str,
+    properties: &Map<String, Value>,
+    context: &BTreeMap<String, String>,
+) -> Result<Value, Error> {
+    if source == "fromContext" {
+        return context
+            .get(name)
+            .map(|v| Value::String(v.clone()))
+            .ok_or_else(|| format_err!("undefined context `{}`", name));
+    }
+    let mut path = name.split('.');
+    let first = properties.get(path.next().unwrap_or_default());
+    path.fold(first, |value, key| value.and_then(|v| v.get(key)))
+        .cloned()
+        .ok_or_else(|| format_err!("undefined property `{}`", name))
+}
+
+/// trait_definitions returns the TraitDefinitions in `namespace` by name. There are none if
+/// their CRD isn't installed.
+pub fn trait_definitions(
+    client: &APIClient,
+    namespace: &str,
+) -> Result<BTreeMap<String, TraitDefinition>, Error> {
+    let req = RawApi::customResource(TRAIT_DEFINITION_CRD)
+        .group("core.oam.dev")
+        .version("v1alpha1")
+        .within(namespace)
+        .list(&ListParams::default())?;
+    let list = match client.timed_request::<ObjectList<Object<TraitDefinition, Void>>>(req) {
+        Ok(list) => list,
+        Err(e) => match e.api_error() {
+            Some(ref err) if err.code == 404 => return Ok(BTreeMap::new()),
+            _ => return Err(e.into()),
+        },
+    };
+    Ok(list
+        .items
+        .into_iter()
+        .map(|d| (d.metadata.name, d.spec))
+        .collect())
+}
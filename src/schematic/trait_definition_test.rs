@@ -0,0 +1,85 @@
+use crate::schematic::trait_definition::*;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_trait_definition_render() {
+    let definition: TraitDefinition = serde_json::from_value(json!({
+        "appliesTo": ["core.oam.dev/v1alpha1.Server"],
+        "properties": r#"{
+            "type": "object",
+            "required": ["replicas"],
+            "properties": {
+                "replicas": {"type": "integer"},
+                "tier": {"type": "string", "default": "gold"}
+            }
+        }"#,
+        "template": [{
+            "apiVersion": "example.com/v1",
+            "kind": "Backup",
+            "metadata": {"name": "[fromContext(instanceName)]-backup"},
+            "spec": {
+                "replicas": "[fromProperty(replicas)]",
+                "schedule": "[fromProperty(schedule.cron)]",
+                "storageClass": "[fromProperty(tier)]-[fromProperty(replicas)]",
+                "target": ["[fromContext(componentName)]"]
+            }
+        }]
+    }))
+    .expect("trait definition");
+    let properties = definition
+        .properties(json!({"replicas": 3, "schedule": {"cron": "@daily"}}).as_object())
+        .expect("properties");
+    let mut context = BTreeMap::new();
+    context.insert("instanceName".to_string(), "squid".to_string());
+    context.insert("componentName".to_string(), "patsy".to_string());
+    let objects = definition.render(&properties, &context).expect("render");
+    assert_eq!(1, objects.len());
+    assert_eq!("squid-backup", objects[0]["metadata"]["name"]);
+    // An expression of its own keeps the type of its value.
+    assert_eq!(
+        json!({
+            "replicas": 3,
+            "schedule": "@daily",
+            "storageClass": "gold-3",
+            "target": ["patsy"]
+        }),
+        objects[0]["spec"]
+    );
+
+    let properties = definition
+        .properties(json!({"replicas": 3}).as_object())
+        .expect("properties");
+    assert!(definition.render(&properties, &context).is_err());
+}
+
+#[test]
+fn test_trait_definition_properties() {
+    let definition: TraitDefinition = serde_json::from_value(json!({
+        "appliesTo": ["core.oam.dev/v1alpha1.Server"],
+        "properties": r#"{
+            "type": "object",
+            "required": ["replicas"],
+            "properties": {
+                "replicas": {"type": "integer"},
+                "tier": {"type": "string", "default": "gold"}
+            }
+        }"#
+    }))
+    .expect("trait definition");
+    assert!(definition.properties(None).is_err());
+    let properties = definition
+        .properties(json!({"replicas": 1, "tier": "silver"}).as_object())
+        .expect("properties");
+    assert_eq!(json!("silver"), properties["tier"]);
+
+    assert!(definition.applies_to("core.oam.dev/v1alpha1.Server"));
+    assert!(!definition.applies_to("core.oam.dev/v1alpha1.Task"));
+    assert!(TraitDefinition::default().applies_to("core.oam.dev/v1alpha1.Task"));
+
+    let invalid = TraitDefinition {
+        properties: Some("{".into()),
+        ..definition
+    };
+    assert!(invalid.validate().is_err());
+}
@@ -1,19 +1,67 @@
 use crate::lifecycle::Phase;
 use crate::schematic::parameter::ParameterValue;
+use crate::trace;
+use crate::workload_type::{PodPatch, RenderResult};
 use kube::client::APIClient;
 use log::info;
 
 // Re-exports
 mod autoscaler;
-pub use crate::schematic::traits::autoscaler::Autoscaler;
+pub use crate::schematic::traits::autoscaler::{hpa_state, Autoscaler};
+mod disruption_budget;
+pub use crate::schematic::traits::disruption_budget::DisruptionBudget;
+mod http_route;
+pub use crate::schematic::traits::http_route::HttpRoute;
 mod ingress;
-pub use crate::schematic::traits::ingress::Ingress;
+pub use crate::schematic::traits::ingress::{ingress_hosts, ingress_state, Ingress};
+mod log_forwarder;
+pub use crate::schematic::traits::log_forwarder::LogForwarder;
+mod metrics_monitor;
+pub use crate::schematic::traits::metrics_monitor::MetricsMonitor;
+mod network_policy;
+pub use crate::schematic::traits::network_policy::NetworkPolicy;
+mod traffic_split;
+pub use crate::schematic::traits::traffic_split::TrafficSplit;
+mod keda_autoscaler;
+pub use crate::schematic::traits::keda_autoscaler::KedaAutoscaler;
 mod empty;
 pub use crate::schematic::traits::empty::Empty;
 mod manual_scaler;
-pub use crate::schematic::traits::manual_scaler::ManualScaler;
+pub use crate::schematic::traits::manual_scaler::{scaling_autoscaler, ManualScaler};
+mod vertical_autoscaler;
+pub use crate::schematic::traits::vertical_autoscaler::VerticalAutoscaler;
 mod volume_mounter;
 pub use crate::schematic::traits::volume_mounter::VolumeMounter;
+mod secret_mounter;
+pub use crate::schematic::traits::secret_mounter::SecretMounter;
+mod config_mounter;
+pub use crate::schematic::traits::config_mounter::ConfigMounter;
+mod dns;
+pub use crate::schematic::traits::dns::Dns;
+mod placement;
+pub use crate::schematic::traits::placement::Placement;
+mod tolerations;
+pub use crate::schematic::traits::tolerations::Tolerations;
+mod topology_spread;
+pub use crate::schematic::traits::topology_spread::TopologySpread;
+mod sidecar;
+pub use crate::schematic::traits::sidecar::Sidecar;
+mod init_container;
+pub use crate::schematic::traits::init_container::InitContainer;
+mod service_mesh;
+pub use crate::schematic::traits::service_mesh::ServiceMesh;
+mod helm_release;
+pub use crate::schematic::traits::helm_release::HelmRelease;
+mod defined;
+pub use crate::schematic::traits::defined::DefinedTrait;
+mod backup;
+pub use crate::schematic::traits::backup::{backup_volumes, Backup};
+mod security_context;
+pub use crate::schematic::traits::security_context::SecurityContext;
+mod image_pull_secret;
+pub use crate::schematic::traits::image_pull_secret::ImagePullSecret;
+mod priority;
+pub use crate::schematic::traits::priority::Priority;
 mod util;
 use crate::schematic::traits::util::*;
 use std::collections::BTreeMap;
@@ -21,16 +69,128 @@ use std::collections::BTreeMap;
 #[cfg(test)]
 mod autoscaler_test;
 #[cfg(test)]
-mod manual_scaler_test;
+mod backup_test;
+#[cfg(test)]
+mod config_mounter_test;
+#[cfg(test)]
+mod defined_test;
+#[cfg(test)]
+mod disruption_budget_test;
+#[cfg(test)]
+mod dns_test;
+#[cfg(test)]
+mod helm_release_test;
+#[cfg(test)]
+mod http_route_test;
+#[cfg(test)]
+mod image_pull_secret_test;
 #[cfg(test)]
 mod ingress_test;
+#[cfg(test)]
+mod init_container_test;
+#[cfg(test)]
+mod keda_autoscaler_test;
+#[cfg(test)]
+mod log_forwarder_test;
+#[cfg(test)]
+mod manual_scaler_test;
+#[cfg(test)]
+mod metrics_monitor_test;
+#[cfg(test)]
+mod network_policy_test;
+#[cfg(test)]
+mod placement_test;
+#[cfg(test)]
+mod priority_test;
+#[cfg(test)]
+mod secret_mounter_test;
+#[cfg(test)]
+mod security_context_test;
+#[cfg(test)]
+mod service_mesh_test;
+#[cfg(test)]
+mod sidecar_test;
+#[cfg(test)]
+mod tolerations_test;
+#[cfg(test)]
+mod topology_spread_test;
+#[cfg(test)]
+mod traffic_split_test;
+#[cfg(test)]
+mod vertical_autoscaler_test;
 
 pub const INGRESS_V1ALPHA1: &str = "ingress";
+pub const HTTP_ROUTE_V1ALPHA1: &str = "http-route";
+pub const TRAFFIC_SPLIT_V1ALPHA1: &str = "traffic-split";
+pub const DISRUPTION_BUDGET_V1ALPHA1: &str = "disruption-budget";
+pub const NETWORK_POLICY_V1ALPHA1: &str = "network-policy";
+pub const METRICS_V1ALPHA1: &str = "metrics";
+pub const LOG_FORWARDER_V1ALPHA1: &str = "log-forwarder";
 pub const AUTOSCALER_V1ALPHA1: &str = "auto-scaler";
+pub const KEDA_AUTOSCALER_V1ALPHA1: &str = "keda-autoscaler";
 pub const MANUAL_SCALER_V1ALPHA1: &str = "manual-scaler";
+pub const VERTICAL_AUTOSCALER_V1ALPHA1: &str = "vertical-autoscaler";
 pub const VOLUME_MOUNTER_V1ALPHA1: &str = "volume-mounter";
+pub const SECRET_MOUNTER_V1ALPHA1: &str = "secret-mounter";
+pub const CONFIG_MOUNTER_V1ALPHA1: &str = "config-mounter";
+pub const DNS_V1ALPHA1: &str = "dns";
+pub const PLACEMENT_V1ALPHA1: &str = "placement";
+pub const TOLERATIONS_V1ALPHA1: &str = "tolerations";
+pub const TOPOLOGY_SPREAD_V1ALPHA1: &str = "topology-spread";
+pub const SIDECAR_V1ALPHA1: &str = "sidecar";
+pub const INIT_CONTAINER_V1ALPHA1: &str = "init-container";
+pub const SERVICE_MESH_V1ALPHA1: &str = "service-mesh";
+pub const HELM_RELEASE_V1ALPHA1: &str = "helm-release";
+pub const BACKUP_V1ALPHA1: &str = "backup";
+pub const SECURITY_CONTEXT_V1ALPHA1: &str = "security-context";
+pub const IMAGE_PULL_SECRET_V1ALPHA1: &str = "image-pull-secret";
+pub const PRIORITY_V1ALPHA1: &str = "priority";
 pub const EMPTY: &str = "empty";
 
+/// BUILTIN are the traits Rudr implements itself. Every other trait is one of a
+/// TraitDefinition.
+pub const BUILTIN: &[&str] = &[
+    INGRESS_V1ALPHA1,
+    HTTP_ROUTE_V1ALPHA1,
+    TRAFFIC_SPLIT_V1ALPHA1,
+    DISRUPTION_BUDGET_V1ALPHA1,
+    NETWORK_POLICY_V1ALPHA1,
+    METRICS_V1ALPHA1,
+    LOG_FORWARDER_V1ALPHA1,
+    AUTOSCALER_V1ALPHA1,
+    KEDA_AUTOSCALER_V1ALPHA1,
+    MANUAL_SCALER_V1ALPHA1,
+    VERTICAL_AUTOSCALER_V1ALPHA1,
+    VOLUME_MOUNTER_V1ALPHA1,
+    SECRET_MOUNTER_V1ALPHA1,
+    CONFIG_MOUNTER_V1ALPHA1,
+    DNS_V1ALPHA1,
+    PLACEMENT_V1ALPHA1,
+    TOLERATIONS_V1ALPHA1,
+    TOPOLOGY_SPREAD_V1ALPHA1,
+    SIDECAR_V1ALPHA1,
+    INIT_CONTAINER_V1ALPHA1,
+    SERVICE_MESH_V1ALPHA1,
+    HELM_RELEASE_V1ALPHA1,
+    BACKUP_V1ALPHA1,
+    SECURITY_CONTEXT_V1ALPHA1,
+    IMAGE_PULL_SECRET_V1ALPHA1,
+    PRIORITY_V1ALPHA1,
+    EMPTY,
+];
+
+/// trait_ready is true if `state`, the state of a resource in the status of a trait, says the
+/// resource is ready. Traits report `ready`, with details after a space or comma, or `created`
+/// for resources without a status of their own.
+pub fn trait_ready(state: &str) -> bool {
+    state == "ready"
+        || state.starts_with("ready ")
+        || state.starts_with("ready,")
+        || state == "created"
+        || state == "running"
+        || state == "succeeded"
+}
+
 /// Trait describes OAM traits.
 ///
 /// OAM traits are ops-oriented "add-ons" that can be attached to Components of the appropriate workloadType.
@@ -49,6 +209,78 @@ pub struct TraitBinding {
     pub name: String,
     pub parameter_values: Option<Vec<ParameterValue>>,
     pub properties: Option<serde_json::Value>,
+    /// Traits of a higher priority are applied first, see `default_priority`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// The names of the traits of the component that are applied before this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+}
+
+impl TraitBinding {
+    /// priority is the priority of the binding, or the default one of its trait.
+    pub fn priority(&self) -> i32 {
+        self.priority
+            .unwrap_or_else(|| default_priority(self.name.as_str()))
+    }
+}
+
+/// default_priority is the priority of the trait `name` unless its binding sets one. The
+/// mounters go first, so that the volumes they create are there for the containers that
+/// other traits add, and those containers go before everything else.
+pub fn default_priority(name: &str) -> i32 {
+    match name {
+        VOLUME_MOUNTER_V1ALPHA1 | SECRET_MOUNTER_V1ALPHA1 | CONFIG_MOUNTER_V1ALPHA1 => 200,
+        SIDECAR_V1ALPHA1 | INIT_CONTAINER_V1ALPHA1 | LOG_FORWARDER_V1ALPHA1 => 100,
+        _ => 0,
+    }
+}
+
+/// apply_order sorts trait bindings in the order their traits are applied: every binding
+/// after the ones it depends on, by priority otherwise, and in the order they are listed
+/// on a tie. A dependency on a trait the component doesn't have, or a cycle, is an error.
+pub fn apply_order(bindings: &[TraitBinding]) -> Result<Vec<&TraitBinding>, failure::Error> {
+    for binding in bindings {
+        for dep in binding.depends_on.iter().flatten() {
+            if !bindings.iter().any(|b| &b.name == dep) {
+                return Err(format_err!(
+                    "trait {} depends on trait {}, which the component doesn't have",
+                    binding.name,
+                    dep
+                ));
+            }
+        }
+    }
+    let mut ordered: Vec<&TraitBinding> = Vec::with_capacity(bindings.len());
+    let mut pending: Vec<&TraitBinding> = bindings.iter().collect();
+    while !pending.is_empty() {
+        // A binding is ready once no pending binding has the name of one of its dependencies.
+        let ready = pending
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| {
+                b.depends_on
+                    .iter()
+                    .flatten()
+                    .all(|dep| !pending.iter().any(|p| &p.name == dep && p.name != b.name))
+            })
+            .max_by(|(i, a), (j, b)| a.priority().cmp(&b.priority()).then(j.cmp(i)))
+            .map(|(i, _)| i);
+        match ready {
+            Some(i) => ordered.push(pending.remove(i)),
+            None => {
+                return Err(format_err!(
+                    "the traits {} can't be ordered, their dependencies form a cycle",
+                    pending
+                        .iter()
+                        .map(|b| b.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+        }
+    }
+    Ok(ordered)
 }
 
 /// OAMTrait is an enumeration of the known traits.
@@ -58,30 +290,201 @@ pub struct TraitBinding {
 /// fulfill the contract.
 pub enum OAMTrait {
     Autoscaler(Autoscaler),
+    KedaAutoscaler(KedaAutoscaler),
     ManualScaler(ManualScaler),
+    VerticalAutoscaler(VerticalAutoscaler),
     Ingress(Ingress),
+    HttpRoute(HttpRoute),
+    TrafficSplit(TrafficSplit),
+    DisruptionBudget(DisruptionBudget),
+    NetworkPolicy(NetworkPolicy),
+    MetricsMonitor(MetricsMonitor),
+    LogForwarder(LogForwarder),
     VolumeMounter(Box<VolumeMounter>),
+    SecretMounter(SecretMounter),
+    ConfigMounter(ConfigMounter),
+    Dns(Dns),
+    Placement(Placement),
+    Tolerations(Tolerations),
+    TopologySpread(TopologySpread),
+    Sidecar(Sidecar),
+    InitContainer(InitContainer),
+    ServiceMesh(ServiceMesh),
+    HelmRelease(HelmRelease),
+    Backup(Backup),
+    SecurityContext(SecurityContext),
+    ImagePullSecret(ImagePullSecret),
+    Priority(Priority),
+    Defined(Box<DefinedTrait>),
     Empty(Empty),
 }
 impl OAMTrait {
-    pub fn exec(&self, ns: &str, client: APIClient, phase: Phase) -> TraitResult {
+    /// name is the name trait bindings use for the trait.
+    pub fn name(&self) -> &str {
         match self {
+            OAMTrait::Autoscaler(_) => AUTOSCALER_V1ALPHA1,
+            OAMTrait::KedaAutoscaler(_) => KEDA_AUTOSCALER_V1ALPHA1,
+            OAMTrait::Ingress(_) => INGRESS_V1ALPHA1,
+            OAMTrait::HttpRoute(_) => HTTP_ROUTE_V1ALPHA1,
+            OAMTrait::TrafficSplit(_) => TRAFFIC_SPLIT_V1ALPHA1,
+            OAMTrait::DisruptionBudget(_) => DISRUPTION_BUDGET_V1ALPHA1,
+            OAMTrait::NetworkPolicy(_) => NETWORK_POLICY_V1ALPHA1,
+            OAMTrait::MetricsMonitor(_) => METRICS_V1ALPHA1,
+            OAMTrait::LogForwarder(_) => LOG_FORWARDER_V1ALPHA1,
+            OAMTrait::ManualScaler(_) => MANUAL_SCALER_V1ALPHA1,
+            OAMTrait::VerticalAutoscaler(_) => VERTICAL_AUTOSCALER_V1ALPHA1,
+            OAMTrait::VolumeMounter(_) => VOLUME_MOUNTER_V1ALPHA1,
+            OAMTrait::SecretMounter(_) => SECRET_MOUNTER_V1ALPHA1,
+            OAMTrait::ConfigMounter(_) => CONFIG_MOUNTER_V1ALPHA1,
+            OAMTrait::Dns(_) => DNS_V1ALPHA1,
+            OAMTrait::Placement(_) => PLACEMENT_V1ALPHA1,
+            OAMTrait::Tolerations(_) => TOLERATIONS_V1ALPHA1,
+            OAMTrait::TopologySpread(_) => TOPOLOGY_SPREAD_V1ALPHA1,
+            OAMTrait::Sidecar(_) => SIDECAR_V1ALPHA1,
+            OAMTrait::InitContainer(_) => INIT_CONTAINER_V1ALPHA1,
+            OAMTrait::ServiceMesh(_) => SERVICE_MESH_V1ALPHA1,
+            OAMTrait::HelmRelease(_) => HELM_RELEASE_V1ALPHA1,
+            OAMTrait::Backup(_) => BACKUP_V1ALPHA1,
+            OAMTrait::SecurityContext(_) => SECURITY_CONTEXT_V1ALPHA1,
+            OAMTrait::ImagePullSecret(_) => IMAGE_PULL_SECRET_V1ALPHA1,
+            OAMTrait::Priority(_) => PRIORITY_V1ALPHA1,
+            OAMTrait::Defined(d) => d.trait_name.as_str(),
+            OAMTrait::Empty(_) => EMPTY,
+        }
+    }
+    pub fn exec(&self, ns: &str, client: APIClient, phase: Phase) -> TraitResult {
+        let mut span = trace::span("trait");
+        span.set_attribute("trait.name", self.name());
+        span.set_attribute("trait.phase", format!("{:?}", phase));
+        let res = match self {
             OAMTrait::Autoscaler(a) => a.exec(ns, client, phase),
+            OAMTrait::KedaAutoscaler(k) => k.exec(ns, client, phase),
             OAMTrait::Ingress(i) => i.exec(ns, client, phase),
+            OAMTrait::HttpRoute(h) => h.exec(ns, client, phase),
+            OAMTrait::TrafficSplit(t) => t.exec(ns, client, phase),
+            OAMTrait::DisruptionBudget(d) => d.exec(ns, client, phase),
+            OAMTrait::NetworkPolicy(n) => n.exec(ns, client, phase),
+            OAMTrait::MetricsMonitor(m) => m.exec(ns, client, phase),
+            OAMTrait::LogForwarder(l) => l.exec(ns, client, phase),
             OAMTrait::ManualScaler(m) => m.exec(ns, client, phase),
+            OAMTrait::VerticalAutoscaler(v) => v.exec(ns, client, phase),
             OAMTrait::VolumeMounter(v) => v.exec(ns, client, phase),
+            OAMTrait::SecretMounter(s) => s.exec(ns, client, phase),
+            OAMTrait::ConfigMounter(c) => c.exec(ns, client, phase),
+            OAMTrait::Dns(d) => d.exec(ns, client, phase),
+            OAMTrait::Placement(p) => p.exec(ns, client, phase),
+            OAMTrait::Tolerations(t) => t.exec(ns, client, phase),
+            OAMTrait::TopologySpread(t) => t.exec(ns, client, phase),
+            OAMTrait::Sidecar(s) => s.exec(ns, client, phase),
+            OAMTrait::InitContainer(i) => i.exec(ns, client, phase),
+            OAMTrait::ServiceMesh(m) => m.exec(ns, client, phase),
+            OAMTrait::HelmRelease(h) => h.exec(ns, client, phase),
+            OAMTrait::Backup(b) => b.exec(ns, client, phase),
+            OAMTrait::SecurityContext(s) => s.exec(ns, client, phase),
+            OAMTrait::ImagePullSecret(p) => p.exec(ns, client, phase),
+            OAMTrait::Priority(p) => p.exec(ns, client, phase),
+            OAMTrait::Defined(d) => d.exec(ns, client, phase),
             OAMTrait::Empty(e) => e.exec(ns, client, phase),
-        }
+        };
+        span.record(&res);
+        res
     }
     pub fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
         match self {
             OAMTrait::Autoscaler(a) => a.status(ns, client),
+            OAMTrait::KedaAutoscaler(k) => k.status(ns, client),
             OAMTrait::Ingress(i) => i.status(ns, client),
+            OAMTrait::HttpRoute(h) => h.status(ns, client),
+            OAMTrait::TrafficSplit(t) => t.status(ns, client),
+            OAMTrait::DisruptionBudget(d) => d.status(ns, client),
+            OAMTrait::NetworkPolicy(n) => n.status(ns, client),
+            OAMTrait::MetricsMonitor(m) => m.status(ns, client),
+            OAMTrait::LogForwarder(l) => l.status(ns, client),
             OAMTrait::ManualScaler(m) => m.status(ns, client),
+            OAMTrait::VerticalAutoscaler(v) => v.status(ns, client),
+            OAMTrait::SecretMounter(s) => s.status(ns, client),
+            OAMTrait::ConfigMounter(c) => c.status(ns, client),
+            OAMTrait::Dns(d) => d.status(ns, client),
+            OAMTrait::Placement(p) => p.status(ns, client),
+            OAMTrait::Tolerations(t) => t.status(ns, client),
+            OAMTrait::TopologySpread(t) => t.status(ns, client),
+            OAMTrait::Sidecar(s) => s.status(ns, client),
+            OAMTrait::InitContainer(i) => i.status(ns, client),
+            OAMTrait::ServiceMesh(m) => m.status(ns, client),
+            OAMTrait::HelmRelease(h) => h.status(ns, client),
+            OAMTrait::Backup(b) => b.status(ns, client),
+            OAMTrait::SecurityContext(s) => s.status(ns, client),
+            OAMTrait::ImagePullSecret(p) => p.status(ns, client),
+            OAMTrait::Priority(p) => p.status(ns, client),
+            OAMTrait::Defined(d) => d.status(ns, client),
             OAMTrait::Empty(e) => e.status(ns, client),
             OAMTrait::VolumeMounter(v) => v.status(ns, client),
         }
     }
+    pub fn render(&self) -> RenderResult {
+        match self {
+            OAMTrait::Autoscaler(a) => a.render(),
+            OAMTrait::KedaAutoscaler(k) => k.render(),
+            OAMTrait::Ingress(i) => i.render(),
+            OAMTrait::HttpRoute(h) => h.render(),
+            OAMTrait::TrafficSplit(t) => t.render(),
+            OAMTrait::DisruptionBudget(d) => d.render(),
+            OAMTrait::NetworkPolicy(n) => n.render(),
+            OAMTrait::MetricsMonitor(m) => m.render(),
+            OAMTrait::LogForwarder(l) => l.render(),
+            OAMTrait::ManualScaler(m) => m.render(),
+            OAMTrait::VerticalAutoscaler(v) => v.render(),
+            OAMTrait::SecretMounter(s) => s.render(),
+            OAMTrait::ConfigMounter(c) => c.render(),
+            OAMTrait::Dns(d) => d.render(),
+            OAMTrait::Placement(p) => p.render(),
+            OAMTrait::Tolerations(t) => t.render(),
+            OAMTrait::TopologySpread(t) => t.render(),
+            OAMTrait::Sidecar(s) => s.render(),
+            OAMTrait::InitContainer(i) => i.render(),
+            OAMTrait::ServiceMesh(m) => m.render(),
+            OAMTrait::HelmRelease(h) => h.render(),
+            OAMTrait::Backup(b) => b.render(),
+            OAMTrait::SecurityContext(s) => s.render(),
+            OAMTrait::ImagePullSecret(p) => p.render(),
+            OAMTrait::Priority(p) => p.render(),
+            OAMTrait::Defined(d) => d.render(),
+            OAMTrait::Empty(e) => e.render(),
+            OAMTrait::VolumeMounter(v) => v.render(),
+        }
+    }
+    pub fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        match self {
+            OAMTrait::Autoscaler(a) => a.pod_patch(),
+            OAMTrait::KedaAutoscaler(k) => k.pod_patch(),
+            OAMTrait::Ingress(i) => i.pod_patch(),
+            OAMTrait::HttpRoute(h) => h.pod_patch(),
+            OAMTrait::TrafficSplit(t) => t.pod_patch(),
+            OAMTrait::DisruptionBudget(d) => d.pod_patch(),
+            OAMTrait::NetworkPolicy(n) => n.pod_patch(),
+            OAMTrait::MetricsMonitor(m) => m.pod_patch(),
+            OAMTrait::LogForwarder(l) => l.pod_patch(),
+            OAMTrait::ManualScaler(m) => m.pod_patch(),
+            OAMTrait::VerticalAutoscaler(v) => v.pod_patch(),
+            OAMTrait::SecretMounter(s) => s.pod_patch(),
+            OAMTrait::ConfigMounter(c) => c.pod_patch(),
+            OAMTrait::Dns(d) => d.pod_patch(),
+            OAMTrait::Placement(p) => p.pod_patch(),
+            OAMTrait::Tolerations(t) => t.pod_patch(),
+            OAMTrait::TopologySpread(t) => t.pod_patch(),
+            OAMTrait::Sidecar(s) => s.pod_patch(),
+            OAMTrait::InitContainer(i) => i.pod_patch(),
+            OAMTrait::ServiceMesh(m) => m.pod_patch(),
+            OAMTrait::HelmRelease(h) => h.pod_patch(),
+            OAMTrait::Backup(b) => b.pod_patch(),
+            OAMTrait::SecurityContext(s) => s.pod_patch(),
+            OAMTrait::ImagePullSecret(p) => p.pod_patch(),
+            OAMTrait::Priority(p) => p.pod_patch(),
+            OAMTrait::Defined(d) => d.pod_patch(),
+            OAMTrait::Empty(e) => e.pod_patch(),
+            OAMTrait::VolumeMounter(v) => v.pod_patch(),
+        }
+    }
 }
 
 /// A TraitImplementation is an implementation of an OAM Trait.
@@ -123,4 +526,17 @@ pub trait TraitImplementation {
     fn status(&self, _ns: &str, _client: APIClient) -> Option<BTreeMap<String, String>> {
         None
     }
+    /// Render returns the Kubernetes resources this trait creates, without creating them.
+    ///
+    /// Traits that only change the resources of the workload create nothing.
+    fn render(&self) -> RenderResult {
+        Ok(vec![])
+    }
+    /// pod_patch is what this trait adds to the pods of the workload, like a sidecar container.
+    ///
+    /// The workload is rendered with it, so the pods start with what the trait adds. Traits that
+    /// only change the pods have nothing to create, their add, modify and delete do nothing.
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(None)
+    }
 }
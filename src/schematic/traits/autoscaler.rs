@@ -1,14 +1,31 @@
+use crate::metrics::TimedRequest;
 use crate::schematic::traits::{util::*, TraitImplementation};
-use crate::workload_type::{SERVER_NAME, TASK_NAME, WORKER_NAME};
-use k8s_openapi::api::autoscaling::v2beta1 as hpa;
-use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use crate::workload_type::{apply, RenderResult, SERVER_NAME, TASK_NAME, WORKER_NAME};
+use kube::api::RawApi;
 use kube::client::APIClient;
 use log::warn;
 use serde_json::map::Map;
+use serde_json::{json, Value};
 use std::collections::BTreeMap;
 
+/// The time zone of schedule windows that don't set one.
+const DEFAULT_TIMEZONE: &str = "Etc/UTC";
+
+/// The metric source types of an autoscaling/v2 HorizontalPodAutoscaler.
+const METRIC_TYPES: &[&str] = &[
+    "Resource",
+    "ContainerResource",
+    "Pods",
+    "Object",
+    "External",
+];
+
 #[derive(Clone, Debug)]
 /// Autoscaler provides autoscaling via a Kubernetes HorizontalPodAutoscaler.
+///
+/// With schedule windows or a `minimum` of 0, which a HorizontalPodAutoscaler can't do, it is
+/// a KEDA ScaledObject instead: the windows are cron triggers, and KEDA scales the component to
+/// zero outside of them.
 pub struct Autoscaler {
     pub name: String,
     pub instance_name: String,
@@ -17,9 +34,30 @@ pub struct Autoscaler {
     pub maximum: Option<i32>,
     pub cpu: Option<i32>,
     pub memory: Option<i32>,
+    /// Metrics in the autoscaling/v2 format, like the Pods, Object and External metrics a queue
+    /// exposes, scaled on next to `cpu` and `memory`.
+    pub metrics: Vec<Value>,
+    /// The scaling behavior in the autoscaling/v2 format: stabilization windows and the policies
+    /// that limit how fast to scale up and down.
+    pub behavior: Option<Value>,
+    /// The windows of time the component runs at least a number of replicas in, see
+    /// `ScheduleWindow`.
+    pub schedules: Option<Value>,
     pub owner_ref: OwnerRefs,
 }
 
+/// ScheduleWindow keeps `replicas` replicas from the `start` to the `end` cron expression, in
+/// `timezone`.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleWindow {
+    pub start: String,
+    pub end: String,
+    pub replicas: i32,
+    /// An IANA time zone, like `Europe/Berlin`, UTC if unset.
+    pub timezone: Option<String>,
+}
+
 impl Autoscaler {
     pub fn from_properties(
         name: String,
@@ -49,64 +87,162 @@ impl Autoscaler {
                 map.get("memory")
                     .and_then(|p| p.as_i64().map(|i64| i64 as i32))
             }),
+            metrics: properties_map
+                .and_then(|map| map.get("metrics"))
+                .and_then(|m| m.as_array())
+                .cloned()
+                .unwrap_or_default(),
+            behavior: properties_map
+                .and_then(|map| map.get("behavior"))
+                .filter(|b| b.is_object())
+                .cloned(),
+            schedules: properties_map
+                .and_then(|map| map.get("schedules"))
+                .filter(|s| !s.is_null())
+                .cloned(),
         }
     }
-    pub fn to_horizontal_pod_autoscaler(&self) -> hpa::HorizontalPodAutoscaler {
-        // TODO: fix this to make it configurable
-        let mut metrics = Vec::new();
 
-        // Add CPU metrics if set
-        self.cpu.and_then(|cpu| {
-            metrics.push(hpa::MetricSpec {
-                type_: "Resource".into(),
-                resource: Some(hpa::ResourceMetricSource {
-                    name: "cpu".to_string(),
-                    target_average_utilization: Some(cpu),
-                    target_average_value: None,
-                }),
-                pods: None,
-                object: None,
-                external: None,
-            });
-            Some(())
+    /// scales_with_keda is true if the autoscaler is a KEDA ScaledObject.
+    pub fn scales_with_keda(&self) -> bool {
+        self.schedules.is_some() || self.minimum == Some(0)
+    }
+
+    fn schedule_windows(&self) -> Result<Vec<ScheduleWindow>, failure::Error> {
+        let windows: Vec<ScheduleWindow> = match self.schedules.as_ref() {
+            Some(schedules) => serde_json::from_value(schedules.clone()).map_err(|e| {
+                format_err!(
+                    "invalid autoscaler schedules of {}: {}",
+                    self.instance_name,
+                    e
+                )
+            })?,
+            None => vec![],
+        };
+        if let Some(window) = windows.iter().find(|w| w.replicas < 1) {
+            return Err(format_err!(
+                "autoscaler schedule from {} of {} has no replicas",
+                window.start,
+                self.instance_name
+            ));
+        }
+        Ok(windows)
+    }
+
+    /// to_scaled_object renders the KEDA ScaledObject of an autoscaler with schedule windows or
+    /// a minimum of 0. CPU and memory are KEDA triggers, other metrics KEDA can't take.
+    pub fn to_scaled_object(&self) -> Result<Value, failure::Error> {
+        if !self.metrics.is_empty() {
+            return Err(format_err!(
+                "autoscaler of {} can't have metrics with schedules or a minimum of 0",
+                self.instance_name
+            ));
+        }
+        let windows = self.schedule_windows()?;
+        if windows.is_empty() {
+            return Err(format_err!(
+                "autoscaler of {} needs schedules to scale to zero",
+                self.instance_name
+            ));
+        }
+        let mut triggers = vec![];
+        for (resource, utilization) in &[("cpu", self.cpu), ("memory", self.memory)] {
+            if let Some(utilization) = utilization {
+                triggers.push(json!({
+                    "type": resource,
+                    "metricType": "Utilization",
+                    "metadata": {"value": utilization.to_string()},
+                }));
+            }
+        }
+        for window in windows {
+            triggers.push(json!({
+                "type": "cron",
+                "metadata": {
+                    "timezone": window.timezone.unwrap_or_else(|| DEFAULT_TIMEZONE.to_string()),
+                    "start": window.start,
+                    "end": window.end,
+                    "desiredReplicas": window.replicas.to_string(),
+                },
+            }));
+        }
+        let minimum = self.minimum.unwrap_or(0);
+        let mut spec = json!({
+            "scaleTargetRef": {"name": self.instance_name},
+            "minReplicaCount": minimum,
+            "maxReplicaCount": self.maximum.unwrap_or(10 + minimum),
+            "triggers": triggers,
         });
+        if let Some(behavior) = self.behavior.as_ref() {
+            spec["advanced"] = json!({"horizontalPodAutoscalerConfig": {"behavior": behavior}});
+        }
+        Ok(json!({
+            "apiVersion": "keda.sh/v1alpha1",
+            "kind": "ScaledObject",
+            "metadata": self.metadata()?,
+            "spec": spec,
+        }))
+    }
 
-        // Add memory metrics if set
-        self.memory.and_then(|mem| {
-            metrics.push(hpa::MetricSpec {
-                type_: "Resource".into(),
-                resource: Some(hpa::ResourceMetricSource {
-                    name: "memory".to_string(),
-                    target_average_utilization: Some(mem),
-                    target_average_value: None,
-                }),
-                pods: None,
-                object: None,
-                external: None,
-            });
-            Some(())
+    fn metadata(&self) -> Result<Value, failure::Error> {
+        let mut metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
         });
+        if let Some(owner_ref) = self.owner_ref.as_ref() {
+            metadata["ownerReferences"] = serde_json::to_value(owner_ref)?;
+        }
+        Ok(metadata)
+    }
 
-        hpa::HorizontalPodAutoscaler {
-            metadata: Some(meta::ObjectMeta {
-                //name: Some(format!("{}-trait-ingress", self.name.clone())),
-                name: Some(self.kube_name()),
-                labels: Some(trait_labels(self.name.clone(), self.instance_name.clone())),
-                owner_references: self.owner_ref.clone(),
-                ..Default::default()
-            }),
-            spec: Some(hpa::HorizontalPodAutoscalerSpec {
-                min_replicas: self.minimum,
-                max_replicas: self.maximum.unwrap_or(10 + self.minimum.unwrap_or(0)),
-                metrics: Some(metrics),
-                scale_target_ref: hpa::CrossVersionObjectReference {
-                    api_version: Some("apps/v1".to_string()),
-                    kind: "Deployment".to_string(),
-                    name: self.instance_name.to_string(),
-                },
-            }),
-            ..Default::default()
+    /// to_horizontal_pod_autoscaler renders the autoscaling/v2 HorizontalPodAutoscaler. The
+    /// autoscaling/v2 types are newer than the Kubernetes API types rudr is built with, so it is
+    /// rendered as JSON.
+    pub fn to_horizontal_pod_autoscaler(&self) -> Result<Value, failure::Error> {
+        let mut metrics = Vec::new();
+
+        // Add CPU metrics if set
+        if let Some(cpu) = self.cpu {
+            metrics.push(resource_metric("cpu", cpu));
+        }
+        // Add memory metrics if set
+        if let Some(mem) = self.memory {
+            metrics.push(resource_metric("memory", mem));
+        }
+        for metric in self.metrics.iter() {
+            match metric["type"].as_str() {
+                Some(t) if METRIC_TYPES.contains(&t) => metrics.push(metric.clone()),
+                _ => {
+                    return Err(format_err!(
+                        "autoscaler metric {} has none of the types {}",
+                        metric,
+                        METRIC_TYPES.join(", ")
+                    ))
+                }
+            }
+        }
+
+        let mut spec = json!({
+            "maxReplicas": self.maximum.unwrap_or(10 + self.minimum.unwrap_or(0)),
+            "metrics": metrics,
+            "scaleTargetRef": {
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "name": self.instance_name,
+            },
+        });
+        if let Some(minimum) = self.minimum {
+            spec["minReplicas"] = json!(minimum);
+        }
+        if let Some(behavior) = self.behavior.as_ref() {
+            spec["behavior"] = behavior.clone();
         }
+        Ok(json!({
+            "apiVersion": "autoscaling/v2",
+            "kind": "HorizontalPodAutoscaler",
+            "metadata": self.metadata()?,
+            "spec": spec,
+        }))
     }
 
     fn kube_name(&self) -> String {
@@ -114,51 +250,131 @@ impl Autoscaler {
     }
 }
 
+/// resource_metric scales on the average `utilization` percent of the resource `name`.
+fn resource_metric(name: &str, utilization: i32) -> Value {
+    json!({
+        "type": "Resource",
+        "resource": {
+            "name": name,
+            "target": {"type": "Utilization", "averageUtilization": utilization},
+        },
+    })
+}
+
+/// The resource of the autoscaling/v2 HorizontalPodAutoscalers in `ns`.
+fn hpa_resource(ns: &str) -> RawApi {
+    RawApi {
+        version: "v2".into(),
+        ..RawApi::v1HorizontalPodAutoscaler()
+    }
+    .within(ns)
+}
+
+/// The resource of the KEDA ScaledObjects in `ns`.
+fn scaled_object_resource(ns: &str) -> RawApi {
+    RawApi::customResource("scaledobjects")
+        .group("keda.sh")
+        .version("v1alpha1")
+        .within(ns)
+}
+
+/// delete_if_present deletes the object `name` of `resource`, unless it isn't there.
+fn delete_if_present(client: &APIClient, resource: &RawApi, name: &str) -> TraitResult {
+    let req = resource.delete(name, &Default::default())?;
+    match client.timed_request::<Value>(req) {
+        Ok(_) => Ok(()),
+        Err(e) => match e.api_error() {
+            Some(ref err) if err.code == 404 => Ok(()),
+            _ => Err(e.into()),
+        },
+    }
+}
+
+impl Autoscaler {
+    fn scaled_object_status(
+        &self,
+        ns: &str,
+        client: APIClient,
+    ) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "scaledobject/".to_string() + self.kube_name().as_str();
+        let scaled_object = match scaled_object_resource(ns)
+            .get(self.kube_name().as_str())
+            .map_err(failure::Error::from)
+            .and_then(|req| Ok(client.timed_request::<Value>(req)?))
+        {
+            Ok(scaled_object) => scaled_object,
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("ScaledObject not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let state = scaled_object["status"]["conditions"]
+            .as_array()
+            .and_then(|conditions| conditions.iter().find(|c| c["type"] == "Ready"))
+            .map(|c| match c["status"].as_str() {
+                Some("True") => "ready".to_string(),
+                _ => c["message"].as_str().unwrap_or("not ready").to_string(),
+            })?;
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
+
 impl TraitImplementation for Autoscaler {
+    fn render(&self) -> RenderResult {
+        if self.scales_with_keda() {
+            return Ok(vec![self.to_scaled_object()?]);
+        }
+        Ok(vec![self.to_horizontal_pod_autoscaler()?])
+    }
     fn add(&self, ns: &str, client: APIClient) -> TraitResult {
-        let scaler = self.to_horizontal_pod_autoscaler();
-        let (req, _) = hpa::HorizontalPodAutoscaler::create_namespaced_horizontal_pod_autoscaler(
-            ns,
+        // The object of the other kind is left from before the properties changed, if at all.
+        if self.scales_with_keda() {
+            let scaler = self.to_scaled_object()?;
+            apply(
+                &client,
+                &scaled_object_resource(ns),
+                self.kube_name().as_str(),
+                &scaler,
+            )?;
+            return delete_if_present(&client, &hpa_resource(ns), self.kube_name().as_str());
+        }
+        let scaler = self.to_horizontal_pod_autoscaler()?;
+        apply(
+            &client,
+            &hpa_resource(ns),
+            self.kube_name().as_str(),
             &scaler,
-            Default::default(),
-        )?;
-
-        // Deserialize into a Value b/c the response from Kubernetes is not
-        // deserializing into an hpa::HorizontalPodAutoscaler correctly.
-        let res = client.request::<serde_json::Value>(req)?;
-        println!(
-            "Autoscaler: {}",
-            serde_json::to_string_pretty(&res).unwrap_or_else(|e| e.to_string())
-        );
-        Ok(())
+        )
+        .map_err(|e| {
+            format_err!(
+                "autoscaler {}: {}, autoscaling/v2 needs Kubernetes 1.23 or later",
+                self.kube_name(),
+                e
+            )
+        })?;
+        delete_if_present(
+            &client,
+            &scaled_object_resource(ns),
+            self.kube_name().as_str(),
+        )
     }
     fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
-        let scaler = self.to_horizontal_pod_autoscaler();
-
-        let values = serde_json::to_value(&scaler)?;
-        let (req, _) = hpa::HorizontalPodAutoscaler::patch_namespaced_horizontal_pod_autoscaler(
-            self.kube_name().as_str(),
-            ns,
-            &meta::Patch::StrategicMerge(values),
-            Default::default(),
-        )?;
-
-        // Deserialize into a Value b/c the response from Kubernetes is not
-        // deserializing into an hpa::HorizontalPodAutoscaler correctly.
-        let res = client.request::<serde_json::Value>(req)?;
-        println!(
-            "Autoscaler modified: {}",
-            serde_json::to_string_pretty(&res).unwrap_or_else(|e| e.to_string())
-        );
-        Ok(())
+        self.add(ns, client)
     }
     fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
-        let (req, _) = hpa::HorizontalPodAutoscaler::delete_namespaced_horizontal_pod_autoscaler(
-            self.kube_name().as_str(),
-            ns,
-            Default::default(),
-        )?;
-        client.request::<serde_json::Value>(req)?;
+        let resource = if self.scales_with_keda() {
+            scaled_object_resource(ns)
+        } else {
+            hpa_resource(ns)
+        };
+        let req = resource.delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<serde_json::Value>(req)?;
         Ok(())
     }
     fn supports_workload_type(name: &str) -> bool {
@@ -166,36 +382,58 @@ impl TraitImplementation for Autoscaler {
         name == SERVER_NAME || name == TASK_NAME || name == WORKER_NAME
     }
     fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        if self.scales_with_keda() {
+            return self.scaled_object_status(ns, client);
+        }
         let mut resource = BTreeMap::new();
         let key = "horizontalpodautoscaler/".to_string() + self.kube_name().as_str();
-        let (req, _) =
-            match hpa::HorizontalPodAutoscaler::read_namespaced_horizontal_pod_autoscaler_status(
-                self.kube_name().as_str(),
-                ns,
-                Default::default(),
-            ) {
-                Ok(req) => req,
-                Err(e) => {
-                    resource.insert(key, e.to_string());
-                    return Some(resource);
+        let req = match hpa_resource(ns).get_status(self.kube_name().as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let resp: Value = match client.timed_request::<Value>(req) {
+            Ok(hpa) => hpa,
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("Autoscaler not found {}. Recreating ...", e.to_string());
+                    self.add(ns, client).unwrap_or(());
                 }
-            };
-        let resp: hpa::HorizontalPodAutoscaler =
-            match client.request::<hpa::HorizontalPodAutoscaler>(req) {
-                Ok(hpa) => hpa,
-                Err(e) => {
-                    if e.to_string().contains("NotFound") {
-                        warn!("Autoscaler not found {}. Recreating ...", e.to_string());
-                        self.add(ns, client).unwrap_or(());
-                    }
-                    resource.insert(key, e.to_string());
-                    return Some(resource);
-                }
-            };
-        if let Some(status) = resp.status {
-            resource.insert(key.clone(), status.current_replicas.to_string());
-            return Some(resource);
-        }
-        None
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let state = hpa_state(&resp)?;
+        resource.insert(key, state);
+        Some(resource)
     }
 }
+
+/// hpa_state is the state of a HorizontalPodAutoscaler: ready with its current replicas once
+/// it can fetch its metrics and scale, the message of the condition that keeps it from either
+/// otherwise. An autoscaler without a status yet has none.
+pub fn hpa_state(hpa: &Value) -> Option<String> {
+    if !hpa["status"].is_object() {
+        return None;
+    }
+    let blocked = hpa["status"]["conditions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|c| {
+            (c["type"] == "AbleToScale" || c["type"] == "ScalingActive") && c["status"] == "False"
+        });
+    Some(match blocked {
+        Some(condition) => condition["message"]
+            .as_str()
+            .or_else(|| condition["reason"].as_str())
+            .unwrap_or("not ready")
+            .to_string(),
+        None => format!(
+            "ready, {} replicas",
+            hpa["status"]["currentReplicas"].as_i64().unwrap_or(0)
+        ),
+    })
+}
@@ -12,15 +12,16 @@ fn test_autoscaler_defaults() {
         memory: None,
         minimum: None,
         maximum: None,
+        metrics: vec![],
+        behavior: None,
+        schedules: None,
         owner_ref: None,
     };
-    let kauto = autoscaler.to_horizontal_pod_autoscaler();
-    assert_eq!(
-        Some("instance-trait-autoscaler".to_string()),
-        kauto.metadata.expect("metadata").name
-    );
-    let spec = kauto.spec.expect("spec");
-    assert_eq!(10, spec.max_replicas);
+    let kauto = autoscaler.to_horizontal_pod_autoscaler().expect("hpa");
+    assert_eq!("autoscaling/v2", kauto["apiVersion"]);
+    assert_eq!("instance-trait-autoscaler", kauto["metadata"]["name"]);
+    let spec = &kauto["spec"];
+    assert_eq!(10, spec["maxReplicas"]);
 }
 
 #[test]
@@ -33,6 +34,8 @@ fn test_autoscaler_v1alpha1_cpu() {
             "minimum": 6,
             "maximum": 7
         })),
+        priority: None,
+        depends_on: None,
     };
 
     let serialized = serde_json::to_string(&autoscaler_alpha1_trait).unwrap();
@@ -47,25 +50,16 @@ fn test_autoscaler_v1alpha1_cpu() {
         prop_map,
         None,
     );
-    let kauto = autoscaler.to_horizontal_pod_autoscaler();
-    assert_eq!(
-        Some("instance-trait-autoscaler".to_string()),
-        kauto.metadata.expect("metadata").name
-    );
-    let spec = kauto.spec.expect("spec");
-    assert_eq!(7, spec.max_replicas);
-    assert_eq!(Some(6), spec.min_replicas);
+    let kauto = autoscaler.to_horizontal_pod_autoscaler().expect("hpa");
+    assert_eq!("autoscaling/v2", kauto["apiVersion"]);
+    assert_eq!("instance-trait-autoscaler", kauto["metadata"]["name"]);
+    let spec = &kauto["spec"];
+    assert_eq!(7, spec["maxReplicas"]);
+    assert_eq!(6, spec["minReplicas"]);
 
     // cpu is added first so index for cpu is 0
-    let metrics = spec.metrics.expect("metrics").clone();
-    assert_eq!(
-        Some(42),
-        metrics[0]
-            .clone()
-            .resource
-            .expect("a resource")
-            .target_average_utilization
-    );
+    let metrics = &spec["metrics"];
+    assert_eq!(42, metrics[0]["resource"]["target"]["averageUtilization"]);
 }
 
 #[test]
@@ -78,6 +72,8 @@ fn test_autoscaler_v1alpha1_memory() {
             "minimum": 6,
             "maximum": 7
         })),
+        priority: None,
+        depends_on: None,
     };
 
     let serialized = serde_json::to_string(&autoscaler_alpha1_trait).unwrap();
@@ -92,25 +88,16 @@ fn test_autoscaler_v1alpha1_memory() {
         prop_map,
         None,
     );
-    let kauto = autoscaler.to_horizontal_pod_autoscaler();
-    assert_eq!(
-        Some("instance-trait-autoscaler".to_string()),
-        kauto.metadata.expect("metadata").name
-    );
-    let spec = kauto.spec.expect("spec");
-    assert_eq!(7, spec.max_replicas);
-    assert_eq!(Some(6), spec.min_replicas);
+    let kauto = autoscaler.to_horizontal_pod_autoscaler().expect("hpa");
+    assert_eq!("autoscaling/v2", kauto["apiVersion"]);
+    assert_eq!("instance-trait-autoscaler", kauto["metadata"]["name"]);
+    let spec = &kauto["spec"];
+    assert_eq!(7, spec["maxReplicas"]);
+    assert_eq!(6, spec["minReplicas"]);
 
     // cpu is added first so index for memory is 1
-    let metrics = spec.metrics.expect("metrics").clone();
-    assert_eq!(
-        Some(50),
-        metrics[0]
-            .clone()
-            .resource
-            .expect("a resource")
-            .target_average_utilization
-    );
+    let metrics = &spec["metrics"];
+    assert_eq!(50, metrics[0]["resource"]["target"]["averageUtilization"]);
 }
 
 #[test]
@@ -124,6 +111,8 @@ fn test_autoscaler_v1alpha1_multi_metrics_resource() {
             "minimum": 6,
             "maximum": 7
         })),
+        priority: None,
+        depends_on: None,
     };
 
     let serialized = serde_json::to_string(&autoscaler_alpha1_trait).unwrap();
@@ -138,33 +127,151 @@ fn test_autoscaler_v1alpha1_multi_metrics_resource() {
         prop_map,
         None,
     );
-    let kauto = autoscaler.to_horizontal_pod_autoscaler();
+    let kauto = autoscaler.to_horizontal_pod_autoscaler().expect("hpa");
+    assert_eq!("autoscaling/v2", kauto["apiVersion"]);
+    assert_eq!("instance-trait-autoscaler", kauto["metadata"]["name"]);
+    let spec = &kauto["spec"];
+    assert_eq!(7, spec["maxReplicas"]);
+    assert_eq!(6, spec["minReplicas"]);
+
+    // cpu is added first so index for cpu is 0
+    let metrics = &spec["metrics"];
+    assert_eq!(42, metrics[0]["resource"]["target"]["averageUtilization"]);
+
+    // cpu is added first so index for memory is 1
+    assert_eq!(50, metrics[1]["resource"]["target"]["averageUtilization"]);
+}
+
+#[test]
+fn test_autoscaler_v2_metrics_behavior() {
+    let properties = json!({
+        "maximum": 20,
+        "metrics": [{
+            "type": "External",
+            "external": {
+                "metric": {"name": "queue_messages_ready", "selector": {"matchLabels": {"queue": "orders"}}},
+                "target": {"type": "AverageValue", "averageValue": "30"}
+            }
+        }],
+        "behavior": {
+            "scaleDown": {
+                "stabilizationWindowSeconds": 300,
+                "policies": [{"type": "Percent", "value": 10, "periodSeconds": 60}]
+            }
+        }
+    });
+    let autoscaler = Autoscaler::from_properties(
+        "release".into(),
+        "instance".into(),
+        "component".into(),
+        properties.as_object(),
+        None,
+    );
+    let kauto = autoscaler.to_horizontal_pod_autoscaler().expect("hpa");
+    let spec = &kauto["spec"];
+    assert!(spec["minReplicas"].is_null());
+    assert_eq!(1, spec["metrics"].as_array().expect("metrics").len());
+    assert_eq!("External", spec["metrics"][0]["type"]);
     assert_eq!(
-        Some("instance-trait-autoscaler".to_string()),
-        kauto.metadata.expect("metadata").name
+        300,
+        spec["behavior"]["scaleDown"]["stabilizationWindowSeconds"]
     );
-    let spec = kauto.spec.expect("spec");
-    assert_eq!(7, spec.max_replicas);
-    assert_eq!(Some(6), spec.min_replicas);
 
-    // cpu is added first so index for cpu is 0
-    let metrics = spec.metrics.expect("metrics").clone();
+    let properties = json!({"metrics": [{"type": "Queue"}]});
+    let autoscaler = Autoscaler::from_properties(
+        "release".into(),
+        "instance".into(),
+        "component".into(),
+        properties.as_object(),
+        None,
+    );
+    assert!(autoscaler.to_horizontal_pod_autoscaler().is_err());
+}
+
+#[test]
+fn test_hpa_state() {
+    assert_eq!(None, hpa_state(&json!({"spec": {}})));
+    let hpa = json!({"status": {
+        "currentReplicas": 3,
+        "conditions": [
+            {"type": "AbleToScale", "status": "True"},
+            {"type": "ScalingActive", "status": "True"}
+        ]
+    }});
+    assert_eq!(Some("ready, 3 replicas".to_string()), hpa_state(&hpa));
+    let hpa = json!({"status": {
+        "currentReplicas": 1,
+        "conditions": [{
+            "type": "ScalingActive",
+            "status": "False",
+            "reason": "FailedGetResourceMetric",
+            "message": "the HPA was unable to compute the replica count"
+        }]
+    }});
     assert_eq!(
-        Some(42),
-        metrics[0]
-            .clone()
-            .resource
-            .expect("a resource")
-            .target_average_utilization
+        Some("the HPA was unable to compute the replica count".to_string()),
+        hpa_state(&hpa)
     );
+}
 
-    // cpu is added first so index for memory is 1
+#[test]
+fn test_autoscaler_schedules() {
+    let autoscaler = Autoscaler::from_properties(
+        "release".into(),
+        "instance".into(),
+        "component".into(),
+        json!({
+            "minimum": 0,
+            "maximum": 5,
+            "cpu": 70,
+            "schedules": [
+                {"start": "0 8 * * 1-5", "end": "0 20 * * 1-5", "replicas": 2, "timezone": "Europe/Berlin"}
+            ]
+        })
+        .as_object(),
+        None,
+    );
+    assert!(autoscaler.scales_with_keda());
+    let objects = autoscaler.render().expect("render");
+    assert_eq!(1, objects.len());
+    assert_eq!("ScaledObject", objects[0]["kind"]);
+    assert_eq!("instance-trait-autoscaler", objects[0]["metadata"]["name"]);
     assert_eq!(
-        Some(50),
-        metrics[1]
-            .clone()
-            .resource
-            .expect("a resource")
-            .target_average_utilization
+        json!({
+            "scaleTargetRef": {"name": "instance"},
+            "minReplicaCount": 0,
+            "maxReplicaCount": 5,
+            "triggers": [
+                {"type": "cpu", "metricType": "Utilization", "metadata": {"value": "70"}},
+                {"type": "cron", "metadata": {
+                    "timezone": "Europe/Berlin",
+                    "start": "0 8 * * 1-5",
+                    "end": "0 20 * * 1-5",
+                    "desiredReplicas": "2"
+                }}
+            ]
+        }),
+        objects[0]["spec"]
+    );
+
+    let to_zero = Autoscaler::from_properties(
+        "a".into(),
+        "b".into(),
+        "c".into(),
+        json!({"minimum": 0}).as_object(),
+        None,
+    );
+    assert!(to_zero.render().is_err());
+    let with_metrics = Autoscaler::from_properties(
+        "a".into(),
+        "b".into(),
+        "c".into(),
+        json!({
+            "schedules": [{"start": "0 8 * * *", "end": "0 9 * * *", "replicas": 1}],
+            "metrics": [{"type": "Pods"}]
+        })
+        .as_object(),
+        None,
     );
+    assert!(with_metrics.render().is_err());
 }
@@ -0,0 +1,300 @@
+use crate::instigator::resource_api;
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, PodPatch, RenderResult};
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The method that takes VolumeSnapshots of the PVCs with a CronJob.
+pub const SNAPSHOT_METHOD: &str = "snapshot";
+/// The method that has Velero back up the volumes of the pods.
+pub const VELERO_METHOD: &str = "velero";
+
+/// The number of snapshots of each volume that are kept, if unset.
+const DEFAULT_RETENTION: u64 = 7;
+/// The image of the CronJob that takes the snapshots.
+const KUBECTL_IMAGE: &str = "bitnami/kubectl:1.28";
+/// The label of a snapshot with the volume it is a snapshot of.
+pub const VOLUME_LABEL: &str = "oam.dev/volume-name";
+
+/// A Backup backs up the volumes a component mounts with the volume-mounter trait.
+///
+/// With the `snapshot` method, a CronJob takes a VolumeSnapshot of every PVC on `schedule`, and
+/// deletes all but the `retention` latest ones. The trait creates the CronJob, and the
+/// ServiceAccount and Role it runs with. The snapshots stay when the trait is deleted.
+///
+/// With the `velero` method, the pods are annotated for the file system backup of
+/// [Velero](https://velero.io), whose own schedules take and expire the backups.
+#[derive(Clone, Debug)]
+pub struct Backup {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The names of the volumes to back up, the `volumeName`s of volume-mounter traits.
+    pub volumes: Vec<String>,
+    /// `snapshot` or `velero`, `snapshot` if unset.
+    pub method: Option<String>,
+    /// The cron schedule of the snapshots.
+    pub schedule: Option<String>,
+    /// The number of snapshots of each volume that are kept.
+    pub retention: Option<u64>,
+    /// The VolumeSnapshotClass of the snapshots, the default one if unset.
+    pub volume_snapshot_class: Option<String>,
+    pub owner_ref: OwnerRefs,
+}
+
+impl Backup {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        Backup {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            volumes: backup_volumes(properties_map),
+            method: string("method"),
+            schedule: string("schedule"),
+            retention: properties_map.and_then(|map| map.get("retention").and_then(|p| p.as_u64())),
+            volume_snapshot_class: string("volumeSnapshotClassName"),
+        }
+    }
+
+    /// method is the method of the trait, failing unless it is a known one with the
+    /// properties it needs.
+    fn method(&self) -> Result<&str, failure::Error> {
+        if self.volumes.is_empty() {
+            return Err(format_err!(
+                "backup of {} has no volumes",
+                self.instance_name
+            ));
+        }
+        match self.method.as_deref().unwrap_or(SNAPSHOT_METHOD) {
+            SNAPSHOT_METHOD => {
+                if self.schedule.is_none() {
+                    return Err(format_err!(
+                        "backup of {} has no schedule",
+                        self.instance_name
+                    ));
+                }
+                if self.retention == Some(0) {
+                    return Err(format_err!(
+                        "backup of {} keeps no snapshots",
+                        self.instance_name
+                    ));
+                }
+                Ok(SNAPSHOT_METHOD)
+            }
+            VELERO_METHOD => {
+                if self.schedule.is_some()
+                    || self.retention.is_some()
+                    || self.volume_snapshot_class.is_some()
+                {
+                    return Err(format_err!(
+                        "backup of {} has snapshot properties but the velero method, whose schedules are Velero's",
+                        self.instance_name
+                    ));
+                }
+                Ok(VELERO_METHOD)
+            }
+            other => Err(format_err!(
+                "backup method {} is neither {} nor {}",
+                other,
+                SNAPSHOT_METHOD,
+                VELERO_METHOD
+            )),
+        }
+    }
+
+    /// to_volume_snapshot renders the VolumeSnapshot of the PVC `volume` the CronJob creates,
+    /// which the API server names after it.
+    pub fn to_volume_snapshot(&self, volume: &str) -> Value {
+        let mut labels = trait_labels(self.name.clone(), self.instance_name.clone());
+        labels.insert(VOLUME_LABEL.to_string(), volume.to_string());
+        let mut spec = json!({"source": {"persistentVolumeClaimName": volume}});
+        if let Some(class) = self.volume_snapshot_class.as_ref() {
+            spec["volumeSnapshotClassName"] = json!(class);
+        }
+        json!({
+            "apiVersion": "snapshot.storage.k8s.io/v1",
+            "kind": "VolumeSnapshot",
+            "metadata": {
+                "generateName": format!("{}-", volume),
+                "labels": labels,
+            },
+            "spec": spec,
+        })
+    }
+
+    /// script is the shell script of the CronJob: it takes a snapshot of every volume, then
+    /// deletes the oldest ones beyond the retention.
+    fn script(&self) -> String {
+        let retention = self.retention.unwrap_or(DEFAULT_RETENTION);
+        let mut script = "set -e\n".to_string();
+        for volume in self.volumes.iter() {
+            script += &format!(
+                "echo '{}' | kubectl create -f -\n",
+                self.to_volume_snapshot(volume)
+            );
+            script += &format!(
+                "kubectl get volumesnapshots -l oam.dev/instance-name={},{}={} \
+                 --sort-by=.metadata.creationTimestamp -o name \
+                 | head -n -{} | xargs -r kubectl delete\n",
+                self.instance_name, VOLUME_LABEL, volume, retention
+            );
+        }
+        script
+    }
+
+    /// to_objects renders the CronJob of the snapshots, and the ServiceAccount, Role and
+    /// RoleBinding it runs with. There are none with the velero method.
+    pub fn to_objects(&self) -> Result<Vec<Value>, failure::Error> {
+        if self.method()? != SNAPSHOT_METHOD {
+            return Ok(vec![]);
+        }
+        let metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+            "ownerReferences": self.owner_ref,
+        });
+        Ok(vec![
+            json!({
+                "apiVersion": "v1",
+                "kind": "ServiceAccount",
+                "metadata": metadata,
+            }),
+            json!({
+                "apiVersion": "rbac.authorization.k8s.io/v1",
+                "kind": "Role",
+                "metadata": metadata,
+                "rules": [{
+                    "apiGroups": ["snapshot.storage.k8s.io"],
+                    "resources": ["volumesnapshots"],
+                    "verbs": ["create", "list", "delete"],
+                }],
+            }),
+            json!({
+                "apiVersion": "rbac.authorization.k8s.io/v1",
+                "kind": "RoleBinding",
+                "metadata": metadata,
+                "roleRef": {
+                    "apiGroup": "rbac.authorization.k8s.io",
+                    "kind": "Role",
+                    "name": self.kube_name(),
+                },
+                "subjects": [{"kind": "ServiceAccount", "name": self.kube_name()}],
+            }),
+            json!({
+                "apiVersion": "batch/v1",
+                "kind": "CronJob",
+                "metadata": metadata,
+                "spec": {
+                    "schedule": self.schedule,
+                    "concurrencyPolicy": "Forbid",
+                    "jobTemplate": {"spec": {
+                        "backoffLimit": 2,
+                        "template": {"spec": {
+                            "serviceAccountName": self.kube_name(),
+                            "restartPolicy": "OnFailure",
+                            "containers": [{
+                                "name": "backup",
+                                "image": KUBECTL_IMAGE,
+                                "command": ["/bin/sh", "-c", self.script()],
+                            }],
+                        }},
+                    }},
+                },
+            }),
+        ])
+    }
+
+    /// to_pod_patch is the annotation that has Velero back up the volumes of the pods.
+    pub fn to_pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        if self.method()? != VELERO_METHOD {
+            return Ok(None);
+        }
+        let mut patch = PodPatch::default();
+        patch.annotations.insert(
+            "backup.velero.io/backup-volumes".to_string(),
+            self.volumes.join(","),
+        );
+        Ok(Some(patch))
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-backup", self.instance_name)
+    }
+}
+
+/// backup_volumes are the names of the volumes the properties of a backup trait back up.
+pub fn backup_volumes(properties_map: Option<&Map<String, Value>>) -> Vec<String> {
+    properties_map
+        .and_then(|map| map.get("volumes"))
+        .and_then(|v| v.as_array())
+        .map(|v| {
+            v.iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl TraitImplementation for Backup {
+    fn render(&self) -> RenderResult {
+        self.to_objects()
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        for object in self.to_objects()? {
+            let resource = resource_api(&object, ns)?;
+            apply(&client, &resource, self.kube_name().as_str(), &object)?;
+        }
+        Ok(())
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        for object in self.to_objects()?.iter().rev() {
+            let req =
+                resource_api(object, ns)?.delete(self.kube_name().as_str(), &Default::default())?;
+            client.timed_request::<Value>(req)?;
+        }
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        self.to_pod_patch()
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let cron_job = self.to_objects().ok()?.pop()?;
+        let mut resource = BTreeMap::new();
+        let key = "cronjob/".to_string() + self.kube_name().as_str();
+        let state = match resource_api(&cron_job, ns)
+            .and_then(|api| Ok(api.get(self.kube_name().as_str())?))
+            .and_then(|req| Ok(client.timed_request::<Value>(req)?))
+        {
+            Ok(current) => match current["status"]["lastSuccessfulTime"].as_str() {
+                Some(time) => format!("ready, last backup at {}", time),
+                None => "ready".to_string(),
+            },
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("Backup CronJob not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                e.to_string()
+            }
+        };
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
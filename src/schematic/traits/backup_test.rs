@@ -0,0 +1,357 @@
+use crate::schematic::traits::backup::{SNAPSHOT_METHOD, VELERO_METHOD, VOLUME_LABEL};
+use crate::schematic::traits::*;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_backup_snapshot_objects() {
+    let backup = Backup {
+        name: "my-app".into(),
+        instance_name: "db".into(),
+        component_name: "postgres".into(),
+        volumes: vec!["data".into()],
+        method: None,
+        schedule: Some("0 3 * * *".into()),
+        retention: None,
+        volume_snapshot_class: None,
+        owner_ref: None,
+    };
+    let objects = backup.render().expect("render");
+    let kinds: Vec<&str> = objects.iter().filter_map(|o| o["kind"].as_str()).collect();
+    assert_eq!(
+        vec!["ServiceAccount", "Role", "RoleBinding", "CronJob"],
+        kinds
+    );
+    for object in objects.iter() {
+        assert_eq!("db-trait-backup", object["metadata"]["name"]);
+        assert!(object["metadata"]["ownerReferences"].is_null());
+    }
+    // The CronJob may only take and delete snapshots.
+    assert_eq!(
+        json!([{
+            "apiGroups": ["snapshot.storage.k8s.io"],
+            "resources": ["volumesnapshots"],
+            "verbs": ["create", "list", "delete"]
+        }]),
+        objects[1]["rules"]
+    );
+    assert_eq!(
+        json!({"apiGroup": "rbac.authorization.k8s.io", "kind": "Role", "name": "db-trait-backup"}),
+        objects[2]["roleRef"]
+    );
+    assert_eq!(
+        json!([{"kind": "ServiceAccount", "name": "db-trait-backup"}]),
+        objects[2]["subjects"]
+    );
+
+    let cron_job = &objects[3]["spec"];
+    assert_eq!("0 3 * * *", cron_job["schedule"]);
+    assert_eq!("Forbid", cron_job["concurrencyPolicy"]);
+    let pod = &cron_job["jobTemplate"]["spec"]["template"]["spec"];
+    assert_eq!("db-trait-backup", pod["serviceAccountName"]);
+    assert_eq!("OnFailure", pod["restartPolicy"]);
+    assert_eq!("bitnami/kubectl:1.28", pod["containers"][0]["image"]);
+    // Snapshots are what the CronJob is for, the pods are left alone.
+    assert!(backup.pod_patch().expect("pod patch").is_none());
+}
+
+#[test]
+fn test_backup_snapshot_script() {
+    let backup = Backup {
+        name: "my-app".into(),
+        instance_name: "db".into(),
+        component_name: "postgres".into(),
+        volumes: vec!["data".into(), "wal".into()],
+        method: None,
+        schedule: Some("0 3 * * *".into()),
+        retention: Some(3),
+        volume_snapshot_class: None,
+        owner_ref: None,
+    };
+    let objects = backup.render().expect("render");
+    let pod = &objects[3]["spec"]["jobTemplate"]["spec"]["template"]["spec"];
+    let command = &pod["containers"][0]["command"];
+    assert_eq!("/bin/sh", command[0]);
+    let script = command[2].as_str().expect("script");
+    assert!(script.starts_with("set -e\n"));
+    for volume in &["data", "wal"] {
+        assert!(script.contains(&format!(
+            "echo '{}' | kubectl create -f -\n",
+            backup.to_volume_snapshot(volume)
+        )));
+        // Only the snapshots of the volume of the instance count against its retention.
+        assert!(script.contains(&format!(
+            "kubectl get volumesnapshots -l oam.dev/instance-name=db,{}={} ",
+            VOLUME_LABEL, volume
+        )));
+    }
+    assert!(script.contains("| head -n -3 | xargs -r kubectl delete\n"));
+
+    // A week of daily snapshots is kept unless the retention says otherwise.
+    let backup = Backup {
+        name: "my-app".into(),
+        instance_name: "db".into(),
+        component_name: "postgres".into(),
+        volumes: vec!["data".into()],
+        method: None,
+        schedule: Some("0 3 * * *".into()),
+        retention: None,
+        volume_snapshot_class: None,
+        owner_ref: None,
+    };
+    let objects = backup.render().expect("render");
+    let pod = &objects[3]["spec"]["jobTemplate"]["spec"]["template"]["spec"];
+    let script = pod["containers"][0]["command"][2].as_str().expect("script");
+    assert!(script.contains("| head -n -7 |"));
+}
+
+#[test]
+fn test_backup_volume_snapshot() {
+    let backup = Backup {
+        name: "my-app".into(),
+        instance_name: "db".into(),
+        component_name: "postgres".into(),
+        volumes: vec!["data".into()],
+        method: None,
+        schedule: Some("0 3 * * *".into()),
+        retention: None,
+        volume_snapshot_class: None,
+        owner_ref: None,
+    };
+    let snapshot = backup.to_volume_snapshot("data");
+    assert_eq!("snapshot.storage.k8s.io/v1", snapshot["apiVersion"]);
+    assert_eq!("VolumeSnapshot", snapshot["kind"]);
+    // The API server names the snapshots after the volume.
+    assert!(snapshot["metadata"]["name"].is_null());
+    assert_eq!("data-", snapshot["metadata"]["generateName"]);
+    assert_eq!(
+        json!({
+            "app.kubernetes.io/name": "my-app",
+            "oam.dev/instance-name": "db",
+            "oam.dev/role": "trait",
+            "oam.dev/volume-name": "data"
+        }),
+        snapshot["metadata"]["labels"]
+    );
+    assert_eq!(
+        json!({"source": {"persistentVolumeClaimName": "data"}}),
+        snapshot["spec"]
+    );
+
+    let backup = Backup {
+        name: "my-app".into(),
+        instance_name: "db".into(),
+        component_name: "postgres".into(),
+        volumes: vec!["data".into()],
+        method: None,
+        schedule: Some("0 3 * * *".into()),
+        retention: None,
+        volume_snapshot_class: Some("csi-snapclass".into()),
+        owner_ref: None,
+    };
+    assert_eq!(
+        "csi-snapclass",
+        backup.to_volume_snapshot("data")["spec"]["volumeSnapshotClassName"]
+    );
+}
+
+#[test]
+fn test_backup_velero() {
+    let backup = Backup {
+        name: "my-app".into(),
+        instance_name: "db".into(),
+        component_name: "postgres".into(),
+        volumes: vec!["data".into(), "wal".into()],
+        method: Some(VELERO_METHOD.into()),
+        schedule: None,
+        retention: None,
+        volume_snapshot_class: None,
+        owner_ref: None,
+    };
+    // Velero's own schedules take the backups, the trait creates nothing.
+    assert!(backup.render().expect("render").is_empty());
+    let patch = backup
+        .pod_patch()
+        .expect("pod patch")
+        .expect("velero patch");
+    assert_eq!(1, patch.annotations.len());
+    assert_eq!(
+        "data,wal",
+        patch.annotations["backup.velero.io/backup-volumes"]
+    );
+}
+
+#[test]
+fn test_backup_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "postgres-db".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let backup = Backup {
+        name: "my-app".into(),
+        instance_name: "db".into(),
+        component_name: "postgres".into(),
+        volumes: vec!["data".into()],
+        method: None,
+        schedule: Some("0 3 * * *".into()),
+        retention: None,
+        volume_snapshot_class: None,
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    for object in backup.render().expect("render") {
+        assert_eq!(
+            serde_json::to_value(vec![owner.clone()]).unwrap(),
+            object["metadata"]["ownerReferences"]
+        );
+    }
+    // The snapshots outlive the trait.
+    assert!(backup.to_volume_snapshot("data")["metadata"]["ownerReferences"].is_null());
+}
+
+#[test]
+fn test_backup_invalid() {
+    let invalid = |backup: Backup| backup.render().unwrap_err().to_string();
+    assert_eq!(
+        "backup of db has no volumes",
+        invalid(Backup {
+            name: "my-app".into(),
+            instance_name: "db".into(),
+            component_name: "postgres".into(),
+            volumes: vec![],
+            method: None,
+            schedule: Some("0 3 * * *".into()),
+            retention: None,
+            volume_snapshot_class: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "backup of db has no schedule",
+        invalid(Backup {
+            name: "my-app".into(),
+            instance_name: "db".into(),
+            component_name: "postgres".into(),
+            volumes: vec!["data".into()],
+            method: None,
+            schedule: None,
+            retention: None,
+            volume_snapshot_class: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "backup of db keeps no snapshots",
+        invalid(Backup {
+            name: "my-app".into(),
+            instance_name: "db".into(),
+            component_name: "postgres".into(),
+            volumes: vec!["data".into()],
+            method: None,
+            schedule: Some("0 3 * * *".into()),
+            retention: Some(0),
+            volume_snapshot_class: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "backup method restic is neither snapshot nor velero",
+        invalid(Backup {
+            name: "my-app".into(),
+            instance_name: "db".into(),
+            component_name: "postgres".into(),
+            volumes: vec!["data".into()],
+            method: Some("restic".into()),
+            schedule: Some("0 3 * * *".into()),
+            retention: None,
+            volume_snapshot_class: None,
+            owner_ref: None,
+        })
+    );
+    let velero = Backup {
+        name: "my-app".into(),
+        instance_name: "db".into(),
+        component_name: "postgres".into(),
+        volumes: vec!["data".into()],
+        method: Some(VELERO_METHOD.into()),
+        schedule: None,
+        retention: None,
+        volume_snapshot_class: None,
+        owner_ref: None,
+    };
+    for backup in &[
+        Backup {
+            schedule: Some("@daily".into()),
+            ..velero.clone()
+        },
+        Backup {
+            retention: Some(2),
+            ..velero.clone()
+        },
+        Backup {
+            volume_snapshot_class: Some("csi-snapclass".into()),
+            ..velero.clone()
+        },
+    ] {
+        assert_eq!(
+            "backup of db has snapshot properties but the velero method, whose schedules are \
+             Velero's",
+            backup.pod_patch().unwrap_err().to_string()
+        );
+    }
+}
+
+#[test]
+fn test_backup_v1alpha1() {
+    let backup_alpha1_trait = TraitBinding {
+        name: String::from("backup"),
+        parameter_values: None,
+        properties: Some(json!({
+            "volumes": ["data", 1, "wal"],
+            "method": "snapshot",
+            "schedule": "@daily",
+            "retention": 14,
+            "volumeSnapshotClassName": "csi-snapclass"
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&backup_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let backup = Backup::from_properties(
+        "my-app".into(),
+        "db".into(),
+        "postgres".into(),
+        prop_map,
+        None,
+    );
+    // Volumes that aren't names are skipped.
+    assert_eq!(vec!["data".to_string(), "wal".to_string()], backup.volumes);
+    assert_eq!(Some(SNAPSHOT_METHOD.to_string()), backup.method);
+    assert_eq!(Some("@daily".to_string()), backup.schedule);
+    assert_eq!(Some(14), backup.retention);
+    assert_eq!(
+        Some("csi-snapclass".to_string()),
+        backup.volume_snapshot_class
+    );
+    assert_eq!(4, backup.render().expect("render").len());
+
+    // A negative retention isn't a number of snapshots.
+    let properties = json!({"volumes": ["data"], "retention": -1});
+    let backup = Backup::from_properties(
+        "my-app".into(),
+        "db".into(),
+        "postgres".into(),
+        properties.as_object(),
+        None,
+    );
+    assert_eq!(None, backup.retention);
+}
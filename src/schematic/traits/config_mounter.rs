@@ -0,0 +1,191 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::schematic::variable::substitute_variables;
+use crate::workload_type::{apply, PodPatch, RenderResult};
+use k8s_openapi::api::core::v1 as core;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The name of the volume of the ConfigMap.
+const CONFIG_VOLUME: &str = "config-mounter";
+
+/// A ConfigMounter writes files into the containers of a component, from contents in its
+/// properties that may reference the variables of the ApplicationConfiguration.
+///
+/// The files are the keys of a ConfigMap the trait creates, mounted in `mount_path`.
+#[derive(Clone, Debug)]
+pub struct ConfigMounter {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The contents of the files, by file name.
+    pub files: Option<Value>,
+    /// The directory to mount the files in.
+    pub mount_path: Option<String>,
+    /// The values of the variables of the configuration, which `[fromVariable(NAME)]` in the
+    /// contents is replaced with.
+    pub variables: BTreeMap<String, Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+impl ConfigMounter {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+        variables: BTreeMap<String, Value>,
+    ) -> Self {
+        ConfigMounter {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            variables,
+            files: properties_map.and_then(|map| map.get("files")).cloned(),
+            mount_path: properties_map
+                .and_then(|map| map.get("mountPath").and_then(|p| p.as_str()))
+                .map(String::from),
+        }
+    }
+
+    /// files are the contents of the files with the variables they reference substituted.
+    pub fn files(&self) -> Result<BTreeMap<String, String>, failure::Error> {
+        let files = match self.files.as_ref().and_then(|f| f.as_object()) {
+            Some(files) if !files.is_empty() => files,
+            _ => {
+                return Err(format_err!(
+                    "config-mounter of {} has no files",
+                    self.instance_name
+                ))
+            }
+        };
+        files
+            .iter()
+            .map(|(file, content)| {
+                let content = content.as_str().ok_or_else(|| {
+                    format_err!(
+                        "config-mounter file {} of {} isn't a string",
+                        file,
+                        self.instance_name
+                    )
+                })?;
+                let content = substitute_variables(content, &self.variables).map_err(|e| {
+                    format_err!(
+                        "config-mounter file {} of {}: {}",
+                        file,
+                        self.instance_name,
+                        e
+                    )
+                })?;
+                Ok((file.clone(), content))
+            })
+            .collect()
+    }
+
+    /// to_config_map renders the ConfigMap of the files.
+    pub fn to_config_map(&self) -> Result<core::ConfigMap, failure::Error> {
+        Ok(core::ConfigMap {
+            metadata: Some(meta::ObjectMeta {
+                name: Some(self.kube_name()),
+                labels: Some(trait_labels(self.name.clone(), self.instance_name.clone())),
+                owner_references: self.owner_ref.clone(),
+                ..Default::default()
+            }),
+            data: Some(self.files()?),
+            ..Default::default()
+        })
+    }
+
+    /// to_pod_patch is the volume of the ConfigMap, mounted in the containers of the component.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        let mount_path = self.mount_path.clone().ok_or_else(|| {
+            format_err!("config-mounter of {} has no mountPath", self.instance_name)
+        })?;
+        Ok(PodPatch {
+            volumes: vec![core::Volume {
+                name: CONFIG_VOLUME.to_string(),
+                config_map: Some(core::ConfigMapVolumeSource {
+                    name: Some(self.kube_name()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            volume_mounts: vec![core::VolumeMount {
+                name: CONFIG_VOLUME.to_string(),
+                mount_path,
+                read_only: Some(true),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-config-mounter", self.instance_name)
+    }
+}
+
+impl TraitImplementation for ConfigMounter {
+    /// The ConfigMap is created before the pods that mount it.
+    fn pre_add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let config_map = self.to_config_map()?;
+        let resource = RawApi::v1ConfigMap().within(ns);
+        apply(&client, &resource, self.kube_name().as_str(), &config_map)
+    }
+    fn pre_modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.pre_add(ns, client)
+    }
+    /// There is nothing left to do once the workload mounts the ConfigMap.
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let req = RawApi::v1ConfigMap()
+            .within(ns)
+            .delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+    fn render(&self) -> RenderResult {
+        Ok(vec![serde_json::to_value(self.to_config_map()?)?])
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "configmap/".to_string() + self.kube_name().as_str();
+        let req = match RawApi::v1ConfigMap()
+            .within(ns)
+            .get(self.kube_name().as_str())
+        {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let state = match client.timed_request::<Value>(req) {
+            Ok(_) => "created".to_string(),
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("Config mounter ConfigMap not found {}. Recreating ...", e);
+                    self.pre_add(ns, client).unwrap_or(());
+                }
+                e.to_string()
+            }
+        };
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
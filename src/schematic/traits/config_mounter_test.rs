@@ -0,0 +1,229 @@
+use crate::schematic::traits::*;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_config_mounter_config_map() {
+    let config_mounter = ConfigMounter {
+        name: "my-config".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        files: Some(json!({"motd": "hello"})),
+        mount_path: Some("/etc/squid".into()),
+        variables: BTreeMap::new(),
+        owner_ref: None,
+    };
+    let config_map = config_mounter.to_config_map().expect("config map");
+    let metadata = config_map.metadata.expect("metadata");
+    assert_eq!(
+        Some("squid-trait-config-mounter".to_string()),
+        metadata.name
+    );
+    assert_eq!(None, metadata.owner_references);
+    assert_eq!(
+        Some(&"squid".to_string()),
+        metadata
+            .labels
+            .as_ref()
+            .and_then(|l| l.get("oam.dev/instance-name"))
+    );
+    assert_eq!("hello", config_map.data.expect("data")["motd"]);
+}
+
+#[test]
+fn test_config_mounter_variables() {
+    let mut variables = BTreeMap::new();
+    variables.insert("host".to_string(), json!("db.local"));
+    variables.insert("replicas".to_string(), json!(3));
+    let mounter = ConfigMounter {
+        name: "my-config".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        files: Some(json!({
+            "app.yaml": "database: [fromVariable(host)]\nreplicas: [fromVariable(replicas)]\n",
+            "url": "postgres://[fromVariable(host)]:5432/[fromVariable(host)]"
+        })),
+        mount_path: Some("/etc/squid".into()),
+        variables: variables.clone(),
+        owner_ref: None,
+    };
+    let files = mounter.files().expect("files");
+    // Strings are substituted as they are, other values as their JSON.
+    assert_eq!("database: db.local\nreplicas: 3\n", files["app.yaml"]);
+    assert_eq!("postgres://db.local:5432/db.local", files["url"]);
+
+    // Contents without references are left alone, brackets and all.
+    let mounter = ConfigMounter {
+        name: "my-config".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        files: Some(json!({"list": "[a, b]", "ref": "[fromVariable(no-word)]"})),
+        mount_path: Some("/etc/squid".into()),
+        variables,
+        owner_ref: None,
+    };
+    let files = mounter.files().expect("files");
+    assert_eq!("[a, b]", files["list"]);
+    assert_eq!("[fromVariable(no-word)]", files["ref"]);
+}
+
+#[test]
+fn test_config_mounter_pod_patch() {
+    let config_mounter = ConfigMounter {
+        name: "my-config".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        files: Some(json!({"motd": "hello"})),
+        mount_path: Some("/etc/squid".into()),
+        variables: BTreeMap::new(),
+        owner_ref: None,
+    };
+    let patch = config_mounter.to_pod_patch().expect("pod patch");
+    assert_eq!("config-mounter", patch.volumes[0].name);
+    assert_eq!(
+        Some("squid-trait-config-mounter".to_string()),
+        patch.volumes[0]
+            .config_map
+            .as_ref()
+            .and_then(|c| c.name.clone())
+    );
+    assert_eq!("config-mounter", patch.volume_mounts[0].name);
+    assert_eq!("/etc/squid", patch.volume_mounts[0].mount_path);
+    assert_eq!(Some(true), patch.volume_mounts[0].read_only);
+    assert!(patch.env.is_empty());
+    assert!(patch.sidecars.is_empty());
+}
+
+#[test]
+fn test_config_mounter_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let mounter = ConfigMounter {
+        name: "my-config".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        files: Some(json!({"motd": "hello"})),
+        mount_path: Some("/etc/squid".into()),
+        variables: BTreeMap::new(),
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    let metadata = mounter
+        .to_config_map()
+        .expect("config map")
+        .metadata
+        .expect("metadata");
+    assert_eq!(Some(vec![owner]), metadata.owner_references);
+}
+
+#[test]
+fn test_config_mounter_invalid() {
+    let invalid = |files: Option<serde_json::Value>| {
+        ConfigMounter {
+            name: "my-config".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            files,
+            mount_path: Some("/etc/squid".into()),
+            variables: BTreeMap::new(),
+            owner_ref: None,
+        }
+        .to_config_map()
+        .unwrap_err()
+        .to_string()
+    };
+    assert_eq!("config-mounter of squid has no files", invalid(None));
+    assert_eq!(
+        "config-mounter of squid has no files",
+        invalid(Some(json!({})))
+    );
+    assert_eq!(
+        "config-mounter of squid has no files",
+        invalid(Some(json!(["motd"])))
+    );
+    assert_eq!(
+        "config-mounter file n of squid isn't a string",
+        invalid(Some(json!({"n": 1})))
+    );
+    assert_eq!(
+        "config-mounter file app.yaml of squid: undefined variable `port`",
+        invalid(Some(json!({"app.yaml": "[fromVariable(port)]"})))
+    );
+
+    let mut variables = BTreeMap::new();
+    variables.insert("host".to_string(), serde_json::Value::Null);
+    let mounter = ConfigMounter {
+        name: "my-config".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        files: Some(json!({"app.yaml": "[fromVariable(host)]"})),
+        mount_path: Some("/etc/squid".into()),
+        variables,
+        owner_ref: None,
+    };
+    assert_eq!(
+        "config-mounter file app.yaml of squid: variable `host` has no value to substitute",
+        mounter.to_config_map().unwrap_err().to_string()
+    );
+
+    let mounter = ConfigMounter {
+        name: "my-config".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        files: Some(json!({"motd": "hello"})),
+        mount_path: None,
+        variables: BTreeMap::new(),
+        owner_ref: None,
+    };
+    assert_eq!(
+        "config-mounter of squid has no mountPath",
+        mounter.to_pod_patch().unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn test_config_mounter_v1alpha1() {
+    let config_mounter_alpha1_trait = TraitBinding {
+        name: String::from("config-mounter"),
+        parameter_values: None,
+        properties: Some(json!({
+            "mountPath": "/etc/squid",
+            "files": {"app.yaml": "database: [fromVariable(host)]\n"}
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&config_mounter_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let mut variables = BTreeMap::new();
+    variables.insert("host".to_string(), json!("db.local"));
+    let mounter = ConfigMounter::from_properties(
+        "my-config".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+        variables,
+    );
+    assert_eq!(Some("/etc/squid".to_string()), mounter.mount_path);
+    // The contents are kept as they are until the ConfigMap is rendered.
+    assert_eq!(
+        Some(json!({"app.yaml": "database: [fromVariable(host)]\n"})),
+        mounter.files
+    );
+    assert_eq!(
+        "database: db.local\n",
+        mounter.files().expect("files")["app.yaml"]
+    );
+}
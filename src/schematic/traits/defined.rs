@@ -0,0 +1,146 @@
+use crate::instigator::resource_api;
+use crate::metrics::TimedRequest;
+use crate::schematic::trait_definition::TraitDefinition;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, RenderResult};
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// A DefinedTrait is a trait of a TraitDefinition, which creates the objects its template
+/// renders with the properties of the binding.
+#[derive(Clone, Debug)]
+pub struct DefinedTrait {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The name of the trait, the name of its TraitDefinition.
+    pub trait_name: String,
+    pub definition: TraitDefinition,
+    pub properties: Option<Map<String, Value>>,
+    pub workload_type: String,
+    pub owner_ref: OwnerRefs,
+}
+
+impl DefinedTrait {
+    /// to_objects renders the objects of the template. Objects without a name are named
+    /// `<instance>-trait-<trait>`, and all of them get the labels and owner of the trait.
+    pub fn to_objects(&self) -> Result<Vec<Value>, failure::Error> {
+        let err = |e: failure::Error| {
+            format_err!("trait {} of {}: {}", self.trait_name, self.instance_name, e)
+        };
+        let properties = self
+            .definition
+            .properties(self.properties.as_ref())
+            .map_err(err)?;
+        let mut context = BTreeMap::new();
+        context.insert("applicationName".to_string(), self.name.clone());
+        context.insert("componentName".to_string(), self.component_name.clone());
+        context.insert("instanceName".to_string(), self.instance_name.clone());
+        context.insert("workloadType".to_string(), self.workload_type.clone());
+        let mut objects = self.definition.render(&properties, &context).map_err(err)?;
+
+        let mut names = vec![];
+        for object in objects.iter_mut() {
+            if !object["metadata"].is_null() && !object["metadata"].is_object() {
+                return Err(err(format_err!("object with invalid metadata")));
+            }
+            if !object["metadata"]["name"].is_string() {
+                object["metadata"]["name"] = json!(self.kube_name());
+            }
+            let labels = trait_labels(self.name.clone(), self.instance_name.clone());
+            for (key, value) in labels {
+                object["metadata"]["labels"][key] = json!(value);
+            }
+            if object["metadata"]["ownerReferences"].is_null() {
+                object["metadata"]["ownerReferences"] = json!(self.owner_ref);
+            }
+            let name = (object["kind"].clone(), object["metadata"]["name"].clone());
+            if names.contains(&name) {
+                return Err(err(format_err!(
+                    "two objects are the {} {}",
+                    name.0,
+                    name.1
+                )));
+            }
+            names.push(name);
+        }
+        Ok(objects)
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-{}", self.instance_name, self.trait_name)
+    }
+}
+
+/// object_name is the name of a rendered object.
+fn object_name(object: &Value) -> &str {
+    object["metadata"]["name"].as_str().unwrap_or_default()
+}
+
+impl TraitImplementation for DefinedTrait {
+    fn render(&self) -> RenderResult {
+        self.to_objects()
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        for object in self.to_objects()? {
+            let resource = resource_api(&object, ns)?;
+            apply(&client, &resource, object_name(&object), &object)?;
+        }
+        Ok(())
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        for object in self.to_objects()? {
+            let req =
+                resource_api(&object, ns)?.delete(object_name(&object), &Default::default())?;
+            client.timed_request::<Value>(req)?;
+        }
+        Ok(())
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        for object in self.to_objects().ok()? {
+            let key = format!(
+                "{}/{}",
+                object["kind"].as_str().unwrap_or_default().to_lowercase(),
+                object_name(&object)
+            );
+            let req = match resource_api(&object, ns)
+                .and_then(|api| Ok(api.get(object_name(&object))?))
+            {
+                Ok(req) => req,
+                Err(e) => {
+                    resource.insert(key, e.to_string());
+                    continue;
+                }
+            };
+            let state = match client.timed_request::<Value>(req) {
+                Ok(current) => current["status"]["conditions"]
+                    .as_array()
+                    .and_then(|conditions| conditions.iter().find(|c| c["type"] == "Ready"))
+                    .map(|c| match c["status"].as_str() {
+                        Some("True") => "ready".to_string(),
+                        _ => c["message"].as_str().unwrap_or("not ready").to_string(),
+                    })
+                    .unwrap_or_else(|| "created".to_string()),
+                Err(e) => {
+                    if e.to_string().contains("NotFound") {
+                        warn!(
+                            "{} of trait {} not found {}. Recreating ...",
+                            key, self.trait_name, e
+                        );
+                        self.add(ns, client.clone()).unwrap_or(());
+                    }
+                    e.to_string()
+                }
+            };
+            resource.insert(key, state);
+        }
+        Some(resource)
+    }
+}
@@ -0,0 +1,58 @@
+use crate::schematic::trait_definition::TraitDefinition;
+use crate::schematic::traits::*;
+use serde_json::json;
+
+#[test]
+fn test_defined_trait_render() {
+    let defined = DefinedTrait {
+        name: "my-app".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        trait_name: "settings".into(),
+        definition: TraitDefinition {
+            template: serde_json::from_value(json!([{
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": {"labels": {"tier": "web"}},
+                "data": {"color": "[fromProperty(color)]", "app": "[fromContext(applicationName)]"}
+            }]))
+            .expect("template"),
+            ..Default::default()
+        },
+        properties: json!({"color": "blue"}).as_object().cloned(),
+        workload_type: "core.oam.dev/v1alpha1.Server".into(),
+        owner_ref: None,
+    };
+    let objects = defined.render().expect("render");
+    assert_eq!(1, objects.len());
+    assert_eq!("squid-trait-settings", objects[0]["metadata"]["name"]);
+    assert_eq!("web", objects[0]["metadata"]["labels"]["tier"]);
+    assert_eq!(
+        "squid",
+        objects[0]["metadata"]["labels"]["oam.dev/instance-name"]
+    );
+    assert_eq!(
+        json!({"color": "blue", "app": "my-app"}),
+        objects[0]["data"]
+    );
+}
+
+#[test]
+fn test_defined_trait_duplicate_objects() {
+    let config_map = json!({"apiVersion": "v1", "kind": "ConfigMap"});
+    let defined = DefinedTrait {
+        name: "my-app".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        trait_name: "settings".into(),
+        definition: TraitDefinition {
+            template: serde_json::from_value(json!([config_map.clone(), config_map]))
+                .expect("template"),
+            ..Default::default()
+        },
+        properties: json!({"color": "blue"}).as_object().cloned(),
+        workload_type: "core.oam.dev/v1alpha1.Server".into(),
+        owner_ref: None,
+    };
+    assert!(defined.render().is_err());
+}
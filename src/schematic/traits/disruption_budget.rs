@@ -0,0 +1,174 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{
+    apply, RenderResult, SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_WORKER, WORKER_NAME,
+};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// A DisruptionBudget keeps some pods of a component running through voluntary disruptions,
+/// like the node drains of a cluster upgrade, with a Kubernetes PodDisruptionBudget.
+#[derive(Clone, Debug)]
+pub struct DisruptionBudget {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The pods that have to stay available, a number or a percentage like `"50%"`.
+    pub min_available: Option<Value>,
+    /// The pods that may be unavailable, a number or a percentage.
+    pub max_unavailable: Option<Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+impl DisruptionBudget {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let budget = |key: &str| {
+            properties_map
+                .and_then(|map| map.get(key))
+                .filter(|b| !b.is_null())
+                .cloned()
+        };
+        DisruptionBudget {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            min_available: budget("minAvailable"),
+            max_unavailable: budget("maxUnavailable"),
+        }
+    }
+
+    /// to_pod_disruption_budget renders the policy/v1 PodDisruptionBudget, which selects the pods
+    /// of the component. It is rendered as JSON since the Kubernetes API types rudr is built with
+    /// predate policy/v1.
+    pub fn to_pod_disruption_budget(&self) -> Result<Value, failure::Error> {
+        let (key, budget) = match (&self.min_available, &self.max_unavailable) {
+            (Some(min), None) => ("minAvailable", min),
+            (None, Some(max)) => ("maxUnavailable", max),
+            _ => {
+                return Err(format_err!(
+                    "disruption-budget of {} needs one of minAvailable and maxUnavailable",
+                    self.instance_name
+                ))
+            }
+        };
+        let valid = match budget {
+            Value::Number(n) => n.as_u64().is_some(),
+            Value::String(s) => s
+                .strip_suffix('%')
+                .and_then(|p| p.parse::<u8>().ok())
+                .map_or(false, |p| p <= 100),
+            _ => false,
+        };
+        if !valid {
+            return Err(format_err!(
+                "disruption-budget {} of {} is neither a number of pods nor a percentage: {}",
+                key,
+                self.instance_name,
+                budget
+            ));
+        }
+        let mut metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+        });
+        if let Some(owner_ref) = self.owner_ref.as_ref() {
+            metadata["ownerReferences"] = serde_json::to_value(owner_ref)?;
+        }
+        let mut spec = json!({
+            "selector": {
+                "matchLabels": instance_selector(self.name.clone(), self.instance_name.clone()),
+            },
+        });
+        spec[key] = budget.clone();
+        Ok(json!({
+            "apiVersion": "policy/v1",
+            "kind": "PodDisruptionBudget",
+            "metadata": metadata,
+            "spec": spec,
+        }))
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-disruption-budget", self.instance_name)
+    }
+}
+
+/// The resource of the PodDisruptionBudgets in `ns`.
+fn pdb_resource(ns: &str) -> RawApi {
+    RawApi {
+        resource: "poddisruptionbudgets".into(),
+        group: "policy".into(),
+        version: "v1".into(),
+        prefix: "apis".into(),
+        namespace: Some(ns.into()),
+    }
+}
+
+impl TraitImplementation for DisruptionBudget {
+    fn render(&self) -> RenderResult {
+        Ok(vec![self.to_pod_disruption_budget()?])
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let pdb = self.to_pod_disruption_budget()?;
+        apply(&client, &pdb_resource(ns), self.kube_name().as_str(), &pdb)
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let req = pdb_resource(ns).delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // Tasks run to completion, evicting them only reruns their pods.
+        name == SERVER_NAME
+            || name == SINGLETON_SERVER_NAME
+            || name == WORKER_NAME
+            || name == SINGLETON_WORKER
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "poddisruptionbudget/".to_string() + self.kube_name().as_str();
+        let req = match pdb_resource(ns).get(self.kube_name().as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let pdb: Value = match client.timed_request::<Value>(req) {
+            Ok(pdb) => pdb,
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("PodDisruptionBudget not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let status = &pdb["status"];
+        resource.insert(
+            key,
+            format!(
+                "healthy {}/{}, disruptions allowed {}",
+                status["currentHealthy"].as_i64().unwrap_or(0),
+                status["desiredHealthy"].as_i64().unwrap_or(0),
+                status["disruptionsAllowed"].as_i64().unwrap_or(0)
+            ),
+        );
+        Some(resource)
+    }
+}
@@ -0,0 +1,181 @@
+use crate::schematic::traits::*;
+use crate::workload_type::{
+    SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_TASK_NAME, SINGLETON_WORKER, TASK_NAME,
+    WORKER_NAME,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_disruption_budget_min_available() {
+    let disruption_budget = DisruptionBudget {
+        name: "my-budget".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        min_available: Some(json!(2)),
+        max_unavailable: None,
+        owner_ref: None,
+    };
+    let pdb = disruption_budget.to_pod_disruption_budget().expect("pdb");
+    assert_eq!("policy/v1", pdb["apiVersion"]);
+    assert_eq!("PodDisruptionBudget", pdb["kind"]);
+    assert_eq!("squid-trait-disruption-budget", pdb["metadata"]["name"]);
+    assert!(pdb["metadata"]["ownerReferences"].is_null());
+    // The budget selects the pods of the instance, not those of its other instances.
+    assert_eq!(
+        json!({"app.kubernetes.io/name": "my-budget", "oam.dev/instance-name": "squid"}),
+        pdb["spec"]["selector"]["matchLabels"]
+    );
+    assert_eq!(2, pdb["spec"]["minAvailable"]);
+    assert!(pdb["spec"]["maxUnavailable"].is_null());
+}
+
+#[test]
+fn test_disruption_budget_max_unavailable() {
+    let budget = DisruptionBudget {
+        name: "my-budget".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        min_available: None,
+        max_unavailable: Some(json!("25%")),
+        owner_ref: None,
+    };
+    let pdb = budget.to_pod_disruption_budget().expect("pdb");
+    assert_eq!("25%", pdb["spec"]["maxUnavailable"]);
+    assert!(pdb["spec"]["minAvailable"].is_null());
+
+    // Zero pods and the whole range of percentages are budgets too.
+    for budget in &[json!(0), json!("0%"), json!("100%")] {
+        let budget = DisruptionBudget {
+            name: "my-budget".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            min_available: None,
+            max_unavailable: Some(budget.clone()),
+            owner_ref: None,
+        };
+        assert!(budget.to_pod_disruption_budget().is_ok());
+    }
+}
+
+#[test]
+fn test_disruption_budget_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let budget = DisruptionBudget {
+        name: "my-budget".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        min_available: Some(json!(2)),
+        max_unavailable: None,
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    assert_eq!(
+        serde_json::to_value(vec![owner]).unwrap(),
+        budget.to_pod_disruption_budget().expect("pdb")["metadata"]["ownerReferences"]
+    );
+}
+
+#[test]
+fn test_disruption_budget_invalid() {
+    let budget = DisruptionBudget {
+        name: "my-budget".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        min_available: None,
+        max_unavailable: None,
+        owner_ref: None,
+    };
+    assert_eq!(
+        "disruption-budget of squid needs one of minAvailable and maxUnavailable",
+        budget.to_pod_disruption_budget().unwrap_err().to_string()
+    );
+    let budget = DisruptionBudget {
+        name: "my-budget".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        min_available: Some(json!(2)),
+        max_unavailable: Some(json!(1)),
+        owner_ref: None,
+    };
+    assert!(budget.to_pod_disruption_budget().is_err());
+
+    for invalid in &[
+        json!(-1),
+        json!(1.5),
+        json!("120%"),
+        json!("half"),
+        json!("2"),
+    ] {
+        let budget = DisruptionBudget {
+            name: "my-budget".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            min_available: Some(invalid.clone()),
+            max_unavailable: None,
+            owner_ref: None,
+        };
+        assert_eq!(
+            format!(
+                "disruption-budget minAvailable of squid is neither a number of pods nor a \
+                 percentage: {}",
+                invalid
+            ),
+            budget.to_pod_disruption_budget().unwrap_err().to_string()
+        );
+    }
+}
+
+#[test]
+fn test_disruption_budget_v1alpha1() {
+    let disruption_budget_alpha1_trait = TraitBinding {
+        name: String::from("disruption-budget"),
+        parameter_values: None,
+        properties: Some(json!({
+            "minAvailable": null,
+            "maxUnavailable": "50%"
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&disruption_budget_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let budget = DisruptionBudget::from_properties(
+        "my-budget".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+    );
+    // A null budget is no budget, as if it were left out.
+    assert_eq!(None, budget.min_available);
+    assert_eq!(Some(json!("50%")), budget.max_unavailable);
+    assert!(budget.to_pod_disruption_budget().is_ok());
+}
+
+#[test]
+fn test_disruption_budget_workload_types() {
+    for name in &[
+        SERVER_NAME,
+        SINGLETON_SERVER_NAME,
+        WORKER_NAME,
+        SINGLETON_WORKER,
+    ] {
+        assert!(DisruptionBudget::supports_workload_type(name));
+    }
+    assert!(!DisruptionBudget::supports_workload_type(TASK_NAME));
+    assert!(!DisruptionBudget::supports_workload_type(
+        SINGLETON_TASK_NAME
+    ));
+}
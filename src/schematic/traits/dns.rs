@@ -0,0 +1,271 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::component::Port;
+use crate::schematic::traits::ingress::{ingress_name, ingress_resource};
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, RenderResult, SERVER_NAME, SINGLETON_SERVER_NAME};
+use k8s_openapi::api::core::v1 as core;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use kube::api::{PatchParams, RawApi};
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The annotation with the hostnames external-dns creates records of.
+pub const HOSTNAME_ANNOTATION: &str = "external-dns.alpha.kubernetes.io/hostname";
+/// The annotation with the TTL of the records, in seconds.
+pub const TTL_ANNOTATION: &str = "external-dns.alpha.kubernetes.io/ttl";
+
+/// The target that annotates the Service of the component.
+pub const SERVICE_TARGET: &str = "service";
+/// The target that annotates the Ingress of the ingress trait of the component.
+pub const INGRESS_TARGET: &str = "ingress";
+/// The target that creates a Service of type LoadBalancer for the records.
+pub const LOAD_BALANCER_TARGET: &str = "loadBalancer";
+
+/// A Dns has [external-dns](https://github.com/kubernetes-sigs/external-dns) keep DNS records
+/// of hostnames pointing at a component, by annotating the Service or Ingress of the component
+/// or a LoadBalancer Service the trait creates.
+#[derive(Clone, Debug)]
+pub struct Dns {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    pub hostnames: Vec<String>,
+    /// The TTL of the records in seconds, the default of external-dns if unset.
+    pub ttl: Option<i64>,
+    /// `service`, `ingress` or `loadBalancer`, `service` if unset.
+    pub target: Option<String>,
+    /// The port of the component, which a LoadBalancer Service exposes.
+    pub port: Option<Port>,
+    pub owner_ref: OwnerRefs,
+}
+
+impl Dns {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+        port: Option<Port>,
+    ) -> Self {
+        Dns {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            port,
+            hostnames: properties_map
+                .and_then(|map| map.get("hostnames"))
+                .and_then(|h| h.as_array())
+                .map(|h| {
+                    h.iter()
+                        .filter_map(|h| h.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            ttl: properties_map.and_then(|map| map.get("ttl").and_then(|t| t.as_i64())),
+            target: properties_map
+                .and_then(|map| map.get("target").and_then(|t| t.as_str()))
+                .map(String::from),
+        }
+    }
+
+    /// target is the target of the trait, failing unless it is a known one.
+    fn target(&self) -> Result<&str, failure::Error> {
+        match self.target.as_deref().unwrap_or(SERVICE_TARGET) {
+            target @ SERVICE_TARGET | target @ INGRESS_TARGET | target @ LOAD_BALANCER_TARGET => {
+                Ok(target)
+            }
+            other => Err(format_err!(
+                "dns target {} is none of {}, {} and {}",
+                other,
+                SERVICE_TARGET,
+                INGRESS_TARGET,
+                LOAD_BALANCER_TARGET
+            )),
+        }
+    }
+
+    /// annotations are the external-dns annotations of the target.
+    pub fn annotations(&self) -> Result<BTreeMap<String, String>, failure::Error> {
+        if self.hostnames.is_empty() {
+            return Err(format_err!(
+                "dns of {} has no hostnames",
+                self.instance_name
+            ));
+        }
+        let mut annotations = BTreeMap::new();
+        annotations.insert(HOSTNAME_ANNOTATION.to_string(), self.hostnames.join(","));
+        if let Some(ttl) = self.ttl {
+            if ttl <= 0 {
+                return Err(format_err!(
+                    "dns ttl of {} isn't a positive number of seconds: {}",
+                    self.instance_name,
+                    ttl
+                ));
+            }
+            annotations.insert(TTL_ANNOTATION.to_string(), ttl.to_string());
+        }
+        Ok(annotations)
+    }
+
+    /// to_load_balancer renders the Service of type LoadBalancer of the `loadBalancer` target,
+    /// which selects the pods of the component.
+    pub fn to_load_balancer(&self) -> Result<core::Service, failure::Error> {
+        let port = self.port.as_ref().ok_or_else(|| {
+            format_err!(
+                "dns of {} can't create a LoadBalancer, the component has no port",
+                self.instance_name
+            )
+        })?;
+        Ok(core::Service {
+            metadata: Some(meta::ObjectMeta {
+                name: Some(self.kube_name()),
+                labels: Some(trait_labels(self.name.clone(), self.instance_name.clone())),
+                annotations: Some(self.annotations()?),
+                owner_references: self.owner_ref.clone(),
+                ..Default::default()
+            }),
+            spec: Some(core::ServiceSpec {
+                type_: Some("LoadBalancer".to_string()),
+                selector: Some(instance_selector(
+                    self.name.clone(),
+                    self.instance_name.clone(),
+                )),
+                ports: Some(vec![port.to_service_port()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// object is the kind, resource and name of the object of the target: the Service or Ingress
+    /// it annotates, or the LoadBalancer Service it creates.
+    fn object(&self, ns: &str) -> Result<(&str, RawApi, String), failure::Error> {
+        Ok(match self.target()? {
+            INGRESS_TARGET => (
+                "ingress",
+                ingress_resource(ns),
+                ingress_name(self.instance_name.as_str()),
+            ),
+            LOAD_BALANCER_TARGET => ("service", RawApi::v1Service().within(ns), self.kube_name()),
+            _ => (
+                "service",
+                RawApi::v1Service().within(ns),
+                self.instance_name.clone(),
+            ),
+        })
+    }
+
+    /// annotate sets the annotations of the object the target annotates to `annotations`,
+    /// with a merge patch that leaves the fields the workload or the ingress trait apply alone.
+    fn annotate(&self, ns: &str, client: &APIClient, annotations: Value) -> TraitResult {
+        let (_, resource, name) = self.object(ns)?;
+        let patch = json!({ "metadata": { "annotations": annotations } });
+        let req = resource.patch(
+            name.as_str(),
+            &PatchParams::default(),
+            serde_json::to_vec(&patch)?,
+        )?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-dns", self.instance_name)
+    }
+}
+
+impl TraitImplementation for Dns {
+    /// Only the LoadBalancer Service is an object of the trait, the annotations are on objects
+    /// of the component.
+    fn render(&self) -> RenderResult {
+        match self.target()? {
+            LOAD_BALANCER_TARGET => Ok(vec![serde_json::to_value(self.to_load_balancer()?)?]),
+            _ => {
+                self.annotations()?;
+                Ok(vec![])
+            }
+        }
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        match self.target()? {
+            LOAD_BALANCER_TARGET => {
+                let service = self.to_load_balancer()?;
+                let resource = RawApi::v1Service().within(ns);
+                apply(&client, &resource, self.kube_name().as_str(), &service)
+            }
+            _ => self.annotate(ns, &client, json!(self.annotations()?)),
+        }
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        match self.target()? {
+            LOAD_BALANCER_TARGET => {
+                let req = RawApi::v1Service()
+                    .within(ns)
+                    .delete(self.kube_name().as_str(), &Default::default())?;
+                client.timed_request::<Value>(req)?;
+                Ok(())
+            }
+            // The annotated object outlives the trait, only the annotations go.
+            _ => self.annotate(
+                ns,
+                &client,
+                json!({HOSTNAME_ANNOTATION: null, TTL_ANNOTATION: null}),
+            ),
+        }
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // Only servers have a Service or an Ingress to point records at.
+        name == SERVER_NAME || name == SINGLETON_SERVER_NAME
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let target = self.target().ok()?;
+        let (kind, api, name) = self.object(ns).ok()?;
+        let key = format!("{}/{}", kind, name);
+        let req = match api.get(name.as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let object = match client.timed_request::<Value>(req) {
+            Ok(object) => object,
+            Err(e) => {
+                if e.to_string().contains("NotFound") && target == LOAD_BALANCER_TARGET {
+                    warn!("DNS LoadBalancer not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let hostnames = self.hostnames.join(",");
+        if object["metadata"]["annotations"][HOSTNAME_ANNOTATION] != json!(hostnames) {
+            // The Ingress of an ingress trait listed after this one didn't exist yet.
+            warn!("{} isn't annotated with the hostnames. Annotating ...", key);
+            self.add(ns, client).unwrap_or(());
+            resource.insert(key, "not annotated".to_string());
+            return Some(resource);
+        }
+        let state = match target {
+            LOAD_BALANCER_TARGET => object["status"]["loadBalancer"]["ingress"][0]
+                .as_object()
+                .and_then(|i| i.get("ip").or_else(|| i.get("hostname")))
+                .and_then(|a| a.as_str())
+                .map(|a| format!("{} at {}", hostnames, a))
+                .unwrap_or_else(|| "pending".to_string()),
+            _ => hostnames,
+        };
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
@@ -0,0 +1,286 @@
+use crate::schematic::component::Port;
+use crate::schematic::traits::dns::{HOSTNAME_ANNOTATION, TTL_ANNOTATION};
+use crate::schematic::traits::*;
+use crate::workload_type::{SERVER_NAME, SINGLETON_SERVER_NAME, TASK_NAME, WORKER_NAME};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_dns_annotations() {
+    let dns = Dns {
+        name: "my-dns".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        hostnames: vec!["squid.example.com".into()],
+        ttl: None,
+        target: None,
+        port: Some(Port::basic("http".into(), 8080)),
+        owner_ref: None,
+    };
+    let annotations = dns.annotations().expect("annotations");
+    assert_eq!("squid.example.com", annotations[HOSTNAME_ANNOTATION]);
+    // external-dns picks the TTL of records without one.
+    assert!(!annotations.contains_key(TTL_ANNOTATION));
+
+    let dns = Dns {
+        name: "my-dns".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        hostnames: vec!["squid.example.com".into(), "www.example.com".into()],
+        ttl: Some(60),
+        target: None,
+        port: Some(Port::basic("http".into(), 8080)),
+        owner_ref: None,
+    };
+    let annotations = dns.annotations().expect("annotations");
+    assert_eq!(
+        "squid.example.com,www.example.com",
+        annotations[HOSTNAME_ANNOTATION]
+    );
+    assert_eq!("60", annotations[TTL_ANNOTATION]);
+}
+
+#[test]
+fn test_dns_render() {
+    // Annotations are on objects of the component, the trait renders none of its own.
+    for target in &[None, Some("service"), Some("ingress")] {
+        let dns = Dns {
+            name: "my-dns".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            hostnames: vec!["squid.example.com".into()],
+            ttl: None,
+            target: target.map(String::from),
+            port: Some(Port::basic("http".into(), 8080)),
+            owner_ref: None,
+        };
+        assert!(dns.render().expect("render").is_empty());
+    }
+    let dns = Dns {
+        name: "my-dns".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        hostnames: vec!["squid.example.com".into()],
+        ttl: None,
+        target: Some("loadBalancer".into()),
+        port: Some(Port::basic("http".into(), 8080)),
+        owner_ref: None,
+    };
+    let objects = dns.render().expect("render");
+    assert_eq!(1, objects.len());
+    assert_eq!("squid-trait-dns", objects[0]["metadata"]["name"]);
+}
+
+#[test]
+fn test_dns_load_balancer() {
+    let dns = Dns {
+        name: "my-dns".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        hostnames: vec!["squid.example.com".into()],
+        ttl: Some(300),
+        target: Some("loadBalancer".into()),
+        port: Some(Port::basic("http".into(), 8080)),
+        owner_ref: None,
+    };
+    let service = dns.to_load_balancer().expect("load balancer");
+    let metadata = service.metadata.expect("metadata");
+    assert_eq!(Some("squid-trait-dns".to_string()), metadata.name);
+    assert_eq!(None, metadata.owner_references);
+    let annotations = metadata.annotations.expect("annotations");
+    assert_eq!("squid.example.com", annotations[HOSTNAME_ANNOTATION]);
+    assert_eq!("300", annotations[TTL_ANNOTATION]);
+    let spec = service.spec.expect("spec");
+    assert_eq!(Some("LoadBalancer".to_string()), spec.type_);
+    let selector = spec.selector.expect("selector");
+    assert_eq!(
+        Some(&"my-dns".to_string()),
+        selector.get("app.kubernetes.io/name")
+    );
+    assert_eq!(
+        Some(&"squid".to_string()),
+        selector.get("oam.dev/instance-name")
+    );
+    let ports = spec.ports.expect("ports");
+    assert_eq!(Some("http".to_string()), ports[0].name);
+    assert_eq!(8080, ports[0].port);
+    assert_eq!(Some("TCP".to_string()), ports[0].protocol);
+}
+
+#[test]
+fn test_dns_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let dns = Dns {
+        name: "my-dns".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        hostnames: vec!["squid.example.com".into()],
+        ttl: None,
+        target: Some("loadBalancer".into()),
+        port: Some(Port::basic("http".into(), 8080)),
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    let metadata = dns
+        .to_load_balancer()
+        .expect("load balancer")
+        .metadata
+        .expect("metadata");
+    assert_eq!(Some(vec![owner]), metadata.owner_references);
+}
+
+#[test]
+fn test_dns_invalid() {
+    let invalid = |dns: Dns| dns.render().unwrap_err().to_string();
+    assert_eq!(
+        "dns of squid has no hostnames",
+        invalid(Dns {
+            name: "my-dns".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            hostnames: vec![],
+            ttl: None,
+            target: None,
+            port: Some(Port::basic("http".into(), 8080)),
+            owner_ref: None,
+        })
+    );
+    for ttl in &[0, -60] {
+        assert_eq!(
+            format!(
+                "dns ttl of squid isn't a positive number of seconds: {}",
+                ttl
+            ),
+            invalid(Dns {
+                name: "my-dns".into(),
+                instance_name: "squid".into(),
+                component_name: "patsy".into(),
+                hostnames: vec!["squid.example.com".into()],
+                ttl: Some(*ttl),
+                target: None,
+                port: Some(Port::basic("http".into(), 8080)),
+                owner_ref: None,
+            })
+        );
+    }
+    assert_eq!(
+        "dns target route is none of service, ingress and loadBalancer",
+        invalid(Dns {
+            name: "my-dns".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            hostnames: vec!["squid.example.com".into()],
+            ttl: None,
+            target: Some("route".into()),
+            port: Some(Port::basic("http".into(), 8080)),
+            owner_ref: None,
+        })
+    );
+    // Targets are case sensitive, like the rest of the properties.
+    assert_eq!(
+        "dns target LoadBalancer is none of service, ingress and loadBalancer",
+        invalid(Dns {
+            name: "my-dns".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            hostnames: vec!["squid.example.com".into()],
+            ttl: None,
+            target: Some("LoadBalancer".into()),
+            port: Some(Port::basic("http".into(), 8080)),
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "dns of squid can't create a LoadBalancer, the component has no port",
+        invalid(Dns {
+            name: "my-dns".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            hostnames: vec!["squid.example.com".into()],
+            ttl: None,
+            target: Some("loadBalancer".into()),
+            port: None,
+            owner_ref: None,
+        })
+    );
+    // Without a LoadBalancer the port isn't needed.
+    assert!(Dns {
+        name: "my-dns".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        hostnames: vec!["squid.example.com".into()],
+        ttl: None,
+        target: None,
+        port: None,
+        owner_ref: None,
+    }
+    .render()
+    .is_ok());
+}
+
+#[test]
+fn test_dns_v1alpha1() {
+    let dns_alpha1_trait = TraitBinding {
+        name: String::from("dns"),
+        parameter_values: None,
+        properties: Some(json!({
+            "hostnames": ["squid.example.com", 42, "www.example.com"],
+            "ttl": 60,
+            "target": "ingress"
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&dns_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let dns = Dns::from_properties(
+        "my-dns".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+        None,
+    );
+    // Hostnames that aren't strings are skipped.
+    assert_eq!(
+        vec![
+            "squid.example.com".to_string(),
+            "www.example.com".to_string()
+        ],
+        dns.hostnames
+    );
+    assert_eq!(Some(60), dns.ttl);
+    assert_eq!(Some("ingress".to_string()), dns.target);
+    assert!(dns.port.is_none());
+
+    let dns = Dns::from_properties(
+        "my-dns".into(),
+        "squid".into(),
+        "patsy".into(),
+        None,
+        None,
+        None,
+    );
+    assert!(dns.hostnames.is_empty());
+    assert_eq!(None, dns.ttl);
+    assert_eq!(None, dns.target);
+}
+
+#[test]
+fn test_dns_workload_types() {
+    assert!(Dns::supports_workload_type(SERVER_NAME));
+    assert!(Dns::supports_workload_type(SINGLETON_SERVER_NAME));
+    assert!(!Dns::supports_workload_type(WORKER_NAME));
+    assert!(!Dns::supports_workload_type(TASK_NAME));
+}
@@ -0,0 +1,257 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, RenderResult};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The interval Flux reconciles the release and fetches the repository at, if unset.
+const DEFAULT_INTERVAL: &str = "5m";
+
+/// A HelmRelease installs a Helm chart with the component, like a Redis or a RabbitMQ it
+/// depends on, through a HelmRelease of the [Flux](https://fluxcd.io/) helm-controller.
+///
+/// The chart comes from the HelmRepository of a `repository` URL, which the trait creates,
+/// or from an existing source of Flux. The release is uninstalled with the trait.
+#[derive(Clone, Debug)]
+pub struct HelmRelease {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    pub chart: Option<String>,
+    /// The version of the chart, a semver range, the latest one if unset.
+    pub version: Option<String>,
+    /// The URL of the Helm repository of the chart, `oci://` for an OCI registry.
+    pub repository: Option<String>,
+    /// An existing source of the chart, with a `kind`, a `name` and a `namespace`.
+    pub source_ref: Option<Value>,
+    /// The values of the release.
+    pub values: Option<Value>,
+    /// The name of the release, the instance name if unset.
+    pub release_name: Option<String>,
+    /// The namespace to install the release in, the namespace of the configuration if unset.
+    pub target_namespace: Option<String>,
+    pub interval: Option<String>,
+    pub owner_ref: OwnerRefs,
+}
+
+impl HelmRelease {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        let value = |key: &str| {
+            properties_map
+                .and_then(|map| map.get(key))
+                .filter(|v| !v.is_null())
+                .cloned()
+        };
+        HelmRelease {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            chart: string("chart"),
+            version: string("version"),
+            repository: string("repository"),
+            source_ref: value("sourceRef"),
+            values: value("values"),
+            release_name: string("releaseName"),
+            target_namespace: string("targetNamespace"),
+            interval: string("interval"),
+        }
+    }
+
+    fn interval(&self) -> String {
+        self.interval
+            .clone()
+            .unwrap_or_else(|| DEFAULT_INTERVAL.to_string())
+    }
+
+    fn metadata(&self) -> Value {
+        json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+            "ownerReferences": self.owner_ref,
+        })
+    }
+
+    /// to_helm_repository renders the source.toolkit.fluxcd.io/v1beta2 HelmRepository of the
+    /// `repository` URL, None for an existing source.
+    pub fn to_helm_repository(&self) -> Result<Option<Value>, failure::Error> {
+        let url = match (self.repository.as_ref(), self.source_ref.as_ref()) {
+            (Some(url), None) => url,
+            (None, Some(_)) => return Ok(None),
+            _ => {
+                return Err(format_err!(
+                    "helm-release of {} needs either a repository or a sourceRef",
+                    self.instance_name
+                ))
+            }
+        };
+        let mut spec = json!({"url": url, "interval": self.interval()});
+        if url.starts_with("oci://") {
+            spec["type"] = json!("oci");
+        }
+        Ok(Some(json!({
+            "apiVersion": "source.toolkit.fluxcd.io/v1beta2",
+            "kind": "HelmRepository",
+            "metadata": self.metadata(),
+            "spec": spec,
+        })))
+    }
+
+    /// to_helm_release renders the helm.toolkit.fluxcd.io/v2beta1 HelmRelease of the chart.
+    pub fn to_helm_release(&self) -> Result<Value, failure::Error> {
+        let chart = self
+            .chart
+            .as_ref()
+            .ok_or_else(|| format_err!("helm-release of {} has no chart", self.instance_name))?;
+        let source_ref = match self.to_helm_repository()? {
+            Some(_) => json!({"kind": "HelmRepository", "name": self.kube_name()}),
+            None => {
+                let source_ref = self.source_ref.clone().unwrap_or_default();
+                if source_ref["kind"].as_str().is_none() || source_ref["name"].as_str().is_none() {
+                    return Err(format_err!(
+                        "helm-release sourceRef of {} has no kind or name",
+                        self.instance_name
+                    ));
+                }
+                source_ref
+            }
+        };
+        let mut chart_spec = json!({"chart": chart, "sourceRef": source_ref});
+        if let Some(version) = self.version.as_ref() {
+            chart_spec["version"] = json!(version);
+        }
+        let mut spec = json!({
+            "interval": self.interval(),
+            "releaseName": self
+                .release_name
+                .clone()
+                .unwrap_or_else(|| self.instance_name.clone()),
+            "chart": {"spec": chart_spec},
+        });
+        if let Some(values) = self.values.as_ref() {
+            if !values.is_object() {
+                return Err(format_err!(
+                    "helm-release values of {} aren't an object",
+                    self.instance_name
+                ));
+            }
+            spec["values"] = values.clone();
+        }
+        if let Some(target_namespace) = self.target_namespace.as_ref() {
+            spec["targetNamespace"] = json!(target_namespace);
+        }
+        Ok(json!({
+            "apiVersion": "helm.toolkit.fluxcd.io/v2beta1",
+            "kind": "HelmRelease",
+            "metadata": self.metadata(),
+            "spec": spec,
+        }))
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-helm-release", self.instance_name)
+    }
+}
+
+fn helm_repository_resource(ns: &str) -> RawApi {
+    RawApi::customResource("helmrepositories")
+        .group("source.toolkit.fluxcd.io")
+        .version("v1beta2")
+        .within(ns)
+}
+
+fn helm_release_resource(ns: &str) -> RawApi {
+    RawApi::customResource("helmreleases")
+        .group("helm.toolkit.fluxcd.io")
+        .version("v2beta1")
+        .within(ns)
+}
+
+impl TraitImplementation for HelmRelease {
+    fn render(&self) -> RenderResult {
+        let mut objects = vec![];
+        objects.extend(self.to_helm_repository()?);
+        objects.push(self.to_helm_release()?);
+        Ok(objects)
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let release = self.to_helm_release()?;
+        if let Some(repository) = self.to_helm_repository()? {
+            apply(
+                &client,
+                &helm_repository_resource(ns),
+                self.kube_name().as_str(),
+                &repository,
+            )?;
+        }
+        apply(
+            &client,
+            &helm_release_resource(ns),
+            self.kube_name().as_str(),
+            &release,
+        )
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        // The release goes first, helm-controller uninstalls it from the repository.
+        let req =
+            helm_release_resource(ns).delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        if self.repository.is_some() {
+            let req = helm_repository_resource(ns)
+                .delete(self.kube_name().as_str(), &Default::default())?;
+            client.timed_request::<Value>(req)?;
+        }
+        Ok(())
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "helmrelease/".to_string() + self.kube_name().as_str();
+        let req = match helm_release_resource(ns).get(self.kube_name().as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let object = match client.timed_request::<Value>(req) {
+            Ok(object) => object,
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("HelmRelease not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let state = object["status"]["conditions"]
+            .as_array()
+            .and_then(|conditions| conditions.iter().find(|c| c["type"] == "Ready"))
+            .map(|c| match c["status"].as_str() {
+                Some("True") => match object["status"]["lastAppliedRevision"].as_str() {
+                    Some(revision) => format!("ready at {}", revision),
+                    None => "ready".to_string(),
+                },
+                _ => c["message"].as_str().unwrap_or("not ready").to_string(),
+            })
+            .unwrap_or_else(|| "pending".to_string());
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
@@ -0,0 +1,353 @@
+use crate::schematic::traits::*;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_helm_release_repository() {
+    let release = HelmRelease {
+        name: "my-deps".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        chart: Some("redis".into()),
+        version: None,
+        repository: Some("https://charts.bitnami.com/bitnami".into()),
+        source_ref: None,
+        values: None,
+        release_name: None,
+        target_namespace: None,
+        interval: None,
+        owner_ref: None,
+    };
+    let repository = release
+        .to_helm_repository()
+        .expect("repository")
+        .expect("a repository");
+    assert_eq!("source.toolkit.fluxcd.io/v1beta2", repository["apiVersion"]);
+    assert_eq!("HelmRepository", repository["kind"]);
+    assert_eq!("squid-trait-helm-release", repository["metadata"]["name"]);
+    assert!(repository["metadata"]["ownerReferences"].is_null());
+    assert_eq!(
+        json!({"url": "https://charts.bitnami.com/bitnami", "interval": "5m"}),
+        repository["spec"]
+    );
+
+    // Charts of an OCI registry need a repository of the oci type.
+    let release = HelmRelease {
+        name: "my-deps".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        chart: Some("redis".into()),
+        version: None,
+        repository: Some("oci://registry-1.docker.io/bitnamicharts".into()),
+        source_ref: None,
+        values: None,
+        release_name: None,
+        target_namespace: None,
+        interval: Some("1h".into()),
+        owner_ref: None,
+    };
+    let repository = release
+        .to_helm_repository()
+        .expect("repository")
+        .expect("a repository");
+    assert_eq!(
+        json!({
+            "url": "oci://registry-1.docker.io/bitnamicharts",
+            "interval": "1h",
+            "type": "oci"
+        }),
+        repository["spec"]
+    );
+}
+
+#[test]
+fn test_helm_release_defaults() {
+    let helm_release = HelmRelease {
+        name: "my-deps".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        chart: Some("redis".into()),
+        version: None,
+        repository: Some("https://charts.bitnami.com/bitnami".into()),
+        source_ref: None,
+        values: None,
+        release_name: None,
+        target_namespace: None,
+        interval: None,
+        owner_ref: None,
+    };
+    let release = helm_release.to_helm_release().expect("helm release");
+    assert_eq!("helm.toolkit.fluxcd.io/v2beta1", release["apiVersion"]);
+    assert_eq!("HelmRelease", release["kind"]);
+    assert_eq!("squid-trait-helm-release", release["metadata"]["name"]);
+    // The release is named after the instance, and installs the latest chart with its own
+    // values in the namespace of the configuration.
+    assert_eq!(
+        json!({
+            "interval": "5m",
+            "releaseName": "squid",
+            "chart": {"spec": {
+                "chart": "redis",
+                "sourceRef": {"kind": "HelmRepository", "name": "squid-trait-helm-release"}
+            }}
+        }),
+        release["spec"]
+    );
+
+    let helm_release = HelmRelease {
+        name: "my-deps".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        chart: Some("redis".into()),
+        version: None,
+        repository: Some("https://charts.bitnami.com/bitnami".into()),
+        source_ref: None,
+        values: None,
+        release_name: None,
+        target_namespace: None,
+        interval: None,
+        owner_ref: None,
+    };
+
+    let objects = helm_release.render().expect("render");
+    assert_eq!(2, objects.len());
+    assert_eq!("HelmRepository", objects[0]["kind"]);
+    assert_eq!("HelmRelease", objects[1]["kind"]);
+}
+
+#[test]
+fn test_helm_release_options() {
+    let release = HelmRelease {
+        name: "my-deps".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        chart: Some("redis".into()),
+        version: Some("18.x".into()),
+        repository: Some("https://charts.bitnami.com/bitnami".into()),
+        source_ref: None,
+        values: Some(json!({"architecture": "standalone", "auth": {"enabled": false}})),
+        release_name: Some("cache".into()),
+        target_namespace: Some("caches".into()),
+        interval: Some("10m".into()),
+        owner_ref: None,
+    };
+    let spec = &release.to_helm_release().expect("helm release")["spec"];
+    assert_eq!("10m", spec["interval"]);
+    assert_eq!("cache", spec["releaseName"]);
+    assert_eq!("caches", spec["targetNamespace"]);
+    assert_eq!("18.x", spec["chart"]["spec"]["version"]);
+    assert_eq!(
+        json!({"architecture": "standalone", "auth": {"enabled": false}}),
+        spec["values"]
+    );
+}
+
+#[test]
+fn test_helm_release_source_ref() {
+    let release = HelmRelease {
+        name: "my-deps".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        chart: Some("redis".into()),
+        version: None,
+        repository: None,
+        source_ref: Some(json!({
+            "kind": "HelmRepository",
+            "name": "bitnami",
+            "namespace": "flux-system"
+        })),
+        values: None,
+        release_name: None,
+        target_namespace: None,
+        interval: None,
+        owner_ref: None,
+    };
+    // The trait creates no repository for an existing source.
+    assert!(release.to_helm_repository().expect("repository").is_none());
+    let objects = release.render().expect("render");
+    assert_eq!(1, objects.len());
+    assert_eq!(
+        json!({"kind": "HelmRepository", "name": "bitnami", "namespace": "flux-system"}),
+        objects[0]["spec"]["chart"]["spec"]["sourceRef"]
+    );
+
+    let release = HelmRelease {
+        source_ref: Some(json!({"kind": "GitRepository", "name": "charts"})),
+        ..release
+    };
+    let chart = &release.to_helm_release().expect("helm release")["spec"]["chart"];
+    assert_eq!("GitRepository", chart["spec"]["sourceRef"]["kind"]);
+}
+
+#[test]
+fn test_helm_release_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let release = HelmRelease {
+        name: "my-deps".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        chart: Some("redis".into()),
+        version: None,
+        repository: Some("https://charts.bitnami.com/bitnami".into()),
+        source_ref: None,
+        values: None,
+        release_name: None,
+        target_namespace: None,
+        interval: None,
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    for object in release.render().expect("render") {
+        assert_eq!(
+            serde_json::to_value(vec![owner.clone()]).unwrap(),
+            object["metadata"]["ownerReferences"]
+        );
+    }
+}
+
+#[test]
+fn test_helm_release_invalid() {
+    let invalid = |release: HelmRelease| release.render().unwrap_err().to_string();
+    assert_eq!(
+        "helm-release of squid needs either a repository or a sourceRef",
+        invalid(HelmRelease {
+            name: "my-deps".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            chart: Some("redis".into()),
+            version: None,
+            repository: None,
+            source_ref: None,
+            values: None,
+            release_name: None,
+            target_namespace: None,
+            interval: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "helm-release of squid needs either a repository or a sourceRef",
+        invalid(HelmRelease {
+            name: "my-deps".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            chart: Some("redis".into()),
+            version: None,
+            repository: Some("https://charts.bitnami.com/bitnami".into()),
+            source_ref: Some(json!({"kind": "HelmRepository", "name": "bitnami"})),
+            values: None,
+            release_name: None,
+            target_namespace: None,
+            interval: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "helm-release of squid has no chart",
+        invalid(HelmRelease {
+            name: "my-deps".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            chart: None,
+            version: None,
+            repository: Some("https://charts.bitnami.com/bitnami".into()),
+            source_ref: None,
+            values: None,
+            release_name: None,
+            target_namespace: None,
+            interval: None,
+            owner_ref: None,
+        })
+    );
+    for source_ref in &[
+        json!({"name": "bitnami"}),
+        json!({"kind": "HelmRepository"}),
+    ] {
+        assert_eq!(
+            "helm-release sourceRef of squid has no kind or name",
+            invalid(HelmRelease {
+                name: "my-deps".into(),
+                instance_name: "squid".into(),
+                component_name: "patsy".into(),
+                chart: Some("redis".into()),
+                version: None,
+                repository: None,
+                source_ref: Some(source_ref.clone()),
+                values: None,
+                release_name: None,
+                target_namespace: None,
+                interval: None,
+                owner_ref: None,
+            })
+        );
+    }
+    assert_eq!(
+        "helm-release values of squid aren't an object",
+        invalid(HelmRelease {
+            name: "my-deps".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            chart: Some("redis".into()),
+            version: None,
+            repository: Some("https://charts.bitnami.com/bitnami".into()),
+            source_ref: None,
+            values: Some(json!("architecture: standalone")),
+            release_name: None,
+            target_namespace: None,
+            interval: None,
+            owner_ref: None,
+        })
+    );
+}
+
+#[test]
+fn test_helm_release_v1alpha1() {
+    let helm_release_alpha1_trait = TraitBinding {
+        name: String::from("helm-release"),
+        parameter_values: None,
+        properties: Some(json!({
+            "chart": "rabbitmq",
+            "version": "12.x",
+            "sourceRef": {"kind": "HelmRepository", "name": "bitnami"},
+            "values": null,
+            "releaseName": "queue",
+            "targetNamespace": "queues",
+            "interval": "1h"
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&helm_release_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let release = HelmRelease::from_properties(
+        "my-deps".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+    );
+    assert_eq!(Some("rabbitmq".to_string()), release.chart);
+    assert_eq!(Some("12.x".to_string()), release.version);
+    assert_eq!(None, release.repository);
+    assert_eq!(
+        Some(json!({"kind": "HelmRepository", "name": "bitnami"})),
+        release.source_ref
+    );
+    // Null values are no values, as if they were left out.
+    assert_eq!(None, release.values);
+    assert_eq!(Some("queue".to_string()), release.release_name);
+    assert_eq!(Some("queues".to_string()), release.target_namespace);
+    assert_eq!(Some("1h".to_string()), release.interval);
+    assert!(release.render().is_ok());
+}
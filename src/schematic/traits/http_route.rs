@@ -0,0 +1,309 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, RenderResult, SERVER_NAME, SINGLETON_SERVER_NAME};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The path match types of an HTTPRoute.
+const PATH_TYPES: &[&str] = &["Exact", "PathPrefix", "RegularExpression"];
+/// The header match types of an HTTPRoute.
+const HEADER_TYPES: &[&str] = &["Exact", "RegularExpression"];
+
+/// An HttpRoute routes the requests a Gateway API Gateway receives to the Service of the
+/// component, as an alternative to the Ingress trait.
+#[derive(Clone, Debug)]
+pub struct HttpRoute {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The Gateway the route attaches to.
+    pub gateway: Option<String>,
+    /// The namespace of the Gateway, the namespace of the component if unset.
+    pub gateway_namespace: Option<String>,
+    /// The listener of the Gateway the route attaches to, all of them if unset.
+    pub section_name: Option<String>,
+    pub hostnames: Vec<String>,
+    /// The port of the Service of the component backends use by default.
+    pub svc_port: i32,
+    /// The rules of the route, see `HttpRouteRule`. Without rules every request goes to the
+    /// Service of the component.
+    pub rules: Option<Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+/// HttpRouteRule sends the requests that match any of its matches to its backends.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpRouteRule {
+    #[serde(default)]
+    pub matches: Vec<HttpRouteMatch>,
+    #[serde(default)]
+    pub backends: Vec<HttpRouteBackend>,
+}
+
+/// HttpRouteMatch matches requests by path, headers and method. All of them have to match.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpRouteMatch {
+    pub path: Option<String>,
+    /// `PathPrefix` if unset.
+    pub path_type: Option<String>,
+    #[serde(default)]
+    pub headers: Vec<HttpHeaderMatch>,
+    pub method: Option<String>,
+}
+
+/// HttpHeaderMatch matches requests with a header.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpHeaderMatch {
+    pub name: String,
+    pub value: String,
+    /// `Exact` if unset.
+    #[serde(rename = "type")]
+    pub match_type: Option<String>,
+}
+
+/// HttpRouteBackend is a Service requests are sent to, the Service of the component by default.
+/// Requests are split between the backends of a rule by their weights.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpRouteBackend {
+    pub service_name: Option<String>,
+    pub port: Option<i32>,
+    pub weight: Option<i32>,
+}
+
+impl HttpRoute {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        HttpRoute {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            gateway: string("gateway"),
+            gateway_namespace: string("gatewayNamespace"),
+            section_name: string("sectionName"),
+            hostnames: properties_map
+                .and_then(|map| map.get("hostnames"))
+                .and_then(|h| h.as_array())
+                .map(|h| {
+                    h.iter()
+                        .filter_map(|h| h.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            svc_port: properties_map
+                .and_then(|map| map.get("servicePort").and_then(|p| p.as_i64()))
+                .map(|p| p as i32)
+                .unwrap_or(80),
+            rules: properties_map.and_then(|map| map.get("rules")).cloned(),
+        }
+    }
+
+    /// route_rules are the rules of the trait, or a rule sending everything to the component.
+    pub fn route_rules(&self) -> Result<Vec<HttpRouteRule>, failure::Error> {
+        match self.rules.as_ref() {
+            Some(rules) => serde_json::from_value(rules.clone()).map_err(|e| {
+                format_err!("invalid http-route rules of {}: {}", self.instance_name, e)
+            }),
+            None => Ok(vec![HttpRouteRule::default()]),
+        }
+    }
+
+    /// to_http_route renders the gateway.networking.k8s.io/v1 HTTPRoute.
+    pub fn to_http_route(&self) -> Result<Value, failure::Error> {
+        let gateway = self.gateway.as_ref().ok_or_else(|| {
+            format_err!(
+                "http-route of {} names no gateway to attach to",
+                self.instance_name
+            )
+        })?;
+        let mut parent = json!({ "name": gateway });
+        if let Some(namespace) = self.gateway_namespace.as_ref() {
+            parent["namespace"] = json!(namespace);
+        }
+        if let Some(section) = self.section_name.as_ref() {
+            parent["sectionName"] = json!(section);
+        }
+        let mut rules = vec![];
+        for rule in self.route_rules()? {
+            let mut matches = vec![];
+            for m in rule.matches {
+                matches.push(to_match(m)?);
+            }
+            let backends = if rule.backends.is_empty() {
+                vec![HttpRouteBackend::default()]
+            } else {
+                rule.backends
+            };
+            let backend_refs: Vec<Value> = backends
+                .into_iter()
+                .map(|b| {
+                    let mut backend = json!({
+                        "name": b.service_name.unwrap_or_else(|| self.instance_name.clone()),
+                        "port": b.port.unwrap_or(self.svc_port),
+                    });
+                    if let Some(weight) = b.weight {
+                        backend["weight"] = json!(weight);
+                    }
+                    backend
+                })
+                .collect();
+            let mut route_rule = json!({ "backendRefs": backend_refs });
+            if !matches.is_empty() {
+                route_rule["matches"] = json!(matches);
+            }
+            rules.push(route_rule);
+        }
+        let mut spec = json!({
+            "parentRefs": [parent],
+            "rules": rules,
+        });
+        if !self.hostnames.is_empty() {
+            spec["hostnames"] = json!(self.hostnames);
+        }
+        let mut metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+        });
+        if let Some(owner_ref) = self.owner_ref.as_ref() {
+            metadata["ownerReferences"] = serde_json::to_value(owner_ref)?;
+        }
+        Ok(json!({
+            "apiVersion": "gateway.networking.k8s.io/v1",
+            "kind": "HTTPRoute",
+            "metadata": metadata,
+            "spec": spec,
+        }))
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-http-route", self.instance_name)
+    }
+}
+
+/// to_match is the HTTPRoute match of a match property.
+fn to_match(m: HttpRouteMatch) -> Result<Value, failure::Error> {
+    let mut rendered = json!({});
+    if let Some(path) = m.path {
+        let path_type = m.path_type.unwrap_or_else(|| "PathPrefix".to_string());
+        if !PATH_TYPES.contains(&path_type.as_str()) {
+            return Err(format_err!(
+                "http-route path type {} is none of {}",
+                path_type,
+                PATH_TYPES.join(", ")
+            ));
+        }
+        rendered["path"] = json!({"type": path_type, "value": path});
+    }
+    if !m.headers.is_empty() {
+        let mut headers = vec![];
+        for header in m.headers {
+            let match_type = header.match_type.unwrap_or_else(|| "Exact".to_string());
+            if !HEADER_TYPES.contains(&match_type.as_str()) {
+                return Err(format_err!(
+                    "http-route header match type {} is none of {}",
+                    match_type,
+                    HEADER_TYPES.join(", ")
+                ));
+            }
+            headers.push(json!({
+                "type": match_type,
+                "name": header.name,
+                "value": header.value,
+            }));
+        }
+        rendered["headers"] = json!(headers);
+    }
+    if let Some(method) = m.method {
+        rendered["method"] = json!(method.to_uppercase());
+    }
+    Ok(rendered)
+}
+
+/// The resource of the HTTPRoutes in `ns`.
+fn http_route_resource(ns: &str) -> RawApi {
+    RawApi::customResource("httproutes")
+        .group("gateway.networking.k8s.io")
+        .version("v1")
+        .within(ns)
+}
+
+impl TraitImplementation for HttpRoute {
+    fn render(&self) -> RenderResult {
+        Ok(vec![self.to_http_route()?])
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let route = self.to_http_route()?;
+        apply(
+            &client,
+            &http_route_resource(ns),
+            self.kube_name().as_str(),
+            &route,
+        )
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let req = http_route_resource(ns).delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // Only servers have a Service to route to.
+        name == SERVER_NAME || name == SINGLETON_SERVER_NAME
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "httproute/".to_string() + self.kube_name().as_str();
+        let req = match http_route_resource(ns).get(self.kube_name().as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let route: Value = match client.timed_request::<Value>(req) {
+            Ok(route) => route,
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("HTTPRoute not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        // Whether the Gateway accepted the route.
+        let accepted = route["status"]["parents"]
+            .as_array()?
+            .iter()
+            .flat_map(|p| p["conditions"].as_array().cloned().unwrap_or_default())
+            .find(|c| c["type"] == "Accepted")?;
+        let state = if accepted["status"] == "True" {
+            "accepted".to_string()
+        } else {
+            format!(
+                "not accepted: {}",
+                accepted["message"].as_str().unwrap_or_default()
+            )
+        };
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
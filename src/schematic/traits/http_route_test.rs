@@ -0,0 +1,268 @@
+use crate::schematic::traits::*;
+use crate::workload_type::{SERVER_NAME, SINGLETON_SERVER_NAME, WORKER_NAME};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_http_route_defaults() {
+    let http_route = HttpRoute {
+        name: "my-route".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        gateway: Some("public".into()),
+        gateway_namespace: None,
+        section_name: None,
+        hostnames: vec![],
+        svc_port: 80,
+        rules: None,
+        owner_ref: None,
+    };
+    let route = http_route.to_http_route().expect("route");
+    assert_eq!("gateway.networking.k8s.io/v1", route["apiVersion"]);
+    assert_eq!("HTTPRoute", route["kind"]);
+    assert_eq!("squid-trait-http-route", route["metadata"]["name"]);
+    assert!(route["metadata"]["ownerReferences"].is_null());
+    let spec = &route["spec"];
+    assert_eq!(json!([{"name": "public"}]), spec["parentRefs"]);
+    assert!(spec["hostnames"].is_null());
+    // Everything goes to the Service of the component.
+    assert_eq!(
+        json!([{"backendRefs": [{"name": "squid", "port": 80}]}]),
+        spec["rules"]
+    );
+}
+
+#[test]
+fn test_http_route_no_gateway() {
+    let route = HttpRoute {
+        name: "my-route".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        gateway: None,
+        gateway_namespace: None,
+        section_name: None,
+        hostnames: vec![],
+        svc_port: 80,
+        rules: None,
+        owner_ref: None,
+    };
+    assert_eq!(
+        "http-route of squid names no gateway to attach to",
+        route.to_http_route().unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn test_http_route_parent() {
+    let route = HttpRoute {
+        name: "my-route".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        gateway: Some("public".into()),
+        gateway_namespace: Some("gateways".into()),
+        section_name: Some("https".into()),
+        hostnames: vec!["shop.example.com".into(), "*.shop.example.com".into()],
+        svc_port: 80,
+        rules: None,
+        owner_ref: None,
+    };
+    let spec = &route.to_http_route().expect("route")["spec"];
+    assert_eq!(
+        json!([{"name": "public", "namespace": "gateways", "sectionName": "https"}]),
+        spec["parentRefs"]
+    );
+    assert_eq!(
+        json!(["shop.example.com", "*.shop.example.com"]),
+        spec["hostnames"]
+    );
+}
+
+#[test]
+fn test_http_route_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let route = HttpRoute {
+        name: "my-route".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        gateway: Some("public".into()),
+        gateway_namespace: None,
+        section_name: None,
+        hostnames: vec![],
+        svc_port: 80,
+        rules: None,
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    assert_eq!(
+        serde_json::to_value(vec![owner]).unwrap(),
+        route.to_http_route().expect("route")["metadata"]["ownerReferences"]
+    );
+}
+
+#[test]
+fn test_http_route_matches() {
+    let route = HttpRoute {
+        name: "my-route".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        gateway: Some("public".into()),
+        gateway_namespace: None,
+        section_name: None,
+        hostnames: vec![],
+        svc_port: 80,
+        rules: Some(json!([
+            {
+                "matches": [{
+                    "path": "/api",
+                    "headers": [
+                        {"name": "x-canary", "value": "true"},
+                        {"name": "x-tenant", "value": "^eu-", "type": "RegularExpression"}
+                    ],
+                    "method": "get"
+                }]
+            },
+            {"matches": [{"path": "/healthz", "pathType": "Exact"}, {"method": "HEAD"}]}
+        ])),
+        owner_ref: None,
+    };
+    let rules = &route.to_http_route().expect("route")["spec"]["rules"];
+    let api = &rules[0]["matches"][0];
+    assert_eq!(json!({"type": "PathPrefix", "value": "/api"}), api["path"]);
+    assert_eq!(
+        json!([
+            {"type": "Exact", "name": "x-canary", "value": "true"},
+            {"type": "RegularExpression", "name": "x-tenant", "value": "^eu-"}
+        ]),
+        api["headers"]
+    );
+    assert_eq!("GET", api["method"]);
+    // A match without a path matches every path.
+    assert_eq!(
+        json!([{"path": {"type": "Exact", "value": "/healthz"}}, {"method": "HEAD"}]),
+        rules[1]["matches"]
+    );
+    assert_eq!(
+        json!([{"name": "squid", "port": 80}]),
+        rules[1]["backendRefs"]
+    );
+}
+
+#[test]
+fn test_http_route_backends() {
+    let route = HttpRoute {
+        name: "my-route".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        gateway: Some("public".into()),
+        gateway_namespace: None,
+        section_name: None,
+        hostnames: vec![],
+        svc_port: 8080,
+        rules: Some(json!([{
+            "backends": [
+                {"weight": 90},
+                {"serviceName": "squid-v2", "port": 9090, "weight": 10}
+            ]
+        }])),
+        owner_ref: None,
+    };
+    let rule = &route.to_http_route().expect("route")["spec"]["rules"][0];
+    assert!(rule["matches"].is_null());
+    assert_eq!(
+        json!([
+            {"name": "squid", "port": 8080, "weight": 90},
+            {"name": "squid-v2", "port": 9090, "weight": 10}
+        ]),
+        rule["backendRefs"]
+    );
+}
+
+#[test]
+fn test_http_route_invalid_rules() {
+    let invalid = |rules: serde_json::Value| {
+        HttpRoute {
+            name: "my-route".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            gateway: Some("public".into()),
+            gateway_namespace: None,
+            section_name: None,
+            hostnames: vec![],
+            svc_port: 80,
+            rules: Some(rules),
+            owner_ref: None,
+        }
+        .to_http_route()
+        .unwrap_err()
+        .to_string()
+    };
+    assert_eq!(
+        "http-route path type Glob is none of Exact, PathPrefix, RegularExpression",
+        invalid(json!([{"matches": [{"path": "/", "pathType": "Glob"}]}]))
+    );
+    assert_eq!(
+        "http-route header match type Prefix is none of Exact, RegularExpression",
+        invalid(json!([{"matches": [{"headers": [
+            {"name": "x", "value": "y", "type": "Prefix"}
+        ]}]}]))
+    );
+    assert!(invalid(json!({"matches": []})).starts_with("invalid http-route rules of squid"));
+}
+
+#[test]
+fn test_http_route_v1alpha1() {
+    let http_route_alpha1_trait = TraitBinding {
+        name: String::from("http-route"),
+        parameter_values: None,
+        properties: Some(json!({
+            "gateway": "public",
+            "hostnames": ["shop.example.com"],
+            "servicePort": 8080,
+            "rules": [{"matches": [{"path": "/api"}]}]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&http_route_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let route = HttpRoute::from_properties(
+        "my-route".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+    );
+    assert_eq!(Some("public".to_string()), route.gateway);
+    assert_eq!(vec!["shop.example.com".to_string()], route.hostnames);
+    assert_eq!(8080, route.svc_port);
+    assert_eq!(1, route.route_rules().expect("rules").len());
+
+    // The Service port defaults to 80.
+    let route = HttpRoute::from_properties(
+        "my-route".into(),
+        "squid".into(),
+        "patsy".into(),
+        None,
+        None,
+    );
+    assert_eq!(80, route.svc_port);
+    assert_eq!(None, route.gateway);
+}
+
+#[test]
+fn test_http_route_workload_types() {
+    assert!(HttpRoute::supports_workload_type(SERVER_NAME));
+    assert!(HttpRoute::supports_workload_type(SINGLETON_SERVER_NAME));
+    assert!(!HttpRoute::supports_workload_type(WORKER_NAME));
+}
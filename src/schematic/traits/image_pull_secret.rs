@@ -0,0 +1,223 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, PodPatch, RenderResult};
+use k8s_openapi::api::core::v1 as core;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// An ImagePullSecret lets the pods of a component pull their images from private registries,
+/// with the imagePullSecrets of existing Secrets or of a docker-registry Secret the trait
+/// creates from the credentials of a registry.
+#[derive(Clone, Debug)]
+pub struct ImagePullSecret {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The names of existing docker-registry Secrets.
+    pub secret_names: Vec<String>,
+    /// The registry to create a Secret for, see `RegistryCredentials`.
+    pub registry: Option<Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+/// RegistryCredentials are the credentials of a private registry.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryCredentials {
+    /// The host of the registry, like `ghcr.io`.
+    pub server: String,
+    pub username: String,
+    pub password: String,
+    pub email: Option<String>,
+}
+
+impl ImagePullSecret {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        ImagePullSecret {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            secret_names: properties_map
+                .and_then(|map| map.get("secretNames"))
+                .and_then(|n| n.as_array())
+                .map(|n| {
+                    n.iter()
+                        .filter_map(|n| n.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            registry: properties_map
+                .and_then(|map| map.get("registry"))
+                .filter(|r| !r.is_null())
+                .cloned(),
+        }
+    }
+
+    fn credentials(&self) -> Result<Option<RegistryCredentials>, failure::Error> {
+        let credentials: RegistryCredentials = match self.registry.as_ref() {
+            Some(registry) => serde_json::from_value(registry.clone()).map_err(|e| {
+                format_err!(
+                    "invalid image-pull-secret registry of {}: {}",
+                    self.instance_name,
+                    e
+                )
+            })?,
+            None => return Ok(None),
+        };
+        if credentials.server.is_empty() || credentials.username.is_empty() {
+            return Err(format_err!(
+                "image-pull-secret registry of {} has no server or username",
+                self.instance_name
+            ));
+        }
+        Ok(Some(credentials))
+    }
+
+    /// secrets are the names of the Secrets the pods pull their images with, failing if
+    /// there are none.
+    pub fn secrets(&self) -> Result<Vec<String>, failure::Error> {
+        let mut secrets = self.secret_names.clone();
+        if self.credentials()?.is_some() {
+            secrets.push(self.kube_name());
+        }
+        if secrets.is_empty() {
+            return Err(format_err!(
+                "image-pull-secret of {} needs secretNames or a registry",
+                self.instance_name
+            ));
+        }
+        Ok(secrets)
+    }
+
+    /// to_secret renders the docker-registry Secret of the registry, if the trait has one.
+    pub fn to_secret(&self) -> Result<Option<core::Secret>, failure::Error> {
+        self.secrets()?;
+        let credentials = match self.credentials()? {
+            Some(credentials) => credentials,
+            None => return Ok(None),
+        };
+        let mut auth = json!({
+            "username": credentials.username,
+            "password": credentials.password,
+            "auth": base64::encode(&format!("{}:{}", credentials.username, credentials.password)),
+        });
+        if let Some(email) = credentials.email {
+            auth["email"] = json!(email);
+        }
+        let mut auths = Map::new();
+        auths.insert(credentials.server, auth);
+        let mut data = BTreeMap::new();
+        data.insert(
+            ".dockerconfigjson".to_string(),
+            json!({ "auths": auths }).to_string(),
+        );
+        Ok(Some(core::Secret {
+            metadata: Some(meta::ObjectMeta {
+                name: Some(self.kube_name()),
+                labels: Some(trait_labels(self.name.clone(), self.instance_name.clone())),
+                owner_references: self.owner_ref.clone(),
+                ..Default::default()
+            }),
+            string_data: Some(data),
+            type_: Some("kubernetes.io/dockerconfigjson".to_string()),
+            ..Default::default()
+        }))
+    }
+
+    /// to_pod_patch is the imagePullSecrets of the pods.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        Ok(PodPatch {
+            image_pull_secrets: self
+                .secrets()?
+                .into_iter()
+                .map(|name| core::LocalObjectReference { name: Some(name) })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-image-pull-secret", self.instance_name)
+    }
+}
+
+impl TraitImplementation for ImagePullSecret {
+    /// The Secret is created before the pods that pull their images with it.
+    fn pre_add(&self, ns: &str, client: APIClient) -> TraitResult {
+        if let Some(secret) = self.to_secret()? {
+            let resource = RawApi::v1Secret().within(ns);
+            apply(&client, &resource, self.kube_name().as_str(), &secret)?;
+        }
+        Ok(())
+    }
+    fn pre_modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.pre_add(ns, client)
+    }
+    /// There is nothing left to do once the pods name the Secrets.
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    /// Existing Secrets are left alone, the trait only deletes the one it creates.
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        if self.registry.is_none() {
+            return Ok(());
+        }
+        let req = RawApi::v1Secret()
+            .within(ns)
+            .delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+    fn render(&self) -> RenderResult {
+        let mut objects = vec![];
+        if let Some(secret) = self.to_secret()? {
+            objects.push(serde_json::to_value(secret)?);
+        }
+        Ok(objects)
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        for secret in self.secrets().ok()? {
+            let key = "secret/".to_string() + secret.as_str();
+            let state = match RawApi::v1Secret()
+                .within(ns)
+                .get(secret.as_str())
+                .map_err(failure::Error::from)
+                .and_then(|req| Ok(client.timed_request::<Value>(req)?))
+            {
+                Ok(_) => "created".to_string(),
+                Err(e) => {
+                    // An existing Secret is someone else's to create.
+                    if e.to_string().contains("NotFound") && secret == self.kube_name() {
+                        warn!(
+                            "Secret of image-pull-secret not found {}. Recreating ...",
+                            e
+                        );
+                        self.pre_add(ns, client.clone()).unwrap_or(());
+                    }
+                    e.to_string()
+                }
+            };
+            resource.insert(key, state);
+        }
+        Some(resource)
+    }
+}
@@ -0,0 +1,244 @@
+use crate::schematic::traits::*;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+fn pull_secret_names(pull_secret: &ImagePullSecret) -> Vec<String> {
+    pull_secret
+        .to_pod_patch()
+        .expect("pod patch")
+        .image_pull_secrets
+        .into_iter()
+        .filter_map(|s| s.name)
+        .collect()
+}
+
+#[test]
+fn test_image_pull_secret_registry() {
+    let image_pull_secret = ImagePullSecret {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        secret_names: vec![],
+        registry: Some(json!({"server": "ghcr.io", "username": "bot", "password": "s3cret"})),
+        owner_ref: None,
+    };
+    let secret = image_pull_secret
+        .to_secret()
+        .expect("secret")
+        .expect("registry secret");
+    let metadata = secret.metadata.expect("metadata");
+    assert_eq!(
+        Some("web-trait-image-pull-secret".to_string()),
+        metadata.name
+    );
+    assert!(metadata.owner_references.is_none());
+    assert_eq!(
+        Some("kubernetes.io/dockerconfigjson".to_string()),
+        secret.type_
+    );
+    let data = secret.string_data.expect("data");
+    let config: serde_json::Value =
+        serde_json::from_str(data[".dockerconfigjson"].as_str()).expect("docker config");
+    // The auth of a registry is the base64 of its username and password.
+    assert_eq!(
+        json!({"auths": {"ghcr.io": {
+            "username": "bot",
+            "password": "s3cret",
+            "auth": "Ym90OnMzY3JldA=="
+        }}}),
+        config
+    );
+    assert_eq!(
+        vec!["web-trait-image-pull-secret"],
+        pull_secret_names(&image_pull_secret)
+    );
+
+    let pull_secret = ImagePullSecret {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        secret_names: vec![],
+        registry: Some(json!({
+            "server": "registry.example.com:5000",
+            "username": "bot",
+            "password": "s3cret",
+            "email": "bot@example.com"
+        })),
+        owner_ref: None,
+    };
+    let secret = pull_secret
+        .to_secret()
+        .expect("secret")
+        .expect("registry secret");
+    let data = secret.string_data.expect("data");
+    let config: serde_json::Value =
+        serde_json::from_str(data[".dockerconfigjson"].as_str()).expect("docker config");
+    assert_eq!(
+        "bot@example.com",
+        config["auths"]["registry.example.com:5000"]["email"]
+    );
+}
+
+#[test]
+fn test_image_pull_secret_existing() {
+    let pull_secret = ImagePullSecret {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        secret_names: vec!["shared-registry".into(), "team-registry".into()],
+        registry: None,
+        owner_ref: None,
+    };
+    // Existing Secrets need nothing to create.
+    assert!(pull_secret.to_secret().expect("secret").is_none());
+    assert!(pull_secret.render().expect("render").is_empty());
+    assert_eq!(
+        vec!["shared-registry", "team-registry"],
+        pull_secret_names(&pull_secret)
+    );
+
+    // The Secret of the registry comes after the existing ones.
+    let pull_secret = ImagePullSecret {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        secret_names: vec!["shared-registry".into()],
+        registry: Some(json!({"server": "ghcr.io", "username": "bot", "password": "s3cret"})),
+        owner_ref: None,
+    };
+    assert_eq!(
+        vec!["shared-registry", "web-trait-image-pull-secret"],
+        pull_secret_names(&pull_secret)
+    );
+    assert_eq!(1, pull_secret.render().expect("render").len());
+}
+
+#[test]
+fn test_image_pull_secret_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "nginx-web".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let pull_secret = ImagePullSecret {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        secret_names: vec![],
+        registry: Some(json!({"server": "ghcr.io", "username": "bot", "password": "s3cret"})),
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    let objects = pull_secret.render().expect("render");
+    assert_eq!(1, objects.len());
+    assert_eq!(
+        serde_json::to_value(vec![owner]).unwrap(),
+        objects[0]["metadata"]["ownerReferences"]
+    );
+}
+
+#[test]
+fn test_image_pull_secret_invalid() {
+    let invalid = |pull_secret: ImagePullSecret| pull_secret.render().unwrap_err().to_string();
+    assert_eq!(
+        "image-pull-secret of web needs secretNames or a registry",
+        invalid(ImagePullSecret {
+            name: "my-app".into(),
+            instance_name: "web".into(),
+            component_name: "nginx".into(),
+            secret_names: vec![],
+            registry: None,
+            owner_ref: None,
+        })
+    );
+    assert!(invalid(ImagePullSecret {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        secret_names: vec![],
+        registry: Some(json!({"server": "ghcr.io", "username": "bot"})),
+        owner_ref: None,
+    })
+    .starts_with("invalid image-pull-secret registry of web"));
+    for registry in &[
+        json!({"server": "", "username": "bot", "password": "s3cret"}),
+        json!({"server": "ghcr.io", "username": "", "password": "s3cret"}),
+    ] {
+        assert_eq!(
+            "image-pull-secret registry of web has no server or username",
+            invalid(ImagePullSecret {
+                name: "my-app".into(),
+                instance_name: "web".into(),
+                component_name: "nginx".into(),
+                secret_names: vec![],
+                registry: Some(registry.clone()),
+                owner_ref: None,
+            })
+        );
+    }
+    // Existing Secrets don't make up for a broken registry.
+    assert!(ImagePullSecret {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        secret_names: vec!["shared-registry".into()],
+        registry: Some(json!("ghcr.io")),
+        owner_ref: None,
+    }
+    .to_pod_patch()
+    .is_err());
+}
+
+#[test]
+fn test_image_pull_secret_v1alpha1() {
+    let image_pull_secret_alpha1_trait = TraitBinding {
+        name: String::from("image-pull-secret"),
+        parameter_values: None,
+        properties: Some(json!({
+            "secretNames": ["shared-registry", 7, "team-registry"],
+            "registry": null
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&image_pull_secret_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let pull_secret = ImagePullSecret::from_properties(
+        "my-app".into(),
+        "web".into(),
+        "nginx".into(),
+        prop_map,
+        None,
+    );
+    // Names that aren't strings are skipped.
+    assert_eq!(
+        vec!["shared-registry".to_string(), "team-registry".to_string()],
+        pull_secret.secret_names
+    );
+    // A null registry is no registry, as if it were left out.
+    assert_eq!(None, pull_secret.registry);
+    assert!(pull_secret.render().expect("render").is_empty());
+
+    let properties = json!({
+        "registry": {"server": "ghcr.io", "username": "bot", "password": "s3cret"}
+    });
+    let pull_secret = ImagePullSecret::from_properties(
+        "my-app".into(),
+        "web".into(),
+        "nginx".into(),
+        properties.as_object(),
+        None,
+    );
+    assert!(pull_secret.secret_names.is_empty());
+    assert_eq!(
+        vec!["web-trait-image-pull-secret"],
+        pull_secret_names(&pull_secret)
+    );
+}
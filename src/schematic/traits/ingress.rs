@@ -1,11 +1,24 @@
+use crate::metrics::TimedRequest;
 use crate::schematic::traits::{util::*, TraitImplementation};
-use k8s_openapi::api::extensions::v1beta1 as ext;
-use k8s_openapi::apimachinery::pkg::{apis::meta::v1 as meta, util::intstr::IntOrString};
+use crate::workload_type::{apply, RenderResult};
+use kube::api::RawApi;
 use kube::client::APIClient;
 use log::warn;
 use serde_json::map::Map;
+use serde_json::{json, Value};
 use std::collections::BTreeMap;
 
+/// The annotation with the cert-manager Issuer of the certificates of an Ingress.
+pub const CERT_MANAGER_ISSUER: &str = "cert-manager.io/issuer";
+/// The annotation with the cert-manager ClusterIssuer of the certificates of an Ingress.
+pub const CERT_MANAGER_CLUSTER_ISSUER: &str = "cert-manager.io/cluster-issuer";
+
+/// The host of the rule of an ingress trait without a `hostname` or `rules`.
+const DEFAULT_HOST: &str = "example.com";
+
+/// The path types of an Ingress path.
+const PATH_TYPES: &[&str] = &["Exact", "Prefix", "ImplementationSpecific"];
+
 /// An Ingress trait creates an ingress point to the workload type to which it is attached.
 ///
 /// In Kubernetes, this will create an Ingress and attach it to the Service of a particular
@@ -18,8 +31,54 @@ pub struct Ingress {
     pub svc_port: i32,
     pub hostname: Option<String>,
     pub path: Option<String>,
+    /// The rules of the Ingress, instead of the single rule of `hostname` and `path`.
+    pub rules: Option<Value>,
+    /// The path type of paths that don't set one, `ImplementationSpecific` if unset.
+    pub path_type: Option<String>,
+    pub ingress_class_name: Option<String>,
+    /// Annotations of the Ingress, which configure controllers like nginx or the ALB controller.
+    pub annotations: BTreeMap<String, String>,
+    /// TLS termination of the hosts of the Ingress, see `IngressTls`.
+    pub tls: Option<Value>,
     pub owner_ref: OwnerRefs,
 }
+
+/// IngressTls terminates TLS for the hosts of the Ingress with the certificate in a Secret.
+/// With an `issuer` or `cluster_issuer`, cert-manager issues the certificate into the Secret.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IngressTls {
+    /// The Secret with the certificate, `<instance>-tls` if cert-manager issues it.
+    pub secret_name: Option<String>,
+    /// The hosts the certificate is for, the hosts of the rules if unset.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// The cert-manager Issuer in the namespace of the component to issue the certificate with.
+    pub issuer: Option<String>,
+    /// The cert-manager ClusterIssuer to issue the certificate with.
+    pub cluster_issuer: Option<String>,
+}
+
+/// IngressRule routes the requests for a host, or for any host without one.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IngressRule {
+    pub host: Option<String>,
+    #[serde(default)]
+    pub paths: Vec<IngressPath>,
+}
+
+/// IngressPath routes the requests for a path to a port of a Service, the port of the trait
+/// and the Service of the component by default.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IngressPath {
+    pub path: Option<String>,
+    pub path_type: Option<String>,
+    pub service_name: Option<String>,
+    pub service_port: Option<i32>,
+}
+
 impl Ingress {
     pub fn from_properties(
         name: String,
@@ -30,6 +89,9 @@ impl Ingress {
     ) -> Self {
         // Right now, we're relying on the higher level validation logic to validate types.
         let instancename = instance_name.clone();
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
         Ingress {
             name,
             instance_name,
@@ -41,84 +103,220 @@ impl Ingress {
             hostname: properties_map
                         .and_then(|map| map.get("hostname").map(|p| p.as_str().unwrap_or("").to_string())),
             path: properties_map
-                        .and_then(|map| map.get("path").map(|p| p.as_str().unwrap_or("").to_string()))
+                        .and_then(|map| map.get("path").map(|p| p.as_str().unwrap_or("").to_string())),
+            rules: properties_map.and_then(|map| map.get("rules")).cloned(),
+            tls: properties_map.and_then(|map| map.get("tls")).cloned(),
+            path_type: string("pathType"),
+            ingress_class_name: string("ingressClassName"),
+            annotations: properties_map
+                .and_then(|map| map.get("annotations"))
+                .and_then(|a| a.as_object())
+                .map(|a| {
+                    a.iter()
+                        .map(|(k, v)| match v {
+                            Value::String(s) => (k.clone(), s.clone()),
+                            other => (k.clone(), other.to_string()),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
         }
     }
-    pub fn to_ext_ingress(&self) -> ext::Ingress {
-        let labels = trait_labels(self.name.clone(), self.instance_name.clone());
-        ext::Ingress {
-            metadata: Some(meta::ObjectMeta {
-                //name: Some(format!("{}-trait-ingress", self.name.clone())),
-                name: Some(self.kube_name()),
-                labels: Some(labels),
-                owner_references: self.owner_ref.clone(),
-                ..Default::default()
-            }),
-            spec: Some(ext::IngressSpec {
-                rules: Some(vec![ext::IngressRule {
-                    host: self
-                        .hostname
-                        .clone()
-                        .or_else(|| Some("example.com".to_string())),
-                    http: Some(ext::HTTPIngressRuleValue {
-                        paths: vec![ext::HTTPIngressPath {
-                            backend: ext::IngressBackend {
-                                service_name: self.instance_name.clone(),
-                                service_port: IntOrString::Int(self.svc_port),
-                            },
-                            path: self.path.clone().or_else(|| Some("/".to_string())),
-                        }],
-                    }),
-                }]),
-                ..Default::default()
-            }),
-            ..Default::default()
+
+    /// ingress_rules are the rules of the trait, or the single rule of its `hostname` and `path`.
+    pub fn ingress_rules(&self) -> Result<Vec<IngressRule>, failure::Error> {
+        match self.rules.as_ref() {
+            Some(rules) => serde_json::from_value(rules.clone())
+                .map_err(|e| format_err!("invalid ingress rules of {}: {}", self.instance_name, e)),
+            None => Ok(vec![IngressRule {
+                host: self
+                    .hostname
+                    .clone()
+                    .or_else(|| Some(DEFAULT_HOST.to_string())),
+                paths: vec![IngressPath {
+                    path: self.path.clone(),
+                    ..Default::default()
+                }],
+            }]),
         }
     }
+
+    /// ingress_tls is the TLS termination of the trait, if it has one.
+    pub fn ingress_tls(&self) -> Result<Option<IngressTls>, failure::Error> {
+        let tls: IngressTls = match self.tls.as_ref() {
+            Some(tls) => serde_json::from_value(tls.clone())
+                .map_err(|e| format_err!("invalid ingress tls of {}: {}", self.instance_name, e))?,
+            None => return Ok(None),
+        };
+        match (&tls.secret_name, &tls.issuer, &tls.cluster_issuer) {
+            (_, Some(_), Some(_)) => Err(format_err!(
+                "ingress tls of {} has both an issuer and a clusterIssuer",
+                self.instance_name
+            )),
+            (None, None, None) => Err(format_err!(
+                "ingress tls of {} needs a secretName, an issuer or a clusterIssuer",
+                self.instance_name
+            )),
+            _ => Ok(Some(tls)),
+        }
+    }
+
+    /// to_ingress renders the networking.k8s.io/v1 Ingress. It is rendered as JSON, since the
+    /// Kubernetes API types rudr is built with predate path types and ingress classes.
+    pub fn to_ingress(&self) -> Result<Value, failure::Error> {
+        let mut rules = vec![];
+        let mut hosts = vec![];
+        for rule in self.ingress_rules()? {
+            let mut paths = vec![];
+            let rule_paths = if rule.paths.is_empty() {
+                vec![IngressPath::default()]
+            } else {
+                rule.paths
+            };
+            for path in rule_paths {
+                let path_type = path
+                    .path_type
+                    .or_else(|| self.path_type.clone())
+                    .unwrap_or_else(|| "ImplementationSpecific".to_string());
+                if !PATH_TYPES.contains(&path_type.as_str()) {
+                    return Err(format_err!(
+                        "ingress path type {} is none of {}",
+                        path_type,
+                        PATH_TYPES.join(", ")
+                    ));
+                }
+                paths.push(json!({
+                    "path": path.path.unwrap_or_else(|| "/".to_string()),
+                    "pathType": path_type,
+                    "backend": {
+                        "service": {
+                            "name": path.service_name.unwrap_or_else(|| self.instance_name.clone()),
+                            "port": {"number": path.service_port.unwrap_or(self.svc_port)},
+                        },
+                    },
+                }));
+            }
+            let mut ingress_rule = json!({"http": {"paths": paths}});
+            if let Some(host) = rule.host {
+                ingress_rule["host"] = json!(host);
+                hosts.push(host);
+            }
+            rules.push(ingress_rule);
+        }
+        let mut spec = json!({ "rules": rules });
+        if let Some(class) = self.ingress_class_name.as_ref() {
+            spec["ingressClassName"] = json!(class);
+        }
+        let mut annotations = self.annotations.clone();
+        if let Some(tls) = self.ingress_tls()? {
+            let secret_name = tls
+                .secret_name
+                .unwrap_or_else(|| format!("{}-tls", self.instance_name));
+            let tls_hosts = if tls.hosts.is_empty() {
+                hosts
+            } else {
+                tls.hosts
+            };
+            spec["tls"] = json!([{"hosts": tls_hosts, "secretName": secret_name}]);
+            // cert-manager issues the certificates of annotated Ingresses.
+            if let Some(issuer) = tls.issuer {
+                annotations.insert(CERT_MANAGER_ISSUER.to_string(), issuer);
+            }
+            if let Some(issuer) = tls.cluster_issuer {
+                annotations.insert(CERT_MANAGER_CLUSTER_ISSUER.to_string(), issuer);
+            }
+        }
+        let mut metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+        });
+        if !annotations.is_empty() {
+            metadata["annotations"] = json!(annotations);
+        }
+        if let Some(owner_ref) = self.owner_ref.as_ref() {
+            metadata["ownerReferences"] = serde_json::to_value(owner_ref)?;
+        }
+        Ok(json!({
+            "apiVersion": "networking.k8s.io/v1",
+            "kind": "Ingress",
+            "metadata": metadata,
+            "spec": spec,
+        }))
+    }
     fn kube_name(&self) -> String {
-        format!("{}-trait-ingress", self.instance_name)
+        ingress_name(self.instance_name.as_str())
+    }
+}
+
+/// ingress_hosts are the hosts an ingress trait with the properties `properties_map` routes,
+/// without the ones of invalid rules, which fail the trait on their own.
+pub fn ingress_hosts(properties_map: Option<&Map<String, Value>>) -> Vec<String> {
+    let map = match properties_map {
+        Some(map) => map,
+        None => return vec![DEFAULT_HOST.to_string()],
+    };
+    match map.get("rules") {
+        Some(rules) => serde_json::from_value::<Vec<IngressRule>>(rules.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|rule| rule.host)
+            .collect(),
+        None => vec![map
+            .get("hostname")
+            .and_then(|h| h.as_str())
+            .unwrap_or(DEFAULT_HOST)
+            .to_string()],
+    }
+}
+
+/// The name of the Ingress of the component instance `instance_name`.
+pub(super) fn ingress_name(instance_name: &str) -> String {
+    format!("{}-trait-ingress", instance_name)
+}
+
+/// The resource of the networking.k8s.io/v1 Ingresses in `ns`.
+pub(super) fn ingress_resource(ns: &str) -> RawApi {
+    RawApi {
+        group: "networking.k8s.io".into(),
+        version: "v1".into(),
+        resource: "ingresses".into(),
+        prefix: "apis".into(),
+        namespace: Some(ns.to_string()),
     }
 }
+
 impl TraitImplementation for Ingress {
+    fn render(&self) -> RenderResult {
+        Ok(vec![self.to_ingress()?])
+    }
     fn add(&self, ns: &str, client: APIClient) -> TraitResult {
-        let ingress = self.to_ext_ingress();
-        let (req, _) = ext::Ingress::create_namespaced_ingress(ns, &ingress, Default::default())?;
-        client.request::<ext::Ingress>(req)?;
-        Ok(())
+        let ingress = self.to_ingress()?;
+        apply(
+            &client,
+            &ingress_resource(ns),
+            self.kube_name().as_str(),
+            &ingress,
+        )
     }
     fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
-        let ingress = self.to_ext_ingress();
-        let values = serde_json::to_value(&ingress)?;
-        let (req, _) = ext::Ingress::patch_namespaced_ingress(
-            self.kube_name().as_str(),
-            ns,
-            &meta::Patch::StrategicMerge(values),
-            Default::default(),
-        )?;
-        client.request::<ext::Ingress>(req)?;
-        Ok(())
+        self.add(ns, client)
     }
     fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
-        let (req, _) =
-            ext::Ingress::delete_namespaced_ingress(self.name.as_str(), ns, Default::default())?;
-        client.request::<ext::Ingress>(req)?;
+        let req = ingress_resource(ns).delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
         Ok(())
     }
     fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
         let mut resource = BTreeMap::new();
         let key = "ingress/".to_string() + self.kube_name().as_str();
-        let (req, _) = match ext::Ingress::read_namespaced_ingress_status(
-            self.kube_name().as_str(),
-            ns,
-            Default::default(),
-        ) {
+        let req = match ingress_resource(ns).get_status(self.kube_name().as_str()) {
             Ok(req) => req,
             Err(e) => {
                 resource.insert(key.clone(), e.to_string());
                 return Some(resource);
             }
         };
-        let ingress = match client.request::<ext::Ingress>(req) {
+        let ingress = match client.timed_request::<Value>(req) {
             Ok(ingress) => ingress,
             Err(e) => {
                 if e.to_string().contains("NotFound") {
@@ -129,14 +327,22 @@ impl TraitImplementation for Ingress {
                 return Some(resource);
             }
         };
+        resource.insert(key, ingress_state(&ingress));
+        Some(resource)
+    }
+}
 
-        if let Some(status) = ingress.status {
-            if let Some(_lbstatus) = status.load_balancer {
-                //we can just put created to status, or combine Hostname and IP to status.
-                resource.insert(key.clone(), "created".to_string());
-                return Some(resource);
-            }
-        }
-        None
+/// ingress_state is the state of an Ingress: ready at the addresses of its load balancer once
+/// the ingress controller assigned them, waiting for one before.
+pub fn ingress_state(ingress: &Value) -> String {
+    let addresses: Vec<&str> = ingress["status"]["loadBalancer"]["ingress"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|lb| lb["ip"].as_str().or_else(|| lb["hostname"].as_str()))
+        .collect();
+    if addresses.is_empty() {
+        return "waiting for an address".to_string();
     }
+    format!("ready at {}", addresses.join(", "))
 }
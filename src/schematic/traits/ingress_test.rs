@@ -1,5 +1,5 @@
 use crate::schematic::traits::*;
-use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
 use serde_json::json;
 use serde_json::map::Map;
 
@@ -12,29 +12,57 @@ fn test_ingress_defaults() {
         svc_port: 8080,
         hostname: None,
         path: None,
+        rules: None,
+        path_type: None,
+        ingress_class_name: None,
+        annotations: Default::default(),
+        tls: None,
         owner_ref: None,
     };
 
-    let king = ig.to_ext_ingress();
-    let rule = king
-        .spec
-        .as_ref()
-        .expect("spec required")
-        .rules
-        .as_ref()
-        .expect("rules required")
-        .get(0)
-        .expect("one rule required");
-    assert_eq!(Some("example.com".into()), rule.host);
+    let king = ig.to_ingress().expect("ingress");
+    assert_eq!("networking.k8s.io/v1", king["apiVersion"]);
+    let rule = &king["spec"]["rules"][0];
+    assert_eq!("example.com", rule["host"]);
+    assert_eq!("/", rule["http"]["paths"][0]["path"]);
     assert_eq!(
-        Some("/".into()),
-        rule.http
-            .as_ref()
-            .expect("http required")
-            .paths
-            .get(0)
-            .expect("path required")
-            .path
+        "ImplementationSpecific",
+        rule["http"]["paths"][0]["pathType"]
+    );
+    assert!(king["spec"]["ingressClassName"].is_null());
+    assert!(king["metadata"]["annotations"].is_null());
+    assert!(king["spec"]["tls"].is_null());
+}
+
+#[test]
+fn test_ingress_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let ig = Ingress {
+        name: "my-ingress".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        svc_port: 8080,
+        hostname: None,
+        path: None,
+        rules: None,
+        path_type: None,
+        ingress_class_name: None,
+        annotations: Default::default(),
+        tls: None,
+        owner_ref: Some(vec![owner.clone()]),
+    };
+
+    let king = ig.to_ingress().expect("ingress");
+    assert_eq!(
+        serde_json::to_value(vec![owner]).unwrap(),
+        king["metadata"]["ownerReferences"]
     );
 }
 
@@ -48,6 +76,8 @@ fn test_ingress_v1alpha1() {
             "path": "/path",
             "servicePort": 9999
         })),
+        priority: None,
+        depends_on: None,
     };
 
     let serialized = serde_json::to_string(&ingress_alpha1_trait).unwrap();
@@ -63,42 +93,19 @@ fn test_ingress_v1alpha1() {
         None,
     );
 
-    let king = ig.to_ext_ingress();
-    assert_eq!(
-        "squid-trait-ingress",
-        king.metadata
-            .expect("md must exits")
-            .name
-            .expect("name must exist")
-    );
+    let king = ig.to_ingress().expect("ingress");
+    assert_eq!("squid-trait-ingress", king["metadata"]["name"]);
 
-    let spec = king.spec.expect("spec is required");
-    assert_eq!(1, spec.rules.as_ref().unwrap().len());
+    let rules = king["spec"]["rules"]
+        .as_array()
+        .expect("rules are required");
+    assert_eq!(1, rules.len());
+    assert_eq!("in.example.com", rules[0]["host"]);
 
-    let rule = spec
-        .rules
-        .as_ref()
-        .expect("rules are required")
-        .get(0)
-        .expect("a rule is required");
-    assert_eq!(
-        "in.example.com",
-        rule.host.as_ref().expect("host is required").to_string()
-    );
-
-    let path = rule
-        .http
-        .as_ref()
-        .expect("http is required")
-        .paths
-        .get(0)
-        .expect("at least one path is required");
-    assert_eq!(
-        "/path",
-        path.clone().path.expect("must be a path.path").as_str()
-    );
-    assert_eq!("squid", path.backend.service_name.as_str());
-    assert_eq!(IntOrString::Int(9999), path.backend.service_port);
+    let path = &rules[0]["http"]["paths"][0];
+    assert_eq!("/path", path["path"]);
+    assert_eq!("squid", path["backend"]["service"]["name"]);
+    assert_eq!(9999, path["backend"]["service"]["port"]["number"]);
 }
 
 #[test]
@@ -111,6 +118,8 @@ fn test_ingress_v1alpha1_invalid_service_port() {
             "path": "/path",
             "servicePort": 8080.01
         })),
+        priority: None,
+        depends_on: None,
     };
 
     let serialized = serde_json::to_string(&ingress_alpha1_trait).unwrap();
@@ -125,24 +134,9 @@ fn test_ingress_v1alpha1_invalid_service_port() {
         None,
     );
 
-    let king = ig.to_ext_ingress();
-    let spec = king.spec.expect("spec is required");
-
-    let rule = spec
-        .rules
-        .as_ref()
-        .expect("rules are required")
-        .get(0)
-        .expect("a rule is required");
-
-    let path = rule
-        .http
-        .as_ref()
-        .expect("http is required")
-        .paths
-        .get(0)
-        .expect("at least one path is required");
-    assert_eq!(IntOrString::Int(80), path.backend.service_port);
+    let king = ig.to_ingress().expect("ingress");
+    let path = &king["spec"]["rules"][0]["http"]["paths"][0];
+    assert_eq!(80, path["backend"]["service"]["port"]["number"]);
 }
 
 #[test]
@@ -153,6 +147,8 @@ fn test_ingress_v1alpha_defaults() {
         properties: Some(json!({
             "servicePort": 9999
         })),
+        priority: None,
+        depends_on: None,
     };
 
     let serialized = serde_json::to_string(&ingress_alpha1_trait).unwrap();
@@ -168,25 +164,150 @@ fn test_ingress_v1alpha_defaults() {
         None,
     );
 
-    let king = ig.to_ext_ingress();
-    let rule = king
-        .spec
-        .as_ref()
-        .expect("spec required")
-        .rules
-        .as_ref()
-        .expect("rules required")
-        .get(0)
-        .expect("one rule required");
-    assert_eq!(Some("example.com".into()), rule.host);
+    let king = ig.to_ingress().expect("ingress");
+    let rule = &king["spec"]["rules"][0];
+    assert_eq!("example.com", rule["host"]);
+    assert_eq!("/", rule["http"]["paths"][0]["path"]);
+}
+
+#[test]
+fn test_ingress_rules() {
+    let properties = json!({
+        "servicePort": 8080,
+        "pathType": "Prefix",
+        "ingressClassName": "alb",
+        "annotations": {
+            "alb.ingress.kubernetes.io/scheme": "internet-facing",
+            "nginx.ingress.kubernetes.io/proxy-body-size": 8
+        },
+        "rules": [
+            {
+                "host": "shop.example.com",
+                "paths": [
+                    {"path": "/"},
+                    {"path": "/api", "pathType": "Exact", "servicePort": 9090},
+                    {"path": "/static", "serviceName": "cdn-proxy"}
+                ]
+            },
+            {"host": "admin.example.com"}
+        ]
+    });
+    let ig = Ingress::from_properties(
+        "my-ingress".into(),
+        "squid".into(),
+        "patsy".into(),
+        properties.as_object(),
+        None,
+    );
+    let king = ig.to_ingress().expect("ingress");
+    assert_eq!("alb", king["spec"]["ingressClassName"]);
+    let annotations = &king["metadata"]["annotations"];
+    assert_eq!(
+        "internet-facing",
+        annotations["alb.ingress.kubernetes.io/scheme"]
+    );
+    // Annotation values are strings.
+    assert_eq!(
+        "8",
+        annotations["nginx.ingress.kubernetes.io/proxy-body-size"]
+    );
+
+    let rules = king["spec"]["rules"].as_array().expect("rules");
+    assert_eq!(2, rules.len());
+    let paths = rules[0]["http"]["paths"].as_array().expect("paths");
+    assert_eq!(3, paths.len());
+    assert_eq!("Prefix", paths[0]["pathType"]);
+    assert_eq!(8080, paths[0]["backend"]["service"]["port"]["number"]);
+    assert_eq!("Exact", paths[1]["pathType"]);
+    assert_eq!(9090, paths[1]["backend"]["service"]["port"]["number"]);
+    assert_eq!("cdn-proxy", paths[2]["backend"]["service"]["name"]);
+    // A rule without paths routes everything to the component.
+    assert_eq!("admin.example.com", rules[1]["host"]);
+    assert_eq!("/", rules[1]["http"]["paths"][0]["path"]);
+    assert_eq!(
+        "squid",
+        rules[1]["http"]["paths"][0]["backend"]["service"]["name"]
+    );
+}
+
+#[test]
+fn test_ingress_invalid_rules() {
+    let ig = |properties: serde_json::Value| {
+        Ingress::from_properties(
+            "my-ingress".into(),
+            "squid".into(),
+            "patsy".into(),
+            properties.as_object(),
+            None,
+        )
+    };
+    assert!(ig(json!({"pathType": "Regex"})).to_ingress().is_err());
+    assert!(ig(json!({"rules": {"host": "example.com"}}))
+        .to_ingress()
+        .is_err());
+}
+
+#[test]
+fn test_ingress_tls() {
+    let ig = |properties: serde_json::Value| {
+        Ingress::from_properties(
+            "my-ingress".into(),
+            "squid".into(),
+            "patsy".into(),
+            properties.as_object(),
+            None,
+        )
+    };
+    let king = ig(json!({
+        "rules": [{"host": "shop.example.com"}, {"host": "admin.example.com"}],
+        "tls": {"clusterIssuer": "letsencrypt"}
+    }))
+    .to_ingress()
+    .expect("ingress");
+    let tls = &king["spec"]["tls"][0];
+    assert_eq!("squid-tls", tls["secretName"]);
+    assert_eq!(
+        json!(["shop.example.com", "admin.example.com"]),
+        tls["hosts"]
+    );
+    assert_eq!(
+        "letsencrypt",
+        king["metadata"]["annotations"]["cert-manager.io/cluster-issuer"]
+    );
+
+    let king = ig(json!({
+        "hostname": "shop.example.com",
+        "tls": {"secretName": "wildcard", "hosts": ["*.example.com"]}
+    }))
+    .to_ingress()
+    .expect("ingress");
+    assert_eq!("wildcard", king["spec"]["tls"][0]["secretName"]);
+    assert_eq!(json!(["*.example.com"]), king["spec"]["tls"][0]["hosts"]);
+    assert!(king["metadata"]["annotations"].is_null());
+
+    assert!(ig(json!({"tls": {}})).to_ingress().is_err());
+    assert!(ig(json!({"tls": {"issuer": "a", "clusterIssuer": "b"}}))
+        .to_ingress()
+        .is_err());
+}
+
+#[test]
+fn test_ingress_state() {
+    assert_eq!(
+        "waiting for an address",
+        ingress_state(&json!({"status": {"loadBalancer": {}}}))
+    );
     assert_eq!(
-        Some("/".into()),
-        rule.http
-            .as_ref()
-            .expect("http required")
-            .paths
-            .get(0)
-            .expect("path required")
-            .path
+        "ready at 10.0.0.1, lb.example.com",
+        ingress_state(&json!({"status": {"loadBalancer": {"ingress": [
+            {"ip": "10.0.0.1"},
+            {"hostname": "lb.example.com"}
+        ]}}}))
     );
+    assert!(trait_ready(
+        ingress_state(&json!({"status": {"loadBalancer": {"ingress": [
+            {"ip": "10.0.0.1"}
+        ]}}}))
+        .as_str()
+    ));
 }
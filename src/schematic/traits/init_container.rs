@@ -0,0 +1,155 @@
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::PodPatch;
+use k8s_openapi::api::core::v1 as core;
+use kube::client::APIClient;
+use serde_json::map::Map;
+use serde_json::Value;
+
+/// The name of the volume the init containers share with the containers of the component.
+const SHARED_VOLUME: &str = "init-container-shared";
+
+/// An InitContainer runs containers to completion before the containers of a component start,
+/// like a schema migration, a download of files or a fix of their permissions.
+///
+/// With a `shared_path`, the init containers and the containers of the component share an
+/// emptyDir there, so that the component finds what they wrote.
+#[derive(Clone, Debug)]
+pub struct InitContainer {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The init containers in the order they run, see `InitContainerSpec`.
+    pub containers: Option<Value>,
+    /// The directory of the volume shared with the component.
+    pub shared_path: Option<String>,
+    /// The names of the containers of the component, which the init containers can't take.
+    pub component_containers: Vec<String>,
+}
+
+/// InitContainerSpec is an init container of an image.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InitContainerSpec {
+    pub name: String,
+    pub image: String,
+    pub command: Option<Vec<String>>,
+    pub args: Option<Vec<String>>,
+    #[serde(default)]
+    pub env: Vec<core::EnvVar>,
+    /// The volumes of the pods the container mounts, next to the shared volume.
+    #[serde(default)]
+    pub mounts: Vec<core::VolumeMount>,
+}
+
+impl InitContainer {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        component_containers: Vec<String>,
+    ) -> Self {
+        InitContainer {
+            name,
+            instance_name,
+            component_name,
+            component_containers,
+            containers: properties_map
+                .and_then(|map| map.get("containers"))
+                .cloned(),
+            shared_path: properties_map
+                .and_then(|map| map.get("sharedPath").and_then(|p| p.as_str()))
+                .map(String::from),
+        }
+    }
+
+    fn shared_mount(&self) -> Option<core::VolumeMount> {
+        self.shared_path
+            .clone()
+            .map(|mount_path| core::VolumeMount {
+                name: SHARED_VOLUME.to_string(),
+                mount_path,
+                ..Default::default()
+            })
+    }
+
+    /// to_init_containers renders the init containers, which mount the shared volume.
+    pub fn to_init_containers(&self) -> Result<Vec<core::Container>, failure::Error> {
+        let specs: Vec<InitContainerSpec> = match self.containers.as_ref() {
+            Some(containers) => serde_json::from_value(containers.clone()).map_err(|e| {
+                format_err!("invalid init containers of {}: {}", self.instance_name, e)
+            })?,
+            None => vec![],
+        };
+        if specs.is_empty() {
+            return Err(format_err!(
+                "init-container of {} has no containers",
+                self.instance_name
+            ));
+        }
+        let mut names: Vec<&str> = vec![];
+        for spec in specs.iter() {
+            if names.contains(&spec.name.as_str()) || self.component_containers.contains(&spec.name)
+            {
+                return Err(format_err!(
+                    "init container {} of {} has the name of another container",
+                    spec.name,
+                    self.instance_name
+                ));
+            }
+            names.push(spec.name.as_str());
+        }
+        Ok(specs
+            .into_iter()
+            .map(|spec| {
+                let mut mounts = spec.mounts;
+                mounts.extend(self.shared_mount());
+                core::Container {
+                    name: spec.name,
+                    image: Some(spec.image),
+                    command: spec.command,
+                    args: spec.args,
+                    env: Some(spec.env).filter(|e| !e.is_empty()),
+                    volume_mounts: Some(mounts).filter(|m| !m.is_empty()),
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+
+    /// to_pod_patch is the init containers, and the volume they share with the component.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        let init_containers = self.to_init_containers()?;
+        Ok(match self.shared_mount() {
+            Some(mount) => PodPatch {
+                init_containers,
+                volumes: vec![core::Volume {
+                    name: SHARED_VOLUME.to_string(),
+                    empty_dir: Some(Default::default()),
+                    ..Default::default()
+                }],
+                volume_mounts: vec![mount],
+                ..Default::default()
+            },
+            None => PodPatch {
+                init_containers,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+impl TraitImplementation for InitContainer {
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn delete(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+}
@@ -0,0 +1,177 @@
+use crate::schematic::traits::*;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_init_container_defaults() {
+    let init_container = InitContainer {
+        name: "my-init".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        containers: Some(json!([{"name": "chown", "image": "busybox:1.36"}])),
+        shared_path: None,
+        component_containers: vec!["server".into()],
+    };
+    let patch = init_container.to_pod_patch().expect("pod patch");
+    assert_eq!(1, patch.init_containers.len());
+    let container = &patch.init_containers[0];
+    assert_eq!("chown", container.name);
+    assert_eq!(Some("busybox:1.36".to_string()), container.image);
+    assert!(container.command.is_none());
+    assert!(container.args.is_none());
+    assert!(container.env.is_none());
+    // Without a shared path there is no volume to share.
+    assert!(container.volume_mounts.is_none());
+    assert!(patch.volumes.is_empty());
+    assert!(patch.volume_mounts.is_empty());
+    assert!(patch.sidecars.is_empty());
+}
+
+#[test]
+fn test_init_container_order() {
+    let init = InitContainer {
+        name: "my-init".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        containers: Some(json!([
+            {
+                "name": "migrate",
+                "image": "flyway/flyway:10",
+                "args": ["migrate"],
+                "env": [{"name": "FLYWAY_URL", "value": "jdbc:postgresql://db/squid"}]
+            },
+            {
+                "name": "download",
+                "image": "curlimages/curl:8.5.0",
+                "command": ["curl", "-o", "/data/model.bin", "https://example.com/model.bin"]
+            }
+        ])),
+        shared_path: None,
+        component_containers: vec!["server".into()],
+    };
+    let containers = init.to_init_containers().expect("init containers");
+    // The init containers run in the order they are listed.
+    assert_eq!("migrate", containers[0].name);
+    assert_eq!(Some(vec!["migrate".to_string()]), containers[0].args);
+    let env = containers[0].env.as_ref().expect("env");
+    assert_eq!("FLYWAY_URL", env[0].name);
+    assert_eq!(Some("jdbc:postgresql://db/squid".to_string()), env[0].value);
+    assert_eq!("download", containers[1].name);
+    assert_eq!(
+        Some("curl".to_string()),
+        containers[1].command.as_ref().map(|c| c[0].clone())
+    );
+    assert!(containers[1].env.is_none());
+}
+
+#[test]
+fn test_init_container_shared_path() {
+    let init = InitContainer {
+        name: "my-init".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        containers: Some(json!([
+            {"name": "download", "image": "curlimages/curl:8.5.0"},
+            {
+                "name": "unpack",
+                "image": "busybox:1.36",
+                "mounts": [{"name": "cache", "mountPath": "/cache"}]
+            }
+        ])),
+        shared_path: Some("/data".into()),
+        component_containers: vec!["server".into()],
+    };
+    let patch = init.to_pod_patch().expect("pod patch");
+    // The init containers and the component share an emptyDir.
+    assert_eq!(1, patch.volumes.len());
+    assert_eq!("init-container-shared", patch.volumes[0].name);
+    assert!(patch.volumes[0].empty_dir.is_some());
+    assert_eq!("init-container-shared", patch.volume_mounts[0].name);
+    assert_eq!("/data", patch.volume_mounts[0].mount_path);
+    let mounts = patch.init_containers[0]
+        .volume_mounts
+        .as_ref()
+        .expect("mounts");
+    assert_eq!(1, mounts.len());
+    assert_eq!("/data", mounts[0].mount_path);
+    // The shared volume comes after the volumes the init container mounts itself.
+    let mounts = patch.init_containers[1]
+        .volume_mounts
+        .as_ref()
+        .expect("mounts");
+    assert_eq!("cache", mounts[0].name);
+    assert_eq!("init-container-shared", mounts[1].name);
+}
+
+#[test]
+fn test_init_container_invalid() {
+    let invalid = |containers: Option<serde_json::Value>| {
+        InitContainer {
+            name: "my-init".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            containers,
+            shared_path: None,
+            component_containers: vec!["server".into()],
+        }
+        .to_init_containers()
+        .unwrap_err()
+        .to_string()
+    };
+    assert_eq!("init-container of squid has no containers", invalid(None));
+    assert_eq!(
+        "init-container of squid has no containers",
+        invalid(Some(json!([])))
+    );
+    assert!(
+        invalid(Some(json!([{"name": "chown"}]))).starts_with("invalid init containers of squid")
+    );
+    assert_eq!(
+        "init container server of squid has the name of another container",
+        invalid(Some(json!([{"name": "server", "image": "busybox"}])))
+    );
+    assert_eq!(
+        "init container chown of squid has the name of another container",
+        invalid(Some(json!([
+            {"name": "chown", "image": "busybox"},
+            {"name": "chown", "image": "busybox"}
+        ])))
+    );
+}
+
+#[test]
+fn test_init_container_v1alpha1() {
+    let init_container_alpha1_trait = TraitBinding {
+        name: String::from("init-container"),
+        parameter_values: None,
+        properties: Some(json!({
+            "containers": [{"name": "migrate", "image": "flyway/flyway:10"}],
+            "sharedPath": "/data"
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&init_container_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let init = InitContainer::from_properties(
+        "my-init".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        vec!["server".into()],
+    );
+    assert_eq!(
+        Some(json!([{"name": "migrate", "image": "flyway/flyway:10"}])),
+        init.containers
+    );
+    assert_eq!(Some("/data".to_string()), init.shared_path);
+    assert_eq!(vec!["server".to_string()], init.component_containers);
+    assert_eq!(
+        "migrate",
+        init.to_init_containers().expect("init containers")[0].name
+    );
+}
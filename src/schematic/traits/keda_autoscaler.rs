@@ -0,0 +1,222 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, RenderResult, SERVER_NAME, WORKER_NAME};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The scalers with a `threshold` shortcut: the metadata key of their threshold, and the
+/// metadata they can't do without.
+const SCALERS: &[(&str, &str, &[&str])] = &[
+    (
+        "kafka",
+        "lagThreshold",
+        &["bootstrapServers", "consumerGroup", "topic"],
+    ),
+    ("aws-sqs-queue", "queueLength", &["queueURL", "awsRegion"]),
+    ("prometheus", "threshold", &["serverAddress", "query"]),
+];
+
+/// A KedaAutoscaler scales a component on events, like the depth of a queue, with a KEDA
+/// ScaledObject.
+///
+/// KEDA creates and drives the HorizontalPodAutoscaler of the Deployment of the component.
+#[derive(Clone, Debug)]
+pub struct KedaAutoscaler {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    pub minimum: Option<i64>,
+    pub maximum: Option<i64>,
+    pub polling_interval: Option<i64>,
+    pub cooldown_period: Option<i64>,
+    /// The KEDA triggers, each with the `type` of its scaler and its `metadata`.
+    pub triggers: Vec<Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+impl KedaAutoscaler {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let int = |key: &str| properties_map.and_then(|map| map.get(key).and_then(|p| p.as_i64()));
+        KedaAutoscaler {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            minimum: int("minimum"),
+            maximum: int("maximum"),
+            polling_interval: int("pollingInterval"),
+            cooldown_period: int("cooldownPeriod"),
+            triggers: properties_map
+                .and_then(|map| map.get("triggers"))
+                .and_then(|t| t.as_array())
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// to_scaled_object renders the ScaledObject, failing for triggers KEDA would reject.
+    pub fn to_scaled_object(&self) -> Result<Value, failure::Error> {
+        if self.triggers.is_empty() {
+            return Err(format_err!(
+                "keda-autoscaler of {} has no triggers",
+                self.instance_name
+            ));
+        }
+        let triggers = self
+            .triggers
+            .iter()
+            .map(to_trigger)
+            .collect::<Result<Vec<Value>, failure::Error>>()?;
+        let mut spec = json!({
+            "scaleTargetRef": {"name": self.instance_name},
+            "minReplicaCount": self.minimum.unwrap_or(0),
+            "maxReplicaCount": self.maximum.unwrap_or(10 + self.minimum.unwrap_or(0)),
+            "triggers": triggers,
+        });
+        if let Some(interval) = self.polling_interval {
+            spec["pollingInterval"] = json!(interval);
+        }
+        if let Some(period) = self.cooldown_period {
+            spec["cooldownPeriod"] = json!(period);
+        }
+        let mut metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+        });
+        if let Some(owner_ref) = self.owner_ref.as_ref() {
+            metadata["ownerReferences"] = serde_json::to_value(owner_ref)?;
+        }
+        Ok(json!({
+            "apiVersion": "keda.sh/v1alpha1",
+            "kind": "ScaledObject",
+            "metadata": metadata,
+            "spec": spec,
+        }))
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-keda-autoscaler", self.instance_name.as_str())
+    }
+}
+
+/// to_trigger is the KEDA trigger of a trigger property. The `threshold` of a known scaler goes
+/// into its metadata, and metadata values are strings, as KEDA wants them.
+fn to_trigger(property: &Value) -> Result<Value, failure::Error> {
+    let scaler = property["type"]
+        .as_str()
+        .ok_or_else(|| format_err!("keda-autoscaler trigger {} has no type", property))?;
+    let mut metadata: Map<String, Value> = property["metadata"]
+        .as_object()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(k, v)| match v {
+            Value::String(s) => (k, Value::String(s)),
+            other => (k, Value::String(other.to_string())),
+        })
+        .collect();
+    if let Some((_, threshold_key, required)) = SCALERS.iter().find(|(s, _, _)| *s == scaler) {
+        if let Some(threshold) = property.get("threshold").filter(|t| !t.is_null()) {
+            let threshold = match threshold {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            metadata.insert(threshold_key.to_string(), Value::String(threshold));
+        }
+        for key in required.iter() {
+            if !metadata.contains_key(*key) {
+                return Err(format_err!(
+                    "keda-autoscaler {} trigger needs the metadata {}",
+                    scaler,
+                    key
+                ));
+            }
+        }
+    }
+    let mut trigger = json!({"type": scaler, "metadata": metadata});
+    if let Some(auth) = property["authenticationRef"].as_str() {
+        trigger["authenticationRef"] = json!({ "name": auth });
+    }
+    Ok(trigger)
+}
+
+/// The resource of the KEDA ScaledObjects in `ns`.
+fn scaled_object_resource(ns: &str) -> RawApi {
+    RawApi::customResource("scaledobjects")
+        .group("keda.sh")
+        .version("v1alpha1")
+        .within(ns)
+}
+
+impl TraitImplementation for KedaAutoscaler {
+    fn render(&self) -> RenderResult {
+        Ok(vec![self.to_scaled_object()?])
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let scaled_object = self.to_scaled_object()?;
+        apply(
+            &client,
+            &scaled_object_resource(ns),
+            self.kube_name().as_str(),
+            &scaled_object,
+        )
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let req =
+            scaled_object_resource(ns).delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<serde_json::Value>(req)?;
+        Ok(())
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // ScaledObjects scale Deployments.
+        name == SERVER_NAME || name == WORKER_NAME
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "scaledobject/".to_string() + self.kube_name().as_str();
+        let req = match scaled_object_resource(ns).get(self.kube_name().as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let scaled_object: Value = match client.timed_request::<Value>(req) {
+            Ok(scaled_object) => scaled_object,
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("ScaledObject not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let ready = scaled_object["status"]["conditions"]
+            .as_array()
+            .and_then(|conditions| conditions.iter().find(|c| c["type"] == "Ready"))
+            .and_then(|ready| ready["status"].as_str())
+            .map(|status| {
+                if status == "True" {
+                    "ready"
+                } else {
+                    "not ready"
+                }
+            })?;
+        resource.insert(key, ready.to_string());
+        Some(resource)
+    }
+}
@@ -0,0 +1,296 @@
+use crate::schematic::traits::*;
+use crate::workload_type::{SERVER_NAME, SINGLETON_SERVER_NAME, TASK_NAME, WORKER_NAME};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_keda_autoscaler_defaults() {
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: None,
+        maximum: None,
+        polling_interval: None,
+        cooldown_period: None,
+        triggers: vec![json!({
+            "type": "prometheus",
+            "metadata": {
+                "serverAddress": "http://prometheus:9090",
+                "query": "sum(rate(http_requests_total[1m]))",
+                "threshold": "100"
+            }
+        })],
+        owner_ref: None,
+    };
+
+    let scaled_object = scaler.to_scaled_object().expect("scaled object");
+    assert_eq!("keda.sh/v1alpha1", scaled_object["apiVersion"]);
+    assert_eq!("ScaledObject", scaled_object["kind"]);
+    let metadata = &scaled_object["metadata"];
+    assert_eq!("instance-trait-keda-autoscaler", metadata["name"]);
+    assert_eq!("trait", metadata["labels"]["oam.dev/role"]);
+    assert!(metadata["ownerReferences"].is_null());
+    let spec = &scaled_object["spec"];
+    assert_eq!("instance", spec["scaleTargetRef"]["name"]);
+    assert_eq!(0, spec["minReplicaCount"]);
+    assert_eq!(10, spec["maxReplicaCount"]);
+    assert!(spec["pollingInterval"].is_null());
+    assert!(spec["cooldownPeriod"].is_null());
+    assert_eq!("100", spec["triggers"][0]["metadata"]["threshold"]);
+}
+
+#[test]
+fn test_keda_autoscaler_replicas() {
+    // Without a maximum there is room for ten replicas above the minimum.
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: Some(3),
+        maximum: None,
+        polling_interval: None,
+        cooldown_period: None,
+        triggers: vec![json!({
+            "type": "prometheus",
+            "metadata": {
+                "serverAddress": "http://prometheus:9090",
+                "query": "sum(rate(http_requests_total[1m]))",
+                "threshold": "100"
+            }
+        })],
+        owner_ref: None,
+    };
+    let spec = &scaler.to_scaled_object().expect("scaled object")["spec"];
+    assert_eq!(3, spec["minReplicaCount"]);
+    assert_eq!(13, spec["maxReplicaCount"]);
+
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: Some(1),
+        maximum: Some(30),
+        polling_interval: Some(15),
+        cooldown_period: Some(120),
+        triggers: vec![json!({
+            "type": "prometheus",
+            "metadata": {
+                "serverAddress": "http://prometheus:9090",
+                "query": "sum(rate(http_requests_total[1m]))",
+                "threshold": "100"
+            }
+        })],
+        owner_ref: None,
+    };
+    let spec = &scaler.to_scaled_object().expect("scaled object")["spec"];
+    assert_eq!(1, spec["minReplicaCount"]);
+    assert_eq!(30, spec["maxReplicaCount"]);
+    assert_eq!(15, spec["pollingInterval"]);
+    assert_eq!(120, spec["cooldownPeriod"]);
+}
+
+#[test]
+fn test_keda_autoscaler_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "component-instance".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: None,
+        maximum: None,
+        polling_interval: None,
+        cooldown_period: None,
+        triggers: vec![json!({
+            "type": "prometheus",
+            "metadata": {
+                "serverAddress": "http://prometheus:9090",
+                "query": "sum(rate(http_requests_total[1m]))",
+                "threshold": "100"
+            }
+        })],
+        owner_ref: Some(vec![owner.clone()]),
+    };
+
+    let scaled_object = scaler.to_scaled_object().expect("scaled object");
+    assert_eq!(
+        serde_json::to_value(vec![owner]).unwrap(),
+        scaled_object["metadata"]["ownerReferences"]
+    );
+}
+
+#[test]
+fn test_keda_autoscaler_thresholds() {
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: None,
+        maximum: None,
+        polling_interval: None,
+        cooldown_period: None,
+        triggers: vec![
+            json!({
+                "type": "aws-sqs-queue",
+                "threshold": 20,
+                "authenticationRef": "sqs-auth",
+                "metadata": {
+                    "queueURL": "https://sqs.eu-west-1.amazonaws.com/1234/orders",
+                    "awsRegion": "eu-west-1",
+                    "scaleOnInFlight": false
+                }
+            }),
+            json!({
+                "type": "kafka",
+                "threshold": "50",
+                "metadata": {
+                    "bootstrapServers": "kafka:9092",
+                    "consumerGroup": "shop",
+                    "topic": "orders",
+                    "lagThreshold": "5"
+                }
+            }),
+        ],
+        owner_ref: None,
+    };
+
+    let triggers = &scaler.to_scaled_object().expect("scaled object")["spec"]["triggers"];
+    let sqs = &triggers[0];
+    assert_eq!("aws-sqs-queue", sqs["type"]);
+    // KEDA wants the metadata as strings.
+    assert_eq!("20", sqs["metadata"]["queueLength"]);
+    assert_eq!("false", sqs["metadata"]["scaleOnInFlight"]);
+    assert_eq!("sqs-auth", sqs["authenticationRef"]["name"]);
+    // The threshold wins over the metadata of the scaler.
+    let kafka = &triggers[1];
+    assert_eq!("50", kafka["metadata"]["lagThreshold"]);
+    assert!(kafka["authenticationRef"].is_null());
+}
+
+#[test]
+fn test_keda_autoscaler_invalid_triggers() {
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: None,
+        maximum: None,
+        polling_interval: None,
+        cooldown_period: None,
+        triggers: vec![],
+        owner_ref: None,
+    };
+    assert_eq!(
+        "keda-autoscaler of instance has no triggers",
+        scaler.to_scaled_object().unwrap_err().to_string()
+    );
+
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: None,
+        maximum: None,
+        polling_interval: None,
+        cooldown_period: None,
+        triggers: vec![json!({"threshold": 5})],
+        owner_ref: None,
+    };
+    assert!(scaler.to_scaled_object().is_err());
+
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: None,
+        maximum: None,
+        polling_interval: None,
+        cooldown_period: None,
+        triggers: vec![json!({
+            "type": "kafka",
+            "threshold": 50,
+            "metadata": {"bootstrapServers": "kafka:9092", "topic": "orders"}
+        })],
+        owner_ref: None,
+    };
+    assert_eq!(
+        "keda-autoscaler kafka trigger needs the metadata consumerGroup",
+        scaler.to_scaled_object().unwrap_err().to_string()
+    );
+
+    // Scalers rudr doesn't know are passed on as they are, threshold included.
+    let scaler = KedaAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        minimum: None,
+        maximum: None,
+        polling_interval: None,
+        cooldown_period: None,
+        triggers: vec![json!({
+            "type": "rabbitmq",
+            "threshold": 20,
+            "metadata": {"queueName": "orders", "value": "20"}
+        })],
+        owner_ref: None,
+    };
+    let scaled_object = scaler.to_scaled_object().expect("scaled object");
+    let metadata = &scaled_object["spec"]["triggers"][0]["metadata"];
+    assert_eq!(json!({"queueName": "orders", "value": "20"}), *metadata);
+}
+
+#[test]
+fn test_keda_autoscaler_v1alpha1() {
+    let keda_alpha1_trait = TraitBinding {
+        name: String::from("keda-autoscaler"),
+        parameter_values: None,
+        properties: Some(json!({
+            "minimum": 2,
+            "maximum": 8,
+            "pollingInterval": 10,
+            "triggers": [{
+                "type": "aws-sqs-queue",
+                "threshold": 5,
+                "metadata": {"queueURL": "https://sqs/orders", "awsRegion": "eu-west-1"}
+            }]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&keda_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let scaler = KedaAutoscaler::from_properties(
+        "release".into(),
+        "instance".into(),
+        "component".into(),
+        prop_map,
+        None,
+    );
+    assert_eq!(Some(2), scaler.minimum);
+    assert_eq!(Some(8), scaler.maximum);
+    assert_eq!(Some(10), scaler.polling_interval);
+    assert_eq!(None, scaler.cooldown_period);
+    assert_eq!(1, scaler.triggers.len());
+}
+
+#[test]
+fn test_keda_autoscaler_workload_types() {
+    assert!(KedaAutoscaler::supports_workload_type(SERVER_NAME));
+    assert!(KedaAutoscaler::supports_workload_type(WORKER_NAME));
+    assert!(!KedaAutoscaler::supports_workload_type(
+        SINGLETON_SERVER_NAME
+    ));
+    assert!(!KedaAutoscaler::supports_workload_type(TASK_NAME));
+}
@@ -0,0 +1,357 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{
+    apply, PodPatch, RenderResult, SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_WORKER,
+    WORKER_NAME,
+};
+use k8s_openapi::api::core::v1 as core;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The agent that forwards logs with fluent-bit.
+pub const FLUENT_BIT: &str = "fluent-bit";
+/// The agent that forwards logs with vector.
+pub const VECTOR: &str = "vector";
+
+/// The name of the sidecar container.
+pub const SIDECAR_NAME: &str = "log-forwarder";
+const LOGS_VOLUME: &str = "log-forwarder-logs";
+const CONFIG_VOLUME: &str = "log-forwarder-config";
+/// Where the sidecar reads its configuration from.
+const CONFIG_PATH: &str = "/etc/log-forwarder";
+/// The parsers of the vector agent, and the VRL functions they parse with.
+const VECTOR_PARSERS: &[(&str, &str)] = &[
+    ("json", "parse_json"),
+    ("logfmt", "parse_logfmt"),
+    ("syslog", "parse_syslog"),
+];
+
+/// A LogForwarder ships the logs a component writes to files with a fluent-bit or vector
+/// sidecar container.
+///
+/// The containers of the component and the sidecar share a volume at `log_path`, and the
+/// configuration of the sidecar is a ConfigMap the trait creates.
+#[derive(Clone, Debug)]
+pub struct LogForwarder {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// `fluent-bit` or `vector`, `fluent-bit` if unset.
+    pub agent: Option<String>,
+    /// The image of the sidecar, the image of the agent if unset.
+    pub image: Option<String>,
+    /// The directory the component writes its logs to, `/var/log/app` if unset.
+    pub log_path: Option<String>,
+    /// The directory of the node to keep the logs in, instead of an emptyDir.
+    pub host_path: Option<String>,
+    /// The parser of the log lines.
+    pub parser: Option<String>,
+    /// The destination of the logs: the `type` of the output plugin or sink and its settings.
+    pub output: Option<Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+impl LogForwarder {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        LogForwarder {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            agent: string("agent"),
+            image: string("image"),
+            log_path: string("logPath"),
+            host_path: string("hostPath"),
+            parser: string("parser"),
+            output: properties_map
+                .and_then(|map| map.get("output"))
+                .filter(|o| !o.is_null())
+                .cloned(),
+        }
+    }
+
+    fn agent(&self) -> &str {
+        self.agent.as_deref().unwrap_or(FLUENT_BIT)
+    }
+
+    fn log_path(&self) -> String {
+        self.log_path
+            .clone()
+            .unwrap_or_else(|| "/var/log/app".to_string())
+    }
+
+    /// output is the settings of the output, and its type.
+    fn output(&self) -> Result<(String, Map<String, Value>), failure::Error> {
+        let mut output = self
+            .output
+            .as_ref()
+            .and_then(|o| o.as_object())
+            .cloned()
+            .ok_or_else(|| {
+                format_err!(
+                    "log-forwarder of {} has no output to forward logs to",
+                    self.instance_name
+                )
+            })?;
+        match output.remove("type") {
+            Some(Value::String(kind)) => Ok((kind, output)),
+            _ => Err(format_err!(
+                "log-forwarder output of {} has no type",
+                self.instance_name
+            )),
+        }
+    }
+
+    /// config is the name and content of the configuration file of the agent.
+    pub fn config(&self) -> Result<(String, String), failure::Error> {
+        let (kind, settings) = self.output()?;
+        let files = format!("{}/*.log", self.log_path());
+        match self.agent() {
+            FLUENT_BIT => {
+                let mut conf =
+                    "[SERVICE]\n    Flush 1\n    Parsers_File /fluent-bit/etc/parsers.conf\n\n"
+                        .to_string();
+                conf += &format!(
+                    "[INPUT]\n    Name tail\n    Path {}\n    Tag {}\n",
+                    files, self.instance_name
+                );
+                if let Some(parser) = self.parser.as_ref() {
+                    conf += &format!("    Parser {}\n", parser);
+                }
+                conf += &format!("\n[OUTPUT]\n    Name {}\n    Match *\n", kind);
+                for (key, value) in settings {
+                    let value = match value {
+                        Value::String(s) => s,
+                        Value::Number(_) | Value::Bool(_) => value.to_string(),
+                        _ => {
+                            return Err(format_err!(
+                            "fluent-bit output setting {} of {} isn't a string, number or boolean",
+                            key,
+                            self.instance_name
+                        ))
+                        }
+                    };
+                    conf += &format!("    {} {}\n", key, value);
+                }
+                Ok(("fluent-bit.conf".to_string(), conf))
+            }
+            VECTOR => {
+                let mut config = json!({
+                    "data_dir": "/var/lib/vector",
+                    "sources": {"app": {"type": "file", "include": [files]}},
+                });
+                let mut input = "app";
+                if let Some(parser) = self.parser.as_ref() {
+                    let function = VECTOR_PARSERS
+                        .iter()
+                        .find(|(p, _)| p == parser)
+                        .map(|(_, f)| f)
+                        .ok_or_else(|| {
+                            format_err!(
+                                "vector parser {} is none of {}",
+                                parser,
+                                VECTOR_PARSERS
+                                    .iter()
+                                    .map(|(p, _)| *p)
+                                    .collect::<Vec<&str>>()
+                                    .join(", ")
+                            )
+                        })?;
+                    config["transforms"] = json!({"parse": {
+                        "type": "remap",
+                        "inputs": ["app"],
+                        "source": format!(". = merge(., {}!(.message))", function),
+                    }});
+                    input = "parse";
+                }
+                let mut sink = settings;
+                sink.insert("type".to_string(), json!(kind));
+                sink.insert("inputs".to_string(), json!([input]));
+                config["sinks"] = json!({ "out": sink });
+                Ok((
+                    "vector.json".to_string(),
+                    serde_json::to_string_pretty(&config)?,
+                ))
+            }
+            other => Err(format_err!(
+                "log-forwarder agent {} is neither {} nor {}",
+                other,
+                FLUENT_BIT,
+                VECTOR
+            )),
+        }
+    }
+
+    /// to_config_map renders the ConfigMap with the configuration of the agent.
+    pub fn to_config_map(&self) -> Result<core::ConfigMap, failure::Error> {
+        let (file, content) = self.config()?;
+        let mut data = BTreeMap::new();
+        data.insert(file, content);
+        Ok(core::ConfigMap {
+            metadata: Some(meta::ObjectMeta {
+                name: Some(self.kube_name()),
+                labels: Some(trait_labels(self.name.clone(), self.instance_name.clone())),
+                owner_references: self.owner_ref.clone(),
+                ..Default::default()
+            }),
+            data: Some(data),
+            ..Default::default()
+        })
+    }
+
+    /// to_pod_patch is the sidecar, and the volumes it shares with the component.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        let (file, _) = self.config()?;
+        let config = format!("{}/{}", CONFIG_PATH, file);
+        let (image, command) = match self.agent() {
+            FLUENT_BIT => (
+                "cr.fluentbit.io/fluent/fluent-bit:2.2",
+                vec![
+                    "/fluent-bit/bin/fluent-bit".to_string(),
+                    "-c".to_string(),
+                    config,
+                ],
+            ),
+            _ => (
+                "timberio/vector:0.34.1-debian",
+                vec!["vector".to_string(), "--config".to_string(), config],
+            ),
+        };
+        let logs = match self.host_path.as_ref() {
+            Some(path) => core::Volume {
+                name: LOGS_VOLUME.to_string(),
+                host_path: Some(core::HostPathVolumeSource {
+                    path: path.clone(),
+                    type_: Some("DirectoryOrCreate".to_string()),
+                }),
+                ..Default::default()
+            },
+            None => core::Volume {
+                name: LOGS_VOLUME.to_string(),
+                empty_dir: Some(Default::default()),
+                ..Default::default()
+            },
+        };
+        let logs_mount = core::VolumeMount {
+            name: LOGS_VOLUME.to_string(),
+            mount_path: self.log_path(),
+            ..Default::default()
+        };
+        Ok(PodPatch {
+            sidecars: vec![core::Container {
+                name: SIDECAR_NAME.to_string(),
+                image: Some(self.image.clone().unwrap_or_else(|| image.to_string())),
+                command: Some(command),
+                volume_mounts: Some(vec![
+                    core::VolumeMount {
+                        read_only: Some(true),
+                        ..logs_mount.clone()
+                    },
+                    core::VolumeMount {
+                        name: CONFIG_VOLUME.to_string(),
+                        mount_path: CONFIG_PATH.to_string(),
+                        read_only: Some(true),
+                        ..Default::default()
+                    },
+                ]),
+                ..Default::default()
+            }],
+            volumes: vec![
+                logs,
+                core::Volume {
+                    name: CONFIG_VOLUME.to_string(),
+                    config_map: Some(core::ConfigMapVolumeSource {
+                        name: Some(self.kube_name()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ],
+            volume_mounts: vec![logs_mount],
+            ..Default::default()
+        })
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-log-forwarder", self.instance_name)
+    }
+}
+
+impl TraitImplementation for LogForwarder {
+    /// The ConfigMap is created before the pods that mount it.
+    fn pre_add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let config_map = self.to_config_map()?;
+        let resource = RawApi::v1ConfigMap().within(ns);
+        apply(&client, &resource, self.kube_name().as_str(), &config_map)
+    }
+    fn pre_modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.pre_add(ns, client)
+    }
+    /// There is nothing left to do once the workload runs the sidecar.
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let req = RawApi::v1ConfigMap()
+            .within(ns)
+            .delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // A sidecar that never exits would keep a task from completing.
+        name == SERVER_NAME
+            || name == SINGLETON_SERVER_NAME
+            || name == WORKER_NAME
+            || name == SINGLETON_WORKER
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+    fn render(&self) -> RenderResult {
+        Ok(vec![serde_json::to_value(self.to_config_map()?)?])
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "configmap/".to_string() + self.kube_name().as_str();
+        let req = match RawApi::v1ConfigMap()
+            .within(ns)
+            .get(self.kube_name().as_str())
+        {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let state = match client.timed_request::<Value>(req) {
+            Ok(_) => "created".to_string(),
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("Log forwarder ConfigMap not found {}. Recreating ...", e);
+                    self.pre_add(ns, client).unwrap_or(());
+                }
+                e.to_string()
+            }
+        };
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
@@ -0,0 +1,335 @@
+use crate::schematic::traits::*;
+use crate::workload_type::{SERVER_NAME, SINGLETON_WORKER, TASK_NAME, WORKER_NAME};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_log_forwarder_fluent_bit_config() {
+    let log_forwarder = LogForwarder {
+        name: "my-logs".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        agent: None,
+        image: None,
+        log_path: None,
+        host_path: None,
+        parser: None,
+        output: Some(json!({"type": "es", "Host": "elasticsearch", "Port": 9200, "tls": false})),
+        owner_ref: None,
+    };
+    let config_map = log_forwarder.to_config_map().expect("config map");
+    let metadata = config_map.metadata.expect("metadata");
+    assert_eq!(Some("squid-trait-log-forwarder".to_string()), metadata.name);
+    assert_eq!(None, metadata.owner_references);
+    let conf = config_map.data.expect("data")["fluent-bit.conf"].clone();
+    // fluent-bit tails the files of the component, tagged with its instance.
+    assert!(conf.contains("    Name tail\n    Path /var/log/app/*.log\n    Tag squid\n\n"));
+    // The output settings are passed on as they are.
+    assert!(conf.contains(
+        "[OUTPUT]\n    Name es\n    Match *\n    Host elasticsearch\n    Port 9200\n    tls false\n"
+    ));
+
+    let forwarder = LogForwarder {
+        name: "my-logs".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        agent: None,
+        image: None,
+        log_path: Some("/logs".into()),
+        host_path: None,
+        parser: Some("json".into()),
+        output: Some(json!({"type": "es", "Host": "elasticsearch", "Port": 9200, "tls": false})),
+        owner_ref: None,
+    };
+    let conf = forwarder.config().expect("config").1;
+    assert!(conf.contains("    Path /logs/*.log\n    Tag squid\n    Parser json\n"));
+}
+
+#[test]
+fn test_log_forwarder_sidecar() {
+    let log_forwarder = LogForwarder {
+        name: "my-logs".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        agent: None,
+        image: None,
+        log_path: None,
+        host_path: None,
+        parser: None,
+        output: Some(json!({"type": "es", "Host": "elasticsearch", "Port": 9200, "tls": false})),
+        owner_ref: None,
+    };
+    let patch = log_forwarder.to_pod_patch().expect("pod patch");
+    let sidecar = &patch.sidecars[0];
+    assert_eq!("log-forwarder", sidecar.name);
+    assert_eq!(
+        Some("cr.fluentbit.io/fluent/fluent-bit:2.2".to_string()),
+        sidecar.image
+    );
+    assert_eq!(
+        Some(vec![
+            "/fluent-bit/bin/fluent-bit".to_string(),
+            "-c".to_string(),
+            "/etc/log-forwarder/fluent-bit.conf".to_string()
+        ]),
+        sidecar.command
+    );
+    // The sidecar only reads the logs, the containers of the component write them.
+    let mounts = sidecar.volume_mounts.as_ref().expect("sidecar mounts");
+    assert_eq!("/var/log/app", mounts[0].mount_path);
+    assert_eq!(Some(true), mounts[0].read_only);
+    assert_eq!("/etc/log-forwarder", mounts[1].mount_path);
+    assert_eq!("/var/log/app", patch.volume_mounts[0].mount_path);
+    assert!(patch.volume_mounts[0].read_only.is_none());
+    assert!(patch.volumes[0].empty_dir.is_some());
+    assert_eq!(
+        Some("squid-trait-log-forwarder".to_string()),
+        patch.volumes[1]
+            .config_map
+            .as_ref()
+            .and_then(|c| c.name.clone())
+    );
+
+    let forwarder = LogForwarder {
+        name: "my-logs".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        agent: None,
+        image: Some("registry.example.com/fluent-bit:2.2-debug".into()),
+        log_path: None,
+        host_path: Some("/var/log/squid".into()),
+        parser: None,
+        output: Some(json!({"type": "es", "Host": "elasticsearch", "Port": 9200, "tls": false})),
+        owner_ref: None,
+    };
+    let patch = forwarder.to_pod_patch().expect("pod patch");
+    assert_eq!(
+        Some("registry.example.com/fluent-bit:2.2-debug".to_string()),
+        patch.sidecars[0].image
+    );
+    let host_path = patch.volumes[0].host_path.as_ref().expect("host path");
+    assert_eq!("/var/log/squid", host_path.path);
+    assert_eq!(Some("DirectoryOrCreate".to_string()), host_path.type_);
+    assert!(patch.volumes[0].empty_dir.is_none());
+}
+
+#[test]
+fn test_log_forwarder_vector() {
+    let forwarder = LogForwarder {
+        name: "my-logs".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        agent: Some("vector".into()),
+        image: None,
+        log_path: Some("/logs".into()),
+        host_path: None,
+        parser: Some("logfmt".into()),
+        output: Some(json!({"type": "loki", "endpoint": "http://loki:3100"})),
+        owner_ref: None,
+    };
+    let config_map = forwarder.to_config_map().expect("config map");
+    let config: serde_json::Value =
+        serde_json::from_str(&config_map.data.expect("data")["vector.json"]).expect("json");
+    assert_eq!(json!(["/logs/*.log"]), config["sources"]["app"]["include"]);
+    assert_eq!(
+        ". = merge(., parse_logfmt!(.message))",
+        config["transforms"]["parse"]["source"]
+    );
+    assert_eq!(
+        json!({"type": "loki", "endpoint": "http://loki:3100", "inputs": ["parse"]}),
+        config["sinks"]["out"]
+    );
+
+    let patch = forwarder.to_pod_patch().expect("pod patch");
+    assert_eq!(
+        Some("timberio/vector:0.34.1-debian".to_string()),
+        patch.sidecars[0].image
+    );
+    assert_eq!(
+        Some(vec![
+            "vector".to_string(),
+            "--config".to_string(),
+            "/etc/log-forwarder/vector.json".to_string()
+        ]),
+        patch.sidecars[0].command
+    );
+
+    // Without a parser the sink reads the lines as they are.
+    let forwarder = LogForwarder {
+        name: "my-logs".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        agent: Some("vector".into()),
+        image: None,
+        log_path: None,
+        host_path: None,
+        parser: None,
+        output: Some(json!({"type": "console", "encoding": {"codec": "json"}})),
+        owner_ref: None,
+    };
+    let (file, content) = forwarder.config().expect("config");
+    assert_eq!("vector.json", file);
+    let config: serde_json::Value = serde_json::from_str(&content).expect("json");
+    assert!(config["transforms"].is_null());
+    assert_eq!(json!(["app"]), config["sinks"]["out"]["inputs"]);
+    assert_eq!("json", config["sinks"]["out"]["encoding"]["codec"]);
+}
+
+#[test]
+fn test_log_forwarder_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let forwarder = LogForwarder {
+        name: "my-logs".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        agent: None,
+        image: None,
+        log_path: None,
+        host_path: None,
+        parser: None,
+        output: Some(json!({"type": "es", "Host": "elasticsearch", "Port": 9200, "tls": false})),
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    let metadata = forwarder
+        .to_config_map()
+        .expect("config map")
+        .metadata
+        .expect("metadata");
+    assert_eq!(Some(vec![owner]), metadata.owner_references);
+}
+
+#[test]
+fn test_log_forwarder_invalid() {
+    let invalid = |forwarder: LogForwarder| forwarder.to_config_map().unwrap_err().to_string();
+    assert_eq!(
+        "log-forwarder of squid has no output to forward logs to",
+        invalid(LogForwarder {
+            name: "my-logs".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            agent: None,
+            image: None,
+            log_path: None,
+            host_path: None,
+            parser: None,
+            output: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "log-forwarder output of squid has no type",
+        invalid(LogForwarder {
+            name: "my-logs".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            agent: None,
+            image: None,
+            log_path: None,
+            host_path: None,
+            parser: None,
+            output: Some(json!({"Host": "es"})),
+            owner_ref: None,
+        })
+    );
+    // fluent-bit settings are flat.
+    assert_eq!(
+        "fluent-bit output setting tls of squid isn't a string, number or boolean",
+        invalid(LogForwarder {
+            name: "my-logs".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            agent: None,
+            image: None,
+            log_path: None,
+            host_path: None,
+            parser: None,
+            output: Some(json!({"type": "es", "tls": {"verify": true}})),
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "vector parser apache is none of json, logfmt, syslog",
+        invalid(LogForwarder {
+            name: "my-logs".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            agent: Some("vector".into()),
+            image: None,
+            log_path: None,
+            host_path: None,
+            parser: Some("apache".into()),
+            output: Some(
+                json!({"type": "es", "Host": "elasticsearch", "Port": 9200, "tls": false})
+            ),
+            owner_ref: None,
+        })
+    );
+    let forwarder = LogForwarder {
+        name: "my-logs".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        agent: Some("logstash".into()),
+        image: None,
+        log_path: None,
+        host_path: None,
+        parser: None,
+        output: Some(json!({"type": "es", "Host": "elasticsearch", "Port": 9200, "tls": false})),
+        owner_ref: None,
+    };
+    assert_eq!(
+        "log-forwarder agent logstash is neither fluent-bit nor vector",
+        forwarder.to_pod_patch().unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn test_log_forwarder_v1alpha1() {
+    let log_forwarder_alpha1_trait = TraitBinding {
+        name: String::from("log-forwarder"),
+        parameter_values: None,
+        properties: Some(json!({
+            "agent": "vector",
+            "logPath": "/logs",
+            "hostPath": "/var/log/squid",
+            "parser": "json",
+            "output": null
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&log_forwarder_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let forwarder = LogForwarder::from_properties(
+        "my-logs".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+    );
+    assert_eq!(Some("vector".to_string()), forwarder.agent);
+    assert_eq!(Some("/logs".to_string()), forwarder.log_path);
+    assert_eq!(Some("/var/log/squid".to_string()), forwarder.host_path);
+    assert_eq!(Some("json".to_string()), forwarder.parser);
+    assert_eq!(None, forwarder.output);
+}
+
+#[test]
+fn test_log_forwarder_workload_types() {
+    for name in &[SERVER_NAME, WORKER_NAME, SINGLETON_WORKER] {
+        assert!(LogForwarder::supports_workload_type(name));
+    }
+    // The sidecar would keep the pods of a task running.
+    assert!(!LogForwarder::supports_workload_type(TASK_NAME));
+}
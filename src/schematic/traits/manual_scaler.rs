@@ -1,12 +1,15 @@
+use crate::kube_event;
+use crate::metrics::TimedRequest;
 use crate::schematic::traits::{util::*, TraitImplementation};
 use crate::workload_type::extended_workload::openfaas::KubeFaaS;
 use crate::workload_type::{SERVER_NAME, TASK_NAME, WORKER_NAME};
-use k8s_openapi::api::{apps::v1 as apps, batch::v1 as batch};
-use kube::api::{PatchParams, RawApi};
+use k8s_openapi::api::{apps::v1 as apps, batch::v1 as batch, core::v1 as core};
+use kube::api::{ListParams, ObjectList, PatchParams, RawApi};
 use kube::client::APIClient;
 use log::info;
 use log::warn;
 use serde_json::map::Map;
+use serde_json::{json, Value};
 use std::collections::BTreeMap;
 
 /// A manual scaler provides a way to manually scale replicable objects.
@@ -50,24 +53,46 @@ impl ManualScaler {
         // deployment with a particular Kubernetess name and update them appropriately.
         match self.workload_type.as_str() {
             SERVER_NAME | WORKER_NAME => {
-                let (req, _) = apps::Deployment::read_namespaced_deployment(
+                // An autoscaler of the Deployment would scale it back, so it is left to that.
+                if let Some(hpa) = self.deployment_autoscaler(ns, &client)? {
+                    warn!(
+                        "Not scaling {}, the HorizontalPodAutoscaler {} scales it",
+                        self.instance_name, hpa
+                    );
+                    let involved = core::ObjectReference {
+                        api_version: Some("apps/v1".to_string()),
+                        kind: Some("Deployment".to_string()),
+                        name: Some(self.instance_name.clone()),
+                        namespace: Some(ns.to_string()),
+                        ..Default::default()
+                    };
+                    let info = kube_event::Info {
+                        action: "scale".to_string(),
+                        message: format!(
+                            "manual-scaler of {} skipped, the HorizontalPodAutoscaler {} scales it",
+                            self.instance_name, hpa
+                        ),
+                        reason: "ScalingSkipped".to_string(),
+                    };
+                    if let Err(e) = kube_event::Event::new(client, ns.to_string())
+                        .push_event_message(kube_event::Type::Warning, info, involved)
+                    {
+                        warn!("Adding event for {} err {:?}", self.instance_name, e);
+                    }
+                    return Ok(());
+                }
+                // The scale subresource changes the replicas only, not the rest of the Deployment.
+                let req = RawApi::v1Deployment().within(ns).patch_scale(
                     self.instance_name.as_str(),
-                    ns,
-                    Default::default(),
+                    &PatchParams::default(),
+                    serde_json::to_vec(&self.to_scale_patch())?,
                 )?;
-                let res = client.request(req);
-                if let Ok(original) = res {
-                    let dep = self.scale_deployment(original);
-
-                    let (req2, _) = apps::Deployment::replace_namespaced_deployment(
-                        self.instance_name.as_str(),
-                        ns,
-                        &dep,
-                        Default::default(),
-                    )?;
-                    client.request::<apps::Deployment>(req2)?;
+                match client.timed_request::<Value>(req) {
+                    Ok(_) => Ok(()),
+                    // The Deployment isn't there yet, it is scaled with the next update.
+                    Err(e) if e.api_error().map(|e| e.code) == Some(404) => Ok(()),
+                    Err(e) => Err(e.into()),
                 }
-                Ok(())
             }
             TASK_NAME => {
                 // Scale jobs
@@ -76,7 +101,7 @@ impl ManualScaler {
                     ns,
                     Default::default(),
                 )?;
-                if let Ok(original) = client.request(jobreq) {
+                if let Ok(original) = client.timed_request(jobreq) {
                     let new_job = self.scale_job(original);
                     let (req2, _) = batch::Job::replace_namespaced_job(
                         self.instance_name.as_str(),
@@ -84,7 +109,7 @@ impl ManualScaler {
                         &new_job,
                         Default::default(),
                     )?;
-                    client.request::<batch::Job>(req2)?;
+                    client.timed_request::<batch::Job>(req2)?;
                 };
                 Ok(())
             }
@@ -95,7 +120,7 @@ impl ManualScaler {
                     .group("openfaas.com")
                     .within(ns);
                 let faas_req = faas_resource.get(self.instance_name.clone().as_str())?;
-                let mut openfaas: KubeFaaS = client.request(faas_req)?;
+                let mut openfaas: KubeFaaS = client.timed_request(faas_req)?;
                 let mut labels = openfaas.metadata.labels.clone();
                 labels.insert(
                     "com.openfaas.scale.min".to_string(),
@@ -107,7 +132,7 @@ impl ManualScaler {
                     &PatchParams::default(),
                     serde_json::to_vec(&openfaas)?,
                 )?;
-                let openfaas: KubeFaaS = client.request(faas_req)?;
+                let openfaas: KubeFaaS = client.timed_request(faas_req)?;
                 info!(
                     "openfass function {} was scaled to {}",
                     openfaas.metadata.name,
@@ -138,6 +163,33 @@ impl ManualScaler {
         }
     }
 
+    /// to_scale_patch is the merge patch of the scale subresource of a Deployment that sets
+    /// its replicas.
+    pub fn to_scale_patch(&self) -> Value {
+        json!({"spec": {"replicas": self.replica_count}})
+    }
+
+    /// deployment_autoscaler is the name of a HorizontalPodAutoscaler that scales the
+    /// Deployment of the component, like the one of an autoscaler trait or of KEDA.
+    fn deployment_autoscaler(
+        &self,
+        ns: &str,
+        client: &APIClient,
+    ) -> Result<Option<String>, failure::Error> {
+        let req = RawApi {
+            version: "v2".into(),
+            ..RawApi::v1HorizontalPodAutoscaler()
+        }
+        .within(ns)
+        .list(&ListParams::default())?;
+        let hpas = client.timed_request::<ObjectList<Value>>(req)?;
+        Ok(scaling_autoscaler(
+            &hpas.items,
+            "Deployment",
+            self.instance_name.as_str(),
+        ))
+    }
+
     pub fn scale_job(&self, job: batch::Job) -> batch::Job {
         batch::Job {
             spec: Some(batch::JobSpec {
@@ -150,6 +202,17 @@ impl ManualScaler {
     }
 }
 
+/// scaling_autoscaler is the name of the HorizontalPodAutoscaler of `hpas` that scales the
+/// `kind` called `name`, if there is one.
+pub fn scaling_autoscaler(hpas: &[Value], kind: &str, name: &str) -> Option<String> {
+    hpas.iter()
+        .find(|hpa| {
+            let target = &hpa["spec"]["scaleTargetRef"];
+            target["kind"] == kind && target["name"] == name
+        })
+        .and_then(|hpa| hpa["metadata"]["name"].as_str().map(String::from))
+}
+
 impl TraitImplementation for ManualScaler {
     fn add(&self, ns: &str, client: APIClient) -> TraitResult {
         self.scale(ns, client)
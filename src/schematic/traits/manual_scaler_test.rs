@@ -1,8 +1,8 @@
-use k8s_openapi::api::{apps::v1 as apps, batch::v1 as batch};
 use crate::{
-        schematic::traits::*,
-        workload_type::{SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_TASK_NAME, TASK_NAME},
+    schematic::traits::*,
+    workload_type::{SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_TASK_NAME, TASK_NAME},
 };
+use k8s_openapi::api::{apps::v1 as apps, batch::v1 as batch};
 use serde_json::json;
 use serde_json::map::Map;
 
@@ -71,16 +71,19 @@ fn test_manual_scaler_v1alpha1_properties() {
     };
 
     let manualscaler_alpha1_trait = TraitBinding {
-        name : String::from("manual-scaler"),
-		parameter_values: None,
+        name: String::from("manual-scaler"),
+        parameter_values: None,
         properties: Some(json!({
-		    "replicaCount": 3
-        }))
+            "replicaCount": 3
+        })),
+        priority: None,
+        depends_on: None,
     };
 
-	let serialized = serde_json::to_string(&manualscaler_alpha1_trait).unwrap();
+    let serialized = serde_json::to_string(&manualscaler_alpha1_trait).unwrap();
     let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
-	let prop_map : Option<&Map<String, serde_json::value::Value>> = deserialized_trait.properties.as_ref().unwrap().as_object();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
 
     let ms = ManualScaler::from_properties(
         "release".into(),
@@ -88,9 +91,38 @@ fn test_manual_scaler_v1alpha1_properties() {
         "component".into(),
         prop_map,
         None,
-		"core.oam.dev/v1alpha1.Task".into(),
+        "core.oam.dev/v1alpha1.Task".into(),
     );
 
     let second = ms.scale_job(first);
     assert_eq!(Some(3), second.spec.expect("spec is required").parallelism);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_scaling_autoscaler() {
+    let ms = ManualScaler {
+        name: "name".into(),
+        instance_name: "web".into(),
+        component_name: "comp_name".into(),
+        owner_ref: None,
+        replica_count: 4,
+        workload_type: SERVER_NAME.into(),
+    };
+    assert_eq!(json!({"spec": {"replicas": 4}}), ms.to_scale_patch());
+
+    let hpa = |name: &str, kind: &str, target: &str| {
+        json!({
+            "metadata": {"name": name},
+            "spec": {"scaleTargetRef": {"apiVersion": "apps/v1", "kind": kind, "name": target}},
+        })
+    };
+    let hpas = vec![
+        hpa("other", "Deployment", "api"),
+        hpa("web-trait-autoscaler", "Deployment", "web"),
+    ];
+    assert_eq!(
+        Some("web-trait-autoscaler".to_string()),
+        scaling_autoscaler(&hpas, "Deployment", "web")
+    );
+    assert_eq!(None, scaling_autoscaler(&hpas, "StatefulSet", "web"));
+}
@@ -0,0 +1,233 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, RenderResult, SERVER_NAME, SINGLETON_SERVER_NAME};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use regex::Regex;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The monitor that scrapes the Service of a component.
+pub const SERVICE_MONITOR: &str = "ServiceMonitor";
+/// The monitor that scrapes the pods of a component.
+pub const POD_MONITOR: &str = "PodMonitor";
+
+/// A MetricsMonitor has the Prometheus Operator scrape the metrics of a component, with a
+/// ServiceMonitor for servers and a PodMonitor for the others.
+#[derive(Clone, Debug)]
+pub struct MetricsMonitor {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// `ServiceMonitor` or `PodMonitor`, a ServiceMonitor for servers if unset.
+    pub monitor: Option<String>,
+    /// The name of the port to scrape, the port of the Service of the component if unset.
+    pub port: Option<String>,
+    pub path: Option<String>,
+    /// How often to scrape, like `30s`, the interval of Prometheus if unset.
+    pub interval: Option<String>,
+    /// The Prometheus relabel configs of the targets.
+    pub relabelings: Vec<Value>,
+    pub owner_ref: OwnerRefs,
+    pub workload_type: String,
+    /// The name of the first port of the component, which the Service of a server exposes.
+    pub service_port: Option<String>,
+}
+
+impl MetricsMonitor {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+        workload_type: String,
+        service_port: Option<String>,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        MetricsMonitor {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            workload_type,
+            service_port,
+            monitor: string("monitor"),
+            port: string("port"),
+            path: string("path"),
+            interval: string("interval"),
+            relabelings: properties_map
+                .and_then(|map| map.get("relabelings"))
+                .and_then(|r| r.as_array())
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// monitor_kind is the kind of the monitor of the trait.
+    fn monitor_kind(&self) -> &str {
+        match self.monitor.as_deref() {
+            Some(monitor) => monitor,
+            None => match self.workload_type.as_str() {
+                SERVER_NAME | SINGLETON_SERVER_NAME => SERVICE_MONITOR,
+                _ => POD_MONITOR,
+            },
+        }
+    }
+
+    /// to_monitor renders the monitoring.coreos.com/v1 ServiceMonitor or PodMonitor, which
+    /// selects the Service or pods of the component.
+    pub fn to_monitor(&self) -> Result<Value, failure::Error> {
+        lazy_static! {
+            static ref DURATION: Regex = Regex::new(r"^([0-9]+(ms|s|m|h|d|w|y))+$").unwrap();
+        }
+        let kind = self.monitor_kind();
+        let port = match (kind, self.port.as_ref()) {
+            (SERVICE_MONITOR, port) => {
+                let service_port = self
+                    .service_port
+                    .as_ref()
+                    .filter(|_| {
+                        self.workload_type == SERVER_NAME
+                            || self.workload_type == SINGLETON_SERVER_NAME
+                    })
+                    .ok_or_else(|| {
+                        format_err!(
+                            "metrics of {} can't use a ServiceMonitor, the component has no Service",
+                            self.instance_name
+                        )
+                    })?;
+                if port.map_or(false, |p| p != service_port) {
+                    return Err(format_err!(
+                        "the Service of {} only exposes the port {}, scrape the pods with a PodMonitor",
+                        self.instance_name,
+                        service_port
+                    ));
+                }
+                service_port.clone()
+            }
+            (POD_MONITOR, Some(port)) => port.clone(),
+            (POD_MONITOR, None) => self.service_port.clone().ok_or_else(|| {
+                format_err!("metrics of {} names no port to scrape", self.instance_name)
+            })?,
+            (other, _) => {
+                return Err(format_err!(
+                    "metrics monitor {} is neither {} nor {}",
+                    other,
+                    SERVICE_MONITOR,
+                    POD_MONITOR
+                ))
+            }
+        };
+        let mut endpoint = json!({
+            "port": port,
+            "path": self.path.clone().unwrap_or_else(|| "/metrics".to_string()),
+        });
+        if let Some(interval) = self.interval.as_ref() {
+            if !DURATION.is_match(interval) {
+                return Err(format_err!(
+                    "metrics interval {} of {} is no duration like 30s",
+                    interval,
+                    self.instance_name
+                ));
+            }
+            endpoint["interval"] = json!(interval);
+        }
+        if !self.relabelings.is_empty() {
+            endpoint["relabelings"] = json!(self.relabelings);
+        }
+        let endpoints = if kind == SERVICE_MONITOR {
+            "endpoints"
+        } else {
+            "podMetricsEndpoints"
+        };
+        let mut metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+        });
+        if let Some(owner_ref) = self.owner_ref.as_ref() {
+            metadata["ownerReferences"] = serde_json::to_value(owner_ref)?;
+        }
+        let mut spec = json!({
+            "selector": {
+                "matchLabels": instance_selector(self.name.clone(), self.instance_name.clone()),
+            },
+        });
+        spec[endpoints] = json!([endpoint]);
+        Ok(json!({
+            "apiVersion": "monitoring.coreos.com/v1",
+            "kind": kind,
+            "metadata": metadata,
+            "spec": spec,
+        }))
+    }
+
+    /// resource is the resource of the monitors of the trait in `ns`.
+    fn resource(&self, ns: &str) -> RawApi {
+        let plural = if self.monitor_kind() == POD_MONITOR {
+            "podmonitors"
+        } else {
+            "servicemonitors"
+        };
+        RawApi::customResource(plural)
+            .group("monitoring.coreos.com")
+            .version("v1")
+            .within(ns)
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-metrics", self.instance_name)
+    }
+}
+
+impl TraitImplementation for MetricsMonitor {
+    fn render(&self) -> RenderResult {
+        Ok(vec![self.to_monitor()?])
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let monitor = self.to_monitor()?;
+        apply(
+            &client,
+            &self.resource(ns),
+            self.kube_name().as_str(),
+            &monitor,
+        )
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let req = self
+            .resource(ns)
+            .delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = self.monitor_kind().to_lowercase() + "/" + self.kube_name().as_str();
+        let req = match self.resource(ns).get(self.kube_name().as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let state = match client.timed_request::<Value>(req) {
+            Ok(_) => "created".to_string(),
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("{} not found {}. Recreating ...", self.monitor_kind(), e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                e.to_string()
+            }
+        };
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
@@ -0,0 +1,312 @@
+use crate::schematic::traits::metrics_monitor::{POD_MONITOR, SERVICE_MONITOR};
+use crate::schematic::traits::*;
+use crate::workload_type::{SERVER_NAME, SINGLETON_SERVER_NAME, TASK_NAME, WORKER_NAME};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_metrics_service_monitor() {
+    let metrics_monitor = MetricsMonitor {
+        name: "my-metrics".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        monitor: None,
+        port: None,
+        path: None,
+        interval: None,
+        relabelings: vec![],
+        owner_ref: None,
+        workload_type: SERVER_NAME.into(),
+        service_port: Some("http".into()),
+    };
+    let monitor = metrics_monitor.to_monitor().expect("service monitor");
+    assert_eq!("monitoring.coreos.com/v1", monitor["apiVersion"]);
+    assert_eq!("ServiceMonitor", monitor["kind"]);
+    assert_eq!("squid-trait-metrics", monitor["metadata"]["name"]);
+    assert!(monitor["metadata"]["ownerReferences"].is_null());
+    // Servers are scraped through the port of their Service.
+    assert_eq!(
+        json!({
+            "selector": {"matchLabels": {
+                "app.kubernetes.io/name": "my-metrics",
+                "oam.dev/instance-name": "squid"
+            }},
+            "endpoints": [{"port": "http", "path": "/metrics"}]
+        }),
+        monitor["spec"]
+    );
+
+    let metrics = MetricsMonitor {
+        name: "my-metrics".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        monitor: Some(SERVICE_MONITOR.into()),
+        port: Some("http".into()),
+        path: Some("/stats".into()),
+        interval: Some("1m30s".into()),
+        relabelings: vec![json!({"action": "labeldrop", "regex": "pod_template_hash"})],
+        owner_ref: None,
+        workload_type: SINGLETON_SERVER_NAME.into(),
+        service_port: Some("http".into()),
+    };
+    assert_eq!(
+        json!([{
+            "port": "http",
+            "path": "/stats",
+            "interval": "1m30s",
+            "relabelings": [{"action": "labeldrop", "regex": "pod_template_hash"}]
+        }]),
+        metrics.to_monitor().expect("service monitor")["spec"]["endpoints"]
+    );
+}
+
+#[test]
+fn test_metrics_pod_monitor() {
+    // Workers have no Service, their pods are scraped on the port of the component.
+    let metrics = MetricsMonitor {
+        name: "my-metrics".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        monitor: None,
+        port: None,
+        path: None,
+        interval: None,
+        relabelings: vec![],
+        owner_ref: None,
+        workload_type: WORKER_NAME.into(),
+        service_port: Some("http".into()),
+    };
+    let monitor = metrics.to_monitor().expect("pod monitor");
+    assert_eq!("PodMonitor", monitor["kind"]);
+    assert!(monitor["spec"]["endpoints"].is_null());
+    assert_eq!(
+        json!([{"port": "http", "path": "/metrics"}]),
+        monitor["spec"]["podMetricsEndpoints"]
+    );
+
+    let metrics = MetricsMonitor {
+        name: "my-metrics".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        monitor: None,
+        port: Some("metrics".into()),
+        path: None,
+        interval: None,
+        relabelings: vec![],
+        owner_ref: None,
+        workload_type: TASK_NAME.into(),
+        service_port: None,
+    };
+    let monitor = metrics.to_monitor().expect("pod monitor");
+    assert_eq!("metrics", monitor["spec"]["podMetricsEndpoints"][0]["port"]);
+
+    // Servers scrape the ports their Service doesn't expose from the pods.
+    let metrics = MetricsMonitor {
+        name: "my-metrics".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        monitor: Some(POD_MONITOR.into()),
+        port: Some("metrics".into()),
+        path: None,
+        interval: None,
+        relabelings: vec![],
+        owner_ref: None,
+        workload_type: SERVER_NAME.into(),
+        service_port: Some("http".into()),
+    };
+    let monitor = metrics.to_monitor().expect("pod monitor");
+    assert_eq!("PodMonitor", monitor["kind"]);
+    assert_eq!("metrics", monitor["spec"]["podMetricsEndpoints"][0]["port"]);
+}
+
+#[test]
+fn test_metrics_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let metrics = MetricsMonitor {
+        name: "my-metrics".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        monitor: None,
+        port: None,
+        path: None,
+        interval: None,
+        relabelings: vec![],
+        owner_ref: Some(vec![owner.clone()]),
+        workload_type: SERVER_NAME.into(),
+        service_port: Some("http".into()),
+    };
+    assert_eq!(
+        serde_json::to_value(vec![owner]).unwrap(),
+        metrics.render().expect("render")[0]["metadata"]["ownerReferences"]
+    );
+}
+
+#[test]
+fn test_metrics_invalid() {
+    let invalid = |metrics: MetricsMonitor| metrics.render().unwrap_err().to_string();
+    assert_eq!(
+        "the Service of squid only exposes the port http, scrape the pods with a PodMonitor",
+        invalid(MetricsMonitor {
+            name: "my-metrics".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            monitor: None,
+            port: Some("metrics".into()),
+            path: None,
+            interval: None,
+            relabelings: vec![],
+            owner_ref: None,
+            workload_type: SERVER_NAME.into(),
+            service_port: Some("http".into()),
+        })
+    );
+    assert_eq!(
+        "metrics of squid can't use a ServiceMonitor, the component has no Service",
+        invalid(MetricsMonitor {
+            name: "my-metrics".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            monitor: Some(SERVICE_MONITOR.into()),
+            port: None,
+            path: None,
+            interval: None,
+            relabelings: vec![],
+            owner_ref: None,
+            workload_type: WORKER_NAME.into(),
+            service_port: Some("http".into()),
+        })
+    );
+    // A server without ports has no Service either.
+    assert_eq!(
+        "metrics of squid can't use a ServiceMonitor, the component has no Service",
+        invalid(MetricsMonitor {
+            name: "my-metrics".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            monitor: None,
+            port: None,
+            path: None,
+            interval: None,
+            relabelings: vec![],
+            owner_ref: None,
+            workload_type: SERVER_NAME.into(),
+            service_port: None,
+        })
+    );
+    assert_eq!(
+        "metrics of squid names no port to scrape",
+        invalid(MetricsMonitor {
+            name: "my-metrics".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            monitor: None,
+            port: None,
+            path: None,
+            interval: None,
+            relabelings: vec![],
+            owner_ref: None,
+            workload_type: WORKER_NAME.into(),
+            service_port: None,
+        })
+    );
+    for interval in &["often", "30", "30 s"] {
+        assert_eq!(
+            format!(
+                "metrics interval {} of squid is no duration like 30s",
+                interval
+            ),
+            invalid(MetricsMonitor {
+                name: "my-metrics".into(),
+                instance_name: "squid".into(),
+                component_name: "patsy".into(),
+                monitor: None,
+                port: None,
+                path: None,
+                interval: Some(interval.to_string()),
+                relabelings: vec![],
+                owner_ref: None,
+                workload_type: SERVER_NAME.into(),
+                service_port: Some("http".into()),
+            })
+        );
+    }
+    assert_eq!(
+        "metrics monitor Probe is neither ServiceMonitor nor PodMonitor",
+        invalid(MetricsMonitor {
+            name: "my-metrics".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            monitor: Some("Probe".into()),
+            port: None,
+            path: None,
+            interval: None,
+            relabelings: vec![],
+            owner_ref: None,
+            workload_type: SERVER_NAME.into(),
+            service_port: Some("http".into()),
+        })
+    );
+}
+
+#[test]
+fn test_metrics_v1alpha1() {
+    let metrics_alpha1_trait = TraitBinding {
+        name: String::from("metrics"),
+        parameter_values: None,
+        properties: Some(json!({
+            "monitor": "PodMonitor",
+            "port": "metrics",
+            "path": "/stats",
+            "interval": "15s",
+            "relabelings": [{"action": "labeldrop", "regex": "pod_template_hash"}]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&metrics_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let metrics = MetricsMonitor::from_properties(
+        "my-metrics".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+        SERVER_NAME.into(),
+        Some("http".into()),
+    );
+    assert_eq!(Some(POD_MONITOR.to_string()), metrics.monitor);
+    assert_eq!(Some("metrics".to_string()), metrics.port);
+    assert_eq!(Some("/stats".to_string()), metrics.path);
+    assert_eq!(Some("15s".to_string()), metrics.interval);
+    assert_eq!(
+        vec![json!({"action": "labeldrop", "regex": "pod_template_hash"})],
+        metrics.relabelings
+    );
+    assert_eq!(Some("http".to_string()), metrics.service_port);
+    assert!(metrics.render().is_ok());
+
+    // Relabelings that aren't a list are left out.
+    let properties = json!({"relabelings": {"action": "labeldrop"}});
+    let metrics = MetricsMonitor::from_properties(
+        "my-metrics".into(),
+        "squid".into(),
+        "patsy".into(),
+        properties.as_object(),
+        None,
+        SERVER_NAME.into(),
+        Some("http".into()),
+    );
+    assert!(metrics.relabelings.is_empty());
+}
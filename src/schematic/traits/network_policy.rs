@@ -0,0 +1,299 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, RenderResult};
+use k8s_openapi::api::networking::v1 as networking;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The label Kubernetes sets on every namespace to its name.
+pub const NAMESPACE_NAME_LABEL: &str = "kubernetes.io/metadata.name";
+
+/// The protocols of a NetworkPolicy port.
+const PROTOCOLS: &[&str] = &["TCP", "UDP", "SCTP"];
+
+/// A NetworkPolicy isolates the pods of a component, letting only the peers its rules allow
+/// connect to them, or be connected to by them.
+#[derive(Clone, Debug)]
+pub struct NetworkPolicy {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The rules of the traffic to the pods, see `NetworkPolicyRule`. Without them, the policy
+    /// leaves the traffic to the pods alone, and with no rules it denies all of it.
+    pub ingress: Option<Value>,
+    /// The rules of the traffic from the pods.
+    pub egress: Option<Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+/// NetworkPolicyRule allows the traffic with any of its peers on any of its ports. A rule
+/// without peers allows every peer, and one without ports every port.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkPolicyRule {
+    #[serde(default)]
+    pub peers: Vec<NetworkPolicyPeer>,
+    #[serde(default)]
+    pub ports: Vec<NetworkPolicyPort>,
+}
+
+/// NetworkPolicyPeer is the pods with some labels in some namespaces, or a block of addresses.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkPolicyPeer {
+    /// The name of the namespace of the pods.
+    pub namespace: Option<String>,
+    /// The labels of the namespaces of the pods.
+    pub namespace_labels: Option<BTreeMap<String, String>>,
+    /// The labels of the pods, in the namespace of the component unless a namespace is set.
+    pub pod_labels: Option<BTreeMap<String, String>>,
+    /// A block of addresses, like `10.0.0.0/16`, instead of pods.
+    pub cidr: Option<String>,
+    /// The blocks of `cidr` that aren't peers.
+    #[serde(default)]
+    pub except: Vec<String>,
+}
+
+/// NetworkPolicyPort is a port number or name of a protocol, TCP by default.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkPolicyPort {
+    pub port: Value,
+    pub protocol: Option<String>,
+}
+
+impl NetworkPolicy {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        NetworkPolicy {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            ingress: properties_map.and_then(|map| map.get("ingress")).cloned(),
+            egress: properties_map.and_then(|map| map.get("egress")).cloned(),
+        }
+    }
+
+    /// to_network_policy renders the NetworkPolicy of the pods of the component.
+    pub fn to_network_policy(&self) -> Result<networking::NetworkPolicy, failure::Error> {
+        let ingress = self.rules("ingress", self.ingress.as_ref())?;
+        let egress = self.rules("egress", self.egress.as_ref())?;
+        let mut policy_types = vec![];
+        if ingress.is_some() {
+            policy_types.push("Ingress".to_string());
+        }
+        if egress.is_some() {
+            policy_types.push("Egress".to_string());
+        }
+        if policy_types.is_empty() {
+            return Err(format_err!(
+                "network-policy of {} has neither ingress nor egress rules",
+                self.instance_name
+            ));
+        }
+        Ok(networking::NetworkPolicy {
+            metadata: Some(meta::ObjectMeta {
+                name: Some(self.kube_name()),
+                labels: Some(trait_labels(self.name.clone(), self.instance_name.clone())),
+                owner_references: self.owner_ref.clone(),
+                ..Default::default()
+            }),
+            spec: Some(networking::NetworkPolicySpec {
+                pod_selector: meta::LabelSelector {
+                    match_labels: Some(instance_selector(
+                        self.name.clone(),
+                        self.instance_name.clone(),
+                    )),
+                    ..Default::default()
+                },
+                ingress,
+                egress: egress.map(|rules| {
+                    rules
+                        .into_iter()
+                        .map(|rule| networking::NetworkPolicyEgressRule {
+                            to: rule.from,
+                            ports: rule.ports,
+                        })
+                        .collect()
+                }),
+                policy_types: Some(policy_types),
+            }),
+        })
+    }
+
+    /// rules are the `direction` rules of the trait, with their peers in `from`.
+    fn rules(
+        &self,
+        direction: &str,
+        rules: Option<&Value>,
+    ) -> Result<Option<Vec<networking::NetworkPolicyIngressRule>>, failure::Error> {
+        let rules: Vec<NetworkPolicyRule> = match rules {
+            Some(rules) => serde_json::from_value(rules.clone()).map_err(|e| {
+                format_err!(
+                    "invalid network-policy {} rules of {}: {}",
+                    direction,
+                    self.instance_name,
+                    e
+                )
+            })?,
+            None => return Ok(None),
+        };
+        let mut converted = vec![];
+        for rule in rules {
+            let peers = rule
+                .peers
+                .into_iter()
+                .map(to_peer)
+                .collect::<Result<Vec<_>, failure::Error>>()?;
+            let ports = rule
+                .ports
+                .into_iter()
+                .map(to_port)
+                .collect::<Result<Vec<_>, failure::Error>>()?;
+            converted.push(networking::NetworkPolicyIngressRule {
+                from: Some(peers).filter(|p| !p.is_empty()),
+                ports: Some(ports).filter(|p| !p.is_empty()),
+            });
+        }
+        Ok(Some(converted))
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-network-policy", self.instance_name)
+    }
+}
+
+/// to_peer is the NetworkPolicy peer of a peer property.
+fn to_peer(peer: NetworkPolicyPeer) -> Result<networking::NetworkPolicyPeer, failure::Error> {
+    if let Some(cidr) = peer.cidr {
+        if peer.namespace.is_some() || peer.namespace_labels.is_some() || peer.pod_labels.is_some()
+        {
+            return Err(format_err!(
+                "network-policy peer {} selects both addresses and pods",
+                cidr
+            ));
+        }
+        return Ok(networking::NetworkPolicyPeer {
+            ip_block: Some(networking::IPBlock {
+                cidr,
+                except: Some(peer.except).filter(|e| !e.is_empty()),
+            }),
+            ..Default::default()
+        });
+    }
+    let mut namespace_labels = peer.namespace_labels;
+    if let Some(namespace) = peer.namespace {
+        namespace_labels
+            .get_or_insert_with(BTreeMap::new)
+            .insert(NAMESPACE_NAME_LABEL.to_string(), namespace);
+    }
+    if namespace_labels.is_none() && peer.pod_labels.is_none() {
+        return Err(format_err!(
+            "network-policy peer selects neither namespaces, pods nor addresses"
+        ));
+    }
+    let selector = |labels: BTreeMap<String, String>| meta::LabelSelector {
+        match_labels: Some(labels),
+        ..Default::default()
+    };
+    Ok(networking::NetworkPolicyPeer {
+        namespace_selector: namespace_labels.map(selector),
+        pod_selector: peer.pod_labels.map(selector),
+        ..Default::default()
+    })
+}
+
+/// to_port is the NetworkPolicy port of a port property.
+fn to_port(port: NetworkPolicyPort) -> Result<networking::NetworkPolicyPort, failure::Error> {
+    let protocol = port.protocol.clone().unwrap_or_else(|| "TCP".to_string());
+    if !PROTOCOLS.contains(&protocol.as_str()) {
+        return Err(format_err!(
+            "network-policy protocol {} is none of {}",
+            protocol,
+            PROTOCOLS.join(", ")
+        ));
+    }
+    let number = match &port.port {
+        Value::Number(n) => n
+            .as_i64()
+            .filter(|n| *n > 0 && *n < 65536)
+            .map(|n| IntOrString::Int(n as i32)),
+        Value::String(s) if !s.is_empty() => Some(IntOrString::String(s.clone())),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        format_err!(
+            "network-policy port {} is neither a port number nor a name",
+            port.port
+        )
+    })?;
+    Ok(networking::NetworkPolicyPort {
+        port: Some(number),
+        protocol: Some(protocol),
+    })
+}
+
+impl TraitImplementation for NetworkPolicy {
+    fn render(&self) -> RenderResult {
+        Ok(vec![serde_json::to_value(self.to_network_policy()?)?])
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let policy = self.to_network_policy()?;
+        let resource = RawApi::v1NetworkPolicy().within(ns);
+        apply(&client, &resource, self.kube_name().as_str(), &policy)
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let (req, _) = networking::NetworkPolicy::delete_namespaced_network_policy(
+            self.kube_name().as_str(),
+            ns,
+            Default::default(),
+        )?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "networkpolicy/".to_string() + self.kube_name().as_str();
+        let req = match networking::NetworkPolicy::read_namespaced_network_policy(
+            self.kube_name().as_str(),
+            ns,
+            Default::default(),
+        ) {
+            Ok((req, _)) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let policy = match client.timed_request::<networking::NetworkPolicy>(req) {
+            Ok(policy) => policy,
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("NetworkPolicy not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        // NetworkPolicies have no status, this shows the traffic the policy restricts.
+        let policy_types = policy.spec.and_then(|s| s.policy_types).unwrap_or_default();
+        resource.insert(key, format!("restricts {}", policy_types.join(", ")));
+        Some(resource)
+    }
+}
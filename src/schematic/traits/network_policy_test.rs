@@ -0,0 +1,273 @@
+use crate::schematic::traits::*;
+use crate::workload_type::{SERVER_NAME, TASK_NAME, WORKER_NAME};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_network_policy_deny_ingress() {
+    let network_policy = NetworkPolicy {
+        name: "my-policy".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        ingress: Some(json!([])),
+        egress: None,
+        owner_ref: None,
+    };
+    let policy = network_policy.to_network_policy().expect("network policy");
+    let metadata = policy.metadata.expect("metadata");
+    assert_eq!(
+        Some("squid-trait-network-policy".to_string()),
+        metadata.name
+    );
+    assert_eq!(None, metadata.owner_references);
+    let spec = policy.spec.expect("spec");
+    let selector = spec.pod_selector.match_labels.expect("pod selector");
+    assert_eq!(
+        Some(&"my-policy".to_string()),
+        selector.get("app.kubernetes.io/name")
+    );
+    assert_eq!(
+        Some(&"squid".to_string()),
+        selector.get("oam.dev/instance-name")
+    );
+    // No ingress rules deny all the traffic to the pods, and egress is left alone.
+    assert_eq!(Some(vec!["Ingress".to_string()]), spec.policy_types);
+    assert_eq!(Some(vec![]), spec.ingress);
+    assert!(spec.egress.is_none());
+}
+
+#[test]
+fn test_network_policy_ingress_peers() {
+    let policy = NetworkPolicy {
+        name: "my-policy".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        ingress: Some(json!([{
+            "peers": [
+                {"podLabels": {"app": "frontend"}},
+                {"namespace": "monitoring", "podLabels": {"app": "prometheus"}},
+                {"namespaceLabels": {"team": "shop"}, "namespace": "shop-staging"}
+            ]
+        }])),
+        egress: None,
+        owner_ref: None,
+    };
+    let policy = policy.to_network_policy().expect("network policy");
+    let policy = serde_json::to_value(policy).unwrap();
+    let rule = &policy["spec"]["ingress"][0];
+    assert_eq!(
+        json!([
+            {"podSelector": {"matchLabels": {"app": "frontend"}}},
+            {
+                "namespaceSelector": {"matchLabels": {"kubernetes.io/metadata.name": "monitoring"}},
+                "podSelector": {"matchLabels": {"app": "prometheus"}}
+            },
+            {
+                "namespaceSelector": {"matchLabels": {
+                    "kubernetes.io/metadata.name": "shop-staging",
+                    "team": "shop"
+                }}
+            }
+        ]),
+        rule["from"]
+    );
+    // A rule without ports allows every port.
+    assert!(rule["ports"].is_null());
+}
+
+#[test]
+fn test_network_policy_ports() {
+    let policy = NetworkPolicy {
+        name: "my-policy".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        ingress: Some(json!([{
+            "ports": [
+                {"port": 8080},
+                {"port": "metrics", "protocol": "TCP"},
+                {"port": 5353, "protocol": "UDP"}
+            ]
+        }])),
+        egress: None,
+        owner_ref: None,
+    };
+    let spec = policy
+        .to_network_policy()
+        .expect("network policy")
+        .spec
+        .expect("spec");
+    let rule = &spec.ingress.expect("ingress")[0];
+    // A rule without peers allows every peer.
+    assert!(rule.from.is_none());
+    let ports = rule.ports.as_ref().expect("ports");
+    assert_eq!(Some(IntOrString::Int(8080)), ports[0].port);
+    assert_eq!(Some("TCP".to_string()), ports[0].protocol);
+    assert_eq!(Some(IntOrString::String("metrics".into())), ports[1].port);
+    assert_eq!(Some("UDP".to_string()), ports[2].protocol);
+}
+
+#[test]
+fn test_network_policy_egress() {
+    let policy = NetworkPolicy {
+        name: "my-policy".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        ingress: None,
+        egress: Some(json!([
+            {"peers": [{"cidr": "10.0.0.0/8", "except": ["10.1.0.0/16"]}]},
+            {"peers": [{"cidr": "192.168.0.0/16"}], "ports": [{"port": 53, "protocol": "UDP"}]}
+        ])),
+        owner_ref: None,
+    };
+    let policy = policy.to_network_policy().expect("network policy");
+    let policy = serde_json::to_value(policy).unwrap();
+    let spec = &policy["spec"];
+    // Ingress is left alone.
+    assert_eq!(json!(["Egress"]), spec["policyTypes"]);
+    assert!(spec["ingress"].is_null());
+    assert_eq!(
+        json!([
+            {"to": [{"ipBlock": {"cidr": "10.0.0.0/8", "except": ["10.1.0.0/16"]}}]},
+            {
+                "to": [{"ipBlock": {"cidr": "192.168.0.0/16"}}],
+                "ports": [{"port": 53, "protocol": "UDP"}]
+            }
+        ]),
+        spec["egress"]
+    );
+
+    let policy = NetworkPolicy {
+        name: "my-policy".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        ingress: Some(json!([])),
+        egress: Some(json!([])),
+        owner_ref: None,
+    };
+    let spec = policy
+        .to_network_policy()
+        .expect("network policy")
+        .spec
+        .expect("spec");
+    assert_eq!(
+        Some(vec!["Ingress".to_string(), "Egress".to_string()]),
+        spec.policy_types
+    );
+}
+
+#[test]
+fn test_network_policy_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let policy = NetworkPolicy {
+        name: "my-policy".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        ingress: Some(json!([])),
+        egress: None,
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    let metadata = policy
+        .to_network_policy()
+        .expect("network policy")
+        .metadata
+        .expect("metadata");
+    assert_eq!(Some(vec![owner]), metadata.owner_references);
+}
+
+#[test]
+fn test_network_policy_invalid() {
+    let invalid = |ingress: Option<serde_json::Value>, egress: Option<serde_json::Value>| {
+        NetworkPolicy {
+            name: "my-policy".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            ingress,
+            egress,
+            owner_ref: None,
+        }
+        .to_network_policy()
+        .unwrap_err()
+        .to_string()
+    };
+    assert_eq!(
+        "network-policy of squid has neither ingress nor egress rules",
+        invalid(None, None)
+    );
+    assert!(invalid(Some(json!({"peers": []})), None)
+        .starts_with("invalid network-policy ingress rules of squid"));
+    assert_eq!(
+        "network-policy peer selects neither namespaces, pods nor addresses",
+        invalid(Some(json!([{"peers": [{}]}])), None)
+    );
+    assert_eq!(
+        "network-policy peer 10.0.0.0/8 selects both addresses and pods",
+        invalid(
+            Some(json!([{"peers": [{"cidr": "10.0.0.0/8", "podLabels": {"app": "frontend"}}]}])),
+            None
+        )
+    );
+    for port in &[json!(0), json!(65536), json!(""), json!(true)] {
+        assert_eq!(
+            format!(
+                "network-policy port {} is neither a port number nor a name",
+                port
+            ),
+            invalid(None, Some(json!([{"ports": [{"port": port}]}])))
+        );
+    }
+    assert_eq!(
+        "network-policy protocol ICMP is none of TCP, UDP, SCTP",
+        invalid(
+            None,
+            Some(json!([{"ports": [{"port": 80, "protocol": "ICMP"}]}]))
+        )
+    );
+}
+
+#[test]
+fn test_network_policy_v1alpha1() {
+    let network_policy_alpha1_trait = TraitBinding {
+        name: String::from("network-policy"),
+        parameter_values: None,
+        properties: Some(json!({
+            "ingress": [{"peers": [{"podLabels": {"app": "frontend"}}]}]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&network_policy_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let policy = NetworkPolicy::from_properties(
+        "my-policy".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+    );
+    assert_eq!(
+        Some(json!([{"peers": [{"podLabels": {"app": "frontend"}}]}])),
+        policy.ingress
+    );
+    assert_eq!(None, policy.egress);
+}
+
+#[test]
+fn test_network_policy_workload_types() {
+    // Every workload has pods to isolate.
+    for name in &[SERVER_NAME, WORKER_NAME, TASK_NAME] {
+        assert!(NetworkPolicy::supports_workload_type(name));
+    }
+}
@@ -0,0 +1,270 @@
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::PodPatch;
+use k8s_openapi::api::core::v1 as core;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use kube::client::APIClient;
+use serde_json::map::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The operators of a node selector requirement.
+const OPERATORS: &[&str] = &["In", "NotIn", "Exists", "DoesNotExist", "Gt", "Lt"];
+
+/// The topology key of the anti-affinity, which spreads the pods across hosts.
+pub const HOSTNAME_TOPOLOGY_KEY: &str = "kubernetes.io/hostname";
+
+/// A Placement tells the scheduler which nodes the pods of a component run on: nodes with some
+/// labels, nodes matching required or preferred node affinity, and nodes or zones without other
+/// pods of the component.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The labels the nodes must have.
+    pub node_selector: Option<BTreeMap<String, String>>,
+    /// The requirements the nodes must all meet, see `NodeRequirement`.
+    pub required_node_affinity: Option<Value>,
+    /// The requirements that make nodes preferred, see `NodePreference`.
+    pub preferred_node_affinity: Option<Value>,
+    /// The anti-affinity of the pods of the component to each other, see `AntiAffinity`.
+    pub anti_affinity: Option<Value>,
+}
+
+/// NodeRequirement is a requirement of the value of a node label.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeRequirement {
+    pub key: String,
+    /// `In`, `NotIn`, `Exists`, `DoesNotExist`, `Gt` or `Lt`.
+    pub operator: String,
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+/// NodePreference prefers the nodes that meet all of its requirements by `weight`, from 1 to
+/// 100.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NodePreference {
+    pub weight: i32,
+    pub requirements: Vec<NodeRequirement>,
+}
+
+/// AntiAffinity keeps the pods of the component off the nodes of a topology, like a host or a
+/// zone, that already run one of them.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AntiAffinity {
+    /// The node label of the topology, `kubernetes.io/hostname` if unset.
+    pub topology_key: Option<String>,
+    /// Whether pods stay pending rather than share a topology, which the scheduler only avoids
+    /// otherwise.
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl Placement {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+    ) -> Self {
+        let value = |key: &str| {
+            properties_map
+                .and_then(|map| map.get(key))
+                .filter(|v| !v.is_null())
+                .cloned()
+        };
+        Placement {
+            name,
+            instance_name,
+            component_name,
+            node_selector: properties_map
+                .and_then(|map| map.get("nodeSelector"))
+                .and_then(|s| s.as_object())
+                .map(|s| {
+                    s.iter()
+                        .map(|(k, v)| (k.clone(), v.as_str().unwrap_or_default().to_string()))
+                        .collect()
+                }),
+            required_node_affinity: value("requiredNodeAffinity"),
+            preferred_node_affinity: value("preferredNodeAffinity"),
+            anti_affinity: value("antiAffinity"),
+        }
+    }
+
+    /// to_affinity renders the affinity of the pods, None if the trait has neither node
+    /// affinity nor anti-affinity.
+    pub fn to_affinity(&self) -> Result<Option<core::Affinity>, failure::Error> {
+        let required: Option<Vec<NodeRequirement>> =
+            self.parse("requiredNodeAffinity", self.required_node_affinity.as_ref())?;
+        let preferred: Option<Vec<NodePreference>> = self.parse(
+            "preferredNodeAffinity",
+            self.preferred_node_affinity.as_ref(),
+        )?;
+        let anti_affinity: Option<AntiAffinity> =
+            self.parse("antiAffinity", self.anti_affinity.as_ref())?;
+
+        let required = required
+            .map(|r| -> Result<core::NodeSelector, failure::Error> {
+                Ok(core::NodeSelector {
+                    node_selector_terms: vec![self.to_term(r)?],
+                })
+            })
+            .transpose()?;
+        let preferred = preferred
+            .map(|preferences| {
+                preferences
+                    .into_iter()
+                    .map(|p| {
+                        if p.weight < 1 || p.weight > 100 {
+                            return Err(format_err!(
+                                "placement preference weight of {} isn't from 1 to 100: {}",
+                                self.instance_name,
+                                p.weight
+                            ));
+                        }
+                        Ok(core::PreferredSchedulingTerm {
+                            weight: p.weight,
+                            preference: self.to_term(p.requirements)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, failure::Error>>()
+            })
+            .transpose()?;
+        let node_affinity = if required.is_some() || preferred.is_some() {
+            Some(core::NodeAffinity {
+                required_during_scheduling_ignored_during_execution: required,
+                preferred_during_scheduling_ignored_during_execution: preferred,
+            })
+        } else {
+            None
+        };
+        let pod_anti_affinity = anti_affinity.map(|a| {
+            let term = core::PodAffinityTerm {
+                label_selector: Some(meta::LabelSelector {
+                    match_labels: Some(instance_selector(
+                        self.name.clone(),
+                        self.instance_name.clone(),
+                    )),
+                    ..Default::default()
+                }),
+                topology_key: a
+                    .topology_key
+                    .unwrap_or_else(|| HOSTNAME_TOPOLOGY_KEY.to_string()),
+                ..Default::default()
+            };
+            if a.required {
+                core::PodAntiAffinity {
+                    required_during_scheduling_ignored_during_execution: Some(vec![term]),
+                    ..Default::default()
+                }
+            } else {
+                core::PodAntiAffinity {
+                    preferred_during_scheduling_ignored_during_execution: Some(vec![
+                        core::WeightedPodAffinityTerm {
+                            weight: 100,
+                            pod_affinity_term: term,
+                        },
+                    ]),
+                    ..Default::default()
+                }
+            }
+        });
+        if node_affinity.is_none() && pod_anti_affinity.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(core::Affinity {
+            node_affinity,
+            pod_anti_affinity,
+            ..Default::default()
+        }))
+    }
+
+    /// to_pod_patch is the node selector and affinity of the pods.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        let affinity = self.to_affinity()?;
+        let node_selector = self.node_selector.clone().unwrap_or_default();
+        if affinity.is_none() && node_selector.is_empty() {
+            return Err(format_err!(
+                "placement of {} has neither a node selector, node affinity nor anti-affinity",
+                self.instance_name
+            ));
+        }
+        Ok(PodPatch {
+            node_selector,
+            affinity,
+            ..Default::default()
+        })
+    }
+
+    /// to_term is the node selector term that requires all of `requirements`.
+    fn to_term(
+        &self,
+        requirements: Vec<NodeRequirement>,
+    ) -> Result<core::NodeSelectorTerm, failure::Error> {
+        if requirements.is_empty() {
+            return Err(format_err!(
+                "placement node affinity of {} has no requirements",
+                self.instance_name
+            ));
+        }
+        let expressions = requirements
+            .into_iter()
+            .map(|r| {
+                if !OPERATORS.contains(&r.operator.as_str()) {
+                    return Err(format_err!(
+                        "placement operator {} is none of {}",
+                        r.operator,
+                        OPERATORS.join(", ")
+                    ));
+                }
+                Ok(core::NodeSelectorRequirement {
+                    key: r.key,
+                    operator: r.operator,
+                    values: Some(r.values).filter(|v| !v.is_empty()),
+                })
+            })
+            .collect::<Result<Vec<_>, failure::Error>>()?;
+        Ok(core::NodeSelectorTerm {
+            match_expressions: Some(expressions),
+            ..Default::default()
+        })
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(
+        &self,
+        property: &str,
+        value: Option<&Value>,
+    ) -> Result<Option<T>, failure::Error> {
+        value
+            .map(|v| {
+                serde_json::from_value(v.clone()).map_err(|e| {
+                    format_err!(
+                        "invalid placement {} of {}: {}",
+                        property,
+                        self.instance_name,
+                        e
+                    )
+                })
+            })
+            .transpose()
+    }
+}
+
+impl TraitImplementation for Placement {
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn delete(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+}
@@ -0,0 +1,359 @@
+use crate::schematic::traits::placement::HOSTNAME_TOPOLOGY_KEY;
+use crate::schematic::traits::*;
+use serde_json::json;
+use serde_json::map::Map;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_placement_node_selector() {
+    let placement = Placement {
+        name: "my-placement".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        node_selector: Some(
+            vec![("pool".to_string(), "general".to_string())]
+                .into_iter()
+                .collect(),
+        ),
+        required_node_affinity: None,
+        preferred_node_affinity: None,
+        anti_affinity: None,
+    };
+    // Labels alone need no affinity.
+    assert!(placement.to_affinity().expect("affinity").is_none());
+    let patch = placement.to_pod_patch().expect("pod patch");
+    assert_eq!(1, patch.node_selector.len());
+    assert_eq!("general", patch.node_selector["pool"]);
+    assert!(patch.affinity.is_none());
+    assert!(patch.tolerations.is_empty());
+}
+
+#[test]
+fn test_placement_required_node_affinity() {
+    let placement = Placement {
+        name: "my-placement".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        node_selector: None,
+        required_node_affinity: Some(json!([
+            {"key": "kubernetes.io/arch", "operator": "In", "values": ["amd64", "arm64"]},
+            {"key": "spot", "operator": "DoesNotExist"},
+            {"key": "cores", "operator": "Gt", "values": ["4"]}
+        ])),
+        preferred_node_affinity: None,
+        anti_affinity: None,
+    };
+    let patch = placement.to_pod_patch().expect("pod patch");
+    assert!(patch.node_selector.is_empty());
+    let affinity = patch.affinity.expect("affinity");
+    assert!(affinity.pod_anti_affinity.is_none());
+    let node_affinity = affinity.node_affinity.expect("node affinity");
+    assert!(node_affinity
+        .preferred_during_scheduling_ignored_during_execution
+        .is_none());
+    let required = node_affinity
+        .required_during_scheduling_ignored_during_execution
+        .expect("required");
+    // The requirements are all of one term, nodes must meet every one of them.
+    assert_eq!(1, required.node_selector_terms.len());
+    let expressions = required.node_selector_terms[0]
+        .match_expressions
+        .as_ref()
+        .expect("expressions");
+    assert_eq!(3, expressions.len());
+    assert_eq!("kubernetes.io/arch", expressions[0].key);
+    assert_eq!("In", expressions[0].operator);
+    assert_eq!(
+        Some(vec!["amd64".to_string(), "arm64".to_string()]),
+        expressions[0].values
+    );
+    assert_eq!("DoesNotExist", expressions[1].operator);
+    assert!(expressions[1].values.is_none());
+    assert_eq!(Some(vec!["4".to_string()]), expressions[2].values);
+}
+
+#[test]
+fn test_placement_preferred_node_affinity() {
+    let placement = Placement {
+        name: "my-placement".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        node_selector: Some(
+            vec![("pool".to_string(), "general".to_string())]
+                .into_iter()
+                .collect(),
+        ),
+        required_node_affinity: None,
+        preferred_node_affinity: Some(json!([
+            {"weight": 100, "requirements": [{"key": "disk", "operator": "In", "values": ["ssd"]}]},
+            {"weight": 1, "requirements": [{"key": "zone", "operator": "NotIn", "values": ["a"]}]}
+        ])),
+        anti_affinity: None,
+    };
+    let patch = placement.to_pod_patch().expect("pod patch");
+    // The node selector and the affinity both hold.
+    assert_eq!("general", patch.node_selector["pool"]);
+    let node_affinity = patch
+        .affinity
+        .and_then(|a| a.node_affinity)
+        .expect("node affinity");
+    assert!(node_affinity
+        .required_during_scheduling_ignored_during_execution
+        .is_none());
+    let preferred = node_affinity
+        .preferred_during_scheduling_ignored_during_execution
+        .expect("preferred");
+    assert_eq!(2, preferred.len());
+    assert_eq!(100, preferred[0].weight);
+    assert_eq!(1, preferred[1].weight);
+    let expressions = preferred[1]
+        .preference
+        .match_expressions
+        .as_ref()
+        .expect("expressions");
+    assert_eq!("zone", expressions[0].key);
+    assert_eq!("NotIn", expressions[0].operator);
+}
+
+#[test]
+fn test_placement_anti_affinity() {
+    let placement = Placement {
+        name: "my-placement".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        node_selector: None,
+        required_node_affinity: None,
+        preferred_node_affinity: None,
+        anti_affinity: Some(json!({})),
+    };
+    let affinity = placement
+        .to_affinity()
+        .expect("affinity")
+        .expect("an affinity");
+    assert!(affinity.node_affinity.is_none());
+    let anti_affinity = affinity.pod_anti_affinity.expect("anti-affinity");
+    // The scheduler only avoids sharing a host, unless the anti-affinity is required.
+    assert!(anti_affinity
+        .required_during_scheduling_ignored_during_execution
+        .is_none());
+    let preferred = anti_affinity
+        .preferred_during_scheduling_ignored_during_execution
+        .expect("preferred");
+    assert_eq!(100, preferred[0].weight);
+    let term = &preferred[0].pod_affinity_term;
+    assert_eq!(HOSTNAME_TOPOLOGY_KEY, term.topology_key);
+    let labels = term
+        .label_selector
+        .as_ref()
+        .and_then(|s| s.match_labels.as_ref())
+        .expect("labels");
+    assert_eq!(
+        Some(&"my-placement".to_string()),
+        labels.get("app.kubernetes.io/name")
+    );
+    assert_eq!(
+        Some(&"squid".to_string()),
+        labels.get("oam.dev/instance-name")
+    );
+
+    let placement = Placement {
+        name: "my-placement".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        node_selector: Some(
+            vec![("pool".to_string(), "general".to_string())]
+                .into_iter()
+                .collect(),
+        ),
+        required_node_affinity: None,
+        preferred_node_affinity: None,
+        anti_affinity: Some(json!({
+            "topologyKey": "topology.kubernetes.io/zone",
+            "required": true
+        })),
+    };
+    let anti_affinity = placement
+        .to_affinity()
+        .expect("affinity")
+        .and_then(|a| a.pod_anti_affinity)
+        .expect("anti-affinity");
+    assert!(anti_affinity
+        .preferred_during_scheduling_ignored_during_execution
+        .is_none());
+    let required = anti_affinity
+        .required_during_scheduling_ignored_during_execution
+        .expect("required");
+    assert_eq!("topology.kubernetes.io/zone", required[0].topology_key);
+}
+
+#[test]
+fn test_placement_invalid() {
+    let invalid = |placement: Placement| placement.to_pod_patch().unwrap_err().to_string();
+    assert_eq!(
+        "placement of squid has neither a node selector, node affinity nor anti-affinity",
+        invalid(Placement {
+            name: "my-placement".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            node_selector: None,
+            required_node_affinity: None,
+            preferred_node_affinity: None,
+            anti_affinity: None,
+        })
+    );
+    assert_eq!(
+        "placement of squid has neither a node selector, node affinity nor anti-affinity",
+        invalid(Placement {
+            name: "my-placement".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            node_selector: Some(BTreeMap::new()),
+            required_node_affinity: None,
+            preferred_node_affinity: None,
+            anti_affinity: None,
+        })
+    );
+    assert_eq!(
+        "placement operator Is is none of In, NotIn, Exists, DoesNotExist, Gt, Lt",
+        invalid(Placement {
+            name: "my-placement".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            node_selector: Some(
+                vec![("pool".to_string(), "general".to_string())]
+                    .into_iter()
+                    .collect()
+            ),
+            required_node_affinity: Some(json!([
+                {"key": "pool", "operator": "Is", "values": ["gpu"]}
+            ])),
+            preferred_node_affinity: None,
+            anti_affinity: None,
+        })
+    );
+    assert_eq!(
+        "placement node affinity of squid has no requirements",
+        invalid(Placement {
+            name: "my-placement".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            node_selector: Some(
+                vec![("pool".to_string(), "general".to_string())]
+                    .into_iter()
+                    .collect()
+            ),
+            required_node_affinity: Some(json!([])),
+            preferred_node_affinity: None,
+            anti_affinity: None,
+        })
+    );
+    for weight in &[0, 101] {
+        assert_eq!(
+            format!(
+                "placement preference weight of squid isn't from 1 to 100: {}",
+                weight
+            ),
+            invalid(Placement {
+                name: "my-placement".into(),
+                instance_name: "squid".into(),
+                component_name: "patsy".into(),
+                node_selector: Some(
+                    vec![("pool".to_string(), "general".to_string())]
+                        .into_iter()
+                        .collect()
+                ),
+                required_node_affinity: None,
+                preferred_node_affinity: Some(json!([
+                    {"weight": weight, "requirements": [{"key": "pool", "operator": "Exists"}]}
+                ])),
+                anti_affinity: None,
+            })
+        );
+    }
+    assert_eq!(
+        "placement node affinity of squid has no requirements",
+        invalid(Placement {
+            name: "my-placement".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            node_selector: Some(
+                vec![("pool".to_string(), "general".to_string())]
+                    .into_iter()
+                    .collect()
+            ),
+            required_node_affinity: None,
+            preferred_node_affinity: Some(json!([{"weight": 10, "requirements": []}])),
+            anti_affinity: None,
+        })
+    );
+    assert!(invalid(Placement {
+        name: "my-placement".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        node_selector: Some(
+            vec![("pool".to_string(), "general".to_string())]
+                .into_iter()
+                .collect()
+        ),
+        required_node_affinity: None,
+        preferred_node_affinity: None,
+        anti_affinity: Some(json!({"required": "yes"})),
+    })
+    .starts_with("invalid placement antiAffinity of squid"));
+    assert!(invalid(Placement {
+        name: "my-placement".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        node_selector: Some(
+            vec![("pool".to_string(), "general".to_string())]
+                .into_iter()
+                .collect()
+        ),
+        required_node_affinity: Some(json!({"key": "pool", "operator": "Exists"})),
+        preferred_node_affinity: None,
+        anti_affinity: None,
+    })
+    .starts_with("invalid placement requiredNodeAffinity of squid"));
+}
+
+#[test]
+fn test_placement_v1alpha1() {
+    let placement_alpha1_trait = TraitBinding {
+        name: String::from("placement"),
+        parameter_values: None,
+        properties: Some(json!({
+            "nodeSelector": {"pool": "general", "gpu": true},
+            "requiredNodeAffinity": null,
+            "preferredNodeAffinity": [
+                {"weight": 50, "requirements": [{"key": "disk", "operator": "Exists"}]}
+            ],
+            "antiAffinity": {"required": true}
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&placement_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let placement = Placement::from_properties(
+        "my-placement".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+    );
+    let node_selector = placement.node_selector.as_ref().expect("node selector");
+    assert_eq!("general", node_selector["pool"]);
+    // Labels that aren't strings are empty.
+    assert_eq!("", node_selector["gpu"]);
+    // A null affinity is no affinity, as if it were left out.
+    assert_eq!(None, placement.required_node_affinity);
+    assert_eq!(
+        Some(json!([{"weight": 50, "requirements": [{"key": "disk", "operator": "Exists"}]}])),
+        placement.preferred_node_affinity
+    );
+    assert_eq!(Some(json!({"required": true})), placement.anti_affinity);
+    assert!(placement.to_pod_patch().is_ok());
+}
@@ -0,0 +1,181 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, PodPatch, RenderResult};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The highest value of a PriorityClass that isn't one of the system.
+const MAX_VALUE: i64 = 1_000_000_000;
+/// The preemption policies of a PriorityClass.
+const PREEMPTION_POLICIES: &[&str] = &["PreemptLowerPriority", "Never"];
+
+/// A Priority sets the PriorityClass of the pods of a component, so that the scheduler
+/// preempts pods of a lower priority for them, and the kubelet evicts them last on node
+/// pressure.
+///
+/// With a `value`, the trait creates the PriorityClass too. PriorityClasses are cluster-wide
+/// and shared by the components that name them, so it stays when the trait is deleted.
+#[derive(Clone, Debug)]
+pub struct Priority {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    pub priority_class_name: Option<String>,
+    /// The value of the PriorityClass to create, none is created if unset.
+    pub value: Option<i64>,
+    /// `PreemptLowerPriority` or `Never`, `PreemptLowerPriority` if unset.
+    pub preemption_policy: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Priority {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        Priority {
+            name,
+            instance_name,
+            component_name,
+            priority_class_name: string("priorityClassName"),
+            value: properties_map.and_then(|map| map.get("value").and_then(|p| p.as_i64())),
+            preemption_policy: string("preemptionPolicy"),
+            description: string("description"),
+        }
+    }
+
+    /// priority_class_name is the name of the PriorityClass of the pods, failing if there is
+    /// none.
+    pub fn priority_class_name(&self) -> Result<String, failure::Error> {
+        self.priority_class_name.clone().ok_or_else(|| {
+            format_err!(
+                "priority of {} has no priorityClassName",
+                self.instance_name
+            )
+        })
+    }
+
+    /// to_priority_class renders the scheduling.k8s.io/v1 PriorityClass of the trait, if it
+    /// has a value.
+    pub fn to_priority_class(&self) -> Result<Option<Value>, failure::Error> {
+        let name = self.priority_class_name()?;
+        let value = match self.value {
+            Some(value) => value,
+            None => {
+                if self.preemption_policy.is_some() || self.description.is_some() {
+                    return Err(format_err!(
+                        "priority of {} describes a PriorityClass without a value",
+                        self.instance_name
+                    ));
+                }
+                return Ok(None);
+            }
+        };
+        if value > MAX_VALUE || name.starts_with("system-") {
+            return Err(format_err!(
+                "priority of {} can't create {}: values above {} and system- names are reserved",
+                self.instance_name,
+                name,
+                MAX_VALUE
+            ));
+        }
+        let mut class = json!({
+            "apiVersion": "scheduling.k8s.io/v1",
+            "kind": "PriorityClass",
+            "metadata": {"name": name},
+            "value": value,
+            "globalDefault": false,
+        });
+        if let Some(policy) = self.preemption_policy.as_ref() {
+            if !PREEMPTION_POLICIES.contains(&policy.as_str()) {
+                return Err(format_err!(
+                    "priority preemptionPolicy {} is none of {}",
+                    policy,
+                    PREEMPTION_POLICIES.join(", ")
+                ));
+            }
+            class["preemptionPolicy"] = json!(policy);
+        }
+        if let Some(description) = self.description.as_ref() {
+            class["description"] = json!(description);
+        }
+        Ok(Some(class))
+    }
+
+    /// to_pod_patch is the PriorityClass of the pods.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        Ok(PodPatch {
+            priority_class_name: Some(self.priority_class_name()?),
+            ..Default::default()
+        })
+    }
+}
+
+/// The resource of the PriorityClasses, which aren't namespaced.
+fn priority_class_resource() -> RawApi {
+    RawApi::customResource("priorityclasses")
+        .group("scheduling.k8s.io")
+        .version("v1")
+}
+
+impl TraitImplementation for Priority {
+    /// The PriorityClass is created before the pods, which can't be admitted without it.
+    fn pre_add(&self, _ns: &str, client: APIClient) -> TraitResult {
+        if let Some(class) = self.to_priority_class()? {
+            let name = self.priority_class_name()?;
+            apply(&client, &priority_class_resource(), name.as_str(), &class)?;
+        }
+        Ok(())
+    }
+    fn pre_modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.pre_add(ns, client)
+    }
+    /// There is nothing left to do once the pods name the PriorityClass.
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    /// Other components may have the PriorityClass, so it is left alone.
+    fn delete(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+    fn render(&self) -> RenderResult {
+        Ok(self.to_priority_class()?.into_iter().collect())
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let name = self.priority_class_name().ok()?;
+        let mut resource = BTreeMap::new();
+        let key = "priorityclass/".to_string() + name.as_str();
+        let state = match priority_class_resource()
+            .get(name.as_str())
+            .map_err(failure::Error::from)
+            .and_then(|req| Ok(client.timed_request::<Value>(req)?))
+        {
+            Ok(class) => format!("ready, priority {}", class["value"]),
+            Err(e) => {
+                // A PriorityClass without a value is someone else's to create.
+                if e.to_string().contains("NotFound") && self.value.is_some() {
+                    warn!("PriorityClass {} not found {}. Recreating ...", name, e);
+                    self.pre_add(ns, client).unwrap_or(());
+                }
+                e.to_string()
+            }
+        };
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
@@ -0,0 +1,256 @@
+use crate::schematic::traits::*;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_priority_class() {
+    let priority = Priority {
+        name: "my-app".into(),
+        instance_name: "api".into(),
+        component_name: "gateway".into(),
+        priority_class_name: Some("platform-critical".into()),
+        value: Some(100_000),
+        preemption_policy: None,
+        description: None,
+    };
+    // PriorityClasses aren't namespaced, and never the default of the cluster.
+    assert_eq!(
+        vec![json!({
+            "apiVersion": "scheduling.k8s.io/v1",
+            "kind": "PriorityClass",
+            "metadata": {"name": "platform-critical"},
+            "value": 100000,
+            "globalDefault": false
+        })],
+        priority.render().expect("render")
+    );
+
+    let priority = Priority {
+        name: "my-app".into(),
+        instance_name: "api".into(),
+        component_name: "gateway".into(),
+        priority_class_name: Some("platform-critical".into()),
+        value: Some(100_000),
+        preemption_policy: Some("Never".into()),
+        description: Some("Services the platform can't do without.".into()),
+    };
+    let class = priority
+        .to_priority_class()
+        .expect("priority class")
+        .expect("a priority class");
+    assert_eq!("Never", class["preemptionPolicy"]);
+    assert_eq!(
+        "Services the platform can't do without.",
+        class["description"]
+    );
+
+    // The highest value of a PriorityClass that isn't one of the system is allowed.
+    let priority = Priority {
+        name: "my-app".into(),
+        instance_name: "api".into(),
+        component_name: "gateway".into(),
+        priority_class_name: Some("platform-critical".into()),
+        value: Some(1_000_000_000),
+        preemption_policy: None,
+        description: None,
+    };
+    assert_eq!(1, priority.render().expect("render").len());
+    let priority = Priority {
+        name: "my-app".into(),
+        instance_name: "api".into(),
+        component_name: "gateway".into(),
+        priority_class_name: Some("platform-critical".into()),
+        value: Some(-10),
+        preemption_policy: None,
+        description: None,
+    };
+    assert_eq!(-10, priority.render().expect("render")[0]["value"]);
+}
+
+#[test]
+fn test_priority_pod_patch() {
+    let priority = Priority {
+        name: "my-app".into(),
+        instance_name: "api".into(),
+        component_name: "gateway".into(),
+        priority_class_name: Some("platform-critical".into()),
+        value: Some(100_000),
+        preemption_policy: None,
+        description: None,
+    };
+    let patch = priority.to_pod_patch().expect("pod patch");
+    assert_eq!(
+        Some("platform-critical".to_string()),
+        patch.priority_class_name
+    );
+
+    // The pods may name a PriorityClass that already exists.
+    let priority = Priority {
+        name: "my-app".into(),
+        instance_name: "api".into(),
+        component_name: "gateway".into(),
+        priority_class_name: Some("batch-low".into()),
+        value: None,
+        preemption_policy: None,
+        description: None,
+    };
+    assert!(priority
+        .to_priority_class()
+        .expect("priority class")
+        .is_none());
+    assert!(priority.render().expect("render").is_empty());
+    assert_eq!(
+        Some("batch-low".to_string()),
+        priority
+            .to_pod_patch()
+            .expect("pod patch")
+            .priority_class_name
+    );
+}
+
+#[test]
+fn test_priority_invalid() {
+    let invalid = |priority: Priority| priority.render().unwrap_err().to_string();
+    assert_eq!(
+        "priority of api has no priorityClassName",
+        invalid(Priority {
+            name: "my-app".into(),
+            instance_name: "api".into(),
+            component_name: "gateway".into(),
+            priority_class_name: None,
+            value: Some(100_000),
+            preemption_policy: None,
+            description: None,
+        })
+    );
+    assert_eq!(
+        "priority of api has no priorityClassName",
+        Priority {
+            name: "my-app".into(),
+            instance_name: "api".into(),
+            component_name: "gateway".into(),
+            priority_class_name: None,
+            value: None,
+            preemption_policy: None,
+            description: None,
+        }
+        .to_pod_patch()
+        .unwrap_err()
+        .to_string()
+    );
+    for priority in &[
+        Priority {
+            name: "my-app".into(),
+            instance_name: "api".into(),
+            component_name: "gateway".into(),
+            priority_class_name: Some("platform-critical".into()),
+            value: Some(100_000),
+            preemption_policy: Some("Never".into()),
+            description: None,
+        },
+        Priority {
+            name: "my-app".into(),
+            instance_name: "api".into(),
+            component_name: "gateway".into(),
+            priority_class_name: Some("platform-critical".into()),
+            value: Some(100_000),
+            preemption_policy: None,
+            description: Some("Batch jobs.".into()),
+        },
+    ] {
+        assert_eq!(
+            "priority of api describes a PriorityClass without a value",
+            invalid(Priority {
+                value: None,
+                ..priority.clone()
+            })
+        );
+    }
+    assert_eq!(
+        "priority of api can't create platform-critical: values above 1000000000 and system- \
+         names are reserved",
+        invalid(Priority {
+            name: "my-app".into(),
+            instance_name: "api".into(),
+            component_name: "gateway".into(),
+            priority_class_name: Some("platform-critical".into()),
+            value: Some(1_000_000_001),
+            preemption_policy: None,
+            description: None,
+        })
+    );
+    assert_eq!(
+        "priority of api can't create system-mine: values above 1000000000 and system- names \
+         are reserved",
+        invalid(Priority {
+            name: "my-app".into(),
+            instance_name: "api".into(),
+            component_name: "gateway".into(),
+            priority_class_name: Some("system-mine".into()),
+            value: Some(100_000),
+            preemption_policy: None,
+            description: None,
+        })
+    );
+    assert_eq!(
+        "priority preemptionPolicy Always is none of PreemptLowerPriority, Never",
+        invalid(Priority {
+            name: "my-app".into(),
+            instance_name: "api".into(),
+            component_name: "gateway".into(),
+            priority_class_name: Some("platform-critical".into()),
+            value: Some(100_000),
+            preemption_policy: Some("Always".into()),
+            description: None,
+        })
+    );
+}
+
+#[test]
+fn test_priority_v1alpha1() {
+    let priority_alpha1_trait = TraitBinding {
+        name: String::from("priority"),
+        parameter_values: None,
+        properties: Some(json!({
+            "priorityClassName": "platform-critical",
+            "value": 100000,
+            "preemptionPolicy": "PreemptLowerPriority",
+            "description": "Services the platform can't do without."
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&priority_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let priority =
+        Priority::from_properties("my-app".into(), "api".into(), "gateway".into(), prop_map);
+    assert_eq!(
+        Some("platform-critical".to_string()),
+        priority.priority_class_name
+    );
+    assert_eq!(Some(100_000), priority.value);
+    assert_eq!(
+        Some("PreemptLowerPriority".to_string()),
+        priority.preemption_policy
+    );
+    assert_eq!(
+        Some("Services the platform can't do without.".to_string()),
+        priority.description
+    );
+    assert_eq!(1, priority.render().expect("render").len());
+
+    // A value that isn't an integer is no value.
+    let properties = json!({"priorityClassName": "batch-low", "value": "1000"});
+    let priority = Priority::from_properties(
+        "my-app".into(),
+        "api".into(),
+        "gateway".into(),
+        properties.as_object(),
+    );
+    assert_eq!(None, priority.value);
+    assert!(priority.render().expect("render").is_empty());
+}
@@ -0,0 +1,370 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, PodPatch, RenderResult};
+use k8s_openapi::api::core::v1 as core;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The name of the volume of the Secret.
+const SECRET_VOLUME: &str = "secret-mounter";
+
+/// A SecretMounter mounts a Secret into the containers of a component, as files at a path or
+/// as environment variables.
+///
+/// The Secret is an existing one, or one the trait creates from its data or from an
+/// ExternalSecret of the [External Secrets Operator](https://external-secrets.io).
+#[derive(Clone, Debug)]
+pub struct SecretMounter {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The name of an existing Secret.
+    pub secret_name: Option<String>,
+    /// The values of the Secret to create, by key.
+    pub data: Option<BTreeMap<String, String>>,
+    /// The type of the Secret to create, like `kubernetes.io/tls`, `Opaque` if unset.
+    pub secret_type: Option<String>,
+    /// The ExternalSecret to create the Secret from, see `ExternalSecretRef`.
+    pub external_secret: Option<Value>,
+    /// The directory to mount the keys of the Secret in.
+    pub mount_path: Option<String>,
+    /// The environment variables to set to keys of the Secret, see `SecretEnv`.
+    pub env: Option<Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+/// ExternalSecretRef is the store an ExternalSecret reads its keys from, and the keys.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalSecretRef {
+    /// The name of the SecretStore.
+    pub store_name: String,
+    /// `SecretStore` or `ClusterSecretStore`, `SecretStore` if unset.
+    pub store_kind: Option<String>,
+    /// How often to read the keys again, like `1h`.
+    pub refresh_interval: Option<String>,
+    pub data: Vec<ExternalSecretData>,
+}
+
+/// ExternalSecretData is a key of the Secret, and where it is in the store.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalSecretData {
+    pub secret_key: String,
+    pub remote_key: String,
+    /// The property of the value of `remote_key`, for stores of structured values.
+    pub property: Option<String>,
+}
+
+/// SecretEnv is an environment variable of the value of a key of the Secret.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretEnv {
+    pub name: String,
+    pub key: String,
+}
+
+impl SecretMounter {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        let value = |key: &str| {
+            properties_map
+                .and_then(|map| map.get(key))
+                .filter(|v| !v.is_null())
+                .cloned()
+        };
+        SecretMounter {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            secret_name: string("secretName"),
+            data: properties_map
+                .and_then(|map| map.get("data"))
+                .and_then(|d| d.as_object())
+                .map(|d| {
+                    d.iter()
+                        .map(|(k, v)| match v {
+                            Value::String(s) => (k.clone(), s.clone()),
+                            _ => (k.clone(), v.to_string()),
+                        })
+                        .collect()
+                }),
+            secret_type: string("type"),
+            external_secret: value("externalSecret"),
+            mount_path: string("mountPath"),
+            env: value("env"),
+        }
+    }
+
+    /// secret is the name of the Secret the trait mounts, failing unless it has exactly one of
+    /// an existing Secret, data and an ExternalSecret.
+    pub fn secret(&self) -> Result<String, failure::Error> {
+        let sources = [
+            self.secret_name.is_some(),
+            self.data.is_some(),
+            self.external_secret.is_some(),
+        ];
+        if sources.iter().filter(|s| **s).count() != 1 {
+            return Err(format_err!(
+                "secret-mounter of {} needs one of secretName, data and externalSecret",
+                self.instance_name
+            ));
+        }
+        Ok(self.secret_name.clone().unwrap_or_else(|| self.kube_name()))
+    }
+
+    /// to_secret renders the Secret of the data of the trait, if it has data.
+    pub fn to_secret(&self) -> Result<Option<core::Secret>, failure::Error> {
+        self.secret()?;
+        Ok(self.data.as_ref().map(|data| core::Secret {
+            metadata: Some(self.metadata()),
+            string_data: Some(data.clone()),
+            type_: Some(
+                self.secret_type
+                    .clone()
+                    .unwrap_or_else(|| "Opaque".to_string()),
+            ),
+            ..Default::default()
+        }))
+    }
+
+    /// to_external_secret renders the external-secrets.io/v1beta1 ExternalSecret of the trait,
+    /// if it has one, which creates the Secret the trait mounts.
+    pub fn to_external_secret(&self) -> Result<Option<Value>, failure::Error> {
+        self.secret()?;
+        let external: ExternalSecretRef = match self.external_secret.as_ref() {
+            Some(external) => serde_json::from_value(external.clone()).map_err(|e| {
+                format_err!(
+                    "invalid secret-mounter externalSecret of {}: {}",
+                    self.instance_name,
+                    e
+                )
+            })?,
+            None => return Ok(None),
+        };
+        if external.data.is_empty() {
+            return Err(format_err!(
+                "secret-mounter externalSecret of {} reads no keys",
+                self.instance_name
+            ));
+        }
+        let data: Vec<Value> = external
+            .data
+            .iter()
+            .map(|d| {
+                let mut remote_ref = json!({ "key": d.remote_key });
+                if let Some(property) = d.property.as_ref() {
+                    remote_ref["property"] = json!(property);
+                }
+                json!({"secretKey": d.secret_key, "remoteRef": remote_ref})
+            })
+            .collect();
+        let mut spec = json!({
+            "secretStoreRef": {
+                "name": external.store_name,
+                "kind": external.store_kind.unwrap_or_else(|| "SecretStore".to_string()),
+            },
+            "target": {"name": self.kube_name()},
+            "data": data,
+        });
+        if let Some(interval) = external.refresh_interval {
+            spec["refreshInterval"] = json!(interval);
+        }
+        Ok(Some(json!({
+            "apiVersion": "external-secrets.io/v1beta1",
+            "kind": "ExternalSecret",
+            "metadata": serde_json::to_value(self.metadata())?,
+            "spec": spec,
+        })))
+    }
+
+    /// to_pod_patch is the volume of the Secret, or the environment variables of its keys.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        let secret = self.secret()?;
+        let env: Vec<SecretEnv> = match self.env.as_ref() {
+            Some(env) => serde_json::from_value(env.clone()).map_err(|e| {
+                format_err!(
+                    "invalid secret-mounter env of {}: {}",
+                    self.instance_name,
+                    e
+                )
+            })?,
+            None => vec![],
+        };
+        if self.mount_path.is_none() && env.is_empty() {
+            return Err(format_err!(
+                "secret-mounter of {} mounts {} neither at a mountPath nor as env",
+                self.instance_name,
+                secret
+            ));
+        }
+        let mut patch = PodPatch {
+            env: env
+                .into_iter()
+                .map(|e| core::EnvVar {
+                    name: e.name,
+                    value_from: Some(core::EnvVarSource {
+                        secret_key_ref: Some(core::SecretKeySelector {
+                            name: Some(secret.clone()),
+                            key: e.key,
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        if let Some(path) = self.mount_path.as_ref() {
+            patch.volumes.push(core::Volume {
+                name: SECRET_VOLUME.to_string(),
+                secret: Some(core::SecretVolumeSource {
+                    secret_name: Some(secret),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+            patch.volume_mounts.push(core::VolumeMount {
+                name: SECRET_VOLUME.to_string(),
+                mount_path: path.clone(),
+                read_only: Some(true),
+                ..Default::default()
+            });
+        }
+        Ok(patch)
+    }
+
+    fn metadata(&self) -> meta::ObjectMeta {
+        meta::ObjectMeta {
+            name: Some(self.kube_name()),
+            labels: Some(trait_labels(self.name.clone(), self.instance_name.clone())),
+            owner_references: self.owner_ref.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-secret-mounter", self.instance_name)
+    }
+}
+
+/// The resource of the ExternalSecrets in `ns`.
+fn external_secret_resource(ns: &str) -> RawApi {
+    RawApi::customResource("externalsecrets")
+        .group("external-secrets.io")
+        .version("v1beta1")
+        .within(ns)
+}
+
+impl TraitImplementation for SecretMounter {
+    /// The Secret is created before the pods that mount it.
+    fn pre_add(&self, ns: &str, client: APIClient) -> TraitResult {
+        if let Some(secret) = self.to_secret()? {
+            let resource = RawApi::v1Secret().within(ns);
+            apply(&client, &resource, self.kube_name().as_str(), &secret)?;
+        }
+        if let Some(external) = self.to_external_secret()? {
+            let resource = external_secret_resource(ns);
+            apply(&client, &resource, self.kube_name().as_str(), &external)?;
+        }
+        Ok(())
+    }
+    fn pre_modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.pre_add(ns, client)
+    }
+    /// There is nothing left to do once the workload mounts the Secret.
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    /// Existing Secrets are left alone, the trait only deletes the objects it creates.
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let resource = if self.data.is_some() {
+            RawApi::v1Secret().within(ns)
+        } else if self.external_secret.is_some() {
+            external_secret_resource(ns)
+        } else {
+            return Ok(());
+        };
+        let req = resource.delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+    fn render(&self) -> RenderResult {
+        let mut objects = vec![];
+        if let Some(secret) = self.to_secret()? {
+            objects.push(serde_json::to_value(secret)?);
+        }
+        if let Some(external) = self.to_external_secret()? {
+            objects.push(external);
+        }
+        Ok(objects)
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let secret = self.secret().ok()?;
+        let (key, req) = if self.external_secret.is_some() {
+            (
+                "externalsecret/".to_string() + self.kube_name().as_str(),
+                external_secret_resource(ns).get(self.kube_name().as_str()),
+            )
+        } else {
+            (
+                "secret/".to_string() + secret.as_str(),
+                RawApi::v1Secret().within(ns).get(secret.as_str()),
+            )
+        };
+        let req = match req {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let object = match client.timed_request::<Value>(req) {
+            Ok(object) => object,
+            Err(e) => {
+                // An existing Secret is someone else's to create.
+                if e.to_string().contains("NotFound") && self.secret_name.is_none() {
+                    warn!("Secret of secret-mounter not found {}. Recreating ...", e);
+                    self.pre_add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let state = match object["status"]["conditions"].as_array() {
+            Some(conditions) => conditions
+                .iter()
+                .find(|c| c["type"] == "Ready")
+                .map(|c| match c["status"].as_str() {
+                    Some("True") => "ready".to_string(),
+                    _ => c["message"].as_str().unwrap_or("not ready").to_string(),
+                })
+                .unwrap_or_else(|| "created".to_string()),
+            None => "created".to_string(),
+        };
+        resource.insert(key, state);
+        Some(resource)
+    }
+}
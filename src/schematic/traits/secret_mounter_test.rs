@@ -0,0 +1,395 @@
+use crate::schematic::traits::*;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_secret_mounter_existing_secret() {
+    let mounter = SecretMounter {
+        name: "my-secrets".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        secret_name: Some("tls-certs".into()),
+        data: None,
+        secret_type: None,
+        external_secret: None,
+        mount_path: Some("/etc/tls".into()),
+        env: None,
+        owner_ref: None,
+    };
+    // The trait creates nothing for an existing Secret.
+    assert!(mounter.render().expect("render").is_empty());
+    assert!(mounter.to_secret().expect("secret").is_none());
+
+    let patch = mounter.to_pod_patch().expect("pod patch");
+    assert_eq!("secret-mounter", patch.volumes[0].name);
+    assert_eq!(
+        Some("tls-certs".to_string()),
+        patch.volumes[0]
+            .secret
+            .as_ref()
+            .and_then(|s| s.secret_name.clone())
+    );
+    assert_eq!("secret-mounter", patch.volume_mounts[0].name);
+    assert_eq!("/etc/tls", patch.volume_mounts[0].mount_path);
+    assert_eq!(Some(true), patch.volume_mounts[0].read_only);
+    assert!(patch.env.is_empty());
+}
+
+#[test]
+fn test_secret_mounter_env() {
+    let mounter = SecretMounter {
+        name: "my-secrets".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        secret_name: Some("tls-certs".into()),
+        data: None,
+        secret_type: None,
+        external_secret: None,
+        mount_path: None,
+        env: Some(json!([
+            {"name": "TLS_KEY", "key": "tls.key"},
+            {"name": "TLS_CERT", "key": "tls.crt"}
+        ])),
+        owner_ref: None,
+    };
+    let patch = mounter.to_pod_patch().expect("pod patch");
+    // Keys set as env need no volume.
+    assert!(patch.volumes.is_empty());
+    assert!(patch.volume_mounts.is_empty());
+    let key_ref = |i: usize| {
+        patch.env[i]
+            .value_from
+            .as_ref()
+            .and_then(|v| v.secret_key_ref.clone())
+            .expect("secret key ref")
+    };
+    assert_eq!("TLS_KEY", patch.env[0].name);
+    assert_eq!(Some("tls-certs".to_string()), key_ref(0).name);
+    assert_eq!("tls.key", key_ref(0).key);
+    assert_eq!("TLS_CERT", patch.env[1].name);
+    assert_eq!("tls.crt", key_ref(1).key);
+
+    // Both at once.
+    let mounter = SecretMounter {
+        name: "my-secrets".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        secret_name: Some("tls-certs".into()),
+        data: None,
+        secret_type: None,
+        external_secret: None,
+        mount_path: Some("/etc/tls".into()),
+        env: Some(json!([{"name": "TLS_KEY", "key": "tls.key"}])),
+        owner_ref: None,
+    };
+    let patch = mounter.to_pod_patch().expect("pod patch");
+    assert_eq!(1, patch.volumes.len());
+    assert_eq!(1, patch.env.len());
+}
+
+#[test]
+fn test_secret_mounter_data() {
+    let mut data = BTreeMap::new();
+    data.insert("username".to_string(), "admin".to_string());
+    let mounter = SecretMounter {
+        name: "my-secrets".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        secret_name: None,
+        data: Some(data),
+        secret_type: None,
+        external_secret: None,
+        mount_path: Some("/etc/tls".into()),
+        env: None,
+        owner_ref: None,
+    };
+    let secret = mounter.to_secret().expect("secret").expect("a secret");
+    let metadata = secret.metadata.expect("metadata");
+    assert_eq!(
+        Some("squid-trait-secret-mounter".to_string()),
+        metadata.name
+    );
+    assert_eq!(None, metadata.owner_references);
+    assert_eq!(
+        Some(&"my-secrets".to_string()),
+        metadata
+            .labels
+            .as_ref()
+            .and_then(|l| l.get("app.kubernetes.io/name"))
+    );
+    assert_eq!(Some("Opaque".to_string()), secret.type_);
+    assert_eq!("admin", secret.string_data.expect("data")["username"]);
+    assert!(mounter.to_external_secret().expect("external").is_none());
+    assert_eq!(1, mounter.render().expect("render").len());
+
+    // The pods mount the Secret the trait creates.
+    let patch = mounter.to_pod_patch().expect("pod patch");
+    assert_eq!(
+        Some("squid-trait-secret-mounter".to_string()),
+        patch.volumes[0]
+            .secret
+            .as_ref()
+            .and_then(|s| s.secret_name.clone())
+    );
+
+    let mounter = SecretMounter {
+        secret_type: Some("kubernetes.io/tls".into()),
+        ..mounter
+    };
+    let secret = mounter.to_secret().expect("secret").expect("a secret");
+    assert_eq!(Some("kubernetes.io/tls".to_string()), secret.type_);
+}
+
+#[test]
+fn test_secret_mounter_external_secret() {
+    let mounter = SecretMounter {
+        name: "my-secrets".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        secret_name: None,
+        data: None,
+        secret_type: None,
+        external_secret: Some(json!({
+            "storeName": "vault",
+            "storeKind": "ClusterSecretStore",
+            "refreshInterval": "1h",
+            "data": [{"secretKey": "password", "remoteKey": "db/squid", "property": "password"}]
+        })),
+        mount_path: Some("/etc/tls".into()),
+        env: None,
+        owner_ref: None,
+    };
+    assert!(mounter.to_secret().expect("secret").is_none());
+    let external = mounter
+        .to_external_secret()
+        .expect("external secret")
+        .expect("an external secret");
+    assert_eq!("external-secrets.io/v1beta1", external["apiVersion"]);
+    assert_eq!("ExternalSecret", external["kind"]);
+    assert_eq!("squid-trait-secret-mounter", external["metadata"]["name"]);
+    assert_eq!(
+        json!({
+            "secretStoreRef": {"name": "vault", "kind": "ClusterSecretStore"},
+            "target": {"name": "squid-trait-secret-mounter"},
+            "refreshInterval": "1h",
+            "data": [{
+                "secretKey": "password",
+                "remoteRef": {"key": "db/squid", "property": "password"}
+            }]
+        }),
+        external["spec"]
+    );
+
+    // The store is a SecretStore of the namespace unless it says otherwise.
+    let mounter = SecretMounter {
+        external_secret: Some(json!({
+            "storeName": "vault",
+            "data": [{"secretKey": "password", "remoteKey": "db/squid"}]
+        })),
+        ..mounter
+    };
+    let spec = &mounter
+        .to_external_secret()
+        .expect("external secret")
+        .expect("an external secret")["spec"];
+    assert_eq!(
+        json!({"name": "vault", "kind": "SecretStore"}),
+        spec["secretStoreRef"]
+    );
+    assert!(spec["refreshInterval"].is_null());
+    assert_eq!(json!({"key": "db/squid"}), spec["data"][0]["remoteRef"]);
+}
+
+#[test]
+fn test_secret_mounter_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let mut data = BTreeMap::new();
+    data.insert("username".to_string(), "admin".to_string());
+    let mounter = SecretMounter {
+        name: "my-secrets".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        secret_name: None,
+        data: Some(data),
+        secret_type: None,
+        external_secret: None,
+        mount_path: Some("/etc/tls".into()),
+        env: None,
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    let metadata = mounter
+        .to_secret()
+        .expect("secret")
+        .expect("a secret")
+        .metadata
+        .expect("metadata");
+    assert_eq!(Some(vec![owner.clone()]), metadata.owner_references);
+
+    let mounter = SecretMounter {
+        name: "my-secrets".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        secret_name: None,
+        data: None,
+        secret_type: None,
+        external_secret: Some(json!({
+            "storeName": "vault",
+            "data": [{"secretKey": "password", "remoteKey": "db/squid"}]
+        })),
+        mount_path: Some("/etc/tls".into()),
+        env: None,
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    assert_eq!(
+        serde_json::to_value(vec![owner]).unwrap(),
+        mounter
+            .to_external_secret()
+            .expect("external secret")
+            .expect("an external secret")["metadata"]["ownerReferences"]
+    );
+}
+
+#[test]
+fn test_secret_mounter_invalid() {
+    let invalid = |mounter: SecretMounter| mounter.to_pod_patch().unwrap_err().to_string();
+    assert_eq!(
+        "secret-mounter of squid needs one of secretName, data and externalSecret",
+        invalid(SecretMounter {
+            name: "my-secrets".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            secret_name: None,
+            data: None,
+            secret_type: None,
+            external_secret: None,
+            mount_path: Some("/etc/tls".into()),
+            env: None,
+            owner_ref: None,
+        })
+    );
+    let mut data = BTreeMap::new();
+    data.insert("a".to_string(), "b".to_string());
+    assert_eq!(
+        "secret-mounter of squid needs one of secretName, data and externalSecret",
+        invalid(SecretMounter {
+            name: "my-secrets".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            secret_name: Some("tls-certs".into()),
+            data: Some(data),
+            secret_type: None,
+            external_secret: None,
+            mount_path: Some("/etc/tls".into()),
+            env: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "secret-mounter of squid mounts tls-certs neither at a mountPath nor as env",
+        invalid(SecretMounter {
+            name: "my-secrets".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            secret_name: Some("tls-certs".into()),
+            data: None,
+            secret_type: None,
+            external_secret: None,
+            mount_path: None,
+            env: None,
+            owner_ref: None,
+        })
+    );
+    assert!(invalid(SecretMounter {
+        name: "my-secrets".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        secret_name: Some("tls-certs".into()),
+        data: None,
+        secret_type: None,
+        external_secret: None,
+        mount_path: Some("/etc/tls".into()),
+        env: Some(json!({"TLS_KEY": "tls.key"})),
+        owner_ref: None,
+    })
+    .starts_with("invalid secret-mounter env of squid"));
+
+    let external = |external: serde_json::Value| {
+        SecretMounter {
+            name: "my-secrets".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            secret_name: None,
+            data: None,
+            secret_type: None,
+            external_secret: Some(external),
+            mount_path: Some("/etc/tls".into()),
+            env: None,
+            owner_ref: None,
+        }
+        .to_external_secret()
+        .unwrap_err()
+        .to_string()
+    };
+    assert_eq!(
+        "secret-mounter externalSecret of squid reads no keys",
+        external(json!({"storeName": "vault", "data": []}))
+    );
+    assert!(
+        external(json!({"data": []})).starts_with("invalid secret-mounter externalSecret of squid")
+    );
+}
+
+#[test]
+fn test_secret_mounter_v1alpha1() {
+    let secret_mounter_alpha1_trait = TraitBinding {
+        name: String::from("secret-mounter"),
+        parameter_values: None,
+        properties: Some(json!({
+            "data": {"username": "admin", "port": 5432},
+            "type": "kubernetes.io/basic-auth",
+            "externalSecret": null,
+            "env": [{"name": "DB_USER", "key": "username"}]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&secret_mounter_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let mounter = SecretMounter::from_properties(
+        "my-secrets".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+    );
+    assert_eq!(None, mounter.secret_name);
+    // Values that aren't strings are kept as their JSON.
+    let data = mounter.data.as_ref().expect("data");
+    assert_eq!("admin", data["username"]);
+    assert_eq!("5432", data["port"]);
+    assert_eq!(
+        Some("kubernetes.io/basic-auth".to_string()),
+        mounter.secret_type
+    );
+    // A null externalSecret is no externalSecret, as if it were left out.
+    assert_eq!(None, mounter.external_secret);
+    assert_eq!(None, mounter.mount_path);
+    assert_eq!(
+        Some(json!([{"name": "DB_USER", "key": "username"}])),
+        mounter.env
+    );
+    assert!(mounter.to_pod_patch().is_ok());
+}
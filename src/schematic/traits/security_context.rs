@@ -0,0 +1,177 @@
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::PodPatch;
+use kube::client::APIClient;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+
+/// The seccomp profile of the pods, if unset.
+const DEFAULT_SECCOMP_PROFILE: &str = "RuntimeDefault";
+/// The types of the seccomp profiles.
+const SECCOMP_PROFILES: &[&str] = &["RuntimeDefault", "Localhost", "Unconfined"];
+
+/// A SecurityContext hardens the pods of a component, so that they pass admission policies
+/// like the restricted Pod Security Standard.
+///
+/// Unless the properties say otherwise, the pods run as a user other than root with the
+/// RuntimeDefault seccomp profile, and their containers drop every capability and can't
+/// escalate their privileges. This applies to the containers traits add, like sidecars.
+#[derive(Clone, Debug)]
+pub struct SecurityContext {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// Whether the containers must run as a user other than root, true if unset.
+    pub run_as_non_root: Option<bool>,
+    pub run_as_user: Option<i64>,
+    pub run_as_group: Option<i64>,
+    /// The group that owns the volumes of the pods.
+    pub fs_group: Option<i64>,
+    pub read_only_root_filesystem: Option<bool>,
+    /// Whether a process can gain more privileges than its parent, false if unset.
+    pub allow_privilege_escalation: Option<bool>,
+    /// The capabilities the containers drop, `ALL` if unset.
+    pub drop_capabilities: Option<Vec<String>>,
+    /// The capabilities the containers add back, like `NET_BIND_SERVICE`.
+    pub add_capabilities: Option<Vec<String>>,
+    /// `RuntimeDefault`, `Localhost` or `Unconfined`, `RuntimeDefault` if unset.
+    pub seccomp_profile: Option<String>,
+    /// The file of the Localhost profile on the node.
+    pub localhost_profile: Option<String>,
+}
+
+impl SecurityContext {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+    ) -> Self {
+        let property = |key: &str| properties_map.and_then(|map| map.get(key));
+        let strings = |key: &str| {
+            property(key).and_then(|v| v.as_array()).map(|v| {
+                v.iter()
+                    .filter_map(|v| v.as_str().map(String::from))
+                    .collect()
+            })
+        };
+        SecurityContext {
+            name,
+            instance_name,
+            component_name,
+            run_as_non_root: property("runAsNonRoot").and_then(|p| p.as_bool()),
+            run_as_user: property("runAsUser").and_then(|p| p.as_i64()),
+            run_as_group: property("runAsGroup").and_then(|p| p.as_i64()),
+            fs_group: property("fsGroup").and_then(|p| p.as_i64()),
+            read_only_root_filesystem: property("readOnlyRootFilesystem").and_then(|p| p.as_bool()),
+            allow_privilege_escalation: property("allowPrivilegeEscalation")
+                .and_then(|p| p.as_bool()),
+            drop_capabilities: strings("dropCapabilities"),
+            add_capabilities: strings("addCapabilities"),
+            seccomp_profile: property("seccompProfile")
+                .and_then(|p| p.as_str())
+                .map(String::from),
+            localhost_profile: property("localhostProfile")
+                .and_then(|p| p.as_str())
+                .map(String::from),
+        }
+    }
+
+    /// to_pod_security_context renders the security context of the pods.
+    pub fn to_pod_security_context(&self) -> Result<Value, failure::Error> {
+        let run_as_non_root = self.run_as_non_root.unwrap_or(true);
+        if run_as_non_root && self.run_as_user == Some(0) {
+            return Err(format_err!(
+                "security-context of {} runs as root but runAsNonRoot",
+                self.instance_name
+            ));
+        }
+        let profile = self
+            .seccomp_profile
+            .as_deref()
+            .unwrap_or(DEFAULT_SECCOMP_PROFILE);
+        if !SECCOMP_PROFILES.contains(&profile) {
+            return Err(format_err!(
+                "security-context seccompProfile {} is none of {}",
+                profile,
+                SECCOMP_PROFILES.join(", ")
+            ));
+        }
+        let mut seccomp = json!({ "type": profile });
+        match (profile, self.localhost_profile.as_ref()) {
+            ("Localhost", Some(file)) => seccomp["localhostProfile"] = json!(file),
+            ("Localhost", None) => {
+                return Err(format_err!(
+                    "security-context of {} has the Localhost seccomp profile but no file",
+                    self.instance_name
+                ))
+            }
+            (_, Some(_)) => {
+                return Err(format_err!(
+                    "security-context of {} has a localhostProfile but the {} seccomp profile",
+                    self.instance_name,
+                    profile
+                ))
+            }
+            _ => {}
+        }
+        let mut context = json!({
+            "runAsNonRoot": run_as_non_root,
+            "seccompProfile": seccomp,
+        });
+        if let Some(user) = self.run_as_user {
+            context["runAsUser"] = json!(user);
+        }
+        if let Some(group) = self.run_as_group {
+            context["runAsGroup"] = json!(group);
+        }
+        if let Some(group) = self.fs_group {
+            context["fsGroup"] = json!(group);
+        }
+        Ok(context)
+    }
+
+    /// to_container_security_context renders the security context of every container.
+    pub fn to_container_security_context(&self) -> Value {
+        let mut capabilities = json!({
+            "drop": self
+                .drop_capabilities
+                .clone()
+                .unwrap_or_else(|| vec!["ALL".to_string()]),
+        });
+        if let Some(add) = self.add_capabilities.as_ref() {
+            capabilities["add"] = json!(add);
+        }
+        let mut context = json!({
+            "allowPrivilegeEscalation": self.allow_privilege_escalation.unwrap_or(false),
+            "capabilities": capabilities,
+        });
+        if let Some(read_only) = self.read_only_root_filesystem {
+            context["readOnlyRootFilesystem"] = json!(read_only);
+        }
+        context
+    }
+
+    /// to_pod_patch is the security contexts of the pods and their containers.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        Ok(PodPatch {
+            security_context: Some(self.to_pod_security_context()?),
+            container_security_context: Some(self.to_container_security_context()),
+            ..Default::default()
+        })
+    }
+}
+
+impl TraitImplementation for SecurityContext {
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn delete(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+}
@@ -0,0 +1,326 @@
+use crate::schematic::traits::*;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_security_context_defaults() {
+    let security_context = SecurityContext {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        run_as_non_root: None,
+        run_as_user: None,
+        run_as_group: None,
+        fs_group: None,
+        read_only_root_filesystem: None,
+        allow_privilege_escalation: None,
+        drop_capabilities: None,
+        add_capabilities: None,
+        seccomp_profile: None,
+        localhost_profile: None,
+    };
+    let patch = security_context
+        .pod_patch()
+        .expect("pod patch")
+        .expect("security contexts");
+    // The defaults pass the restricted Pod Security Standard.
+    assert_eq!(
+        Some(json!({
+            "runAsNonRoot": true,
+            "seccompProfile": {"type": "RuntimeDefault"}
+        })),
+        patch.security_context
+    );
+    assert_eq!(
+        Some(json!({
+            "allowPrivilegeEscalation": false,
+            "capabilities": {"drop": ["ALL"]}
+        })),
+        patch.container_security_context
+    );
+}
+
+#[test]
+fn test_security_context_pod() {
+    let security_context = SecurityContext {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        run_as_non_root: None,
+        run_as_user: Some(1000),
+        run_as_group: Some(3000),
+        fs_group: Some(2000),
+        read_only_root_filesystem: None,
+        allow_privilege_escalation: None,
+        drop_capabilities: None,
+        add_capabilities: None,
+        seccomp_profile: None,
+        localhost_profile: None,
+    };
+    assert_eq!(
+        json!({
+            "runAsNonRoot": true,
+            "runAsUser": 1000,
+            "runAsGroup": 3000,
+            "fsGroup": 2000,
+            "seccompProfile": {"type": "RuntimeDefault"}
+        }),
+        security_context
+            .to_pod_security_context()
+            .expect("pod security context")
+    );
+
+    // Root is allowed once the pods no longer have to run as another user.
+    let security_context = SecurityContext {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        run_as_non_root: Some(false),
+        run_as_user: Some(0),
+        run_as_group: None,
+        fs_group: None,
+        read_only_root_filesystem: None,
+        allow_privilege_escalation: None,
+        drop_capabilities: None,
+        add_capabilities: None,
+        seccomp_profile: Some("Unconfined".into()),
+        localhost_profile: None,
+    };
+    assert_eq!(
+        json!({
+            "runAsNonRoot": false,
+            "runAsUser": 0,
+            "seccompProfile": {"type": "Unconfined"}
+        }),
+        security_context
+            .to_pod_security_context()
+            .expect("pod security context")
+    );
+}
+
+#[test]
+fn test_security_context_localhost_profile() {
+    let security_context = SecurityContext {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        run_as_non_root: None,
+        run_as_user: None,
+        run_as_group: None,
+        fs_group: None,
+        read_only_root_filesystem: None,
+        allow_privilege_escalation: None,
+        drop_capabilities: None,
+        add_capabilities: None,
+        seccomp_profile: Some("Localhost".into()),
+        localhost_profile: Some("profiles/audit.json".into()),
+    };
+    assert_eq!(
+        json!({"type": "Localhost", "localhostProfile": "profiles/audit.json"}),
+        security_context
+            .to_pod_security_context()
+            .expect("pod security context")["seccompProfile"]
+    );
+}
+
+#[test]
+fn test_security_context_containers() {
+    let security_context = SecurityContext {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        run_as_non_root: None,
+        run_as_user: None,
+        run_as_group: None,
+        fs_group: None,
+        read_only_root_filesystem: Some(true),
+        allow_privilege_escalation: None,
+        drop_capabilities: None,
+        add_capabilities: Some(vec!["NET_BIND_SERVICE".into()]),
+        seccomp_profile: None,
+        localhost_profile: None,
+    };
+    assert_eq!(
+        json!({
+            "allowPrivilegeEscalation": false,
+            "readOnlyRootFilesystem": true,
+            "capabilities": {"drop": ["ALL"], "add": ["NET_BIND_SERVICE"]}
+        }),
+        security_context.to_container_security_context()
+    );
+
+    let security_context = SecurityContext {
+        name: "my-app".into(),
+        instance_name: "web".into(),
+        component_name: "nginx".into(),
+        run_as_non_root: None,
+        run_as_user: None,
+        run_as_group: None,
+        fs_group: None,
+        read_only_root_filesystem: Some(false),
+        allow_privilege_escalation: Some(true),
+        drop_capabilities: Some(vec!["NET_RAW".into(), "SYS_ADMIN".into()]),
+        add_capabilities: None,
+        seccomp_profile: None,
+        localhost_profile: None,
+    };
+    assert_eq!(
+        json!({
+            "allowPrivilegeEscalation": true,
+            "readOnlyRootFilesystem": false,
+            "capabilities": {"drop": ["NET_RAW", "SYS_ADMIN"]}
+        }),
+        security_context.to_container_security_context()
+    );
+}
+
+#[test]
+fn test_security_context_invalid() {
+    let invalid =
+        |security_context: SecurityContext| security_context.pod_patch().unwrap_err().to_string();
+    assert_eq!(
+        "security-context of web runs as root but runAsNonRoot",
+        invalid(SecurityContext {
+            name: "my-app".into(),
+            instance_name: "web".into(),
+            component_name: "nginx".into(),
+            run_as_non_root: None,
+            run_as_user: Some(0),
+            run_as_group: None,
+            fs_group: None,
+            read_only_root_filesystem: None,
+            allow_privilege_escalation: None,
+            drop_capabilities: None,
+            add_capabilities: None,
+            seccomp_profile: None,
+            localhost_profile: None,
+        })
+    );
+    assert_eq!(
+        "security-context of web runs as root but runAsNonRoot",
+        invalid(SecurityContext {
+            name: "my-app".into(),
+            instance_name: "web".into(),
+            component_name: "nginx".into(),
+            run_as_non_root: Some(true),
+            run_as_user: Some(0),
+            run_as_group: None,
+            fs_group: None,
+            read_only_root_filesystem: None,
+            allow_privilege_escalation: None,
+            drop_capabilities: None,
+            add_capabilities: None,
+            seccomp_profile: None,
+            localhost_profile: None,
+        })
+    );
+    assert_eq!(
+        "security-context seccompProfile Permissive is none of RuntimeDefault, Localhost, \
+         Unconfined",
+        invalid(SecurityContext {
+            name: "my-app".into(),
+            instance_name: "web".into(),
+            component_name: "nginx".into(),
+            run_as_non_root: None,
+            run_as_user: None,
+            run_as_group: None,
+            fs_group: None,
+            read_only_root_filesystem: None,
+            allow_privilege_escalation: None,
+            drop_capabilities: None,
+            add_capabilities: None,
+            seccomp_profile: Some("Permissive".into()),
+            localhost_profile: None,
+        })
+    );
+    assert_eq!(
+        "security-context of web has the Localhost seccomp profile but no file",
+        invalid(SecurityContext {
+            name: "my-app".into(),
+            instance_name: "web".into(),
+            component_name: "nginx".into(),
+            run_as_non_root: None,
+            run_as_user: None,
+            run_as_group: None,
+            fs_group: None,
+            read_only_root_filesystem: None,
+            allow_privilege_escalation: None,
+            drop_capabilities: None,
+            add_capabilities: None,
+            seccomp_profile: Some("Localhost".into()),
+            localhost_profile: None,
+        })
+    );
+    assert_eq!(
+        "security-context of web has a localhostProfile but the RuntimeDefault seccomp profile",
+        invalid(SecurityContext {
+            name: "my-app".into(),
+            instance_name: "web".into(),
+            component_name: "nginx".into(),
+            run_as_non_root: None,
+            run_as_user: None,
+            run_as_group: None,
+            fs_group: None,
+            read_only_root_filesystem: None,
+            allow_privilege_escalation: None,
+            drop_capabilities: None,
+            add_capabilities: None,
+            seccomp_profile: None,
+            localhost_profile: Some("profiles/audit.json".into()),
+        })
+    );
+}
+
+#[test]
+fn test_security_context_v1alpha1() {
+    let security_context_alpha1_trait = TraitBinding {
+        name: String::from("security-context"),
+        parameter_values: None,
+        properties: Some(json!({
+            "runAsNonRoot": false,
+            "runAsUser": 1000,
+            "runAsGroup": "3000",
+            "fsGroup": 2000,
+            "readOnlyRootFilesystem": true,
+            "allowPrivilegeEscalation": true,
+            "dropCapabilities": ["NET_RAW"],
+            "addCapabilities": ["NET_BIND_SERVICE", 1],
+            "seccompProfile": "Localhost",
+            "localhostProfile": "profiles/audit.json"
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&security_context_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let security_context =
+        SecurityContext::from_properties("my-app".into(), "web".into(), "nginx".into(), prop_map);
+    assert_eq!(Some(false), security_context.run_as_non_root);
+    assert_eq!(Some(1000), security_context.run_as_user);
+    // IDs that aren't numbers are left unset.
+    assert_eq!(None, security_context.run_as_group);
+    assert_eq!(Some(2000), security_context.fs_group);
+    assert_eq!(Some(true), security_context.read_only_root_filesystem);
+    assert_eq!(Some(true), security_context.allow_privilege_escalation);
+    assert_eq!(
+        Some(vec!["NET_RAW".to_string()]),
+        security_context.drop_capabilities
+    );
+    assert_eq!(
+        Some(vec!["NET_BIND_SERVICE".to_string()]),
+        security_context.add_capabilities
+    );
+    assert_eq!(
+        Some("Localhost".to_string()),
+        security_context.seccomp_profile
+    );
+    assert_eq!(
+        Some("profiles/audit.json".to_string()),
+        security_context.localhost_profile
+    );
+    assert!(security_context.to_pod_patch().is_ok());
+}
@@ -0,0 +1,253 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{
+    apply, PodPatch, RenderResult, SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_WORKER,
+    WORKER_NAME,
+};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The mesh of Istio, whose sidecars are injected into pods with a label.
+pub const ISTIO_MESH: &str = "istio";
+/// The mesh of Linkerd, whose proxies are injected into pods with an annotation.
+pub const LINKERD_MESH: &str = "linkerd";
+
+/// The modes of the mutual TLS of the Istio PeerAuthentication.
+const MTLS_MODES: &[&str] = &["STRICT", "PERMISSIVE", "DISABLE"];
+
+/// A ServiceMesh enrolls the pods of a component in Istio or Linkerd, with the labels and
+/// annotations the injector of the mesh adds its proxy to pods by. Since they are part of the
+/// pods Rudr renders, they survive every reconcile.
+///
+/// With Istio, the trait may also create a PeerAuthentication for the mutual TLS of the pods,
+/// and a Sidecar limiting the hosts their proxies reach.
+#[derive(Clone, Debug)]
+pub struct ServiceMesh {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// `istio` or `linkerd`, `istio` if unset.
+    pub mesh: Option<String>,
+    /// The Istio revision to inject the sidecar of, the default one if unset.
+    pub revision: Option<String>,
+    /// The mutual TLS mode of the PeerAuthentication, none if unset.
+    pub mtls: Option<String>,
+    /// The hosts of the Sidecar the proxies reach, like `./*` or `istio-system/*`, every host
+    /// without a Sidecar if unset.
+    pub egress_hosts: Option<Vec<String>>,
+    pub owner_ref: OwnerRefs,
+}
+
+impl ServiceMesh {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        ServiceMesh {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            mesh: string("mesh"),
+            revision: string("revision"),
+            mtls: string("mtls"),
+            egress_hosts: properties_map
+                .and_then(|map| map.get("egressHosts"))
+                .and_then(|h| h.as_array())
+                .map(|h| {
+                    h.iter()
+                        .filter_map(|h| h.as_str().map(String::from))
+                        .collect()
+                }),
+        }
+    }
+
+    /// mesh is the mesh of the trait, failing unless it is a known one or its properties are
+    /// ones of another mesh.
+    fn mesh(&self) -> Result<&str, failure::Error> {
+        match self.mesh.as_deref().unwrap_or(ISTIO_MESH) {
+            ISTIO_MESH => Ok(ISTIO_MESH),
+            LINKERD_MESH => {
+                if self.revision.is_some() || self.mtls.is_some() || self.egress_hosts.is_some() {
+                    return Err(format_err!(
+                        "service-mesh of {} has Istio properties but the linkerd mesh",
+                        self.instance_name
+                    ));
+                }
+                Ok(LINKERD_MESH)
+            }
+            other => Err(format_err!(
+                "service-mesh {} is neither {} nor {}",
+                other,
+                ISTIO_MESH,
+                LINKERD_MESH
+            )),
+        }
+    }
+
+    /// to_pod_patch is the labels or annotations that enroll the pods in the mesh.
+    pub fn to_pod_patch(&self) -> Result<PodPatch, failure::Error> {
+        let mut patch = PodPatch::default();
+        match self.mesh()? {
+            LINKERD_MESH => {
+                patch
+                    .annotations
+                    .insert("linkerd.io/inject".to_string(), "enabled".to_string());
+            }
+            _ => match self.revision.as_ref() {
+                Some(revision) => {
+                    patch
+                        .labels
+                        .insert("istio.io/rev".to_string(), revision.clone());
+                }
+                None => {
+                    patch
+                        .labels
+                        .insert("sidecar.istio.io/inject".to_string(), "true".to_string());
+                }
+            },
+        }
+        Ok(patch)
+    }
+
+    /// to_objects renders the Istio objects of the trait, with the kinds they are shown as in
+    /// the status and their resources.
+    fn to_objects(&self, ns: &str) -> Result<Vec<(&str, RawApi, Value)>, failure::Error> {
+        let mut objects = vec![];
+        if self.mesh()? != ISTIO_MESH {
+            return Ok(objects);
+        }
+        let metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+            "ownerReferences": self.owner_ref,
+        });
+        let selector = instance_selector(self.name.clone(), self.instance_name.clone());
+        if let Some(mode) = self.mtls.as_ref() {
+            if !MTLS_MODES.contains(&mode.as_str()) {
+                return Err(format_err!(
+                    "service-mesh mtls {} is none of {}",
+                    mode,
+                    MTLS_MODES.join(", ")
+                ));
+            }
+            objects.push((
+                "peerauthentication",
+                RawApi::customResource("peerauthentications")
+                    .group("security.istio.io")
+                    .version("v1beta1")
+                    .within(ns),
+                json!({
+                    "apiVersion": "security.istio.io/v1beta1",
+                    "kind": "PeerAuthentication",
+                    "metadata": metadata,
+                    "spec": {
+                        "selector": {"matchLabels": selector},
+                        "mtls": {"mode": mode},
+                    },
+                }),
+            ));
+        }
+        if let Some(hosts) = self.egress_hosts.as_ref() {
+            if hosts.is_empty() {
+                return Err(format_err!(
+                    "service-mesh of {} has no egress hosts",
+                    self.instance_name
+                ));
+            }
+            objects.push((
+                "sidecar",
+                RawApi::customResource("sidecars")
+                    .group("networking.istio.io")
+                    .version("v1beta1")
+                    .within(ns),
+                json!({
+                    "apiVersion": "networking.istio.io/v1beta1",
+                    "kind": "Sidecar",
+                    "metadata": metadata,
+                    "spec": {
+                        "workloadSelector": {"labels": selector},
+                        "egress": [{"hosts": hosts}],
+                    },
+                }),
+            ));
+        }
+        Ok(objects)
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-service-mesh", self.instance_name)
+    }
+}
+
+impl TraitImplementation for ServiceMesh {
+    fn render(&self) -> RenderResult {
+        Ok(self
+            .to_objects("")?
+            .into_iter()
+            .map(|(_, _, object)| object)
+            .collect())
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        for (_, resource, object) in self.to_objects(ns)? {
+            apply(&client, &resource, self.kube_name().as_str(), &object)?;
+        }
+        Ok(())
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        for (_, resource, _) in self.to_objects(ns)? {
+            let req = resource.delete(self.kube_name().as_str(), &Default::default())?;
+            client.timed_request::<Value>(req)?;
+        }
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(self.to_pod_patch()?))
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // The proxy of the mesh never exits, which would keep a task from completing.
+        name == SERVER_NAME
+            || name == SINGLETON_SERVER_NAME
+            || name == WORKER_NAME
+            || name == SINGLETON_WORKER
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let objects = self.to_objects(ns).ok()?;
+        if objects.is_empty() {
+            return None;
+        }
+        let mut resource = BTreeMap::new();
+        for (kind, api, _) in objects {
+            let key = format!("{}/{}", kind, self.kube_name());
+            let state = match api
+                .get(self.kube_name().as_str())
+                .map_err(failure::Error::from)
+                .and_then(|req| Ok(client.timed_request::<Value>(req)?))
+            {
+                Ok(_) => "created".to_string(),
+                Err(e) => {
+                    if e.to_string().contains("NotFound") {
+                        warn!("Service mesh {} not found {}. Recreating ...", kind, e);
+                        self.add(ns, client.clone()).unwrap_or(());
+                    }
+                    e.to_string()
+                }
+            };
+            resource.insert(key, state);
+        }
+        Some(resource)
+    }
+}
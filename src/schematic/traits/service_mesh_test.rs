@@ -0,0 +1,318 @@
+use crate::schematic::traits::service_mesh::{ISTIO_MESH, LINKERD_MESH};
+use crate::schematic::traits::*;
+use crate::workload_type::{
+    SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_TASK_NAME, SINGLETON_WORKER, TASK_NAME,
+    WORKER_NAME,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_service_mesh_istio() {
+    let mesh = ServiceMesh {
+        name: "my-mesh".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        mesh: None,
+        revision: None,
+        mtls: None,
+        egress_hosts: None,
+        owner_ref: None,
+    };
+    let patch = mesh.to_pod_patch().expect("pod patch");
+    assert_eq!(1, patch.labels.len());
+    assert_eq!("true", patch.labels["sidecar.istio.io/inject"]);
+    assert!(patch.annotations.is_empty());
+    // Injecting the sidecar needs nothing to create.
+    assert!(mesh.render().expect("render").is_empty());
+
+    // A revision of Istio injects its own sidecar instead.
+    let mesh = ServiceMesh {
+        name: "my-mesh".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        mesh: Some(ISTIO_MESH.into()),
+        revision: Some("1-20".into()),
+        mtls: None,
+        egress_hosts: None,
+        owner_ref: None,
+    };
+    let patch = mesh.to_pod_patch().expect("pod patch");
+    assert_eq!("1-20", patch.labels["istio.io/rev"]);
+    assert!(!patch.labels.contains_key("sidecar.istio.io/inject"));
+    assert!(mesh.render().expect("render").is_empty());
+}
+
+#[test]
+fn test_service_mesh_peer_authentication() {
+    let mesh = ServiceMesh {
+        name: "my-mesh".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        mesh: None,
+        revision: None,
+        mtls: Some("STRICT".into()),
+        egress_hosts: None,
+        owner_ref: None,
+    };
+    let objects = mesh.render().expect("render");
+    assert_eq!(1, objects.len());
+    assert_eq!("security.istio.io/v1beta1", objects[0]["apiVersion"]);
+    assert_eq!("PeerAuthentication", objects[0]["kind"]);
+    assert_eq!("squid-trait-service-mesh", objects[0]["metadata"]["name"]);
+    assert!(objects[0]["metadata"]["ownerReferences"].is_null());
+    assert_eq!(
+        json!({
+            "selector": {"matchLabels": {
+                "app.kubernetes.io/name": "my-mesh",
+                "oam.dev/instance-name": "squid"
+            }},
+            "mtls": {"mode": "STRICT"}
+        }),
+        objects[0]["spec"]
+    );
+
+    for mode in &["PERMISSIVE", "DISABLE"] {
+        let mesh = ServiceMesh {
+            name: "my-mesh".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            mesh: None,
+            revision: None,
+            mtls: Some(mode.to_string()),
+            egress_hosts: None,
+            owner_ref: None,
+        };
+        assert_eq!(
+            json!(mode),
+            mesh.render().expect("render")[0]["spec"]["mtls"]["mode"]
+        );
+    }
+}
+
+#[test]
+fn test_service_mesh_sidecar() {
+    let mesh = ServiceMesh {
+        name: "my-mesh".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        mesh: None,
+        revision: None,
+        mtls: None,
+        egress_hosts: Some(vec!["./*".into(), "istio-system/*".into()]),
+        owner_ref: None,
+    };
+    let objects = mesh.render().expect("render");
+    assert_eq!(1, objects.len());
+    assert_eq!("networking.istio.io/v1beta1", objects[0]["apiVersion"]);
+    assert_eq!("Sidecar", objects[0]["kind"]);
+    assert_eq!(
+        json!({
+            "workloadSelector": {"labels": {
+                "app.kubernetes.io/name": "my-mesh",
+                "oam.dev/instance-name": "squid"
+            }},
+            "egress": [{"hosts": ["./*", "istio-system/*"]}]
+        }),
+        objects[0]["spec"]
+    );
+
+    // Both objects share the name of the trait, they are of different kinds.
+    let mesh = ServiceMesh {
+        mtls: Some("STRICT".into()),
+        ..mesh
+    };
+    let objects = mesh.render().expect("render");
+    assert_eq!(2, objects.len());
+    assert_eq!("PeerAuthentication", objects[0]["kind"]);
+    assert_eq!("Sidecar", objects[1]["kind"]);
+    assert_eq!(objects[0]["metadata"], objects[1]["metadata"]);
+}
+
+#[test]
+fn test_service_mesh_linkerd() {
+    let mesh = ServiceMesh {
+        name: "my-mesh".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        mesh: Some(LINKERD_MESH.into()),
+        revision: None,
+        mtls: None,
+        egress_hosts: None,
+        owner_ref: None,
+    };
+    let patch = mesh.to_pod_patch().expect("pod patch");
+    assert_eq!(1, patch.annotations.len());
+    assert_eq!("enabled", patch.annotations["linkerd.io/inject"]);
+    assert!(patch.labels.is_empty());
+    assert!(mesh.render().expect("render").is_empty());
+}
+
+#[test]
+fn test_service_mesh_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "patsy-squid".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let mesh = ServiceMesh {
+        name: "my-mesh".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        mesh: None,
+        revision: None,
+        mtls: Some("STRICT".into()),
+        egress_hosts: Some(vec!["./*".into()]),
+        owner_ref: Some(vec![owner.clone()]),
+    };
+    for object in mesh.render().expect("render") {
+        assert_eq!(
+            serde_json::to_value(vec![owner.clone()]).unwrap(),
+            object["metadata"]["ownerReferences"]
+        );
+    }
+}
+
+#[test]
+fn test_service_mesh_invalid() {
+    let invalid = |mesh: ServiceMesh| mesh.render().unwrap_err().to_string();
+    assert_eq!(
+        "service-mesh consul is neither istio nor linkerd",
+        invalid(ServiceMesh {
+            name: "my-mesh".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            mesh: Some("consul".into()),
+            revision: None,
+            mtls: None,
+            egress_hosts: None,
+            owner_ref: None,
+        })
+    );
+    // The Istio objects don't exist in Linkerd.
+    let linkerd = ServiceMesh {
+        name: "my-mesh".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        mesh: Some(LINKERD_MESH.into()),
+        revision: None,
+        mtls: None,
+        egress_hosts: None,
+        owner_ref: None,
+    };
+    for mesh in vec![
+        ServiceMesh {
+            revision: Some("1-20".into()),
+            ..linkerd.clone()
+        },
+        ServiceMesh {
+            mtls: Some("STRICT".into()),
+            ..linkerd.clone()
+        },
+        ServiceMesh {
+            egress_hosts: Some(vec!["./*".into()]),
+            ..linkerd.clone()
+        },
+    ] {
+        assert_eq!(
+            "service-mesh of squid has Istio properties but the linkerd mesh",
+            mesh.to_pod_patch().unwrap_err().to_string()
+        );
+    }
+    assert_eq!(
+        "service-mesh mtls ON is none of STRICT, PERMISSIVE, DISABLE",
+        invalid(ServiceMesh {
+            name: "my-mesh".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            mesh: None,
+            revision: None,
+            mtls: Some("ON".into()),
+            egress_hosts: None,
+            owner_ref: None,
+        })
+    );
+    // The modes of Istio are upper case.
+    assert_eq!(
+        "service-mesh mtls strict is none of STRICT, PERMISSIVE, DISABLE",
+        invalid(ServiceMesh {
+            name: "my-mesh".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            mesh: None,
+            revision: None,
+            mtls: Some("strict".into()),
+            egress_hosts: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "service-mesh of squid has no egress hosts",
+        invalid(ServiceMesh {
+            name: "my-mesh".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            mesh: None,
+            revision: None,
+            mtls: None,
+            egress_hosts: Some(vec![]),
+            owner_ref: None,
+        })
+    );
+}
+
+#[test]
+fn test_service_mesh_v1alpha1() {
+    let service_mesh_alpha1_trait = TraitBinding {
+        name: String::from("service-mesh"),
+        parameter_values: None,
+        properties: Some(json!({
+            "mesh": "istio",
+            "revision": "1-20",
+            "mtls": "PERMISSIVE",
+            "egressHosts": ["./*", 42, "istio-system/*"]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&service_mesh_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let mesh = ServiceMesh::from_properties(
+        "my-mesh".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        None,
+    );
+    assert_eq!(Some("istio".to_string()), mesh.mesh);
+    assert_eq!(Some("1-20".to_string()), mesh.revision);
+    assert_eq!(Some("PERMISSIVE".to_string()), mesh.mtls);
+    // Hosts that aren't strings are skipped.
+    assert_eq!(
+        Some(vec!["./*".to_string(), "istio-system/*".to_string()]),
+        mesh.egress_hosts
+    );
+    assert_eq!(2, mesh.render().expect("render").len());
+}
+
+#[test]
+fn test_service_mesh_workload_types() {
+    for name in &[
+        SERVER_NAME,
+        SINGLETON_SERVER_NAME,
+        WORKER_NAME,
+        SINGLETON_WORKER,
+    ] {
+        assert!(ServiceMesh::supports_workload_type(name));
+    }
+    assert!(!ServiceMesh::supports_workload_type(TASK_NAME));
+    assert!(!ServiceMesh::supports_workload_type(SINGLETON_TASK_NAME));
+}
@@ -0,0 +1,152 @@
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{
+    PodPatch, SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_WORKER, WORKER_NAME,
+};
+use k8s_openapi::api::core::v1 as core;
+use kube::client::APIClient;
+use serde_json::map::Map;
+use serde_json::Value;
+
+/// A Sidecar runs one more container of any image next to the containers of a component, like
+/// a proxy or an agent.
+#[derive(Clone, Debug)]
+pub struct Sidecar {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The name of the container.
+    pub container_name: Option<String>,
+    pub image: Option<String>,
+    pub command: Option<Vec<String>>,
+    pub args: Option<Vec<String>>,
+    /// The environment of the container, `EnvVar`s with a `value` or a `valueFrom`.
+    pub env: Option<Value>,
+    /// The volumes of the pods the container mounts, `VolumeMount`s.
+    pub mounts: Option<Value>,
+    /// The `requests` and `limits` of the resources of the container.
+    pub resources: Option<Value>,
+    /// The names of the containers of the component, which the container can't take.
+    pub component_containers: Vec<String>,
+}
+
+impl Sidecar {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        component_containers: Vec<String>,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        let strings = |key: &str| {
+            properties_map
+                .and_then(|map| map.get(key))
+                .and_then(|p| p.as_array())
+                .map(|p| {
+                    p.iter()
+                        .filter_map(|s| s.as_str().map(String::from))
+                        .collect()
+                })
+        };
+        let value = |key: &str| {
+            properties_map
+                .and_then(|map| map.get(key))
+                .filter(|v| !v.is_null())
+                .cloned()
+        };
+        Sidecar {
+            name,
+            instance_name,
+            component_name,
+            component_containers,
+            container_name: string("name"),
+            image: string("image"),
+            command: strings("command"),
+            args: strings("args"),
+            env: value("env"),
+            mounts: value("mounts"),
+            resources: value("resources"),
+        }
+    }
+
+    /// to_container renders the sidecar container.
+    pub fn to_container(&self) -> Result<core::Container, failure::Error> {
+        let name = self
+            .container_name
+            .clone()
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| format_err!("sidecar of {} has no name", self.instance_name))?;
+        if self.component_containers.contains(&name) {
+            return Err(format_err!(
+                "sidecar {} of {} has the name of a container of the component",
+                name,
+                self.instance_name
+            ));
+        }
+        let image = self
+            .image
+            .clone()
+            .filter(|i| !i.is_empty())
+            .ok_or_else(|| {
+                format_err!("sidecar {} of {} has no image", name, self.instance_name)
+            })?;
+        Ok(core::Container {
+            env: self.parse(&name, "env", self.env.as_ref())?,
+            volume_mounts: self.parse(&name, "mounts", self.mounts.as_ref())?,
+            resources: self.parse(&name, "resources", self.resources.as_ref())?,
+            name,
+            image: Some(image),
+            command: self.command.clone(),
+            args: self.args.clone(),
+            ..Default::default()
+        })
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(
+        &self,
+        container: &str,
+        property: &str,
+        value: Option<&Value>,
+    ) -> Result<Option<T>, failure::Error> {
+        value
+            .map(|v| {
+                serde_json::from_value(v.clone()).map_err(|e| {
+                    format_err!(
+                        "invalid {} of sidecar {} of {}: {}",
+                        property,
+                        container,
+                        self.instance_name,
+                        e
+                    )
+                })
+            })
+            .transpose()
+    }
+}
+
+impl TraitImplementation for Sidecar {
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn delete(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(PodPatch {
+            sidecars: vec![self.to_container()?],
+            ..Default::default()
+        }))
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // A sidecar that never exits would keep a task from completing.
+        name == SERVER_NAME
+            || name == SINGLETON_SERVER_NAME
+            || name == WORKER_NAME
+            || name == SINGLETON_WORKER
+    }
+}
@@ -0,0 +1,285 @@
+use crate::schematic::traits::*;
+use crate::workload_type::{
+    SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_TASK_NAME, SINGLETON_WORKER, TASK_NAME,
+    WORKER_NAME,
+};
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_sidecar_container() {
+    let sidecar = Sidecar {
+        name: "my-sidecar".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        container_name: Some("agent".into()),
+        image: Some("datadog/agent:7".into()),
+        command: None,
+        args: None,
+        env: None,
+        mounts: None,
+        resources: None,
+        component_containers: vec!["server".into()],
+    };
+    let container = sidecar.to_container().expect("container");
+    assert_eq!("agent", container.name);
+    assert_eq!(Some("datadog/agent:7".to_string()), container.image);
+    // The entrypoint of the image runs unless the sidecar says otherwise.
+    assert!(container.command.is_none());
+    assert!(container.args.is_none());
+    assert!(container.env.is_none());
+    assert!(container.volume_mounts.is_none());
+    assert!(container.resources.is_none());
+
+    let sidecar = Sidecar {
+        name: "my-sidecar".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        container_name: Some("proxy".into()),
+        image: Some("envoyproxy/envoy:v1.28.0".into()),
+        command: Some(vec!["envoy".into()]),
+        args: Some(vec!["-c".into(), "/etc/envoy/envoy.yaml".into()]),
+        env: None,
+        mounts: None,
+        resources: None,
+        component_containers: vec!["server".into()],
+    };
+    let container = sidecar.to_container().expect("container");
+    assert_eq!(Some(vec!["envoy".to_string()]), container.command);
+    assert_eq!(
+        Some(vec!["-c".to_string(), "/etc/envoy/envoy.yaml".to_string()]),
+        container.args
+    );
+}
+
+#[test]
+fn test_sidecar_env_mounts_resources() {
+    let sidecar = Sidecar {
+        name: "my-sidecar".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        container_name: Some("agent".into()),
+        image: Some("datadog/agent:7".into()),
+        command: None,
+        args: None,
+        env: Some(json!([
+            {"name": "LOG_LEVEL", "value": "info"},
+            {"name": "POD_IP", "valueFrom": {"fieldRef": {"fieldPath": "status.podIP"}}}
+        ])),
+        mounts: Some(json!([
+            {"name": "envoy-config", "mountPath": "/etc/envoy", "readOnly": true}
+        ])),
+        resources: Some(json!({
+            "requests": {"cpu": "100m", "memory": "64Mi"},
+            "limits": {"memory": "128Mi"}
+        })),
+        component_containers: vec!["server".into()],
+    };
+    let container = sidecar.to_container().expect("container");
+    let env = container.env.expect("env");
+    assert_eq!("LOG_LEVEL", env[0].name);
+    assert_eq!(Some("info".to_string()), env[0].value);
+    assert_eq!(
+        Some("status.podIP".to_string()),
+        env[1]
+            .value_from
+            .as_ref()
+            .and_then(|v| v.field_ref.as_ref())
+            .map(|f| f.field_path.clone())
+    );
+    let mounts = container.volume_mounts.expect("mounts");
+    assert_eq!("envoy-config", mounts[0].name);
+    assert_eq!("/etc/envoy", mounts[0].mount_path);
+    assert_eq!(Some(true), mounts[0].read_only);
+    let resources = container.resources.expect("resources");
+    let requests = resources.requests.expect("requests");
+    assert_eq!("100m", requests["cpu"].0);
+    assert_eq!("64Mi", requests["memory"].0);
+    assert_eq!("128Mi", resources.limits.expect("limits")["memory"].0);
+}
+
+#[test]
+fn test_sidecar_pod_patch() {
+    let sidecar = Sidecar {
+        name: "my-sidecar".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        container_name: Some("agent".into()),
+        image: Some("datadog/agent:7".into()),
+        command: None,
+        args: None,
+        env: None,
+        mounts: None,
+        resources: None,
+        component_containers: vec!["server".into()],
+    };
+    let patch = sidecar
+        .pod_patch()
+        .expect("pod patch")
+        .expect("a pod patch");
+    assert_eq!(1, patch.sidecars.len());
+    assert_eq!("agent", patch.sidecars[0].name);
+    // The sidecar runs next to the containers of the component, not before them.
+    assert!(patch.init_containers.is_empty());
+    assert!(patch.volumes.is_empty());
+}
+
+#[test]
+fn test_sidecar_invalid() {
+    let invalid = |sidecar: Sidecar| sidecar.to_container().unwrap_err().to_string();
+    for name in &[None, Some("")] {
+        assert_eq!(
+            "sidecar of squid has no name",
+            invalid(Sidecar {
+                name: "my-sidecar".into(),
+                instance_name: "squid".into(),
+                component_name: "patsy".into(),
+                container_name: name.map(String::from),
+                image: Some("datadog/agent:7".into()),
+                command: None,
+                args: None,
+                env: None,
+                mounts: None,
+                resources: None,
+                component_containers: vec!["server".into()],
+            })
+        );
+    }
+    assert_eq!(
+        "sidecar server of squid has the name of a container of the component",
+        invalid(Sidecar {
+            name: "my-sidecar".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            container_name: Some("server".into()),
+            image: Some("datadog/agent:7".into()),
+            command: None,
+            args: None,
+            env: None,
+            mounts: None,
+            resources: None,
+            component_containers: vec!["server".into()],
+        })
+    );
+    for image in &[None, Some("")] {
+        assert_eq!(
+            "sidecar agent of squid has no image",
+            invalid(Sidecar {
+                name: "my-sidecar".into(),
+                instance_name: "squid".into(),
+                component_name: "patsy".into(),
+                container_name: Some("agent".into()),
+                image: image.map(String::from),
+                command: None,
+                args: None,
+                env: None,
+                mounts: None,
+                resources: None,
+                component_containers: vec!["server".into()],
+            })
+        );
+    }
+    assert!(invalid(Sidecar {
+        name: "my-sidecar".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        container_name: Some("agent".into()),
+        image: Some("datadog/agent:7".into()),
+        command: None,
+        args: None,
+        env: None,
+        mounts: Some(json!([{"name": "a"}])),
+        resources: None,
+        component_containers: vec!["server".into()],
+    })
+    .starts_with("invalid mounts of sidecar agent of squid"));
+    assert!(invalid(Sidecar {
+        name: "my-sidecar".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        container_name: Some("agent".into()),
+        image: Some("datadog/agent:7".into()),
+        command: None,
+        args: None,
+        env: Some(json!({"LOG_LEVEL": "info"})),
+        mounts: None,
+        resources: None,
+        component_containers: vec!["server".into()],
+    })
+    .starts_with("invalid env of sidecar agent of squid"));
+    assert!(invalid(Sidecar {
+        name: "my-sidecar".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        container_name: Some("agent".into()),
+        image: Some("datadog/agent:7".into()),
+        command: None,
+        args: None,
+        env: None,
+        mounts: None,
+        resources: Some(json!({"requests": "100m"})),
+        component_containers: vec!["server".into()],
+    })
+    .starts_with("invalid resources of sidecar agent of squid"));
+}
+
+#[test]
+fn test_sidecar_v1alpha1() {
+    let sidecar_alpha1_trait = TraitBinding {
+        name: String::from("sidecar"),
+        parameter_values: None,
+        properties: Some(json!({
+            "name": "proxy",
+            "image": "envoyproxy/envoy:v1.28.0",
+            "command": ["envoy", 1],
+            "args": ["-c", "/etc/envoy/envoy.yaml"],
+            "env": null,
+            "mounts": [{"name": "envoy-config", "mountPath": "/etc/envoy"}]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&sidecar_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let sidecar = Sidecar::from_properties(
+        "my-sidecar".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+        vec!["server".into()],
+    );
+    assert_eq!(Some("proxy".to_string()), sidecar.container_name);
+    assert_eq!(Some("envoyproxy/envoy:v1.28.0".to_string()), sidecar.image);
+    // Arguments that aren't strings are skipped.
+    assert_eq!(Some(vec!["envoy".to_string()]), sidecar.command);
+    assert_eq!(
+        Some(vec!["-c".to_string(), "/etc/envoy/envoy.yaml".to_string()]),
+        sidecar.args
+    );
+    // A null env is no env, as if it were left out.
+    assert_eq!(None, sidecar.env);
+    assert_eq!(
+        Some(json!([{"name": "envoy-config", "mountPath": "/etc/envoy"}])),
+        sidecar.mounts
+    );
+    assert_eq!(None, sidecar.resources);
+    assert_eq!(vec!["server".to_string()], sidecar.component_containers);
+}
+
+#[test]
+fn test_sidecar_workload_types() {
+    for name in &[
+        SERVER_NAME,
+        SINGLETON_SERVER_NAME,
+        WORKER_NAME,
+        SINGLETON_WORKER,
+    ] {
+        assert!(Sidecar::supports_workload_type(name));
+    }
+    assert!(!Sidecar::supports_workload_type(TASK_NAME));
+    assert!(!Sidecar::supports_workload_type(SINGLETON_TASK_NAME));
+}
@@ -0,0 +1,136 @@
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::PodPatch;
+use k8s_openapi::api::core::v1 as core;
+use kube::client::APIClient;
+use serde_json::map::Map;
+use serde_json::Value;
+
+/// The operators of a toleration.
+const OPERATORS: &[&str] = &["Equal", "Exists"];
+/// The effects of a taint.
+const EFFECTS: &[&str] = &["NoSchedule", "PreferNoSchedule", "NoExecute"];
+
+/// Tolerations let the pods of a component run on nodes with the taints they tolerate, like a
+/// pool of GPU or spot nodes reserved with a taint.
+#[derive(Clone, Debug)]
+pub struct Tolerations {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The tolerations of the pods, see `Toleration`.
+    pub tolerations: Option<Value>,
+}
+
+/// Toleration tolerates the taints with a key, a value and an effect.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Toleration {
+    /// The key of the taints, every key if unset, which takes the `Exists` operator.
+    pub key: Option<String>,
+    /// `Equal` or `Exists`, `Equal` if unset.
+    pub operator: Option<String>,
+    /// The value of the taints for the `Equal` operator.
+    pub value: Option<String>,
+    /// `NoSchedule`, `PreferNoSchedule` or `NoExecute`, every effect if unset.
+    pub effect: Option<String>,
+    /// How long pods stay on a node after it is tainted with a `NoExecute` taint.
+    pub toleration_seconds: Option<i64>,
+}
+
+impl Tolerations {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+    ) -> Self {
+        Tolerations {
+            name,
+            instance_name,
+            component_name,
+            tolerations: properties_map
+                .and_then(|map| map.get("tolerations"))
+                .cloned(),
+        }
+    }
+
+    /// to_tolerations renders the tolerations of the pods.
+    pub fn to_tolerations(&self) -> Result<Vec<core::Toleration>, failure::Error> {
+        let tolerations: Vec<Toleration> = match self.tolerations.as_ref() {
+            Some(tolerations) => serde_json::from_value(tolerations.clone())
+                .map_err(|e| format_err!("invalid tolerations of {}: {}", self.instance_name, e))?,
+            None => vec![],
+        };
+        if tolerations.is_empty() {
+            return Err(format_err!(
+                "tolerations of {} tolerate no taints",
+                self.instance_name
+            ));
+        }
+        tolerations
+            .into_iter()
+            .map(|t| {
+                let operator = t.operator.unwrap_or_else(|| "Equal".to_string());
+                if !OPERATORS.contains(&operator.as_str()) {
+                    return Err(format_err!(
+                        "toleration operator {} is none of {}",
+                        operator,
+                        OPERATORS.join(", ")
+                    ));
+                }
+                if operator == "Exists" && t.value.is_some() {
+                    return Err(format_err!(
+                        "toleration of {} with the Exists operator has a value",
+                        t.key.unwrap_or_default()
+                    ));
+                }
+                if t.key.is_none() && operator != "Exists" {
+                    return Err(format_err!(
+                        "a toleration of every key of {} needs the Exists operator",
+                        self.instance_name
+                    ));
+                }
+                if let Some(effect) = t.effect.as_ref() {
+                    if !EFFECTS.contains(&effect.as_str()) {
+                        return Err(format_err!(
+                            "toleration effect {} is none of {}",
+                            effect,
+                            EFFECTS.join(", ")
+                        ));
+                    }
+                }
+                if t.toleration_seconds.is_some() && t.effect.as_deref() != Some("NoExecute") {
+                    return Err(format_err!(
+                        "only tolerations of NoExecute taints of {} have tolerationSeconds",
+                        self.instance_name
+                    ));
+                }
+                Ok(core::Toleration {
+                    key: t.key,
+                    operator: Some(operator),
+                    value: t.value,
+                    effect: t.effect,
+                    toleration_seconds: t.toleration_seconds,
+                })
+            })
+            .collect()
+    }
+}
+
+impl TraitImplementation for Tolerations {
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn delete(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(PodPatch {
+            tolerations: self.to_tolerations()?,
+            ..Default::default()
+        }))
+    }
+}
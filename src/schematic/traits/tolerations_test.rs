@@ -0,0 +1,165 @@
+use crate::schematic::traits::*;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_tolerations_equal() {
+    let tolerations = Tolerations {
+        name: "my-tolerations".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        tolerations: Some(json!([
+            {"key": "nvidia.com/gpu", "value": "present", "effect": "NoSchedule"}
+        ])),
+    };
+    let tolerations = tolerations.to_tolerations().expect("tolerations");
+    assert_eq!(1, tolerations.len());
+    assert_eq!(Some("nvidia.com/gpu".to_string()), tolerations[0].key);
+    // The operator is Equal unless it says otherwise.
+    assert_eq!(Some("Equal".to_string()), tolerations[0].operator);
+    assert_eq!(Some("present".to_string()), tolerations[0].value);
+    assert_eq!(Some("NoSchedule".to_string()), tolerations[0].effect);
+    assert_eq!(None, tolerations[0].toleration_seconds);
+
+    // Without an effect every effect of the taint is tolerated.
+    let tolerations = Tolerations {
+        name: "my-tolerations".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        tolerations: Some(json!([{"key": "dedicated", "value": "shop"}])),
+    };
+    let tolerations = tolerations.to_tolerations().expect("tolerations");
+    assert_eq!(None, tolerations[0].effect);
+}
+
+#[test]
+fn test_tolerations_exists() {
+    let tolerations = Tolerations {
+        name: "my-tolerations".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        tolerations: Some(json!([
+            {"key": "spot", "operator": "Exists", "effect": "NoExecute", "tolerationSeconds": 30},
+            {"operator": "Exists", "effect": "PreferNoSchedule"}
+        ])),
+    };
+    let tolerations = tolerations.to_tolerations().expect("tolerations");
+    assert_eq!(2, tolerations.len());
+    assert_eq!(Some("spot".to_string()), tolerations[0].key);
+    assert_eq!(Some("Exists".to_string()), tolerations[0].operator);
+    assert_eq!(None, tolerations[0].value);
+    assert_eq!(Some(30), tolerations[0].toleration_seconds);
+    // The Exists operator without a key tolerates every taint of the effect.
+    assert_eq!(None, tolerations[1].key);
+    assert_eq!(Some("PreferNoSchedule".to_string()), tolerations[1].effect);
+}
+
+#[test]
+fn test_tolerations_pod_patch() {
+    let tolerations = Tolerations {
+        name: "my-tolerations".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        tolerations: Some(json!([
+            {"key": "nvidia.com/gpu", "value": "present", "effect": "NoSchedule"}
+        ])),
+    };
+    let patch = tolerations
+        .pod_patch()
+        .expect("pod patch")
+        .expect("a pod patch");
+    assert_eq!(1, patch.tolerations.len());
+    assert_eq!(Some("nvidia.com/gpu".to_string()), patch.tolerations[0].key);
+    // Tolerations let the pods run on tainted nodes, they don't pick the nodes.
+    assert!(patch.node_selector.is_empty());
+    assert!(patch.affinity.is_none());
+}
+
+#[test]
+fn test_tolerations_invalid() {
+    let invalid = |tolerations: Option<serde_json::Value>| {
+        Tolerations {
+            name: "my-tolerations".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            tolerations,
+        }
+        .to_tolerations()
+        .unwrap_err()
+        .to_string()
+    };
+    assert_eq!("tolerations of squid tolerate no taints", invalid(None));
+    assert_eq!(
+        "tolerations of squid tolerate no taints",
+        invalid(Some(json!([])))
+    );
+    assert!(invalid(Some(json!({"key": "spot"}))).starts_with("invalid tolerations of squid"));
+    assert_eq!(
+        "toleration operator In is none of Equal, Exists",
+        invalid(Some(json!([{"key": "spot", "operator": "In"}])))
+    );
+    assert_eq!(
+        "toleration of spot with the Exists operator has a value",
+        invalid(Some(json!([
+            {"key": "spot", "operator": "Exists", "value": "true"}
+        ])))
+    );
+    assert_eq!(
+        "a toleration of every key of squid needs the Exists operator",
+        invalid(Some(json!([{"value": "true"}])))
+    );
+    assert_eq!(
+        "toleration effect NoRun is none of NoSchedule, PreferNoSchedule, NoExecute",
+        invalid(Some(json!([{"key": "spot", "effect": "NoRun"}])))
+    );
+    for effect in &[json!("NoSchedule"), json!(null)] {
+        assert_eq!(
+            "only tolerations of NoExecute taints of squid have tolerationSeconds",
+            invalid(Some(json!([
+                {"key": "spot", "effect": effect, "tolerationSeconds": 30}
+            ])))
+        );
+    }
+}
+
+#[test]
+fn test_tolerations_v1alpha1() {
+    let tolerations_alpha1_trait = TraitBinding {
+        name: String::from("tolerations"),
+        parameter_values: None,
+        properties: Some(json!({
+            "tolerations": [{
+                "key": "spot",
+                "operator": "Exists",
+                "effect": "NoExecute",
+                "tolerationSeconds": 60
+            }]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&tolerations_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let tolerations = Tolerations::from_properties(
+        "my-tolerations".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+    );
+    let tolerations = tolerations.to_tolerations().expect("tolerations");
+    assert_eq!(Some("spot".to_string()), tolerations[0].key);
+    assert_eq!(Some("NoExecute".to_string()), tolerations[0].effect);
+    assert_eq!(Some(60), tolerations[0].toleration_seconds);
+
+    let tolerations = Tolerations::from_properties(
+        "my-tolerations".into(),
+        "squid".into(),
+        "patsy".into(),
+        None,
+    );
+    assert_eq!(None, tolerations.tolerations);
+}
@@ -0,0 +1,124 @@
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::PodPatch;
+use kube::client::APIClient;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+
+/// The topology key of a constraint, which spreads the pods across zones.
+pub const ZONE_TOPOLOGY_KEY: &str = "topology.kubernetes.io/zone";
+/// What the scheduler does with a pod that can't be placed within the skew.
+const WHEN_UNSATISFIABLE: &[&str] = &["DoNotSchedule", "ScheduleAnyway"];
+
+/// A TopologySpread spreads the pods of a component evenly across the nodes of topologies like
+/// zones or hosts, with the topology spread constraints of the pods.
+#[derive(Clone, Debug)]
+pub struct TopologySpread {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// The constraints of the pods, see `SpreadConstraint`.
+    pub constraints: Option<Value>,
+}
+
+/// SpreadConstraint keeps the number of pods of the component in any two topologies of a
+/// kind, like two zones, from differing by more than `max_skew`.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SpreadConstraint {
+    /// The difference of the numbers of pods allowed, 1 if unset.
+    pub max_skew: Option<i32>,
+    /// The node label of the topology, `topology.kubernetes.io/zone` if unset.
+    pub topology_key: Option<String>,
+    /// `DoNotSchedule` or `ScheduleAnyway`, `ScheduleAnyway` if unset.
+    pub when_unsatisfiable: Option<String>,
+}
+
+impl TopologySpread {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+    ) -> Self {
+        TopologySpread {
+            name,
+            instance_name,
+            component_name,
+            constraints: properties_map
+                .and_then(|map| map.get("constraints"))
+                .cloned(),
+        }
+    }
+
+    /// to_constraints renders the topology spread constraints of the pods, which select the
+    /// pods of the instance.
+    pub fn to_constraints(&self) -> Result<Vec<Value>, failure::Error> {
+        let constraints: Vec<SpreadConstraint> = match self.constraints.as_ref() {
+            Some(constraints) => serde_json::from_value(constraints.clone()).map_err(|e| {
+                format_err!(
+                    "invalid topology-spread constraints of {}: {}",
+                    self.instance_name,
+                    e
+                )
+            })?,
+            // Spreading across zones is what the trait is for.
+            None => vec![SpreadConstraint::default()],
+        };
+        if constraints.is_empty() {
+            return Err(format_err!(
+                "topology-spread of {} has no constraints",
+                self.instance_name
+            ));
+        }
+        let selector = instance_selector(self.name.clone(), self.instance_name.clone());
+        constraints
+            .into_iter()
+            .map(|c| {
+                let max_skew = c.max_skew.unwrap_or(1);
+                if max_skew < 1 {
+                    return Err(format_err!(
+                        "topology-spread maxSkew of {} isn't a positive number: {}",
+                        self.instance_name,
+                        max_skew
+                    ));
+                }
+                let when_unsatisfiable = c
+                    .when_unsatisfiable
+                    .unwrap_or_else(|| "ScheduleAnyway".to_string());
+                if !WHEN_UNSATISFIABLE.contains(&when_unsatisfiable.as_str()) {
+                    return Err(format_err!(
+                        "topology-spread whenUnsatisfiable {} is none of {}",
+                        when_unsatisfiable,
+                        WHEN_UNSATISFIABLE.join(", ")
+                    ));
+                }
+                Ok(json!({
+                    "maxSkew": max_skew,
+                    "topologyKey": c
+                        .topology_key
+                        .unwrap_or_else(|| ZONE_TOPOLOGY_KEY.to_string()),
+                    "whenUnsatisfiable": when_unsatisfiable,
+                    "labelSelector": {"matchLabels": selector},
+                }))
+            })
+            .collect()
+    }
+}
+
+impl TraitImplementation for TopologySpread {
+    fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn modify(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn delete(&self, _ns: &str, _client: APIClient) -> TraitResult {
+        Ok(())
+    }
+    fn pod_patch(&self) -> Result<Option<PodPatch>, failure::Error> {
+        Ok(Some(PodPatch {
+            topology_spread_constraints: self.to_constraints()?,
+            ..Default::default()
+        }))
+    }
+}
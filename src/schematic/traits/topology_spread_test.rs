@@ -0,0 +1,159 @@
+use crate::schematic::traits::topology_spread::ZONE_TOPOLOGY_KEY;
+use crate::schematic::traits::*;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_topology_spread_defaults() {
+    // Without constraints the pods are spread across zones.
+    let topology_spread = TopologySpread {
+        name: "my-spread".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        constraints: None,
+    };
+    let constraints = topology_spread.to_constraints().expect("constraints");
+    assert_eq!(
+        vec![json!({
+            "maxSkew": 1,
+            "topologyKey": ZONE_TOPOLOGY_KEY,
+            "whenUnsatisfiable": "ScheduleAnyway",
+            "labelSelector": {"matchLabels": {
+                "app.kubernetes.io/name": "my-spread",
+                "oam.dev/instance-name": "squid"
+            }}
+        })],
+        constraints
+    );
+
+    // An empty constraint takes the same defaults.
+    let spread = TopologySpread {
+        name: "my-spread".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        constraints: Some(json!([{}])),
+    };
+    assert_eq!(constraints, spread.to_constraints().expect("constraints"));
+}
+
+#[test]
+fn test_topology_spread_constraints() {
+    let spread = TopologySpread {
+        name: "my-spread".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        constraints: Some(json!([
+            {
+                "maxSkew": 2,
+                "topologyKey": "kubernetes.io/hostname",
+                "whenUnsatisfiable": "DoNotSchedule"
+            },
+            {"topologyKey": "topology.kubernetes.io/region"}
+        ])),
+    };
+    let constraints = spread.to_constraints().expect("constraints");
+    assert_eq!(2, constraints.len());
+    assert_eq!(2, constraints[0]["maxSkew"]);
+    assert_eq!("kubernetes.io/hostname", constraints[0]["topologyKey"]);
+    assert_eq!("DoNotSchedule", constraints[0]["whenUnsatisfiable"]);
+    assert_eq!(1, constraints[1]["maxSkew"]);
+    assert_eq!(
+        "topology.kubernetes.io/region",
+        constraints[1]["topologyKey"]
+    );
+    assert_eq!("ScheduleAnyway", constraints[1]["whenUnsatisfiable"]);
+    // Every constraint counts the pods of the instance only.
+    assert_eq!(
+        constraints[0]["labelSelector"],
+        constraints[1]["labelSelector"]
+    );
+}
+
+#[test]
+fn test_topology_spread_pod_patch() {
+    let topology_spread = TopologySpread {
+        name: "my-spread".into(),
+        instance_name: "squid".into(),
+        component_name: "patsy".into(),
+        constraints: None,
+    };
+    let patch = topology_spread
+        .pod_patch()
+        .expect("pod patch")
+        .expect("a pod patch");
+    assert_eq!(1, patch.topology_spread_constraints.len());
+    assert_eq!(
+        ZONE_TOPOLOGY_KEY,
+        patch.topology_spread_constraints[0]["topologyKey"]
+    );
+    assert!(patch.affinity.is_none());
+}
+
+#[test]
+fn test_topology_spread_invalid() {
+    let invalid = |constraints: serde_json::Value| {
+        TopologySpread {
+            name: "my-spread".into(),
+            instance_name: "squid".into(),
+            component_name: "patsy".into(),
+            constraints: Some(constraints),
+        }
+        .to_constraints()
+        .unwrap_err()
+        .to_string()
+    };
+    assert_eq!(
+        "topology-spread of squid has no constraints",
+        invalid(json!([]))
+    );
+    for max_skew in &[0, -1] {
+        assert_eq!(
+            format!(
+                "topology-spread maxSkew of squid isn't a positive number: {}",
+                max_skew
+            ),
+            invalid(json!([{ "maxSkew": max_skew }]))
+        );
+    }
+    assert_eq!(
+        "topology-spread whenUnsatisfiable Sometimes is none of DoNotSchedule, ScheduleAnyway",
+        invalid(json!([{"whenUnsatisfiable": "Sometimes"}]))
+    );
+    assert!(
+        invalid(json!({"maxSkew": 1})).starts_with("invalid topology-spread constraints of squid")
+    );
+    assert!(invalid(json!([{"maxSkew": "1"}]))
+        .starts_with("invalid topology-spread constraints of squid"));
+}
+
+#[test]
+fn test_topology_spread_v1alpha1() {
+    let topology_spread_alpha1_trait = TraitBinding {
+        name: String::from("topology-spread"),
+        parameter_values: None,
+        properties: Some(json!({
+            "constraints": [{"maxSkew": 3, "whenUnsatisfiable": "DoNotSchedule"}]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&topology_spread_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let spread = TopologySpread::from_properties(
+        "my-spread".into(),
+        "squid".into(),
+        "patsy".into(),
+        prop_map,
+    );
+    assert_eq!(
+        Some(json!([{"maxSkew": 3, "whenUnsatisfiable": "DoNotSchedule"}])),
+        spread.constraints
+    );
+    let constraints = spread.to_constraints().expect("constraints");
+    assert_eq!(3, constraints[0]["maxSkew"]);
+    assert_eq!(ZONE_TOPOLOGY_KEY, constraints[0]["topologyKey"]);
+}
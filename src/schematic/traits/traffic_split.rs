@@ -0,0 +1,241 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{apply, RenderResult, SERVER_NAME, SINGLETON_SERVER_NAME};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The provider that renders an SMI TrafficSplit.
+pub const SMI_PROVIDER: &str = "smi";
+/// The provider that renders an Istio VirtualService.
+pub const ISTIO_PROVIDER: &str = "istio";
+
+/// A TrafficSplit sends a share of the requests for the Service of a component to the Services
+/// of other component instances, like a canary of its next revision.
+///
+/// Every instance of a server has a Service named after it, so the split routes to those, and
+/// the instances need no Service selecting the pods of all of them.
+#[derive(Clone, Debug)]
+pub struct TrafficSplit {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// `smi` or `istio`, `smi` if unset.
+    pub provider: Option<String>,
+    /// The Service clients send requests to, the Service of the component if unset.
+    pub service: Option<String>,
+    /// The port of the backends, for VirtualServices of Services with several ports.
+    pub port: Option<i32>,
+    /// The backends the requests are split between, see `TrafficSplitBackend`.
+    pub backends: Option<Value>,
+    pub owner_ref: OwnerRefs,
+}
+
+/// TrafficSplitBackend is a component instance that gets `weight` of the requests.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficSplitBackend {
+    pub instance_name: String,
+    pub weight: u32,
+}
+
+impl TrafficSplit {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+    ) -> Self {
+        let string = |key: &str| {
+            properties_map.and_then(|map| map.get(key).and_then(|p| p.as_str()).map(String::from))
+        };
+        TrafficSplit {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            provider: string("provider"),
+            service: string("service"),
+            port: properties_map
+                .and_then(|map| map.get("port").and_then(|p| p.as_i64()))
+                .map(|p| p as i32),
+            backends: properties_map.and_then(|map| map.get("backends")).cloned(),
+        }
+    }
+
+    /// split_backends are the backends of the trait, failing unless some of them get requests.
+    pub fn split_backends(&self) -> Result<Vec<TrafficSplitBackend>, failure::Error> {
+        let backends: Vec<TrafficSplitBackend> = match self.backends.as_ref() {
+            Some(backends) => serde_json::from_value(backends.clone()).map_err(|e| {
+                format_err!(
+                    "invalid traffic-split backends of {}: {}",
+                    self.instance_name,
+                    e
+                )
+            })?,
+            None => vec![],
+        };
+        if backends.iter().all(|b| b.weight == 0) {
+            return Err(format_err!(
+                "traffic-split of {} sends no requests to any backend",
+                self.instance_name
+            ));
+        }
+        Ok(backends)
+    }
+
+    /// to_traffic_split renders the split.smi-spec.io/v1alpha2 TrafficSplit or the
+    /// networking.istio.io/v1beta1 VirtualService of the provider.
+    pub fn to_traffic_split(&self) -> Result<Value, failure::Error> {
+        let backends = self.split_backends()?;
+        let service = self
+            .service
+            .clone()
+            .unwrap_or_else(|| self.instance_name.clone());
+        let mut metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+        });
+        if let Some(owner_ref) = self.owner_ref.as_ref() {
+            metadata["ownerReferences"] = serde_json::to_value(owner_ref)?;
+        }
+        match self.provider() {
+            SMI_PROVIDER => Ok(json!({
+                "apiVersion": "split.smi-spec.io/v1alpha2",
+                "kind": "TrafficSplit",
+                "metadata": metadata,
+                "spec": {
+                    "service": service,
+                    "backends": backends
+                        .iter()
+                        .map(|b| json!({"service": b.instance_name, "weight": b.weight}))
+                        .collect::<Vec<Value>>(),
+                },
+            })),
+            ISTIO_PROVIDER => {
+                // Istio wants the weights of a route in percent.
+                let total: u32 = backends.iter().map(|b| b.weight).sum();
+                if total != 100 {
+                    return Err(format_err!(
+                        "istio traffic-split weights of {} add up to {}, not 100",
+                        self.instance_name,
+                        total
+                    ));
+                }
+                let route: Vec<Value> = backends
+                    .iter()
+                    .map(|b| {
+                        let mut destination = json!({ "host": b.instance_name });
+                        if let Some(port) = self.port {
+                            destination["port"] = json!({ "number": port });
+                        }
+                        json!({"destination": destination, "weight": b.weight})
+                    })
+                    .collect();
+                Ok(json!({
+                    "apiVersion": "networking.istio.io/v1beta1",
+                    "kind": "VirtualService",
+                    "metadata": metadata,
+                    "spec": {
+                        "hosts": [service],
+                        "http": [{ "route": route }],
+                    },
+                }))
+            }
+            other => Err(format_err!(
+                "traffic-split provider {} is neither {} nor {}",
+                other,
+                SMI_PROVIDER,
+                ISTIO_PROVIDER
+            )),
+        }
+    }
+
+    fn provider(&self) -> &str {
+        self.provider.as_deref().unwrap_or(SMI_PROVIDER)
+    }
+
+    /// resource is the resource of the objects of the provider in `ns`.
+    fn resource(&self, ns: &str) -> RawApi {
+        match self.provider() {
+            ISTIO_PROVIDER => RawApi::customResource("virtualservices")
+                .group("networking.istio.io")
+                .version("v1beta1")
+                .within(ns),
+            _ => RawApi::customResource("trafficsplits")
+                .group("split.smi-spec.io")
+                .version("v1alpha2")
+                .within(ns),
+        }
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-traffic-split", self.instance_name)
+    }
+}
+
+impl TraitImplementation for TrafficSplit {
+    fn render(&self) -> RenderResult {
+        Ok(vec![self.to_traffic_split()?])
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let split = self.to_traffic_split()?;
+        apply(
+            &client,
+            &self.resource(ns),
+            self.kube_name().as_str(),
+            &split,
+        )
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let req = self
+            .resource(ns)
+            .delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<Value>(req)?;
+        Ok(())
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // Only servers have a Service to split the requests of.
+        name == SERVER_NAME || name == SINGLETON_SERVER_NAME
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = match self.provider() {
+            ISTIO_PROVIDER => "virtualservice/",
+            _ => "trafficsplit/",
+        }
+        .to_string()
+            + self.kube_name().as_str();
+        let req = match self.resource(ns).get(self.kube_name().as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        if let Err(e) = client.timed_request::<Value>(req) {
+            if e.to_string().contains("NotFound") {
+                warn!("Traffic split not found {}. Recreating ...", e);
+                self.add(ns, client).unwrap_or(());
+            }
+            resource.insert(key, e.to_string());
+            return Some(resource);
+        }
+        // Neither provider reports a status, so this shows the split the trait asks for.
+        let weights: Vec<String> = self
+            .split_backends()
+            .ok()?
+            .iter()
+            .map(|b| format!("{}={}", b.instance_name, b.weight))
+            .collect();
+        resource.insert(key, weights.join(" "));
+        Some(resource)
+    }
+}
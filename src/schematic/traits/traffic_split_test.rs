@@ -0,0 +1,257 @@
+use crate::schematic::traits::traffic_split::{ISTIO_PROVIDER, SMI_PROVIDER};
+use crate::schematic::traits::*;
+use crate::workload_type::{SERVER_NAME, SINGLETON_SERVER_NAME, WORKER_NAME};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_traffic_split_smi() {
+    let traffic_split = TrafficSplit {
+        name: "my-split".into(),
+        instance_name: "shop-v1".into(),
+        component_name: "shop".into(),
+        provider: None,
+        service: None,
+        port: None,
+        backends: Some(json!([
+            {"instanceName": "shop-v1", "weight": 90},
+            {"instanceName": "shop-v2", "weight": 10}
+        ])),
+        owner_ref: None,
+    };
+    let split = traffic_split.to_traffic_split().expect("traffic split");
+    assert_eq!("split.smi-spec.io/v1alpha2", split["apiVersion"]);
+    assert_eq!("TrafficSplit", split["kind"]);
+    assert_eq!("shop-v1-trait-traffic-split", split["metadata"]["name"]);
+    // Clients keep sending their requests to the Service of the instance.
+    assert_eq!("shop-v1", split["spec"]["service"]);
+    assert_eq!(
+        json!([
+            {"service": "shop-v1", "weight": 90},
+            {"service": "shop-v2", "weight": 10}
+        ]),
+        split["spec"]["backends"]
+    );
+
+    // SMI weights are relative, they needn't add up to 100.
+    let split = TrafficSplit {
+        name: "my-split".into(),
+        instance_name: "shop-v1".into(),
+        component_name: "shop".into(),
+        provider: Some(SMI_PROVIDER.into()),
+        service: Some("shop".into()),
+        port: None,
+        backends: Some(json!([
+            {"instanceName": "shop-v1", "weight": 900},
+            {"instanceName": "shop-v2", "weight": 100},
+            {"instanceName": "shop-v3", "weight": 0}
+        ])),
+        owner_ref: None,
+    };
+    let split = split.to_traffic_split().expect("traffic split");
+    assert_eq!("shop", split["spec"]["service"]);
+    assert_eq!(900, split["spec"]["backends"][0]["weight"]);
+    assert_eq!(0, split["spec"]["backends"][2]["weight"]);
+}
+
+#[test]
+fn test_traffic_split_istio() {
+    let split = TrafficSplit {
+        name: "my-split".into(),
+        instance_name: "shop-v1".into(),
+        component_name: "shop".into(),
+        provider: Some(ISTIO_PROVIDER.into()),
+        service: Some("shop".into()),
+        port: Some(8080),
+        backends: Some(json!([
+            {"instanceName": "shop-v1", "weight": 90},
+            {"instanceName": "shop-v2", "weight": 10}
+        ])),
+        owner_ref: None,
+    };
+    let split = split.to_traffic_split().expect("virtual service");
+    assert_eq!("networking.istio.io/v1beta1", split["apiVersion"]);
+    assert_eq!("VirtualService", split["kind"]);
+    assert_eq!("shop-v1-trait-traffic-split", split["metadata"]["name"]);
+    assert_eq!(json!(["shop"]), split["spec"]["hosts"]);
+    assert_eq!(
+        json!([
+            {"destination": {"host": "shop-v1", "port": {"number": 8080}}, "weight": 90},
+            {"destination": {"host": "shop-v2", "port": {"number": 8080}}, "weight": 10}
+        ]),
+        split["spec"]["http"][0]["route"]
+    );
+
+    // Without a port the destinations are the only port of their Service.
+    let split = TrafficSplit {
+        name: "my-split".into(),
+        instance_name: "shop-v1".into(),
+        component_name: "shop".into(),
+        provider: Some(ISTIO_PROVIDER.into()),
+        service: None,
+        port: None,
+        backends: Some(json!([
+            {"instanceName": "shop-v1", "weight": 90},
+            {"instanceName": "shop-v2", "weight": 10}
+        ])),
+        owner_ref: None,
+    };
+    let split = split.to_traffic_split().expect("virtual service");
+    assert_eq!(json!(["shop-v1"]), split["spec"]["hosts"]);
+    assert_eq!(
+        json!({"host": "shop-v2"}),
+        split["spec"]["http"][0]["route"][1]["destination"]
+    );
+}
+
+#[test]
+fn test_traffic_split_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "shop-shop-v1".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    for provider in &[SMI_PROVIDER, ISTIO_PROVIDER] {
+        let split = TrafficSplit {
+            name: "my-split".into(),
+            instance_name: "shop-v1".into(),
+            component_name: "shop".into(),
+            provider: Some(provider.to_string()),
+            service: None,
+            port: None,
+            backends: Some(json!([
+                {"instanceName": "shop-v1", "weight": 90},
+                {"instanceName": "shop-v2", "weight": 10}
+            ])),
+            owner_ref: Some(vec![owner.clone()]),
+        };
+        assert_eq!(
+            serde_json::to_value(vec![owner.clone()]).unwrap(),
+            split.to_traffic_split().expect("split")["metadata"]["ownerReferences"]
+        );
+    }
+}
+
+#[test]
+fn test_traffic_split_invalid() {
+    let invalid = |split: TrafficSplit| split.to_traffic_split().unwrap_err().to_string();
+    assert_eq!(
+        "traffic-split of shop-v1 sends no requests to any backend",
+        invalid(TrafficSplit {
+            name: "my-split".into(),
+            instance_name: "shop-v1".into(),
+            component_name: "shop".into(),
+            provider: None,
+            service: None,
+            port: None,
+            backends: None,
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "traffic-split of shop-v1 sends no requests to any backend",
+        invalid(TrafficSplit {
+            name: "my-split".into(),
+            instance_name: "shop-v1".into(),
+            component_name: "shop".into(),
+            provider: None,
+            service: None,
+            port: None,
+            backends: Some(json!([{"instanceName": "shop-v1", "weight": 0}])),
+            owner_ref: None,
+        })
+    );
+    assert!(invalid(TrafficSplit {
+        name: "my-split".into(),
+        instance_name: "shop-v1".into(),
+        component_name: "shop".into(),
+        provider: None,
+        service: None,
+        port: None,
+        backends: Some(json!([{"instanceName": "shop-v1", "weight": -1}])),
+        owner_ref: None,
+    })
+    .starts_with("invalid traffic-split backends of shop-v1"));
+    // Istio weights are percentages.
+    assert_eq!(
+        "istio traffic-split weights of shop-v1 add up to 110, not 100",
+        invalid(TrafficSplit {
+            name: "my-split".into(),
+            instance_name: "shop-v1".into(),
+            component_name: "shop".into(),
+            provider: Some(ISTIO_PROVIDER.into()),
+            service: None,
+            port: None,
+            backends: Some(json!([
+                {"instanceName": "shop-v1", "weight": 90},
+                {"instanceName": "shop-v2", "weight": 20}
+            ])),
+            owner_ref: None,
+        })
+    );
+    assert_eq!(
+        "traffic-split provider linkerd is neither smi nor istio",
+        invalid(TrafficSplit {
+            name: "my-split".into(),
+            instance_name: "shop-v1".into(),
+            component_name: "shop".into(),
+            provider: Some("linkerd".into()),
+            service: None,
+            port: None,
+            backends: Some(json!([
+                {"instanceName": "shop-v1", "weight": 90},
+                {"instanceName": "shop-v2", "weight": 10}
+            ])),
+            owner_ref: None,
+        })
+    );
+}
+
+#[test]
+fn test_traffic_split_v1alpha1() {
+    let traffic_split_alpha1_trait = TraitBinding {
+        name: String::from("traffic-split"),
+        parameter_values: None,
+        properties: Some(json!({
+            "provider": "istio",
+            "service": "shop",
+            "port": 8080,
+            "backends": [
+                {"instanceName": "shop-v1", "weight": 50},
+                {"instanceName": "shop-v2", "weight": 50}
+            ]
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&traffic_split_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let split = TrafficSplit::from_properties(
+        "my-split".into(),
+        "shop-v1".into(),
+        "shop".into(),
+        prop_map,
+        None,
+    );
+    assert_eq!(Some("istio".to_string()), split.provider);
+    assert_eq!(Some("shop".to_string()), split.service);
+    assert_eq!(Some(8080), split.port);
+    let backends = split.split_backends().expect("backends");
+    assert_eq!("shop-v2", backends[1].instance_name);
+    assert_eq!(50, backends[1].weight);
+}
+
+#[test]
+fn test_traffic_split_workload_types() {
+    assert!(TrafficSplit::supports_workload_type(SERVER_NAME));
+    assert!(TrafficSplit::supports_workload_type(SINGLETON_SERVER_NAME));
+    assert!(!TrafficSplit::supports_workload_type(WORKER_NAME));
+}
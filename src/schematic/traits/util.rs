@@ -18,6 +18,14 @@ pub fn trait_labels(name: String, inst_name: String) -> Labels {
     labels
 }
 
+/// Generate the labels that select the pods of a component instance.
+pub fn instance_selector(name: String, inst_name: String) -> Labels {
+    let mut labels: Labels = BTreeMap::new();
+    labels.insert("app.kubernetes.io/name".to_string(), name);
+    labels.insert("oam.dev/instance-name".to_string(), inst_name);
+    labels
+}
+
 #[cfg(test)]
 mod tests {
     use crate::schematic::traits::util::*;
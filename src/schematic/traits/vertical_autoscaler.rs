@@ -0,0 +1,194 @@
+use crate::metrics::TimedRequest;
+use crate::schematic::traits::{util::*, TraitImplementation};
+use crate::workload_type::{
+    apply, RenderResult, SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_WORKER, WORKER_NAME,
+};
+use kube::api::RawApi;
+use kube::client::APIClient;
+use log::warn;
+use serde_json::map::Map;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The update modes of a VerticalPodAutoscaler.
+const UPDATE_MODES: &[&str] = &["Off", "Initial", "Recreate", "Auto"];
+
+/// A VerticalAutoscaler right-sizes the resource requests of a component with a Kubernetes
+/// VerticalPodAutoscaler.
+#[derive(Clone, Debug)]
+pub struct VerticalAutoscaler {
+    pub name: String,
+    pub instance_name: String,
+    pub component_name: String,
+    /// `Off`, `Initial`, `Recreate` or `Auto`, `Auto` if unset.
+    pub update_mode: Option<String>,
+    /// The least resources to request, like `{"cpu": "100m", "memory": "128Mi"}`.
+    pub min_allowed: Option<Value>,
+    /// The most resources to request.
+    pub max_allowed: Option<Value>,
+    pub owner_ref: OwnerRefs,
+    pub workload_type: String,
+}
+
+impl VerticalAutoscaler {
+    pub fn from_properties(
+        name: String,
+        instance_name: String,
+        component_name: String,
+        properties_map: Option<&Map<String, serde_json::value::Value>>,
+        owner_ref: OwnerRefs,
+        workload_type: String,
+    ) -> Self {
+        let resources = |key: &str| {
+            properties_map
+                .and_then(|map| map.get(key))
+                .filter(|r| r.is_object())
+                .cloned()
+        };
+        VerticalAutoscaler {
+            name,
+            instance_name,
+            component_name,
+            owner_ref,
+            workload_type,
+            update_mode: properties_map
+                .and_then(|map| map.get("updateMode"))
+                .and_then(|m| m.as_str())
+                .map(String::from),
+            min_allowed: resources("minAllowed"),
+            max_allowed: resources("maxAllowed"),
+        }
+    }
+
+    /// to_vertical_pod_autoscaler renders the autoscaling.k8s.io/v1 VerticalPodAutoscaler, which
+    /// bounds the requests of every container of the component.
+    pub fn to_vertical_pod_autoscaler(&self) -> Result<Value, failure::Error> {
+        let update_mode = self.update_mode.as_deref().unwrap_or("Auto");
+        if !UPDATE_MODES.contains(&update_mode) {
+            return Err(format_err!(
+                "vertical-autoscaler update mode {} is none of {}",
+                update_mode,
+                UPDATE_MODES.join(", ")
+            ));
+        }
+        let mut policy = json!({"containerName": "*"});
+        if let Some(min) = self.min_allowed.as_ref() {
+            policy["minAllowed"] = min.clone();
+        }
+        if let Some(max) = self.max_allowed.as_ref() {
+            policy["maxAllowed"] = max.clone();
+        }
+        let mut metadata = json!({
+            "name": self.kube_name(),
+            "labels": trait_labels(self.name.clone(), self.instance_name.clone()),
+        });
+        if let Some(owner_ref) = self.owner_ref.as_ref() {
+            metadata["ownerReferences"] = serde_json::to_value(owner_ref)?;
+        }
+        Ok(json!({
+            "apiVersion": "autoscaling.k8s.io/v1",
+            "kind": "VerticalPodAutoscaler",
+            "metadata": metadata,
+            "spec": {
+                "targetRef": {
+                    "apiVersion": "apps/v1",
+                    "kind": self.target_kind(),
+                    "name": self.instance_name,
+                },
+                "updatePolicy": {"updateMode": update_mode},
+                "resourcePolicy": {"containerPolicies": [policy]},
+            },
+        }))
+    }
+
+    /// target_kind is the kind of the object the workload of the component runs its pods with.
+    fn target_kind(&self) -> &'static str {
+        match self.workload_type.as_str() {
+            SINGLETON_SERVER_NAME | SINGLETON_WORKER => "StatefulSet",
+            _ => "Deployment",
+        }
+    }
+
+    fn kube_name(&self) -> String {
+        format!("{}-trait-vertical-autoscaler", self.instance_name.as_str())
+    }
+}
+
+/// The resource of the VerticalPodAutoscalers in `ns`.
+fn vpa_resource(ns: &str) -> RawApi {
+    RawApi::customResource("verticalpodautoscalers")
+        .group("autoscaling.k8s.io")
+        .version("v1")
+        .within(ns)
+}
+
+impl TraitImplementation for VerticalAutoscaler {
+    fn render(&self) -> RenderResult {
+        Ok(vec![self.to_vertical_pod_autoscaler()?])
+    }
+    fn add(&self, ns: &str, client: APIClient) -> TraitResult {
+        let vpa = self.to_vertical_pod_autoscaler()?;
+        apply(&client, &vpa_resource(ns), self.kube_name().as_str(), &vpa)
+    }
+    fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
+        self.add(ns, client)
+    }
+    fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        let req = vpa_resource(ns).delete(self.kube_name().as_str(), &Default::default())?;
+        client.timed_request::<serde_json::Value>(req)?;
+        Ok(())
+    }
+    fn supports_workload_type(name: &str) -> bool {
+        // Tasks run to completion, before a recommendation would help them.
+        name == SERVER_NAME
+            || name == SINGLETON_SERVER_NAME
+            || name == WORKER_NAME
+            || name == SINGLETON_WORKER
+    }
+    fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
+        let mut resource = BTreeMap::new();
+        let key = "verticalpodautoscaler/".to_string() + self.kube_name().as_str();
+        let req = match vpa_resource(ns).get(self.kube_name().as_str()) {
+            Ok(req) => req,
+            Err(e) => {
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        let vpa: Value = match client.timed_request::<Value>(req) {
+            Ok(vpa) => vpa,
+            Err(e) => {
+                if e.to_string().contains("NotFound") {
+                    warn!("VerticalPodAutoscaler not found {}. Recreating ...", e);
+                    self.add(ns, client).unwrap_or(());
+                }
+                resource.insert(key, e.to_string());
+                return Some(resource);
+            }
+        };
+        // The recommended requests of the containers, like `web: cpu=250m memory=256Mi`.
+        let recommendations: Vec<String> = vpa["status"]["recommendation"]
+            ["containerRecommendations"]
+            .as_array()?
+            .iter()
+            .map(|r| {
+                let target = r["target"]
+                    .as_object()
+                    .map(|t| {
+                        t.iter()
+                            .map(|(k, v)| format!("{}={}", k, v.as_str().unwrap_or_default()))
+                            .collect::<Vec<String>>()
+                            .join(" ")
+                    })
+                    .unwrap_or_default();
+                format!(
+                    "{}: {}",
+                    r["containerName"].as_str().unwrap_or_default(),
+                    target
+                )
+            })
+            .collect();
+        resource.insert(key, recommendations.join(", "));
+        Some(resource)
+    }
+}
@@ -0,0 +1,208 @@
+use crate::schematic::traits::*;
+use crate::workload_type::{
+    SERVER_NAME, SINGLETON_SERVER_NAME, SINGLETON_TASK_NAME, SINGLETON_WORKER, TASK_NAME,
+    WORKER_NAME,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use serde_json::json;
+use serde_json::map::Map;
+
+#[test]
+fn test_vertical_autoscaler_defaults() {
+    let vertical_autoscaler = VerticalAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        update_mode: None,
+        min_allowed: None,
+        max_allowed: None,
+        owner_ref: None,
+        workload_type: SERVER_NAME.into(),
+    };
+    let vpa = vertical_autoscaler
+        .to_vertical_pod_autoscaler()
+        .expect("vpa");
+    assert_eq!("autoscaling.k8s.io/v1", vpa["apiVersion"]);
+    assert_eq!("VerticalPodAutoscaler", vpa["kind"]);
+    assert_eq!(
+        "instance-trait-vertical-autoscaler",
+        vpa["metadata"]["name"]
+    );
+    assert!(vpa["metadata"]["ownerReferences"].is_null());
+    // Every container is right-sized, without bounds unless they are set.
+    assert_eq!(
+        json!({
+            "targetRef": {"apiVersion": "apps/v1", "kind": "Deployment", "name": "instance"},
+            "updatePolicy": {"updateMode": "Auto"},
+            "resourcePolicy": {"containerPolicies": [{"containerName": "*"}]}
+        }),
+        vpa["spec"]
+    );
+}
+
+#[test]
+fn test_vertical_autoscaler_bounds() {
+    let autoscaler = VerticalAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        update_mode: Some("Initial".into()),
+        min_allowed: Some(json!({"cpu": "100m", "memory": "128Mi"})),
+        max_allowed: Some(json!({"cpu": "2", "memory": "4Gi"})),
+        owner_ref: None,
+        workload_type: SERVER_NAME.into(),
+    };
+    let vpa = autoscaler.to_vertical_pod_autoscaler().expect("vpa");
+    assert_eq!("Initial", vpa["spec"]["updatePolicy"]["updateMode"]);
+    assert_eq!(
+        json!([{
+            "containerName": "*",
+            "minAllowed": {"cpu": "100m", "memory": "128Mi"},
+            "maxAllowed": {"cpu": "2", "memory": "4Gi"}
+        }]),
+        vpa["spec"]["resourcePolicy"]["containerPolicies"]
+    );
+
+    let autoscaler = VerticalAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        update_mode: None,
+        min_allowed: None,
+        max_allowed: Some(json!({"memory": "1Gi"})),
+        owner_ref: None,
+        workload_type: SERVER_NAME.into(),
+    };
+    let vpa = autoscaler.to_vertical_pod_autoscaler().expect("vpa");
+    let policy = &vpa["spec"]["resourcePolicy"]["containerPolicies"][0];
+    assert!(policy["minAllowed"].is_null());
+    assert_eq!("1Gi", policy["maxAllowed"]["memory"]);
+}
+
+#[test]
+fn test_vertical_autoscaler_target_kind() {
+    for (workload_type, kind) in &[
+        (SERVER_NAME, "Deployment"),
+        (WORKER_NAME, "Deployment"),
+        (SINGLETON_SERVER_NAME, "StatefulSet"),
+        (SINGLETON_WORKER, "StatefulSet"),
+    ] {
+        let autoscaler = VerticalAutoscaler {
+            name: "release".into(),
+            instance_name: "instance".into(),
+            component_name: "component".into(),
+            update_mode: None,
+            min_allowed: None,
+            max_allowed: None,
+            owner_ref: None,
+            workload_type: workload_type.to_string(),
+        };
+        let vpa = autoscaler.to_vertical_pod_autoscaler().expect("vpa");
+        assert_eq!(json!(kind), vpa["spec"]["targetRef"]["kind"]);
+    }
+}
+
+#[test]
+fn test_vertical_autoscaler_owner_reference() {
+    let owner = meta::OwnerReference {
+        api_version: "core.oam.dev/v1alpha1".into(),
+        kind: "ComponentInstance".into(),
+        name: "component-instance".into(),
+        uid: "uid".into(),
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    };
+    let autoscaler = VerticalAutoscaler {
+        name: "release".into(),
+        instance_name: "instance".into(),
+        component_name: "component".into(),
+        update_mode: None,
+        min_allowed: None,
+        max_allowed: None,
+        owner_ref: Some(vec![owner.clone()]),
+        workload_type: SERVER_NAME.into(),
+    };
+    assert_eq!(
+        serde_json::to_value(vec![owner]).unwrap(),
+        autoscaler.render().expect("render")[0]["metadata"]["ownerReferences"]
+    );
+}
+
+#[test]
+fn test_vertical_autoscaler_invalid() {
+    let invalid = |update_mode: &str| {
+        VerticalAutoscaler {
+            name: "release".into(),
+            instance_name: "instance".into(),
+            component_name: "component".into(),
+            update_mode: Some(update_mode.into()),
+            min_allowed: None,
+            max_allowed: None,
+            owner_ref: None,
+            workload_type: SERVER_NAME.into(),
+        }
+        .render()
+        .unwrap_err()
+        .to_string()
+    };
+    assert_eq!(
+        "vertical-autoscaler update mode Sometimes is none of Off, Initial, Recreate, Auto",
+        invalid("Sometimes")
+    );
+    // The update modes are capitalized.
+    assert_eq!(
+        "vertical-autoscaler update mode auto is none of Off, Initial, Recreate, Auto",
+        invalid("auto")
+    );
+}
+
+#[test]
+fn test_vertical_autoscaler_v1alpha1() {
+    let vertical_autoscaler_alpha1_trait = TraitBinding {
+        name: String::from("vertical-autoscaler"),
+        parameter_values: None,
+        properties: Some(json!({
+            "updateMode": "Off",
+            "minAllowed": {"cpu": "100m"},
+            "maxAllowed": "4Gi"
+        })),
+        priority: None,
+        depends_on: None,
+    };
+
+    let serialized = serde_json::to_string(&vertical_autoscaler_alpha1_trait).unwrap();
+    let deserialized_trait: TraitBinding = serde_json::from_str(&serialized).unwrap();
+    let prop_map: Option<&Map<String, serde_json::value::Value>> =
+        deserialized_trait.properties.as_ref().unwrap().as_object();
+
+    let autoscaler = VerticalAutoscaler::from_properties(
+        "release".into(),
+        "instance".into(),
+        "component".into(),
+        prop_map,
+        None,
+        SINGLETON_WORKER.into(),
+    );
+    assert_eq!(Some("Off".to_string()), autoscaler.update_mode);
+    assert_eq!(Some(json!({"cpu": "100m"})), autoscaler.min_allowed);
+    // Resources that aren't objects are left out.
+    assert_eq!(None, autoscaler.max_allowed);
+    assert_eq!(SINGLETON_WORKER, autoscaler.workload_type);
+    assert!(autoscaler.render().is_ok());
+}
+
+#[test]
+fn test_vertical_autoscaler_workload_types() {
+    for name in &[
+        SERVER_NAME,
+        SINGLETON_SERVER_NAME,
+        WORKER_NAME,
+        SINGLETON_WORKER,
+    ] {
+        assert!(VerticalAutoscaler::supports_workload_type(name));
+    }
+    assert!(!VerticalAutoscaler::supports_workload_type(TASK_NAME));
+    assert!(!VerticalAutoscaler::supports_workload_type(
+        SINGLETON_TASK_NAME
+    ));
+}
@@ -1,15 +1,18 @@
 use k8s_openapi::api::core::v1 as core;
 use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
+use kube::api::RawApi;
 use kube::client::APIClient;
 use log::warn;
 use serde_json::map::Map;
 
+use crate::metrics::TimedRequest;
 use crate::schematic::{
     component::{AccessMode, Component, SharingPolicy, Volume},
     traits::util::{OwnerRefs, TraitResult},
     traits::TraitImplementation,
 };
+use crate::workload_type::{apply, RenderResult};
 
 use std::collections::BTreeMap;
 
@@ -18,6 +21,20 @@ use std::collections::BTreeMap;
 /// default if no size is specified.
 pub const DEFAULT_VOLUME_SIZE: &str = "200M";
 
+/// The access modes of a PersistentVolumeClaim.
+const ACCESS_MODES: &[&str] = &[
+    "ReadWriteOnce",
+    "ReadOnlyMany",
+    "ReadWriteMany",
+    "ReadWriteOncePod",
+];
+/// The volume modes of a PersistentVolumeClaim.
+const VOLUME_MODES: &[&str] = &["Filesystem", "Block"];
+/// The reclaim policy that keeps the PVC when the trait or component is removed.
+pub const RECLAIM_RETAIN: &str = "Retain";
+/// The reclaim policy that deletes the PVC with the trait or component, the default.
+pub const RECLAIM_DELETE: &str = "Delete";
+
 /// The VolumeMounter trait provisions volumes that can
 /// be mounted by a Component.
 #[derive(Clone, Debug)]
@@ -37,6 +54,12 @@ pub struct VolumeMounter {
     pub volume_name: String,
     /// The name of the storage class to which this will derive a PVC
     pub storage_class: String,
+    /// The access modes of the PVC, derived from the volume of the component if unset.
+    pub access_modes: Option<Vec<String>>,
+    /// `Filesystem` or `Block`, the Kubernetes default if unset.
+    pub volume_mode: Option<String>,
+    /// `Delete` or `Retain`, whether the PVC goes with the trait or stays, `Delete` if unset.
+    pub reclaim_policy: Option<String>,
 }
 
 impl VolumeMounter {
@@ -60,11 +83,58 @@ impl VolumeMounter {
                         .unwrap_or_else( || { warn!("Unable to parse volumeName value for instance:{}. Setting it to default value:empty", instancename); "" } )
                         .to_string(),
             storage_class: properties_map
-                        .and_then(|map| map.get("storageClass").and_then(|p| p.as_str()))
+                        .and_then(|map| map.get("storageClass").or_else(|| map.get("storageClassName")).and_then(|p| p.as_str()))
                         .unwrap_or_else( || { warn!("Unable to parse storageClass value for instance:{}. Setting it to default value:empty", instancename); "" } )
                         .to_string(),
+            access_modes: properties_map
+                .and_then(|map| map.get("accessModes"))
+                .and_then(|m| m.as_array())
+                .map(|m| {
+                    m.iter()
+                        .filter_map(|m| m.as_str().map(String::from))
+                        .collect()
+                }),
+            volume_mode: properties_map
+                .and_then(|map| map.get("volumeMode").and_then(|p| p.as_str()))
+                .map(String::from),
+            reclaim_policy: properties_map
+                .and_then(|map| map.get("reclaimPolicy").and_then(|p| p.as_str()))
+                .map(String::from),
+        }
+    }
+    /// validate checks the access modes, volume mode and reclaim policy of the trait.
+    pub fn validate(&self) -> TraitResult {
+        for mode in self.access_modes.iter().flatten() {
+            if !ACCESS_MODES.contains(&mode.as_str()) {
+                return Err(format_err!(
+                    "volume-mounter access mode {} is none of {}",
+                    mode,
+                    ACCESS_MODES.join(", ")
+                ));
+            }
+        }
+        if let Some(mode) = self.volume_mode.as_ref() {
+            if !VOLUME_MODES.contains(&mode.as_str()) {
+                return Err(format_err!(
+                    "volume-mounter volume mode {} is none of {}",
+                    mode,
+                    VOLUME_MODES.join(", ")
+                ));
+            }
+        }
+        match self.reclaim_policy.as_deref() {
+            None | Some(RECLAIM_DELETE) | Some(RECLAIM_RETAIN) => Ok(()),
+            Some(other) => Err(format_err!(
+                "volume-mounter reclaim policy {} is neither {} nor {}",
+                other,
+                RECLAIM_DELETE,
+                RECLAIM_RETAIN
+            )),
         }
     }
+    fn retained(&self) -> bool {
+        self.reclaim_policy.as_deref() == Some(RECLAIM_RETAIN)
+    }
     fn labels(&self) -> BTreeMap<String, String> {
         let mut labels = BTreeMap::new();
         labels.insert("app".to_string(), self.name.clone());
@@ -73,26 +143,42 @@ impl VolumeMounter {
         labels.insert("trait".to_string(), "volume-mounter".to_string());
         labels
     }
+    /// The size the volume of the component requires.
+    fn size(&self) -> String {
+        self.find_volume()
+            .and_then(|v| v.disk.as_ref().map(|d| d.required.clone()))
+            .unwrap_or_else(|| DEFAULT_VOLUME_SIZE.to_string())
+    }
     /// Conver the volume mounter data to a PersistentVolumeClaim
     pub fn to_pvc(&self) -> core::PersistentVolumeClaim {
+        self.to_pvc_of_size(self.size())
+    }
+    /// to_pvc_of_size is the PersistentVolumeClaim with a request of `size`. A retained PVC has
+    /// no owner, or it would be garbage collected with the component instance.
+    fn to_pvc_of_size(&self, size: String) -> core::PersistentVolumeClaim {
         let attach_to = self.find_volume();
-        let size = Quantity(
-            attach_to
-                .and_then(|v| v.disk.as_ref().and_then(|d| Some(d.required.clone())))
-                .unwrap_or_else(|| DEFAULT_VOLUME_SIZE.to_string()),
-        );
+        let size = Quantity(size);
         let mut reqs = BTreeMap::new();
         reqs.insert("storage".to_string(), size);
         core::PersistentVolumeClaim {
             metadata: Some(meta::ObjectMeta {
                 name: Some(self.volume_name.clone()),
                 labels: Some(self.labels()),
-                owner_references: self.owner_ref.clone(),
+                owner_references: if self.retained() {
+                    None
+                } else {
+                    self.owner_ref.clone()
+                },
                 ..Default::default()
             }),
             spec: Some(core::PersistentVolumeClaimSpec {
-                access_modes: Some(vec![self.mount_policy(attach_to)]),
+                access_modes: Some(
+                    self.access_modes
+                        .clone()
+                        .unwrap_or_else(|| vec![self.mount_policy(attach_to)]),
+                ),
                 storage_class_name: Some(self.storage_class.clone()),
+                volume_mode: self.volume_mode.clone(),
                 resources: Some(core::ResourceRequirements {
                     requests: Some(reqs),
                     ..Default::default()
@@ -132,39 +218,57 @@ impl TraitImplementation for VolumeMounter {
     /// Make sure the PVC is created before the Pod.
     /// This won't make a difference most of the time, but on fast disk provisioning operations
     /// this may help a little.
+    ///
+    /// A PVC that is there already is expanded when the volume requires more, but never shrunk,
+    /// which Kubernetes refuses.
     fn pre_add(&self, ns: &str, client: APIClient) -> TraitResult {
-        let pvc = self.to_pvc();
-        let (req, _) = core::PersistentVolumeClaim::create_namespaced_persistent_volume_claim(
-            ns,
-            &pvc,
-            Default::default(),
-        )?;
-        client.request::<core::PersistentVolumeClaim>(req)?;
-        Ok(())
+        self.validate()?;
+        let resource = RawApi::v1PersistentVolumeClaim().within(ns);
+        let current = match client
+            .timed_request::<core::PersistentVolumeClaim>(resource.get(self.volume_name.as_str())?)
+        {
+            Ok(pvc) => pvc
+                .spec
+                .and_then(|s| s.resources)
+                .and_then(|r| r.requests)
+                .and_then(|r| r.get("storage").map(|q| q.0.clone())),
+            Err(e) => match e.api_error() {
+                Some(ref err) if err.code == 404 => None,
+                _ => return Err(e.into()),
+            },
+        };
+        let requested = self.size();
+        let size = grown_size(requested.as_str(), current.as_deref());
+        if size != requested {
+            warn!(
+                "PVC {} stays at {}, it can't shrink to {}",
+                self.volume_name, size, requested
+            );
+        }
+        let pvc = self.to_pvc_of_size(size);
+        apply(&client, &resource, self.volume_name.as_str(), &pvc)
+    }
+    fn render(&self) -> RenderResult {
+        self.validate()?;
+        Ok(vec![serde_json::to_value(self.to_pvc())?])
     }
     /// There is nothing to do on the add phase for this trait
     fn add(&self, _ns: &str, _client: APIClient) -> TraitResult {
         Ok(())
     }
     fn modify(&self, ns: &str, client: APIClient) -> TraitResult {
-        let pvc = self.to_pvc();
-        let values = serde_json::to_value(&pvc)?;
-        let (req, _) = core::PersistentVolumeClaim::patch_namespaced_persistent_volume_claim(
-            self.volume_name.as_str(),
-            ns,
-            &meta::Patch::StrategicMerge(values),
-            Default::default(),
-        )?;
-        client.request::<core::PersistentVolumeClaim>(req)?;
-        Ok(())
+        self.pre_add(ns, client)
     }
     fn delete(&self, ns: &str, client: APIClient) -> TraitResult {
+        if self.retained() {
+            return Ok(());
+        }
         let (req, _) = core::PersistentVolumeClaim::delete_namespaced_persistent_volume_claim(
             self.volume_name.as_str(),
             ns,
             Default::default(),
         )?;
-        client.request::<core::PersistentVolumeClaim>(req)?;
+        client.timed_request::<core::PersistentVolumeClaim>(req)?;
         Ok(())
     }
     fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
@@ -182,14 +286,21 @@ impl TraitImplementation for VolumeMounter {
         }
 
         let (raw_req, _) = req.unwrap();
-        match client.request::<core::PersistentVolumeClaim>(raw_req) {
+        match client.timed_request::<core::PersistentVolumeClaim>(raw_req) {
             Ok(pvc) => {
-                resource.insert(
-                    key,
-                    pvc.status
-                        .and_then(|s| s.phase)
-                        .unwrap_or_else(|| "unknown phase".to_string()),
-                );
+                let status = pvc.status.unwrap_or_default();
+                let resizing = status.conditions.iter().flatten().any(|c| {
+                    (c.type_ == "Resizing" || c.type_ == "FileSystemResizePending")
+                        && c.status == "True"
+                });
+                // A claim is ready once it is bound to a volume, and resized if it grew.
+                let state = match status.phase {
+                    Some(ref phase) if phase == "Bound" && resizing => "resizing".to_string(),
+                    Some(ref phase) if phase == "Bound" => "ready".to_string(),
+                    Some(phase) => phase,
+                    None => "unknown phase".to_string(),
+                };
+                resource.insert(key, state);
             }
             Err(e) => {
                 resource.insert(key, e.to_string());
@@ -199,9 +310,47 @@ impl TraitImplementation for VolumeMounter {
     }
 }
 
+/// grown_size is the size to request for a PVC that requests `current`, if it is there: the
+/// `requested` size if it is larger, the current one otherwise.
+pub fn grown_size(requested: &str, current: Option<&str>) -> String {
+    match current {
+        Some(current) => match (quantity_bytes(requested), quantity_bytes(current)) {
+            (Some(r), Some(c)) if r < c => current.to_string(),
+            _ => requested.to_string(),
+        },
+        None => requested.to_string(),
+    }
+}
+
+/// quantity_bytes is the number of a Kubernetes quantity, like `200M` or `1.5Gi`.
+fn quantity_bytes(quantity: &str) -> Option<f64> {
+    const SUFFIXES: &[(&str, f64)] = &[
+        ("Ki", 1024.0),
+        ("Mi", 1_048_576.0),
+        ("Gi", 1_073_741_824.0),
+        ("Ti", 1_099_511_627_776.0),
+        ("Pi", 1_125_899_906_842_624.0),
+        ("Ei", 1_152_921_504_606_846_976.0),
+        ("k", 1e3),
+        ("M", 1e6),
+        ("G", 1e9),
+        ("T", 1e12),
+        ("P", 1e15),
+        ("E", 1e18),
+        ("m", 1e-3),
+    ];
+    let quantity = quantity.trim();
+    for (suffix, factor) in SUFFIXES {
+        if let Some(number) = quantity.strip_suffix(suffix) {
+            return number.parse::<f64>().ok().map(|n| n * factor);
+        }
+    }
+    quantity.parse::<f64>().ok()
+}
+
 #[cfg(test)]
 mod test {
-    use super::VolumeMounter;
+    use super::{grown_size, VolumeMounter};
     use crate::schematic::component::{
         AccessMode, Component, Container, Disk, Resources, SharingPolicy, Volume,
     };
@@ -216,6 +365,7 @@ mod test {
             parameters: vec![],
             containers: vec![],
             workload_settings: vec![],
+            outputs: vec![],
             ..Default::default()
         };
         let volume_mounter_alpha1_trait = TraitBinding {
@@ -225,6 +375,8 @@ mod test {
                 "storageClass": "really-fast",
                 "volumeName": "panda-bears"
             })),
+            priority: None,
+            depends_on: None,
         };
 
         let serialized = serde_json::to_string(&volume_mounter_alpha1_trait).unwrap();
@@ -245,6 +397,49 @@ mod test {
         assert_eq!("panda-bears", vm.volume_name);
     }
 
+    #[test]
+    fn test_volume_mounter_properties() {
+        let vm = VolumeMounter::from_properties(
+            "my-volume-mount".to_string(),
+            "instance".to_string(),
+            "component".to_string(),
+            json!({
+                "volumeName": "panda-bears",
+                "storageClassName": "fast",
+                "accessModes": ["ReadWriteMany"],
+                "volumeMode": "Block",
+                "reclaimPolicy": "Retain"
+            })
+            .as_object(),
+            Some(vec![Default::default()]),
+            Component {
+                containers: vec![mock_container("server")],
+                ..Default::default()
+            },
+        );
+        assert!(vm.validate().is_ok());
+        let pvc = vm.to_pvc();
+        assert_eq!(None, pvc.metadata.expect("metadata").owner_references);
+        let spec = pvc.spec.expect("spec");
+        assert_eq!(Some("fast".to_string()), spec.storage_class_name);
+        assert_eq!(Some(vec!["ReadWriteMany".to_string()]), spec.access_modes);
+        assert_eq!(Some("Block".to_string()), spec.volume_mode);
+
+        let invalid = VolumeMounter {
+            volume_mode: Some("Raw".to_string()),
+            ..vm
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_grown_size() {
+        assert_eq!("2Gi", grown_size("2Gi", Some("1Gi")));
+        assert_eq!("1Gi", grown_size("500M", Some("1Gi")));
+        assert_eq!("1.5G", grown_size("1.5G", Some("1G")));
+        assert_eq!("123M", grown_size("123M", None));
+    }
+
     fn mock_container(name: &str) -> Container {
         Container {
             name: name.to_string(),
@@ -15,6 +15,47 @@ fn test_autoscaler_workload_types() {
     assert!(!Autoscaler::supports_workload_type(SINGLETON_SERVER_NAME));
 }
 
+#[test]
+fn test_apply_order() {
+    let bindings: Vec<TraitBinding> = serde_json::from_value(serde_json::json!([
+        {"name": "ingress"},
+        {"name": "sidecar"},
+        {"name": "metrics", "dependsOn": ["ingress"]},
+        {"name": "volume-mounter"},
+        {"name": "dns", "priority": 300},
+    ]))
+    .expect("bindings");
+    let names = |bindings: &[TraitBinding]| -> Vec<String> {
+        apply_order(bindings)
+            .expect("order")
+            .iter()
+            .map(|b| b.name.clone())
+            .collect()
+    };
+    assert_eq!(
+        vec!["dns", "volume-mounter", "sidecar", "ingress", "metrics"],
+        names(&bindings)
+    );
+
+    let dependent: Vec<TraitBinding> = serde_json::from_value(serde_json::json!([
+        {"name": "volume-mounter", "dependsOn": ["dns"]},
+        {"name": "dns"},
+    ]))
+    .expect("bindings");
+    assert_eq!(vec!["dns", "volume-mounter"], names(&dependent));
+
+    let cycle: Vec<TraitBinding> = serde_json::from_value(serde_json::json!([
+        {"name": "dns", "dependsOn": ["ingress"]},
+        {"name": "ingress", "dependsOn": ["dns"]},
+    ]))
+    .expect("bindings");
+    assert!(apply_order(&cycle).is_err());
+    let unknown: Vec<TraitBinding> =
+        serde_json::from_value(serde_json::json!([{"name": "dns", "dependsOn": ["ingress"]}]))
+            .expect("bindings");
+    assert!(apply_order(&unknown).is_err());
+}
+
 #[test]
 fn test_traits_exec() {
     let emptytrait = OAMTrait::Empty(Empty {});
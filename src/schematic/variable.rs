@@ -1,4 +1,4 @@
-use super::parameter::ParameterValue;
+use super::parameter::{ParameterValue, ValueFrom};
 use failure::Error;
 use regex::Regex;
 use std::cmp::Ordering;
@@ -15,13 +15,20 @@ fn parse_from_variable(input: String) -> Option<String> {
 /// Variables are common values that can be substituted into
 /// predefined locations within an application configuration
 /// using the [fromVariable(VARNAME)] syntax.
+///
+/// A variable can reference another variable with the same syntax, or take its value from a key
+/// of a Secret or ConfigMap, which is read when the configuration is rendered.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Variable {
     /// The variable's name (must be unique per configuration).
     pub name: String,
     /// The variable's name scalar value.
+    #[serde(default)]
     pub value: serde_json::Value,
+    /// A secret or config map key the instigator reads the value from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_from: Option<ValueFrom>,
 }
 
 impl From<Variable> for ParameterValue {
@@ -30,6 +37,9 @@ impl From<Variable> for ParameterValue {
             name: var.name.clone(),
             value: Some(var.value.clone()),
             from_param: None,
+            value_from: var.value_from.clone(),
+            from_component: None,
+            output: None,
         }
     }
 }
@@ -82,16 +92,92 @@ pub fn expand_variables(
 
 /// Resolve parameter values containing variables.
 pub fn resolve_variables(
-    values: Vec<ParameterValue>,
+    mut values: Vec<ParameterValue>,
     vars: Vec<Variable>,
 ) -> Result<Vec<ParameterValue>, Error> {
-    expand_variables(
-        &mut values.clone(),
-        vars.into_iter()
-            .map(|var| (var.name.clone(), var.value.clone()))
-            .collect::<BTreeMap<String, serde_json::Value>>(),
-    )?;
-    Ok(values.to_vec())
+    expand_variables(&mut values, variable_values(&vars)?)?;
+    Ok(values)
+}
+
+/// Returns the value of every variable, with references to other variables replaced by the values
+/// they reference. A reference to an undefined variable or a cycle of references is an error.
+pub fn variable_values(vars: &[Variable]) -> Result<BTreeMap<String, serde_json::Value>, Error> {
+    let defined: BTreeMap<&str, &serde_json::Value> = vars
+        .iter()
+        .map(|var| (var.name.as_str(), &var.value))
+        .collect();
+    let mut values = BTreeMap::new();
+    for var in vars {
+        resolve_variable(var.name.as_str(), &defined, &mut values, &mut vec![])?;
+    }
+    Ok(values)
+}
+
+/// Resolves the variable `name`, which `path` references through the variables in it.
+fn resolve_variable(
+    name: &str,
+    defined: &BTreeMap<&str, &serde_json::Value>,
+    values: &mut BTreeMap<String, serde_json::Value>,
+    path: &mut Vec<String>,
+) -> Result<serde_json::Value, Error> {
+    if let Some(value) = values.get(name) {
+        return Ok(value.clone());
+    }
+    if path.iter().any(|var| var == name) {
+        path.push(name.to_string());
+        return Err(format_err!("variable cycle {}", path.join(" -> ")));
+    }
+    let value = defined.get(name).ok_or_else(|| {
+        format_err!(
+            "variable `{}` references undefined variable `{}`",
+            path.last().cloned().unwrap_or_default(),
+            name
+        )
+    })?;
+    let resolved = match value
+        .as_str()
+        .and_then(|s| parse_from_variable(s.to_string()))
+    {
+        Some(reference) => {
+            path.push(name.to_string());
+            let resolved = resolve_variable(reference.as_str(), defined, values, path)?;
+            path.pop();
+            resolved
+        }
+        None => (*value).clone(),
+    };
+    values.insert(name.to_string(), resolved.clone());
+    Ok(resolved)
+}
+
+/// substitute_variables replaces every [fromVariable(VARNAME)] in `text` with the value of the
+/// variable, strings as they are and other values as JSON.
+pub fn substitute_variables(
+    text: &str,
+    vars: &BTreeMap<String, serde_json::Value>,
+) -> Result<String, Error> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r#"\[fromVariable\((?P<var>[[:word:]]+)\)\]"#).unwrap();
+    }
+    let mut substituted = String::with_capacity(text.len());
+    let mut last = 0;
+    for cap in RE.captures_iter(text) {
+        let reference = cap.get(0).expect("match");
+        let var = &cap["var"];
+        let value = match vars.get(var) {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Null) => {
+                return Err(format_err!("variable `{}` has no value to substitute", var))
+            }
+            Some(value) => value.to_string(),
+            None => return Err(format_err!("undefined variable `{}`", var)),
+        };
+        substituted.push_str(&text[last..reference.start()]);
+        substituted.push_str(&value);
+        last = reference.end();
+    }
+    substituted.push_str(&text[last..]);
+    Ok(substituted)
 }
 
 /// Transform a vector of variables into parameter values.
@@ -114,31 +200,41 @@ mod tests {
 
     #[test]
     fn test_resolve_variables() {
-        resolve_variables(
+        let values = resolve_variables(
             vec![
                 ParameterValue {
                     name: "dinner1".into(),
                     value: Some(json!("[fromVariable(pet1)]")),
                     from_param: None,
+                    value_from: None,
+                    from_component: None,
+                    output: None,
                 },
                 ParameterValue {
                     name: "dinner2".into(),
                     value: Some(json!("[fromVariable(pet2)]")),
                     from_param: None,
+                    value_from: None,
+                    from_component: None,
+                    output: None,
                 },
             ],
             vec![
                 Variable {
                     name: "pet1".into(),
                     value: json!("cat"),
+                    value_from: None,
                 },
                 Variable {
                     name: "pet2".into(),
-                    value: json!("dog"),
+                    value: json!("[fromVariable(pet1)]"),
+                    value_from: None,
                 },
             ],
         )
         .expect("resolve variables");
+        assert_eq!(Some(json!("cat")), values[0].value);
+        assert_eq!(Some(json!("cat")), values[1].value);
 
         // test parameter value referencing undefine variable should error.
         resolve_variables(
@@ -146,12 +242,71 @@ mod tests {
                 name: "dinner".into(),
                 value: Some(json!("[fromVariable(cereal)]")),
                 from_param: None,
+                value_from: None,
+                from_component: None,
+                output: None,
             }],
             vec![],
         )
         .expect_err(r#"undefined variable `"cereal"`"#);
     }
 
+    #[test]
+    fn test_variable_values() {
+        let var = |name: &str, value: serde_json::Value| Variable {
+            name: name.into(),
+            value,
+            value_from: None,
+        };
+        let values = variable_values(&[
+            var("url", json!("[fromVariable(host)]")),
+            var("host", json!("[fromVariable(default_host)]")),
+            var("default_host", json!("db.local")),
+            var("port", json!(5432)),
+        ])
+        .expect("variable values");
+        assert_eq!(Some(&json!("db.local")), values.get("url"));
+        assert_eq!(Some(&json!("db.local")), values.get("host"));
+        assert_eq!(Some(&json!(5432)), values.get("port"));
+
+        let err = variable_values(&[
+            var("a", json!("[fromVariable(b)]")),
+            var("b", json!("[fromVariable(c)]")),
+            var("c", json!("[fromVariable(a)]")),
+        ])
+        .expect_err("cycle");
+        assert_eq!("variable cycle a -> b -> c -> a", err.to_string());
+
+        let err = variable_values(&[var("a", json!("[fromVariable(b)]"))]).expect_err("undefined");
+        assert_eq!(
+            "variable `a` references undefined variable `b`",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables() {
+        let mut vars = BTreeMap::new();
+        vars.insert("host".to_string(), json!("db.local"));
+        vars.insert("port".to_string(), json!(5432));
+        vars.insert("password".to_string(), serde_json::Value::Null);
+        assert_eq!(
+            "url: postgres://db.local:5432/app\nhost: db.local\n",
+            substitute_variables(
+                "url: postgres://[fromVariable(host)]:[fromVariable(port)]/app\nhost: [fromVariable(host)]\n",
+                &vars
+            )
+            .expect("substitute")
+        );
+        assert_eq!(
+            "no [fromVariable (host)]",
+            substitute_variables("no [fromVariable (host)]", &vars).expect("substitute")
+        );
+        let err = substitute_variables("[fromVariable(user)]", &vars).expect_err("undefined");
+        assert_eq!("undefined variable `user`", err.to_string());
+        substitute_variables("[fromVariable(password)]", &vars).expect_err("no value");
+    }
+
     #[test]
     fn test_parse_from_variable() {
         assert_eq!(
@@ -0,0 +1,51 @@
+/// Shard is the subset of the configurations one of several rudr replicas reconciles. Every
+/// configuration belongs to exactly one shard, by a hash of its namespace and name, so the
+/// replicas split the work without coordinating.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shard {
+    /// The shard of this replica, in `0..count`.
+    pub index: u32,
+    /// The number of shards, 1 reconciles everything.
+    pub count: u32,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Shard { index: 0, count: 1 }
+    }
+}
+
+impl Shard {
+    pub fn new(index: u32, count: u32) -> Result<Self, failure::Error> {
+        if count == 0 || index >= count {
+            return Err(format_err!(
+                "invalid shard {} of {}: the index must be below the number of shards",
+                index,
+                count
+            ));
+        }
+        Ok(Shard { index, count })
+    }
+
+    /// owns is true if the configuration `name` in `namespace` belongs to this shard.
+    pub fn owns(&self, namespace: &str, name: &str) -> bool {
+        self.count <= 1 || shard_of(namespace, name, self.count) == self.index
+    }
+}
+
+/// shard_of returns the shard of the configuration `name` in `namespace` among `count` shards.
+/// The hash is FNV-1a, which unlike the hasher of the standard library is the same for every
+/// build, so that replicas of different versions agree during a rollout.
+pub fn shard_of(namespace: &str, name: &str, count: u32) -> u32 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in namespace.bytes().chain(Some(b'/')).chain(name.bytes()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    (hash % u64::from(count.max(1))) as u32
+}
+
+/// ordinal is the ordinal of a StatefulSet pod named `identity`, the number after its last `-`.
+pub fn ordinal(identity: &str) -> Option<u32> {
+    identity.rsplit('-').next().and_then(|n| n.parse().ok())
+}
@@ -0,0 +1,31 @@
+use crate::shard::*;
+
+#[test]
+fn test_shard() {
+    assert!(Shard::default().owns("default", "app"));
+    assert!(Shard::new(3, 3).is_err());
+    assert!(Shard::new(0, 0).is_err());
+
+    // Every configuration is owned by exactly one of the shards.
+    let shards: Vec<Shard> = (0..3).map(|i| Shard::new(i, 3).unwrap()).collect();
+    let mut owned = vec![0; 3];
+    for n in 0..300 {
+        let name = format!("app-{}", n);
+        let owners: Vec<&Shard> = shards.iter().filter(|s| s.owns("default", &name)).collect();
+        assert_eq!(1, owners.len());
+        owned[owners[0].index as usize] += 1;
+    }
+    assert!(owned.iter().all(|n| *n > 50), "uneven shards {:?}", owned);
+
+    // The shard doesn't depend on the build or platform.
+    assert_eq!(867, shard_of("default", "app-0", 1000));
+    assert_eq!(3, shard_of("prod", "shop", 7));
+}
+
+#[test]
+fn test_ordinal() {
+    assert_eq!(Some(2), ordinal("rudr-2"));
+    assert_eq!(Some(10), ordinal("my-rudr-10"));
+    assert_eq!(None, ordinal("rudr-5d8f7c-xk2lp"));
+    assert_eq!(None, ordinal("rudr"));
+}
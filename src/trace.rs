@@ -0,0 +1,244 @@
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often finished spans are sent to the collector.
+pub const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+/// Spans beyond this many waiting to be sent are dropped, so that a collector that is down
+/// doesn't grow the memory of rudr.
+const MAX_PENDING: usize = 4096;
+
+lazy_static! {
+    static ref PENDING: Mutex<Option<Vec<SpanRecord>>> = Mutex::new(None);
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static CURRENT: RefCell<Vec<SpanContext>> = RefCell::new(vec![]);
+}
+
+/// SpanContext identifies a span, the parent of the spans started while it is current.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpanContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+}
+
+/// SpanRecord is a finished span waiting to be exported.
+#[derive(Clone, Debug)]
+pub struct SpanRecord {
+    pub context: SpanContext,
+    pub parent: Option<u64>,
+    pub name: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(String, String)>,
+    pub error: Option<String>,
+}
+
+impl SpanRecord {
+    /// to_otlp is the span in the OTLP/JSON encoding.
+    pub fn to_otlp(&self) -> Value {
+        let mut span = json!({
+            "traceId": format!("{:032x}", self.context.trace_id),
+            "spanId": format!("{:016x}", self.context.span_id),
+            "name": self.name,
+            // Internal, kube API calls are made through clients that are not traced themselves.
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(self.start).to_string(),
+            "endTimeUnixNano": unix_nanos(self.end).to_string(),
+            "attributes": self.attributes.iter().map(|(key, value)| json!({
+                "key": key,
+                "value": {"stringValue": value},
+            })).collect::<Vec<Value>>(),
+            "status": match &self.error {
+                Some(message) => json!({"code": 2, "message": message}),
+                None => json!({"code": 0}),
+            },
+        });
+        if let Some(parent) = self.parent {
+            span["parentSpanId"] = json!(format!("{:016x}", parent));
+        }
+        span
+    }
+}
+
+/// export_body is the body of an OTLP/HTTP export request of `spans`.
+pub fn export_body(service_name: &str, spans: &[SpanRecord]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{"key": "service.name", "value": {"stringValue": service_name}}],
+            },
+            "scopeSpans": [{
+                "scope": {"name": "rudr", "version": env!("CARGO_PKG_VERSION")},
+                "spans": spans.iter().map(SpanRecord::to_otlp).collect::<Vec<Value>>(),
+            }],
+        }],
+    })
+}
+
+fn unix_nanos(t: SystemTime) -> u128 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos()
+}
+
+/// Span times a unit of work from its start until it is dropped. Spans started while it is alive
+/// on the same thread are its children. Without a collector configured spans record nothing.
+pub struct Span {
+    record: Option<SpanRecord>,
+}
+
+impl Span {
+    pub fn set_attribute<V: ToString>(&mut self, key: &str, value: V) {
+        if let Some(record) = self.record.as_mut() {
+            record.attributes.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    /// record marks the span failed if `res` is an error.
+    pub fn record<T, E: std::fmt::Display>(&mut self, res: &Result<T, E>) {
+        if let (Some(record), Err(e)) = (self.record.as_mut(), res) {
+            record.error = Some(e.to_string());
+        }
+    }
+
+    pub fn context(&self) -> Option<SpanContext> {
+        self.record.as_ref().map(|r| r.context)
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let mut record = match self.record.take() {
+            Some(record) => record,
+            None => return,
+        };
+        CURRENT.with(|current| {
+            let mut current = current.borrow_mut();
+            if let Some(i) = current.iter().rposition(|c| *c == record.context) {
+                current.remove(i);
+            }
+        });
+        record.end = SystemTime::now();
+        if let Ok(mut pending) = PENDING.lock() {
+            if let Some(pending) = pending.as_mut() {
+                if pending.len() < MAX_PENDING {
+                    pending.push(record);
+                }
+            }
+        }
+    }
+}
+
+/// span starts a span named `name`, a child of the current span of the thread.
+pub fn span(name: &str) -> Span {
+    let enabled = PENDING.lock().map(|p| p.is_some()).unwrap_or(false);
+    if !enabled {
+        return Span { record: None };
+    }
+    let parent = current();
+    let context = SpanContext {
+        trace_id: parent
+            .map(|p| p.trace_id)
+            .unwrap_or_else(|| u128::from(next_id()) << 64 | u128::from(next_id())),
+        span_id: next_id(),
+    };
+    CURRENT.with(|current| current.borrow_mut().push(context));
+    let now = SystemTime::now();
+    Span {
+        record: Some(SpanRecord {
+            context,
+            parent: parent.map(|p| p.span_id),
+            name: name.to_string(),
+            start: now,
+            end: now,
+            attributes: vec![],
+            error: None,
+        }),
+    }
+}
+
+/// current is the innermost span of the thread that is not done yet.
+pub fn current() -> Option<SpanContext> {
+    CURRENT.with(|current| current.borrow().last().cloned())
+}
+
+/// Attached makes a span of another thread the current span of this one until it is dropped.
+pub struct Attached {
+    context: Option<SpanContext>,
+}
+
+/// attach continues `parent`, taken from `current` on the thread that handed the work over.
+pub fn attach(parent: Option<SpanContext>) -> Attached {
+    if let Some(context) = parent {
+        CURRENT.with(|current| current.borrow_mut().push(context));
+    }
+    Attached { context: parent }
+}
+
+impl Drop for Attached {
+    fn drop(&mut self) {
+        if let Some(context) = self.context {
+            CURRENT.with(|current| {
+                let mut current = current.borrow_mut();
+                if let Some(i) = current.iter().rposition(|c| *c == context) {
+                    current.remove(i);
+                }
+            });
+        }
+    }
+}
+
+/// next_id returns a random-looking, non-zero id. Ids only have to be unique, they are a
+/// counter mixed with the time the process started and its pid.
+fn next_id() -> u64 {
+    lazy_static! {
+        static ref SEED: u64 =
+            unix_nanos(SystemTime::now()) as u64 ^ (u64::from(std::process::id()) << 32);
+    }
+    // splitmix64
+    let mut z = SEED.wrapping_add(
+        NEXT_ID
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_add(1)
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15),
+    );
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)).max(1)
+}
+
+/// configure starts exporting spans to the OTLP/HTTP collector at `endpoint`, for example
+/// `http://otel-collector:4318`, as the service `service_name`.
+pub fn configure(endpoint: &str, service_name: &str) {
+    if let Ok(mut pending) = PENDING.lock() {
+        *pending = Some(vec![]);
+    }
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    let service_name = service_name.to_string();
+    std::thread::spawn(move || {
+        let client = reqwest::Client::new();
+        loop {
+            std::thread::sleep(EXPORT_INTERVAL);
+            let spans = match PENDING.lock() {
+                Ok(mut pending) => pending.as_mut().map(std::mem::take).unwrap_or_default(),
+                Err(_) => continue,
+            };
+            if spans.is_empty() {
+                continue;
+            }
+            let body = export_body(service_name.as_str(), &spans);
+            match client.post(url.as_str()).json(&body).send() {
+                Ok(resp) if resp.status().is_success() => {
+                    debug!("exported {} spans", spans.len())
+                }
+                Ok(resp) => warn!("export {} spans to {}: {}", spans.len(), url, resp.status()),
+                Err(e) => warn!("export {} spans to {}: {}", spans.len(), url, e),
+            }
+        }
+    });
+}
@@ -0,0 +1,52 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::trace::*;
+
+#[test]
+fn test_span_record_to_otlp() {
+    let record = SpanRecord {
+        context: SpanContext {
+            trace_id: 0xab,
+            span_id: 0x12,
+        },
+        parent: Some(0x34),
+        name: "component".to_string(),
+        start: UNIX_EPOCH + Duration::from_secs(1),
+        end: UNIX_EPOCH + Duration::from_millis(1500),
+        attributes: vec![("component.instance".to_string(), "web".to_string())],
+        error: Some("boom".to_string()),
+    };
+    let span = record.to_otlp();
+    assert_eq!("000000000000000000000000000000ab", span["traceId"]);
+    assert_eq!("0000000000000012", span["spanId"]);
+    assert_eq!("0000000000000034", span["parentSpanId"]);
+    assert_eq!("1000000000", span["startTimeUnixNano"]);
+    assert_eq!("1500000000", span["endTimeUnixNano"]);
+    assert_eq!("component.instance", span["attributes"][0]["key"]);
+    assert_eq!("web", span["attributes"][0]["value"]["stringValue"]);
+    assert_eq!(2, span["status"]["code"]);
+    assert_eq!("boom", span["status"]["message"]);
+
+    let root = SpanRecord {
+        parent: None,
+        error: None,
+        ..record
+    };
+    let body = export_body("rudr", &[root]);
+    let resource = &body["resourceSpans"][0];
+    assert_eq!(
+        "rudr",
+        resource["resource"]["attributes"][0]["value"]["stringValue"]
+    );
+    let span = &resource["scopeSpans"][0]["spans"][0];
+    assert!(span.get("parentSpanId").is_none());
+    assert_eq!(0, span["status"]["code"]);
+}
+
+#[test]
+fn test_span_without_collector() {
+    // Nothing is recorded until a collector is configured.
+    let span = span("reconcile");
+    assert_eq!(None, span.context());
+    assert_eq!(None, current());
+}
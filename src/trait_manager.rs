@@ -12,10 +12,17 @@ use crate::{
         component::Component,
         configuration::ComponentConfiguration,
         parameter::ParameterValue,
+        trait_definition::TraitDefinition,
         traits::{
-            self, Autoscaler, Empty, Ingress, ManualScaler, OAMTrait, TraitBinding, VolumeMounter,
+            self, Autoscaler, Backup, ConfigMounter, DefinedTrait, DisruptionBudget, Dns, Empty,
+            HelmRelease, HttpRoute, ImagePullSecret, Ingress, InitContainer, KedaAutoscaler,
+            LogForwarder, ManualScaler, MetricsMonitor, NetworkPolicy, OAMTrait, Placement,
+            Priority, SecretMounter, SecurityContext, ServiceMesh, Sidecar, Tolerations,
+            TopologySpread, TrafficSplit, TraitBinding, VerticalAutoscaler, VolumeMounter,
         },
+        variable::{variable_values, Variable},
     },
+    workload_type::PodPatch,
 };
 
 // TraitManager maps a component to its traits, and handles trait lifecycle.
@@ -33,14 +40,17 @@ pub struct TraitManager {
     // Component schematic loaded from cluster.
     pub component_schematic: Component,
     pub traits: Vec<OAMTrait>,
+    // TraitDefinitions by name, for the traits that aren't built in.
+    pub definitions: BTreeMap<String, TraitDefinition>,
 }
 
 impl TraitManager {
     pub fn load_traits(&mut self) -> Result<(), failure::Error> {
         let mut traits: Vec<OAMTrait> = vec![];
-        for t in self.component.traits.as_ref().unwrap_or(&vec![]).iter() {
-            // Load all of the traits into the manager.
-            let imp = self.load_trait(&t)?;
+        let bindings = self.component.traits.as_deref().unwrap_or_default();
+        for t in traits::apply_order(bindings)? {
+            // Load all of the traits into the manager, in the order they are applied.
+            let imp = self.load_trait(t)?;
             traits.push(imp);
         }
         self.traits = traits;
@@ -66,6 +76,235 @@ impl TraitManager {
                 debug!("INGRESS_V1ALPHA1: {:?}", ing);
                 Ok(OAMTrait::Ingress(ing))
             }
+            traits::HTTP_ROUTE_V1ALPHA1 => {
+                let route = HttpRoute::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("HTTP_ROUTE_V1ALPHA1: {:?}", route);
+                Ok(OAMTrait::HttpRoute(route))
+            }
+            traits::TRAFFIC_SPLIT_V1ALPHA1 => {
+                let split = TrafficSplit::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("TRAFFIC_SPLIT_V1ALPHA1: {:?}", split);
+                Ok(OAMTrait::TrafficSplit(split))
+            }
+            traits::DISRUPTION_BUDGET_V1ALPHA1 => {
+                let budget = DisruptionBudget::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("DISRUPTION_BUDGET_V1ALPHA1: {:?}", budget);
+                Ok(OAMTrait::DisruptionBudget(budget))
+            }
+            traits::NETWORK_POLICY_V1ALPHA1 => {
+                let policy = NetworkPolicy::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("NETWORK_POLICY_V1ALPHA1: {:?}", policy);
+                Ok(OAMTrait::NetworkPolicy(policy))
+            }
+            traits::METRICS_V1ALPHA1 => {
+                let monitor = MetricsMonitor::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                    self.workload_type.clone(),
+                    self.component_schematic
+                        .listening_port()
+                        .map(|p| p.name.clone()),
+                );
+                debug!("METRICS_V1ALPHA1: {:?}", monitor);
+                Ok(OAMTrait::MetricsMonitor(monitor))
+            }
+            traits::LOG_FORWARDER_V1ALPHA1 => {
+                let forwarder = LogForwarder::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("LOG_FORWARDER_V1ALPHA1: {:?}", forwarder);
+                Ok(OAMTrait::LogForwarder(forwarder))
+            }
+            traits::SECRET_MOUNTER_V1ALPHA1 => {
+                let s = SecretMounter::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("SECRET_MOUNTER_V1ALPHA1: {:?}", s);
+                Ok(OAMTrait::SecretMounter(s))
+            }
+            traits::CONFIG_MOUNTER_V1ALPHA1 => {
+                let c = ConfigMounter::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                    self.variables()?,
+                );
+                debug!("CONFIG_MOUNTER_V1ALPHA1: {:?}", c);
+                Ok(OAMTrait::ConfigMounter(c))
+            }
+            traits::DNS_V1ALPHA1 => {
+                let d = Dns::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                    self.component_schematic.listening_port().cloned(),
+                );
+                debug!("DNS_V1ALPHA1: {:?}", d);
+                Ok(OAMTrait::Dns(d))
+            }
+            traits::PLACEMENT_V1ALPHA1 => {
+                let p = Placement::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                );
+                debug!("PLACEMENT_V1ALPHA1: {:?}", p);
+                Ok(OAMTrait::Placement(p))
+            }
+            traits::TOLERATIONS_V1ALPHA1 => {
+                let t = Tolerations::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                );
+                debug!("TOLERATIONS_V1ALPHA1: {:?}", t);
+                Ok(OAMTrait::Tolerations(t))
+            }
+            traits::TOPOLOGY_SPREAD_V1ALPHA1 => {
+                let t = TopologySpread::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                );
+                debug!("TOPOLOGY_SPREAD_V1ALPHA1: {:?}", t);
+                Ok(OAMTrait::TopologySpread(t))
+            }
+            traits::SIDECAR_V1ALPHA1 => {
+                let s = Sidecar::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.component_schematic
+                        .containers
+                        .iter()
+                        .map(|c| c.name.clone())
+                        .collect(),
+                );
+                debug!("SIDECAR_V1ALPHA1: {:?}", s);
+                Ok(OAMTrait::Sidecar(s))
+            }
+            traits::INIT_CONTAINER_V1ALPHA1 => {
+                let i = InitContainer::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.component_schematic
+                        .containers
+                        .iter()
+                        .map(|c| c.name.clone())
+                        .collect(),
+                );
+                debug!("INIT_CONTAINER_V1ALPHA1: {:?}", i);
+                Ok(OAMTrait::InitContainer(i))
+            }
+            traits::SERVICE_MESH_V1ALPHA1 => {
+                let m = ServiceMesh::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("SERVICE_MESH_V1ALPHA1: {:?}", m);
+                Ok(OAMTrait::ServiceMesh(m))
+            }
+            traits::HELM_RELEASE_V1ALPHA1 => {
+                let h = HelmRelease::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("HELM_RELEASE_V1ALPHA1: {:?}", h);
+                Ok(OAMTrait::HelmRelease(h))
+            }
+            traits::BACKUP_V1ALPHA1 => {
+                let b = Backup::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("BACKUP_V1ALPHA1: {:?}", b);
+                Ok(OAMTrait::Backup(b))
+            }
+            traits::SECURITY_CONTEXT_V1ALPHA1 => {
+                let s = SecurityContext::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                );
+                debug!("SECURITY_CONTEXT_V1ALPHA1: {:?}", s);
+                Ok(OAMTrait::SecurityContext(s))
+            }
+            traits::IMAGE_PULL_SECRET_V1ALPHA1 => {
+                let p = ImagePullSecret::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("IMAGE_PULL_SECRET_V1ALPHA1: {:?}", p);
+                Ok(OAMTrait::ImagePullSecret(p))
+            }
+            traits::PRIORITY_V1ALPHA1 => {
+                let p = Priority::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                );
+                debug!("PRIORITY_V1ALPHA1: {:?}", p);
+                Ok(OAMTrait::Priority(p))
+            }
             traits::VOLUME_MOUNTER_V1ALPHA1 => {
                 let volmount = VolumeMounter::from_properties(
                     self.config_name.clone(),
@@ -89,6 +328,17 @@ impl TraitManager {
                 debug!("Auto_scaler: {:?}", auto_scaler);
                 Ok(OAMTrait::Autoscaler(auto_scaler))
             }
+            traits::KEDA_AUTOSCALER_V1ALPHA1 => {
+                let keda_scaler = KedaAutoscaler::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                );
+                debug!("Keda_autoscaler: {:?}", keda_scaler);
+                Ok(OAMTrait::KedaAutoscaler(keda_scaler))
+            }
             traits::MANUAL_SCALER_V1ALPHA1 => {
                 let scaler = ManualScaler::from_properties(
                     self.config_name.clone(),
@@ -101,17 +351,60 @@ impl TraitManager {
                 debug!("Manual_scaler: {:?}", scaler);
                 Ok(OAMTrait::ManualScaler(scaler))
             }
+            traits::VERTICAL_AUTOSCALER_V1ALPHA1 => {
+                let vertical_scaler = VerticalAutoscaler::from_properties(
+                    self.config_name.clone(),
+                    self.instance_name.clone(),
+                    self.component.component_name.clone(),
+                    prop_map,
+                    self.owner_ref.clone(),
+                    self.workload_type.clone(),
+                );
+                debug!("Vertical_autoscaler: {:?}", vertical_scaler);
+                Ok(OAMTrait::VerticalAutoscaler(vertical_scaler))
+            }
             // Empty is a debugging tool for checking whether the traits system is functioning independently of
             // its environment.
             traits::EMPTY => {
                 let empty = Empty {};
                 Ok(OAMTrait::Empty(empty))
             }
-            _ => Err(format_err!("unknown trait {}", binding.name)),
+            name => {
+                let definition = self
+                    .definitions
+                    .get(name)
+                    .ok_or_else(|| format_err!("unknown trait {}", name))?;
+                if !definition.applies_to(self.workload_type.as_str()) {
+                    return Err(format_err!(
+                        "trait {} doesn't apply to workload type {}",
+                        name,
+                        self.workload_type
+                    ));
+                }
+                let defined = DefinedTrait {
+                    name: self.config_name.clone(),
+                    instance_name: self.instance_name.clone(),
+                    component_name: self.component.component_name.clone(),
+                    trait_name: name.to_string(),
+                    definition: definition.clone(),
+                    properties: prop_map.cloned(),
+                    workload_type: self.workload_type.clone(),
+                    owner_ref: self.owner_ref.clone(),
+                };
+                debug!("Defined trait: {:?}", defined);
+                Ok(OAMTrait::Defined(Box::new(defined)))
+            }
         }
     }
     pub fn exec(&self, ns: &str, client: APIClient, phase: Phase) -> Result<(), Error> {
-        for imp in &self.traits {
+        // Traits are deleted in the reverse order they are applied in, dependents first.
+        let deleting = phase == Phase::PreDelete || phase == Phase::Delete;
+        let ordered: Vec<&OAMTrait> = if deleting {
+            self.traits.iter().rev().collect()
+        } else {
+            self.traits.iter().collect()
+        };
+        for imp in ordered {
             // At the moment, we don't return an error if a trait fails.
             let res = imp.exec(ns, client.clone(), phase.clone());
             if let Err(err) = res {
@@ -125,6 +418,38 @@ impl TraitManager {
         }
         Ok(())
     }
+    /// variables are the values of the variables of the configuration, with the references to
+    /// other variables resolved. Variables read from a Secret or ConfigMap have no value here.
+    fn variables(&self) -> Result<BTreeMap<String, serde_json::Value>, Error> {
+        let vars: Vec<Variable> = self
+            .parent_params
+            .iter()
+            .map(|p| Variable {
+                name: p.name.clone(),
+                value: p.value.clone().unwrap_or_default(),
+                value_from: None,
+            })
+            .collect();
+        variable_values(&vars)
+    }
+    /// pod_patch is what the traits add to the pods of the workload.
+    pub fn pod_patch(&self) -> Result<PodPatch, Error> {
+        let mut patch = PodPatch::default();
+        for imp in &self.traits {
+            if let Some(p) = imp.pod_patch()? {
+                patch.extend(p);
+            }
+        }
+        Ok(patch)
+    }
+    /// render returns the Kubernetes resources the traits would create, without creating them.
+    pub fn render(&self) -> Result<Vec<serde_json::Value>, Error> {
+        let mut objects = vec![];
+        for imp in &self.traits {
+            objects.extend(imp.render()?);
+        }
+        Ok(objects)
+    }
     pub fn status(&self, ns: &str, client: APIClient) -> Option<BTreeMap<String, String>> {
         let mut all_status = BTreeMap::new();
         for imp in &self.traits {
@@ -0,0 +1,339 @@
+use failure::Error;
+use kube::client::APIClient;
+use log::{error, info, warn};
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+use crate::instigator::{get_component_def, Instigator, OpResource};
+use crate::schematic::{
+    component::Component,
+    parameter::{validate_parameters, Parameter},
+    trait_definition::TraitDefinition,
+    traits,
+};
+
+/// Admission requests larger than this are rejected before they are parsed.
+const MAX_REQUEST_SIZE: usize = 4 * 1024 * 1024;
+
+/// Defaults are filled in on ApplicationConfigurations by the mutating webhook, on top of the
+/// instance names and parameter defaults every configuration gets.
+#[derive(Clone, Debug, Default)]
+pub struct Defaults {
+    /// The scopes of components that don't name any.
+    pub application_scopes: Vec<String>,
+}
+
+/// serve runs the admission webhooks on `addr` until the process exits. The API server only
+/// talks to webhooks over TLS, `cert` and `key` are the PEM files of the serving certificate.
+///
+/// `/validate` checks ApplicationConfigurations and ComponentSchematics the way the instigator
+/// would, so that bad objects are rejected when they are applied. `/mutate` fills in the defaults
+/// of ApplicationConfigurations, so that the stored object is explicit.
+pub fn serve(
+    addr: &str,
+    cert: &str,
+    key: &str,
+    client: APIClient,
+    defaults: Defaults,
+) -> Result<(), Error> {
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    acceptor.set_private_key_file(key, SslFiletype::PEM)?;
+    acceptor.set_certificate_chain_file(cert)?;
+    acceptor.check_private_key()?;
+    let acceptor = Arc::new(acceptor.build());
+    let listener = TcpListener::bind(addr)?;
+    info!("Admission webhook is running on {}", addr);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("accept webhook connection err {:?}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let client = client.clone();
+        let defaults = defaults.clone();
+        thread::spawn(move || {
+            let res = acceptor
+                .accept(stream)
+                .map_err(|e| format_err!("TLS handshake failed: {}", e))
+                .and_then(|mut stream| handle(&mut stream, &client, &defaults));
+            if let Err(e) = res {
+                error!("serve admission request err {:?}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle<S: Read + Write>(
+    stream: &mut S,
+    client: &APIClient,
+    defaults: &Defaults,
+) -> Result<(), Error> {
+    let (path, body) = read_request(stream)?;
+    let (status, body) = match (path.as_str(), serde_json::from_slice(&body)) {
+        ("/validate", Ok(request)) => (
+            "200 OK",
+            review(request, |kind, namespace, object| {
+                validate(client, kind, namespace, object)
+            }),
+        ),
+        ("/mutate", Ok(request)) => (
+            "200 OK",
+            mutation_review(request, |kind, namespace, object| {
+                default(client, defaults, kind, namespace, object)
+            }),
+        ),
+        ("/validate", Err(e)) | ("/mutate", Err(e)) => {
+            ("400 Bad Request", json!({"error": e.to_string()}))
+        }
+        _ => ("404 Not Found", json!({})),
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// read_request reads one HTTP/1.1 request and returns its path and body.
+pub fn read_request<R: Read>(stream: &mut R) -> Result<(String, Vec<u8>), Error> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 8192];
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_REQUEST_SIZE {
+            return Err(format_err!("request headers too large"));
+        }
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(format_err!(
+                "connection closed before the request was complete"
+            ));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let path = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| format_err!("malformed request line"))?
+        .to_string();
+    let length = lines
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) if name.eq_ignore_ascii_case("content-length") => {
+                    value.trim().parse::<usize>().ok()
+                }
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or(0);
+    if length > MAX_REQUEST_SIZE {
+        return Err(format_err!("request body of {} bytes too large", length));
+    }
+    let mut body = buf.split_off(header_end);
+    while body.len() < length {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(format_err!(
+                "connection closed before the body was complete"
+            ));
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    Ok((path, body))
+}
+
+/// review answers an admission.k8s.io/v1beta1 AdmissionReview. `check` gets the kind, namespace
+/// and object of the request, an error rejects the object with its message.
+pub fn review<F>(review: Value, check: F) -> Value
+where
+    F: Fn(&str, &str, Value) -> Result<(), Error>,
+{
+    let request = &review["request"];
+    let mut response = json!({
+        "uid": request["uid"],
+        "allowed": true,
+    });
+    let kind = request["kind"]["kind"].as_str().unwrap_or_default();
+    let namespace = request["namespace"].as_str().unwrap_or("default");
+    if let Err(e) = check(kind, namespace, request["object"].clone()) {
+        response["allowed"] = json!(false);
+        response["status"] = json!({"code": 422, "message": e.to_string()});
+    }
+    json!({
+        "apiVersion": "admission.k8s.io/v1beta1",
+        "kind": "AdmissionReview",
+        "response": response,
+    })
+}
+
+/// mutation_review answers an admission.k8s.io/v1beta1 AdmissionReview with the JSON patch
+/// `mutate` returns for the kind, namespace and object of the request. An object that can't be
+/// defaulted is allowed unchanged, rejecting it is up to validation.
+pub fn mutation_review<F>(review: Value, mutate: F) -> Value
+where
+    F: Fn(&str, &str, Value) -> Result<Vec<Value>, Error>,
+{
+    let request = &review["request"];
+    let mut response = json!({
+        "uid": request["uid"],
+        "allowed": true,
+    });
+    let kind = request["kind"]["kind"].as_str().unwrap_or_default();
+    let namespace = request["namespace"].as_str().unwrap_or("default");
+    match mutate(kind, namespace, request["object"].clone()) {
+        Ok(patch) if patch.is_empty() => {}
+        Ok(patch) => {
+            response["patchType"] = json!("JSONPatch");
+            response["patch"] = json!(base64::encode(&Value::Array(patch).to_string()));
+        }
+        Err(e) => warn!("default {} err {:?}", kind, e),
+    }
+    json!({
+        "apiVersion": "admission.k8s.io/v1beta1",
+        "kind": "AdmissionReview",
+        "response": response,
+    })
+}
+
+/// default returns the JSON patch that fills in the defaults of an ApplicationConfiguration, see
+/// `default_configuration`. Other objects are left alone.
+pub fn default(
+    client: &APIClient,
+    defaults: &Defaults,
+    kind: &str,
+    namespace: &str,
+    object: Value,
+) -> Result<Vec<Value>, Error> {
+    if kind != "ApplicationConfiguration" {
+        return Ok(vec![]);
+    }
+    // Components whose schematic doesn't exist are rejected by validation.
+    let parameters = |name: &str| {
+        get_component_def(namespace.to_string(), name.to_string(), client.clone())
+            .ok()
+            .map(|comp_def| comp_def.spec.parameters)
+    };
+    Ok(default_configuration(&object, parameters, defaults))
+}
+
+/// default_configuration returns the JSON patch that fills in the defaults of the components of
+/// a configuration: a component without instance name is named after its schematic, the
+/// parameters with a default in the schematic that have no value get it, and components without
+/// scopes join the default scopes. `parameters` returns the parameters of a schematic by name.
+pub fn default_configuration<F>(object: &Value, parameters: F, defaults: &Defaults) -> Vec<Value>
+where
+    F: Fn(&str) -> Option<Vec<Parameter>>,
+{
+    let components = match object["spec"]["components"].as_array() {
+        Some(components) => components,
+        None => return vec![],
+    };
+    let mut defaulted = components.clone();
+    for component in defaulted.iter_mut().filter(|c| c.is_object()) {
+        let name = match component["componentName"].as_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        if component["instanceName"].is_null() {
+            component["instanceName"] = json!(name);
+        }
+        if component["applicationScopes"].is_null() && !defaults.application_scopes.is_empty() {
+            component["applicationScopes"] = json!(defaults.application_scopes);
+        }
+        if component["parameterValues"].is_null() {
+            component["parameterValues"] = json!([]);
+        }
+        let values = match component["parameterValues"].as_array_mut() {
+            Some(values) => values,
+            None => continue,
+        };
+        for param in parameters(name.as_str()).unwrap_or_default() {
+            let default = match param.default.clone() {
+                Some(default) if !default.is_null() => default,
+                _ => continue,
+            };
+            if !values.iter().any(|v| v["name"] == param.name.as_str()) {
+                values.push(json!({"name": param.name, "value": default}));
+            }
+        }
+    }
+    // Components that had no parameter values and got none stay as they were.
+    for (component, original) in defaulted.iter_mut().zip(components.iter()) {
+        if original["parameterValues"].is_null() && component["parameterValues"] == json!([]) {
+            if let Some(component) = component.as_object_mut() {
+                component.remove("parameterValues");
+            }
+        }
+    }
+    if &defaulted == components {
+        return vec![];
+    }
+    vec![json!({
+        "op": "replace",
+        "path": "/spec/components",
+        "value": defaulted,
+    })]
+}
+
+/// validate checks an object the instigator would act on. A configuration must render: its
+/// components exist, its parameter values match the parameter types of their schematics and its
+/// traits are known. A schematic must declare its parameters consistently, and a trait
+/// definition must have a template and not take the name of a built-in trait.
+pub fn validate(
+    client: &APIClient,
+    kind: &str,
+    namespace: &str,
+    object: Value,
+) -> Result<(), Error> {
+    match kind {
+        "ApplicationConfiguration" => {
+            let config: OpResource = serde_json::from_value(object)
+                .map_err(|e| format_err!("invalid application configuration: {}", e))?;
+            Instigator::new(client.clone(), namespace.to_string())
+                .render(&config)
+                .map(|_| ())
+        }
+        "ComponentSchematic" => validate_component(object),
+        "TraitDefinition" => validate_trait_definition(object),
+        // Everything else is left to the API server.
+        _ => Ok(()),
+    }
+}
+
+/// validate_component checks the spec of a ComponentSchematic object.
+pub fn validate_component(object: Value) -> Result<(), Error> {
+    let component: Component = serde_json::from_value(object["spec"].clone())
+        .map_err(|e| format_err!("invalid component schematic: {}", e))?;
+    validate_parameters(&component.parameters)
+}
+
+/// validate_trait_definition checks a TraitDefinition object.
+pub fn validate_trait_definition(object: Value) -> Result<(), Error> {
+    let name = object["metadata"]["name"].as_str().unwrap_or_default();
+    if traits::BUILTIN.contains(&name) {
+        return Err(format_err!("{} is the name of a built-in trait", name));
+    }
+    let definition: TraitDefinition = serde_json::from_value(object["spec"].clone())
+        .map_err(|e| format_err!("invalid trait definition: {}", e))?;
+    definition.validate()
+}
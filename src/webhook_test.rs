@@ -0,0 +1,152 @@
+use crate::schematic::parameter::{Parameter, ParameterType};
+use crate::webhook::*;
+use serde_json::json;
+use std::io::Cursor;
+
+#[test]
+fn test_review() {
+    let request = |kind: &str| {
+        json!({
+            "apiVersion": "admission.k8s.io/v1beta1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "1",
+                "kind": {"group": "core.oam.dev", "version": "v1alpha1", "kind": kind},
+                "namespace": "prod",
+                "object": {"spec": {}},
+            },
+        })
+    };
+    let check = |kind: &str, namespace: &str, _object: serde_json::Value| {
+        assert_eq!("prod", namespace);
+        if kind == "ComponentSchematic" {
+            return Err(format_err!("bad schematic"));
+        }
+        Ok(())
+    };
+    let resp = review(request("ApplicationConfiguration"), check);
+    assert_eq!(resp["response"]["uid"], "1");
+    assert_eq!(resp["response"]["allowed"], true);
+
+    let resp = review(request("ComponentSchematic"), check);
+    assert_eq!(resp["response"]["allowed"], false);
+    assert_eq!(resp["response"]["status"]["message"], "bad schematic");
+}
+
+#[test]
+fn test_mutation_review() {
+    let request = json!({
+        "request": {
+            "uid": "1",
+            "kind": {"group": "core.oam.dev", "version": "v1alpha1", "kind": "ApplicationConfiguration"},
+            "namespace": "prod",
+            "object": {"spec": {}},
+        },
+    });
+    let patch = || vec![json!({"op": "add", "path": "/spec/components", "value": []})];
+    let resp = mutation_review(request.clone(), |_, _, _| Ok(patch()));
+    assert_eq!(resp["response"]["allowed"], true);
+    assert_eq!(resp["response"]["patchType"], "JSONPatch");
+    let decoded = base64::decode(resp["response"]["patch"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        serde_json::Value::Array(patch()),
+        serde_json::from_slice::<serde_json::Value>(&decoded).unwrap()
+    );
+
+    // Nothing to default, or an object that can't be defaulted, is allowed unchanged.
+    let resp = mutation_review(request.clone(), |_, _, _| Ok(vec![]));
+    assert!(resp["response"].get("patch").is_none());
+    let resp = mutation_review(request, |_, _, _| Err(format_err!("not found")));
+    assert_eq!(resp["response"]["allowed"], true);
+    assert!(resp["response"].get("patch").is_none());
+}
+
+#[test]
+fn test_default_configuration() {
+    let parameters = |name: &str| {
+        if name != "web" {
+            return None;
+        }
+        let param = |name: &str, default: Option<serde_json::Value>| Parameter {
+            name: name.to_string(),
+            description: None,
+            parameter_type: ParameterType::Number,
+            required: false,
+            default,
+        };
+        Some(vec![
+            param("port", Some(json!(8080))),
+            param("replicas", Some(json!(1))),
+            param("weight", None),
+        ])
+    };
+    let config = json!({"spec": {"components": [
+        {"componentName": "web", "parameterValues": [{"name": "port", "value": 80}]},
+        {"componentName": "db", "instanceName": "db-1", "applicationScopes": []},
+    ]}});
+    let defaults = Defaults {
+        application_scopes: vec!["health".to_string()],
+    };
+    let patch = default_configuration(&config, parameters, &defaults);
+    assert_eq!(
+        vec![
+            json!({"op": "replace", "path": "/spec/components", "value": [
+                {
+                    "componentName": "web",
+                    "instanceName": "web",
+                    "applicationScopes": ["health"],
+                    "parameterValues": [
+                        {"name": "port", "value": 80},
+                        {"name": "replicas", "value": 1},
+                    ],
+                },
+                {"componentName": "db", "instanceName": "db-1", "applicationScopes": []},
+            ]})
+        ],
+        patch
+    );
+
+    // A configuration with its defaults filled in is left alone.
+    let defaulted = json!({"spec": {"components": patch[0]["value"]}});
+    assert!(default_configuration(&defaulted, parameters, &defaults).is_empty());
+    assert!(default_configuration(&json!({"spec": {}}), parameters, &defaults).is_empty());
+}
+
+#[test]
+fn test_validate_component() {
+    let schematic = |default: serde_json::Value| {
+        json!({
+            "metadata": {"name": "web"},
+            "spec": {
+                "workloadType": "core.oam.dev/v1alpha1.Server",
+                "parameters": [{"name": "port", "type": "number", "default": default}],
+                "containers": [],
+            },
+        })
+    };
+    assert!(validate_component(schematic(json!(8080))).is_ok());
+    assert!(validate_component(schematic(json!("http"))).is_err());
+    assert!(validate_component(json!({"spec": {"parameters": "port"}})).is_err());
+}
+
+#[test]
+fn test_validate_trait_definition() {
+    let definition = |name: &str, template: serde_json::Value| {
+        json!({
+            "metadata": {"name": name},
+            "spec": {"template": template},
+        })
+    };
+    let config_map = json!([{"apiVersion": "v1", "kind": "ConfigMap"}]);
+    assert!(validate_trait_definition(definition("settings", config_map.clone())).is_ok());
+    assert!(validate_trait_definition(definition("ingress", config_map)).is_err());
+    assert!(validate_trait_definition(definition("settings", json!([]))).is_err());
+}
+
+#[test]
+fn test_read_request() {
+    let raw = "POST /validate HTTP/1.1\r\nHost: x\r\ncontent-length: 4\r\n\r\n{}{}extra";
+    let (path, body) = read_request(&mut Cursor::new(raw.as_bytes())).unwrap();
+    assert_eq!(path, "/validate");
+    assert_eq!(body, b"{}{}");
+}
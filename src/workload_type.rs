@@ -2,6 +2,9 @@ use failure::Error;
 use log::info;
 use std::collections::BTreeMap;
 
+use crate::metrics::TimedRequest;
+use crate::mutator;
+
 mod server;
 pub use crate::workload_type::server::{ReplicatedServer, SingletonServer};
 
@@ -12,7 +15,7 @@ mod worker;
 pub use crate::workload_type::worker::{ReplicatedWorker, SingletonWorker};
 
 mod workload_builder;
-pub use crate::workload_type::workload_builder::WorkloadMetadata;
+pub use crate::workload_type::workload_builder::{PodPatch, Propagated, WorkloadMetadata};
 
 mod statefulset_builder;
 
@@ -37,6 +40,7 @@ pub const WORKER_NAME: &str = "core.oam.dev/v1alpha1.Worker";
 
 type InstigatorResult = Result<(), Error>;
 type StatusResult = Result<BTreeMap<String, String>, Error>;
+pub type RenderResult = Result<Vec<serde_json::Value>, Error>;
 pub type ParamMap = BTreeMap<String, serde_json::Value>;
 pub type ValidationResult = Result<(), failure::Error>;
 
@@ -79,6 +83,10 @@ pub trait WorkloadType {
     fn validate(&self) -> ValidationResult {
         Ok(())
     }
+    /// Render returns the Kubernetes resources `add` would create, without creating them.
+    fn render(&self) -> RenderResult {
+        Err(format_err!("Not implemented"))
+    }
 }
 
 pub enum CoreWorkloadType {
@@ -141,6 +149,16 @@ impl WorkloadType for CoreWorkloadType {
             CoreWorkloadType::SingletonWorkerType(task) => task.validate(),
         }
     }
+    fn render(&self) -> RenderResult {
+        match self {
+            CoreWorkloadType::SingletonServerType(sing) => sing.render(),
+            CoreWorkloadType::ReplicatedServerType(repl) => repl.render(),
+            CoreWorkloadType::SingletonTaskType(task) => task.render(),
+            CoreWorkloadType::ReplicatedTaskType(task) => task.render(),
+            CoreWorkloadType::ReplicatedWorkerType(task) => task.render(),
+            CoreWorkloadType::SingletonWorkerType(task) => task.render(),
+        }
+    }
 }
 
 pub enum ExtendedWorkloadType {
@@ -179,4 +197,257 @@ impl WorkloadType for ExtendedWorkloadType {
             ExtendedWorkloadType::Others(other) => other.validate(),
         }
     }
+    fn render(&self) -> RenderResult {
+        match self {
+            ExtendedWorkloadType::OpenFaaS(faas) => faas.render(),
+            ExtendedWorkloadType::Others(other) => other.render(),
+        }
+    }
+}
+
+/// is_subset is true if every field set in `desired` has the same value in `live`. Fields that
+/// only `live` has, such as defaults filled in by the API server, don't count as a change.
+pub fn is_subset(desired: &serde_json::Value, live: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (desired, live) {
+        (Value::Object(desired), Value::Object(live)) => {
+            desired.iter().all(|(k, v)| match live.get(k) {
+                Some(l) => is_subset(v, l),
+                None => v.is_null(),
+            })
+        }
+        (Value::Array(desired), Value::Array(live)) => {
+            desired.len() == live.len()
+                && desired
+                    .iter()
+                    .zip(live.iter())
+                    .all(|(d, l)| is_subset(d, l))
+        }
+        _ => desired == live,
+    }
+}
+
+/// The field manager rudr applies generated resources as.
+pub const FIELD_MANAGER: &str = "rudr";
+
+/// apply_params are the parameters of a server-side apply by FIELD_MANAGER. Without force, a field
+/// another manager set to a different value fails the apply with a conflict.
+pub fn apply_params() -> kube::api::PatchParams {
+    kube::api::PatchParams {
+        patch_strategy: kube::api::PatchStrategy::Apply,
+        field_manager: Some(FIELD_MANAGER.to_string()),
+        ..Default::default()
+    }
+}
+
+/// apply creates or updates the object `name` of `resource` with a server-side apply, so that
+/// rudr only owns the fields it sets. Fields other managers own, like the replicas an autoscaler
+/// sets, are left alone.
+pub(crate) fn apply<T: serde::Serialize>(
+    client: &kube::client::APIClient,
+    resource: &kube::api::RawApi,
+    name: &str,
+    object: &T,
+) -> InstigatorResult {
+    apply_with(client, resource, name, object, false)
+}
+
+/// force_apply is `apply` taking over the fields other managers changed, to undo drift.
+pub(crate) fn force_apply<T: serde::Serialize>(
+    client: &kube::client::APIClient,
+    resource: &kube::api::RawApi,
+    name: &str,
+    object: &T,
+) -> InstigatorResult {
+    apply_with(client, resource, name, object, true)
+}
+
+fn apply_with<T: serde::Serialize>(
+    client: &kube::client::APIClient,
+    resource: &kube::api::RawApi,
+    name: &str,
+    object: &T,
+    force: bool,
+) -> InstigatorResult {
+    let pp = kube::api::PatchParams {
+        force,
+        ..apply_params()
+    };
+    let object = mutator::mutate(serde_json::to_value(object)?)?;
+    let req = resource.patch(name, &pp, serde_json::to_vec(&object)?)?;
+    match client.timed_request::<serde_json::Value>(req) {
+        Ok(_) => Ok(()),
+        Err(e) => match e.api_error() {
+            Some(ref err) if err.code == 409 => Err(format_err!(
+                "conflict applying {} {}: {}",
+                resource.resource,
+                name,
+                err.message
+            )),
+            // The API server doesn't support server-side apply.
+            Some(ref err) if err.code == 415 => merge_apply(client, resource, name, &object),
+            _ => Err(e.into()),
+        },
+    }
+}
+
+/// The annotation with the object rudr applied last, where server-side apply is not available.
+pub const LAST_APPLIED_ANNOTATION: &str = "core.oam.dev/last-applied-configuration";
+
+/// merge_apply creates or updates an object with a three-way merge: the patch sets the fields of
+/// `object` and removes the fields rudr applied last time but no longer renders. Fields neither
+/// has, like a sidecar container injected by another controller, are left alone.
+fn merge_apply(
+    client: &kube::client::APIClient,
+    resource: &kube::api::RawApi,
+    name: &str,
+    object: &serde_json::Value,
+) -> InstigatorResult {
+    let desired = with_last_applied(object)?;
+    let live = match client.timed_request::<serde_json::Value>(resource.get(name)?) {
+        Ok(live) => live,
+        Err(e) if e.api_error().map(|err| err.code) == Some(404) => {
+            let pp = kube::api::PostParams::default();
+            let req = resource.create(&pp, serde_json::to_vec(&desired)?)?;
+            client.timed_request::<serde_json::Value>(req)?;
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let strategic = supports_strategic_merge(resource);
+    let patch = three_way_patch(last_applied(&live).as_ref(), &desired, strategic);
+    let pp = kube::api::PatchParams {
+        patch_strategy: if strategic {
+            kube::api::PatchStrategy::Strategic
+        } else {
+            kube::api::PatchStrategy::Merge
+        },
+        ..Default::default()
+    };
+    let req = resource.patch(name, &pp, serde_json::to_vec(&patch)?)?;
+    client.timed_request::<serde_json::Value>(req)?;
+    Ok(())
+}
+
+/// with_last_applied is `object` with itself recorded in the LAST_APPLIED_ANNOTATION.
+pub fn with_last_applied(object: &serde_json::Value) -> Result<serde_json::Value, Error> {
+    if !object.is_object() {
+        return Err(format_err!("can't apply {}, it is not an object", object));
+    }
+    let mut annotated = object.clone();
+    annotated["metadata"]["annotations"][LAST_APPLIED_ANNOTATION] =
+        serde_json::Value::String(serde_json::to_string(object)?);
+    Ok(annotated)
+}
+
+/// last_applied is the object recorded in the LAST_APPLIED_ANNOTATION of `live`, if any.
+pub fn last_applied(live: &serde_json::Value) -> Option<serde_json::Value> {
+    live["metadata"]["annotations"][LAST_APPLIED_ANNOTATION]
+        .as_str()
+        .and_then(|last| serde_json::from_str(last).ok())
+}
+
+/// Strategic merge patches are supported by the built-in resources only, whose groups have no dots
+/// or belong to k8s.io. The groups of custom resources are domains.
+fn supports_strategic_merge(resource: &kube::api::RawApi) -> bool {
+    !resource.group.contains('.') || resource.group.ends_with(".k8s.io")
+}
+
+/// three_way_patch is the patch from the `last` applied object to `desired`: it sets the fields
+/// of `desired` and sets the fields only `last` has to null, which removes them. A `strategic`
+/// patch merges the lists with a known merge key element by element and deletes the elements
+/// only `last` has, a JSON merge patch replaces lists.
+pub fn three_way_patch(
+    last: Option<&serde_json::Value>,
+    desired: &serde_json::Value,
+    strategic: bool,
+) -> serde_json::Value {
+    use serde_json::Value;
+    match (last, desired) {
+        (Some(Value::Object(last)), Value::Object(desired)) => {
+            let mut patch = serde_json::Map::new();
+            for (field, value) in desired.iter() {
+                let value = match (last.get(field), value) {
+                    (Some(Value::Array(last)), Value::Array(items)) if strategic => {
+                        merge_list(field, last, items)
+                    }
+                    (last, value) => three_way_patch(last, value, strategic),
+                };
+                patch.insert(field.clone(), value);
+            }
+            for field in last.keys().filter(|f| !desired.contains_key(*f)) {
+                patch.insert(field.clone(), Value::Null);
+            }
+            Value::Object(patch)
+        }
+        _ => desired.clone(),
+    }
+}
+
+/// merge_list is the strategic merge patch of the list `field`, elements with the same merge key
+/// are patched and elements only `last` has are deleted with a `$patch: delete` directive.
+fn merge_list(
+    field: &str,
+    last: &[serde_json::Value],
+    desired: &[serde_json::Value],
+) -> serde_json::Value {
+    use serde_json::Value;
+    let key = match merge_key(field, desired) {
+        Some(key) => key,
+        None => return Value::Array(desired.to_vec()),
+    };
+    let same = |a: &Value, b: &Value| a.get(key).is_some() && a.get(key) == b.get(key);
+    let mut items: Vec<Value> = desired
+        .iter()
+        .map(|item| three_way_patch(last.iter().find(|l| same(l, item)), item, true))
+        .collect();
+    for removed in last.iter().filter(|l| !desired.iter().any(|d| same(l, d))) {
+        if let Some(value) = removed.get(key) {
+            let mut directive = serde_json::Map::new();
+            directive.insert(key.to_string(), value.clone());
+            directive.insert("$patch".to_string(), Value::String("delete".to_string()));
+            items.push(Value::Object(directive));
+        }
+    }
+    Value::Array(items)
+}
+
+/// merge_key is the key the API server merges the elements of the list `field` of the pod and
+/// service specs by.
+fn merge_key(field: &str, items: &[serde_json::Value]) -> Option<&'static str> {
+    match field {
+        "containers" | "initContainers" | "env" | "volumes" | "imagePullSecrets" => Some("name"),
+        "volumeMounts" => Some("mountPath"),
+        // Container ports and service ports share the name.
+        "ports" if items.iter().all(|p| p.get("containerPort").is_some()) => Some("containerPort"),
+        "ports" => Some("port"),
+        _ => None,
+    }
+}
+
+/// up_to_date fetches the object `name` of `resource` and compares it with `desired`, so that
+/// a modify can skip objects that wouldn't change. If the object can't be read it is patched.
+pub(crate) fn up_to_date<T: serde::Serialize>(
+    client: &kube::client::APIClient,
+    resource: &kube::api::RawApi,
+    name: &str,
+    desired: &T,
+) -> bool {
+    let live = resource
+        .get(name)
+        .map_err(Error::from)
+        .and_then(|req| Ok(client.timed_request::<serde_json::Value>(req)?));
+    let desired = serde_json::to_value(desired)
+        .map_err(Error::from)
+        .and_then(mutator::mutate);
+    match (live, desired) {
+        (Ok(live), Ok(desired)) if is_subset(&desired, &live) => {
+            info!(
+                "{} {} is up to date, skip the update",
+                resource.resource, name
+            );
+            true
+        }
+        _ => false,
+    }
 }
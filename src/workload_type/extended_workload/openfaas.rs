@@ -1,10 +1,11 @@
 use crate::schematic::parameter::resolve_value;
 use crate::workload_type::{
-    InstigatorResult, StatusResult, ValidationResult, WorkloadMetadata, WorkloadType,
+    apply, up_to_date, InstigatorResult, RenderResult, StatusResult, ValidationResult,
+    WorkloadMetadata, WorkloadType,
 };
 use failure::{format_err, Error};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
-use kube::api::{Object, ObjectMeta, PatchParams, PostParams, RawApi, TypeMeta};
+use kube::api::{Object, ObjectMeta, RawApi, TypeMeta};
 use log::info;
 use std::collections::BTreeMap;
 
@@ -204,10 +205,13 @@ impl WorkloadType for OpenFaaS {
             .group("openfaas.com")
             .within(self.meta.namespace.as_str());
         let kubefaas = self.get_kube_faas()?;
-        let faas_req =
-            faas_resource.create(&PostParams::default(), serde_json::to_vec(&kubefaas)?)?;
-        let openfaas: KubeFaaS = self.meta.client.request(faas_req)?;
-        info!("openfass function {} was created", openfaas.metadata.name);
+        apply(
+            &self.meta.client,
+            &faas_resource,
+            self.meta.instance_name.as_str(),
+            &kubefaas,
+        )?;
+        info!("openfass function {} was created", self.meta.instance_name);
         Ok(())
     }
     fn modify(&self) -> InstigatorResult {
@@ -216,13 +220,21 @@ impl WorkloadType for OpenFaaS {
             .group("openfaas.com")
             .within(self.meta.namespace.as_str());
         let kubefaas = self.get_kube_faas()?;
-        let faas_req = faas_resource.patch(
-            self.meta.instance_name.clone().as_str(),
-            &PatchParams::default(),
-            serde_json::to_vec(&kubefaas)?,
+        if up_to_date(
+            &self.meta.client,
+            &faas_resource,
+            self.meta.instance_name.as_str(),
+            &kubefaas,
+        ) {
+            return Ok(());
+        }
+        apply(
+            &self.meta.client,
+            &faas_resource,
+            self.meta.instance_name.as_str(),
+            &kubefaas,
         )?;
-        let openfaas: KubeFaaS = self.meta.client.request(faas_req)?;
-        info!("openfass function {} was modified", openfaas.metadata.name);
+        info!("openfass function {} was modified", self.meta.instance_name);
         Ok(())
     }
     fn delete(&self) -> InstigatorResult {
@@ -234,6 +246,9 @@ impl WorkloadType for OpenFaaS {
     fn validate(&self) -> ValidationResult {
         Ok(())
     }
+    fn render(&self) -> RenderResult {
+        Ok(vec![serde_json::to_value(self.get_kube_faas()?)?])
+    }
 }
 
 #[cfg(test)]
@@ -294,6 +309,7 @@ mod test {
                 params,
                 owner_ref: None,
                 annotations: None,
+                propagated: Default::default(),
             },
         };
         let mut envs = BTreeMap::new();
@@ -1,9 +1,10 @@
 use crate::schematic::GroupVersionKind;
 use crate::workload_type::{
-    InstigatorResult, StatusResult, ValidationResult, WorkloadMetadata, WorkloadType,
+    apply, up_to_date, InstigatorResult, RenderResult, StatusResult, ValidationResult,
+    WorkloadMetadata, WorkloadType,
 };
 use failure::{format_err, Error};
-use kube::api::{PatchParams, PostParams, RawApi};
+use kube::api::RawApi;
 use serde_json::json;
 use std::collections::BTreeMap;
 
@@ -51,7 +52,7 @@ impl Others {
     }
 }
 
-fn form_plural(word: &str) -> String {
+pub(crate) fn form_plural(word: &str) -> String {
     if word.is_empty() {
         return word.to_string();
     }
@@ -80,9 +81,12 @@ impl WorkloadType for Others {
         .group(self.gvk.group.as_str())
         .within(self.meta.namespace.as_str());
         let object = self.get_object();
-        let crd_req = crd_resource.create(&PostParams::default(), serde_json::to_vec(&object)?)?;
-        let _: serde_json::Value = self.meta.client.request(crd_req)?;
-        Ok(())
+        apply(
+            &self.meta.client,
+            &crd_resource,
+            self.meta.instance_name.as_str(),
+            &object,
+        )
     }
     fn modify(&self) -> InstigatorResult {
         let crd_resource = RawApi::customResource(
@@ -92,13 +96,20 @@ impl WorkloadType for Others {
         .group(self.gvk.group.as_str())
         .within(self.meta.namespace.as_str());
         let object = self.get_object();
-        let crd_req = crd_resource.patch(
-            self.meta.instance_name.clone().as_str(),
-            &PatchParams::default(),
-            serde_json::to_vec(&object)?,
-        )?;
-        let _: serde_json::Value = self.meta.client.request(crd_req)?;
-        Ok(())
+        if up_to_date(
+            &self.meta.client,
+            &crd_resource,
+            self.meta.instance_name.as_str(),
+            &object,
+        ) {
+            return Ok(());
+        }
+        apply(
+            &self.meta.client,
+            &crd_resource,
+            self.meta.instance_name.as_str(),
+            &object,
+        )
     }
     fn delete(&self) -> InstigatorResult {
         Ok(())
@@ -107,6 +118,9 @@ impl WorkloadType for Others {
         // TODO: how to implement status while we don't know the spec?
         Ok(BTreeMap::new())
     }
+    fn render(&self) -> RenderResult {
+        Ok(vec![self.get_object()])
+    }
     fn validate(&self) -> ValidationResult {
         Ok(())
     }
@@ -152,6 +166,7 @@ mod test {
                 params: BTreeMap::new(),
                 owner_ref: None,
                 annotations: None,
+                propagated: Default::default(),
             },
             "extend.oam.dev/v1alpha1.Test",
         )
@@ -3,12 +3,12 @@ use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
 
 use crate::workload_type::workload_builder::{DeploymentBuilder, ServiceBuilder};
 use crate::workload_type::{
-    InstigatorResult, KubeName, StatusResult, WorkloadMetadata, WorkloadType,
+    InstigatorResult, KubeName, RenderResult, StatusResult, WorkloadMetadata, WorkloadType,
 };
 
 use crate::workload_type::statefulset_builder::StatefulsetBuilder;
+use log::warn;
 use std::collections::BTreeMap;
-use log::{warn};
 
 /// A Replicated Server can take one component and scale it up or down.
 pub struct ReplicatedServer {
@@ -19,20 +19,34 @@ impl ReplicatedServer {
     fn labels(&self) -> BTreeMap<String, String> {
         self.meta.labels("Service")
     }
-    fn add_deployment_builder(&self) -> InstigatorResult {
+    fn deployment_builder(&self) -> DeploymentBuilder {
         DeploymentBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
-            .do_request(self.meta.client.clone(), self.meta.namespace.clone(), "add")
     }
-    fn add_service_builder(&self) -> InstigatorResult {
+    fn service_builder(&self) -> ServiceBuilder {
         ServiceBuilder::new(self.kube_name(), self.meta.definition.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .select_labels(self.meta.select_labels())
             .owner_ref(self.meta.owner_ref.clone())
-            .do_request(self.meta.client.clone(), self.meta.namespace.clone(), "add")
+    }
+    fn add_deployment_builder(&self) -> InstigatorResult {
+        self.deployment_builder().do_request(
+            self.meta.client.clone(),
+            self.meta.namespace.clone(),
+            "add",
+        )
+    }
+    fn add_service_builder(&self) -> InstigatorResult {
+        self.service_builder().do_request(
+            self.meta.client.clone(),
+            self.meta.namespace.clone(),
+            "add",
+        )
     }
 }
 
@@ -78,6 +92,7 @@ impl WorkloadType for ReplicatedServer {
         DeploymentBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
             .do_request(
@@ -88,6 +103,7 @@ impl WorkloadType for ReplicatedServer {
 
         ServiceBuilder::new(self.kube_name(), self.meta.definition.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .select_labels(self.meta.select_labels())
             .owner_ref(self.meta.owner_ref.clone())
             .do_request(
@@ -115,8 +131,10 @@ impl WorkloadType for ReplicatedServer {
         let key = "deployment/".to_string() + self.kube_name().as_str();
         let state = self.meta.deployment_status().unwrap_or_else(|e| {
             if e.to_string().contains("NotFound") {
-                warn!("Deployment not found for instance_name:{} component_name:{}. Recreating it...", 
-                    self.meta.instance_name, self.meta.component_name);
+                warn!(
+                    "Deployment not found for instance_name:{} component_name:{}. Recreating it...",
+                    self.meta.instance_name, self.meta.component_name
+                );
                 self.add_deployment_builder().unwrap_or(());
             }
             e.to_string()
@@ -126,18 +144,26 @@ impl WorkloadType for ReplicatedServer {
         let svc_key = "service/".to_string() + self.kube_name().as_str();
         let svc_status = ServiceBuilder::new(self.kube_name(), self.meta.definition.clone())
             .get_status(self.meta.client.clone(), self.meta.namespace.clone());
-        let svc_state = svc_status.unwrap_or_else( |e| {
+        let svc_state = svc_status.unwrap_or_else(|e| {
             if e.to_string().contains("NotFound") {
-                warn!("Service not found for instance_name:{} component_name:{}. Recreating it.", 
-                    self.meta.instance_name, self.meta.component_name);
+                warn!(
+                    "Service not found for instance_name:{} component_name:{}. Recreating it.",
+                    self.meta.instance_name, self.meta.component_name
+                );
                 self.add_service_builder().unwrap_or(());
             }
             e.to_string()
-            });
+        });
         resources.insert(svc_key.clone(), svc_state);
 
         Ok(resources)
     }
+    fn render(&self) -> RenderResult {
+        let mut objects = self.meta.render_config_maps("Service")?;
+        objects.extend(self.deployment_builder().render()?);
+        objects.extend(self.service_builder().render()?);
+        Ok(objects)
+    }
 }
 
 /// Singleton represents the Singleton Workload Type, as defined in the OAM specification.
@@ -150,20 +176,34 @@ impl SingletonServer {
     fn labels(&self) -> BTreeMap<String, String> {
         self.meta.labels("SingletonServer")
     }
-    fn add_statefulset_deployment_builder(&self) -> InstigatorResult {
+    fn statefulset_builder(&self) -> StatefulsetBuilder {
         StatefulsetBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
-            .do_request(self.meta.client.clone(), self.meta.namespace.clone(), "add")
     }
-    fn add_service_builder(&self) -> InstigatorResult {
+    fn service_builder(&self) -> ServiceBuilder {
         ServiceBuilder::new(self.kube_name(), self.meta.definition.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .select_labels(self.meta.select_labels())
             .owner_ref(self.meta.owner_ref.clone())
-            .do_request(self.meta.client.clone(), self.meta.namespace.clone(), "add")
+    }
+    fn add_statefulset_deployment_builder(&self) -> InstigatorResult {
+        self.statefulset_builder().do_request(
+            self.meta.client.clone(),
+            self.meta.namespace.clone(),
+            "add",
+        )
+    }
+    fn add_service_builder(&self) -> InstigatorResult {
+        self.service_builder().do_request(
+            self.meta.client.clone(),
+            self.meta.namespace.clone(),
+            "add",
+        )
     }
 }
 
@@ -237,14 +277,20 @@ impl WorkloadType for SingletonServer {
 
         Ok(resources)
     }
+    fn render(&self) -> RenderResult {
+        let mut objects = self.meta.render_config_maps("singleton-service")?;
+        objects.extend(self.statefulset_builder().render()?);
+        objects.extend(self.service_builder().render()?);
+        Ok(objects)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use kube::{client::APIClient, config::Configuration};
 
-    use crate::schematic::component::Component;
-    use crate::workload_type::{server::*, KubeName, WorkloadMetadata};
+    use crate::schematic::component::{Component, Container, Port};
+    use crate::workload_type::{server::*, KubeName, WorkloadMetadata, WorkloadType};
 
     use std::collections::BTreeMap;
 
@@ -262,6 +308,7 @@ mod test {
                     ..Default::default()
                 },
                 annotations: None,
+                propagated: Default::default(),
                 params: BTreeMap::new(),
                 client: cli,
                 owner_ref: None,
@@ -289,6 +336,7 @@ mod test {
                     ..Default::default()
                 },
                 annotations: None,
+                propagated: Default::default(),
                 params: BTreeMap::new(),
                 client: cli,
                 owner_ref: None,
@@ -299,6 +347,41 @@ mod test {
         assert_eq!("Service", rs.labels().get("oam.dev/workload-type").unwrap());
     }
 
+    #[test]
+    fn test_replicated_service_render() {
+        let cli = APIClient::new(mock_kube_config());
+
+        let rs = ReplicatedServer {
+            meta: WorkloadMetadata {
+                name: "de".into(),
+                component_name: "hydrate".into(),
+                instance_name: "dehydrate".into(),
+                namespace: "tests".into(),
+                definition: Component {
+                    containers: vec![Container {
+                        name: "web".into(),
+                        image: "nginx".into(),
+                        ports: vec![Port::basic("http".into(), 80)],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                annotations: None,
+                propagated: Default::default(),
+                params: BTreeMap::new(),
+                client: cli,
+                owner_ref: None,
+            },
+        };
+
+        let objects = rs.render().unwrap();
+        assert_eq!(2, objects.len());
+        assert_eq!("Deployment", objects[0]["kind"]);
+        assert_eq!("dehydrate", objects[0]["metadata"]["name"]);
+        assert_eq!("Service", objects[1]["kind"]);
+        assert_eq!(80, objects[1]["spec"]["ports"][0]["port"]);
+    }
+
     /// This mock builds a KubeConfig that will not be able to make any requests.
     fn mock_kube_config() -> Configuration {
         Configuration {
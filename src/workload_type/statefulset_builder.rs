@@ -1,6 +1,7 @@
+use crate::metrics::TimedRequest;
 use crate::schematic::component::Component;
 use crate::workload_type::workload_builder;
-use crate::workload_type::{InstigatorResult, ParamMap};
+use crate::workload_type::{apply, up_to_date, InstigatorResult, ParamMap, RenderResult};
 use k8s_openapi::api::apps::v1 as apps;
 use k8s_openapi::api::core::v1 as api;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
@@ -16,6 +17,7 @@ pub(crate) struct StatefulsetBuilder {
     component: Component,
     labels: workload_builder::Labels,
     annotations: Option<workload_builder::Labels>,
+    propagated: workload_builder::Propagated,
     name: String,
     restart_policy: String,
     owner_ref: Option<Vec<meta::OwnerReference>>,
@@ -30,6 +32,7 @@ impl StatefulsetBuilder {
             name: instance_name,
             labels: workload_builder::Labels::new(),
             annotations: None,
+            propagated: workload_builder::Propagated::default(),
             restart_policy: "Always".to_string(),
             owner_ref: None,
             param_vals: BTreeMap::new(),
@@ -49,6 +52,12 @@ impl StatefulsetBuilder {
         self
     }
 
+    /// Add the labels and annotations propagated from the configuration.
+    pub fn propagated(mut self, propagated: workload_builder::Propagated) -> Self {
+        self.propagated = propagated;
+        self
+    }
+
     pub fn parameter_map(mut self, param_vals: ParamMap) -> Self {
         self.param_vals = param_vals;
         self
@@ -61,9 +70,9 @@ impl StatefulsetBuilder {
 
     pub fn to_statefulset(&self) -> apps::StatefulSet {
         apps::StatefulSet {
-            metadata: workload_builder::form_metadata(
+            metadata: self.propagated.metadata(
                 self.name.clone(),
-                self.labels.clone(),
+                &self.labels,
                 self.owner_ref.clone(),
             ),
             spec: Some(apps::StatefulSetSpec {
@@ -74,14 +83,16 @@ impl StatefulsetBuilder {
                 template: api::PodTemplateSpec {
                     metadata: Some(meta::ObjectMeta {
                         name: Some(self.name.clone()),
-                        labels: Some(self.labels.clone()),
-                        annotations: self.annotations.clone(),
+                        labels: Some(self.propagated.pod_labels(&self.labels)),
+                        annotations: self.propagated.pod_annotations(self.annotations.as_ref()),
                         owner_references: self.owner_ref.clone(),
                         ..Default::default()
                     }),
-                    spec: Some(self.component.to_pod_spec_with_policy(
-                        self.param_vals.clone(),
-                        self.restart_policy.clone(),
+                    spec: Some(self.propagated.pod_spec(
+                        self.component.to_pod_spec_with_policy(
+                            self.param_vals.clone(),
+                            self.restart_policy.clone(),
+                        ),
                     )),
                 },
                 ..Default::default()
@@ -91,14 +102,11 @@ impl StatefulsetBuilder {
     }
 
     pub fn status(self, client: APIClient, namespace: String) -> Result<String, kube::Error> {
-        let sts: Object<_, apps::StatefulSetStatus> =
-            match kube::api::Api::v1StatefulSet(client.clone())
-                .within(namespace.as_str())
-                .get_status(self.name.as_str())
-            {
-                Ok(sts) => sts,
-                Err(e) => return Err(e)
-            };
+        let req = kube::api::RawApi::v1Statefulset()
+            .within(namespace.as_str())
+            .get_status(self.name.as_str())?;
+        let sts: Object<apps::StatefulSetSpec, apps::StatefulSetStatus> =
+            client.timed_request(req)?;
         let status: apps::StatefulSetStatus = sts.status.unwrap();
         let replica = status.replicas;
         let available_replicas = status.ready_replicas.unwrap_or(0);
@@ -109,29 +117,31 @@ impl StatefulsetBuilder {
         Ok(state)
     }
 
+    pub fn render(&self) -> RenderResult {
+        Ok(vec![self.propagated.pod_patch.patch_object(&self.to_statefulset())?])
+    }
+
     pub fn do_request(self, client: APIClient, namespace: String, phase: &str) -> InstigatorResult {
-        let statefulset = self.to_statefulset();
+        let statefulset = self.propagated.pod_patch.patch_object(&self.to_statefulset())?;
         match phase {
             "modify" => {
-                let pp = kube::api::PatchParams::default();
-                kube::api::Api::v1StatefulSet(client)
-                    .within(namespace.as_str())
-                    .patch(self.name.as_str(), &pp, serde_json::to_vec(&statefulset)?)?;
-                Ok(())
+                let resource = kube::api::RawApi::v1Statefulset().within(namespace.as_str());
+                if up_to_date(&client, &resource, self.name.as_str(), &statefulset) {
+                    return Ok(());
+                }
+                apply(&client, &resource, self.name.as_str(), &statefulset)
             }
             "delete" => {
                 let pp = kube::api::DeleteParams::default();
-                kube::api::Api::v1StatefulSet(client)
+                let req = kube::api::RawApi::v1Statefulset()
                     .within(namespace.as_str())
                     .delete(self.name.as_str(), &pp)?;
+                client.timed_request::<serde_json::Value>(req)?;
                 Ok(())
             }
             _ => {
-                let pp = kube::api::PostParams::default();
-                kube::api::Api::v1StatefulSet(client)
-                    .within(namespace.as_str())
-                    .create(&pp, serde_json::to_vec(&statefulset)?)?;
-                Ok(())
+                let resource = kube::api::RawApi::v1Statefulset().within(namespace.as_str());
+                apply(&client, &resource, self.name.as_str(), &statefulset)
             }
         }
     }
@@ -1,6 +1,6 @@
 use crate::workload_type::{
     workload_builder::{JobBuilder, WorkloadMetadata},
-    InstigatorResult, KubeName, StatusResult, WorkloadType,
+    InstigatorResult, KubeName, RenderResult, StatusResult, WorkloadType,
 };
 
 use std::collections::BTreeMap;
@@ -29,6 +29,7 @@ impl WorkloadType for ReplicatedTask {
         JobBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .parallelism(self.replica_count.unwrap_or(1))
             .owner_ref(self.meta.owner_ref.clone())
@@ -39,6 +40,7 @@ impl WorkloadType for ReplicatedTask {
         JobBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .parallelism(self.replica_count.unwrap_or(1))
             .owner_ref(self.meta.owner_ref.clone())
@@ -65,6 +67,17 @@ impl WorkloadType for ReplicatedTask {
 
         Ok(resources)
     }
+    fn render(&self) -> RenderResult {
+        JobBuilder::new(self.kube_name(), self.meta.definition.clone())
+            .parameter_map(self.meta.params.clone())
+            .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
+            .annotations(self.meta.annotations.clone())
+            .parallelism(self.replica_count.unwrap_or(1))
+            .owner_ref(self.meta.owner_ref.clone())
+            .restart_policy("Never".to_string())
+            .render()
+    }
 }
 
 /// SingletonTask represents a non-daemon process.
@@ -88,6 +101,7 @@ impl WorkloadType for SingletonTask {
         JobBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
             .restart_policy("Never".to_string())
@@ -97,6 +111,7 @@ impl WorkloadType for SingletonTask {
         JobBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
             .restart_policy("Never".to_string())
@@ -122,6 +137,16 @@ impl WorkloadType for SingletonTask {
 
         Ok(resources)
     }
+    fn render(&self) -> RenderResult {
+        JobBuilder::new(self.kube_name(), self.meta.definition.clone())
+            .parameter_map(self.meta.params.clone())
+            .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
+            .annotations(self.meta.annotations.clone())
+            .owner_ref(self.meta.owner_ref.clone())
+            .restart_policy("Never".to_string())
+            .render()
+    }
 }
 
 #[cfg(test)]
@@ -147,6 +172,7 @@ mod test {
                     ..Default::default()
                 },
                 annotations: None,
+                propagated: Default::default(),
                 params: BTreeMap::new(),
                 client: cli,
                 owner_ref: None,
@@ -170,6 +196,7 @@ mod test {
                     ..Default::default()
                 },
                 annotations: None,
+                propagated: Default::default(),
                 params: BTreeMap::new(),
                 client: cli,
                 owner_ref: None,
@@ -188,5 +215,4 @@ mod test {
             client: reqwest::Client::new(),
         }
     }
-
 }
@@ -1,10 +1,10 @@
 use crate::workload_type::statefulset_builder::StatefulsetBuilder;
 use crate::workload_type::{
     workload_builder::DeploymentBuilder, workload_builder::WorkloadMetadata, InstigatorResult,
-    KubeName, StatusResult, ValidationResult, WorkloadType,
+    KubeName, RenderResult, StatusResult, ValidationResult, WorkloadType,
 };
+use log::warn;
 use std::collections::BTreeMap;
-use log::{warn};
 
 #[derive(Clone)]
 pub struct ReplicatedWorker {
@@ -16,13 +16,20 @@ impl ReplicatedWorker {
     fn labels(&self) -> BTreeMap<String, String> {
         self.meta.labels("Worker")
     }
-    fn add_deployment_builder(&self) -> InstigatorResult {
+    fn deployment_builder(&self) -> DeploymentBuilder {
         DeploymentBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
-            .do_request(self.meta.client.clone(), self.meta.namespace.clone(), "add")
+    }
+    fn add_deployment_builder(&self) -> InstigatorResult {
+        self.deployment_builder().do_request(
+            self.meta.client.clone(),
+            self.meta.namespace.clone(),
+            "add",
+        )
     }
 }
 
@@ -44,6 +51,7 @@ impl WorkloadType for ReplicatedWorker {
         DeploymentBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
             .do_request(
@@ -77,6 +85,11 @@ impl WorkloadType for ReplicatedWorker {
     fn validate(&self) -> ValidationResult {
         validate_worker(&self.meta)
     }
+    fn render(&self) -> RenderResult {
+        let mut objects = self.meta.render_config_maps("Worker")?;
+        objects.extend(self.deployment_builder().render()?);
+        Ok(objects)
+    }
 }
 
 fn validate_worker(meta: &WorkloadMetadata) -> ValidationResult {
@@ -106,13 +119,20 @@ impl SingletonWorker {
     fn labels(&self) -> BTreeMap<String, String> {
         self.meta.labels("SingletonWorker")
     }
-    fn add_statefulset_builder(&self) -> InstigatorResult {
+    fn statefulset_builder(&self) -> StatefulsetBuilder {
         StatefulsetBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
-            .do_request(self.meta.client.clone(), self.meta.namespace.clone(), "add")
+    }
+    fn add_statefulset_builder(&self) -> InstigatorResult {
+        self.statefulset_builder().do_request(
+            self.meta.client.clone(),
+            self.meta.namespace.clone(),
+            "add",
+        )
     }
 }
 
@@ -130,6 +150,7 @@ impl WorkloadType for SingletonWorker {
         StatefulsetBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
             .do_request(self.meta.client.clone(), self.meta.namespace.clone(), "add")?;
@@ -141,6 +162,7 @@ impl WorkloadType for SingletonWorker {
         StatefulsetBuilder::new(self.kube_name(), self.meta.definition.clone())
             .parameter_map(self.meta.params.clone())
             .labels(self.labels())
+            .propagated(self.meta.propagated.clone())
             .annotations(self.meta.annotations.clone())
             .owner_ref(self.meta.owner_ref.clone())
             .do_request(
@@ -175,6 +197,11 @@ impl WorkloadType for SingletonWorker {
     fn validate(&self) -> ValidationResult {
         validate_worker(&self.meta)
     }
+    fn render(&self) -> RenderResult {
+        let mut objects = self.meta.render_config_maps("SingletonWorker")?;
+        objects.extend(self.statefulset_builder().render()?);
+        Ok(objects)
+    }
 }
 
 #[cfg(test)]
@@ -199,6 +226,7 @@ mod test {
                         ..Default::default()
                     },
                     annotations: None,
+                    propagated: Default::default(),
                     params: BTreeMap::new(),
                     client: APIClient::new(mock_kube_config()),
                     owner_ref: None,
@@ -223,6 +251,7 @@ mod test {
                     namespace: "tests".into(),
                     definition: Default::default(),
                     annotations: None,
+                    propagated: Default::default(),
                     params: BTreeMap::new(),
                     client: APIClient::new(mock_kube_config()),
                     owner_ref: None,
@@ -259,6 +288,7 @@ mod test {
                 params: BTreeMap::new(),
                 client: cli.clone(),
                 annotations: None,
+                propagated: Default::default(),
                 owner_ref: None,
             },
         };
@@ -296,6 +326,7 @@ mod test {
                 params: BTreeMap::new(),
                 client: cli,
                 annotations: None,
+                propagated: Default::default(),
                 owner_ref: None,
             },
         };
@@ -324,6 +355,7 @@ mod test {
                 params: BTreeMap::new(),
                 client: cli.clone(),
                 annotations: None,
+                propagated: Default::default(),
                 owner_ref: None,
             },
         };
@@ -361,6 +393,7 @@ mod test {
                     ..Default::default()
                 },
                 annotations: Some(annotations),
+                propagated: Default::default(),
                 params: BTreeMap::new(),
                 client: cli,
                 owner_ref: None,
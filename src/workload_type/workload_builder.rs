@@ -2,13 +2,75 @@ use k8s_openapi::api::apps::v1 as apps;
 use k8s_openapi::api::batch::v1 as batchapi;
 use k8s_openapi::api::core::v1 as api;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
-use kube::api::{DeleteParams, Object, PatchParams, PostParams};
+use kube::api::{DeleteParams, Object};
 use kube::client::APIClient;
 use log::info;
 use std::collections::BTreeMap;
 
+use crate::metrics::TimedRequest;
 use crate::schematic::component::Component;
-use crate::workload_type::{server::to_config_maps, InstigatorResult, ParamMap};
+use crate::workload_type::{
+    apply, server::to_config_maps, up_to_date, InstigatorResult, ParamMap, RenderResult,
+};
+
+/// deployment_state is the state of a Deployment for the status of its component. It is only
+/// running once the deployment controller observed the latest spec, and all replicas of it are
+/// updated and available, so a Deployment that was just created isn't running yet.
+pub fn deployment_state(deploy: &Object<apps::DeploymentSpec, apps::DeploymentStatus>) -> String {
+    let status = match deploy.status.as_ref() {
+        Some(status) => status,
+        None => return "updating".to_string(),
+    };
+    let deadline_exceeded = status.conditions.iter().flatten().any(|c| {
+        c.type_ == "Progressing"
+            && c.status == "False"
+            && c.reason.as_deref() == Some("ProgressDeadlineExceeded")
+    });
+    if deadline_exceeded {
+        return "failed".to_string();
+    }
+    let observed = match (deploy.metadata.generation, status.observed_generation) {
+        (Some(generation), Some(observed)) => observed as f64 >= generation,
+        (Some(_), None) => false,
+        (None, _) => true,
+    };
+    let wanted = deploy.spec.replicas.unwrap_or(1);
+    if observed
+        && status.updated_replicas.unwrap_or(0) >= wanted
+        && status.available_replicas.unwrap_or(0) >= wanted
+    {
+        "running".to_string()
+    } else if status.unavailable_replicas.unwrap_or(0) > 0 {
+        "unavailable".to_string()
+    } else {
+        "updating".to_string()
+    }
+}
+
+/// job_state is the state of a Job for the status of its component. A Job that has neither
+/// started nor finished its pods yet is pending.
+pub fn job_state(status: &batchapi::JobStatus) -> String {
+    let condition = |type_: &str| {
+        status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|c| c.type_ == type_ && c.status == "True")
+    };
+    if condition("Complete") {
+        "succeeded".to_string()
+    } else if condition("Failed") {
+        "failed".to_string()
+    } else if status.active.unwrap_or(0) > 0 {
+        "running".to_string()
+    } else if status.failed.unwrap_or(0) > 0 {
+        "failed".to_string()
+    } else if status.succeeded.unwrap_or(0) > 0 {
+        "succeeded".to_string()
+    } else {
+        "pending".to_string()
+    }
+}
 
 /// WorkloadMetadata contains common data about a workload.
 ///
@@ -36,6 +98,8 @@ pub struct WorkloadMetadata {
     /// for cleaning it up.
     pub owner_ref: Option<Vec<meta::OwnerReference>>,
     pub annotations: Option<Labels>,
+    /// Propagated are the labels and annotations of the configuration to set on every object
+    pub propagated: Propagated,
 }
 
 impl WorkloadMetadata {
@@ -73,42 +137,37 @@ impl WorkloadMetadata {
             Some(self.labels(workload_type)),
         )
     }
+    pub fn render_config_maps(&self, workload_type: &str) -> RenderResult {
+        let mut objects = vec![];
+        for config in self.to_config_maps(workload_type) {
+            objects.push(serde_json::to_value(config)?);
+        }
+        Ok(objects)
+    }
     pub fn create_config_maps(&self, workload_type: &str) -> InstigatorResult {
         let config_maps = self.to_config_maps(workload_type);
         if !config_maps.is_empty() {
             log::debug!("start to create {} config_maps", config_maps.len());
         }
+        let resource = kube::api::RawApi::v1ConfigMap().within(self.namespace.as_str());
         for config in config_maps.iter() {
-            let (req, _) = api::ConfigMap::create_namespaced_config_map(
-                self.namespace.as_str(),
+            let name = config.metadata.as_ref().and_then(|m| m.name.clone());
+            apply(
+                &self.client,
+                &resource,
+                name.unwrap_or_default().as_str(),
                 config,
-                Default::default(),
             )?;
-            self.client.request::<api::ConfigMap>(req)?;
         }
         Ok(())
     }
 
     pub fn deployment_status(&self) -> Result<String, kube::Error> {
-        let deploy: Object<_, apps::DeploymentStatus> =
-            match kube::api::Api::v1Deployment(self.client.clone())
-                .within(self.namespace.as_str())
-                .get_status(self.kube_name().as_str())
-            {
-                Ok(deploy) => deploy,
-                Err(e) => return Err(e),
-            };
-        let status: apps::DeploymentStatus = deploy.status.unwrap();
-        let replica = status.replicas.unwrap_or(0);
-        let available_replicas = status.available_replicas.unwrap_or(0);
-        let unavailable_replicas = status.unavailable_replicas.unwrap_or(0);
-        let mut state = "updating".to_string();
-        if available_replicas == replica {
-            state = "running".to_string()
-        } else if unavailable_replicas > 0 {
-            state = "unavailable".to_string();
-        }
-        Ok(state)
+        let req = kube::api::RawApi::v1Deployment()
+            .within(self.namespace.as_str())
+            .get_status(self.kube_name().as_str())?;
+        let deploy = self.client.timed_request(req)?;
+        Ok(deployment_state(&deploy))
     }
 
     pub fn get_workload_setting(&self, key: &str) -> Option<serde_json::Value> {
@@ -135,6 +194,263 @@ pub fn form_metadata(
 
 pub type Labels = BTreeMap<String, String>;
 
+/// Propagated holds the labels and annotations of an ApplicationConfiguration that are set on
+/// the generated objects and their pods, next to the ones Rudr sets itself, and the
+/// ServiceAccount of the component instance the pods run as.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Propagated {
+    pub labels: Labels,
+    pub annotations: Labels,
+    pub service_account: Option<String>,
+    /// pod_patch is what the traits of the instance add to its pods.
+    pub pod_patch: PodPatch,
+}
+
+/// PodPatch is what traits add to the pods of a workload, like a sidecar container and the
+/// volumes it shares with the containers of the component.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PodPatch {
+    /// sidecars run next to the containers of the component.
+    pub sidecars: Vec<api::Container>,
+    /// init_containers run to completion, in order, before the containers of the component.
+    pub init_containers: Vec<api::Container>,
+    pub volumes: Vec<api::Volume>,
+    /// volume_mounts are added to every container of the component.
+    pub volume_mounts: Vec<api::VolumeMount>,
+    /// env is added to the environment of every container of the component.
+    pub env: Vec<api::EnvVar>,
+    /// image_pull_secrets are added to the ones the containers of the component name.
+    pub image_pull_secrets: Vec<api::LocalObjectReference>,
+    /// labels and annotations are added to the metadata of the pods, like the ones that enroll
+    /// them in a service mesh.
+    pub labels: Labels,
+    pub annotations: Labels,
+    /// node_selector is added to the node selector of the component, like its OS.
+    pub node_selector: Labels,
+    /// affinity replaces the affinity of the pods.
+    pub affinity: Option<api::Affinity>,
+    /// tolerations let the pods run on nodes with the taints they tolerate.
+    pub tolerations: Vec<api::Toleration>,
+    /// priority_class_name replaces the PriorityClass of the pods.
+    pub priority_class_name: Option<String>,
+    /// topology_spread_constraints spread the pods across the nodes of topologies like zones.
+    /// The API types predate them, so they are JSON set on the rendered pod template.
+    pub topology_spread_constraints: Vec<serde_json::Value>,
+    /// security_context is merged into the security context of the pods, and
+    /// container_security_context into the one of each of their containers. The API types
+    /// predate seccompProfile, so they are JSON set on the rendered pod template too.
+    pub security_context: Option<serde_json::Value>,
+    pub container_security_context: Option<serde_json::Value>,
+}
+
+impl PodPatch {
+    /// extend adds what `other` adds to the pods, after what this patch adds.
+    pub fn extend(&mut self, other: PodPatch) {
+        self.sidecars.extend(other.sidecars);
+        self.init_containers.extend(other.init_containers);
+        self.volumes.extend(other.volumes);
+        self.volume_mounts.extend(other.volume_mounts);
+        self.env.extend(other.env);
+        self.image_pull_secrets.extend(other.image_pull_secrets);
+        self.labels.extend(other.labels);
+        self.annotations.extend(other.annotations);
+        self.node_selector.extend(other.node_selector);
+        if other.affinity.is_some() {
+            self.affinity = other.affinity;
+        }
+        self.tolerations.extend(other.tolerations);
+        if other.priority_class_name.is_some() {
+            self.priority_class_name = other.priority_class_name;
+        }
+        self.topology_spread_constraints
+            .extend(other.topology_spread_constraints);
+        if other.security_context.is_some() {
+            self.security_context = other.security_context;
+        }
+        if other.container_security_context.is_some() {
+            self.container_security_context = other.container_security_context;
+        }
+    }
+
+    /// patch adds the containers, volumes, mounts, environment, pull secrets, placement and
+    /// priority to `spec`. The labels and annotations go on the metadata of the pods, see
+    /// `Propagated::pod_labels`, and `patch_object` adds the topology spread constraints and
+    /// security contexts.
+    pub fn patch(&self, mut spec: api::PodSpec) -> api::PodSpec {
+        if *self == PodPatch::default() {
+            return spec;
+        }
+        for container in spec.containers.iter_mut() {
+            if !self.volume_mounts.is_empty() {
+                container
+                    .volume_mounts
+                    .get_or_insert_with(Vec::new)
+                    .extend(self.volume_mounts.clone());
+            }
+            if !self.env.is_empty() {
+                container
+                    .env
+                    .get_or_insert_with(Vec::new)
+                    .extend(self.env.clone());
+            }
+        }
+        spec.containers.extend(self.sidecars.clone());
+        if !self.init_containers.is_empty() {
+            spec.init_containers
+                .get_or_insert_with(Vec::new)
+                .extend(self.init_containers.clone());
+        }
+        if !self.volumes.is_empty() {
+            spec.volumes
+                .get_or_insert_with(Vec::new)
+                .extend(self.volumes.clone());
+        }
+        if !self.image_pull_secrets.is_empty() {
+            let secrets = spec.image_pull_secrets.get_or_insert_with(Vec::new);
+            for secret in self.image_pull_secrets.iter() {
+                if !secrets.contains(secret) {
+                    secrets.push(secret.clone());
+                }
+            }
+        }
+        if !self.node_selector.is_empty() {
+            spec.node_selector
+                .get_or_insert_with(BTreeMap::new)
+                .extend(self.node_selector.clone());
+        }
+        if self.affinity.is_some() {
+            spec.affinity = self.affinity.clone();
+        }
+        if !self.tolerations.is_empty() {
+            spec.tolerations
+                .get_or_insert_with(Vec::new)
+                .extend(self.tolerations.clone());
+        }
+        if self.priority_class_name.is_some() {
+            spec.priority_class_name = self.priority_class_name.clone();
+        }
+        spec
+    }
+
+    /// patch_object renders `object`, a Deployment, Job or StatefulSet whose pod spec was
+    /// patched, with the topology spread constraints and security contexts in its pod template.
+    /// The container security context applies to the init containers and sidecars too.
+    pub fn patch_object<T: serde::Serialize>(
+        &self,
+        object: &T,
+    ) -> Result<serde_json::Value, serde_json::Error> {
+        let mut object = serde_json::to_value(object)?;
+        if let Some(spec) = object
+            .pointer_mut("/spec/template/spec")
+            .and_then(|s| s.as_object_mut())
+        {
+            if !self.topology_spread_constraints.is_empty() {
+                spec.insert(
+                    "topologySpreadConstraints".to_string(),
+                    serde_json::Value::Array(self.topology_spread_constraints.clone()),
+                );
+            }
+            if let Some(context) = self.security_context.as_ref() {
+                merge_object(spec, "securityContext", context);
+            }
+            if let Some(context) = self.container_security_context.as_ref() {
+                for key in &["containers", "initContainers"] {
+                    let containers = spec.get_mut(*key).and_then(|c| c.as_array_mut());
+                    for container in containers.into_iter().flatten() {
+                        if let Some(container) = container.as_object_mut() {
+                            merge_object(container, "securityContext", context);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(object)
+    }
+}
+
+/// merge_object sets the fields of `value` on the object `key` of `object`, keeping its others.
+fn merge_object(
+    object: &mut serde_json::Map<String, serde_json::Value>,
+    key: &str,
+    value: &serde_json::Value,
+) {
+    let target = object
+        .entry(key.to_string())
+        .or_insert_with(|| serde_json::json!({}));
+    if !target.is_object() {
+        *target = serde_json::json!({});
+    }
+    if let (Some(target), Some(fields)) = (target.as_object_mut(), value.as_object()) {
+        for (field, v) in fields {
+            target.insert(field.clone(), v.clone());
+        }
+    }
+}
+
+impl Propagated {
+    /// labels overlays `own` on the propagated labels, so that the labels Rudr selects objects
+    /// by can't be replaced.
+    pub fn labels(&self, own: &Labels) -> Labels {
+        let mut labels = self.labels.clone();
+        labels.extend(own.clone());
+        labels
+    }
+
+    /// annotations overlays `own` on the propagated annotations. It is None if both are empty.
+    pub fn annotations(&self, own: Option<&Labels>) -> Option<Labels> {
+        let mut annotations = self.annotations.clone();
+        annotations.extend(own.cloned().unwrap_or_default());
+        if annotations.is_empty() {
+            None
+        } else {
+            Some(annotations)
+        }
+    }
+
+    /// pod_labels are the labels of the pods: the propagated ones, the ones the traits add,
+    /// and `own`, which replace both.
+    pub fn pod_labels(&self, own: &Labels) -> Labels {
+        let mut labels = self.labels.clone();
+        labels.extend(self.pod_patch.labels.clone());
+        labels.extend(own.clone());
+        labels
+    }
+
+    /// pod_annotations are the annotations of the pods, with the ones the traits add.
+    pub fn pod_annotations(&self, own: Option<&Labels>) -> Option<Labels> {
+        let mut annotations = self.pod_patch.annotations.clone();
+        annotations.extend(own.cloned().unwrap_or_default());
+        self.annotations(Some(&annotations))
+    }
+
+    /// pod_spec runs the pods of `spec` as the ServiceAccount of the instance, if it has one,
+    /// with what its traits add to them.
+    pub fn pod_spec(&self, spec: api::PodSpec) -> api::PodSpec {
+        let spec = self.pod_patch.patch(spec);
+        match &self.service_account {
+            Some(name) => api::PodSpec {
+                service_account_name: Some(name.clone()),
+                ..spec
+            },
+            None => spec,
+        }
+    }
+
+    /// metadata is the metadata of a generated object with the propagated labels and
+    /// annotations.
+    pub fn metadata(
+        &self,
+        name: String,
+        labels: &Labels,
+        owner_references: Option<Vec<meta::OwnerReference>>,
+    ) -> Option<meta::ObjectMeta> {
+        form_metadata(name, self.labels(labels), owner_references).map(|m| meta::ObjectMeta {
+            annotations: self.annotations(None),
+            ..m
+        })
+    }
+}
+
 /// DeploymentBuilder builds new deployments specific to Rudr
 ///
 /// This hides many of the details of building a Deployment, exposing only
@@ -143,6 +459,7 @@ pub(crate) struct DeploymentBuilder {
     component: Component,
     labels: Labels,
     annotations: Option<Labels>,
+    propagated: Propagated,
     name: String,
     replicas: Option<i32>,
     restart_policy: String,
@@ -158,6 +475,7 @@ impl DeploymentBuilder {
             name: instance_name,
             labels: Labels::new(),
             annotations: None,
+            propagated: Propagated::default(),
             replicas: None,
             restart_policy: "Always".to_string(),
             owner_ref: None,
@@ -178,6 +496,14 @@ impl DeploymentBuilder {
         self
     }
 
+    /// Add the labels and annotations propagated from the configuration.
+    ///
+    /// They are set on the object and the pod specification, but never selected by.
+    pub fn propagated(mut self, propagated: Propagated) -> Self {
+        self.propagated = propagated;
+        self
+    }
+
     pub fn parameter_map(mut self, param_vals: ParamMap) -> Self {
         self.param_vals = param_vals;
         self
@@ -191,9 +517,9 @@ impl DeploymentBuilder {
     pub fn to_deployment(&self) -> apps::Deployment {
         apps::Deployment {
             // TODO: Could make this generic.
-            metadata: form_metadata(
+            metadata: self.propagated.metadata(
                 self.name.clone(),
-                self.labels.clone(),
+                &self.labels,
                 self.owner_ref.clone(),
             ),
             spec: Some(apps::DeploymentSpec {
@@ -205,15 +531,18 @@ impl DeploymentBuilder {
                 template: api::PodTemplateSpec {
                     metadata: Some(meta::ObjectMeta {
                         name: Some(self.name.clone()),
-                        labels: Some(self.labels.clone()),
-                        annotations: self.annotations.clone(),
+                        labels: Some(self.propagated.pod_labels(&self.labels)),
+                        annotations: self.propagated.pod_annotations(self.annotations.as_ref()),
                         owner_references: self.owner_ref.clone(),
                         ..Default::default()
                     }),
-                    spec: Some(self.component.to_pod_spec_with_policy(
-                        self.param_vals.clone(),
-                        self.restart_policy.clone(),
-                    )),
+                    spec: Some(
+                        self.propagated
+                            .pod_spec(self.component.to_pod_spec_with_policy(
+                                self.param_vals.clone(),
+                                self.restart_policy.clone(),
+                            )),
+                    ),
                 },
                 ..Default::default()
             }),
@@ -221,29 +550,37 @@ impl DeploymentBuilder {
         }
     }
 
+    pub fn render(&self) -> RenderResult {
+        Ok(vec![self
+            .propagated
+            .pod_patch
+            .patch_object(&self.to_deployment())?])
+    }
+
     pub fn do_request(self, client: APIClient, namespace: String, phase: &str) -> InstigatorResult {
-        let deployment = self.to_deployment();
+        let deployment = self
+            .propagated
+            .pod_patch
+            .patch_object(&self.to_deployment())?;
         match phase {
             "modify" => {
-                let pp = kube::api::PatchParams::default();
-                kube::api::Api::v1Deployment(client)
-                    .within(namespace.as_str())
-                    .patch(self.name.as_str(), &pp, serde_json::to_vec(&deployment)?)?;
-                Ok(())
+                let resource = kube::api::RawApi::v1Deployment().within(namespace.as_str());
+                if up_to_date(&client, &resource, self.name.as_str(), &deployment) {
+                    return Ok(());
+                }
+                apply(&client, &resource, self.name.as_str(), &deployment)
             }
             "delete" => {
                 let pp = kube::api::DeleteParams::default();
-                kube::api::Api::v1Deployment(client)
+                let req = kube::api::RawApi::v1Deployment()
                     .within(namespace.as_str())
                     .delete(self.name.as_str(), &pp)?;
+                client.timed_request::<serde_json::Value>(req)?;
                 Ok(())
             }
             _ => {
-                let pp = kube::api::PostParams::default();
-                kube::api::Api::v1Deployment(client)
-                    .within(namespace.as_str())
-                    .create(&pp, serde_json::to_vec(&deployment)?)?;
-                Ok(())
+                let resource = kube::api::RawApi::v1Deployment().within(namespace.as_str());
+                apply(&client, &resource, self.name.as_str(), &deployment)
             }
         }
     }
@@ -257,6 +594,7 @@ pub(crate) struct JobBuilder {
     component: Component,
     labels: Labels,
     annotations: Option<Labels>,
+    propagated: Propagated,
     name: String,
     restart_policy: String,
     owner_ref: Option<Vec<meta::OwnerReference>>,
@@ -272,6 +610,7 @@ impl JobBuilder {
             name: instance_name,
             labels: Labels::new(),
             annotations: None,
+            propagated: Propagated::default(),
             restart_policy: "Never".to_string(),
             owner_ref: None,
             parallelism: None,
@@ -292,6 +631,14 @@ impl JobBuilder {
         self
     }
 
+    /// Add the labels and annotations propagated from the configuration.
+    ///
+    /// They are set on the object and the pod specification, but never selected by.
+    pub fn propagated(mut self, propagated: Propagated) -> Self {
+        self.propagated = propagated;
+        self
+    }
+
     pub fn parameter_map(mut self, param_vals: ParamMap) -> Self {
         self.param_vals = param_vals;
         self
@@ -319,9 +666,9 @@ impl JobBuilder {
 
     fn to_job(&self) -> batchapi::Job {
         batchapi::Job {
-            metadata: form_metadata(
+            metadata: self.propagated.metadata(
                 self.name.clone(),
-                self.labels.clone(),
+                &self.labels,
                 self.owner_ref.clone(),
             ),
             spec: Some(batchapi::JobSpec {
@@ -330,15 +677,18 @@ impl JobBuilder {
                 template: api::PodTemplateSpec {
                     metadata: Some(meta::ObjectMeta {
                         name: Some(self.name.clone()),
-                        labels: Some(self.labels.clone()),
-                        annotations: self.annotations.clone(),
+                        labels: Some(self.propagated.pod_labels(&self.labels)),
+                        annotations: self.propagated.pod_annotations(self.annotations.as_ref()),
                         owner_references: self.owner_ref.clone(),
                         ..Default::default()
                     }),
-                    spec: Some(self.component.to_pod_spec_with_policy(
-                        self.param_vals.clone(),
-                        self.restart_policy.clone(),
-                    )),
+                    spec: Some(
+                        self.propagated
+                            .pod_spec(self.component.to_pod_spec_with_policy(
+                                self.param_vals.clone(),
+                                self.restart_policy.clone(),
+                            )),
+                    ),
                 },
                 ..Default::default()
             }),
@@ -347,63 +697,63 @@ impl JobBuilder {
     }
 
     pub fn get_status(self, client: APIClient, namespace: String) -> String {
-        let job: Object<_, batchapi::JobStatus> = match kube::api::Api::v1Job(client)
+        let req = match kube::api::RawApi::v1Job()
             .within(namespace.as_str())
             .get_status(self.name.as_str())
         {
+            Ok(req) => req,
+            Err(e) => return e.to_string(),
+        };
+        let job: Object<batchapi::JobSpec, batchapi::JobStatus> = match client.timed_request(req) {
             Ok(job) => job,
             Err(e) => return e.to_string(),
         };
-        let status: batchapi::JobStatus = job.status.unwrap();
+        job_state(&job.status.unwrap_or_default())
+    }
 
-        //just a simple way to give the job status
-        if let Some(sts) = status.active {
-            if sts > 0 {
-                return "running".to_string();
-            }
+    pub fn render(&self) -> RenderResult {
+        let mut objects = vec![];
+        for config in self.to_config_maps() {
+            objects.push(serde_json::to_value(config)?);
         }
-        if let Some(sts) = status.failed {
-            if sts > 0 {
-                return "failed".to_string();
-            }
-        }
-        "succeeded".to_string()
+        objects.push(self.propagated.pod_patch.patch_object(&self.to_job())?);
+        Ok(objects)
     }
 
     pub fn do_request(self, client: APIClient, namespace: String, phase: &str) -> InstigatorResult {
-        let job = self.to_job();
+        let job = self.propagated.pod_patch.patch_object(&self.to_job())?;
         match phase {
             "modify" => {
                 //TODO support modify config_map
-                let pp = kube::api::PatchParams::default();
-                kube::api::Api::v1Job(client)
-                    .within(namespace.as_str())
-                    .patch(self.name.as_str(), &pp, serde_json::to_vec(&job)?)?;
-                Ok(())
+                let resource = kube::api::RawApi::v1Job().within(namespace.as_str());
+                if up_to_date(&client, &resource, self.name.as_str(), &job) {
+                    return Ok(());
+                }
+                apply(&client, &resource, self.name.as_str(), &job)
             }
             "delete" => {
                 let pp = kube::api::DeleteParams::default();
-                kube::api::Api::v1Job(client)
+                let req = kube::api::RawApi::v1Job()
                     .within(namespace.as_str())
                     .delete(self.name.as_str(), &pp)?;
+                client.timed_request::<serde_json::Value>(req)?;
                 Ok(())
             }
             _ => {
                 //pre create config_map
                 let config_maps = self.to_config_maps();
+                let resource = kube::api::RawApi::v1ConfigMap().within(namespace.as_str());
                 for config in config_maps.iter() {
-                    let (req, _) = api::ConfigMap::create_namespaced_config_map(
-                        namespace.as_str(),
+                    let name = config.metadata.as_ref().and_then(|m| m.name.clone());
+                    apply(
+                        &client,
+                        &resource,
+                        name.unwrap_or_default().as_str(),
                         config,
-                        Default::default(),
                     )?;
-                    client.request::<api::ConfigMap>(req)?;
                 }
-                let pp = kube::api::PostParams::default();
-                kube::api::Api::v1Job(client)
-                    .within(namespace.as_str())
-                    .create(&pp, serde_json::to_vec(&job)?)?;
-                Ok(())
+                let resource = kube::api::RawApi::v1Job().within(namespace.as_str());
+                apply(&client, &resource, self.name.as_str(), &job)
             }
         }
     }
@@ -413,6 +763,7 @@ pub struct ServiceBuilder {
     component: Component,
     labels: Labels,
     selector: Labels,
+    propagated: Propagated,
     name: String,
     owner_ref: Option<Vec<meta::OwnerReference>>,
 }
@@ -424,6 +775,7 @@ impl ServiceBuilder {
             name: instance_name,
             labels: Labels::new(),
             selector: Labels::new(),
+            propagated: Propagated::default(),
             owner_ref: None,
         }
     }
@@ -435,6 +787,10 @@ impl ServiceBuilder {
         self.selector = labels;
         self
     }
+    pub fn propagated(mut self, propagated: Propagated) -> Self {
+        self.propagated = propagated;
+        self
+    }
     pub fn owner_ref(mut self, owner_ref: Option<Vec<meta::OwnerReference>>) -> Self {
         self.owner_ref = owner_ref;
         self
@@ -442,9 +798,9 @@ impl ServiceBuilder {
     fn to_service(&self) -> Option<api::Service> {
         self.component.clone().listening_port().and_then(|port| {
             Some(api::Service {
-                metadata: form_metadata(
+                metadata: self.propagated.metadata(
                     self.name.clone(),
-                    self.labels.clone(),
+                    &self.labels,
                     self.owner_ref.clone(),
                 ),
                 spec: Some(api::ServiceSpec {
@@ -457,10 +813,10 @@ impl ServiceBuilder {
         })
     }
     pub fn get_status(self, client: APIClient, namespace: String) -> Result<String, kube::Error> {
-        match kube::api::Api::v1Service(client)
+        let req = kube::api::RawApi::v1Service()
             .within(namespace.as_str())
-            .get_status(self.name.as_str())
-        {
+            .get_status(self.name.as_str())?;
+        match client.timed_request(req) {
             Ok(status) => {
                 let svc_status: Object<api::ServiceSpec, api::ServiceStatus> = status;
                 if let Some(_state) = svc_status.status {
@@ -468,7 +824,13 @@ impl ServiceBuilder {
                 }
                 Ok("not existed".to_string())
             }
-            Err(e) => Err(e),
+            Err(e) => Err(e.into()),
+        }
+    }
+    pub fn render(&self) -> RenderResult {
+        match self.to_service() {
+            Some(svc) => Ok(vec![serde_json::to_value(svc)?]),
+            None => Ok(vec![]),
         }
     }
     pub fn do_request(self, client: APIClient, namespace: String, phase: &str) -> InstigatorResult {
@@ -477,25 +839,23 @@ impl ServiceBuilder {
                 log::debug!("Service:\n{}", serde_json::to_string_pretty(&svc).unwrap());
                 match phase {
                     "modify" => {
-                        let pp = PatchParams::default();
-                        kube::api::Api::v1Service(client)
-                            .within(namespace.as_str())
-                            .patch(self.name.as_str(), &pp, serde_json::to_vec(&svc.spec)?)?;
-                        Ok(())
+                        let resource = kube::api::RawApi::v1Service().within(namespace.as_str());
+                        if up_to_date(&client, &resource, self.name.as_str(), &svc) {
+                            return Ok(());
+                        }
+                        apply(&client, &resource, self.name.as_str(), &svc)
                     }
                     "delete" => {
                         let pp = DeleteParams::default();
-                        kube::api::Api::v1Service(client)
+                        let req = kube::api::RawApi::v1Service()
                             .within(namespace.as_str())
                             .delete(self.name.as_str(), &pp)?;
+                        client.timed_request::<serde_json::Value>(req)?;
                         Ok(())
                     }
                     _ => {
-                        let pp = PostParams::default();
-                        kube::api::Api::v1Service(client)
-                            .within(namespace.as_str())
-                            .create(&pp, serde_json::to_vec(&svc)?)?;
-                        Ok(())
+                        let resource = kube::api::RawApi::v1Service().within(namespace.as_str());
+                        apply(&client, &resource, self.name.as_str(), &svc)
                     }
                 }
             }
@@ -524,6 +884,7 @@ mod test {
             namespace: "namespace".into(),
             client: APIClient::new(mock_kube_config()),
             annotations: None,
+            propagated: Propagated::default(),
             params: BTreeMap::new(),
             definition: skeleton_component(),
             owner_ref: skeleton_owner_ref(),
@@ -619,6 +980,316 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_propagated_deployment() {
+        let mut propagated = Propagated::default();
+        propagated
+            .labels
+            .insert("cost-center".to_string(), "retail".to_string());
+        propagated
+            .labels
+            .insert("first".to_string(), "replaced".to_string());
+        propagated
+            .annotations
+            .insert("team".to_string(), "checkout".to_string());
+        propagated.service_account = Some("test".to_string());
+        let deployment = DeploymentBuilder::new("test".into(), skeleton_component())
+            .labels(skeleton_labels())
+            .propagated(propagated)
+            .to_deployment();
+        let metadata = deployment.metadata.expect("metadata");
+        let labels = metadata.labels.expect("labels");
+        assert_eq!(Some(&"retail".to_string()), labels.get("cost-center"));
+        // Propagated labels never replace the ones Rudr sets.
+        assert_eq!(Some(&"one".to_string()), labels.get("first"));
+        assert_eq!(
+            Some(&"checkout".to_string()),
+            metadata.annotations.expect("annotations").get("team")
+        );
+
+        let spec = deployment.spec.expect("spec");
+        // The selector is immutable, it keeps selecting by Rudr's labels only.
+        assert_eq!(Some(skeleton_labels()), spec.selector.match_labels);
+        let pod = spec.template.metadata.expect("pod metadata");
+        assert_eq!(Some(labels), pod.labels);
+        assert_eq!(
+            Some(&"checkout".to_string()),
+            pod.annotations.expect("annotations").get("team")
+        );
+        assert_eq!(
+            Some("test".to_string()),
+            spec.template.spec.expect("pod spec").service_account_name
+        );
+    }
+
+    #[test]
+    fn test_pod_patch() {
+        let mut propagated = Propagated::default();
+        propagated.pod_patch.extend(PodPatch {
+            sidecars: vec![api::Container {
+                name: "forwarder".into(),
+                ..Default::default()
+            }],
+            volumes: vec![api::Volume {
+                name: "logs".into(),
+                ..Default::default()
+            }],
+            volume_mounts: vec![api::VolumeMount {
+                name: "logs".into(),
+                mount_path: "/var/log/app".into(),
+                ..Default::default()
+            }],
+            env: vec![api::EnvVar {
+                name: "LOG_DIR".into(),
+                value: Some("/var/log/app".into()),
+                ..Default::default()
+            }],
+            labels: [("sidecar.istio.io/inject".to_string(), "true".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            annotations: [("linkerd.io/inject".to_string(), "enabled".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            ..Default::default()
+        });
+        propagated.pod_patch.extend(PodPatch {
+            init_containers: vec![api::Container {
+                name: "migrate".into(),
+                ..Default::default()
+            }],
+            node_selector: [("pool".to_string(), "gpu".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            tolerations: vec![api::Toleration {
+                key: Some("pool".into()),
+                value: Some("gpu".into()),
+                effect: Some("NoSchedule".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let deployment = DeploymentBuilder::new("test".into(), skeleton_component())
+            .labels(skeleton_labels())
+            .propagated(propagated)
+            .to_deployment();
+        let spec = deployment.spec.expect("spec");
+        let metadata = spec.template.metadata.expect("pod metadata");
+        let labels = metadata.labels.expect("labels");
+        assert_eq!(
+            Some(&"true".to_string()),
+            labels.get("sidecar.istio.io/inject")
+        );
+        assert_eq!(Some(&"one".to_string()), labels.get("first"));
+        assert_eq!(
+            Some(&"enabled".to_string()),
+            metadata
+                .annotations
+                .as_ref()
+                .and_then(|a| a.get("linkerd.io/inject"))
+        );
+        // The selector only selects by the labels of Rudr.
+        assert_eq!(Some(skeleton_labels()), spec.selector.match_labels);
+        let pod = spec.template.spec.expect("pod spec");
+        assert_eq!(2, pod.containers.len());
+        let container = &pod.containers[0];
+        assert_eq!(
+            "logs",
+            container.volume_mounts.as_ref().expect("mounts")[0].name
+        );
+        assert_eq!("LOG_DIR", container.env.as_ref().expect("env")[0].name);
+        // Sidecars don't get the mounts of the component.
+        assert_eq!("forwarder", pod.containers[1].name);
+        assert!(pod.containers[1].volume_mounts.is_none());
+        assert_eq!("logs", pod.volumes.expect("volumes")[0].name);
+        assert_eq!(
+            "migrate",
+            pod.init_containers.expect("init containers")[0].name
+        );
+        assert_eq!(
+            Some(&"gpu".to_string()),
+            pod.node_selector.as_ref().and_then(|s| s.get("pool"))
+        );
+        assert_eq!(
+            Some("pool".to_string()),
+            pod.tolerations.as_ref().and_then(|t| t[0].key.clone())
+        );
+        // The node selector of the component stays.
+        assert_eq!(
+            Some(&"linux".to_string()),
+            pod.node_selector
+                .as_ref()
+                .and_then(|s| s.get("kubernetes.io/os"))
+        );
+
+        // Without a patch the pod spec is the one of the component.
+        let deployment = DeploymentBuilder::new("test".into(), skeleton_component())
+            .labels(skeleton_labels())
+            .to_deployment();
+        let pod = deployment
+            .spec
+            .expect("spec")
+            .template
+            .spec
+            .expect("pod spec");
+        assert_eq!(1, pod.containers.len());
+        assert!(pod.volumes.unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn test_pod_patch_security_context() {
+        let mut propagated = Propagated::default();
+        propagated.pod_patch.extend(PodPatch {
+            init_containers: vec![api::Container {
+                name: "migrate".into(),
+                ..Default::default()
+            }],
+            security_context: Some(serde_json::json!({"runAsNonRoot": true})),
+            container_security_context: Some(serde_json::json!({
+                "allowPrivilegeEscalation": false,
+                "capabilities": {"drop": ["ALL"]},
+            })),
+            ..Default::default()
+        });
+        let rendered = DeploymentBuilder::new("test".into(), skeleton_component())
+            .labels(skeleton_labels())
+            .propagated(propagated)
+            .render()
+            .expect("render");
+        let pod = &rendered[0]["spec"]["template"]["spec"];
+        assert_eq!(
+            serde_json::json!({"runAsNonRoot": true}),
+            pod["securityContext"]
+        );
+        for container in &[&pod["containers"][0], &pod["initContainers"][0]] {
+            assert_eq!(
+                false,
+                container["securityContext"]["allowPrivilegeEscalation"]
+            );
+            assert_eq!(
+                serde_json::json!(["ALL"]),
+                container["securityContext"]["capabilities"]["drop"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_pod_patch_topology_spread() {
+        let constraint = serde_json::json!({
+            "maxSkew": 1,
+            "topologyKey": "topology.kubernetes.io/zone",
+            "whenUnsatisfiable": "ScheduleAnyway",
+        });
+        let mut propagated = Propagated::default();
+        propagated.pod_patch.extend(PodPatch {
+            topology_spread_constraints: vec![constraint.clone()],
+            ..Default::default()
+        });
+        let rendered = DeploymentBuilder::new("test".into(), skeleton_component())
+            .labels(skeleton_labels())
+            .propagated(propagated)
+            .render()
+            .expect("render");
+        assert_eq!(
+            serde_json::json!([constraint]),
+            rendered[0]["spec"]["template"]["spec"]["topologySpreadConstraints"]
+        );
+        // The rest of the pod spec is still rendered.
+        assert_eq!(
+            "linux",
+            rendered[0]["spec"]["template"]["spec"]["nodeSelector"]["kubernetes.io/os"]
+        );
+
+        let rendered = DeploymentBuilder::new("test".into(), skeleton_component())
+            .labels(skeleton_labels())
+            .render()
+            .expect("render");
+        assert!(rendered[0]["spec"]["template"]["spec"]
+            .get("topologySpreadConstraints")
+            .is_none());
+    }
+
+    #[test]
+    fn test_deployment_state() {
+        let deployment = |generation: f64, status: serde_json::Value| {
+            let mut deploy: Object<apps::DeploymentSpec, apps::DeploymentStatus> =
+                serde_json::from_value(serde_json::json!({
+                    "metadata": {"name": "web", "generation": generation},
+                    "spec": {
+                        "replicas": 2,
+                        "selector": {},
+                        "template": {},
+                    },
+                }))
+                .expect("deployment");
+            deploy.status = serde_json::from_value(status).expect("status");
+            deployment_state(&deploy)
+        };
+        // A Deployment that was just created has no status yet, and no replicas to wait for.
+        assert_eq!("updating", deployment(1.0, serde_json::json!(null)));
+        assert_eq!("updating", deployment(1.0, serde_json::json!({})));
+        assert_eq!(
+            "running",
+            deployment(
+                1.0,
+                serde_json::json!({"observedGeneration": 1, "replicas": 2, "updatedReplicas": 2, "availableReplicas": 2})
+            )
+        );
+        // The replicas that are available belong to the previous spec.
+        assert_eq!(
+            "updating",
+            deployment(
+                2.0,
+                serde_json::json!({"observedGeneration": 1, "replicas": 2, "updatedReplicas": 2, "availableReplicas": 2})
+            )
+        );
+        assert_eq!(
+            "unavailable",
+            deployment(
+                1.0,
+                serde_json::json!({"observedGeneration": 1, "replicas": 2, "updatedReplicas": 2, "availableReplicas": 1, "unavailableReplicas": 1})
+            )
+        );
+        assert_eq!(
+            "failed",
+            deployment(
+                1.0,
+                serde_json::json!({"observedGeneration": 1, "conditions": [{
+                    "type": "Progressing",
+                    "status": "False",
+                    "reason": "ProgressDeadlineExceeded",
+                }]})
+            )
+        );
+    }
+
+    #[test]
+    fn test_job_state() {
+        let job = |status: serde_json::Value| {
+            job_state(&serde_json::from_value(status).expect("job status"))
+        };
+        assert_eq!("pending", job(serde_json::json!({})));
+        assert_eq!("running", job(serde_json::json!({"active": 1})));
+        // Failed pods are retried while the job is active.
+        assert_eq!(
+            "running",
+            job(serde_json::json!({"active": 1, "failed": 1}))
+        );
+        assert_eq!("failed", job(serde_json::json!({"failed": 1})));
+        assert_eq!(
+            "succeeded",
+            job(
+                serde_json::json!({"succeeded": 1, "conditions": [{"type": "Complete", "status": "True"}]})
+            )
+        );
+        assert_eq!(
+            "failed",
+            job(serde_json::json!({"conditions": [{"type": "Failed", "status": "True"}]}))
+        );
+    }
+
     #[test]
     fn test_job_builder() {
         let mut annotations = Labels::new();
@@ -746,6 +1417,7 @@ mod test {
                 resources: Default::default(),
             }],
             workload_settings: vec![],
+            outputs: vec![],
         };
         assert!(ServiceBuilder::new("test".into(), c)
             .labels(skeleton_labels())
@@ -789,6 +1461,7 @@ mod test {
                 resources: Default::default(),
             }],
             workload_settings: vec![],
+            outputs: vec![],
         }
     }
     fn skeleton_owner_ref() -> Option<Vec<OwnerReference>> {
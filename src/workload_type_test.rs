@@ -17,3 +17,102 @@ fn test_workload_type() {
     assert!(mwlt.modify().is_err());
     assert!(mwlt.delete().is_ok());
 }
+
+#[test]
+fn test_is_subset() {
+    let live = serde_json::json!({
+        "metadata": {"name": "web", "labels": {"app": "web"}, "resourceVersion": "12"},
+        "spec": {
+            "replicas": 2,
+            "template": {"spec": {"containers": [{"name": "web", "image": "nginx", "imagePullPolicy": "Always"}]}}
+        }
+    });
+    let desired = serde_json::json!({
+        "metadata": {"name": "web", "labels": {"app": "web"}},
+        "spec": {
+            "replicas": 2,
+            "template": {"spec": {"containers": [{"name": "web", "image": "nginx"}]}}
+        }
+    });
+    assert!(is_subset(&desired, &live));
+
+    let mut scaled = desired.clone();
+    scaled["spec"]["replicas"] = serde_json::json!(3);
+    assert!(!is_subset(&scaled, &live));
+
+    let mut sidecar = desired.clone();
+    sidecar["spec"]["template"]["spec"]["containers"]
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!({"name": "proxy", "image": "envoy"}));
+    assert!(!is_subset(&sidecar, &live));
+
+    let mut labeled = desired;
+    labeled["metadata"]["labels"]["tier"] = serde_json::json!("frontend");
+    assert!(!is_subset(&labeled, &live));
+}
+
+#[test]
+fn test_apply_params() {
+    let pp = apply_params();
+    assert_eq!(Some(FIELD_MANAGER.to_string()), pp.field_manager);
+    // Conflicts with other managers are reported, not overridden.
+    assert!(!pp.force);
+    match pp.patch_strategy {
+        kube::api::PatchStrategy::Apply => {}
+        _ => panic!("expected a server-side apply"),
+    }
+}
+
+#[test]
+fn test_last_applied() {
+    let object = serde_json::json!({"metadata": {"name": "web"}, "spec": {"replicas": 1}});
+    let annotated = with_last_applied(&object).expect("annotated");
+    assert_eq!("web", annotated["metadata"]["name"]);
+    assert_eq!(Some(object), last_applied(&annotated));
+    assert_eq!(None, last_applied(&serde_json::json!({"metadata": {}})));
+}
+
+#[test]
+fn test_three_way_patch() {
+    let last = serde_json::json!({
+        "metadata": {"labels": {"app": "web", "tier": "front"}},
+        "spec": {"template": {"spec": {"containers": [
+            {"name": "web", "image": "web:v1", "env": [{"name": "A", "value": "1"}, {"name": "B", "value": "2"}]},
+            {"name": "helper", "image": "helper:v1"}
+        ]}}}
+    });
+    let desired = serde_json::json!({
+        "metadata": {"labels": {"app": "web"}},
+        "spec": {"template": {"spec": {"containers": [
+            {"name": "web", "image": "web:v2", "env": [{"name": "A", "value": "1"}]}
+        ]}}}
+    });
+
+    // Removed fields are set to null, removed list elements are deleted by their merge key.
+    // Elements neither has, like an injected sidecar, are not mentioned and so kept.
+    assert_eq!(
+        serde_json::json!({
+            "metadata": {"labels": {"app": "web", "tier": null}},
+            "spec": {"template": {"spec": {"containers": [
+                {"name": "web", "image": "web:v2", "env": [
+                    {"name": "A", "value": "1"},
+                    {"name": "B", "$patch": "delete"}
+                ]},
+                {"name": "helper", "$patch": "delete"}
+            ]}}}
+        }),
+        three_way_patch(Some(&last), &desired, true)
+    );
+
+    // A JSON merge patch replaces lists.
+    let patch = three_way_patch(Some(&last), &desired, false);
+    assert_eq!(
+        desired["spec"]["template"]["spec"]["containers"],
+        patch["spec"]["template"]["spec"]["containers"]
+    );
+    assert_eq!(serde_json::Value::Null, patch["metadata"]["labels"]["tier"]);
+
+    // Without a last applied object the desired object is the patch.
+    assert_eq!(desired, three_way_patch(None, &desired, true));
+}